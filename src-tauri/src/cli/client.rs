@@ -0,0 +1,92 @@
+// 管理 API 客户端：当服务在运行时，CLI 通过 /api/* 操作，保证与 Web UI 状态一致
+use reqwest::{Client, Method, RequestBuilder};
+use serde_json::Value;
+use std::time::Duration;
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+pub struct AdminClient {
+    base_url: String,
+    api_key: Option<String>,
+    http: Client,
+}
+
+impl AdminClient {
+    pub fn new(base_url: &str, api_key: Option<String>) -> Result<Self, String> {
+        let http = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .no_proxy()
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {}", e))?;
+        Ok(Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key: api_key.filter(|k| !k.is_empty()),
+            http,
+        })
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// 探测管理 API 是否在线 (/api/health 免鉴权)
+    pub async fn is_up(&self) -> bool {
+        self.http
+            .get(format!("{}/api/health", self.base_url))
+            .timeout(PROBE_TIMEOUT)
+            .send()
+            .await
+            .map(|r| r.status().is_success())
+            .unwrap_or(false)
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let builder = self.http.request(method, format!("{}/api{}", self.base_url, path));
+        match &self.api_key {
+            Some(key) => builder.bearer_auth(key),
+            None => builder,
+        }
+    }
+
+    pub async fn get(&self, path: &str, query: &[(&str, String)]) -> Result<Value, String> {
+        Self::send(self.request(Method::GET, path).query(query)).await
+    }
+
+    pub async fn post(&self, path: &str, body: &Value) -> Result<Value, String> {
+        Self::send(self.request(Method::POST, path).json(body)).await
+    }
+
+    pub async fn delete(&self, path: &str) -> Result<Value, String> {
+        Self::send(self.request(Method::DELETE, path)).await
+    }
+
+    async fn send(builder: RequestBuilder) -> Result<Value, String> {
+        let resp = builder
+            .send()
+            .await
+            .map_err(|e| format!("Admin API request failed: {}", e))?;
+        let status = resp.status();
+        let text = resp
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read admin API response: {}", e))?;
+
+        if !status.is_success() {
+            // 管理接口错误统一为 {"error": "..."}
+            let message = serde_json::from_str::<Value>(&text)
+                .ok()
+                .and_then(|v| v.get("error").and_then(|e| e.as_str()).map(str::to_string))
+                .unwrap_or(text);
+            return Err(match status.as_u16() {
+                401 | 403 => format!("HTTP {}: unauthorized, check --api-key / ABV_WEB_PASSWORD", status),
+                _ => format!("HTTP {}: {}", status, message),
+            });
+        }
+
+        if text.trim().is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_str(&text).or(Ok(Value::String(text)))
+    }
+}
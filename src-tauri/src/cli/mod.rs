@@ -0,0 +1,827 @@
+// Headless 管理子命令
+// 用法: antigravity-tools <accounts|quota|tokens|config|logs|proxy> <action> [options]
+// 管理 API 在线时通过 /api/* 操作 (与 Web UI 共享内存状态)，否则直接读写数据目录。
+mod client;
+mod output;
+
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::models::{Account, AppConfig};
+use crate::modules::{self, account, config, proxy_db, user_token_db};
+use client::AdminClient;
+use output::{col, time_col, CellKind, Column, View};
+
+/// 管理 API 地址覆盖 (默认 http://127.0.0.1:{proxy.port})
+const ENV_API_URL: &str = "ABV_API_URL";
+
+const COMMANDS: &[&str] = &["accounts", "quota", "tokens", "config", "logs", "proxy"];
+/// 不带值的开关参数
+const SWITCHES: &[&str] = &["json", "offline", "follow", "errors", "help"];
+
+const LOGS_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+const USAGE: &str = "Usage: antigravity-tools <command> <action> [options]
+
+Commands:
+  accounts list
+  accounts add --refresh-token <token>
+  accounts import <file>              JSON export file or one refresh token per line
+  accounts export [id...] [--output <file>]
+  accounts disable <id> [--reason <text>]
+  accounts enable <id>
+  quota refresh [id]
+  tokens list
+  tokens create --username <name> [--expires day|week|month|never]
+                [--description <text>] [--max-ips <n>]
+                [--curfew-start HH:MM --curfew-end HH:MM]
+  tokens revoke <id>
+  config get [path]                   e.g. proxy.port
+  config set <path> <value>           value is parsed as JSON, falling back to a string
+  logs tail [-n <count>] [--filter <text>] [--errors] [-f|--follow]
+  proxy status
+
+Global options:
+  --json              print raw JSON instead of tables
+  --api-url <url>     admin API address (env ABV_API_URL, default http://127.0.0.1:<proxy.port>)
+  --api-key <key>     admin password or API key (default: admin_password / api_key from config)
+  --offline           skip the admin API and operate on the data directory directly";
+
+const ACCOUNT_COLUMNS: &[Column] = &[
+    col("ID", "id"),
+    col("EMAIL", "email"),
+    col("TIER", "quota.subscription_tier"),
+    col("CURRENT", "is_current"),
+    col("DISABLED", "disabled"),
+    col("PROXY_DISABLED", "proxy_disabled"),
+    col("REASON", "proxy_disabled_reason"),
+    time_col("LAST_USED", "last_used", CellKind::Secs),
+];
+
+const IMPORT_COLUMNS: &[Column] = &[
+    col("TOKEN", "token"),
+    col("EMAIL", "email"),
+    col("STATUS", "status"),
+    col("ERROR", "error"),
+];
+
+const QUOTA_COLUMNS: &[Column] = &[
+    col("MODEL", "name"),
+    col("REMAINING_%", "percentage"),
+    col("RESET_TIME", "reset_time"),
+];
+
+const TOKEN_COLUMNS: &[Column] = &[
+    col("ID", "id"),
+    col("USERNAME", "username"),
+    col("TOKEN", "token"),
+    col("ENABLED", "enabled"),
+    time_col("EXPIRES_AT", "expires_at", CellKind::Secs),
+    col("MAX_IPS", "max_ips"),
+    col("REQUESTS", "total_requests"),
+    time_col("LAST_USED", "last_used_at", CellKind::Secs),
+];
+
+const LOG_COLUMNS: &[Column] = &[
+    time_col("TIME", "timestamp", CellKind::Millis),
+    col("STATUS", "status"),
+    col("PROTOCOL", "protocol"),
+    col("MODEL", "model"),
+    col("MAPPED", "mapped_model"),
+    col("ACCOUNT", "account_email"),
+    col("MS", "duration"),
+    col("IN", "input_tokens"),
+    col("OUT", "output_tokens"),
+    col("URL", "url"),
+];
+
+/// 首个参数是否为管理子命令
+pub fn is_cli_invocation(args: &[String]) -> bool {
+    args.get(1).is_some_and(|a| COMMANDS.contains(&a.as_str()))
+}
+
+/// 执行子命令并返回进程退出码 (args 不含程序名)
+pub fn run(args: &[String]) -> i32 {
+    let parsed = match CliArgs::parse(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            return 2;
+        }
+    };
+    if parsed.switch("help") {
+        println!("{}", USAGE);
+        return 0;
+    }
+
+    let rt = match tokio::runtime::Runtime::new() {
+        Ok(rt) => rt,
+        Err(e) => {
+            eprintln!("Error: failed to create Tokio runtime: {}", e);
+            return 1;
+        }
+    };
+    match rt.block_on(dispatch(&parsed)) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    }
+}
+
+// ============================================================================
+// 参数解析
+// ============================================================================
+
+#[derive(Debug, Default)]
+struct CliArgs {
+    positionals: Vec<String>,
+    options: HashMap<String, String>,
+    switches: HashSet<String>,
+}
+
+impl CliArgs {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = match arg.strip_prefix("--").filter(|n| !n.is_empty()) {
+                Some(name) => name,
+                None => match arg.as_str() {
+                    "-n" => "limit",
+                    "-f" => "follow",
+                    "-h" => "help",
+                    _ => {
+                        parsed.positionals.push(arg.clone());
+                        continue;
+                    }
+                },
+            };
+
+            if let Some((key, value)) = name.split_once('=') {
+                parsed.options.insert(key.to_string(), value.to_string());
+            } else if SWITCHES.contains(&name) {
+                parsed.switches.insert(name.to_string());
+            } else {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("option --{} requires a value", name))?;
+                parsed.options.insert(name.to_string(), value.clone());
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn switch(&self, name: &str) -> bool {
+        self.switches.contains(name)
+    }
+
+    fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(String::as_str)
+    }
+
+    fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+
+    fn require(&self, index: usize, what: &str) -> Result<&str, String> {
+        self.positional(index)
+            .ok_or_else(|| format!("missing argument <{}>", what))
+    }
+
+    fn usize_option(&self, name: &str, default: usize) -> Result<usize, String> {
+        match self.option(name) {
+            Some(v) => v
+                .parse()
+                .map_err(|_| format!("option --{} expects a number, got '{}'", name, v)),
+            None => Ok(default),
+        }
+    }
+}
+
+// ============================================================================
+// 后端选择：在线 (管理 API) / 离线 (数据目录)
+// ============================================================================
+
+enum Backend {
+    Online(AdminClient),
+    Offline,
+}
+
+async fn select_backend(args: &CliArgs) -> Result<Backend, String> {
+    if args.switch("offline") {
+        return Ok(Backend::Offline);
+    }
+
+    // load_app_config 已合并 ABV_PORT / ABV_WEB_PASSWORD / ABV_API_KEY 等环境变量
+    let cfg = config::load_app_config().unwrap_or_else(|_| AppConfig::new());
+    let explicit_url = args
+        .option("api-url")
+        .map(str::to_string)
+        .or_else(|| std::env::var(ENV_API_URL).ok());
+    let base_url = explicit_url
+        .clone()
+        .unwrap_or_else(|| format!("http://127.0.0.1:{}", cfg.proxy.port));
+    let api_key = args
+        .option("api-key")
+        .map(str::to_string)
+        .or_else(|| cfg.proxy.admin_password.clone().filter(|p| !p.is_empty()))
+        .or(Some(cfg.proxy.api_key));
+
+    let client = AdminClient::new(&base_url, api_key)?;
+    if client.is_up().await {
+        return Ok(Backend::Online(client));
+    }
+    if explicit_url.is_some() {
+        return Err(format!("admin API at {} is not reachable", base_url));
+    }
+    Ok(Backend::Offline)
+}
+
+/// 离线模式需要的初始化 (与主程序启动时一致，但不挂载日志输出，避免污染 --json)
+fn init_offline() {
+    if let Err(e) = proxy_db::init_db() {
+        eprintln!("Warning: failed to initialize proxy log database: {}", e);
+    }
+    if let Err(e) = user_token_db::init_db() {
+        eprintln!("Warning: failed to initialize user token database: {}", e);
+    }
+    modules::vault::init();
+}
+
+async fn dispatch(args: &CliArgs) -> Result<(), String> {
+    let json = args.switch("json");
+    let backend = select_backend(args).await?;
+    if let Backend::Offline = backend {
+        init_offline();
+    }
+
+    let command = (args.positional(0).unwrap_or(""), args.positional(1).unwrap_or(""));
+    let (value, view) = match command {
+        ("accounts", "list") => (accounts_list(&backend).await?, View::Table(ACCOUNT_COLUMNS)),
+        ("accounts", "add") => {
+            let token = args
+                .option("refresh-token")
+                .or_else(|| args.positional(2))
+                .ok_or("missing --refresh-token")?;
+            (add_account(&backend, token).await?, View::Table(ACCOUNT_COLUMNS))
+        }
+        ("accounts", "import") => {
+            let path = args.require(2, "file")?;
+            (accounts_import(&backend, path).await?, View::Table(IMPORT_COLUMNS))
+        }
+        ("accounts", "export") => return accounts_export(&backend, args).await,
+        ("accounts", action @ ("disable" | "enable")) => {
+            let id = args.require(2, "id")?;
+            let enable = action == "enable";
+            (
+                toggle_proxy(&backend, id, enable, args.option("reason")).await?,
+                View::Record,
+            )
+        }
+        ("quota", "refresh") => match args.positional(2) {
+            Some(id) => (refresh_quota(&backend, id).await?, View::Table(QUOTA_COLUMNS)),
+            None => (refresh_all_quotas(&backend).await?, View::Record),
+        },
+        ("tokens", "list") => (tokens_list(&backend).await?, View::Table(TOKEN_COLUMNS)),
+        ("tokens", "create") => (tokens_create(&backend, args).await?, View::Record),
+        ("tokens", "revoke") => {
+            let id = args.require(2, "id")?;
+            (tokens_revoke(&backend, id).await?, View::Record)
+        }
+        ("config", "get") => (config_get(&backend, args.positional(2)).await?, View::Record),
+        ("config", "set") => {
+            let path = args.require(2, "path")?;
+            let raw = args.require(3, "value")?;
+            (config_set(&backend, path, raw).await?, View::Record)
+        }
+        ("logs", "tail") => return logs_tail(&backend, args, json).await,
+        ("proxy", "status") => (proxy_status(&backend).await?, View::Record),
+        _ => return Err(format!("unknown command '{} {}'\n\n{}", command.0, command.1, USAGE)),
+    };
+
+    output::print(&value, &view, json);
+    Ok(())
+}
+
+// ============================================================================
+// accounts
+// ============================================================================
+
+/// 离线账号行，字段与管理 API 的 AccountResponse 保持一致
+fn account_row(acc: &Account, current_id: Option<&str>) -> Value {
+    json!({
+        "id": acc.id,
+        "email": acc.email,
+        "name": acc.name(),
+        "is_current": current_id == Some(acc.id.as_str()),
+        "disabled": acc.disabled,
+        "disabled_reason": acc.disabled_reason,
+        "proxy_disabled": acc.proxy_disabled,
+        "proxy_disabled_reason": acc.proxy_disabled_reason,
+        "validation_blocked": acc.validation_blocked,
+        "quota": acc.quota,
+        "last_used": acc.last_used(),
+    })
+}
+
+async fn accounts_list(backend: &Backend) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => {
+            let resp = client.get("/accounts", &[]).await?;
+            Ok(resp.get("accounts").cloned().unwrap_or(Value::Array(vec![])))
+        }
+        Backend::Offline => {
+            let current_id = account::get_current_account_id()?;
+            let rows = account::list_accounts()?
+                .iter()
+                .map(|acc| account_row(acc, current_id.as_deref()))
+                .collect();
+            Ok(Value::Array(rows))
+        }
+    }
+}
+
+async fn add_account(backend: &Backend, refresh_token: &str) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => {
+            client
+                .post("/accounts", &json!({ "refreshToken": refresh_token }))
+                .await
+        }
+        Backend::Offline => {
+            let service = modules::account_service::AccountService::new(
+                modules::integration::SystemManager::Headless,
+            );
+            let acc = service.add_account(refresh_token).await?;
+            let current_id = account::get_current_account_id()?;
+            Ok(account_row(&acc, current_id.as_deref()))
+        }
+    }
+}
+
+/// 解析导入文件：兼容 accounts export 的 JSON ({"accounts": [...]} 或数组)，
+/// 以及每行一个 refresh_token 的纯文本 (# 开头为注释)
+fn parse_import_tokens(content: &str) -> Vec<String> {
+    let Ok(value) = serde_json::from_str::<Value>(content) else {
+        return content
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect();
+    };
+
+    let items = match &value {
+        Value::Array(items) => items.clone(),
+        Value::Object(_) => value
+            .get("accounts")
+            .and_then(|a| a.as_array())
+            .cloned()
+            .unwrap_or_else(|| vec![value.clone()]),
+        _ => vec![],
+    };
+    items
+        .iter()
+        .filter_map(|item| match item {
+            Value::String(s) => Some(s.trim().to_string()),
+            Value::Object(_) => item
+                .get("refresh_token")
+                .or_else(|| item.get("refreshToken"))
+                .and_then(|t| t.as_str())
+                .map(|t| t.trim().to_string()),
+            _ => None,
+        })
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+/// 仅展示 token 尾部，避免在终端输出完整凭据
+fn mask_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    let tail: String = chars[chars.len().saturating_sub(6)..].iter().collect();
+    format!("...{}", tail)
+}
+
+async fn accounts_import(backend: &Backend, path: &str) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("failed to read {}: {}", path, e))?;
+    let tokens = parse_import_tokens(&content);
+    if tokens.is_empty() {
+        return Err(format!("no refresh tokens found in {}", path));
+    }
+
+    let mut results = Vec::with_capacity(tokens.len());
+    for token in &tokens {
+        let row = match add_account(backend, token).await {
+            Ok(acc) => json!({
+                "token": mask_token(token),
+                "email": acc.get("email"),
+                "status": "imported",
+            }),
+            Err(e) => json!({
+                "token": mask_token(token),
+                "status": "failed",
+                "error": e,
+            }),
+        };
+        results.push(row);
+    }
+    Ok(Value::Array(results))
+}
+
+async fn accounts_export(backend: &Backend, args: &CliArgs) -> Result<(), String> {
+    let mut ids: Vec<String> = args.positionals.iter().skip(2).cloned().collect();
+    if ids.is_empty() {
+        ids = accounts_list(backend)
+            .await?
+            .as_array()
+            .map(|rows| {
+                rows.iter()
+                    .filter_map(|r| r.get("id").and_then(|v| v.as_str()).map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+    }
+
+    let exported = match backend {
+        Backend::Online(client) => {
+            client
+                .post("/accounts/export", &json!({ "accountIds": ids }))
+                .await?
+        }
+        Backend::Offline => serde_json::to_value(account::export_accounts_by_ids(&ids)?)
+            .map_err(|e| e.to_string())?,
+    };
+    let body = serde_json::to_string_pretty(&exported).map_err(|e| e.to_string())?;
+
+    match args.option("output") {
+        Some(path) => {
+            std::fs::write(path, body).map_err(|e| format!("failed to write {}: {}", path, e))?;
+            let count = exported
+                .get("accounts")
+                .and_then(|a| a.as_array())
+                .map_or(0, |a| a.len());
+            eprintln!("Exported {} account(s) to {}", count, path);
+        }
+        None => println!("{}", body),
+    }
+    Ok(())
+}
+
+async fn toggle_proxy(
+    backend: &Backend,
+    id: &str,
+    enable: bool,
+    reason: Option<&str>,
+) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => {
+            client
+                .post(
+                    &format!("/accounts/{}/toggle-proxy", id),
+                    &json!({ "enable": enable, "reason": reason }),
+                )
+                .await?;
+        }
+        Backend::Offline => account::toggle_proxy_status(id, enable, reason)?,
+    }
+    Ok(json!({
+        "id": id,
+        "proxy_disabled": !enable,
+        "reason": if enable { None } else { reason },
+    }))
+}
+
+// ============================================================================
+// quota
+// ============================================================================
+
+async fn refresh_quota(backend: &Backend, id: &str) -> Result<Value, String> {
+    let quota = match backend {
+        Backend::Online(client) => client.get(&format!("/accounts/{}/quota", id), &[]).await?,
+        Backend::Offline => {
+            let mut acc = account::load_account(id)?;
+            let quota = account::fetch_quota_with_retry(&mut acc)
+                .await
+                .map_err(|e| e.to_string())?;
+            account::update_account_quota(id, quota.clone())?;
+            serde_json::to_value(quota).map_err(|e| e.to_string())?
+        }
+    };
+    Ok(quota.get("models").cloned().unwrap_or(Value::Array(vec![])))
+}
+
+async fn refresh_all_quotas(backend: &Backend) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => client.post("/accounts/refresh", &json!({})).await,
+        Backend::Offline => {
+            let stats = account::refresh_all_quotas_logic().await?;
+            serde_json::to_value(stats).map_err(|e| e.to_string())
+        }
+    }
+}
+
+// ============================================================================
+// tokens
+// ============================================================================
+
+async fn tokens_list(backend: &Backend) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => client.get("/user-tokens", &[]).await,
+        Backend::Offline => {
+            serde_json::to_value(user_token_db::list_tokens()?).map_err(|e| e.to_string())
+        }
+    }
+}
+
+async fn tokens_create(backend: &Backend, args: &CliArgs) -> Result<Value, String> {
+    let request = crate::commands::user_token::CreateTokenRequest {
+        username: args
+            .option("username")
+            .ok_or("missing --username")?
+            .to_string(),
+        expires_type: args.option("expires").unwrap_or("never").to_string(),
+        description: args.option("description").map(str::to_string),
+        max_ips: args.usize_option("max-ips", 0)? as i32,
+        curfew_start: args.option("curfew-start").map(str::to_string),
+        curfew_end: args.option("curfew-end").map(str::to_string),
+    };
+
+    match backend {
+        Backend::Online(client) => {
+            let body = serde_json::to_value(&request).map_err(|e| e.to_string())?;
+            client.post("/user-tokens", &body).await
+        }
+        Backend::Offline => {
+            let token = user_token_db::create_token(
+                request.username,
+                request.expires_type,
+                request.description,
+                request.max_ips,
+                request.curfew_start,
+                request.curfew_end,
+            )?;
+            serde_json::to_value(token).map_err(|e| e.to_string())
+        }
+    }
+}
+
+async fn tokens_revoke(backend: &Backend, id: &str) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => {
+            client.delete(&format!("/user-tokens/{}", id)).await?;
+        }
+        Backend::Offline => user_token_db::delete_token(id)?,
+    }
+    Ok(json!({ "id": id, "revoked": true }))
+}
+
+// ============================================================================
+// config
+// ============================================================================
+
+async fn load_config_value(backend: &Backend) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => client.get("/config", &[]).await,
+        Backend::Offline => {
+            serde_json::to_value(config::load_app_config()?).map_err(|e| e.to_string())
+        }
+    }
+}
+
+/// 值按 JSON 解析 (数字/布尔/对象)，失败时作为字符串
+fn parse_config_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+/// 按点分路径写入；父节点必须已存在，仅允许在对象上新增末级字段
+fn set_path(root: &mut Value, path: &str, new_value: Value) -> Result<(), String> {
+    let (parent_path, key) = match path.rsplit_once('.') {
+        Some((parent, key)) => (Some(parent), key),
+        None => (None, path),
+    };
+    let parent = match parent_path {
+        Some(p) => p
+            .split('.')
+            .try_fold(&mut *root, |current, segment| current.get_mut(segment))
+            .ok_or_else(|| format!("config path '{}' does not exist", p))?,
+        None => root,
+    };
+    match parent {
+        Value::Object(map) => {
+            map.insert(key.to_string(), new_value);
+            Ok(())
+        }
+        _ => Err(format!("config path '{}' is not an object", parent_path.unwrap_or(""))),
+    }
+}
+
+async fn config_get(backend: &Backend, path: Option<&str>) -> Result<Value, String> {
+    let cfg = load_config_value(backend).await?;
+    match path {
+        Some(p) => output::lookup(&cfg, p)
+            .cloned()
+            .ok_or_else(|| format!("config path '{}' does not exist", p)),
+        None => Ok(cfg),
+    }
+}
+
+async fn config_set(backend: &Backend, path: &str, raw: &str) -> Result<Value, String> {
+    let mut cfg = load_config_value(backend).await?;
+    set_path(&mut cfg, path, parse_config_value(raw))?;
+
+    // 反序列化校验，类型不匹配时拒绝写入
+    let new_config: AppConfig = serde_json::from_value(cfg.clone())
+        .map_err(|e| format!("invalid value for '{}': {}", path, e))?;
+
+    match backend {
+        Backend::Online(client) => {
+            client.post("/config", &json!({ "config": cfg })).await?;
+        }
+        Backend::Offline => config::save_app_config(&new_config)?,
+    }
+
+    Ok(json!({
+        "path": path,
+        "value": output::lookup(&cfg, path).cloned().unwrap_or(Value::Null),
+    }))
+}
+
+// ============================================================================
+// logs / proxy
+// ============================================================================
+
+async fn fetch_logs(
+    backend: &Backend,
+    filter: &str,
+    errors_only: bool,
+    limit: usize,
+) -> Result<Vec<Value>, String> {
+    let logs = match backend {
+        Backend::Online(client) => {
+            let query = [
+                ("filter", filter.to_string()),
+                ("errors_only", errors_only.to_string()),
+                ("limit", limit.to_string()),
+                ("offset", "0".to_string()),
+            ];
+            client.get("/logs", &query).await?
+        }
+        Backend::Offline => {
+            serde_json::to_value(proxy_db::get_logs_filtered(filter, errors_only, limit, 0)?)
+                .map_err(|e| e.to_string())?
+        }
+    };
+    // 接口按时间倒序返回，tail 需要正序输出
+    let mut rows = logs.as_array().cloned().unwrap_or_default();
+    rows.reverse();
+    Ok(rows)
+}
+
+fn log_key(row: &Value) -> (i64, String) {
+    (
+        row.get("timestamp").and_then(|t| t.as_i64()).unwrap_or(0),
+        row.get("id").and_then(|v| v.as_str()).unwrap_or_default().to_string(),
+    )
+}
+
+fn print_logs(rows: &[Value], json: bool, with_header: bool) {
+    if json {
+        // --follow 下逐行输出 (NDJSON)，便于管道处理
+        for row in rows {
+            println!("{}", row);
+        }
+    } else if !rows.is_empty() {
+        println!("{}", output::render_table(rows, LOG_COLUMNS, with_header));
+    }
+}
+
+async fn logs_tail(backend: &Backend, args: &CliArgs, json: bool) -> Result<(), String> {
+    let filter = args.option("filter").unwrap_or("");
+    let errors_only = args.switch("errors");
+    let limit = args.usize_option("limit", 20)?;
+
+    let rows = fetch_logs(backend, filter, errors_only, limit).await?;
+    if !args.switch("follow") {
+        output::print(&Value::Array(rows), &View::Table(LOG_COLUMNS), json);
+        return Ok(());
+    }
+
+    print_logs(&rows, json, true);
+    let mut seen: HashSet<String> = rows.iter().map(|r| log_key(r).1).collect();
+    let mut last_ts = rows.iter().map(|r| log_key(r).0).max().unwrap_or(0);
+
+    loop {
+        tokio::time::sleep(LOGS_POLL_INTERVAL).await;
+        let batch = match fetch_logs(backend, filter, errors_only, limit.max(50)).await {
+            Ok(batch) => batch,
+            Err(e) => {
+                eprintln!("Warning: {}", e);
+                continue;
+            }
+        };
+
+        let fresh: Vec<Value> = batch
+            .iter()
+            .filter(|r| {
+                let (ts, id) = log_key(r);
+                ts >= last_ts && !seen.contains(&id)
+            })
+            .cloned()
+            .collect();
+        if fresh.is_empty() {
+            continue;
+        }
+
+        print_logs(&fresh, json, false);
+        last_ts = batch.iter().map(|r| log_key(r).0).max().unwrap_or(last_ts);
+        seen = batch.iter().map(|r| log_key(r).1).collect();
+    }
+}
+
+async fn proxy_status(backend: &Backend) -> Result<Value, String> {
+    match backend {
+        Backend::Online(client) => {
+            let mut status = client.get("/proxy/status", &[]).await?;
+            if let Value::Object(map) = &mut status {
+                map.insert("admin_api".to_string(), json!(client.base_url()));
+            }
+            Ok(status)
+        }
+        Backend::Offline => {
+            let cfg = config::load_app_config()?;
+            Ok(json!({
+                "running": false,
+                "port": cfg.proxy.port,
+                "base_url": format!("http://127.0.0.1:{}", cfg.proxy.port),
+                "active_accounts": account::list_accounts()?.len(),
+                "admin_api": "unreachable",
+            }))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_cli_invocation() {
+        assert!(is_cli_invocation(&args(&["bin", "accounts", "list"])));
+        assert!(!is_cli_invocation(&args(&["bin", "--headless"])));
+        assert!(!is_cli_invocation(&args(&["bin"])));
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let parsed = CliArgs::parse(&args(&[
+            "logs", "tail", "-n", "5", "--json", "--filter=claude", "-f", "--api-key", "secret",
+        ]))
+        .unwrap();
+        assert_eq!(parsed.positionals, vec!["logs", "tail"]);
+        assert_eq!(parsed.usize_option("limit", 20).unwrap(), 5);
+        assert_eq!(parsed.option("filter"), Some("claude"));
+        assert_eq!(parsed.option("api-key"), Some("secret"));
+        assert!(parsed.switch("json"));
+        assert!(parsed.switch("follow"));
+
+        assert!(CliArgs::parse(&args(&["tokens", "create", "--username"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_import_tokens() {
+        let exported = r#"{"accounts": [{"email": "a@x.com", "refresh_token": "1//a"}, {"refreshToken": "1//b"}]}"#;
+        assert_eq!(parse_import_tokens(exported), vec!["1//a", "1//b"]);
+
+        assert_eq!(parse_import_tokens(r#"["1//c", " "]"#), vec!["1//c"]);
+        assert_eq!(
+            parse_import_tokens("# backup\n1//d\n\n  1//e  \n"),
+            vec!["1//d", "1//e"]
+        );
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut cfg = json!({"proxy": {"port": 8045, "auth_mode": "off"}});
+        set_path(&mut cfg, "proxy.port", parse_config_value("9000")).unwrap();
+        set_path(&mut cfg, "proxy.auth_mode", parse_config_value("strict")).unwrap();
+        assert_eq!(cfg["proxy"]["port"], json!(9000));
+        assert_eq!(cfg["proxy"]["auth_mode"], json!("strict"));
+
+        assert!(set_path(&mut cfg, "missing.port", json!(1)).is_err());
+        assert!(set_path(&mut cfg, "proxy.port.value", json!(1)).is_err());
+    }
+
+    #[test]
+    fn test_mask_token() {
+        assert_eq!(mask_token("1//0abcdefghij"), "...efghij");
+        assert_eq!(mask_token("abc"), "...abc");
+    }
+}
@@ -0,0 +1,204 @@
+// CLI 输出渲染：人类可读表格 / 键值记录，或 --json 原样输出
+use serde_json::Value;
+
+/// 单元格格式
+#[derive(Clone, Copy)]
+pub enum CellKind {
+    Plain,
+    /// Unix 秒级时间戳
+    Secs,
+    /// Unix 毫秒级时间戳
+    Millis,
+}
+
+/// 表格列定义 (path 为点分路径，如 "quota.subscription_tier")
+pub struct Column {
+    pub header: &'static str,
+    pub path: &'static str,
+    pub kind: CellKind,
+}
+
+pub const fn col(header: &'static str, path: &'static str) -> Column {
+    Column { header, path, kind: CellKind::Plain }
+}
+
+pub const fn time_col(header: &'static str, path: &'static str, kind: CellKind) -> Column {
+    Column { header, path, kind }
+}
+
+/// 输出视图
+pub enum View {
+    Table(&'static [Column]),
+    Record,
+}
+
+/// 按点分路径读取 JSON 值
+pub fn lookup<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    if path.is_empty() {
+        return Some(value);
+    }
+    path.split('.').try_fold(value, |current, segment| match current {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+        _ => None,
+    })
+}
+
+fn format_time(ts: i64, kind: CellKind) -> String {
+    let dt = match kind {
+        CellKind::Secs => chrono::DateTime::from_timestamp(ts, 0),
+        CellKind::Millis => chrono::DateTime::from_timestamp_millis(ts),
+        CellKind::Plain => None,
+    };
+    dt.map(|d| {
+        d.with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string()
+    })
+    .unwrap_or_else(|| ts.to_string())
+}
+
+pub fn format_cell(value: Option<&Value>, kind: CellKind) -> String {
+    match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::Bool(b)) => (if *b { "yes" } else { "no" }).to_string(),
+        Some(Value::String(s)) if s.is_empty() => "-".to_string(),
+        Some(Value::String(s)) => s.replace('\n', " "),
+        Some(Value::Number(n)) => match (kind, n.as_i64()) {
+            (CellKind::Secs | CellKind::Millis, Some(ts)) if ts > 0 => format_time(ts, kind),
+            _ => n.to_string(),
+        },
+        Some(Value::Array(items)) if items.iter().all(|v| !v.is_object()) => items
+            .iter()
+            .map(|v| format_cell(Some(v), CellKind::Plain))
+            .collect::<Vec<_>>()
+            .join(","),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// 渲染表格 (列宽按字符数对齐；with_header=false 用于 logs --follow 的增量输出)
+pub fn render_table(rows: &[Value], columns: &[Column], with_header: bool) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|c| format_cell(lookup(row, c.path), c.kind))
+                .collect()
+        })
+        .collect();
+
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            cells
+                .iter()
+                .map(|r| r[i].chars().count())
+                .chain(std::iter::once(c.header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let line = |values: Vec<&str>| -> String {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{}{}", v, " ".repeat(w - v.chars().count())))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut out = Vec::with_capacity(cells.len() + 1);
+    if with_header {
+        out.push(line(columns.iter().map(|c| c.header).collect()));
+    }
+    out.extend(cells.iter().map(|r| line(r.iter().map(String::as_str).collect())));
+    out.join("\n")
+}
+
+/// 渲染键值记录 (嵌套对象展开为点分路径)
+pub fn render_record(value: &Value) -> String {
+    fn flatten(prefix: &str, value: &Value, out: &mut Vec<(String, String)>) {
+        match value {
+            Value::Object(map) if !map.is_empty() => {
+                for (k, v) in map {
+                    let key = if prefix.is_empty() { k.clone() } else { format!("{}.{}", prefix, k) };
+                    flatten(&key, v, out);
+                }
+            }
+            _ => out.push((prefix.to_string(), format_cell(Some(value), CellKind::Plain))),
+        }
+    }
+
+    let mut pairs = Vec::new();
+    flatten("", value, &mut pairs);
+    let width = pairs.iter().map(|(k, _)| k.chars().count()).max().unwrap_or(0);
+    pairs
+        .into_iter()
+        .map(|(k, v)| {
+            if k.is_empty() {
+                v
+            } else {
+                format!("{}{}  {}", k, " ".repeat(width - k.chars().count()), v)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 按视图输出结果
+pub fn print(value: &Value, view: &View, json: bool) {
+    if json {
+        println!("{}", serde_json::to_string_pretty(value).unwrap_or_default());
+        return;
+    }
+    match view {
+        View::Table(columns) => {
+            let rows = value.as_array().cloned().unwrap_or_else(|| vec![value.clone()]);
+            if rows.is_empty() {
+                println!("(empty)");
+            } else {
+                println!("{}", render_table(&rows, columns, true));
+            }
+        }
+        View::Record => println!("{}", render_record(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_lookup_nested_path() {
+        let v = json!({"quota": {"subscription_tier": "PRO"}, "models": [{"name": "a"}]});
+        assert_eq!(lookup(&v, "quota.subscription_tier"), Some(&json!("PRO")));
+        assert_eq!(lookup(&v, "models.0.name"), Some(&json!("a")));
+        assert_eq!(lookup(&v, "quota.missing"), None);
+    }
+
+    #[test]
+    fn test_render_table_aligns_columns() {
+        const COLUMNS: &[Column] = &[col("ID", "id"), col("ENABLED", "enabled")];
+        let rows = vec![json!({"id": "a", "enabled": true}), json!({"id": "long-id", "enabled": null})];
+        let table = render_table(&rows, COLUMNS, true);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "ID       ENABLED");
+        assert_eq!(lines[1], "a        yes");
+        assert_eq!(lines[2], "long-id  -");
+    }
+
+    #[test]
+    fn test_render_record_flattens_objects() {
+        let v = json!({"running": false, "proxy": {"port": 8045}});
+        let record = render_record(&v);
+        assert!(record.contains("proxy.port  8045"));
+        assert!(record.contains("running     no"));
+    }
+}
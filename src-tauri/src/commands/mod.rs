@@ -17,6 +17,8 @@ pub mod proxy_pool;
 pub mod user_token;
 // 导出 openai_accounts 命令
 pub mod openai_accounts;
+// 导出 vault 命令 (账号保险库)
+pub mod vault;
 
 // Re-export OpenAI account commands
 pub use openai_accounts::{add_openai_web_account, add_openai_api_account, validate_openai_session, start_openai_oauth_flow};
@@ -368,6 +370,16 @@ pub async fn save_config(
         instance.axum_server.update_user_agent(&config.proxy).await;
         // 更新 Thinking Budget 配置
         crate::proxy::update_thinking_budget_config(config.proxy.thinking_budget.clone());
+        // 更新自定义客户端适配器
+        crate::proxy::common::client_adapter::update_custom_client_adapters(&config.proxy.client_adapters);
+        // 更新工具结果压缩配置
+        crate::proxy::update_tool_result_compression_config(config.proxy.tool_result_compression.clone());
+        // 更新日志保留策略
+        crate::proxy::update_log_retention_config(config.proxy.log_retention.clone());
+        // 更新服务端工具配置
+        crate::proxy::update_server_tools_config(config.proxy.server_tools.clone());
+        // 更新 Message Batches 配置
+        crate::proxy::update_message_batches_config(config.proxy.message_batches.clone());
         // 更新代理池配置
         instance.axum_server.update_proxy_pool(config.proxy.proxy_pool.clone()).await;
         // 更新熔断配置
@@ -766,11 +778,7 @@ pub async fn toggle_proxy_status(
         return Err(format!("账号文件不存在: {}", account_id));
     }
 
-    let content = std::fs::read_to_string(&account_path)
-        .map_err(|e| format!("读取账号文件失败: {}", e))?;
-
-    let mut account_json: serde_json::Value = serde_json::from_str(&content)
-        .map_err(|e| format!("解析账号文件失败: {}", e))?;
+    let mut account_json = modules::vault::read_account_value(&account_path)?;
 
     // 2. 更新 proxy_disabled 字段
     if enable {
@@ -789,8 +797,7 @@ pub async fn toggle_proxy_status(
     }
 
     // 3. 保存到磁盘
-    std::fs::write(&account_path, serde_json::to_string_pretty(&account_json).unwrap())
-        .map_err(|e| format!("写入账号文件失败: {}", e))?;
+    modules::vault::write_account_value(&account_path, &account_json)?;
 
     modules::logger::log_info(&format!(
         "账号反代状态已更新: {} ({})",
@@ -918,3 +925,33 @@ pub async fn get_token_stats_account_trend_hourly(hours: i64) -> Result<Vec<crat
 pub async fn get_token_stats_account_trend_daily(days: i64) -> Result<Vec<crate::modules::token_stats::AccountTrendPoint>, String> {
     crate::modules::token_stats::get_account_trend_daily(days)
 }
+
+#[tauri::command]
+pub async fn get_token_cost_report(
+    hours: i64,
+    group_by: crate::modules::token_stats::CostGroupBy,
+) -> Result<crate::modules::token_stats::CostReport, String> {
+    crate::modules::token_stats::get_cost_report(hours, group_by)
+}
+
+#[tauri::command]
+pub async fn recompute_token_costs(hours: i64) -> Result<u64, String> {
+    crate::modules::token_stats::recompute_costs(hours)
+}
+
+#[tauri::command]
+pub async fn get_model_prices() -> Result<crate::modules::pricing::PriceTable, String> {
+    Ok(crate::modules::pricing::get_price_table())
+}
+
+#[tauri::command]
+pub async fn save_model_price(
+    price: crate::modules::pricing::ModelPrice,
+) -> Result<crate::modules::pricing::ModelPrice, String> {
+    crate::modules::pricing::save_price(price)
+}
+
+#[tauri::command]
+pub async fn delete_model_price(price_id: String) -> Result<(), String> {
+    crate::modules::pricing::delete_price(&price_id)
+}
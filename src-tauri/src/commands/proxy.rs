@@ -127,6 +127,7 @@ pub async fn internal_start_proxy_service(
     // 同步配置到运行中的 TokenManager
     token_manager.start_auto_cleanup().await;
     token_manager.update_sticky_config(config.scheduling.clone()).await;
+    token_manager.start_session_snapshot_task().await;
 
     // [NEW] 加载熔断配置 (从主配置加载)
     let app_config = crate::modules::config::load_app_config().unwrap_or_else(|_| crate::models::AppConfig::new());
@@ -214,6 +215,9 @@ pub async fn ensure_admin_server(
     let token_manager = Arc::new(TokenManager::new(app_data_dir));
     // [NEW] 加载账号数据，否则管理界面统计为 0
     let _ = token_manager.load_accounts().await;
+    // [NEW] 恢复上次运行的会话快照 (签名缓存 + 粘性会话绑定)
+    token_manager.update_sticky_config(config.scheduling.clone()).await;
+    token_manager.restore_session_snapshot().await;
 
     let (axum_server, server_handle) =
         match crate::proxy::AxumServer::start(
@@ -244,6 +248,16 @@ pub async fn ensure_admin_server(
 
     // [NEW] 初始化全局 Thinking Budget 配置
     crate::proxy::update_thinking_budget_config(config.thinking_budget.clone());
+    // 初始化自定义客户端适配器
+    crate::proxy::common::client_adapter::update_custom_client_adapters(&config.client_adapters);
+    // 初始化工具结果压缩配置
+    crate::proxy::update_tool_result_compression_config(config.tool_result_compression.clone());
+    // 初始化日志保留策略
+    crate::proxy::update_log_retention_config(config.log_retention.clone());
+    // 初始化服务端工具配置
+    crate::proxy::update_server_tools_config(config.server_tools.clone());
+    // 初始化 Message Batches 配置
+    crate::proxy::update_message_batches_config(config.message_batches.clone());
 
     Ok(())
 }
@@ -261,6 +275,7 @@ pub async fn stop_proxy_service(
 
     // 停止 Axum 服务器 (仅逻辑停止，不杀死进程)
     if let Some(instance) = instance_lock.take() {
+        instance.token_manager.save_session_snapshot().await;
         instance.token_manager.abort_background_tasks().await;
         instance.axum_server.set_running(false).await;
         // 已移除 instance.axum_server.stop() 调用，防止杀死 Admin Server
@@ -432,6 +447,59 @@ pub async fn export_proxy_logs_json(
     Ok(count)
 }
 
+/// 结构化 / 全文检索日志 (游标分页)
+#[tauri::command]
+pub async fn search_proxy_logs(
+    query: crate::modules::log_query::LogQuery,
+) -> Result<crate::modules::log_query::LogPage, String> {
+    crate::modules::proxy_db::search_logs(&query)
+}
+
+/// 按维度聚合日志
+#[tauri::command]
+pub async fn aggregate_proxy_logs(
+    query: crate::modules::log_query::LogQuery,
+    group_by: crate::modules::log_query::LogGroupBy,
+) -> Result<Vec<crate::modules::log_query::LogAggregate>, String> {
+    crate::modules::proxy_db::aggregate_logs(&query, group_by)
+}
+
+/// 延迟分位数报告 (TTFT / 总耗时 / 生成速度)
+#[tauri::command]
+pub async fn get_latency_report(
+    query: crate::modules::log_query::LogQuery,
+    group_by: crate::modules::log_query::LogGroupBy,
+) -> Result<Vec<crate::modules::log_query::LatencyStats>, String> {
+    crate::modules::proxy_db::latency_report(&query, group_by)
+}
+
+/// 最近一次日志保留策略执行报告
+#[tauri::command]
+pub async fn get_log_retention_report() -> Result<Option<crate::modules::log_retention::RetentionReport>, String> {
+    Ok(crate::modules::log_retention::last_report())
+}
+
+/// 立即执行日志保留策略
+#[tauri::command]
+pub async fn run_log_retention(
+    force_vacuum: bool,
+) -> Result<crate::modules::log_retention::RetentionReport, String> {
+    crate::modules::log_retention::run_from_app_config(force_vacuum).await
+}
+
+/// 按检索条件导出日志到文件 (JSONL / CSV)
+#[tauri::command]
+pub async fn export_proxy_logs_query(
+    file_path: String,
+    query: crate::modules::log_query::LogQuery,
+    format: crate::modules::log_query::LogExportFormat,
+) -> Result<usize, String> {
+    let file = std::fs::File::create(&file_path)
+        .map_err(|e| format!("Failed to create file: {}", e))?;
+    let mut writer = std::io::BufWriter::new(file);
+    crate::modules::proxy_db::export_logs(&query, format, &mut writer)
+}
+
 /// 获取带搜索条件的日志数量
 #[tauri::command]
 pub async fn get_proxy_logs_count_filtered(
@@ -1,8 +1,9 @@
 use tauri::State;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use crate::modules::user_token_db::{self, UserToken, TokenIpBinding};
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct CreateTokenRequest {
     pub username: String,
     pub expires_type: String,
@@ -12,7 +13,7 @@ pub struct CreateTokenRequest {
     pub curfew_end: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UpdateTokenRequest {
     pub username: Option<String>,
     pub description: Option<String>,
@@ -75,7 +76,7 @@ pub async fn get_token_ip_bindings(token_id: String) -> Result<Vec<TokenIpBindin
     user_token_db::get_token_ips(&token_id)
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct UserTokenStats {
     pub total_tokens: usize,
     pub active_tokens: usize,
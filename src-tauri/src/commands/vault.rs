@@ -0,0 +1,39 @@
+use tauri::State;
+
+use crate::commands::proxy::{reload_proxy_accounts, ProxyServiceState};
+use crate::modules::vault::{self, VaultStatus};
+
+/// 获取账号保险库状态
+#[tauri::command]
+pub async fn get_vault_status() -> Result<VaultStatus, String> {
+    Ok(vault::get_status())
+}
+
+/// 启用账号保险库 (passphrase 为空时使用 ABV_VAULT_KEY)
+#[tauri::command]
+pub async fn enable_vault(passphrase: Option<String>) -> Result<usize, String> {
+    vault::enable(passphrase.as_deref())
+}
+
+/// 解锁账号保险库，并重载反代账号池
+#[tauri::command]
+pub async fn unlock_vault(
+    proxy_state: State<'_, ProxyServiceState>,
+    passphrase: String,
+) -> Result<usize, String> {
+    let migrated = vault::unlock(&passphrase)?;
+    let _ = reload_proxy_accounts(proxy_state).await;
+    Ok(migrated)
+}
+
+/// 使用新口令 (或 ABV_VAULT_KEY) 重新加密所有账号
+#[tauri::command]
+pub async fn rekey_vault(new_passphrase: Option<String>) -> Result<usize, String> {
+    vault::rekey(new_passphrase.as_deref())
+}
+
+/// 关闭账号保险库 (凭据还原为明文)
+#[tauri::command]
+pub async fn disable_vault() -> Result<usize, String> {
+    vault::disable()
+}
@@ -4,6 +4,7 @@ mod commands;
 mod utils;
 mod proxy;  // Proxy service module
 mod auth;   // Authentication modules for different providers
+mod cli;    // Headless management subcommands
 pub mod error;
 pub mod constants;
 
@@ -50,6 +51,11 @@ pub fn run() {
     let args: Vec<String> = std::env::args().collect();
     let is_headless = args.iter().any(|arg| arg == "--headless");
 
+    // [NEW] 管理子命令 (accounts/quota/tokens/config/logs/proxy)：执行后直接退出，不启动 GUI/代理
+    if cli::is_cli_invocation(&args) {
+        std::process::exit(cli::run(&args[1..]));
+    }
+
     // Increase file descriptor limit (macOS only)
     #[cfg(target_os = "macos")]
     increase_nofile_limit();
@@ -72,6 +78,9 @@ pub fn run() {
         error!("Failed to initialize user token database: {}", e);
     }
 
+    // Unlock account vault from environment and migrate plaintext account files
+    modules::vault::init();
+
     if is_headless {
         info!("Starting in HEADLESS mode...");
 
@@ -143,6 +152,12 @@ pub fn run() {
             // Wait for Ctrl-C
             tokio::signal::ctrl_c().await.ok();
             info!("Headless mode shutting down");
+            if let Some(instance) = proxy_state.instance.read().await.as_ref() {
+                instance
+                    .token_manager
+                    .graceful_shutdown(std::time::Duration::from_secs(2))
+                    .await;
+            }
         });
         return;
     }
@@ -322,6 +337,12 @@ pub fn run() {
             commands::proxy::get_proxy_logs_count,
             commands::proxy::export_proxy_logs,
             commands::proxy::export_proxy_logs_json,
+            commands::proxy::export_proxy_logs_query,
+            commands::proxy::search_proxy_logs,
+            commands::proxy::aggregate_proxy_logs,
+            commands::proxy::get_latency_report,
+            commands::proxy::get_log_retention_report,
+            commands::proxy::run_log_retention,
             commands::proxy::get_proxy_logs_count_filtered,
             commands::proxy::get_proxy_logs_filtered,
             commands::proxy::set_proxy_monitor_enabled,
@@ -365,6 +386,11 @@ pub fn run() {
             commands::get_token_stats_model_trend_daily,
             commands::get_token_stats_account_trend_hourly,
             commands::get_token_stats_account_trend_daily,
+            commands::get_token_cost_report,
+            commands::recompute_token_costs,
+            commands::get_model_prices,
+            commands::save_model_price,
+            commands::delete_model_price,
             proxy::cli_sync::get_cli_sync_status,
             proxy::cli_sync::execute_cli_sync,
             proxy::cli_sync::execute_cli_restore,
@@ -411,6 +437,12 @@ pub fn run() {
             commands::openai_accounts::add_openai_api_account,
             commands::openai_accounts::validate_openai_session,
             commands::openai_accounts::start_openai_oauth_flow,
+            // Account vault commands
+            commands::vault::get_vault_status,
+            commands::vault::enable_vault,
+            commands::vault::unlock_vault,
+            commands::vault::rekey_vault,
+            commands::vault::disable_vault,
         ])
         .build(tauri::generate_context!())
         .expect("error while building tauri application")
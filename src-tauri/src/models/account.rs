@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use super::{token::TokenData, quota::QuotaData};
@@ -386,7 +387,7 @@ impl Default for AccountIndex {
 }
 
 /// 设备指纹（storage.json 中 telemetry 相关字段）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DeviceProfile {
     pub machine_id: String,
     pub mac_machine_id: String,
@@ -395,7 +396,7 @@ pub struct DeviceProfile {
 }
 
 /// 指纹历史版本
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DeviceProfileVersion {
     pub id: String,
     pub created_at: i64,
@@ -406,14 +407,14 @@ pub struct DeviceProfileVersion {
 }
 
 /// 导出账号项（用于备份/迁移）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AccountExportItem {
     pub email: String,
     pub refresh_token: String,
 }
 
 /// 导出账号响应
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AccountExportResponse {
     pub accounts: Vec<AccountExportItem>,
 }
@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use crate::proxy::ProxyConfig;
 
 /// Application configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppConfig {
     pub language: String,
     pub theme: String,
@@ -28,7 +29,7 @@ pub struct AppConfig {
 }
 
 /// Scheduled warmup configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ScheduledWarmupConfig {
     /// Whether smart warmup is enabled
     pub enabled: bool,
@@ -63,7 +64,7 @@ impl Default for ScheduledWarmupConfig {
 }
 
 /// Quota protection configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct QuotaProtectionConfig {
     /// Whether quota protection is enabled
     pub enabled: bool,
@@ -102,7 +103,7 @@ impl Default for QuotaProtectionConfig {
 }
 
 /// Pinned quota models configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PinnedQuotaModelsConfig {
     /// List of pinned models (displayed outside the account list)
     #[serde(default = "default_pinned_models")]
@@ -133,7 +134,7 @@ impl Default for PinnedQuotaModelsConfig {
 }
 
 /// Circuit breaker configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CircuitBreakerConfig {
     /// Whether circuit breaker is enabled
     pub enabled: bool,
@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// 模型配额信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ModelQuota {
     pub name: String,
     pub percentage: i32,  // 剩余百分比 0-100
@@ -9,7 +10,7 @@ pub struct ModelQuota {
 }
 
 /// 配额数据结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct QuotaData {
     pub models: Vec<ModelQuota>,
     pub last_updated: i64,
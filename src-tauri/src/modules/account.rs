@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::Serialize;
 use serde_json;
 use std::fs;
@@ -23,6 +24,15 @@ const ACCOUNTS_DIR: &str = "accounts";
 // ... existing functions get_data_dir, get_accounts_dir, load_account_index, save_account_index ...
 /// Get data directory path
 pub fn get_data_dir() -> Result<PathBuf, String> {
+    // 测试进程固定使用临时目录: 不读取 ABV_DATA_DIR，也不写入真实的 ~/.antigravity_tools，
+    // 无需在并行运行的测试中修改进程环境变量
+    if cfg!(test) {
+        let data_dir =
+            std::env::temp_dir().join(format!("antigravity-test-data-{}", std::process::id()));
+        fs::create_dir_all(&data_dir).map_err(|e| format!("failed_to_create_data_dir: {}", e))?;
+        return Ok(data_dir);
+    }
+
     // [NEW] 支持通过环境变量自定义数据目录
     if let Ok(env_path) = std::env::var("ABV_DATA_DIR") {
         if !env_path.trim().is_empty() {
@@ -116,7 +126,12 @@ pub fn load_account(account_id: &str) -> Result<Account, String> {
     let content = fs::read_to_string(&account_path)
         .map_err(|e| format!("failed_to_read_account_data: {}", e))?;
 
-    serde_json::from_str(&content).map_err(|e| format!("failed_to_parse_account_data: {}", e))
+    let mut value: serde_json::Value = serde_json::from_str(&content)
+        .map_err(|e| format!("failed_to_parse_account_data: {}", e))?;
+    // [NEW] 保险库模式下解密凭据字段
+    modules::vault::open_account_value(&mut value)?;
+
+    serde_json::from_value(value).map_err(|e| format!("failed_to_parse_account_data: {}", e))
 }
 
 /// Save account data
@@ -124,7 +139,12 @@ pub fn save_account(account: &Account) -> Result<(), String> {
     let accounts_dir = get_accounts_dir()?;
     let account_path = accounts_dir.join(format!("{}.json", account.id));
 
-    let content = serde_json::to_string_pretty(account)
+    let mut value = serde_json::to_value(account)
+        .map_err(|e| format!("failed_to_serialize_account_data: {}", e))?;
+    // [NEW] 保险库模式下加密凭据字段
+    modules::vault::seal_account_value(&mut value)?;
+
+    let content = serde_json::to_string_pretty(&value)
         .map_err(|e| format!("failed_to_serialize_account_data: {}", e))?;
 
     fs::write(&account_path, content).map_err(|e| format!("failed_to_save_account_data: {}", e))
@@ -472,7 +492,7 @@ pub async fn switch_account(
 }
 
 /// Get device profile info: current storage.json + account bound profile
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, JsonSchema)]
 pub struct DeviceProfiles {
     pub current_storage: Option<DeviceProfile>,
     pub bound_profile: Option<DeviceProfile>,
@@ -693,6 +713,11 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
                                 account.email, standard_id, model.name, model.percentage, threshold
                             ));
                             account.protected_models.insert(standard_id.clone());
+                            publish_account_event(
+                                &account,
+                                "protected",
+                                serde_json::json!({ "model": standard_id, "percentage": model.percentage }),
+                            );
                         }
                     } else {
                         // Auto-recover single model
@@ -702,6 +727,11 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
                                 account.email, standard_id, model.name, model.percentage
                             ));
                             account.protected_models.remove(&standard_id);
+                            publish_account_event(
+                                &account,
+                                "protection_recovered",
+                                serde_json::json!({ "model": standard_id, "percentage": model.percentage }),
+                            );
                         }
                     }
                 }
@@ -733,9 +763,33 @@ pub fn update_account_quota(account_id: &str, quota: QuotaData) -> Result<(), St
     // 这样内存中的 protected_models 会被同步更新
     crate::proxy::server::trigger_account_reload(account_id);
 
+    crate::modules::event_bus::publish(
+        crate::modules::event_bus::EventTopic::Quota,
+        "refreshed",
+        &serde_json::json!({
+            "account_id": account.id,
+            "email": account.email,
+            "quota": account.quota,
+            "protected_models": account.protected_models,
+        }),
+    );
+
     Ok(())
 }
 
+/// 推送账号状态变化事件 (/api/events)
+fn publish_account_event(account: &Account, kind: &str, detail: serde_json::Value) {
+    crate::modules::event_bus::publish(
+        crate::modules::event_bus::EventTopic::Account,
+        kind,
+        &serde_json::json!({
+            "account_id": account.id,
+            "email": account.email,
+            "detail": detail,
+        }),
+    );
+}
+
 /// Toggle proxy disabled status for an account
 pub fn toggle_proxy_status(
     account_id: &str,
@@ -826,6 +880,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                     account.disabled_reason = Some(format!("invalid_grant: {}", e));
                     let _ = save_account(account);
                     crate::proxy::server::trigger_account_reload(&account.id);
+                    publish_account_event(account, "disabled", serde_json::json!({ "reason": account.disabled_reason }));
                 }
                 return Err(AppError::OAuth(e));
             }
@@ -941,6 +996,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
                                 account.disabled_reason = Some(format!("invalid_grant: {}", e));
                                 let _ = save_account(account);
                                 crate::proxy::server::trigger_account_reload(&account.id);
+                                publish_account_event(account, "disabled", serde_json::json!({ "reason": account.disabled_reason }));
                             }
                             return Err(AppError::OAuth(e));
                         }
@@ -1021,7 +1077,7 @@ pub async fn fetch_quota_with_retry(account: &mut Account) -> crate::error::AppR
     result.map(|(q, _)| q)
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 pub struct RefreshStats {
     pub total: usize,
     pub success: usize,
@@ -4,12 +4,13 @@
 //! to resolve login failures, version validation errors, and OAuth issues.
 
 use crate::modules::logger;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
 /// Result of cache clearing operation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ClearResult {
     /// Paths that were successfully cleared
     pub cleared_paths: Vec<String>,
@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use std::process::Stdio;
@@ -18,7 +19,7 @@ const DETACHED_PROCESS: u32 = 0x00000008;
 const CREATE_NEW_PROCESS_GROUP: u32 = 0x00000200;
 
 /// Cloudflared隧道模式
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum TunnelMode {
     /// 快速隧道(临时URL)
@@ -34,7 +35,7 @@ impl Default for TunnelMode {
 }
 
 /// Cloudflared配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CloudflaredConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -63,7 +64,7 @@ impl Default for CloudflaredConfig {
 }
 
 /// Cloudflared状态
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CloudflaredStatus {
     pub installed: bool,
     pub version: Option<String>,
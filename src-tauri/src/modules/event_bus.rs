@@ -0,0 +1,164 @@
+//! Admin Event Bus - 将请求日志、账号状态变化、配额刷新、代理池健康与调试控制台日志
+//! 多路复用到同一个广播通道，供 /api/events (SSE) 推送给 Web UI / 无头模式订阅者。
+//! Tauri 前端仍通过 app_handle.emit 接收事件，两条通道互不影响。
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use tokio::sync::broadcast;
+
+/// 广播通道容量 (慢订阅者超出后会收到 lagged 通知)
+const CHANNEL_CAPACITY: usize = 1024;
+
+static EVENT_ID_COUNTER: AtomicU64 = AtomicU64::new(1);
+
+static EVENT_SENDER: OnceLock<broadcast::Sender<AdminEvent>> = OnceLock::new();
+
+fn sender() -> &'static broadcast::Sender<AdminEvent> {
+    EVENT_SENDER.get_or_init(|| broadcast::channel(CHANNEL_CAPACITY).0)
+}
+
+/// 事件主题 (订阅时按主题过滤)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopic {
+    /// 反代请求日志 (不含请求/响应体)
+    Request,
+    /// 账号状态变化: 限流 / 配额保护 / 禁用 / 验证阻断
+    Account,
+    /// 配额刷新结果
+    Quota,
+    /// 代理池健康状态变化
+    ProxyPool,
+    /// 调试控制台日志行 (仅在调试控制台开启时产生)
+    Debug,
+}
+
+impl EventTopic {
+    pub const ALL: [EventTopic; 5] = [
+        EventTopic::Request,
+        EventTopic::Account,
+        EventTopic::Quota,
+        EventTopic::ProxyPool,
+        EventTopic::Debug,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventTopic::Request => "request",
+            EventTopic::Account => "account",
+            EventTopic::Quota => "quota",
+            EventTopic::ProxyPool => "proxy_pool",
+            EventTopic::Debug => "debug",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+}
+
+/// 解析逗号分隔的主题列表，空值表示订阅全部主题
+pub fn parse_topics(raw: &str) -> Result<Vec<EventTopic>, String> {
+    let names: Vec<&str> = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect();
+    if names.is_empty() {
+        return Ok(EventTopic::ALL.to_vec());
+    }
+
+    let mut topics = Vec::with_capacity(names.len());
+    for name in names {
+        let topic = EventTopic::parse(name).ok_or_else(|| {
+            let valid: Vec<&str> = EventTopic::ALL.iter().map(EventTopic::as_str).collect();
+            format!(
+                "Unknown event topic '{}', expected one of: {}",
+                name,
+                valid.join(", ")
+            )
+        })?;
+        if !topics.contains(&topic) {
+            topics.push(topic);
+        }
+    }
+    Ok(topics)
+}
+
+/// 推送给订阅者的事件
+#[derive(Debug, Clone, Serialize)]
+pub struct AdminEvent {
+    /// 单调递增 ID (用作 SSE id)
+    pub id: u64,
+    /// 毫秒时间戳
+    pub timestamp: i64,
+    pub topic: EventTopic,
+    /// 主题内的事件类型 (如 rate_limited / disabled / refreshed)
+    pub kind: String,
+    pub data: Value,
+}
+
+/// 订阅事件流
+pub fn subscribe() -> broadcast::Receiver<AdminEvent> {
+    sender().subscribe()
+}
+
+/// 当前是否存在订阅者 (无订阅者时发布方可跳过构造事件)
+pub fn has_subscribers() -> bool {
+    EVENT_SENDER
+        .get()
+        .map(|s| s.receiver_count() > 0)
+        .unwrap_or(false)
+}
+
+/// 发布事件；无订阅者时直接丢弃，不做序列化
+pub fn publish<T: Serialize + ?Sized>(topic: EventTopic, kind: &str, data: &T) {
+    if !has_subscribers() {
+        return;
+    }
+    let event = AdminEvent {
+        id: EVENT_ID_COUNTER.fetch_add(1, Ordering::Relaxed),
+        timestamp: chrono::Utc::now().timestamp_millis(),
+        topic,
+        kind: kind.to_string(),
+        data: serde_json::to_value(data).unwrap_or(Value::Null),
+    };
+    // 注意: 此函数会在 tracing Layer 中被调用，不能在这里打日志
+    let _ = sender().send(event);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_topics() {
+        assert_eq!(parse_topics("").unwrap(), EventTopic::ALL.to_vec());
+        assert_eq!(
+            parse_topics("account, proxy_pool,account").unwrap(),
+            vec![EventTopic::Account, EventTopic::ProxyPool]
+        );
+        assert!(parse_topics("request,bogus").unwrap_err().contains("bogus"));
+    }
+
+    #[test]
+    fn test_publish_reaches_subscribers() {
+        let mut rx = subscribe();
+        publish(
+            EventTopic::Quota,
+            "event_bus_test",
+            &json!({ "account_id": "a1" }),
+        );
+
+        // 其他测试可能同时发布事件，只关注本测试的 kind
+        let event = std::iter::from_fn(|| rx.try_recv().ok())
+            .find(|e| e.kind == "event_bus_test")
+            .expect("event not received");
+        assert_eq!(event.topic, EventTopic::Quota);
+        assert_eq!(event.data["account_id"], "a1");
+        assert_eq!(serde_json::to_value(&event).unwrap()["topic"], "quota");
+    }
+}
@@ -8,6 +8,7 @@ use axum::{
     routing::{get, post},
     Json, Router,
 };
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
@@ -23,7 +24,7 @@ pub const DEFAULT_PORT: u16 = 19527;
 // ============================================================================
 
 /// HTTP API Settings
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct HttpApiSettings {
     /// Whether to enable HTTP API service
     #[serde(default = "default_enabled")]
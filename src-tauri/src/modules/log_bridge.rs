@@ -2,6 +2,7 @@
 //! Uses a global ring buffer that can be attached to Tauri after app initialization.
 
 use parking_lot::RwLock;
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::collections::VecDeque;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
@@ -32,7 +33,7 @@ fn get_log_buffer() -> &'static Arc<RwLock<VecDeque<LogEntry>>> {
 }
 
 /// Log entry sent to frontend
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct LogEntry {
     pub id: u64,
@@ -201,6 +202,9 @@ where
             buffer.push_back(entry.clone());
         }
 
+        // Push to /api/events subscribers
+        crate::modules::event_bus::publish(crate::modules::event_bus::EventTopic::Debug, "log", &entry);
+
         // Emit to frontend
         if let Some(handle) = APP_HANDLE.get() {
             let _ = handle.emit("log-event", entry);
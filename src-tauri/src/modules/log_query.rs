@@ -0,0 +1,477 @@
+// 请求日志检索条件：全文检索 (FTS5) + 结构化过滤 + 游标分页 + 聚合维度
+// 列表 / 计数 / 聚合 / 导出共用同一套 WHERE 生成逻辑，保证结果口径一致。
+// SQL 执行见 proxy_db::{search_logs, aggregate_logs, latency_report, export_logs}。
+
+use rusqlite::types::Value as SqlValue;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// 日志检索条件 (所有字段可选，组合为 AND)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LogQuery {
+    /// 全文检索 (错误信息 / 请求体 / 响应体)，空格分隔的词须全部命中，词尾 * 表示前缀匹配
+    pub text: Option<String>,
+    /// 模糊过滤 (兼容旧版搜索框：url / method / model / status / 账号 / IP)
+    pub filter: Option<String>,
+    pub errors_only: bool,
+    /// 起始时间 (毫秒时间戳，含)
+    pub since: Option<i64>,
+    /// 截止时间 (毫秒时间戳，不含)
+    pub until: Option<i64>,
+    /// 状态码或状态类，逗号分隔，如 "429,5xx"
+    pub status: Option<String>,
+    pub protocol: Option<String>,
+    /// 匹配请求模型或映射后模型
+    pub model: Option<String>,
+    pub mapped_model: Option<String>,
+    pub account_email: Option<String>,
+    pub username: Option<String>,
+    pub client_ip: Option<String>,
+    /// 代理池节点 ID
+    pub proxy_id: Option<String>,
+    /// 耗时下限 (ms)
+    pub min_duration: Option<u64>,
+    /// 耗时上限 (ms)
+    pub max_duration: Option<u64>,
+    pub min_input_tokens: Option<u32>,
+    pub min_output_tokens: Option<u32>,
+    pub min_total_tokens: Option<u32>,
+    /// 上一页返回的 next_cursor
+    pub cursor: Option<String>,
+    /// 每页条数 / 聚合分组数 (默认 50，最大 1000)
+    pub limit: Option<usize>,
+}
+
+/// 聚合维度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogGroupBy {
+    Model,
+    MappedModel,
+    Account,
+    Username,
+    ClientIp,
+    /// 代理池节点 (直连 / 全局上游代理为 null)
+    Proxy,
+    Protocol,
+    Status,
+    StatusClass,
+    /// 按小时 (UTC)
+    Hour,
+    /// 按天 (UTC)
+    Day,
+}
+
+impl LogGroupBy {
+    pub(crate) fn sql_expr(&self) -> &'static str {
+        match self {
+            LogGroupBy::Model => "model",
+            LogGroupBy::MappedModel => "mapped_model",
+            LogGroupBy::Account => "account_email",
+            LogGroupBy::Username => "username",
+            LogGroupBy::ClientIp => "client_ip",
+            LogGroupBy::Proxy => "proxy_id",
+            LogGroupBy::Protocol => "protocol",
+            LogGroupBy::Status => "CAST(status AS TEXT)",
+            LogGroupBy::StatusClass => "(status / 100) || 'xx'",
+            LogGroupBy::Hour => "strftime('%Y-%m-%d %H:00', timestamp / 1000, 'unixepoch')",
+            LogGroupBy::Day => "strftime('%Y-%m-%d', timestamp / 1000, 'unixepoch')",
+        }
+    }
+
+    /// 时间维度按时间升序，其他维度按请求数降序
+    pub(crate) fn is_time_bucket(&self) -> bool {
+        matches!(self, LogGroupBy::Hour | LogGroupBy::Day)
+    }
+}
+
+/// 导出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogExportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+impl LogExportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            LogExportFormat::Jsonl => "application/x-ndjson",
+            LogExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            LogExportFormat::Jsonl => "jsonl",
+            LogExportFormat::Csv => "csv",
+        }
+    }
+}
+
+/// 游标分页结果
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LogPage {
+    pub logs: Vec<crate::proxy::monitor::ProxyRequestLog>,
+    /// 满足条件的总数 (不受游标影响)
+    pub total: u64,
+    /// 下一页游标，None 表示已到末页
+    pub next_cursor: Option<String>,
+}
+
+/// 聚合结果行
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LogAggregate {
+    pub key: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    /// 缓存命中的输入 Token (包含在 input_tokens 中)
+    pub cached_tokens: u64,
+    pub thinking_tokens: u64,
+    pub tool_tokens: u64,
+    /// 平均耗时 (ms)
+    pub avg_duration: f64,
+    pub max_duration: u64,
+}
+
+/// 延迟分位数 (样本为空时各分位为 None)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, JsonSchema)]
+pub struct Percentiles {
+    pub samples: u64,
+    pub p50: Option<f64>,
+    pub p90: Option<f64>,
+    pub p99: Option<f64>,
+}
+
+impl Percentiles {
+    /// 最近秩法计算分位数 (忽略 NaN)
+    pub fn from_samples(mut values: Vec<f64>) -> Self {
+        values.retain(|v| !v.is_nan());
+        values.sort_by(|a, b| a.total_cmp(b));
+        let rank = |p: f64| {
+            let idx = ((p / 100.0) * values.len() as f64).ceil() as usize;
+            values.get(idx.saturating_sub(1)).copied()
+        };
+        Percentiles {
+            samples: values.len() as u64,
+            p50: rank(50.0),
+            p90: rank(90.0),
+            p99: rank(99.0),
+        }
+    }
+}
+
+/// 延迟报告行：仅成功请求 (2xx/3xx) 计入分位数，流式指标仅统计流式响应
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct LatencyStats {
+    pub key: Option<String>,
+    pub requests: u64,
+    pub errors: u64,
+    /// 总耗时 (ms，流式响应为整个流的墙钟时间)
+    pub duration_ms: Percentiles,
+    /// 首 Token 耗时 (ms)
+    pub ttft_ms: Percentiles,
+    /// 首 Token 至流结束的生成耗时 (ms)
+    pub stream_duration_ms: Percentiles,
+    /// 输出速度 (Token/s)
+    pub tokens_per_sec: Percentiles,
+}
+
+/// 生成的 WHERE 子句 (含 "WHERE " 前缀，无条件时为空串) 与按序绑定的参数
+pub struct WhereClause {
+    pub sql: String,
+    pub params: Vec<SqlValue>,
+}
+
+impl LogQuery {
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(DEFAULT_PAGE_SIZE)
+            .clamp(1, MAX_PAGE_SIZE)
+    }
+
+    /// 生成 WHERE 子句；include_cursor=false 用于计数 / 聚合 / 导出
+    pub fn build_where(&self, include_cursor: bool) -> Result<WhereClause, String> {
+        let mut conds: Vec<String> = Vec::new();
+        let mut params: Vec<SqlValue> = Vec::new();
+
+        if let Some(text) = non_empty(&self.text) {
+            if let Some(expr) = fts_match_expr(text) {
+                conds.push(
+                    "rowid IN (SELECT rowid FROM request_logs_fts WHERE request_logs_fts MATCH ?)"
+                        .to_string(),
+                );
+                params.push(SqlValue::Text(expr));
+            }
+        }
+
+        if let Some(filter) = non_empty(&self.filter) {
+            let pattern = format!("%{}%", filter);
+            let columns = [
+                "url",
+                "method",
+                "model",
+                "CAST(status AS TEXT)",
+                "account_email",
+                "client_ip",
+            ];
+            conds.push(format!(
+                "({})",
+                columns
+                    .iter()
+                    .map(|c| format!("{} LIKE ?", c))
+                    .collect::<Vec<_>>()
+                    .join(" OR ")
+            ));
+            params.extend(columns.iter().map(|_| SqlValue::Text(pattern.clone())));
+        }
+
+        if self.errors_only {
+            conds.push("(status < 200 OR status >= 400)".to_string());
+        }
+        if let Some(since) = self.since {
+            conds.push("timestamp >= ?".to_string());
+            params.push(SqlValue::Integer(since));
+        }
+        if let Some(until) = self.until {
+            conds.push("timestamp < ?".to_string());
+            params.push(SqlValue::Integer(until));
+        }
+
+        if let Some(status) = non_empty(&self.status) {
+            let mut alternatives = Vec::new();
+            for item in status.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                let lower = item.to_ascii_lowercase();
+                if let Some(class) = lower.strip_suffix("xx") {
+                    let class: i64 = class
+                        .parse()
+                        .ok()
+                        .filter(|c| (1..=5).contains(c))
+                        .ok_or_else(|| format!("Invalid status class: {}", item))?;
+                    alternatives.push("(status >= ? AND status < ?)".to_string());
+                    params.push(SqlValue::Integer(class * 100));
+                    params.push(SqlValue::Integer(class * 100 + 100));
+                } else {
+                    let code: i64 = item
+                        .parse()
+                        .map_err(|_| format!("Invalid status code: {}", item))?;
+                    alternatives.push("status = ?".to_string());
+                    params.push(SqlValue::Integer(code));
+                }
+            }
+            if !alternatives.is_empty() {
+                conds.push(format!("({})", alternatives.join(" OR ")));
+            }
+        }
+
+        if let Some(model) = non_empty(&self.model) {
+            conds.push("(model = ? OR mapped_model = ?)".to_string());
+            params.push(SqlValue::Text(model.to_string()));
+            params.push(SqlValue::Text(model.to_string()));
+        }
+        let exact = [
+            ("protocol", &self.protocol),
+            ("mapped_model", &self.mapped_model),
+            ("account_email", &self.account_email),
+            ("username", &self.username),
+            ("client_ip", &self.client_ip),
+            ("proxy_id", &self.proxy_id),
+        ];
+        for (column, value) in exact {
+            if let Some(value) = non_empty(value) {
+                conds.push(format!("{} = ? COLLATE NOCASE", column));
+                params.push(SqlValue::Text(value.to_string()));
+            }
+        }
+
+        let thresholds = [
+            ("duration >= ?", self.min_duration.map(|v| v as i64)),
+            ("duration <= ?", self.max_duration.map(|v| v as i64)),
+            (
+                "COALESCE(input_tokens, 0) >= ?",
+                self.min_input_tokens.map(i64::from),
+            ),
+            (
+                "COALESCE(output_tokens, 0) >= ?",
+                self.min_output_tokens.map(i64::from),
+            ),
+            (
+                "COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0) >= ?",
+                self.min_total_tokens.map(i64::from),
+            ),
+        ];
+        for (cond, value) in thresholds {
+            if let Some(value) = value {
+                conds.push(cond.to_string());
+                params.push(SqlValue::Integer(value));
+            }
+        }
+
+        if include_cursor {
+            if let Some(cursor) = non_empty(&self.cursor) {
+                let (ts, id) = decode_cursor(cursor)?;
+                conds.push("(timestamp < ? OR (timestamp = ? AND id < ?))".to_string());
+                params.push(SqlValue::Integer(ts));
+                params.push(SqlValue::Integer(ts));
+                params.push(SqlValue::Text(id));
+            }
+        }
+
+        let sql = if conds.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", conds.join(" AND "))
+        };
+        Ok(WhereClause { sql, params })
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// 将用户输入转为安全的 FTS5 表达式：每个词作为短语引用，保留词尾 * 前缀匹配
+pub fn fts_match_expr(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .filter_map(|word| {
+            let (body, prefix) = match word.strip_suffix('*') {
+                Some(body) => (body, true),
+                None => (word, false),
+            };
+            if body.is_empty() {
+                return None;
+            }
+            let quoted = format!("\"{}\"", body.replace('"', "\"\""));
+            Some(if prefix {
+                format!("{}*", quoted)
+            } else {
+                quoted
+            })
+        })
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+/// 游标格式: "<timestamp>:<id>" (按 timestamp DESC, id DESC 排序)
+pub fn encode_cursor(timestamp: i64, id: &str) -> String {
+    format!("{}:{}", timestamp, id)
+}
+
+fn decode_cursor(cursor: &str) -> Result<(i64, String), String> {
+    cursor
+        .split_once(':')
+        .and_then(|(ts, id)| ts.parse::<i64>().ok().map(|ts| (ts, id.to_string())))
+        .ok_or_else(|| format!("Invalid cursor: {}", cursor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fts_match_expr_quotes_terms() {
+        assert_eq!(
+            fts_match_expr("quota  exhausted").unwrap(),
+            "\"quota\" \"exhausted\""
+        );
+        assert_eq!(
+            fts_match_expr("RESOURCE_EXH*").unwrap(),
+            "\"RESOURCE_EXH\"*"
+        );
+        assert_eq!(
+            fts_match_expr("say \"hi\"").unwrap(),
+            "\"say\" \"\"\"hi\"\"\""
+        );
+        assert!(fts_match_expr("  * ").is_none());
+    }
+
+    #[test]
+    fn test_build_where_combines_filters() {
+        let q = LogQuery {
+            status: Some("429, 5xx".to_string()),
+            username: Some("alice".to_string()),
+            min_total_tokens: Some(1000),
+            since: Some(1_700_000_000_000),
+            ..Default::default()
+        };
+        let w = q.build_where(false).unwrap();
+        assert_eq!(
+            w.sql,
+            "WHERE timestamp >= ? AND (status = ? OR (status >= ? AND status < ?)) \
+             AND username = ? COLLATE NOCASE \
+             AND COALESCE(input_tokens, 0) + COALESCE(output_tokens, 0) >= ?"
+        );
+        assert_eq!(w.params.len(), 6);
+        assert_eq!(w.params[2], SqlValue::Integer(500));
+        assert_eq!(w.params[3], SqlValue::Integer(600));
+    }
+
+    #[test]
+    fn test_build_where_rejects_bad_input() {
+        let bad_status = LogQuery {
+            status: Some("9xx".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_status.build_where(false).is_err());
+
+        let bad_cursor = LogQuery {
+            cursor: Some("nope".to_string()),
+            ..Default::default()
+        };
+        assert!(bad_cursor.build_where(true).is_err());
+        // 计数 / 聚合不使用游标
+        assert!(bad_cursor.build_where(false).unwrap().sql.is_empty());
+    }
+
+    #[test]
+    fn test_query_uses_camel_case_fields() {
+        let q: LogQuery = serde_json::from_value(serde_json::json!({
+            "errorsOnly": true,
+            "mappedModel": "gemini-2.5-flash",
+            "minDuration": 500,
+            "minTotalTokens": 1000
+        }))
+        .unwrap();
+        assert!(q.errors_only);
+        assert_eq!(q.mapped_model.as_deref(), Some("gemini-2.5-flash"));
+        assert_eq!(q.min_duration, Some(500));
+        assert_eq!(q.min_total_tokens, Some(1000));
+    }
+
+    #[test]
+    fn test_percentiles_nearest_rank() {
+        let p = Percentiles::from_samples((1..=100).rev().map(f64::from).collect());
+        assert_eq!(p.samples, 100);
+        assert_eq!(p.p50, Some(50.0));
+        assert_eq!(p.p90, Some(90.0));
+        assert_eq!(p.p99, Some(99.0));
+
+        let single = Percentiles::from_samples(vec![42.0]);
+        assert_eq!((single.p50, single.p99), (Some(42.0), Some(42.0)));
+        assert_eq!(
+            Percentiles::from_samples(Vec::new()),
+            Percentiles::default()
+        );
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = encode_cursor(1_700_000_000_123, "abc-1");
+        assert_eq!(
+            decode_cursor(&cursor).unwrap(),
+            (1_700_000_000_123, "abc-1".to_string())
+        );
+    }
+}
@@ -0,0 +1,613 @@
+// 日志保留策略：请求日志 / IP 访问日志 / 调试日志目录统一按保留时长、条数、磁盘占用清理，
+// 定期 VACUUM 回收空间，并在写入请求日志前按 body_capture 裁剪请求 / 响应体。
+// 策略配置见 proxy::config::LogRetentionConfig，后台任务由 scheduler 启动。
+
+use crate::proxy::config::{BodyCaptureMode, LogRetentionConfig, ProxyConfig, StoreRetention};
+use crate::proxy::monitor::ProxyRequestLog;
+use rusqlite::Connection;
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+/// 按磁盘上限删除时最多估算的轮数 (每轮按平均行大小估算需删除的条数)
+const MAX_SIZE_PASSES: usize = 3;
+
+/// 上次执行的报告
+static LAST_REPORT: Mutex<Option<RetentionReport>> = Mutex::new(None);
+/// 上次 VACUUM 时间 (秒)
+static LAST_VACUUM: Mutex<Option<i64>> = Mutex::new(None);
+
+/// 表中 timestamp 列的单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimestampUnit {
+    Seconds,
+    Millis,
+}
+
+/// 单个存储的清理结果
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct StoreRetentionReport {
+    /// request_logs / ip_access_logs / debug_logs / message_batches
+    pub store: String,
+    /// 删除的记录数 (调试日志为文件数)
+    pub deleted: u64,
+    pub vacuumed: bool,
+    /// 清理前后的磁盘占用 (字节，SQLite 含 WAL 文件)
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    pub reclaimed_bytes: u64,
+    pub error: Option<String>,
+}
+
+/// 一次保留策略执行的报告
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RetentionReport {
+    /// 开始时间 (毫秒时间戳)
+    pub started_at: i64,
+    pub duration_ms: u64,
+    pub reclaimed_bytes: u64,
+    pub stores: Vec<StoreRetentionReport>,
+}
+
+/// 按 body_capture 裁剪请求 / 响应体 (错误信息 error 字段保持不变)
+pub fn apply_body_capture(log: &mut ProxyRequestLog, config: &LogRetentionConfig) {
+    let failed = log.status >= 400 || log.error.is_some();
+    let mode = match config.body_capture {
+        BodyCaptureMode::ErrorsOnlyFull if failed => BodyCaptureMode::Full,
+        BodyCaptureMode::ErrorsOnlyFull => BodyCaptureMode::MetadataOnly,
+        mode => mode,
+    };
+    let max_bytes = config.body_max_kb.saturating_mul(1024);
+    for body in [&mut log.request_body, &mut log.response_body] {
+        *body = match mode {
+            BodyCaptureMode::Full | BodyCaptureMode::ErrorsOnlyFull => body.take(),
+            BodyCaptureMode::None => None,
+            BodyCaptureMode::MetadataOnly => body.take().map(|b| format!("[{} bytes]", b.len())),
+            BodyCaptureMode::Truncated => body.take().map(|b| truncate_body(b, max_bytes)),
+        };
+    }
+}
+
+fn truncate_body(body: String, max_bytes: usize) -> String {
+    if body.len() <= max_bytes {
+        return body;
+    }
+    let mut end = max_bytes;
+    while !body.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!(
+        "{}\n...[truncated, {} bytes total]",
+        &body[..end],
+        body.len()
+    )
+}
+
+/// 按保留上限删除最旧的记录，返回删除条数。
+/// 磁盘上限按已用页估算，删除后需 VACUUM 才会缩小文件。
+pub fn prune_table(
+    conn: &Connection,
+    table: &str,
+    unit: TimestampUnit,
+    limits: &StoreRetention,
+    now_secs: i64,
+) -> Result<usize, String> {
+    let mut deleted = 0;
+
+    if let Some(days) = limits.max_age_days {
+        let cutoff = now_secs - i64::from(days) * 24 * 3600;
+        let cutoff = match unit {
+            TimestampUnit::Seconds => cutoff,
+            TimestampUnit::Millis => cutoff * 1000,
+        };
+        deleted += conn
+            .execute(
+                &format!("DELETE FROM {} WHERE timestamp < ?1", table),
+                [cutoff],
+            )
+            .map_err(|e| e.to_string())?;
+    }
+
+    if let Some(max_rows) = limits.max_rows {
+        let rows = count_rows(conn, table)?;
+        deleted += delete_oldest(conn, table, rows.saturating_sub(max_rows))?;
+    }
+
+    if let Some(max_bytes) = limits.max_size_bytes() {
+        for _ in 0..MAX_SIZE_PASSES {
+            let used = used_bytes(conn)?;
+            let rows = count_rows(conn, table)?;
+            if used <= max_bytes || rows == 0 {
+                break;
+            }
+            let per_row = (used / rows).max(1);
+            let excess = ((used - max_bytes) / per_row + 1).min(rows);
+            deleted += delete_oldest(conn, table, excess)?;
+        }
+    }
+
+    Ok(deleted)
+}
+
+fn count_rows(conn: &Connection, table: &str) -> Result<u64, String> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+        row.get(0)
+    })
+    .map_err(|e| e.to_string())
+}
+
+fn delete_oldest(conn: &Connection, table: &str, count: u64) -> Result<usize, String> {
+    if count == 0 {
+        return Ok(0);
+    }
+    conn.execute(
+        &format!(
+            "DELETE FROM {0} WHERE rowid IN (SELECT rowid FROM {0} ORDER BY timestamp ASC LIMIT ?1)",
+            table
+        ),
+        [count as i64],
+    )
+    .map_err(|e| e.to_string())
+}
+
+fn pragma_i64(conn: &Connection, name: &str) -> Result<i64, String> {
+    conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get(0))
+        .map_err(|e| e.to_string())
+}
+
+/// 数据库已用空间 (不含空闲页)
+pub fn used_bytes(conn: &Connection) -> Result<u64, String> {
+    let page_size = pragma_i64(conn, "page_size")?;
+    let used_pages = pragma_i64(conn, "page_count")? - pragma_i64(conn, "freelist_count")?;
+    Ok((used_pages.max(0) * page_size) as u64)
+}
+
+/// 是否存在可回收的空闲页
+pub fn has_free_pages(conn: &Connection) -> Result<bool, String> {
+    Ok(pragma_i64(conn, "freelist_count")? > 0)
+}
+
+/// 将 WAL 写回主库并截断 WAL 文件 (VACUUM 之后才能真正释放磁盘)
+pub fn checkpoint(conn: &Connection) -> Result<(), String> {
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| e.to_string())
+}
+
+pub fn vacuum(conn: &Connection) -> Result<(), String> {
+    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
+    checkpoint(conn)
+}
+
+/// 清理单个 SQLite 存储：删除超限记录，并在计划 VACUUM 到期或超出磁盘上限时回收空闲页。
+/// 返回 (删除条数, 是否执行了 VACUUM)
+pub fn enforce_sqlite_store(
+    conn: &Connection,
+    table: &str,
+    unit: TimestampUnit,
+    limits: &StoreRetention,
+    vacuum_due: bool,
+    vacuum: impl FnOnce(&Connection) -> Result<(), String>,
+) -> Result<(usize, bool), String> {
+    let deleted = prune_table(conn, table, unit, limits, chrono::Utc::now().timestamp())?;
+    let over_size = match limits.max_size_bytes() {
+        Some(max) => {
+            let allocated = pragma_i64(conn, "page_count")? * pragma_i64(conn, "page_size")?;
+            allocated.max(0) as u64 > max
+        }
+        None => false,
+    };
+    let vacuumed = (vacuum_due || over_size) && has_free_pages(conn)?;
+    if vacuumed {
+        vacuum(conn)?;
+    }
+    Ok((deleted, vacuumed))
+}
+
+/// SQLite 数据库文件 (含 WAL) 的磁盘占用
+pub fn sqlite_file_bytes(path: &Path) -> u64 {
+    let wal = PathBuf::from(format!("{}-wal", path.display()));
+    [path, wal.as_path()]
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+/// 清理调试日志目录中的 .json 文件 (按修改时间从旧到新删除)，返回删除的文件数
+pub fn prune_directory(
+    dir: &Path,
+    limits: &StoreRetention,
+    now: SystemTime,
+) -> Result<u64, String> {
+    let mut files = list_json_files(dir)?;
+    // 新文件在前
+    files.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let max_age = limits
+        .max_age_days
+        .map(|days| Duration::from_secs(u64::from(days) * 24 * 3600));
+    let max_files = limits.max_rows.map(|n| n as usize);
+    let max_bytes = limits.max_size_bytes();
+
+    let mut kept_bytes = 0u64;
+    let mut deleted = 0u64;
+    for (index, (path, modified, size)) in files.into_iter().enumerate() {
+        let expired = max_age
+            .map(|age| now.duration_since(modified).unwrap_or_default() > age)
+            .unwrap_or(false);
+        let over_count = max_files.map(|n| index >= n).unwrap_or(false);
+        let over_size = max_bytes
+            .map(|max| kept_bytes + size > max)
+            .unwrap_or(false);
+        if expired || over_count || over_size {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e))?;
+            deleted += 1;
+        } else {
+            kept_bytes += size;
+        }
+    }
+    Ok(deleted)
+}
+
+fn list_json_files(dir: &Path) -> Result<Vec<(PathBuf, SystemTime, u64)>, String> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries = std::fs::read_dir(dir).map_err(|e| e.to_string())?;
+    Ok(entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter_map(|entry| {
+            let meta = entry.metadata().ok()?;
+            meta.is_file().then(|| {
+                (
+                    entry.path(),
+                    meta.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+                    meta.len(),
+                )
+            })
+        })
+        .collect())
+}
+
+fn directory_bytes(dir: &Path) -> u64 {
+    list_json_files(dir)
+        .map(|files| files.iter().map(|(_, _, size)| size).sum())
+        .unwrap_or(0)
+}
+
+fn store_report(
+    store: &str,
+    measure: impl Fn() -> u64,
+    run: impl FnOnce() -> Result<(u64, bool), String>,
+) -> StoreRetentionReport {
+    let bytes_before = measure();
+    let (deleted, vacuumed, error) = match run() {
+        Ok((deleted, vacuumed)) => (deleted, vacuumed, None),
+        Err(e) => (0, false, Some(e)),
+    };
+    let bytes_after = measure();
+    StoreRetentionReport {
+        store: store.to_string(),
+        deleted,
+        vacuumed,
+        bytes_before,
+        bytes_after,
+        reclaimed_bytes: bytes_before.saturating_sub(bytes_after),
+        error,
+    }
+}
+
+fn vacuum_due(config: &LogRetentionConfig, now_secs: i64, force: bool) -> bool {
+    if force {
+        return true;
+    }
+    if config.vacuum_interval_hours == 0 {
+        return false;
+    }
+    match LAST_VACUUM.lock().ok().and_then(|guard| *guard) {
+        Some(last) => now_secs - last >= config.vacuum_interval_hours as i64 * 3600,
+        None => true,
+    }
+}
+
+/// 执行一次保留策略 (阻塞，含数据库与文件操作)。force_vacuum 为 true 时忽略 VACUUM 间隔
+pub fn run_retention(
+    config: &LogRetentionConfig,
+    debug_dir: Option<PathBuf>,
+    force_vacuum: bool,
+) -> RetentionReport {
+    let started = std::time::Instant::now();
+    let started_at = chrono::Utc::now().timestamp_millis();
+    let due = vacuum_due(config, started_at / 1000, force_vacuum);
+    let mut stores = Vec::new();
+
+    if let Ok(path) = crate::modules::proxy_db::get_proxy_db_path() {
+        stores.push(store_report(
+            "request_logs",
+            || sqlite_file_bytes(&path),
+            || {
+                crate::modules::proxy_db::enforce_retention(&config.request_logs, due)
+                    .map(|(deleted, vacuumed)| (deleted as u64, vacuumed))
+            },
+        ));
+    }
+    if let Ok(path) = crate::modules::security_db::get_security_db_path() {
+        stores.push(store_report(
+            "ip_access_logs",
+            || sqlite_file_bytes(&path),
+            || {
+                crate::modules::security_db::enforce_retention(&config.ip_access_logs, due)
+                    .map(|(deleted, vacuumed)| (deleted as u64, vacuumed))
+            },
+        ));
+    }
+    if let Some(dir) = debug_dir {
+        stores.push(store_report(
+            "debug_logs",
+            || directory_bytes(&dir),
+            || prune_directory(&dir, &config.debug_logs, SystemTime::now()).map(|n| (n, false)),
+        ));
+    }
+    if let Ok(store) = crate::proxy::message_batches::MessageBatchStore::open_default() {
+        stores.push(store_report(
+            "message_batches",
+            || store.disk_usage(),
+            || {
+                let now_ms = chrono::Utc::now().timestamp_millis();
+                store
+                    .enforce_retention(&config.message_batches, now_ms)
+                    .map(|n| (n, false))
+            },
+        ));
+    }
+
+    if due {
+        if let Ok(mut last) = LAST_VACUUM.lock() {
+            *last = Some(started_at / 1000);
+        }
+    }
+
+    let report = RetentionReport {
+        started_at,
+        duration_ms: started.elapsed().as_millis() as u64,
+        reclaimed_bytes: stores.iter().map(|s| s.reclaimed_bytes).sum(),
+        stores,
+    };
+    if let Ok(mut last) = LAST_REPORT.lock() {
+        *last = Some(report.clone());
+    }
+    report
+}
+
+/// 最近一次执行的报告
+pub fn last_report() -> Option<RetentionReport> {
+    LAST_REPORT.lock().ok().and_then(|guard| guard.clone())
+}
+
+async fn run_with_config(
+    proxy: &ProxyConfig,
+    force_vacuum: bool,
+) -> Result<RetentionReport, String> {
+    let config = proxy.log_retention.clone();
+    let debug_dir = crate::proxy::debug_logger::resolve_output_dir(&proxy.debug_logging);
+    tokio::task::spawn_blocking(move || run_retention(&config, debug_dir, force_vacuum))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 从配置文件读取策略并立即执行一次
+pub async fn run_from_app_config(force_vacuum: bool) -> Result<RetentionReport, String> {
+    let app_config = crate::modules::config::load_app_config()?;
+    run_with_config(&app_config.proxy, force_vacuum).await
+}
+
+fn log_report(report: &RetentionReport) {
+    for store in report.stores.iter().filter(|s| s.error.is_some()) {
+        tracing::warn!(
+            "[Log-Retention] {} cleanup failed: {}",
+            store.store,
+            store.error.as_deref().unwrap_or_default()
+        );
+    }
+    let deleted: u64 = report.stores.iter().map(|s| s.deleted).sum();
+    if deleted > 0 || report.reclaimed_bytes > 0 {
+        tracing::info!(
+            "[Log-Retention] Removed {} records/files, reclaimed {} bytes in {}ms",
+            deleted,
+            report.reclaimed_bytes,
+            report.duration_ms
+        );
+    }
+}
+
+/// 启动后台清理任务 (启动时立即执行一次，之后按 check_interval_minutes 周期执行)
+pub fn start_retention_task() {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            let interval = match crate::modules::config::load_app_config() {
+                Ok(app_config) => {
+                    let retention = &app_config.proxy.log_retention;
+                    if retention.enabled {
+                        match run_with_config(&app_config.proxy, false).await {
+                            Ok(report) => log_report(&report),
+                            Err(e) => tracing::error!("[Log-Retention] Run failed: {}", e),
+                        }
+                    }
+                    retention.check_interval_minutes
+                }
+                Err(_) => LogRetentionConfig::default().check_interval_minutes,
+            };
+            tokio::time::sleep(Duration::from_secs(interval.max(1) * 60)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seeded(rows: i64) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE logs (id TEXT PRIMARY KEY, timestamp INTEGER, body TEXT)")
+            .unwrap();
+        for i in 0..rows {
+            conn.execute(
+                "INSERT INTO logs VALUES (?1, ?2, ?3)",
+                rusqlite::params![format!("id-{}", i), i * 1000, "x".repeat(2000)],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn timestamps(conn: &Connection) -> Vec<i64> {
+        let mut stmt = conn
+            .prepare("SELECT timestamp FROM logs ORDER BY timestamp")
+            .unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.map(|r| r.unwrap()).collect()
+    }
+
+    #[test]
+    fn test_prune_by_age_and_rows() {
+        let conn = seeded(10);
+        // now = 1 天 + 5 秒 (毫秒时间戳 0..9000)，保留 1 天 => 删除 timestamp < 5000
+        let limits = StoreRetention::max_age(1);
+        let deleted =
+            prune_table(&conn, "logs", TimestampUnit::Millis, &limits, 24 * 3600 + 5).unwrap();
+        assert_eq!(deleted, 5);
+
+        let limits = StoreRetention {
+            max_rows: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            prune_table(&conn, "logs", TimestampUnit::Millis, &limits, 0).unwrap(),
+            3
+        );
+        assert_eq!(timestamps(&conn), vec![8000, 9000]);
+    }
+
+    #[test]
+    fn test_prune_by_size_keeps_newest() {
+        let conn = seeded(1000);
+        let before = used_bytes(&conn).unwrap();
+        let limits = StoreRetention {
+            max_size_mb: Some(1),
+            ..Default::default()
+        };
+        let deleted = prune_table(&conn, "logs", TimestampUnit::Seconds, &limits, 0).unwrap();
+        assert!(before > 1024 * 1024);
+        assert!(deleted > 0 && deleted < 1000);
+        assert!(used_bytes(&conn).unwrap() <= 1024 * 1024);
+        // 保留的是最新的记录
+        assert_eq!(*timestamps(&conn).last().unwrap(), 999_000);
+        assert_eq!(timestamps(&conn)[0], deleted as i64 * 1000);
+    }
+
+    fn body_log(status: u16) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "x".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status,
+            duration: 0,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            client_ip: None,
+            error: None,
+            request_body: Some("请求".repeat(1000)),
+            response_body: Some("r".repeat(5000)),
+            input_tokens: None,
+            output_tokens: None,
+            cached_tokens: None,
+            thinking_tokens: None,
+            tool_tokens: None,
+            protocol: None,
+            username: None,
+            context_compression: None,
+            ttft_ms: None,
+            stream_duration_ms: None,
+            tokens_per_sec: None,
+            proxy_id: None,
+        }
+    }
+
+    #[test]
+    fn test_body_capture_modes() {
+        let mut config = LogRetentionConfig {
+            body_capture: BodyCaptureMode::Truncated,
+            body_max_kb: 1,
+            ..Default::default()
+        };
+        let mut log = body_log(200);
+        apply_body_capture(&mut log, &config);
+        let request = log.request_body.unwrap();
+        // 按字符边界截断 (每个汉字 3 字节)
+        assert!(request.starts_with(&"请求".repeat(170)));
+        assert!(request.ends_with("[truncated, 6000 bytes total]"));
+        assert!(log.response_body.unwrap().len() < 1100);
+
+        config.body_capture = BodyCaptureMode::ErrorsOnlyFull;
+        let mut ok = body_log(200);
+        apply_body_capture(&mut ok, &config);
+        assert_eq!(ok.response_body.as_deref(), Some("[5000 bytes]"));
+        let mut failed = body_log(500);
+        apply_body_capture(&mut failed, &config);
+        assert_eq!(failed.response_body.map(|b| b.len()), Some(5000));
+
+        config.body_capture = BodyCaptureMode::None;
+        let mut log = body_log(500);
+        apply_body_capture(&mut log, &config);
+        assert!(log.request_body.is_none() && log.response_body.is_none());
+    }
+
+    #[test]
+    fn test_prune_directory() {
+        let dir = std::env::temp_dir().join(format!("retention-test-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..5 {
+            std::fs::write(dir.join(format!("{}.json", i)), vec![b'x'; 100]).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        std::fs::write(dir.join("notes.txt"), "keep").unwrap();
+
+        let limits = StoreRetention {
+            max_rows: Some(3),
+            max_size_mb: None,
+            max_age_days: None,
+        };
+        assert_eq!(
+            prune_directory(&dir, &limits, SystemTime::now()).unwrap(),
+            2
+        );
+        assert!(!dir.join("0.json").exists() && !dir.join("1.json").exists());
+        assert!(dir.join("4.json").exists() && dir.join("notes.txt").exists());
+
+        // 全部超期
+        let later = SystemTime::now() + Duration::from_secs(2 * 24 * 3600);
+        assert_eq!(
+            prune_directory(&dir, &StoreRetention::max_age(1), later).unwrap(),
+            3
+        );
+        assert_eq!(directory_bytes(&dir), 0);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_default_policy_prunes_by_age() {
+        // 未配置 log_retention 的旧配置也应按年龄清理 (请求日志 30 天, Message Batches 29 天)
+        let config: LogRetentionConfig = serde_json::from_value(serde_json::json!({})).unwrap();
+        assert_eq!(config, LogRetentionConfig::default());
+        assert!(config.enabled);
+        assert_eq!(config.request_logs.max_age_days, Some(30));
+        assert_eq!(config.ip_access_logs.max_age_days, Some(30));
+        assert_eq!(config.message_batches.max_age_days, Some(29));
+    }
+}
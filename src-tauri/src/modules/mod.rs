@@ -14,14 +14,19 @@ pub mod device;
 pub mod update_checker;
 pub mod scheduler;
 pub mod token_stats;
+pub mod pricing;
 pub mod cloudflared;
 pub mod integration;
 pub mod account_service;
 pub mod http_api;
 pub mod cache;
 pub mod log_bridge;
+pub mod log_query;
+pub mod log_retention;
+pub mod event_bus;
 pub mod security_db;
 pub mod user_token_db;
+pub mod vault;
 pub mod version;
 
 use crate::models;
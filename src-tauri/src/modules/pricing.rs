@@ -0,0 +1,352 @@
+// 模型价格表：按模型 (支持 * 通配) 与生效日期分版本维护，用于把 Token 用量折算为等价 API 费用。
+// 价格单位为 USD / 百万 Token，持久化在数据目录下的 model_prices.json，并在内存中缓存。
+// 费用在记录用量时计算并写入 token_stats (见 token_stats::record_usage)。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::{OnceLock, RwLock};
+
+use crate::proxy::common::model_mapping::wildcard_match;
+
+pub const CURRENCY: &str = "USD";
+const PRICES_FILE: &str = "model_prices.json";
+/// 内置基准价格的生效日期 (覆盖全部历史数据)
+const BASELINE_DATE: &str = "1970-01-01";
+
+/// 单个模型价格版本
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ModelPrice {
+    /// 唯一 ID (新增时留空，由服务端生成)
+    #[serde(default)]
+    pub id: String,
+    /// 模型名，支持 * 通配 (如 claude-sonnet-4*)
+    pub model: String,
+    /// 生效日期 (UTC, YYYY-MM-DD，含当天)
+    pub effective_from: String,
+    /// 输入单价 (USD / 1M tokens)
+    pub input_per_mtok: f64,
+    /// 输出单价 (USD / 1M tokens)
+    pub output_per_mtok: f64,
+    /// 缓存命中输入单价，未设置时按输入单价计
+    #[serde(default)]
+    pub cached_input_per_mtok: Option<f64>,
+    /// 思考 Token 单价，未设置时按输出单价计
+    #[serde(default)]
+    pub thinking_per_mtok: Option<f64>,
+}
+
+/// 价格表
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PriceTable {
+    pub currency: String,
+    pub prices: Vec<ModelPrice>,
+}
+
+/// 单次请求的计费 Token 拆分
+/// input_tokens 含缓存命中部分 (与上游 promptTokenCount 口径一致)，output_tokens 不含思考 Token，
+/// tool_tokens 为工具调用结果 (如搜索 grounding) 注入的提示词 Token，按输入单价计
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BillableTokens {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cached_tokens: u64,
+    pub thinking_tokens: u64,
+    pub tool_tokens: u64,
+}
+
+impl ModelPrice {
+    fn baseline(model: &str, input: f64, output: f64, cached_input: f64) -> Self {
+        Self {
+            id: format!("baseline:{}", model),
+            model: model.to_string(),
+            effective_from: BASELINE_DATE.to_string(),
+            input_per_mtok: input,
+            output_per_mtok: output,
+            cached_input_per_mtok: Some(cached_input),
+            thinking_per_mtok: None,
+        }
+    }
+
+    /// 按本价格计算费用 (USD)
+    pub fn cost(&self, tokens: &BillableTokens) -> f64 {
+        let cached = tokens.cached_tokens.min(tokens.input_tokens);
+        let uncached = tokens.input_tokens - cached;
+        let per_token =
+            |count: u64, price_per_mtok: f64| count as f64 * price_per_mtok / 1_000_000.0;
+
+        per_token(uncached + tokens.tool_tokens, self.input_per_mtok)
+            + per_token(
+                cached,
+                self.cached_input_per_mtok.unwrap_or(self.input_per_mtok),
+            )
+            + per_token(tokens.output_tokens, self.output_per_mtok)
+            + per_token(
+                tokens.thinking_tokens,
+                self.thinking_per_mtok.unwrap_or(self.output_per_mtok),
+            )
+    }
+
+    /// 模式具体程度：精确匹配优先，其次按非通配字符数
+    fn specificity(&self) -> (bool, usize) {
+        (
+            !self.model.contains('*'),
+            self.model.chars().filter(|c| *c != '*').count(),
+        )
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.model.trim().is_empty() {
+            return Err("Model must not be empty".to_string());
+        }
+        chrono::NaiveDate::parse_from_str(&self.effective_from, "%Y-%m-%d").map_err(|_| {
+            format!(
+                "Invalid effective_from '{}', expected YYYY-MM-DD",
+                self.effective_from
+            )
+        })?;
+        let prices = [
+            Some(self.input_per_mtok),
+            Some(self.output_per_mtok),
+            self.cached_input_per_mtok,
+            self.thinking_per_mtok,
+        ];
+        if prices
+            .into_iter()
+            .flatten()
+            .any(|p| !p.is_finite() || p < 0.0)
+        {
+            return Err("Prices must be non-negative numbers".to_string());
+        }
+        Ok(())
+    }
+}
+
+impl PriceTable {
+    /// 内置基准价格 (各厂商公开标准价，可在设置中覆盖或追加新版本)
+    pub fn builtin() -> Self {
+        Self {
+            currency: CURRENCY.to_string(),
+            prices: vec![
+                ModelPrice::baseline("claude-opus-4-5*", 5.0, 25.0, 0.5),
+                ModelPrice::baseline("claude-opus-4*", 15.0, 75.0, 1.5),
+                ModelPrice::baseline("claude-sonnet-4*", 3.0, 15.0, 0.3),
+                ModelPrice::baseline("claude-haiku-4*", 1.0, 5.0, 0.1),
+                ModelPrice::baseline("gemini-3-pro*", 2.0, 12.0, 0.2),
+                ModelPrice::baseline("gemini-3-flash*", 0.5, 3.0, 0.05),
+                ModelPrice::baseline("gemini-2.5-pro*", 1.25, 10.0, 0.125),
+                ModelPrice::baseline("gemini-2.5-flash*", 0.3, 2.5, 0.03),
+                ModelPrice::baseline("gemini-2.5-flash-lite*", 0.1, 0.4, 0.01),
+            ],
+        }
+    }
+
+    /// 查找模型在指定日期 (YYYY-MM-DD) 生效的价格：
+    /// 先选最具体的匹配模式，再取该模式下最近生效的版本
+    pub fn resolve(&self, model: &str, date: &str) -> Option<&ModelPrice> {
+        self.prices
+            .iter()
+            .filter(|p| p.effective_from.as_str() <= date && wildcard_match(&p.model, model))
+            .max_by(|a, b| {
+                a.specificity()
+                    .cmp(&b.specificity())
+                    .then_with(|| a.effective_from.cmp(&b.effective_from))
+            })
+    }
+
+    /// 新增或按 ID 更新价格版本
+    fn upsert(&mut self, mut price: ModelPrice) -> Result<ModelPrice, String> {
+        price.model = price.model.trim().to_string();
+        price.validate()?;
+
+        if price.id.trim().is_empty() {
+            price.id = uuid::Uuid::new_v4().to_string();
+        }
+        if let Some(existing) = self.prices.iter().find(|p| {
+            p.id != price.id && p.model == price.model && p.effective_from == price.effective_from
+        }) {
+            return Err(format!(
+                "Price for '{}' effective {} already exists (id: {})",
+                price.model, price.effective_from, existing.id
+            ));
+        }
+
+        match self.prices.iter_mut().find(|p| p.id == price.id) {
+            Some(slot) => *slot = price.clone(),
+            None => self.prices.push(price.clone()),
+        }
+        self.prices.sort_by(|a, b| {
+            a.model
+                .cmp(&b.model)
+                .then_with(|| a.effective_from.cmp(&b.effective_from))
+        });
+        Ok(price)
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), String> {
+        let before = self.prices.len();
+        self.prices.retain(|p| p.id != id);
+        if self.prices.len() == before {
+            return Err(format!("Price not found: {}", id));
+        }
+        Ok(())
+    }
+}
+
+static PRICE_TABLE: OnceLock<RwLock<PriceTable>> = OnceLock::new();
+
+fn prices_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::modules::account::get_data_dir()?.join(PRICES_FILE))
+}
+
+fn load_from_disk() -> PriceTable {
+    let path = match prices_path() {
+        Ok(path) if path.exists() => path,
+        _ => return PriceTable::builtin(),
+    };
+    match std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| serde_json::from_str::<PriceTable>(&content).map_err(|e| e.to_string()))
+    {
+        Ok(table) => table,
+        Err(e) => {
+            tracing::warn!(
+                "[Pricing] Failed to load {}, using builtin prices: {}",
+                PRICES_FILE,
+                e
+            );
+            PriceTable::builtin()
+        }
+    }
+}
+
+fn table() -> &'static RwLock<PriceTable> {
+    PRICE_TABLE.get_or_init(|| RwLock::new(load_from_disk()))
+}
+
+fn save_to_disk(table: &PriceTable) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(table)
+        .map_err(|e| format!("Failed to serialize prices: {}", e))?;
+    std::fs::write(prices_path()?, content)
+        .map_err(|e| format!("Failed to write prices file: {}", e))
+}
+
+/// 获取当前价格表
+pub fn get_price_table() -> PriceTable {
+    table().read().map(|t| t.clone()).unwrap_or_default()
+}
+
+/// 查找模型在指定日期生效的价格
+pub fn price_for(model: &str, date: &str) -> Option<ModelPrice> {
+    table().read().ok()?.resolve(model, date).cloned()
+}
+
+/// 新增或更新价格版本并持久化
+pub fn save_price(price: ModelPrice) -> Result<ModelPrice, String> {
+    let mut guard = table().write().map_err(|e| e.to_string())?;
+    let mut updated = guard.clone();
+    let saved = updated.upsert(price)?;
+    save_to_disk(&updated)?;
+    *guard = updated;
+    Ok(saved)
+}
+
+/// 删除价格版本并持久化
+pub fn delete_price(id: &str) -> Result<(), String> {
+    let mut guard = table().write().map_err(|e| e.to_string())?;
+    let mut updated = guard.clone();
+    updated.remove(id)?;
+    save_to_disk(&updated)?;
+    *guard = updated;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(model: &str, effective_from: &str, input: f64, output: f64) -> ModelPrice {
+        ModelPrice {
+            id: String::new(),
+            model: model.to_string(),
+            effective_from: effective_from.to_string(),
+            input_per_mtok: input,
+            output_per_mtok: output,
+            cached_input_per_mtok: None,
+            thinking_per_mtok: None,
+        }
+    }
+
+    #[test]
+    fn test_resolve_prefers_specific_pattern_and_latest_version() {
+        let mut table = PriceTable::builtin();
+        table
+            .upsert(price("gemini-2.5-flash*", "2026-01-01", 0.5, 4.0))
+            .unwrap();
+        table
+            .upsert(price("gemini-2.5-flash", "2026-03-01", 0.6, 5.0))
+            .unwrap();
+
+        let lite = table
+            .resolve("gemini-2.5-flash-lite", "2026-06-01")
+            .unwrap();
+        assert_eq!(lite.input_per_mtok, 0.1);
+
+        // 新版本只对生效日期之后的数据生效
+        let old = table
+            .resolve("gemini-2.5-flash-preview", "2025-12-31")
+            .unwrap();
+        assert_eq!(old.input_per_mtok, 0.3);
+        let new = table
+            .resolve("gemini-2.5-flash-preview", "2026-01-01")
+            .unwrap();
+        assert_eq!(new.input_per_mtok, 0.5);
+
+        // 精确匹配优先于通配
+        let exact = table.resolve("gemini-2.5-flash", "2026-06-01").unwrap();
+        assert_eq!(exact.output_per_mtok, 5.0);
+        assert!(table
+            .resolve("gemini-2.5-flash", "2026-02-01")
+            .unwrap()
+            .model
+            .contains('*'));
+
+        assert!(table.resolve("unknown-model", "2026-06-01").is_none());
+    }
+
+    #[test]
+    fn test_cost_breakdown() {
+        let mut p = price("m", BASELINE_DATE, 2.0, 10.0);
+        p.cached_input_per_mtok = Some(0.5);
+        let tokens = BillableTokens {
+            input_tokens: 1_000_000,
+            output_tokens: 100_000,
+            cached_tokens: 400_000,
+            thinking_tokens: 50_000,
+            tool_tokens: 100_000,
+        };
+        // (600k + 100k 工具) * 2 + 400k * 0.5 + 100k * 10 + 50k * 10 (思考按输出价)
+        let expected = 1.4 + 0.2 + 1.0 + 0.5;
+        assert!((p.cost(&tokens) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_upsert_validation_and_remove() {
+        let mut table = PriceTable::default();
+        assert!(table.upsert(price("", "2026-01-01", 1.0, 1.0)).is_err());
+        assert!(table.upsert(price("m", "2026/01/01", 1.0, 1.0)).is_err());
+        assert!(table.upsert(price("m", "2026-01-01", -1.0, 1.0)).is_err());
+
+        let saved = table.upsert(price("m", "2026-01-01", 1.0, 1.0)).unwrap();
+        assert!(!saved.id.is_empty());
+        assert!(table.upsert(price("m", "2026-01-01", 2.0, 2.0)).is_err());
+
+        let mut edited = saved.clone();
+        edited.output_per_mtok = 3.0;
+        table.upsert(edited).unwrap();
+        assert_eq!(table.prices.len(), 1);
+        assert_eq!(table.prices[0].output_per_mtok, 3.0);
+
+        table.remove(&saved.id).unwrap();
+        assert!(table.remove(&saved.id).is_err());
+    }
+}
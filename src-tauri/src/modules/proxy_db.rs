@@ -1,5 +1,11 @@
-use rusqlite::{params, Connection};
+use rusqlite::{params, params_from_iter, Connection, Row};
+use std::io::Write;
 use std::path::PathBuf;
+use crate::modules::log_query::{
+    encode_cursor, LatencyStats, LogAggregate, LogExportFormat, LogGroupBy, LogPage, LogQuery,
+    Percentiles,
+};
+use std::collections::HashMap;
 use crate::proxy::monitor::ProxyRequestLog;
 
 pub fn get_proxy_db_path() -> Result<PathBuf, String> {
@@ -26,7 +32,32 @@ fn connect_db() -> Result<Connection, String> {
 pub fn init_db() -> Result<(), String> {
     // connect_db will initialize WAL mode and other pragmas
     let conn = connect_db()?;
-    
+    create_schema(&conn)
+}
+
+/// 全文索引 (外部内容表，由触发器与 request_logs 同步)
+const FTS_SCHEMA: &str = "
+    CREATE VIRTUAL TABLE IF NOT EXISTS request_logs_fts USING fts5(
+        error, request_body, response_body,
+        content='request_logs', content_rowid='rowid'
+    );
+    CREATE TRIGGER IF NOT EXISTS request_logs_fts_ai AFTER INSERT ON request_logs BEGIN
+        INSERT INTO request_logs_fts(rowid, error, request_body, response_body)
+        VALUES (new.rowid, new.error, new.request_body, new.response_body);
+    END;
+    CREATE TRIGGER IF NOT EXISTS request_logs_fts_ad AFTER DELETE ON request_logs BEGIN
+        INSERT INTO request_logs_fts(request_logs_fts, rowid, error, request_body, response_body)
+        VALUES ('delete', old.rowid, old.error, old.request_body, old.response_body);
+    END;
+    CREATE TRIGGER IF NOT EXISTS request_logs_fts_au AFTER UPDATE ON request_logs BEGIN
+        INSERT INTO request_logs_fts(request_logs_fts, rowid, error, request_body, response_body)
+        VALUES ('delete', old.rowid, old.error, old.request_body, old.response_body);
+        INSERT INTO request_logs_fts(rowid, error, request_body, response_body)
+        VALUES (new.rowid, new.error, new.request_body, new.response_body);
+    END;
+";
+
+fn create_schema(conn: &Connection) -> Result<(), String> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS request_logs (
             id TEXT PRIMARY KEY,
@@ -51,6 +82,14 @@ pub fn init_db() -> Result<(), String> {
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN protocol TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN client_ip TEXT", []);
     let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN username TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN context_compression TEXT", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN cached_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN thinking_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN tool_tokens INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN ttft_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN stream_duration_ms INTEGER", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN tokens_per_sec REAL", []);
+    let _ = conn.execute("ALTER TABLE request_logs ADD COLUMN proxy_id TEXT", []);
 
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_timestamp ON request_logs (timestamp DESC)",
@@ -63,15 +102,92 @@ pub fn init_db() -> Result<(), String> {
         [],
     ).map_err(|e| e.to_string())?;
 
+    // 常用结构化过滤列
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_account_email ON request_logs (account_email);
+         CREATE INDEX IF NOT EXISTS idx_username ON request_logs (username);
+         CREATE INDEX IF NOT EXISTS idx_client_ip ON request_logs (client_ip);
+         CREATE INDEX IF NOT EXISTS idx_proxy_id ON request_logs (proxy_id);",
+    ).map_err(|e| e.to_string())?;
+
+    let fts_exists: bool = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'request_logs_fts'",
+        [],
+        |row| row.get::<_, i64>(0),
+    ).map_err(|e| e.to_string())? > 0;
+    conn.execute_batch(FTS_SCHEMA).map_err(|e| e.to_string())?;
+    if !fts_exists {
+        // 首次创建索引时为已有日志补建
+        rebuild_fts_index(conn)?;
+    }
+
     Ok(())
 }
 
+/// 重建全文索引 (VACUUM 可能改变 rowid，之后必须重建)
+fn rebuild_fts_index(conn: &Connection) -> Result<(), String> {
+    conn.execute("INSERT INTO request_logs_fts(request_logs_fts) VALUES ('rebuild')", [])
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+fn vacuum_and_reindex(conn: &Connection) -> Result<(), String> {
+    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
+    rebuild_fts_index(conn)?;
+    crate::modules::log_retention::checkpoint(conn)
+}
+
+/// 列表查询列 (顺序与 row_to_log 对应)
+const SUMMARY_COLUMNS: &str = "id, timestamp, method, url, status, duration, model, error,
+    NULL AS request_body, NULL AS response_body, input_tokens, output_tokens,
+    account_email, mapped_model, protocol, client_ip, username, NULL AS context_compression,
+    cached_tokens, thinking_tokens, tool_tokens, ttft_ms, stream_duration_ms, tokens_per_sec, proxy_id";
+
+/// 完整列 (含请求/响应体，用于详情与导出)
+const FULL_COLUMNS: &str = "id, timestamp, method, url, status, duration, model, error,
+    request_body, response_body, input_tokens, output_tokens,
+    account_email, mapped_model, protocol, client_ip, username, context_compression,
+    cached_tokens, thinking_tokens, tool_tokens, ttft_ms, stream_duration_ms, tokens_per_sec, proxy_id";
+
+fn row_to_log(row: &Row) -> rusqlite::Result<ProxyRequestLog> {
+    Ok(ProxyRequestLog {
+        id: row.get(0)?,
+        timestamp: row.get(1)?,
+        method: row.get(2)?,
+        url: row.get(3)?,
+        status: row.get(4)?,
+        duration: row.get(5)?,
+        model: row.get(6)?,
+        mapped_model: row.get(13).unwrap_or(None),
+        account_email: row.get(12).unwrap_or(None),
+        error: row.get(7)?,
+        request_body: row.get(8).unwrap_or(None),
+        response_body: row.get(9).unwrap_or(None),
+        input_tokens: row.get(10).unwrap_or(None),
+        output_tokens: row.get(11).unwrap_or(None),
+        protocol: row.get(14).unwrap_or(None),
+        client_ip: row.get(15).unwrap_or(None),
+        username: row.get(16).unwrap_or(None),
+        context_compression: row.get(17).unwrap_or(None),
+        cached_tokens: row.get(18).unwrap_or(None),
+        thinking_tokens: row.get(19).unwrap_or(None),
+        tool_tokens: row.get(20).unwrap_or(None),
+        ttft_ms: row.get(21).unwrap_or(None),
+        stream_duration_ms: row.get(22).unwrap_or(None),
+        tokens_per_sec: row.get(23).unwrap_or(None),
+        proxy_id: row.get(24).unwrap_or(None),
+    })
+}
+
 pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
     let conn = connect_db()?;
+    insert_log(&conn, log)
+}
 
+fn insert_log(conn: &Connection, log: &ProxyRequestLog) -> Result<(), String> {
     conn.execute(
-        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip, username)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+        "INSERT INTO request_logs (id, timestamp, method, url, status, duration, model, error, request_body, response_body, input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip, username, context_compression, cached_tokens, thinking_tokens, tool_tokens, ttft_ms, stream_duration_ms, tokens_per_sec, proxy_id)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)",
         params![
             log.id,
             log.timestamp,
@@ -90,6 +206,14 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
             log.protocol,
             log.client_ip,
             log.username,
+            log.context_compression,
+            log.cached_tokens,
+            log.thinking_tokens,
+            log.tool_tokens,
+            log.ttft_ms,
+            log.stream_duration_ms,
+            log.tokens_per_sec,
+            log.proxy_id,
         ],
     ).map_err(|e| e.to_string())?;
 
@@ -100,37 +224,14 @@ pub fn save_log(log: &ProxyRequestLog) -> Result<(), String> {
 pub fn get_logs_summary(limit: usize, offset: usize) -> Result<Vec<ProxyRequestLog>, String> {
     let conn = connect_db()?;
 
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error, 
-                NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip
-         FROM request_logs 
-         ORDER BY timestamp DESC 
-         LIMIT ?1 OFFSET ?2"
-    ).map_err(|e| e.to_string())?;
-
-    let logs_iter = stmt.query_map([limit, offset], |row| {
-        Ok(ProxyRequestLog {
-            id: row.get(0)?,
-            timestamp: row.get(1)?,
-            method: row.get(2)?,
-            url: row.get(3)?,
-            status: row.get(4)?,
-            duration: row.get(5)?,
-            model: row.get(6)?,
-            mapped_model: row.get(13).unwrap_or(None),
-            account_email: row.get(12).unwrap_or(None),
-            error: row.get(7)?,
-            request_body: None,  // Don't query large fields for list view
-            response_body: None, // Don't query large fields for list view
-            input_tokens: row.get(10).unwrap_or(None),
-            output_tokens: row.get(11).unwrap_or(None),
-            protocol: row.get(14).unwrap_or(None),
-            client_ip: row.get(15).unwrap_or(None),
-            username: row.get(16).unwrap_or(None),
-        })
+    // Don't query large fields for list view
+    let sql = format!(
+        "SELECT {} FROM request_logs ORDER BY timestamp DESC LIMIT ?1 OFFSET ?2",
+        SUMMARY_COLUMNS
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
 
-    }).map_err(|e| e.to_string())?;
+    let logs_iter = stmt.query_map([limit, offset], row_to_log).map_err(|e| e.to_string())?;
 
     let mut logs = Vec::new();
     for log in logs_iter {
@@ -169,69 +270,27 @@ pub fn get_stats() -> Result<crate::proxy::monitor::ProxyStats, String> {
 pub fn get_log_detail(log_id: &str) -> Result<ProxyRequestLog, String> {
     let conn = connect_db()?;
 
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error,
-                request_body, response_body, input_tokens, output_tokens,
-                account_email, mapped_model, protocol, client_ip, username
-         FROM request_logs
-         WHERE id = ?1"
-    ).map_err(|e| e.to_string())?;
+    let sql = format!("SELECT {} FROM request_logs WHERE id = ?1", FULL_COLUMNS);
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
 
-    stmt.query_row([log_id], |row| {
-        Ok(ProxyRequestLog {
-            id: row.get(0)?,
-            timestamp: row.get(1)?,
-            method: row.get(2)?,
-            url: row.get(3)?,
-            status: row.get(4)?,
-            duration: row.get(5)?,
-            model: row.get(6)?,
-            mapped_model: row.get(13).unwrap_or(None),
-            account_email: row.get(12).unwrap_or(None),
-            error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
-            input_tokens: row.get(10).unwrap_or(None),
-            output_tokens: row.get(11).unwrap_or(None),
-            protocol: row.get(14).unwrap_or(None),
-            client_ip: row.get(15).unwrap_or(None),
-            username: row.get(16).unwrap_or(None),
-        })
-    }).map_err(|e| e.to_string())
+    stmt.query_row([log_id], row_to_log).map_err(|e| e.to_string())
 }
 
-/// Cleanup old logs (keep last N days)
-pub fn cleanup_old_logs(days: i64) -> Result<usize, String> {
+/// 按保留策略清理请求日志 (保留时长 / 条数 / 磁盘占用)，返回 (删除条数, 是否执行了 VACUUM)
+pub fn enforce_retention(
+    limits: &crate::proxy::config::StoreRetention,
+    vacuum_due: bool,
+) -> Result<(usize, bool), String> {
     let conn = connect_db()?;
-    
-    let cutoff_timestamp = chrono::Utc::now().timestamp() - (days * 24 * 3600);
-    
-    let deleted = conn.execute(
-        "DELETE FROM request_logs WHERE timestamp < ?1",
-        [cutoff_timestamp],
-    ).map_err(|e| e.to_string())?;
-    
-    // Execute VACUUM to reclaim disk space
-    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
-    
-    Ok(deleted)
-}
-
-/// Limit maximum log count (keep newest N records)
-#[allow(dead_code)]
-pub fn limit_max_logs(max_count: usize) -> Result<usize, String> {
-    let conn = connect_db()?;
-    
-    let deleted = conn.execute(
-        "DELETE FROM request_logs WHERE id NOT IN (
-            SELECT id FROM request_logs ORDER BY timestamp DESC LIMIT ?1
-        )",
-        [max_count],
-    ).map_err(|e| e.to_string())?;
-    
-    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
-    
-    Ok(deleted)
+    create_schema(&conn)?;
+    crate::modules::log_retention::enforce_sqlite_store(
+        &conn,
+        "request_logs",
+        crate::modules::log_retention::TimestampUnit::Millis,
+        limits,
+        vacuum_due,
+        vacuum_and_reindex,
+    )
 }
 
 pub fn clear_logs() -> Result<(), String> {
@@ -254,180 +313,312 @@ pub fn get_logs_count() -> Result<u64, String> {
 }
 
 /// Get count of logs matching search filter
-/// filter: search text to match in url, method, model, or status
+/// filter: search text to match in url, method, model, status, account or client IP
 /// errors_only: if true, only count logs with status < 200 or >= 400
 pub fn get_logs_count_filtered(filter: &str, errors_only: bool) -> Result<u64, String> {
     let conn = connect_db()?;
-    
-    let filter_pattern = format!("%{}%", filter);
-    
-    let sql = if errors_only {
-        "SELECT COUNT(*) FROM request_logs WHERE (status < 200 OR status >= 400)"
-    } else if filter.is_empty() {
-        "SELECT COUNT(*) FROM request_logs"
-    } else {
-        "SELECT COUNT(*) FROM request_logs WHERE
-            (url LIKE ?1 OR method LIKE ?1 OR model LIKE ?1 OR CAST(status AS TEXT) LIKE ?1 OR account_email LIKE ?1)"
-    };
-    
-    let count: u64 = if filter.is_empty() && !errors_only {
-        conn.query_row(sql, [], |row| row.get(0))
-    } else if errors_only {
-        conn.query_row(sql, [], |row| row.get(0))
-    } else {
-        conn.query_row(sql, [&filter_pattern], |row| row.get(0))
-    }.map_err(|e| e.to_string())?;
-    
-    Ok(count)
+    count_logs_with_conn(&conn, &legacy_query(filter, errors_only))
 }
 
 /// Get logs with search filter and pagination
-/// filter: search text to match in url, method, model, or status
+/// filter: search text to match in url, method, model, status, account or client IP
 /// errors_only: if true, only return logs with status < 200 or >= 400
 pub fn get_logs_filtered(filter: &str, errors_only: bool, limit: usize, offset: usize) -> Result<Vec<ProxyRequestLog>, String> {
     let conn = connect_db()?;
+    let where_clause = legacy_query(filter, errors_only).build_where(false)?;
+    let sql = format!(
+        "SELECT {} FROM request_logs {} ORDER BY timestamp DESC LIMIT {} OFFSET {}",
+        SUMMARY_COLUMNS, where_clause.sql, limit, offset
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let logs = stmt
+        .query_map(params_from_iter(where_clause.params), row_to_log)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+    Ok(logs)
+}
 
-    let filter_pattern = format!("%{}%", filter);
-    
-    let sql = if errors_only {
-        "SELECT id, timestamp, method, url, status, duration, model, error,
-                NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip, username
-         FROM request_logs
-         WHERE (status < 200 OR status >= 400)
-         ORDER BY timestamp DESC
-         LIMIT ?1 OFFSET ?2"
-    } else if filter.is_empty() {
-        "SELECT id, timestamp, method, url, status, duration, model, error,
-                NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip, username
-         FROM request_logs
-         ORDER BY timestamp DESC
-         LIMIT ?1 OFFSET ?2"
+fn legacy_query(filter: &str, errors_only: bool) -> LogQuery {
+    LogQuery {
+        filter: Some(filter.to_string()),
+        errors_only,
+        ..Default::default()
+    }
+}
+
+fn count_logs_with_conn(conn: &Connection, query: &LogQuery) -> Result<u64, String> {
+    let where_clause = query.build_where(false)?;
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM request_logs {}", where_clause.sql),
+        params_from_iter(where_clause.params),
+        |row| row.get(0),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// 结构化 + 全文检索 (游标分页，按时间倒序)
+pub fn search_logs(query: &LogQuery) -> Result<LogPage, String> {
+    let conn = connect_db()?;
+    search_logs_with_conn(&conn, query)
+}
+
+fn search_logs_with_conn(conn: &Connection, query: &LogQuery) -> Result<LogPage, String> {
+    let page_size = query.page_size();
+    let where_clause = query.build_where(true)?;
+    // 多取一条用于判断是否还有下一页
+    let sql = format!(
+        "SELECT {} FROM request_logs {} ORDER BY timestamp DESC, id DESC LIMIT {}",
+        SUMMARY_COLUMNS,
+        where_clause.sql,
+        page_size + 1
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut logs = stmt
+        .query_map(params_from_iter(where_clause.params), row_to_log)
+        .map_err(|e| e.to_string())?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    let next_cursor = if logs.len() > page_size {
+        logs.truncate(page_size);
+        logs.last().map(|log| encode_cursor(log.timestamp, &log.id))
     } else {
-        "SELECT id, timestamp, method, url, status, duration, model, error,
-                NULL as request_body, NULL as response_body,
-                input_tokens, output_tokens, account_email, mapped_model, protocol, client_ip, username
-         FROM request_logs
-         WHERE (url LIKE ?3 OR method LIKE ?3 OR model LIKE ?3 OR CAST(status AS TEXT) LIKE ?3 OR account_email LIKE ?3 OR client_ip LIKE ?3)
-         ORDER BY timestamp DESC
-         LIMIT ?1 OFFSET ?2"
+        None
     };
 
-    let logs: Vec<ProxyRequestLog> = if filter.is_empty() && !errors_only {
-        let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-        let logs_iter = stmt.query_map([limit, offset], |row| {
-            Ok(ProxyRequestLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                method: row.get(2)?,
-                url: row.get(3)?,
-                status: row.get(4)?,
-                duration: row.get(5)?,
-                model: row.get(6)?,
-                mapped_model: row.get(13).unwrap_or(None),
-                account_email: row.get(12).unwrap_or(None),
-                error: row.get(7)?,
-                request_body: None,
-                response_body: None,
-                input_tokens: row.get(10).unwrap_or(None),
-                output_tokens: row.get(11).unwrap_or(None),
-                protocol: row.get(14).unwrap_or(None),
-                client_ip: row.get(15).unwrap_or(None),
-                username: row.get(16).unwrap_or(None),
-            })
+    Ok(LogPage {
+        logs,
+        total: count_logs_with_conn(conn, query)?,
+        next_cursor,
+    })
+}
+
+/// 按维度聚合 (请求数 / 错误数 / token / 耗时)
+pub fn aggregate_logs(query: &LogQuery, group_by: LogGroupBy) -> Result<Vec<LogAggregate>, String> {
+    let conn = connect_db()?;
+    aggregate_logs_with_conn(&conn, query, group_by)
+}
 
-        }).map_err(|e| e.to_string())?;
-        logs_iter.filter_map(|r| r.ok()).collect()
-    } else if errors_only {
-        let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-        let logs_iter = stmt.query_map([limit, offset], |row| {
-            Ok(ProxyRequestLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                method: row.get(2)?,
-                url: row.get(3)?,
-                status: row.get(4)?,
-                duration: row.get(5)?,
-                model: row.get(6)?,
-                mapped_model: row.get(13).unwrap_or(None),
-                account_email: row.get(12).unwrap_or(None),
-                error: row.get(7)?,
-                request_body: None,
-                response_body: None,
-                input_tokens: row.get(10).unwrap_or(None),
-                output_tokens: row.get(11).unwrap_or(None),
-                protocol: row.get(14).unwrap_or(None),
-                client_ip: row.get(15).unwrap_or(None),
-                username: row.get(16).unwrap_or(None),
+fn aggregate_logs_with_conn(
+    conn: &Connection,
+    query: &LogQuery,
+    group_by: LogGroupBy,
+) -> Result<Vec<LogAggregate>, String> {
+    let where_clause = query.build_where(false)?;
+    let order = if group_by.is_time_bucket() { "key ASC" } else { "requests DESC, key ASC" };
+    let sql = format!(
+        "SELECT {} AS key,
+                COUNT(*) AS requests,
+                SUM(CASE WHEN status < 200 OR status >= 400 THEN 1 ELSE 0 END),
+                COALESCE(SUM(input_tokens), 0),
+                COALESCE(SUM(output_tokens), 0),
+                COALESCE(SUM(cached_tokens), 0),
+                COALESCE(SUM(thinking_tokens), 0),
+                COALESCE(SUM(tool_tokens), 0),
+                COALESCE(AVG(duration), 0),
+                COALESCE(MAX(duration), 0)
+         FROM request_logs {}
+         GROUP BY key
+         ORDER BY {}
+         LIMIT {}",
+        group_by.sql_expr(),
+        where_clause.sql,
+        order,
+        query.page_size()
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params_from_iter(where_clause.params), |row| {
+            Ok(LogAggregate {
+                key: row.get(0)?,
+                requests: row.get(1)?,
+                errors: row.get(2)?,
+                input_tokens: row.get(3)?,
+                output_tokens: row.get(4)?,
+                cached_tokens: row.get(5)?,
+                thinking_tokens: row.get(6)?,
+                tool_tokens: row.get(7)?,
+                avg_duration: row.get(8)?,
+                max_duration: row.get(9)?,
             })
+        })
+        .map_err(|e| e.to_string())?
+        .collect::<rusqlite::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+    Ok(rows)
+}
 
-        }).map_err(|e| e.to_string())?;
-        logs_iter.filter_map(|r| r.ok()).collect()
+/// 延迟分位数报告 (按维度分组，分位数在内存中计算)
+pub fn latency_report(query: &LogQuery, group_by: LogGroupBy) -> Result<Vec<LatencyStats>, String> {
+    let conn = connect_db()?;
+    latency_report_with_conn(&conn, query, group_by)
+}
+
+#[derive(Default)]
+struct LatencySamples {
+    requests: u64,
+    errors: u64,
+    duration: Vec<f64>,
+    ttft: Vec<f64>,
+    stream_duration: Vec<f64>,
+    tokens_per_sec: Vec<f64>,
+}
+
+fn latency_report_with_conn(
+    conn: &Connection,
+    query: &LogQuery,
+    group_by: LogGroupBy,
+) -> Result<Vec<LatencyStats>, String> {
+    let where_clause = query.build_where(false)?;
+    let sql = format!(
+        "SELECT {} AS key, status, duration, ttft_ms, stream_duration_ms, tokens_per_sec
+         FROM request_logs {}",
+        group_by.sql_expr(),
+        where_clause.sql
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let mut rows = stmt
+        .query(params_from_iter(where_clause.params))
+        .map_err(|e| e.to_string())?;
+
+    let mut groups: HashMap<Option<String>, LatencySamples> = HashMap::new();
+    while let Some(row) = rows.next().map_err(|e| e.to_string())? {
+        let key: Option<String> = row.get(0).map_err(|e| e.to_string())?;
+        let status: u16 = row.get(1).map_err(|e| e.to_string())?;
+        let group = groups.entry(key).or_default();
+        group.requests += 1;
+        if !(200..400).contains(&status) {
+            group.errors += 1;
+            continue;
+        }
+        let duration: Option<i64> = row.get(2).map_err(|e| e.to_string())?;
+        let ttft: Option<i64> = row.get(3).map_err(|e| e.to_string())?;
+        let stream_duration: Option<i64> = row.get(4).map_err(|e| e.to_string())?;
+        let tokens_per_sec: Option<f64> = row.get(5).map_err(|e| e.to_string())?;
+        group.duration.extend(duration.map(|v| v as f64));
+        group.ttft.extend(ttft.map(|v| v as f64));
+        group.stream_duration.extend(stream_duration.map(|v| v as f64));
+        group.tokens_per_sec.extend(tokens_per_sec);
+    }
+
+    let mut report: Vec<LatencyStats> = groups
+        .into_iter()
+        .map(|(key, samples)| LatencyStats {
+            key,
+            requests: samples.requests,
+            errors: samples.errors,
+            duration_ms: Percentiles::from_samples(samples.duration),
+            ttft_ms: Percentiles::from_samples(samples.ttft),
+            stream_duration_ms: Percentiles::from_samples(samples.stream_duration),
+            tokens_per_sec: Percentiles::from_samples(samples.tokens_per_sec),
+        })
+        .collect();
+    // 与 aggregate_logs 排序口径一致
+    if group_by.is_time_bucket() {
+        report.sort_by(|a, b| a.key.cmp(&b.key));
     } else {
-        let mut stmt = conn.prepare(sql).map_err(|e| e.to_string())?;
-        let logs_iter = stmt.query_map(rusqlite::params![limit, offset, filter_pattern], |row| {
-            Ok(ProxyRequestLog {
-                id: row.get(0)?,
-                timestamp: row.get(1)?,
-                method: row.get(2)?,
-                url: row.get(3)?,
-                status: row.get(4)?,
-                duration: row.get(5)?,
-                model: row.get(6)?,
-                mapped_model: row.get(13).unwrap_or(None),
-                account_email: row.get(12).unwrap_or(None),
-                error: row.get(7)?,
-                request_body: None,
-                response_body: None,
-                input_tokens: row.get(10).unwrap_or(None),
-                output_tokens: row.get(11).unwrap_or(None),
-                protocol: row.get(14).unwrap_or(None),
-                client_ip: row.get(15).unwrap_or(None),
-                username: row.get(16).unwrap_or(None),
-            })
+        report.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.key.cmp(&b.key)));
+    }
+    report.truncate(query.page_size());
+    Ok(report)
+}
 
-        }).map_err(|e| e.to_string())?;
-        logs_iter.filter_map(|r| r.ok()).collect()
-    };
+const CSV_HEADER: &[&str] = &[
+    "id", "timestamp", "method", "url", "status", "duration", "model", "mapped_model",
+    "account_email", "username", "client_ip", "protocol", "input_tokens", "output_tokens",
+    "cached_tokens", "thinking_tokens", "tool_tokens", "ttft_ms", "stream_duration_ms",
+    "tokens_per_sec", "proxy_id", "error", "request_body", "response_body",
+];
 
-    Ok(logs)
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn csv_row(log: &ProxyRequestLog) -> String {
+    let opt = |v: &Option<String>| csv_field(v.as_deref().unwrap_or(""));
+    let num = |v: Option<u32>| v.map(|n| n.to_string()).unwrap_or_default();
+    [
+        csv_field(&log.id),
+        log.timestamp.to_string(),
+        csv_field(&log.method),
+        csv_field(&log.url),
+        log.status.to_string(),
+        log.duration.to_string(),
+        opt(&log.model),
+        opt(&log.mapped_model),
+        opt(&log.account_email),
+        opt(&log.username),
+        opt(&log.client_ip),
+        opt(&log.protocol),
+        num(log.input_tokens),
+        num(log.output_tokens),
+        num(log.cached_tokens),
+        num(log.thinking_tokens),
+        num(log.tool_tokens),
+        log.ttft_ms.map(|n| n.to_string()).unwrap_or_default(),
+        log.stream_duration_ms.map(|n| n.to_string()).unwrap_or_default(),
+        log.tokens_per_sec.map(|n| format!("{:.2}", n)).unwrap_or_default(),
+        opt(&log.proxy_id),
+        opt(&log.error),
+        opt(&log.request_body),
+        opt(&log.response_body),
+    ]
+    .join(",")
+}
+
+/// 按检索条件导出 (含请求/响应体)，逐行写出避免整表载入内存，返回导出条数
+pub fn export_logs(query: &LogQuery, format: LogExportFormat, out: &mut dyn Write) -> Result<usize, String> {
+    let conn = connect_db()?;
+    export_logs_with_conn(&conn, query, format, out)
+}
+
+fn export_logs_with_conn(
+    conn: &Connection,
+    query: &LogQuery,
+    format: LogExportFormat,
+    out: &mut dyn Write,
+) -> Result<usize, String> {
+    let where_clause = query.build_where(false)?;
+    let sql = format!(
+        "SELECT {} FROM request_logs {} ORDER BY timestamp DESC, id DESC",
+        FULL_COLUMNS, where_clause.sql
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map(params_from_iter(where_clause.params), row_to_log)
+        .map_err(|e| e.to_string())?;
+
+    let write_err = |e: std::io::Error| format!("Failed to write export: {}", e);
+    if format == LogExportFormat::Csv {
+        writeln!(out, "{}", CSV_HEADER.join(",")).map_err(write_err)?;
+    }
+    let mut count = 0;
+    for row in rows {
+        let log = row.map_err(|e| e.to_string())?;
+        let line = match format {
+            LogExportFormat::Jsonl => serde_json::to_string(&log).map_err(|e| e.to_string())?,
+            LogExportFormat::Csv => csv_row(&log),
+        };
+        writeln!(out, "{}", line).map_err(write_err)?;
+        count += 1;
+    }
+    out.flush().map_err(write_err)?;
+    Ok(count)
 }
 
 /// Get all logs with full details for export
 pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
     let conn = connect_db()?;
 
-    let mut stmt = conn.prepare(
-        "SELECT id, timestamp, method, url, status, duration, model, error,
-                request_body, response_body, input_tokens, output_tokens,
-                account_email, mapped_model, protocol, client_ip, username
-         FROM request_logs
-         ORDER BY timestamp DESC"
-    ).map_err(|e| e.to_string())?;
+    let sql = format!("SELECT {} FROM request_logs ORDER BY timestamp DESC", FULL_COLUMNS);
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
 
-    let logs_iter = stmt.query_map([], |row| {
-        Ok(ProxyRequestLog {
-            id: row.get(0)?,
-            timestamp: row.get(1)?,
-            method: row.get(2)?,
-            url: row.get(3)?,
-            status: row.get(4)?,
-            duration: row.get(5)?,
-            model: row.get(6)?,
-            mapped_model: row.get(13).unwrap_or(None),
-            account_email: row.get(12).unwrap_or(None),
-            error: row.get(7)?,
-            request_body: row.get(8).unwrap_or(None),
-            response_body: row.get(9).unwrap_or(None),
-            input_tokens: row.get(10).unwrap_or(None),
-            output_tokens: row.get(11).unwrap_or(None),
-            protocol: row.get(14).unwrap_or(None),
-            client_ip: row.get(15).unwrap_or(None),
-            username: row.get(16).unwrap_or(None),
-        })
-
-    }).map_err(|e| e.to_string())?;
+    let logs_iter = stmt.query_map([], row_to_log).map_err(|e| e.to_string())?;
 
     let mut logs = Vec::new();
     for log in logs_iter {
@@ -438,7 +629,7 @@ pub fn get_all_logs_for_export() -> Result<Vec<ProxyRequestLog>, String> {
 
 // ... existing code ...
 
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, schemars::JsonSchema)]
 pub struct IpTokenStats {
     pub client_ip: String,
     pub total_tokens: i64,
@@ -503,3 +694,173 @@ pub fn get_token_usage_by_ip(limit: usize, hours: i64) -> Result<Vec<IpTokenStat
     Ok(stats)
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log(id: &str, ts: i64, status: u16, email: &str, model: &str, error: Option<&str>) -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: id.to_string(),
+            timestamp: ts,
+            method: "POST".to_string(),
+            url: "/v1/messages".to_string(),
+            status,
+            duration: 100 + ts as u64 % 1000,
+            model: Some(model.to_string()),
+            mapped_model: Some(format!("{}-mapped", model)),
+            account_email: Some(email.to_string()),
+            client_ip: Some("10.0.0.1".to_string()),
+            error: error.map(str::to_string),
+            request_body: Some(format!("{{\"prompt\": \"hello from {}\"}}", id)),
+            response_body: None,
+            input_tokens: Some(10),
+            output_tokens: Some(5),
+            cached_tokens: Some(4),
+            thinking_tokens: None,
+            tool_tokens: None,
+            protocol: Some("anthropic".to_string()),
+            username: Some("alice".to_string()),
+            context_compression: None,
+            ttft_ms: None,
+            stream_duration_ms: None,
+            tokens_per_sec: None,
+            proxy_id: None,
+        }
+    }
+
+    fn seeded_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        insert_log(&conn, &log("a", 1_000, 200, "one@x.com", "claude", None)).unwrap();
+        insert_log(&conn, &log("b", 2_000, 429, "one@x.com", "claude", Some("RESOURCE_EXHAUSTED quota"))).unwrap();
+        insert_log(&conn, &log("c", 3_000, 503, "two@x.com", "gemini", Some("upstream overloaded"))).unwrap();
+        insert_log(&conn, &log("d", 4_000, 200, "two@x.com", "gemini", None)).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_full_text_search_and_filters() {
+        let conn = seeded_db();
+        let q = LogQuery { text: Some("resource_exhausted".to_string()), ..Default::default() };
+        let page = search_logs_with_conn(&conn, &q).unwrap();
+        assert_eq!(page.logs.iter().map(|l| l.id.as_str()).collect::<Vec<_>>(), vec!["b"]);
+
+        // 请求体同样被索引
+        let q = LogQuery { text: Some("hello c".to_string()), ..Default::default() };
+        assert_eq!(search_logs_with_conn(&conn, &q).unwrap().total, 1);
+
+        let q = LogQuery {
+            status: Some("4xx,5xx".to_string()),
+            account_email: Some("TWO@x.com".to_string()),
+            ..Default::default()
+        };
+        let page = search_logs_with_conn(&conn, &q).unwrap();
+        assert_eq!(page.logs.len(), 1);
+        assert_eq!(page.logs[0].id, "c");
+        assert!(page.logs[0].request_body.is_none());
+    }
+
+    #[test]
+    fn test_cursor_pagination_walks_all_rows() {
+        let conn = seeded_db();
+        let mut q = LogQuery { limit: Some(3), ..Default::default() };
+        let first = search_logs_with_conn(&conn, &q).unwrap();
+        assert_eq!(first.total, 4);
+        assert_eq!(first.logs.iter().map(|l| l.id.as_str()).collect::<Vec<_>>(), vec!["d", "c", "b"]);
+
+        q.cursor = first.next_cursor.clone();
+        let second = search_logs_with_conn(&conn, &q).unwrap();
+        assert_eq!(second.logs.len(), 1);
+        assert_eq!(second.logs[0].id, "a");
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn test_aggregate_by_account_and_status_class() {
+        let conn = seeded_db();
+        let rows = aggregate_logs_with_conn(&conn, &LogQuery::default(), LogGroupBy::Account).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|r| r.requests == 2 && r.errors == 1 && r.input_tokens == 20));
+        assert!(rows.iter().all(|r| r.cached_tokens == 8 && r.thinking_tokens == 0));
+
+        let rows = aggregate_logs_with_conn(&conn, &LogQuery::default(), LogGroupBy::StatusClass).unwrap();
+        let keys: Vec<_> = rows.iter().map(|r| (r.key.clone().unwrap(), r.requests)).collect();
+        assert_eq!(keys, vec![("2xx".to_string(), 2), ("4xx".to_string(), 1), ("5xx".to_string(), 1)]);
+    }
+
+    #[test]
+    fn test_latency_report_by_proxy() {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        for i in 1..=10u64 {
+            let mut entry = log(&format!("s{}", i), i as i64, 200, "one@x.com", "claude", None);
+            entry.duration = i * 1000;
+            entry.ttft_ms = Some(i * 100);
+            entry.stream_duration_ms = Some(i * 900);
+            entry.tokens_per_sec = Some(i as f64 * 10.0);
+            entry.proxy_id = Some("proxy-a".to_string());
+            insert_log(&conn, &entry).unwrap();
+        }
+        // 失败请求只计数，不进入分位数
+        let mut failed = log("f", 99, 429, "one@x.com", "claude", Some("quota"));
+        failed.proxy_id = Some("proxy-a".to_string());
+        failed.duration = 5;
+        insert_log(&conn, &failed).unwrap();
+        // 非流式直连请求
+        insert_log(&conn, &log("n", 100, 200, "two@x.com", "gemini", None)).unwrap();
+
+        let rows = latency_report_with_conn(&conn, &LogQuery::default(), LogGroupBy::Proxy).unwrap();
+        assert_eq!(rows.len(), 2);
+        let proxy = &rows[0];
+        assert_eq!(proxy.key.as_deref(), Some("proxy-a"));
+        assert_eq!((proxy.requests, proxy.errors), (11, 1));
+        assert_eq!(proxy.duration_ms.samples, 10);
+        assert_eq!(proxy.duration_ms.p50, Some(5000.0));
+        assert_eq!(proxy.ttft_ms.p90, Some(900.0));
+        assert_eq!(proxy.ttft_ms.p99, Some(1000.0));
+        assert_eq!(proxy.tokens_per_sec.p50, Some(50.0));
+
+        let direct = &rows[1];
+        assert_eq!(direct.key, None);
+        assert_eq!(direct.duration_ms.samples, 1);
+        assert_eq!(direct.ttft_ms.samples, 0);
+        assert_eq!(direct.ttft_ms.p50, None);
+
+        let q = LogQuery { proxy_id: Some("proxy-a".to_string()), ..Default::default() };
+        let rows = latency_report_with_conn(&conn, &q, LogGroupBy::Model).unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].key.as_deref(), Some("claude"));
+    }
+
+    #[test]
+    fn test_export_uses_same_query() {
+        let conn = seeded_db();
+        let q = LogQuery { errors_only: true, ..Default::default() };
+
+        let mut jsonl = Vec::new();
+        assert_eq!(export_logs_with_conn(&conn, &q, LogExportFormat::Jsonl, &mut jsonl).unwrap(), 2);
+        let first: ProxyRequestLog = serde_json::from_str(String::from_utf8(jsonl).unwrap().lines().next().unwrap()).unwrap();
+        assert_eq!(first.id, "c");
+        assert!(first.request_body.is_some());
+        assert_eq!(first.cached_tokens, Some(4));
+
+        let mut csv = Vec::new();
+        export_logs_with_conn(&conn, &q, LogExportFormat::Csv, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        assert!(csv.starts_with("id,timestamp,method"));
+        // 含引号与逗号的字段被转义
+        assert!(csv.contains("\"{\"\"prompt\"\": \"\"hello from b\"\"}\""));
+    }
+
+    #[test]
+    fn test_fts_index_follows_deletes() {
+        let conn = seeded_db();
+        conn.execute("DELETE FROM request_logs WHERE id = 'b'", []).unwrap();
+        let q = LogQuery { text: Some("quota".to_string()), ..Default::default() };
+        assert_eq!(search_logs_with_conn(&conn, &q).unwrap().total, 0);
+
+        vacuum_and_reindex(&conn).unwrap();
+        let q = LogQuery { text: Some("overloaded".to_string()), ..Default::default() };
+        assert_eq!(search_logs_with_conn(&conn, &q).unwrap().logs[0].id, "c");
+    }
+}
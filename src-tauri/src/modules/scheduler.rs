@@ -52,6 +52,11 @@ pub fn check_cooldown(key: &str, cooldown_seconds: i64) -> bool {
 }
 
 pub fn start_scheduler(app_handle: Option<tauri::AppHandle>, proxy_state: crate::commands::proxy::ProxyServiceState) {
+    // 日志保留策略清理任务
+    crate::modules::log_retention::start_retention_task();
+    // 过期上传文件清理任务
+    crate::proxy::files::start_cleanup_task();
+
     tauri::async_runtime::spawn(async move {
         logger::log_info("Smart Warmup Scheduler started. Monitoring quota at 100%...");
         
@@ -2,11 +2,12 @@
 //! 安全监控相关的数据库操作
 
 use rusqlite::{params, Connection};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// IP 访问日志
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpAccessLog {
     pub id: String,
     pub client_ip: String,
@@ -24,7 +25,7 @@ pub struct IpAccessLog {
 }
 
 /// IP 黑名单条目
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpBlacklistEntry {
     pub id: String,
     pub ip_pattern: String,
@@ -36,7 +37,7 @@ pub struct IpBlacklistEntry {
 }
 
 /// IP 白名单条目
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpWhitelistEntry {
     pub id: String,
     pub ip_pattern: String,
@@ -56,7 +57,7 @@ pub struct IpStats {
 }
 
 /// IP 访问排行
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpRanking {
     pub client_ip: String,
     pub request_count: u64,
@@ -362,21 +363,37 @@ pub fn get_top_ips(limit: usize, hours: i64) -> Result<Vec<IpRanking>, String> {
 pub fn cleanup_old_ip_logs(days: i64) -> Result<usize, String> {
     let conn = connect_db()?;
 
-    let cutoff_timestamp = chrono::Utc::now().timestamp() - (days * 24 * 3600);
-
-    let deleted = conn
-        .execute(
-            "DELETE FROM ip_access_logs WHERE timestamp < ?1",
-            [cutoff_timestamp],
-        )
-        .map_err(|e| e.to_string())?;
+    let limits = crate::proxy::config::StoreRetention::max_age(days.max(0) as u32);
+    let deleted = crate::modules::log_retention::prune_table(
+        &conn,
+        "ip_access_logs",
+        crate::modules::log_retention::TimestampUnit::Seconds,
+        &limits,
+        chrono::Utc::now().timestamp(),
+    )?;
 
     // VACUUM to reclaim space
-    conn.execute("VACUUM", []).map_err(|e| e.to_string())?;
+    crate::modules::log_retention::vacuum(&conn)?;
 
     Ok(deleted)
 }
 
+/// 按保留策略清理 IP 访问日志，返回 (删除条数, 是否执行了 VACUUM)
+pub fn enforce_retention(
+    limits: &crate::proxy::config::StoreRetention,
+    vacuum_due: bool,
+) -> Result<(usize, bool), String> {
+    let conn = connect_db()?;
+    crate::modules::log_retention::enforce_sqlite_store(
+        &conn,
+        "ip_access_logs",
+        crate::modules::log_retention::TimestampUnit::Seconds,
+        limits,
+        vacuum_due,
+        crate::modules::log_retention::vacuum,
+    )
+}
+
 // ============================================================================
 // 黑名单操作
 // ============================================================================
@@ -2,13 +2,14 @@
 //! UserToken 数据库操作模块
 
 use rusqlite::{params, Connection, OptionalExtension};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use uuid::Uuid;
 use chrono::{Utc, Local, Timelike};
 
 /// 用户令牌结构体
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UserToken {
     pub id: String,
     pub token: String,
@@ -0,0 +1,722 @@
+//! 账号凭据保险库 (Encrypted-at-rest)
+//!
+//! 启用后，账号 JSON 中的所有凭据字段 (refresh_token / access_token / session_token / api_key 等)
+//! 以 AES-256-GCM 逐字段加密后落盘，其余字段 (邮箱、配额、禁用状态) 保持明文，
+//! 以便代理侧直接读写状态字段而无需解密。
+//!
+//! 密钥来源:
+//! - 口令: 通过 Argon2id 派生，启动时可由 `ABV_VAULT_PASSPHRASE` 自动解锁，否则需在界面/管理 API 中解锁
+//! - 环境变量: `ABV_VAULT_KEY` 直接提供 32 字节密钥 (Base64)，适用于无头/Docker 部署
+//!
+//! 加密字段格式: `vault:v1:<base64(nonce || ciphertext)>`。
+//! 明文字段在写入时自动加密，因此旧账号文件会被透明迁移。
+
+use aes_gcm::{
+    aead::{Aead, KeyInit},
+    Aes256Gcm, Nonce,
+};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::{engine::general_purpose, Engine as _};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use crate::modules::{account, logger};
+
+const VAULT_META_FILE: &str = "vault.json";
+const SEALED_PREFIX: &str = "vault:v1:";
+const VERIFIER_PLAINTEXT: &str = "antigravity-vault-check";
+const REKEY_SUFFIX: &str = "rekey";
+const NONCE_LEN: usize = 12;
+const SALT_LEN: usize = 16;
+
+/// 环境变量: Base64 编码的 32 字节密钥
+pub const ENV_VAULT_KEY: &str = "ABV_VAULT_KEY";
+/// 环境变量: 启动时自动解锁口令
+pub const ENV_VAULT_PASSPHRASE: &str = "ABV_VAULT_PASSPHRASE";
+
+/// 需要加密的凭据所在子树 (新格式 credentials, 旧格式 token)
+const CREDENTIAL_ROOTS: &[&str] = &["credentials", "token"];
+/// 需要加密的凭据字段
+const CREDENTIAL_FIELDS: &[&str] = &[
+    "access_token",
+    "refresh_token",
+    "session_token",
+    "api_key",
+    "cf_clearance",
+    "puid",
+];
+
+type VaultKey = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum VaultKeySource {
+    Passphrase,
+    Env,
+}
+
+/// Argon2id 参数 (随元数据保存，便于未来调整默认值)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+/// 保险库元数据 (vault.json)，不包含任何密钥材料
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultMeta {
+    version: u32,
+    key_source: VaultKeySource,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kdf: Option<KdfParams>,
+    /// 用当前密钥加密的固定串，用于校验口令/密钥是否正确
+    verifier: String,
+    created_at: i64,
+}
+
+#[derive(Default)]
+struct VaultState {
+    meta: Option<VaultMeta>,
+    key: Option<VaultKey>,
+}
+
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct VaultStatus {
+    pub enabled: bool,
+    pub unlocked: bool,
+    pub key_source: Option<VaultKeySource>,
+    pub env_key_present: bool,
+}
+
+static VAULT_STATE: Lazy<RwLock<VaultState>> = Lazy::new(|| RwLock::new(load_initial_state()));
+
+fn get_meta_path() -> Result<PathBuf, String> {
+    Ok(account::get_data_dir()?.join(VAULT_META_FILE))
+}
+
+fn load_meta() -> Result<Option<VaultMeta>, String> {
+    let path = get_meta_path()?;
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content =
+        fs::read_to_string(&path).map_err(|e| format!("failed_to_read_vault_meta: {}", e))?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .map_err(|e| format!("failed_to_parse_vault_meta: {}", e))
+}
+
+fn save_meta(meta: &VaultMeta) -> Result<(), String> {
+    let path = get_meta_path()?;
+    let temp_path = path.with_extension("json.tmp");
+    let content = serde_json::to_string_pretty(meta)
+        .map_err(|e| format!("failed_to_serialize_vault_meta: {}", e))?;
+    fs::write(&temp_path, content).map_err(|e| format!("failed_to_write_vault_meta: {}", e))?;
+    fs::rename(temp_path, path).map_err(|e| format!("failed_to_replace_vault_meta: {}", e))
+}
+
+fn load_initial_state() -> VaultState {
+    let meta = match load_meta() {
+        Ok(meta) => meta,
+        Err(e) => {
+            logger::log_error(&format!("[Vault] {}", e));
+            None
+        }
+    };
+    let Some(meta) = meta else {
+        return VaultState::default();
+    };
+
+    // 尝试通过环境变量自动解锁
+    let key = match meta.key_source {
+        VaultKeySource::Env => env_key().and_then(|r| r.map_err(|e| logger::log_error(&e)).ok()),
+        VaultKeySource::Passphrase => std::env::var(ENV_VAULT_PASSPHRASE)
+            .ok()
+            .filter(|p| !p.is_empty())
+            .and_then(|p| derive_key_for(&meta, &p).map_err(|e| logger::log_error(&e)).ok()),
+    };
+    let key = key.filter(|k| {
+        let valid = verify_key(&meta, k);
+        if !valid {
+            logger::log_error("[Vault] Key from environment does not match vault, staying locked");
+        }
+        valid
+    });
+
+    VaultState {
+        meta: Some(meta),
+        key,
+    }
+}
+
+/// 读取 `ABV_VAULT_KEY`，未设置时返回 None
+fn env_key() -> Option<Result<VaultKey, String>> {
+    let raw = std::env::var(ENV_VAULT_KEY).ok()?;
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return None;
+    }
+    Some(parse_env_key(raw))
+}
+
+fn parse_env_key(raw: &str) -> Result<VaultKey, String> {
+    let bytes = general_purpose::STANDARD
+        .decode(raw)
+        .map_err(|e| format!("{} is not valid base64: {}", ENV_VAULT_KEY, e))?;
+    bytes
+        .try_into()
+        .map_err(|_| format!("{} must decode to exactly 32 bytes", ENV_VAULT_KEY))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], params: &KdfParams) -> Result<VaultKey, String> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|e| format!("invalid_kdf_params: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("key_derivation_failed: {}", e))?;
+    Ok(key)
+}
+
+fn derive_key_for(meta: &VaultMeta, passphrase: &str) -> Result<VaultKey, String> {
+    let salt = meta
+        .salt
+        .as_deref()
+        .ok_or("vault_meta_missing_salt")
+        .and_then(|s| general_purpose::STANDARD.decode(s).map_err(|_| "vault_meta_invalid_salt"))?;
+    derive_key(passphrase, &salt, &meta.kdf.clone().unwrap_or_default())
+}
+
+fn verify_key(meta: &VaultMeta, key: &VaultKey) -> bool {
+    open_str(&meta.verifier, key).is_ok_and(|v| v == VERIFIER_PLAINTEXT)
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut buf = [0u8; N];
+    rand::thread_rng().fill_bytes(&mut buf);
+    buf
+}
+
+/// 根据口令 (或环境变量密钥) 生成新的元数据与密钥
+fn new_meta(passphrase: Option<&str>) -> Result<(VaultMeta, VaultKey), String> {
+    let (key_source, salt, kdf, key) = match passphrase {
+        Some(p) => {
+            if p.is_empty() {
+                return Err("vault_passphrase_empty".to_string());
+            }
+            let salt = random_bytes::<SALT_LEN>();
+            let kdf = KdfParams::default();
+            let key = derive_key(p, &salt, &kdf)?;
+            (
+                VaultKeySource::Passphrase,
+                Some(general_purpose::STANDARD.encode(salt)),
+                Some(kdf),
+                key,
+            )
+        }
+        None => {
+            let key = env_key().ok_or_else(|| format!("{} is not set", ENV_VAULT_KEY))??;
+            (VaultKeySource::Env, None, None, key)
+        }
+    };
+
+    let meta = VaultMeta {
+        version: 1,
+        key_source,
+        salt,
+        kdf,
+        verifier: seal_str(VERIFIER_PLAINTEXT, &key)?,
+        created_at: chrono::Utc::now().timestamp(),
+    };
+    Ok((meta, key))
+}
+
+fn seal_str(plaintext: &str, key: &VaultKey) -> Result<String, String> {
+    let cipher = Aes256Gcm::new(key.into());
+    let nonce = random_bytes::<NONCE_LEN>();
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_bytes())
+        .map_err(|e| format!("Encryption failed: {}", e))?;
+
+    let mut payload = nonce.to_vec();
+    payload.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", SEALED_PREFIX, general_purpose::STANDARD.encode(payload)))
+}
+
+fn open_str(sealed: &str, key: &VaultKey) -> Result<String, String> {
+    let encoded = sealed
+        .strip_prefix(SEALED_PREFIX)
+        .ok_or("value is not sealed")?;
+    let payload = general_purpose::STANDARD
+        .decode(encoded)
+        .map_err(|e| format!("Base64 decode failed: {}", e))?;
+    if payload.len() <= NONCE_LEN {
+        return Err("sealed value too short".to_string());
+    }
+
+    let (nonce, ciphertext) = payload.split_at(NONCE_LEN);
+    let plaintext = Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| "Decryption failed (wrong vault key?)".to_string())?;
+    String::from_utf8(plaintext).map_err(|e| format!("UTF-8 conversion failed: {}", e))
+}
+
+fn is_sealed(value: &str) -> bool {
+    value.starts_with(SEALED_PREFIX)
+}
+
+/// 遍历账号 JSON 中的所有凭据字符串字段
+fn for_each_credential(
+    account: &mut Value,
+    f: &mut impl FnMut(&mut String) -> Result<(), String>,
+) -> Result<(), String> {
+    fn walk(
+        node: &mut Value,
+        f: &mut impl FnMut(&mut String) -> Result<(), String>,
+    ) -> Result<(), String> {
+        if let Some(obj) = node.as_object_mut() {
+            for (key, child) in obj.iter_mut() {
+                match child {
+                    Value::String(s) if CREDENTIAL_FIELDS.contains(&key.as_str()) => f(s)?,
+                    Value::Object(_) => walk(child, f)?,
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+
+    for root in CREDENTIAL_ROOTS {
+        if let Some(node) = account.get_mut(*root) {
+            walk(node, f)?;
+        }
+    }
+    Ok(())
+}
+
+fn seal_with(account: &mut Value, key: &VaultKey) -> Result<usize, String> {
+    let mut sealed = 0;
+    for_each_credential(account, &mut |s| {
+        if !s.is_empty() && !is_sealed(s) {
+            *s = seal_str(s, key)?;
+            sealed += 1;
+        }
+        Ok(())
+    })?;
+    Ok(sealed)
+}
+
+fn open_with(account: &mut Value, key: Option<&VaultKey>) -> Result<(), String> {
+    for_each_credential(account, &mut |s| {
+        if is_sealed(s) {
+            let key = key.ok_or("vault_locked: unlock the account vault first")?;
+            *s = open_str(s, key)?;
+        }
+        Ok(())
+    })
+}
+
+fn has_plaintext_credentials(account: &mut Value) -> bool {
+    let mut found = false;
+    let _ = for_each_credential(account, &mut |s| {
+        found |= !s.is_empty() && !is_sealed(s);
+        Ok(())
+    });
+    found
+}
+
+/// 解密账号 JSON 中的凭据字段 (明文字段原样保留)
+pub fn open_account_value(account: &mut Value) -> Result<(), String> {
+    let state = VAULT_STATE.read().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    open_with(account, state.key.as_ref())
+}
+
+/// 在保险库启用时加密账号 JSON 中的凭据字段 (已加密字段不会重复加密)
+pub fn seal_account_value(account: &mut Value) -> Result<(), String> {
+    let state = VAULT_STATE.read().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    if state.meta.is_none() {
+        return Ok(());
+    }
+    let key = state
+        .key
+        .as_ref()
+        .ok_or("vault_locked: refusing to write account credentials while the vault is locked")?;
+    seal_with(account, key).map(|_| ())
+}
+
+/// 读取账号文件并解密凭据字段
+pub fn read_account_value(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let mut account: Value =
+        serde_json::from_str(&content).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+    open_account_value(&mut account)?;
+    Ok(account)
+}
+
+/// 加密凭据字段后写入账号文件
+pub fn write_account_value(path: &Path, account: &Value) -> Result<(), String> {
+    let mut account = account.clone();
+    seal_account_value(&mut account)?;
+    let content = serde_json::to_string_pretty(&account).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| format!("写入文件失败: {}", e))
+}
+
+fn read_raw(path: &Path) -> Result<Value, String> {
+    let content = fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析 JSON 失败: {}", e))
+}
+
+fn write_raw(path: &Path, account: &Value) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(account).map_err(|e| e.to_string())?;
+    fs::write(path, content).map_err(|e| format!("写入文件失败: {}", e))
+}
+
+fn list_files_with_extension(dir: &Path, ext: &str) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("failed_to_read_accounts_dir: {}", e))?;
+    Ok(entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().and_then(|s| s.to_str()) == Some(ext))
+        .collect())
+}
+
+fn rekey_path(path: &Path) -> PathBuf {
+    path.with_extension(format!("json.{}", REKEY_SUFFIX))
+}
+
+/// 完成/清理中断的重新加密: 能用当前密钥解开的临时文件替换原文件，否则丢弃
+fn recover_interrupted_rekey(dir: &Path, key: &VaultKey) -> Result<(), String> {
+    for temp in list_files_with_extension(dir, REKEY_SUFFIX)? {
+        let target = temp.with_extension("");
+        let usable = read_raw(&temp)
+            .and_then(|mut v| open_with(&mut v, Some(key)))
+            .is_ok();
+        if usable {
+            fs::rename(&temp, &target).map_err(|e| format!("failed_to_finish_rekey: {}", e))?;
+            logger::log_info(&format!("[Vault] Finished interrupted re-key for {:?}", target));
+        } else {
+            let _ = fs::remove_file(&temp);
+        }
+    }
+    Ok(())
+}
+
+/// 将所有仍含明文凭据的账号文件加密，返回迁移的文件数
+fn migrate_accounts(key: &VaultKey) -> Result<usize, String> {
+    let dir = account::get_accounts_dir()?;
+    recover_interrupted_rekey(&dir, key)?;
+
+    let mut migrated = 0;
+    for path in list_files_with_extension(&dir, "json")? {
+        let mut value = match read_raw(&path) {
+            Ok(v) => v,
+            Err(e) => {
+                logger::log_warn(&format!("[Vault] Skipping {:?}: {}", path, e));
+                continue;
+            }
+        };
+        if !has_plaintext_credentials(&mut value) {
+            continue;
+        }
+        seal_with(&mut value, key)?;
+        write_raw(&path, &value)?;
+        migrated += 1;
+    }
+    Ok(migrated)
+}
+
+/// 启动时初始化保险库: 自动解锁并迁移明文账号文件
+pub fn init() {
+    let status = get_status();
+    if !status.enabled {
+        return;
+    }
+    if !status.unlocked {
+        logger::log_warn(&format!(
+            "[Vault] Account vault is locked. Unlock it from the UI / admin API, or set {} / {}",
+            ENV_VAULT_PASSPHRASE, ENV_VAULT_KEY
+        ));
+        return;
+    }
+
+    let key = VAULT_STATE.read().ok().and_then(|s| s.key);
+    if let Some(key) = key {
+        match migrate_accounts(&key) {
+            Ok(0) => logger::log_info("[Vault] Account vault unlocked"),
+            Ok(n) => logger::log_info(&format!("[Vault] Account vault unlocked, encrypted {} legacy account file(s)", n)),
+            Err(e) => logger::log_error(&format!("[Vault] Migration failed: {}", e)),
+        }
+    }
+}
+
+pub fn get_status() -> VaultStatus {
+    let env_key_present = std::env::var(ENV_VAULT_KEY).is_ok_and(|v| !v.trim().is_empty());
+    match VAULT_STATE.read() {
+        Ok(state) => VaultStatus {
+            enabled: state.meta.is_some(),
+            unlocked: state.key.is_some(),
+            key_source: state.meta.as_ref().map(|m| m.key_source),
+            env_key_present,
+        },
+        Err(_) => VaultStatus {
+            enabled: false,
+            unlocked: false,
+            key_source: None,
+            env_key_present,
+        },
+    }
+}
+
+/// 启用保险库。`passphrase` 为 None 时使用 `ABV_VAULT_KEY`。返回加密的账号文件数
+pub fn enable(passphrase: Option<&str>) -> Result<usize, String> {
+    let mut state = VAULT_STATE.write().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    if state.meta.is_some() {
+        return Err("vault_already_enabled".to_string());
+    }
+
+    let (meta, key) = new_meta(passphrase)?;
+    // 先落盘元数据：即使迁移中断，已加密的文件也能被正确识别
+    save_meta(&meta)?;
+    state.meta = Some(meta);
+    state.key = Some(key);
+
+    let migrated = migrate_accounts(&key)?;
+    logger::log_info(&format!("[Vault] Enabled, encrypted {} account file(s)", migrated));
+    Ok(migrated)
+}
+
+/// 使用口令解锁保险库 (环境变量密钥模式下忽略口令，直接读取 `ABV_VAULT_KEY`)
+pub fn unlock(passphrase: &str) -> Result<usize, String> {
+    let mut state = VAULT_STATE.write().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    let meta = state.meta.clone().ok_or("vault_not_enabled")?;
+
+    let key = match meta.key_source {
+        VaultKeySource::Passphrase => derive_key_for(&meta, passphrase)?,
+        VaultKeySource::Env => env_key().ok_or_else(|| format!("{} is not set", ENV_VAULT_KEY))??,
+    };
+    if !verify_key(&meta, &key) {
+        return Err("invalid_vault_passphrase".to_string());
+    }
+    state.key = Some(key);
+
+    migrate_accounts(&key)
+}
+
+/// 使用新口令 (或 `ABV_VAULT_KEY`) 重新加密所有账号文件，返回处理的文件数
+pub fn rekey(new_passphrase: Option<&str>) -> Result<usize, String> {
+    let mut state = VAULT_STATE.write().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    if state.meta.is_none() {
+        return Err("vault_not_enabled".to_string());
+    }
+    let old_key = state.key.ok_or("vault_locked: unlock the account vault first")?;
+    let (meta, new_key) = new_meta(new_passphrase)?;
+
+    // 1. 全部重新加密到临时文件 (任何一个失败都不会影响现有数据)
+    let dir = account::get_accounts_dir()?;
+    let files = list_files_with_extension(&dir, "json")?;
+    let mut staged = Vec::with_capacity(files.len());
+    for path in files {
+        let mut value = read_raw(&path)?;
+        open_with(&mut value, Some(&old_key)).map_err(|e| format!("{:?}: {}", path, e))?;
+        seal_with(&mut value, &new_key)?;
+        let temp = rekey_path(&path);
+        write_raw(&temp, &value)?;
+        staged.push((temp, path));
+    }
+
+    // 2. 切换元数据，再替换账号文件 (中断后由 recover_interrupted_rekey 收尾)
+    save_meta(&meta)?;
+    state.meta = Some(meta);
+    state.key = Some(new_key);
+    for (temp, path) in &staged {
+        fs::rename(temp, path).map_err(|e| format!("failed_to_finish_rekey: {}", e))?;
+    }
+
+    logger::log_info(&format!("[Vault] Re-keyed {} account file(s)", staged.len()));
+    Ok(staged.len())
+}
+
+/// 关闭保险库并将所有凭据还原为明文，返回处理的文件数
+pub fn disable() -> Result<usize, String> {
+    let mut state = VAULT_STATE.write().map_err(|e| format!("vault_lock_poisoned: {}", e))?;
+    if state.meta.is_none() {
+        return Err("vault_not_enabled".to_string());
+    }
+    let key = state.key.ok_or("vault_locked: unlock the account vault first")?;
+
+    let dir = account::get_accounts_dir()?;
+    recover_interrupted_rekey(&dir, &key)?;
+    let files = list_files_with_extension(&dir, "json")?;
+    for path in &files {
+        let mut value = read_raw(path)?;
+        open_with(&mut value, Some(&key))?;
+        write_raw(path, &value)?;
+    }
+
+    fs::remove_file(get_meta_path()?).map_err(|e| format!("failed_to_remove_vault_meta: {}", e))?;
+    *state = VaultState::default();
+
+    logger::log_info(&format!("[Vault] Disabled, decrypted {} account file(s)", files.len()));
+    Ok(files.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const KEY: VaultKey = [7u8; 32];
+
+    fn sample_account() -> Value {
+        json!({
+            "id": "acc-1",
+            "email": "user@example.com",
+            "credentials": {
+                "type": "google",
+                "token": {
+                    "access_token": "ya29.access",
+                    "refresh_token": "1//refresh",
+                    "expires_in": 3600,
+                    "project_id": "proj-1"
+                }
+            },
+            "disabled": false
+        })
+    }
+
+    #[test]
+    fn test_seal_and_open_roundtrip() {
+        let mut account = sample_account();
+        assert_eq!(seal_with(&mut account, &KEY).unwrap(), 2);
+
+        let token = &account["credentials"]["token"];
+        assert!(token["refresh_token"].as_str().unwrap().starts_with(SEALED_PREFIX));
+        assert!(token["access_token"].as_str().unwrap().starts_with(SEALED_PREFIX));
+        // 非凭据字段保持明文
+        assert_eq!(token["project_id"], "proj-1");
+        assert_eq!(account["email"], "user@example.com");
+
+        open_with(&mut account, Some(&KEY)).unwrap();
+        assert_eq!(account, sample_account());
+    }
+
+    #[test]
+    fn test_seal_is_idempotent() {
+        let mut account = sample_account();
+        seal_with(&mut account, &KEY).unwrap();
+        let once = account.clone();
+        assert_eq!(seal_with(&mut account, &KEY).unwrap(), 0);
+        assert_eq!(account, once);
+    }
+
+    #[test]
+    fn test_open_requires_key_only_for_sealed_fields() {
+        let mut plain = sample_account();
+        open_with(&mut plain, None).unwrap();
+
+        let mut sealed = sample_account();
+        seal_with(&mut sealed, &KEY).unwrap();
+        let err = open_with(&mut sealed, None).unwrap_err();
+        assert!(err.starts_with("vault_locked"));
+    }
+
+    #[test]
+    fn test_wrong_key_rejected() {
+        let mut account = sample_account();
+        seal_with(&mut account, &KEY).unwrap();
+        assert!(open_with(&mut account, Some(&[8u8; 32])).is_err());
+    }
+
+    #[test]
+    fn test_legacy_and_openai_layouts() {
+        let mut legacy = json!({ "token": { "refresh_token": "rt", "access_token": "" } });
+        assert_eq!(seal_with(&mut legacy, &KEY).unwrap(), 1);
+        assert_eq!(legacy["token"]["access_token"], "");
+
+        let mut openai = json!({
+            "credentials": {
+                "type": "open_a_i_web",
+                "access_token": "at",
+                "session_token": "st",
+                "cf_clearance": "cf",
+                "expires_at": 1
+            }
+        });
+        assert_eq!(seal_with(&mut openai, &KEY).unwrap(), 3);
+        assert!(!has_plaintext_credentials(&mut openai));
+    }
+
+    #[test]
+    fn test_passphrase_derivation_and_verifier() {
+        let salt = [1u8; SALT_LEN];
+        let kdf = KdfParams::default();
+        let key = derive_key("correct horse", &salt, &kdf).unwrap();
+        assert_eq!(key, derive_key("correct horse", &salt, &kdf).unwrap());
+        assert_ne!(key, derive_key("wrong horse", &salt, &kdf).unwrap());
+
+        let meta = VaultMeta {
+            version: 1,
+            key_source: VaultKeySource::Passphrase,
+            salt: Some(general_purpose::STANDARD.encode(salt)),
+            kdf: Some(kdf),
+            verifier: seal_str(VERIFIER_PLAINTEXT, &key).unwrap(),
+            created_at: 0,
+        };
+        assert!(verify_key(&meta, &derive_key_for(&meta, "correct horse").unwrap()));
+        assert!(!verify_key(&meta, &derive_key_for(&meta, "wrong horse").unwrap()));
+    }
+
+    #[test]
+    fn test_parse_env_key() {
+        let encoded = general_purpose::STANDARD.encode([9u8; 32]);
+        assert_eq!(parse_env_key(&encoded).unwrap(), [9u8; 32]);
+        assert!(parse_env_key(&general_purpose::STANDARD.encode([9u8; 16])).is_err());
+        assert!(parse_env_key("not base64!").is_err());
+    }
+
+    #[test]
+    fn test_recover_interrupted_rekey() {
+        let dir = std::env::temp_dir().join(format!("abv-vault-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+
+        // 可用新密钥解开的临时文件 -> 替换原文件
+        let mut done = sample_account();
+        seal_with(&mut done, &KEY).unwrap();
+        write_raw(&dir.join("a.json"), &json!({})).unwrap();
+        write_raw(&dir.join("a.json.rekey"), &done).unwrap();
+
+        // 旧密钥残留的临时文件 -> 丢弃
+        let mut stale = sample_account();
+        seal_with(&mut stale, &[8u8; 32]).unwrap();
+        write_raw(&dir.join("b.json"), &json!({})).unwrap();
+        write_raw(&dir.join("b.json.rekey"), &stale).unwrap();
+
+        recover_interrupted_rekey(&dir, &KEY).unwrap();
+
+        assert_eq!(read_raw(&dir.join("a.json")).unwrap(), done);
+        assert_eq!(read_raw(&dir.join("b.json")).unwrap(), json!({}));
+        assert!(!dir.join("a.json.rekey").exists());
+        assert!(!dir.join("b.json.rekey").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,458 @@
+// 长音频分片 - 将超长录音切成带重叠的片段，以便并行转录后再拼接
+//
+// 只处理无需解码即可安全切分的格式:
+// - MP3: 按帧边界切分 (每帧自带同步头，可独立解码，VBR 也能精确计时)
+// - WAV: 按 block_align 对齐切分 PCM 数据，并为每段重写 RIFF 头
+
+/// 分片参数
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkingOptions {
+    /// 单个分片最大字节数 (需低于 Inline Data 限制)
+    pub max_bytes: usize,
+    /// 单个分片最大时长 (秒)
+    pub max_secs: f64,
+    /// 相邻分片之间的重叠时长 (秒)，避免在句子中间切断
+    pub overlap_secs: f64,
+}
+
+impl Default for ChunkingOptions {
+    fn default() -> Self {
+        Self {
+            max_bytes: 12 * 1024 * 1024,
+            max_secs: 600.0,
+            overlap_secs: 5.0,
+        }
+    }
+}
+
+/// 切分后的音频片段
+#[derive(Debug, Clone)]
+pub struct AudioChunk {
+    pub index: usize,
+    pub data: Vec<u8>,
+    /// 片段在原始录音中的起始时间 (秒)
+    pub start_secs: f64,
+    /// 片段时长 (秒)，无法探测时为 None
+    pub duration_secs: Option<f64>,
+}
+
+/// 可独立切分的最小单元 (MP3 帧 / WAV 数据块)
+#[derive(Debug, Clone, Copy)]
+struct Unit {
+    offset: usize,
+    len: usize,
+    secs: f64,
+}
+
+/// 是否支持自动分片
+pub fn supports_chunking(mime_type: &str) -> bool {
+    matches!(mime_type, "audio/mp3" | "audio/mpeg" | "audio/wav")
+}
+
+/// 探测音频总时长 (秒)
+pub fn probe_duration(data: &[u8], mime_type: &str) -> Option<f64> {
+    match mime_type {
+        "audio/mp3" | "audio/mpeg" => {
+            let frames = scan_mp3_frames(data);
+            if frames.is_empty() {
+                None
+            } else {
+                Some(frames.iter().map(|f| f.secs).sum())
+            }
+        }
+        "audio/wav" => parse_wav(data).map(|wav| wav.data_len as f64 / wav.byte_rate as f64),
+        _ => None,
+    }
+}
+
+/// 将音频切分为带重叠的片段
+///
+/// 若整段音频已满足限制，则返回包含原始数据的单个片段。
+pub fn split_audio(
+    data: &[u8],
+    mime_type: &str,
+    opts: &ChunkingOptions,
+) -> Result<Vec<AudioChunk>, String> {
+    match mime_type {
+        "audio/mp3" | "audio/mpeg" => split_mp3(data, opts),
+        "audio/wav" => split_wav(data, opts),
+        _ => Err(format!("不支持自动分片的音频格式: {}", mime_type)),
+    }
+}
+
+fn split_mp3(data: &[u8], opts: &ChunkingOptions) -> Result<Vec<AudioChunk>, String> {
+    let frames = scan_mp3_frames(data);
+    if frames.is_empty() {
+        return Err("无法识别 MP3 帧结构".to_string());
+    }
+
+    let ranges = plan_chunks(&frames, opts);
+    let starts = cumulative_secs(&frames);
+
+    if ranges.len() == 1 {
+        return Ok(vec![AudioChunk {
+            index: 0,
+            data: data.to_vec(),
+            start_secs: 0.0,
+            duration_secs: Some(starts[frames.len()]),
+        }]);
+    }
+
+    Ok(ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, (from, to))| {
+            let first = frames[from];
+            let last = frames[to - 1];
+            AudioChunk {
+                index,
+                data: data[first.offset..last.offset + last.len].to_vec(),
+                start_secs: starts[from],
+                duration_secs: Some(starts[to] - starts[from]),
+            }
+        })
+        .collect())
+}
+
+fn split_wav(data: &[u8], opts: &ChunkingOptions) -> Result<Vec<AudioChunk>, String> {
+    let wav = parse_wav(data).ok_or("无法解析 WAV 文件头")?;
+    let block_align = wav.block_align.max(1);
+
+    // 以 1 秒 (按 block_align 对齐) 作为切分单元
+    let unit_len = ((wav.byte_rate / block_align).max(1) * block_align) as usize;
+    let mut units = Vec::new();
+    let mut offset = wav.data_offset;
+    let data_end = wav.data_offset + wav.data_len;
+    while offset < data_end {
+        let len = unit_len.min(data_end - offset);
+        units.push(Unit {
+            offset,
+            len,
+            secs: len as f64 / wav.byte_rate as f64,
+        });
+        offset += len;
+    }
+    if units.is_empty() {
+        return Err("WAV 文件不包含音频数据".to_string());
+    }
+
+    let ranges = plan_chunks(&units, opts);
+    let starts = cumulative_secs(&units);
+
+    if ranges.len() == 1 {
+        return Ok(vec![AudioChunk {
+            index: 0,
+            data: data.to_vec(),
+            start_secs: 0.0,
+            duration_secs: Some(starts[units.len()]),
+        }]);
+    }
+
+    Ok(ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, (from, to))| {
+            let first = units[from];
+            let last = units[to - 1];
+            let pcm = &data[first.offset..last.offset + last.len];
+            AudioChunk {
+                index,
+                data: build_wav(wav.fmt, pcm),
+                start_secs: starts[from],
+                duration_secs: Some(starts[to] - starts[from]),
+            }
+        })
+        .collect())
+}
+
+/// 规划分片范围 (单元下标的左闭右开区间)
+fn plan_chunks(units: &[Unit], opts: &ChunkingOptions) -> Vec<(usize, usize)> {
+    let mut ranges = Vec::new();
+    let mut from = 0;
+
+    while from < units.len() {
+        let mut to = from;
+        let mut bytes = 0usize;
+        let mut secs = 0.0;
+        while to < units.len() {
+            let unit = units[to];
+            // 至少放入一个单元，避免死循环
+            if to > from && (bytes + unit.len > opts.max_bytes || secs + unit.secs > opts.max_secs) {
+                break;
+            }
+            bytes += unit.len;
+            secs += unit.secs;
+            to += 1;
+        }
+        ranges.push((from, to));
+
+        if to >= units.len() {
+            break;
+        }
+
+        // 回退若干单元形成重叠，但必须保证向前推进
+        let mut next = to;
+        let mut overlap = 0.0;
+        while next > from + 1 && overlap < opts.overlap_secs {
+            next -= 1;
+            overlap += units[next].secs;
+        }
+        from = next;
+    }
+
+    ranges
+}
+
+/// 每个单元的起始时间，末尾附加总时长
+fn cumulative_secs(units: &[Unit]) -> Vec<f64> {
+    let mut starts = Vec::with_capacity(units.len() + 1);
+    let mut acc = 0.0;
+    for unit in units {
+        starts.push(acc);
+        acc += unit.secs;
+    }
+    starts.push(acc);
+    starts
+}
+
+// ===== MP3 =====
+
+const MP3_BITRATES_V1_L3: [u32; 15] = [
+    0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320,
+];
+const MP3_BITRATES_V2_L3: [u32; 15] = [
+    0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160,
+];
+
+/// 解析 MPEG Layer III 帧头，返回 (帧长度, 帧时长)
+fn parse_mp3_frame_header(header: &[u8]) -> Option<(usize, f64)> {
+    if header.len() < 4 || header[0] != 0xFF || header[1] & 0xE0 != 0xE0 {
+        return None;
+    }
+
+    let version = (header[1] >> 3) & 0x03; // 0 = MPEG2.5, 2 = MPEG2, 3 = MPEG1
+    let layer = (header[1] >> 1) & 0x03; // 1 = Layer III
+    if version == 1 || layer != 1 {
+        return None;
+    }
+
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0x03) as usize;
+    let padding = ((header[2] >> 1) & 0x01) as usize;
+    if bitrate_index == 0 || bitrate_index >= 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let (bitrate_kbps, sample_rate, samples) = match version {
+        3 => (
+            MP3_BITRATES_V1_L3[bitrate_index],
+            [44100, 48000, 32000][sample_rate_index],
+            1152,
+        ),
+        2 => (
+            MP3_BITRATES_V2_L3[bitrate_index],
+            [22050, 24000, 16000][sample_rate_index],
+            576,
+        ),
+        _ => (
+            MP3_BITRATES_V2_L3[bitrate_index],
+            [11025, 12000, 8000][sample_rate_index],
+            576,
+        ),
+    };
+
+    let coefficient = if version == 3 { 144_000 } else { 72_000 };
+    let frame_len = (coefficient * bitrate_kbps / sample_rate) as usize + padding;
+    Some((frame_len, samples as f64 / sample_rate as f64))
+}
+
+/// 跳过 ID3v2 标签
+fn id3v2_len(data: &[u8]) -> usize {
+    if data.len() < 10 || &data[0..3] != b"ID3" {
+        return 0;
+    }
+    let size = data[6..10]
+        .iter()
+        .fold(0usize, |acc, b| (acc << 7) | (*b as usize & 0x7F));
+    let footer = if data[5] & 0x10 != 0 { 10 } else { 0 };
+    (10 + size + footer).min(data.len())
+}
+
+fn scan_mp3_frames(data: &[u8]) -> Vec<Unit> {
+    let mut frames = Vec::new();
+    let mut pos = id3v2_len(data);
+
+    while pos + 4 <= data.len() {
+        match parse_mp3_frame_header(&data[pos..pos + 4]) {
+            Some((len, secs)) if len > 4 && pos + len <= data.len() => {
+                frames.push(Unit {
+                    offset: pos,
+                    len,
+                    secs,
+                });
+                pos += len;
+            }
+            // 非帧数据 (标签/垃圾字节)，逐字节重新同步
+            _ => pos += 1,
+        }
+    }
+
+    frames
+}
+
+// ===== WAV =====
+
+struct WavInfo<'a> {
+    fmt: &'a [u8],
+    byte_rate: u32,
+    block_align: u32,
+    data_offset: usize,
+    data_len: usize,
+}
+
+fn parse_wav(data: &[u8]) -> Option<WavInfo<'_>> {
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return None;
+    }
+
+    let mut fmt: Option<&[u8]> = None;
+    let mut pos = 12;
+    while pos + 8 <= data.len() {
+        let id = &data[pos..pos + 4];
+        let size = u32::from_le_bytes(data[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let body = pos + 8;
+
+        if id == b"fmt " {
+            fmt = Some(data.get(body..body + size)?);
+        } else if id == b"data" {
+            let fmt = fmt?;
+            if fmt.len() < 16 {
+                return None;
+            }
+            let byte_rate = u32::from_le_bytes(fmt[8..12].try_into().ok()?);
+            let block_align = u16::from_le_bytes(fmt[12..14].try_into().ok()?) as u32;
+            if byte_rate == 0 {
+                return None;
+            }
+            // 部分录音软件写入的 data 长度不可靠，以实际文件长度为准
+            let data_len = size.min(data.len() - body);
+            return Some(WavInfo {
+                fmt,
+                byte_rate,
+                block_align,
+                data_offset: body,
+                data_len,
+            });
+        }
+
+        // RIFF 子块按偶数字节对齐
+        pos = body + size + (size & 1);
+    }
+
+    None
+}
+
+fn build_wav(fmt: &[u8], pcm: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(20 + fmt.len() + 8 + pcm.len());
+    let riff_len = 4 + 8 + fmt.len() + 8 + pcm.len();
+    out.extend_from_slice(b"RIFF");
+    out.extend_from_slice(&(riff_len as u32).to_le_bytes());
+    out.extend_from_slice(b"WAVE");
+    out.extend_from_slice(b"fmt ");
+    out.extend_from_slice(&(fmt.len() as u32).to_le_bytes());
+    out.extend_from_slice(fmt);
+    out.extend_from_slice(b"data");
+    out.extend_from_slice(&(pcm.len() as u32).to_le_bytes());
+    out.extend_from_slice(pcm);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 生成 16-bit 单声道 PCM WAV
+    fn make_wav(sample_rate: u32, secs: u32) -> Vec<u8> {
+        let mut fmt = Vec::new();
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        fmt.extend_from_slice(&1u16.to_le_bytes()); // mono
+        fmt.extend_from_slice(&sample_rate.to_le_bytes());
+        fmt.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+        fmt.extend_from_slice(&2u16.to_le_bytes());
+        fmt.extend_from_slice(&16u16.to_le_bytes());
+        build_wav(&fmt, &vec![0u8; (sample_rate * 2 * secs) as usize])
+    }
+
+    /// 生成 MPEG1 Layer III 128kbps 44.1kHz 的空帧序列
+    fn make_mp3(frames: usize) -> Vec<u8> {
+        let mut out = Vec::new();
+        for _ in 0..frames {
+            let mut frame = vec![0u8; 417];
+            frame[..4].copy_from_slice(&[0xFF, 0xFB, 0x90, 0x00]);
+            out.extend_from_slice(&frame);
+        }
+        out
+    }
+
+    #[test]
+    fn test_wav_duration_and_no_split_when_short() {
+        let wav = make_wav(8000, 10);
+        assert_eq!(probe_duration(&wav, "audio/wav"), Some(10.0));
+
+        let chunks = split_audio(&wav, "audio/wav", &ChunkingOptions::default()).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].data, wav);
+    }
+
+    #[test]
+    fn test_wav_split_with_overlap() {
+        let wav = make_wav(8000, 100);
+        let opts = ChunkingOptions {
+            max_bytes: usize::MAX,
+            max_secs: 40.0,
+            overlap_secs: 5.0,
+        };
+        let chunks = split_audio(&wav, "audio/wav", &opts).unwrap();
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[0].start_secs, 0.0);
+        assert_eq!(chunks[1].start_secs, 35.0);
+        assert_eq!(chunks[2].start_secs, 70.0);
+        assert_eq!(chunks[2].duration_secs, Some(30.0));
+
+        // 每个分片都是合法的 WAV
+        for chunk in &chunks {
+            let secs = probe_duration(&chunk.data, "audio/wav").unwrap();
+            assert_eq!(Some(secs), chunk.duration_secs);
+        }
+    }
+
+    #[test]
+    fn test_mp3_frames_and_split() {
+        let mut mp3 = b"ID3\x03\x00\x00\x00\x00\x00\x0A".to_vec();
+        mp3.extend_from_slice(&[0u8; 10]);
+        mp3.extend_from_slice(&make_mp3(1000));
+
+        let duration = probe_duration(&mp3, "audio/mp3").unwrap();
+        assert!((duration - 1000.0 * 1152.0 / 44100.0).abs() < 1e-6);
+
+        let opts = ChunkingOptions {
+            max_bytes: 417 * 400,
+            max_secs: f64::MAX,
+            overlap_secs: 1.0,
+        };
+        let chunks = split_audio(&mp3, "audio/mp3", &opts).unwrap();
+        assert_eq!(chunks.len(), 3);
+        assert!(chunks.iter().all(|c| c.data.len() <= opts.max_bytes));
+        assert!(chunks.iter().all(|c| c.data[0] == 0xFF));
+        // 相邻分片存在重叠
+        let first_end = chunks[0].start_secs + chunks[0].duration_secs.unwrap();
+        assert!(chunks[1].start_secs < first_end);
+    }
+
+    #[test]
+    fn test_unsupported_format() {
+        assert!(!supports_chunking("audio/aac"));
+        assert!(split_audio(b"xxxx", "audio/aac", &ChunkingOptions::default()).is_err());
+    }
+}
@@ -1,6 +1,9 @@
 use base64::{engine::general_purpose, Engine as _};
 use std::path::Path;
 
+pub mod chunker; // 长音频分片
+pub mod transcript; // 转录结果解析与格式化
+
 pub struct AudioProcessor;
 
 impl AudioProcessor {
@@ -12,7 +15,7 @@ impl AudioProcessor {
             .ok_or("无法获取文件扩展名")?;
 
         match ext.to_lowercase().as_str() {
-            "mp3" => Ok("audio/mp3".to_string()),
+            "mp3" | "mpeg" | "mpga" => Ok("audio/mp3".to_string()),
             "wav" => Ok("audio/wav".to_string()),
             "m4a" => Ok("audio/aac".to_string()),
             "ogg" => Ok("audio/ogg".to_string()),
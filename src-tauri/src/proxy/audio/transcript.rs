@@ -0,0 +1,412 @@
+// 转录结果处理 - 解析模型输出的分段、拼接分片结果、按 response_format 渲染
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// 音频任务类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioTask {
+    Transcribe,
+    Translate,
+}
+
+impl AudioTask {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AudioTask::Transcribe => "transcribe",
+            AudioTask::Translate => "translate",
+        }
+    }
+}
+
+/// OpenAI 兼容的 response_format
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Json,
+    Text,
+    Srt,
+    Vtt,
+    VerboseJson,
+}
+
+impl ResponseFormat {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim() {
+            "" | "json" => Ok(ResponseFormat::Json),
+            "text" => Ok(ResponseFormat::Text),
+            "srt" => Ok(ResponseFormat::Srt),
+            "vtt" => Ok(ResponseFormat::Vtt),
+            "verbose_json" => Ok(ResponseFormat::VerboseJson),
+            other => Err(format!(
+                "不支持的 response_format: {} (可选: json, text, srt, vtt, verbose_json)",
+                other
+            )),
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ResponseFormat::Json | ResponseFormat::VerboseJson => "application/json",
+            ResponseFormat::Text | ResponseFormat::Srt => "text/plain; charset=utf-8",
+            ResponseFormat::Vtt => "text/vtt; charset=utf-8",
+        }
+    }
+}
+
+/// 带时间戳的转录分段 (时间单位: 秒)
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TranscriptSegment {
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// 单个分片的转录结果 (时间相对于分片起点)
+#[derive(Debug, Clone, Default)]
+pub struct ChunkTranscript {
+    pub language: Option<String>,
+    pub segments: Vec<TranscriptSegment>,
+}
+
+/// 构建发送给模型的指令
+pub fn build_instruction(task: AudioTask, language: Option<&str>, prompt: Option<&str>) -> String {
+    let mut instruction = match task {
+        AudioTask::Transcribe => "Transcribe the speech in this audio verbatim.".to_string(),
+        AudioTask::Translate => {
+            "Translate the speech in this audio into English. Output only the English translation."
+                .to_string()
+        }
+    };
+
+    if let Some(lang) = language.filter(|l| !l.trim().is_empty()) {
+        instruction.push_str(&format!(" The spoken language is '{}'.", lang.trim()));
+    }
+
+    instruction.push_str(
+        " Respond with JSON only, in the form \
+         {\"language\": \"<ISO-639-1 code of the spoken language>\", \
+         \"segments\": [{\"start\": <seconds>, \"end\": <seconds>, \"text\": \"...\"}]}. \
+         Timestamps are seconds from the beginning of this audio clip. \
+         Split segments at sentence or pause boundaries and keep each under 30 seconds.",
+    );
+
+    if let Some(p) = prompt.filter(|p| !p.trim().is_empty()) {
+        instruction.push_str(&format!(
+            "\nContext for vocabulary, spelling and style (do not transcribe it): {}",
+            p.trim()
+        ));
+    }
+
+    instruction
+}
+
+/// 解析模型输出；无法解析为分段 JSON 时，将全文作为覆盖整个分片的单一分段
+pub fn parse_chunk_transcript(raw: &str, chunk_duration: Option<f64>) -> ChunkTranscript {
+    let trimmed = strip_code_fence(raw.trim());
+
+    if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+        let language = value
+            .get("language")
+            .and_then(|l| l.as_str())
+            .map(|l| l.trim().to_lowercase())
+            .filter(|l| !l.is_empty());
+
+        let segments_value = value.get("segments").or(if value.is_array() { Some(&value) } else { None });
+        if let Some(items) = segments_value.and_then(|s| s.as_array()) {
+            let mut segments = Vec::new();
+            for item in items {
+                let text = item
+                    .get("text")
+                    .and_then(|t| t.as_str())
+                    .unwrap_or("")
+                    .trim()
+                    .to_string();
+                if text.is_empty() {
+                    continue;
+                }
+                let start = item.get("start").and_then(parse_timestamp).unwrap_or(0.0);
+                let end = item
+                    .get("end")
+                    .and_then(parse_timestamp)
+                    .unwrap_or(start)
+                    .max(start);
+                segments.push(TranscriptSegment { start, end, text });
+            }
+            return ChunkTranscript { language, segments };
+        }
+
+        if let Some(text) = value.get("text").and_then(|t| t.as_str()) {
+            return ChunkTranscript {
+                language,
+                segments: whole_chunk_segment(text, chunk_duration),
+            };
+        }
+    }
+
+    ChunkTranscript {
+        language: None,
+        segments: whole_chunk_segment(trimmed, chunk_duration),
+    }
+}
+
+fn whole_chunk_segment(text: &str, chunk_duration: Option<f64>) -> Vec<TranscriptSegment> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+    vec![TranscriptSegment {
+        start: 0.0,
+        end: chunk_duration.unwrap_or(0.0),
+        text: text.to_string(),
+    }]
+}
+
+fn strip_code_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    let rest = rest.strip_prefix("json").unwrap_or(rest);
+    rest.strip_suffix("```").unwrap_or(rest).trim()
+}
+
+/// 解析时间戳: 数字秒数，或 "HH:MM:SS.mmm" / "MM:SS" 字符串
+fn parse_timestamp(value: &Value) -> Option<f64> {
+    if let Some(n) = value.as_f64() {
+        return Some(n.max(0.0));
+    }
+    let s = value.as_str()?.trim().replace(',', ".");
+    let mut secs = 0.0;
+    for part in s.split(':') {
+        secs = secs * 60.0 + part.trim().parse::<f64>().ok()?;
+    }
+    Some(secs.max(0.0))
+}
+
+/// 拼接各分片的转录结果
+///
+/// `chunks` 为 (分片起点, 分片时长, 转录结果)，按时间顺序排列。相邻分片的重叠区
+/// 以中点为界，每个分段按其中点归属到唯一一个分片，从而去掉重复内容。
+pub fn stitch_segments(chunks: &[(f64, Option<f64>, ChunkTranscript)]) -> Vec<TranscriptSegment> {
+    let mut result = Vec::new();
+
+    for (i, (start, duration, transcript)) in chunks.iter().enumerate() {
+        let lower = if i == 0 {
+            f64::NEG_INFINITY
+        } else {
+            let (prev_start, prev_duration, _) = &chunks[i - 1];
+            boundary(*prev_start, *prev_duration, *start)
+        };
+        let upper = match chunks.get(i + 1) {
+            Some((next_start, _, _)) => boundary(*start, *duration, *next_start),
+            None => f64::INFINITY,
+        };
+
+        for seg in &transcript.segments {
+            let abs = TranscriptSegment {
+                start: start + seg.start,
+                end: start + seg.end,
+                text: seg.text.clone(),
+            };
+            let mid = (abs.start + abs.end) / 2.0;
+            if mid >= lower && mid < upper {
+                result.push(abs);
+            }
+        }
+    }
+
+    result
+}
+
+/// 相邻分片的分界点: 重叠区中点
+fn boundary(prev_start: f64, prev_duration: Option<f64>, next_start: f64) -> f64 {
+    match prev_duration {
+        Some(d) => (next_start + (prev_start + d).max(next_start)) / 2.0,
+        None => next_start,
+    }
+}
+
+/// 拼接分段文本 (CJK 文本之间不插入空格)
+pub fn join_text(segments: &[TranscriptSegment]) -> String {
+    let mut text = String::new();
+    for seg in segments {
+        let piece = seg.text.trim();
+        if piece.is_empty() {
+            continue;
+        }
+        let needs_space = match (text.chars().last(), piece.chars().next()) {
+            (Some(a), Some(b)) => a.is_ascii() && b.is_ascii(),
+            _ => false,
+        };
+        if needs_space {
+            text.push(' ');
+        }
+        text.push_str(piece);
+    }
+    text
+}
+
+/// 按 response_format 渲染最终响应体
+pub fn render(
+    format: ResponseFormat,
+    task: AudioTask,
+    language: Option<&str>,
+    duration: Option<f64>,
+    segments: &[TranscriptSegment],
+) -> String {
+    match format {
+        ResponseFormat::Json => json!({ "text": join_text(segments) }).to_string(),
+        ResponseFormat::Text => join_text(segments),
+        ResponseFormat::Srt => segments
+            .iter()
+            .enumerate()
+            .map(|(i, seg)| {
+                format!(
+                    "{}\n{} --> {}\n{}\n",
+                    i + 1,
+                    format_timestamp(seg.start, ','),
+                    format_timestamp(seg.end, ','),
+                    seg.text.trim()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ResponseFormat::Vtt => {
+            let mut out = String::from("WEBVTT\n");
+            for seg in segments {
+                out.push_str(&format!(
+                    "\n{} --> {}\n{}\n",
+                    format_timestamp(seg.start, '.'),
+                    format_timestamp(seg.end, '.'),
+                    seg.text.trim()
+                ));
+            }
+            out
+        }
+        ResponseFormat::VerboseJson => {
+            let duration = duration
+                .or_else(|| segments.last().map(|s| s.end))
+                .unwrap_or(0.0);
+            let segments_json: Vec<Value> = segments
+                .iter()
+                .enumerate()
+                .map(|(i, seg)| {
+                    json!({
+                        "id": i,
+                        "seek": (seg.start * 100.0).round() as u64,
+                        "start": round_ms(seg.start),
+                        "end": round_ms(seg.end),
+                        "text": seg.text.trim(),
+                    })
+                })
+                .collect();
+            json!({
+                "task": task.as_str(),
+                "language": language.unwrap_or("unknown"),
+                "duration": round_ms(duration),
+                "text": join_text(segments),
+                "segments": segments_json,
+            })
+            .to_string()
+        }
+    }
+}
+
+fn round_ms(secs: f64) -> f64 {
+    (secs * 1000.0).round() / 1000.0
+}
+
+/// 格式化为 SRT/VTT 时间戳: HH:MM:SS,mmm (SRT) 或 HH:MM:SS.mmm (VTT)
+fn format_timestamp(secs: f64, ms_sep: char) -> String {
+    let total_ms = (secs.max(0.0) * 1000.0).round() as u64;
+    let hours = total_ms / 3_600_000;
+    let minutes = (total_ms % 3_600_000) / 60_000;
+    let seconds = (total_ms % 60_000) / 1000;
+    let millis = total_ms % 1000;
+    format!("{:02}:{:02}:{:02}{}{:03}", hours, minutes, seconds, ms_sep, millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seg(start: f64, end: f64, text: &str) -> TranscriptSegment {
+        TranscriptSegment {
+            start,
+            end,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_response_format() {
+        assert_eq!(ResponseFormat::parse("").unwrap(), ResponseFormat::Json);
+        assert_eq!(
+            ResponseFormat::parse("verbose_json").unwrap(),
+            ResponseFormat::VerboseJson
+        );
+        assert!(ResponseFormat::parse("xml").is_err());
+    }
+
+    #[test]
+    fn test_parse_chunk_transcript() {
+        let raw = "```json\n{\"language\":\"EN\",\"segments\":[{\"start\":0,\"end\":\"00:02.5\",\"text\":\" Hello \"},{\"start\":3,\"end\":4,\"text\":\"\"}]}\n```";
+        let parsed = parse_chunk_transcript(raw, Some(10.0));
+        assert_eq!(parsed.language.as_deref(), Some("en"));
+        assert_eq!(parsed.segments, vec![seg(0.0, 2.5, "Hello")]);
+
+        // 非 JSON 输出回退为整段
+        let parsed = parse_chunk_transcript("plain words", Some(10.0));
+        assert_eq!(parsed.segments, vec![seg(0.0, 10.0, "plain words")]);
+    }
+
+    #[test]
+    fn test_stitch_drops_overlap_duplicates() {
+        let first = ChunkTranscript {
+            language: None,
+            segments: vec![seg(0.0, 20.0, "a"), seg(20.0, 38.0, "b"), seg(38.0, 40.0, "c")],
+        };
+        // 第二个分片从 35s 开始，与第一个分片重叠 5s
+        let second = ChunkTranscript {
+            language: None,
+            segments: vec![seg(0.0, 3.0, "b-tail"), seg(3.0, 5.0, "c"), seg(5.0, 20.0, "d")],
+        };
+        let stitched = stitch_segments(&[(0.0, Some(40.0), first), (35.0, Some(20.0), second)]);
+        let texts: Vec<_> = stitched.iter().map(|s| s.text.as_str()).collect();
+        assert_eq!(texts, vec!["a", "b", "c", "d"]);
+        assert_eq!(stitched[3].start, 40.0);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let segments = vec![seg(0.0, 1.5, "Hello"), seg(1.5, 3723.25, "world")];
+        assert_eq!(
+            render(ResponseFormat::Text, AudioTask::Transcribe, None, None, &segments),
+            "Hello world"
+        );
+
+        let srt = render(ResponseFormat::Srt, AudioTask::Transcribe, None, None, &segments);
+        assert!(srt.starts_with("1\n00:00:00,000 --> 00:00:01,500\nHello\n"));
+        assert!(srt.contains("2\n00:00:01,500 --> 01:02:03,250\nworld\n"));
+
+        let vtt = render(ResponseFormat::Vtt, AudioTask::Transcribe, None, None, &segments);
+        assert!(vtt.starts_with("WEBVTT\n\n00:00:00.000 --> 00:00:01.500\nHello\n"));
+
+        let verbose: Value = serde_json::from_str(&render(
+            ResponseFormat::VerboseJson,
+            AudioTask::Translate,
+            Some("fr"),
+            None,
+            &segments,
+        ))
+        .unwrap();
+        assert_eq!(verbose["task"], "translate");
+        assert_eq!(verbose["language"], "fr");
+        assert_eq!(verbose["duration"], 3723.25);
+        assert_eq!(verbose["segments"][1]["start"], 1.5);
+    }
+
+    #[test]
+    fn test_join_text_cjk() {
+        let segments = vec![seg(0.0, 1.0, "你好"), seg(1.0, 2.0, "世界")];
+        assert_eq!(join_text(&segments), "你好世界");
+    }
+}
@@ -0,0 +1,424 @@
+// Gemini cachedContents 模拟层
+// v1internal 不提供显式缓存接口，这里在本地保存缓存前缀 (systemInstruction / contents / tools)，
+// 请求引用 cachedContent 时展开为完整请求，并固定到首次使用该缓存的账号，
+// 使相同前缀始终落在同一账号上以命中上游的隐式前缀缓存。
+
+use axum::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{OnceLock, RwLock};
+
+const CACHE_FILE: &str = "cached_contents.json";
+/// 默认 TTL (与 Gemini API 一致)
+pub const DEFAULT_CACHE_TTL_SECS: i64 = 3600;
+const NAME_PREFIX: &str = "cachedContents/";
+
+static GLOBAL_STORE: OnceLock<CachedContentStore> = OnceLock::new();
+
+/// 缓存条目
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedContent {
+    pub id: String,
+    /// "models/<model>"
+    pub model: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub system_instruction: Option<Value>,
+    #[serde(default)]
+    pub contents: Vec<Value>,
+    #[serde(default)]
+    pub tools: Option<Value>,
+    #[serde(default)]
+    pub tool_config: Option<Value>,
+    /// 秒级时间戳
+    pub create_time: i64,
+    pub update_time: i64,
+    pub expire_time: i64,
+    pub total_token_count: u32,
+    /// 固定使用的账号 (首次使用时绑定)
+    #[serde(default)]
+    pub account_id: Option<String>,
+}
+
+impl CachedContent {
+    pub fn name(&self) -> String {
+        format!("{}{}", NAME_PREFIX, self.id)
+    }
+
+    /// Gemini API 格式 (不回传缓存内容本身)
+    pub fn to_api(&self) -> Value {
+        let mut value = json!({
+            "name": self.name(),
+            "model": self.model,
+            "createTime": rfc3339(self.create_time),
+            "updateTime": rfc3339(self.update_time),
+            "expireTime": rfc3339(self.expire_time),
+            "usageMetadata": { "totalTokenCount": self.total_token_count }
+        });
+        if let Some(display_name) = &self.display_name {
+            value["displayName"] = json!(display_name);
+        }
+        value
+    }
+}
+
+fn rfc3339(ts: i64) -> String {
+    chrono::DateTime::from_timestamp(ts, 0)
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Secs, true))
+        .unwrap_or_default()
+}
+
+fn normalize_model(model: &str) -> &str {
+    model.strip_prefix("models/").unwrap_or(model)
+}
+
+/// 解析 "cachedContents/<id>" 或裸 id
+pub fn parse_cache_name(name: &str) -> &str {
+    name.strip_prefix(NAME_PREFIX).unwrap_or(name)
+}
+
+/// 解析过期时间: ttl ("3600s" / "1.5s") 或 expireTime (RFC3339)，均未提供时返回 None
+fn parse_expiry(body: &Value, now: i64) -> Result<Option<i64>, String> {
+    if let Some(ttl) = body.get("ttl").and_then(|v| v.as_str()) {
+        let secs = ttl
+            .trim()
+            .strip_suffix('s')
+            .and_then(|s| s.parse::<f64>().ok())
+            .filter(|s| *s > 0.0)
+            .ok_or_else(|| format!("Invalid ttl '{}', expected a duration like \"3600s\"", ttl))?;
+        return Ok(Some(now + secs.ceil() as i64));
+    }
+    if let Some(expire) = body.get("expireTime").and_then(|v| v.as_str()) {
+        let ts = chrono::DateTime::parse_from_rfc3339(expire)
+            .map_err(|e| format!("Invalid expireTime '{}': {}", expire, e))?
+            .timestamp();
+        if ts <= now {
+            return Err("expireTime must be in the future".to_string());
+        }
+        return Ok(Some(ts));
+    }
+    Ok(None)
+}
+
+/// 本地缓存存储 (内存 + JSON 文件持久化)
+pub struct CachedContentStore {
+    path: Option<PathBuf>,
+    entries: RwLock<HashMap<String, CachedContent>>,
+}
+
+impl CachedContentStore {
+    /// 仅内存存储 (测试用)
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn load(path: PathBuf) -> Self {
+        let entries: HashMap<String, CachedContent> = std::fs::read(&path)
+            .ok()
+            .and_then(|raw| serde_json::from_slice(&raw).ok())
+            .unwrap_or_default();
+        Self {
+            path: Some(path),
+            entries: RwLock::new(entries),
+        }
+    }
+
+    /// 全局存储 (数据目录下的 cached_contents.json)
+    pub fn global() -> &'static CachedContentStore {
+        GLOBAL_STORE.get_or_init(|| match crate::modules::account::get_data_dir() {
+            Ok(dir) => Self::load(dir.join(CACHE_FILE)),
+            Err(e) => {
+                tracing::warn!(
+                    "[CachedContents] Data dir unavailable, using memory store: {}",
+                    e
+                );
+                Self::in_memory()
+            }
+        })
+    }
+
+    fn persist(&self, entries: &HashMap<String, CachedContent>) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        match serde_json::to_vec(entries) {
+            Ok(json) => {
+                if let Err(e) = std::fs::write(path, json) {
+                    tracing::warn!("[CachedContents] Failed to persist caches: {}", e);
+                }
+            }
+            Err(e) => tracing::warn!("[CachedContents] Failed to serialize caches: {}", e),
+        }
+    }
+
+    /// 修改并持久化，同时清理已过期的条目
+    fn mutate<T>(&self, f: impl FnOnce(&mut HashMap<String, CachedContent>) -> T) -> T {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        let now = chrono::Utc::now().timestamp();
+        entries.retain(|_, c| c.expire_time > now);
+        let result = f(&mut entries);
+        self.persist(&entries);
+        result
+    }
+
+    /// 创建缓存 (请求体为 Gemini CachedContent 资源)
+    pub fn create(&self, body: &Value) -> Result<CachedContent, String> {
+        let model = body
+            .get("model")
+            .and_then(|v| v.as_str())
+            .filter(|m| !m.is_empty())
+            .ok_or("model is required")?;
+        let contents = body
+            .get("contents")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let system_instruction = body.get("systemInstruction").cloned();
+        if contents.is_empty() && system_instruction.is_none() {
+            return Err("contents or systemInstruction is required".to_string());
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let expire_time = parse_expiry(body, now)?.unwrap_or(now + DEFAULT_CACHE_TTL_SECS);
+        let tools = body.get("tools").cloned();
+        let total_token_count =
+            crate::proxy::mappers::context_compression::estimate_request_tokens(&json!({
+                "contents": contents,
+                "systemInstruction": system_instruction,
+                "tools": tools
+            }));
+
+        let cache = CachedContent {
+            id: uuid::Uuid::new_v4().simple().to_string()[..16].to_string(),
+            model: format!("models/{}", normalize_model(model)),
+            display_name: body
+                .get("displayName")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string()),
+            system_instruction,
+            contents,
+            tools,
+            tool_config: body.get("toolConfig").cloned(),
+            create_time: now,
+            update_time: now,
+            expire_time,
+            total_token_count,
+            account_id: None,
+        };
+        self.mutate(|entries| entries.insert(cache.id.clone(), cache.clone()));
+        Ok(cache)
+    }
+
+    pub fn get(&self, name: &str) -> Option<CachedContent> {
+        let now = chrono::Utc::now().timestamp();
+        self.entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(parse_cache_name(name))
+            .filter(|c| c.expire_time > now)
+            .cloned()
+    }
+
+    /// 列出未过期的缓存 (按创建时间排序)
+    pub fn list(&self) -> Vec<CachedContent> {
+        let now = chrono::Utc::now().timestamp();
+        let mut caches: Vec<CachedContent> = self
+            .entries
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .values()
+            .filter(|c| c.expire_time > now)
+            .cloned()
+            .collect();
+        caches.sort_by(|a, b| a.create_time.cmp(&b.create_time).then(a.id.cmp(&b.id)));
+        caches
+    }
+
+    /// 更新过期时间 (仅 ttl / expireTime 可修改)
+    pub fn update(&self, name: &str, body: &Value) -> Result<Option<CachedContent>, String> {
+        let now = chrono::Utc::now().timestamp();
+        let expire_time = parse_expiry(body, now)?.ok_or("ttl or expireTime is required")?;
+        Ok(self.mutate(|entries| {
+            let cache = entries.get_mut(parse_cache_name(name))?;
+            cache.expire_time = expire_time;
+            cache.update_time = now;
+            Some(cache.clone())
+        }))
+    }
+
+    pub fn delete(&self, name: &str) -> bool {
+        self.mutate(|entries| entries.remove(parse_cache_name(name)).is_some())
+    }
+
+    /// 将缓存固定到指定账号
+    pub fn pin_account(&self, name: &str, account_id: &str) {
+        let already_pinned = self
+            .get(name)
+            .map(|c| c.account_id.as_deref() == Some(account_id))
+            .unwrap_or(true);
+        if already_pinned {
+            return;
+        }
+        self.mutate(|entries| {
+            if let Some(cache) = entries.get_mut(parse_cache_name(name)) {
+                cache.account_id = Some(account_id.to_string());
+            }
+        });
+    }
+
+    /// 展开请求中的 cachedContent 引用
+    ///
+    /// 缓存的 contents 拼接在请求 contents 之前，systemInstruction / tools / toolConfig 取自缓存。
+    /// 与 Gemini API 一致: 请求自身不能再设置这三项，模型必须与缓存一致。
+    pub fn apply(
+        &self,
+        body: &mut Value,
+        model_name: &str,
+    ) -> Result<Option<CachedContent>, (StatusCode, String)> {
+        let Some(name) = body
+            .get("cachedContent")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+        else {
+            return Ok(None);
+        };
+        let cache = self.get(&name).ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                format!("CachedContent not found (or expired): {}", name),
+            )
+        })?;
+
+        if normalize_model(&cache.model) != normalize_model(model_name) {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!(
+                    "Model used by GenerateContent request ({}) and CachedContent ({}) has to be the same",
+                    model_name, cache.model
+                ),
+            ));
+        }
+        if ["systemInstruction", "tools", "toolConfig"]
+            .iter()
+            .any(|key| body.get(*key).is_some())
+        {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                "CachedContent can not be used with GenerateContent request setting system_instruction, tools or tool_config".to_string(),
+            ));
+        }
+
+        let Some(obj) = body.as_object_mut() else {
+            return Ok(None);
+        };
+        obj.remove("cachedContent");
+        let mut contents = cache.contents.clone();
+        if let Some(Value::Array(request_contents)) = obj.remove("contents") {
+            contents.extend(request_contents);
+        }
+        obj.insert("contents".to_string(), Value::Array(contents));
+        for (key, value) in [
+            ("systemInstruction", &cache.system_instruction),
+            ("tools", &cache.tools),
+            ("toolConfig", &cache.tool_config),
+        ] {
+            if let Some(value) = value {
+                obj.insert(key.to_string(), value.clone());
+            }
+        }
+        Ok(Some(cache))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_body() -> Value {
+        json!({
+            "model": "models/gemini-2.5-pro",
+            "displayName": "manual",
+            "systemInstruction": { "parts": [{ "text": "You answer from the manual." }] },
+            "contents": [{ "role": "user", "parts": [{ "text": "MANUAL TEXT" }] }],
+            "ttl": "600s"
+        })
+    }
+
+    #[test]
+    fn test_create_get_update_delete() {
+        let store = CachedContentStore::in_memory();
+        let cache = store.create(&create_body()).unwrap();
+        assert!(cache.total_token_count > 0);
+        assert!(cache.expire_time - cache.create_time >= 600);
+
+        let api = cache.to_api();
+        assert_eq!(api["name"], cache.name());
+        assert_eq!(api["displayName"], "manual");
+        assert!(api.get("contents").is_none());
+
+        let updated = store
+            .update(&cache.name(), &json!({ "ttl": "7200s" }))
+            .unwrap()
+            .unwrap();
+        assert!(updated.expire_time - cache.create_time >= 7200);
+        assert_eq!(store.list().len(), 1);
+
+        assert!(store.delete(&cache.name()));
+        assert!(store.get(&cache.id).is_none());
+        assert!(store.create(&json!({ "model": "gemini-2.5-pro" })).is_err());
+    }
+
+    #[test]
+    fn test_apply_expands_cached_prefix() {
+        let store = CachedContentStore::in_memory();
+        let cache = store.create(&create_body()).unwrap();
+        let mut body = json!({
+            "cachedContent": cache.name(),
+            "contents": [{ "role": "user", "parts": [{ "text": "What does chapter 2 say?" }] }]
+        });
+
+        let applied = store.apply(&mut body, "gemini-2.5-pro").unwrap().unwrap();
+        assert_eq!(applied.id, cache.id);
+        assert!(body.get("cachedContent").is_none());
+        assert_eq!(body["contents"].as_array().unwrap().len(), 2);
+        assert_eq!(body["contents"][0]["parts"][0]["text"], "MANUAL TEXT");
+        assert_eq!(
+            body["systemInstruction"]["parts"][0]["text"],
+            "You answer from the manual."
+        );
+
+        store.pin_account(&cache.name(), "acc-1");
+        assert_eq!(
+            store.get(&cache.id).unwrap().account_id.as_deref(),
+            Some("acc-1")
+        );
+    }
+
+    #[test]
+    fn test_apply_rejects_conflicts() {
+        let store = CachedContentStore::in_memory();
+        let cache = store.create(&create_body()).unwrap();
+
+        let mut wrong_model = json!({ "cachedContent": cache.name(), "contents": [] });
+        let err = store
+            .apply(&mut wrong_model, "gemini-2.5-flash")
+            .unwrap_err();
+        assert_eq!(err.0, StatusCode::BAD_REQUEST);
+
+        let mut with_system = json!({
+            "cachedContent": cache.name(),
+            "systemInstruction": { "parts": [{ "text": "x" }] }
+        });
+        assert!(store.apply(&mut with_system, "gemini-2.5-pro").is_err());
+
+        let mut missing = json!({ "cachedContent": "cachedContents/nope" });
+        let err = store.apply(&mut missing, "gemini-2.5-pro").unwrap_err();
+        assert_eq!(err.0, StatusCode::NOT_FOUND);
+    }
+}
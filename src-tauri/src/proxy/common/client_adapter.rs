@@ -1,7 +1,14 @@
 use axum::http::HeaderMap;
 use once_cell::sync::Lazy;
-use std::sync::Arc; // [NEW] Import Arc
-use super::client_adapters::OpencodeAdapter;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::sync::{Arc, RwLock}; // [NEW] Import Arc
+use super::client_adapters::{
+    CherryStudioAdapter, ClaudeCodeAdapter, ClineAdapter, ConfigurableAdapter, CursorAdapter,
+    GeminiCliAdapter, OpencodeAdapter,
+};
+use crate::proxy::config::CustomClientAdapterConfig;
 
 /// 客户端适配器 trait
 /// 
@@ -21,6 +28,11 @@ pub trait ClientAdapter: Send + Sync {
     /// # Returns
     /// 如果匹配返回 true，否则返回 false
     fn matches(&self, headers: &HeaderMap) -> bool;
+
+    /// 适配器名称 (用于日志)
+    fn name(&self) -> &str {
+        "client"
+    }
     
     /// 是否绕过签名校验
     /// 
@@ -42,6 +54,20 @@ pub trait ClientAdapter: Send + Sync {
     fn signature_buffer_strategy(&self) -> SignatureBufferStrategy {
         SignatureBufferStrategy::Default
     }
+
+    /// 流式响应中途出错时使用的 SSE 错误格式
+    ///
+    /// 部分客户端只解析 `data:` 行，或按 OpenAI/Gemini 的错误结构做类型校验
+    fn sse_error_format(&self) -> SseErrorFormat {
+        SseErrorFormat::Anthropic
+    }
+
+    /// 客户端为 MCP 工具命名的方式
+    ///
+    /// 用于将工具名还原为 `mcp__server__tool` 规范形式，以便匹配工具适配器
+    fn tool_name_style(&self) -> ToolNameStyle {
+        ToolNameStyle::McpDoubleUnderscore
+    }
     
     /// 注入客户端缺少的 Beta Header
     /// 
@@ -59,9 +85,11 @@ pub trait ClientAdapter: Send + Sync {
 }
 
 /// 签名缓存策略
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum SignatureBufferStrategy {
     /// 默认策略（当前实现）
+    #[default]
     Default,
     /// FIFO（先进先出）- 适用于多并发工具调用
     Fifo,
@@ -71,25 +99,188 @@ pub enum SignatureBufferStrategy {
 }
 
 /// 支持的协议类型
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
 pub enum Protocol {
     Anthropic,
+    #[serde(rename = "openai")]
     OpenAI,
+    #[serde(rename = "oa_compatible")]
     OACompatible,
+    #[serde(rename = "gemini")]
     GoogleGemini,
 }
 
-/// 全局客户端适配器注册表
+/// 流式错误事件格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SseErrorFormat {
+    /// `event: error` + `{"type":"error","error":{...}}` (Anthropic 标准)
+    #[default]
+    Anthropic,
+    /// 仅 `data:` 行，`{"error":{"message","type","code"}}`
+    #[serde(rename = "openai")]
+    OpenAI,
+    /// 仅 `data:` 行，`{"error":{"code","message","status"}}`
+    Gemini,
+}
+
+impl SseErrorFormat {
+    /// 生成完整的 SSE 错误帧
+    ///
+    /// `error_type` 使用 Anthropic 错误类型 (如 `overloaded_error`)，其他格式会自动转换
+    pub fn render(&self, error_type: &str, message: &str) -> String {
+        match self {
+            SseErrorFormat::Anthropic => format!(
+                "event: error\ndata: {}\n\n",
+                json!({
+                    "type": "error",
+                    "error": { "type": error_type, "message": message }
+                })
+            ),
+            SseErrorFormat::OpenAI => format!(
+                "data: {}\n\n",
+                json!({
+                    "error": { "message": message, "type": error_type, "code": null }
+                })
+            ),
+            SseErrorFormat::Gemini => {
+                let (code, status) = match error_type {
+                    "invalid_request_error" => (400, "INVALID_ARGUMENT"),
+                    "authentication_error" => (401, "UNAUTHENTICATED"),
+                    "permission_error" => (403, "PERMISSION_DENIED"),
+                    "not_found_error" => (404, "NOT_FOUND"),
+                    "rate_limit_error" => (429, "RESOURCE_EXHAUSTED"),
+                    "overloaded_error" => (503, "UNAVAILABLE"),
+                    _ => (500, "INTERNAL"),
+                };
+                format!(
+                    "data: {}\n\n",
+                    json!({
+                        "error": { "code": code, "message": message, "status": status }
+                    })
+                )
+            }
+        }
+    }
+}
+
+/// 客户端的 MCP 工具命名约定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolNameStyle {
+    /// `mcp__server__tool` (Claude Code)
+    #[default]
+    McpDoubleUnderscore,
+    /// `mcp_server_tool`
+    McpSingleUnderscore,
+    /// `server__tool` (Gemini CLI 在工具名冲突时使用)
+    ServerDoubleUnderscore,
+    /// 通过统一的 `use_mcp_tool` 工具间接调用 (Cline/Roo)，工具名不含服务器信息
+    UseMcpTool,
+}
+
+impl ToolNameStyle {
+    /// 转换为 `mcp__server__tool` 规范形式，无法识别时返回 None
+    pub fn to_canonical(&self, name: &str) -> Option<String> {
+        match self {
+            ToolNameStyle::McpDoubleUnderscore => name
+                .strip_prefix("mcp__")
+                .filter(|rest| rest.contains("__"))
+                .map(|_| name.to_string()),
+            ToolNameStyle::McpSingleUnderscore => {
+                let rest = name.strip_prefix("mcp_")?;
+                let (server, tool) = rest.split_once('_')?;
+                (!server.is_empty() && !tool.is_empty())
+                    .then(|| format!("mcp__{}__{}", server, tool))
+            }
+            ToolNameStyle::ServerDoubleUnderscore => {
+                let (server, tool) = name.split_once("__")?;
+                (!server.is_empty() && !tool.is_empty())
+                    .then(|| format!("mcp__{}__{}", server, tool))
+            }
+            ToolNameStyle::UseMcpTool => None,
+        }
+    }
+}
+
+/// 全局客户端适配器注册表 (内置)
 /// 
 /// 所有注册的适配器都会在请求处理时被检查
 pub static CLIENT_ADAPTERS: Lazy<Vec<Arc<dyn ClientAdapter>>> = Lazy::new(|| {
     vec![
         Arc::new(OpencodeAdapter),
-        // 未来可以轻松添加更多适配器:
-        // Arc::new(CherryStudioAdapter),
+        Arc::new(ClaudeCodeAdapter),
+        Arc::new(ClineAdapter),
+        Arc::new(CursorAdapter),
+        Arc::new(CherryStudioAdapter),
+        Arc::new(GeminiCliAdapter),
     ]
 });
 
+/// 用户在配置中声明的自定义适配器 (优先于内置适配器)
+static CUSTOM_CLIENT_ADAPTERS: Lazy<RwLock<Vec<Arc<dyn ClientAdapter>>>> =
+    Lazy::new(|| RwLock::new(Vec::new()));
+
+/// 更新自定义适配器 (配置保存或服务启动时调用)
+///
+/// 规则无效 (如正则语法错误) 的适配器会被跳过并记录警告
+pub fn update_custom_client_adapters(configs: &[CustomClientAdapterConfig]) {
+    let adapters = build_custom_adapters(configs);
+    tracing::info!("[ClientAdapter] Loaded {} custom adapter(s)", adapters.len());
+    if let Ok(mut custom) = CUSTOM_CLIENT_ADAPTERS.write() {
+        *custom = adapters;
+    }
+}
+
+fn build_custom_adapters(configs: &[CustomClientAdapterConfig]) -> Vec<Arc<dyn ClientAdapter>> {
+    configs
+        .iter()
+        .filter(|c| c.enabled)
+        .filter_map(|c| match ConfigurableAdapter::from_config(c.clone()) {
+            Ok(adapter) => Some(Arc::new(adapter) as Arc<dyn ClientAdapter>),
+            Err(e) => {
+                tracing::warn!("[ClientAdapter] Skipping custom adapter '{}': {}", c.name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// 查找与请求匹配、且声明支持当前协议的客户端适配器 (自定义优先，其次内置)
+pub fn find_client_adapter(headers: &HeaderMap, protocol: Protocol) -> Option<Arc<dyn ClientAdapter>> {
+    let custom = CUSTOM_CLIENT_ADAPTERS
+        .read()
+        .map(|c| c.clone())
+        .unwrap_or_default();
+    select_adapter(&custom, &CLIENT_ADAPTERS, headers, protocol)
+}
+
+fn select_adapter(
+    custom: &[Arc<dyn ClientAdapter>],
+    builtin: &[Arc<dyn ClientAdapter>],
+    headers: &HeaderMap,
+    protocol: Protocol,
+) -> Option<Arc<dyn ClientAdapter>> {
+    custom
+        .iter()
+        .chain(builtin.iter())
+        .find(|a| a.supported_protocols().contains(&protocol) && a.matches(headers))
+        .cloned()
+}
+
+/// 合并逗号分隔的 anthropic-beta 值 (保持顺序并去重)
+pub fn merge_beta_header(existing: &str, added: &str) -> String {
+    let mut values: Vec<&str> = Vec::new();
+    for value in existing.split(',').chain(added.split(',')) {
+        let value = value.trim();
+        if !value.is_empty() && !values.contains(&value) {
+            values.push(value);
+        }
+    }
+    values.join(",")
+}
+
 /// 辅助函数：从 HeaderMap 中提取 User-Agent
 pub fn get_user_agent(headers: &HeaderMap) -> Option<String> {
     headers
@@ -138,6 +329,69 @@ mod tests {
         assert!(!adapter.matches(&headers));
     }
 
+    #[test]
+    fn test_sse_error_formats() {
+        let anthropic = SseErrorFormat::Anthropic.render("overloaded_error", "busy");
+        assert!(anthropic.starts_with("event: error\ndata: "));
+        assert!(anthropic.contains("\"type\":\"overloaded_error\""));
+
+        let openai = SseErrorFormat::OpenAI.render("overloaded_error", "busy");
+        assert!(openai.starts_with("data: {\"error\""));
+
+        let gemini = SseErrorFormat::Gemini.render("overloaded_error", "busy");
+        assert!(gemini.contains("\"status\":\"UNAVAILABLE\""));
+        assert!(gemini.contains("\"code\":503"));
+    }
+
+    #[test]
+    fn test_tool_name_canonicalization() {
+        assert_eq!(
+            ToolNameStyle::McpDoubleUnderscore.to_canonical("mcp__github__create_issue"),
+            Some("mcp__github__create_issue".to_string())
+        );
+        assert_eq!(ToolNameStyle::McpDoubleUnderscore.to_canonical("Read"), None);
+        assert_eq!(
+            ToolNameStyle::McpSingleUnderscore.to_canonical("mcp_playwright_browser_click"),
+            Some("mcp__playwright__browser_click".to_string())
+        );
+        assert_eq!(
+            ToolNameStyle::ServerDoubleUnderscore.to_canonical("context7__resolve-library-id"),
+            Some("mcp__context7__resolve-library-id".to_string())
+        );
+        assert_eq!(ToolNameStyle::UseMcpTool.to_canonical("use_mcp_tool"), None);
+    }
+
+    #[test]
+    fn test_find_client_adapter_prefers_custom() {
+        // 使用局部注册表，避免修改全局自定义适配器导致测试间竞争
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("claude-cli/1.0.80 (external, cli)"));
+        let select = |custom: &[Arc<dyn ClientAdapter>], protocol| {
+            select_adapter(custom, &CLIENT_ADAPTERS, &headers, protocol).map(|a| a.name().to_string())
+        };
+        assert_eq!(select(&[], Protocol::Anthropic).as_deref(), Some("claude-code"));
+
+        let custom = build_custom_adapters(&[CustomClientAdapterConfig {
+            name: "my-claude".to_string(),
+            enabled: true,
+            user_agent_pattern: Some("^claude-cli/".to_string()),
+            ..Default::default()
+        }]);
+        assert_eq!(select(&custom, Protocol::Anthropic).as_deref(), Some("my-claude"));
+
+        // 适配器未声明的协议不生效
+        assert_eq!(select(&custom, Protocol::OpenAI), None);
+    }
+
+    #[test]
+    fn test_merge_beta_header() {
+        assert_eq!(
+            merge_beta_header("claude-code-20250219", "a-2025, claude-code-20250219,b-2025"),
+            "claude-code-20250219,a-2025,b-2025"
+        );
+        assert_eq!(merge_beta_header("", "a-2025"), "a-2025");
+    }
+
     #[test]
     fn test_get_user_agent() {
         let mut headers = HeaderMap::new();
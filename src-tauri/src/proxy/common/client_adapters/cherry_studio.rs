@@ -0,0 +1,77 @@
+use super::super::client_adapter::{
+    ClientAdapter, Protocol, SignatureBufferStrategy, SseErrorFormat, get_user_agent,
+};
+use axum::http::HeaderMap;
+
+/// Cherry Studio 桌面客户端适配器
+///
+/// Electron 应用，User-Agent 中带有 `CherryStudio/x.y.z`。
+///
+/// 定制策略：
+/// 1. FIFO 签名管理策略（会在同一轮中并行发起多个工具调用）
+/// 2. SSE 错误使用 OpenAI 结构（各协议共用同一套错误展示逻辑）
+pub struct CherryStudioAdapter;
+
+impl ClientAdapter for CherryStudioAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        get_user_agent(headers)
+            .map(|ua| {
+                let ua = ua.to_lowercase();
+                ua.contains("cherrystudio") || ua.contains("cherry-studio")
+            })
+            .unwrap_or(false)
+    }
+
+    fn name(&self) -> &str {
+        "cherry-studio"
+    }
+
+    fn signature_buffer_strategy(&self) -> SignatureBufferStrategy {
+        SignatureBufferStrategy::Fifo
+    }
+
+    fn sse_error_format(&self) -> SseErrorFormat {
+        SseErrorFormat::OpenAI
+    }
+
+    fn supported_protocols(&self) -> Vec<Protocol> {
+        vec![
+            Protocol::Anthropic,
+            Protocol::OpenAI,
+            Protocol::OACompatible,
+            Protocol::GoogleGemini,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_cherry_studio_adapter_matches() {
+        let adapter = CherryStudioAdapter;
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "user-agent",
+            HeaderValue::from_static("Mozilla/5.0 (Windows NT 10.0) CherryStudio/1.2.4 Chrome/126.0 Electron/31.7.6"),
+        );
+        assert!(adapter.matches(&headers));
+
+        headers.insert("user-agent", HeaderValue::from_static("Mozilla/5.0 Chrome/126.0"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_cherry_studio_adapter_strategies() {
+        let adapter = CherryStudioAdapter;
+
+        assert_eq!(adapter.signature_buffer_strategy(), SignatureBufferStrategy::Fifo);
+        assert_eq!(adapter.supported_protocols().len(), 4);
+
+        let frame = adapter.sse_error_format().render("api_error", "boom");
+        assert!(frame.starts_with("data: {\"error\":"));
+    }
+}
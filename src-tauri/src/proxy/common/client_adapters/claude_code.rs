@@ -0,0 +1,78 @@
+use super::super::client_adapter::{ClientAdapter, SseErrorFormat, ToolNameStyle, get_user_agent};
+use axum::http::HeaderMap;
+
+/// Claude Code CLI 客户端适配器
+///
+/// User-Agent 形如 `claude-cli/1.0.80 (external, cli)`。
+///
+/// 定制策略：
+/// 1. 保持默认签名策略（Claude Code 会完整回传 thinking 签名）
+/// 2. 不额外注入 Beta Header（handler 已为 Claude 模型注入 `claude-code-20250219`）
+/// 3. 使用 Anthropic 标准 SSE 错误格式，客户端据此触发自身的重试
+/// 4. MCP 工具命名为 `mcp__server__tool`
+pub struct ClaudeCodeAdapter;
+
+impl ClientAdapter for ClaudeCodeAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        get_user_agent(headers)
+            .map(|ua| {
+                let ua = ua.to_lowercase();
+                ua.starts_with("claude-cli/") || ua.contains("claude-code")
+            })
+            .unwrap_or(false)
+    }
+
+    fn name(&self) -> &str {
+        "claude-code"
+    }
+
+    fn sse_error_format(&self) -> SseErrorFormat {
+        SseErrorFormat::Anthropic
+    }
+
+    fn tool_name_style(&self) -> ToolNameStyle {
+        ToolNameStyle::McpDoubleUnderscore
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_claude_code_adapter_matches() {
+        let adapter = ClaudeCodeAdapter;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("claude-cli/1.0.80 (external, cli)"));
+        assert!(adapter.matches(&headers));
+
+        headers.insert("user-agent", HeaderValue::from_static("Anthropic/JS 0.39.0"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_claude_code_adapter_strategies() {
+        let adapter = ClaudeCodeAdapter;
+
+        assert!(!adapter.let_it_crash());
+        assert_eq!(adapter.tool_name_style(), ToolNameStyle::McpDoubleUnderscore);
+        assert_eq!(
+            adapter.tool_name_style().to_canonical("mcp__github__create_issue").as_deref(),
+            Some("mcp__github__create_issue")
+        );
+
+        // 中途出错时下发 Anthropic 标准 error 事件
+        let frame = adapter.sse_error_format().render("overloaded_error", "busy");
+        let data: serde_json::Value =
+            serde_json::from_str(frame.strip_prefix("event: error\ndata: ").unwrap().trim()).unwrap();
+        assert_eq!(data["type"], "error");
+        assert_eq!(data["error"]["type"], "overloaded_error");
+        assert_eq!(data["error"]["message"], "busy");
+
+        let mut headers = HeaderMap::new();
+        adapter.inject_beta_headers(&mut headers);
+        assert!(headers.is_empty());
+    }
+}
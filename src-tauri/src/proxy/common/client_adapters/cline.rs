@@ -0,0 +1,84 @@
+use super::super::client_adapter::{ClientAdapter, Protocol, ToolNameStyle, get_user_agent};
+use axum::http::HeaderMap;
+
+/// Cline / Roo Code 客户端适配器
+///
+/// 两者都是 VS Code 扩展，通过 SDK 发送请求，User-Agent 不固定，
+/// 但会携带 `X-Title` / `HTTP-Referer` 标识头。
+///
+/// 定制策略：
+/// 1. 绕过签名校验（切换模式或压缩历史时会丢弃 thinking 块的签名）
+/// 2. MCP 工具统一通过 `use_mcp_tool` 调用，工具名不包含服务器信息
+pub struct ClineAdapter;
+
+impl ClientAdapter for ClineAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        let header_contains = |name: &str, needles: &[&str]| {
+            headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| {
+                    let v = v.to_lowercase();
+                    needles.iter().any(|n| v.contains(n))
+                })
+                .unwrap_or(false)
+        };
+
+        header_contains("x-title", &["cline", "roo code", "roo-code"])
+            || header_contains("http-referer", &["cline.bot", "roocode", "roo-cline"])
+            || get_user_agent(headers)
+                .map(|ua| {
+                    let ua = ua.to_lowercase();
+                    ua.contains("cline") || ua.contains("roo-code") || ua.contains("roocode")
+                })
+                .unwrap_or(false)
+    }
+
+    fn name(&self) -> &str {
+        "cline"
+    }
+
+    fn bypass_signature_matching(&self) -> bool {
+        true
+    }
+
+    fn tool_name_style(&self) -> ToolNameStyle {
+        ToolNameStyle::UseMcpTool
+    }
+
+    fn supported_protocols(&self) -> Vec<Protocol> {
+        vec![Protocol::Anthropic, Protocol::OpenAI, Protocol::GoogleGemini]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_cline_adapter_matches_identity_headers() {
+        let adapter = ClineAdapter;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-title", HeaderValue::from_static("Cline"));
+        assert!(adapter.matches(&headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("http-referer", HeaderValue::from_static("https://github.com/RooVetGit/Roo-Cline"));
+        assert!(adapter.matches(&headers));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("OpenAI/JS 4.73.0"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_cline_adapter_strategies() {
+        let adapter = ClineAdapter;
+
+        assert!(adapter.bypass_signature_matching());
+        assert_eq!(adapter.supported_protocols().len(), 3);
+        assert_eq!(adapter.tool_name_style().to_canonical("use_mcp_tool"), None);
+    }
+}
@@ -0,0 +1,78 @@
+use super::super::client_adapter::{
+    ClientAdapter, Protocol, SseErrorFormat, ToolNameStyle, get_user_agent,
+};
+use axum::http::HeaderMap;
+
+/// Cursor 编辑器适配器
+///
+/// Cursor 通过 OpenAI 兼容协议访问自定义端点。
+///
+/// 定制策略：
+/// 1. 快速失败（Cursor 自带超时与重试，服务端重试只会拉长等待）
+/// 2. SSE 错误使用 OpenAI 结构，仅 `data:` 行
+/// 3. MCP 工具命名为 `mcp_server_tool`
+pub struct CursorAdapter;
+
+impl ClientAdapter for CursorAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        get_user_agent(headers)
+            .map(|ua| ua.to_lowercase().contains("cursor"))
+            .unwrap_or(false)
+    }
+
+    fn name(&self) -> &str {
+        "cursor"
+    }
+
+    fn let_it_crash(&self) -> bool {
+        true
+    }
+
+    fn sse_error_format(&self) -> SseErrorFormat {
+        SseErrorFormat::OpenAI
+    }
+
+    fn tool_name_style(&self) -> ToolNameStyle {
+        ToolNameStyle::McpSingleUnderscore
+    }
+
+    fn supported_protocols(&self) -> Vec<Protocol> {
+        vec![Protocol::OpenAI, Protocol::Anthropic]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_cursor_adapter_matches() {
+        let adapter = CursorAdapter;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("Cursor/0.45.11"));
+        assert!(adapter.matches(&headers));
+
+        headers.insert("user-agent", HeaderValue::from_static("curl/8.0.1"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_cursor_adapter_strategies() {
+        let adapter = CursorAdapter;
+
+        assert!(adapter.let_it_crash());
+        assert!(adapter.supported_protocols().contains(&Protocol::OpenAI));
+        assert_eq!(adapter.sse_error_format(), SseErrorFormat::OpenAI);
+        assert_eq!(
+            adapter.tool_name_style().to_canonical("mcp_github_create_issue"),
+            Some("mcp__github__create_issue".to_string())
+        );
+
+        // 错误帧只有 data 行
+        let frame = adapter.sse_error_format().render("api_error", "boom");
+        assert!(frame.starts_with("data: {\"error\":"));
+        assert!(!frame.contains("event:"));
+    }
+}
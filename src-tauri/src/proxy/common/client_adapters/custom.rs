@@ -0,0 +1,210 @@
+use super::super::client_adapter::{
+    ClientAdapter, Protocol, SignatureBufferStrategy, SseErrorFormat, ToolNameStyle,
+    get_user_agent,
+};
+use crate::proxy::config::CustomClientAdapterConfig;
+use axum::http::{HeaderMap, HeaderValue};
+use regex::{Regex, RegexBuilder};
+
+/// 由配置声明的客户端适配器
+///
+/// 匹配条件：User-Agent 正则与所有请求头规则同时满足；
+/// 两者均未配置时永不匹配，避免误伤所有请求。
+pub struct ConfigurableAdapter {
+    config: CustomClientAdapterConfig,
+    user_agent: Option<Regex>,
+    header_rules: Vec<(String, Option<Regex>)>,
+}
+
+impl ConfigurableAdapter {
+    pub fn from_config(config: CustomClientAdapterConfig) -> Result<Self, String> {
+        let user_agent = config
+            .user_agent_pattern
+            .as_deref()
+            .filter(|p| !p.trim().is_empty())
+            .map(compile_pattern)
+            .transpose()?;
+
+        let header_rules = config
+            .header_matches
+            .iter()
+            .map(|rule| {
+                let pattern = rule
+                    .value_pattern
+                    .as_deref()
+                    .filter(|p| !p.trim().is_empty())
+                    .map(compile_pattern)
+                    .transpose()?;
+                Ok((rule.name.to_lowercase(), pattern))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        if user_agent.is_none() && header_rules.is_empty() {
+            return Err("no user_agent_pattern or header_matches configured".to_string());
+        }
+
+        Ok(Self {
+            config,
+            user_agent,
+            header_rules,
+        })
+    }
+}
+
+fn compile_pattern(pattern: &str) -> Result<Regex, String> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| format!("invalid pattern '{}': {}", pattern, e))
+}
+
+impl ClientAdapter for ConfigurableAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        if let Some(re) = &self.user_agent {
+            match get_user_agent(headers) {
+                Some(ua) if re.is_match(&ua) => {}
+                _ => return false,
+            }
+        }
+
+        self.header_rules.iter().all(|(name, pattern)| {
+            match (headers.get(name.as_str()).and_then(|v| v.to_str().ok()), pattern) {
+                (Some(value), Some(re)) => re.is_match(value),
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn bypass_signature_matching(&self) -> bool {
+        self.config.bypass_signature_matching
+    }
+
+    fn let_it_crash(&self) -> bool {
+        self.config.let_it_crash
+    }
+
+    fn signature_buffer_strategy(&self) -> SignatureBufferStrategy {
+        self.config.signature_buffer_strategy
+    }
+
+    fn inject_beta_headers(&self, headers: &mut HeaderMap) {
+        if self.config.beta_headers.is_empty() {
+            return;
+        }
+        if let Ok(value) = HeaderValue::from_str(&self.config.beta_headers.join(",")) {
+            headers.insert("anthropic-beta", value);
+        }
+    }
+
+    fn supported_protocols(&self) -> Vec<Protocol> {
+        if self.config.protocols.is_empty() {
+            vec![Protocol::Anthropic]
+        } else {
+            self.config.protocols.clone()
+        }
+    }
+
+    fn sse_error_format(&self) -> SseErrorFormat {
+        self.config.sse_error_format
+    }
+
+    fn tool_name_style(&self) -> ToolNameStyle {
+        self.config.tool_name_style
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::config::HeaderMatchRule;
+
+    fn config() -> CustomClientAdapterConfig {
+        CustomClientAdapterConfig {
+            name: "acme".to_string(),
+            enabled: true,
+            user_agent_pattern: Some(r"^acme-agent/\d+".to_string()),
+            header_matches: vec![HeaderMatchRule {
+                name: "X-Client".to_string(),
+                value_pattern: None,
+            }],
+            let_it_crash: true,
+            beta_headers: vec!["a-2025".to_string(), "b-2025".to_string()],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_configurable_adapter_requires_all_rules() {
+        let adapter = ConfigurableAdapter::from_config(config()).unwrap();
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("ACME-Agent/2.1"));
+        assert!(!adapter.matches(&headers));
+
+        headers.insert("x-client", HeaderValue::from_static("anything"));
+        assert!(adapter.matches(&headers));
+
+        headers.insert("user-agent", HeaderValue::from_static("other/1.0"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_configurable_adapter_toggles() {
+        let adapter = ConfigurableAdapter::from_config(config()).unwrap();
+
+        assert_eq!(adapter.name(), "acme");
+        assert!(adapter.let_it_crash());
+        assert_eq!(adapter.supported_protocols(), vec![Protocol::Anthropic]);
+
+        let mut headers = HeaderMap::new();
+        adapter.inject_beta_headers(&mut headers);
+        assert_eq!(headers.get("anthropic-beta").unwrap(), "a-2025,b-2025");
+
+        let mut gemini_style = config();
+        gemini_style.sse_error_format = SseErrorFormat::Gemini;
+        gemini_style.tool_name_style = ToolNameStyle::ServerDoubleUnderscore;
+        let adapter = ConfigurableAdapter::from_config(gemini_style).unwrap();
+        assert!(adapter
+            .sse_error_format()
+            .render("overloaded_error", "busy")
+            .contains("\"status\":\"UNAVAILABLE\""));
+        assert_eq!(
+            adapter.tool_name_style().to_canonical("github__create_issue").as_deref(),
+            Some("mcp__github__create_issue")
+        );
+    }
+
+    #[test]
+    fn test_configurable_adapter_rejects_invalid_rules() {
+        let mut bad_regex = config();
+        bad_regex.user_agent_pattern = Some("(".to_string());
+        assert!(ConfigurableAdapter::from_config(bad_regex).is_err());
+
+        let mut no_rules = config();
+        no_rules.user_agent_pattern = None;
+        no_rules.header_matches.clear();
+        assert!(ConfigurableAdapter::from_config(no_rules).is_err());
+    }
+
+    #[test]
+    fn test_config_deserialize_defaults() {
+        let cfg: CustomClientAdapterConfig = serde_json::from_value(serde_json::json!({
+            "name": "x",
+            "user_agent_pattern": "x",
+            "sse_error_format": "openai",
+            "protocols": ["openai", "gemini"]
+        }))
+        .unwrap();
+
+        assert!(cfg.enabled);
+        assert_eq!(cfg.sse_error_format, SseErrorFormat::OpenAI);
+        assert_eq!(cfg.tool_name_style, ToolNameStyle::McpDoubleUnderscore);
+        assert_eq!(cfg.protocols, vec![Protocol::OpenAI, Protocol::GoogleGemini]);
+        assert_eq!(cfg.signature_buffer_strategy, SignatureBufferStrategy::Default);
+    }
+}
@@ -0,0 +1,80 @@
+use super::super::client_adapter::{
+    ClientAdapter, Protocol, SseErrorFormat, ToolNameStyle, get_user_agent,
+};
+use axum::http::HeaderMap;
+
+/// Gemini CLI 客户端适配器
+///
+/// User-Agent 形如 `GeminiCLI/0.1.5 (linux; x64)`，只使用 Gemini 原生协议。
+///
+/// 定制策略：
+/// 1. 快速失败（客户端自带退避重试，并会在配额耗尽时自行降级到 Flash）
+/// 2. SSE 错误使用 Gemini 结构 `{"error":{"code","message","status"}}`
+/// 3. MCP 工具在重名时命名为 `server__tool`
+pub struct GeminiCliAdapter;
+
+impl ClientAdapter for GeminiCliAdapter {
+    fn matches(&self, headers: &HeaderMap) -> bool {
+        get_user_agent(headers)
+            .map(|ua| {
+                let ua = ua.to_lowercase();
+                ua.starts_with("geminicli/") || ua.contains("gemini-cli")
+            })
+            .unwrap_or(false)
+    }
+
+    fn name(&self) -> &str {
+        "gemini-cli"
+    }
+
+    fn let_it_crash(&self) -> bool {
+        true
+    }
+
+    fn sse_error_format(&self) -> SseErrorFormat {
+        SseErrorFormat::Gemini
+    }
+
+    fn tool_name_style(&self) -> ToolNameStyle {
+        ToolNameStyle::ServerDoubleUnderscore
+    }
+
+    fn supported_protocols(&self) -> Vec<Protocol> {
+        vec![Protocol::GoogleGemini]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_gemini_cli_adapter_matches() {
+        let adapter = GeminiCliAdapter;
+
+        let mut headers = HeaderMap::new();
+        headers.insert("user-agent", HeaderValue::from_static("GeminiCLI/0.1.5 (linux; x64)"));
+        assert!(adapter.matches(&headers));
+
+        headers.insert("user-agent", HeaderValue::from_static("google-genai-sdk/1.0"));
+        assert!(!adapter.matches(&headers));
+    }
+
+    #[test]
+    fn test_gemini_cli_adapter_strategies() {
+        let adapter = GeminiCliAdapter;
+
+        assert!(adapter.let_it_crash());
+        assert_eq!(adapter.supported_protocols(), vec![Protocol::GoogleGemini]);
+        assert_eq!(adapter.sse_error_format(), SseErrorFormat::Gemini);
+        assert_eq!(
+            adapter.tool_name_style().to_canonical("filesystem__read_file").as_deref(),
+            Some("mcp__filesystem__read_file")
+        );
+
+        let frame = adapter.sse_error_format().render("rate_limit_error", "slow down");
+        assert!(frame.contains("\"code\":429"));
+        assert!(frame.contains("\"status\":\"RESOURCE_EXHAUSTED\""));
+    }
+}
@@ -1,6 +1,18 @@
 // Client Adapters 模块
 // 存放各种客户端的适配器实现
 
+pub mod cherry_studio;
+pub mod claude_code;
+pub mod cline;
+pub mod cursor;
+pub mod custom;
+pub mod gemini_cli;
 pub mod opencode;
 
+pub use cherry_studio::CherryStudioAdapter;
+pub use claude_code::ClaudeCodeAdapter;
+pub use cline::ClineAdapter;
+pub use cursor::CursorAdapter;
+pub use custom::ConfigurableAdapter;
+pub use gemini_cli::GeminiCliAdapter;
 pub use opencode::OpencodeAdapter;
@@ -21,6 +21,10 @@ impl ClientAdapter for OpencodeAdapter {
             .map(|ua| ua.to_lowercase().contains("opencode"))
             .unwrap_or(false)
     }
+
+    fn name(&self) -> &str {
+        "opencode"
+    }
     
     fn bypass_signature_matching(&self) -> bool {
         // Opencode 对签名校验较为宽松
@@ -0,0 +1,816 @@
+// 文档输入 (PDF / Office / 纯文本)
+// PDF 以 inlineData 原样发送给 Gemini; docx / xlsx 与纯文本类型在本地提取为文本
+
+use axum::http::StatusCode;
+use base64::Engine;
+use quick_xml::escape::resolve_predefined_entity;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::{Decoder, Reader};
+use std::fmt;
+use std::io::{Cursor, Read};
+
+/// 单个文档的最大字节数 (Gemini inlineData 请求上限约 20MB)
+pub const MAX_DOCUMENT_BYTES: usize = 20 * 1024 * 1024;
+/// 本地提取文本的最大字符数
+pub const MAX_EXTRACTED_CHARS: usize = 1_000_000;
+/// Office 压缩包内单个 XML 条目的最大解压字节数 (防止压缩炸弹)
+const MAX_ZIP_ENTRY_BYTES: u64 = 64 * 1024 * 1024;
+/// xlsx 最大列数 (XFD)
+const MAX_XLSX_COLUMNS: usize = 16_384;
+/// 单个工作表最多展开的单元格数 (含空白填充)；每个单元格至少产生一个分隔符，超出时文本必然超限
+const MAX_SHEET_CELLS: usize = MAX_EXTRACTED_CHARS;
+
+pub const PDF_MIME: &str = "application/pdf";
+pub const DOCX_MIME: &str =
+    "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+pub const XLSX_MIME: &str = "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+const TEXT_EXTENSIONS: &[&str] = &[
+    "txt", "md", "markdown", "csv", "tsv", "json", "jsonl", "xml", "yaml", "yml", "toml", "ini",
+    "log", "html", "htm", "css", "js", "ts", "tsx", "jsx", "py", "rs", "go", "java", "c", "h",
+    "cpp", "hpp", "cs", "rb", "php", "sh", "sql", "swift", "kt",
+];
+
+/// 文档处理错误
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentInputError {
+    /// 超出大小限制
+    TooLarge(String),
+    /// 格式不支持或内容无法解析
+    Invalid(String),
+}
+
+impl DocumentInputError {
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Invalid(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl fmt::Display for DocumentInputError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooLarge(msg) | Self::Invalid(msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// 文档类型
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DocumentKind {
+    Pdf,
+    Docx,
+    Xlsx,
+    PlainText,
+}
+
+/// 处理后的文档
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolvedDocument {
+    /// Gemini 原生支持的格式，以 inlineData 发送 (data 为 base64)
+    Inline { mime_type: String, data: String },
+    /// 本地提取的文本
+    Text(String),
+}
+
+/// 解析 data URL (data:<mime>;base64,<data>)
+pub fn parse_data_url(url: &str) -> Option<(&str, &str)> {
+    let rest = url.strip_prefix("data:")?;
+    let comma = rest.find(',')?;
+    let meta = &rest[..comma];
+    if !meta.ends_with(";base64") && !meta.contains(";base64;") {
+        return None;
+    }
+    let mime = meta.split(';').next().unwrap_or("");
+    Some((mime, &rest[comma + 1..]))
+}
+
+fn display_name(filename: Option<&str>) -> &str {
+    filename.filter(|n| !n.is_empty()).unwrap_or("document")
+}
+
+fn extension(filename: Option<&str>) -> Option<String> {
+    let name = filename?;
+    let (_, ext) = name.rsplit_once('.')?;
+    Some(ext.to_ascii_lowercase())
+}
+
+/// 按文件名或 URL 路径的扩展名推断 MIME (忽略查询串与片段)
+pub fn mime_from_name(name: &str) -> Option<&'static str> {
+    let path = name.split(['?', '#']).next().unwrap_or(name);
+    let file = path.rsplit('/').next().unwrap_or(path);
+    let mime = match extension(Some(file))?.as_str() {
+        "pdf" => PDF_MIME,
+        "docx" => DOCX_MIME,
+        "xlsx" => XLSX_MIME,
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn is_text_mime(mime: &str) -> bool {
+    mime.starts_with("text/")
+        || matches!(
+            mime,
+            "application/json"
+                | "application/xml"
+                | "application/x-yaml"
+                | "application/yaml"
+                | "application/toml"
+                | "application/javascript"
+                | "application/x-ndjson"
+        )
+}
+
+/// 根据 MIME、文件扩展名与文件头判断文档类型
+pub fn detect_kind(
+    mime_type: Option<&str>,
+    filename: Option<&str>,
+    bytes: &[u8],
+) -> Option<DocumentKind> {
+    let mime = mime_type
+        .map(|m| {
+            m.split(';')
+                .next()
+                .unwrap_or("")
+                .trim()
+                .to_ascii_lowercase()
+        })
+        .unwrap_or_default();
+    let ext = extension(filename);
+
+    if bytes.starts_with(b"%PDF") || mime == PDF_MIME || ext.as_deref() == Some("pdf") {
+        return Some(DocumentKind::Pdf);
+    }
+    if mime == DOCX_MIME || ext.as_deref() == Some("docx") {
+        return Some(DocumentKind::Docx);
+    }
+    if mime == XLSX_MIME || ext.as_deref() == Some("xlsx") {
+        return Some(DocumentKind::Xlsx);
+    }
+    if bytes.starts_with(b"PK\x03\x04") {
+        // 未声明类型的 Office 压缩包，按内部结构识别
+        if let Ok(archive) = zip::ZipArchive::new(Cursor::new(bytes)) {
+            let names: Vec<&str> = archive.file_names().collect();
+            if names.contains(&"word/document.xml") {
+                return Some(DocumentKind::Docx);
+            }
+            if names.contains(&"xl/workbook.xml") {
+                return Some(DocumentKind::Xlsx);
+            }
+        }
+        return None;
+    }
+    if is_text_mime(&mime)
+        || ext
+            .as_deref()
+            .map(|e| TEXT_EXTENSIONS.contains(&e))
+            .unwrap_or(false)
+    {
+        return Some(DocumentKind::PlainText);
+    }
+    // 未声明类型时，合法 UTF-8 且不含 NUL 的内容视为纯文本
+    if (mime.is_empty() || mime == "application/octet-stream")
+        && !bytes.contains(&0)
+        && std::str::from_utf8(bytes).is_ok()
+    {
+        return Some(DocumentKind::PlainText);
+    }
+    None
+}
+
+/// 处理 base64 编码的文档
+pub fn resolve_base64_document(
+    data: &str,
+    mime_type: Option<&str>,
+    filename: Option<&str>,
+) -> Result<ResolvedDocument, DocumentInputError> {
+    let name = display_name(filename);
+    let data = data.trim();
+
+    // 解码前按 base64 长度预估，避免解码超大负载
+    let estimated = data.len() / 4 * 3;
+    if estimated > MAX_DOCUMENT_BYTES + 3 {
+        return Err(too_large(name, estimated));
+    }
+
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(data)
+        .map_err(|e| {
+            DocumentInputError::Invalid(format!("File '{}' is not valid base64: {}", name, e))
+        })?;
+    if bytes.len() > MAX_DOCUMENT_BYTES {
+        return Err(too_large(name, bytes.len()));
+    }
+
+    let kind = detect_kind(mime_type, filename, &bytes).ok_or_else(|| {
+        DocumentInputError::Invalid(format!(
+            "File '{}' has unsupported type '{}'; supported: PDF, DOCX, XLSX and plain-text files",
+            name,
+            mime_type.unwrap_or("unknown")
+        ))
+    })?;
+
+    match kind {
+        DocumentKind::Pdf => Ok(ResolvedDocument::Inline {
+            mime_type: PDF_MIME.to_string(),
+            data: data.to_string(),
+        }),
+        DocumentKind::Docx => Ok(ResolvedDocument::Text(check_extracted(
+            name,
+            extract_docx(name, &bytes)?,
+        )?)),
+        DocumentKind::Xlsx => Ok(ResolvedDocument::Text(check_extracted(
+            name,
+            extract_xlsx(name, &bytes)?,
+        )?)),
+        DocumentKind::PlainText => {
+            let text = String::from_utf8_lossy(&bytes).into_owned();
+            Ok(ResolvedDocument::Text(check_extracted(name, text)?))
+        }
+    }
+}
+
+/// 处理 data URL 或裸 base64 形式的文档
+pub fn resolve_document_data(
+    file_data: &str,
+    mime_type: Option<&str>,
+    filename: Option<&str>,
+) -> Result<ResolvedDocument, DocumentInputError> {
+    match parse_data_url(file_data) {
+        Some((mime, data)) => {
+            let mime = if mime.is_empty() {
+                mime_type
+            } else {
+                Some(mime)
+            };
+            resolve_base64_document(data, mime, filename)
+        }
+        None => resolve_base64_document(file_data, mime_type, filename),
+    }
+}
+
+/// 提取出的文本以文档标签包裹，便于模型区分来源
+pub fn wrap_extracted_text(filename: Option<&str>, text: &str) -> String {
+    format!(
+        "<document name=\"{}\">\n{}\n</document>",
+        display_name(filename).replace('"', "'"),
+        text
+    )
+}
+
+fn too_large(name: &str, size: usize) -> DocumentInputError {
+    DocumentInputError::TooLarge(format!(
+        "File '{}' is {:.1} MB, exceeding the {} MB document limit",
+        name,
+        size as f64 / (1024.0 * 1024.0),
+        MAX_DOCUMENT_BYTES / (1024 * 1024)
+    ))
+}
+
+fn check_extracted(name: &str, text: String) -> Result<String, DocumentInputError> {
+    let chars = text.chars().count();
+    if chars > MAX_EXTRACTED_CHARS {
+        return Err(DocumentInputError::TooLarge(format!(
+            "File '{}' contains {} characters of text, exceeding the {} character limit",
+            name, chars, MAX_EXTRACTED_CHARS
+        )));
+    }
+    if text.trim().is_empty() {
+        return Err(DocumentInputError::Invalid(format!(
+            "File '{}' contains no extractable text",
+            name
+        )));
+    }
+    Ok(text)
+}
+
+fn read_zip_entry(
+    archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    entry: &str,
+    name: &str,
+) -> Result<Option<String>, DocumentInputError> {
+    let file = match archive.by_name(entry) {
+        Ok(f) => f,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => {
+            return Err(DocumentInputError::Invalid(format!(
+                "File '{}' is not a valid Office document: {}",
+                name, e
+            )))
+        }
+    };
+    if file.size() > MAX_ZIP_ENTRY_BYTES {
+        return Err(DocumentInputError::TooLarge(format!(
+            "File '{}' expands beyond the {} MB extraction limit",
+            name,
+            MAX_ZIP_ENTRY_BYTES / (1024 * 1024)
+        )));
+    }
+
+    let mut xml = String::new();
+    file.take(MAX_ZIP_ENTRY_BYTES)
+        .read_to_string(&mut xml)
+        .map_err(|e| {
+            DocumentInputError::Invalid(format!("File '{}' could not be read: {}", name, e))
+        })?;
+    Ok(Some(xml))
+}
+
+fn open_archive<'a>(
+    name: &str,
+    bytes: &'a [u8],
+) -> Result<zip::ZipArchive<Cursor<&'a [u8]>>, DocumentInputError> {
+    zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| {
+        DocumentInputError::Invalid(format!(
+            "File '{}' is not a valid Office document: {}",
+            name, e
+        ))
+    })
+}
+
+/// 提取 docx 正文 (段落、制表符、换行)
+fn extract_docx(name: &str, bytes: &[u8]) -> Result<String, DocumentInputError> {
+    let mut archive = open_archive(name, bytes)?;
+    let xml = read_zip_entry(&mut archive, "word/document.xml", name)?.ok_or_else(|| {
+        DocumentInputError::Invalid(format!("File '{}' is missing word/document.xml", name))
+    })?;
+
+    let mut out = String::new();
+    let mut in_text = false;
+    walk_xml(name, "word/document.xml", &xml, |event| {
+        match event {
+            XmlEvent::Open {
+                name: b"t",
+                self_closing,
+                ..
+            } => in_text = !self_closing,
+            XmlEvent::Close(b"t") => in_text = false,
+            XmlEvent::Open { name: b"tab", .. } => out.push('\t'),
+            XmlEvent::Open {
+                name: b"br" | b"cr",
+                ..
+            } => out.push('\n'),
+            XmlEvent::Close(b"p") => out.push('\n'),
+            XmlEvent::Text(text) if in_text => out.push_str(text),
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(out.trim_end().to_string())
+}
+
+/// 提取 xlsx 各工作表内容 (单元格以制表符分隔)
+fn extract_xlsx(name: &str, bytes: &[u8]) -> Result<String, DocumentInputError> {
+    let mut archive = open_archive(name, bytes)?;
+
+    let shared_strings = match read_zip_entry(&mut archive, "xl/sharedStrings.xml", name)? {
+        Some(xml) => parse_shared_strings(name, &xml)?,
+        None => Vec::new(),
+    };
+    let sheet_names = match read_zip_entry(&mut archive, "xl/workbook.xml", name)? {
+        Some(xml) => parse_sheet_names(name, &xml)?,
+        None => Vec::new(),
+    };
+
+    let mut sheets: Vec<(u32, String)> = archive
+        .file_names()
+        .filter_map(|entry| {
+            let number = entry
+                .strip_prefix("xl/worksheets/sheet")?
+                .strip_suffix(".xml")?
+                .parse::<u32>()
+                .ok()?;
+            Some((number, entry.to_string()))
+        })
+        .collect();
+    sheets.sort();
+
+    let mut out = String::new();
+    for (position, (number, entry)) in sheets.iter().enumerate() {
+        let Some(xml) = read_zip_entry(&mut archive, entry, name)? else {
+            continue;
+        };
+        let title = sheet_names
+            .get(position)
+            .cloned()
+            .unwrap_or_else(|| format!("Sheet{}", number));
+        let rows = parse_sheet_rows(name, entry, &title, &xml, &shared_strings)?;
+        if rows.is_empty() {
+            continue;
+        }
+        out.push_str(&format!("## {}\n", title));
+        out.push_str(&rows.join("\n"));
+        out.push_str("\n\n");
+    }
+    Ok(out.trim_end().to_string())
+}
+
+fn parse_shared_strings(name: &str, xml: &str) -> Result<Vec<String>, DocumentInputError> {
+    let mut strings = Vec::new();
+    let mut current = String::new();
+    let mut in_text = false;
+    let mut in_phonetic = false;
+    walk_xml(name, "xl/sharedStrings.xml", xml, |event| {
+        match event {
+            XmlEvent::Open { name: b"si", .. } => current.clear(),
+            XmlEvent::Close(b"si") => strings.push(std::mem::take(&mut current)),
+            XmlEvent::Open {
+                name: b"rPh",
+                self_closing,
+                ..
+            } => in_phonetic = !self_closing,
+            XmlEvent::Close(b"rPh") => in_phonetic = false,
+            XmlEvent::Open {
+                name: b"t",
+                self_closing,
+                ..
+            } => in_text = !self_closing,
+            XmlEvent::Close(b"t") => in_text = false,
+            XmlEvent::Text(text) if in_text && !in_phonetic => current.push_str(text),
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(strings)
+}
+
+fn parse_sheet_names(name: &str, xml: &str) -> Result<Vec<String>, DocumentInputError> {
+    let mut names = Vec::new();
+    walk_xml(name, "xl/workbook.xml", xml, |event| {
+        if let XmlEvent::Open {
+            name: b"sheet",
+            attrs,
+            ..
+        } = event
+        {
+            names.extend(attrs.get(b"name"));
+        }
+        Ok(())
+    })?;
+    Ok(names)
+}
+
+/// 单元格引用 (如 "BC12") 的列序号 (从 0 开始)，无列字母或超出 XFD 时返回 None
+fn column_index(cell_ref: &str) -> Option<usize> {
+    let mut index = 0usize;
+    for c in cell_ref.bytes().take_while(u8::is_ascii_alphabetic) {
+        let digit = (c.to_ascii_uppercase() - b'A') as usize + 1;
+        index = index.checked_mul(26)?.checked_add(digit)?;
+        if index > MAX_XLSX_COLUMNS {
+            return None;
+        }
+    }
+    index.checked_sub(1)
+}
+
+/// 解析工作表各行；展开的单元格数超过 MAX_SHEET_CELLS 时返回 TooLarge
+fn parse_sheet_rows(
+    name: &str,
+    entry: &str,
+    title: &str,
+    xml: &str,
+    shared_strings: &[String],
+) -> Result<Vec<String>, DocumentInputError> {
+    let mut rows = Vec::new();
+    let mut cells: Vec<String> = Vec::new();
+    let mut total_cells = 0usize;
+    let mut cell_type = String::new();
+    // 外层 None: 未声明 r 属性 (按顺序追加)；内层 None: 列引用无效或越界 (忽略该单元格)
+    let mut cell_column: Option<Option<usize>> = None;
+    let mut value = String::new();
+    let mut in_value = false;
+
+    walk_xml(name, entry, xml, |event| {
+        match event {
+            XmlEvent::Open { name: b"row", .. } => cells.clear(),
+            XmlEvent::Close(b"row") => {
+                while cells.last().map(|c| c.is_empty()).unwrap_or(false) {
+                    cells.pop();
+                }
+                if !cells.is_empty() {
+                    rows.push(cells.join("\t"));
+                }
+            }
+            XmlEvent::Open {
+                name: b"c", attrs, ..
+            } => {
+                cell_type = attrs.get(b"t").unwrap_or_default();
+                cell_column = attrs.get(b"r").map(|r| column_index(&r));
+                value.clear();
+            }
+            XmlEvent::Open {
+                name: b"v" | b"t",
+                self_closing,
+                ..
+            } => in_value = !self_closing,
+            XmlEvent::Close(b"v" | b"t") => in_value = false,
+            XmlEvent::Text(text) if in_value => value.push_str(text),
+            XmlEvent::Close(b"c") => {
+                let resolved = if cell_type == "s" {
+                    value
+                        .trim()
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| shared_strings.get(i).cloned())
+                        .unwrap_or_default()
+                } else {
+                    std::mem::take(&mut value)
+                };
+                value.clear();
+                let column = match cell_column {
+                    Some(Some(column)) => column,
+                    Some(None) => return Ok(()),
+                    None => cells.len(),
+                };
+                if column >= cells.len() {
+                    total_cells += column + 1 - cells.len();
+                    if total_cells > MAX_SHEET_CELLS {
+                        return Err(DocumentInputError::TooLarge(format!(
+                            "File '{}' sheet '{}' has more than {} cells",
+                            name, title, MAX_SHEET_CELLS
+                        )));
+                    }
+                    cells.resize(column + 1, String::new());
+                }
+                cells[column] = resolved.replace(['\t', '\n'], " ");
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(rows)
+}
+
+/// 按本地名 (忽略 w: / x: 等命名空间前缀) 报告的 XML 事件
+enum XmlEvent<'e> {
+    Open {
+        name: &'e [u8],
+        attrs: XmlAttrs<'e>,
+        self_closing: bool,
+    },
+    Close(&'e [u8]),
+    /// 已解码的文本 (含实体与字符引用、CDATA)
+    Text(&'e str),
+}
+
+/// 元素属性访问 (同样按本地名匹配)
+struct XmlAttrs<'e> {
+    start: &'e BytesStart<'e>,
+    decoder: Decoder,
+}
+
+impl XmlAttrs<'_> {
+    fn get(&self, key: &[u8]) -> Option<String> {
+        self.start
+            .attributes()
+            .with_checks(false)
+            .flatten()
+            .find(|a| a.key.local_name().as_ref() == key)
+            .and_then(|a| a.decode_and_unescape_value(self.decoder).ok())
+            .map(|v| v.into_owned())
+    }
+}
+
+/// 以 quick-xml 逐个处理 OOXML 事件；XML 格式错误时返回 Invalid
+fn walk_xml<F>(name: &str, entry: &str, xml: &str, mut f: F) -> Result<(), DocumentInputError>
+where
+    F: FnMut(XmlEvent<'_>) -> Result<(), DocumentInputError>,
+{
+    let malformed = |e: &dyn fmt::Display| {
+        DocumentInputError::Invalid(format!(
+            "File '{}' has malformed XML in {}: {}",
+            name, entry, e
+        ))
+    };
+
+    let mut reader = Reader::from_str(xml);
+    let decoder = reader.decoder();
+    loop {
+        let event = reader.read_event().map_err(|e| malformed(&e))?;
+        match &event {
+            Event::Start(start) | Event::Empty(start) => f(XmlEvent::Open {
+                name: start.local_name().into_inner(),
+                attrs: XmlAttrs { start, decoder },
+                self_closing: matches!(event, Event::Empty(_)),
+            })?,
+            Event::End(end) => f(XmlEvent::Close(end.local_name().into_inner()))?,
+            Event::Text(text) => f(XmlEvent::Text(&text.decode().map_err(|e| malformed(&e))?))?,
+            Event::CData(data) => f(XmlEvent::Text(&data.decode().map_err(|e| malformed(&e))?))?,
+            Event::GeneralRef(reference) => {
+                let resolved = match reference.resolve_char_ref().map_err(|e| malformed(&e))? {
+                    Some(c) => c.to_string(),
+                    None => {
+                        let entity = reference.decode().map_err(|e| malformed(&e))?;
+                        resolve_predefined_entity(&entity)
+                            .map(str::to_string)
+                            .unwrap_or_else(|| format!("&{};", entity))
+                    }
+                };
+                f(XmlEvent::Text(&resolved))?
+            }
+            Event::Eof => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn office_zip(entries: &[(&str, &str)]) -> String {
+        let mut buf = Cursor::new(Vec::new());
+        {
+            let mut writer = zip::ZipWriter::new(&mut buf);
+            let options = zip::write::SimpleFileOptions::default()
+                .compression_method(zip::CompressionMethod::Deflated);
+            for (name, content) in entries {
+                writer.start_file(*name, options).unwrap();
+                writer.write_all(content.as_bytes()).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+        base64::engine::general_purpose::STANDARD.encode(buf.into_inner())
+    }
+
+    #[test]
+    fn test_pdf_is_sent_inline() {
+        let data = base64::engine::general_purpose::STANDARD.encode(b"%PDF-1.7 test");
+        let resolved = resolve_document_data(
+            &format!("data:application/pdf;base64,{}", data),
+            None,
+            Some("a.pdf"),
+        )
+        .unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedDocument::Inline {
+                mime_type: PDF_MIME.to_string(),
+                data
+            }
+        );
+    }
+
+    #[test]
+    fn test_docx_text_extraction() {
+        let data = office_zip(&[(
+            "word/document.xml",
+            r#"<?xml version="1.0"?><w:document><w:body><w:p><w:r><w:t>Q3 &amp; Q4</w:t></w:r><w:r><w:tab/><w:t xml:space="preserve">revenue</w:t></w:r></w:p><w:p><w:r><w:t>Second</w:t></w:r></w:p></w:body></w:document>"#,
+        )]);
+        let resolved =
+            resolve_base64_document(&data, Some(DOCX_MIME), Some("report.docx")).unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedDocument::Text("Q3 & Q4\trevenue\nSecond".to_string())
+        );
+    }
+
+    #[test]
+    fn test_xlsx_text_extraction() {
+        let data = office_zip(&[
+            (
+                "xl/workbook.xml",
+                r#"<workbook><sheets><sheet name="Sales" sheetId="1"/></sheets></workbook>"#,
+            ),
+            (
+                "xl/sharedStrings.xml",
+                r#"<sst><si><t>Region</t></si><si><t>Total</t></si><si><r><t>No</t></r><r><t>rth</t></r></si></sst>"#,
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                r#"<worksheet><sheetData><row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="s"><v>1</v></c></row><row r="2"><c r="A2" t="s"><v>2</v></c><c r="C2"><v>42.5</v></c></row></sheetData></worksheet>"#,
+            ),
+        ]);
+        // 未声明 MIME 时按压缩包结构识别
+        let resolved = resolve_base64_document(&data, None, None).unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedDocument::Text("## Sales\nRegion\t\tTotal\nNorth\t\t42.5".to_string())
+        );
+    }
+
+    #[test]
+    fn test_column_index_bounds() {
+        assert_eq!(column_index("A1"), Some(0));
+        assert_eq!(column_index("bc12"), Some(54));
+        assert_eq!(column_index("XFD1"), Some(MAX_XLSX_COLUMNS - 1));
+        assert_eq!(column_index("XFE1"), None);
+        assert_eq!(column_index("ZZZZZZZZZZ1"), None);
+        assert_eq!(column_index(&"Z".repeat(64)), None);
+        assert_eq!(column_index("12"), None);
+    }
+
+    #[test]
+    fn test_xlsx_out_of_range_columns_are_ignored() {
+        let rows = parse_sheet_rows(
+            "t.xlsx",
+            "xl/worksheets/sheet1.xml",
+            "Sheet1",
+            r#"<sheetData><row r="1"><c r="A1"><v>1</v></c><c r="ZZZZZZZZZZ1"><v>2</v></c><c r="B1"><v>3</v></c></row></sheetData>"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(rows, vec!["1\t3".to_string()]);
+
+        // 每行都展开到 XFD 时触发单元格总数上限
+        let row = r#"<row><c r="XFD1"><v>x</v></c></row>"#;
+        let xml = format!(
+            "<sheetData>{}</sheetData>",
+            row.repeat(MAX_SHEET_CELLS / MAX_XLSX_COLUMNS + 1)
+        );
+        let err = parse_sheet_rows("t.xlsx", "xl/worksheets/sheet1.xml", "Sheet1", &xml, &[])
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn test_xml_prefixes_references_and_cdata() {
+        // 元素与属性均按本地名匹配，前缀可任意
+        let data = office_zip(&[(
+            "word/document.xml",
+            r#"<?xml version="1.0"?><doc:document xmlns:doc="urn:w"><doc:body><doc:p><doc:r><doc:t>A&#x2192;B &lt;x&gt;</doc:t><doc:br/><doc:t><![CDATA[1 < 2]]></doc:t></doc:r></doc:p></doc:body></doc:document>"#,
+        )]);
+        let resolved = resolve_base64_document(&data, Some(DOCX_MIME), Some("a.docx")).unwrap();
+        assert_eq!(
+            resolved,
+            ResolvedDocument::Text("A\u{2192}B <x>\n1 < 2".to_string())
+        );
+
+        let rows = parse_sheet_rows(
+            "t.xlsx",
+            "xl/worksheets/sheet1.xml",
+            "Sheet1",
+            r#"<x:sheetData><x:row><x:c x:r="B1" t="inlineStr"><x:is><x:t>R&amp;D</x:t></x:is></x:c></x:row></x:sheetData>"#,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(rows, vec!["\tR&D".to_string()]);
+    }
+
+    #[test]
+    fn test_malformed_xml_is_rejected() {
+        let data = office_zip(&[(
+            "word/document.xml",
+            "<w:document><w:body><w:p><w:t>unclosed</w:p></w:body></w:document>",
+        )]);
+        let err = resolve_base64_document(&data, Some(DOCX_MIME), Some("bad.docx")).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("word/document.xml"));
+    }
+
+    #[test]
+    fn test_plain_text_and_unsupported_types() {
+        let data = base64::engine::general_purpose::STANDARD.encode("a,b\n1,2");
+        assert_eq!(
+            resolve_base64_document(&data, Some("text/csv"), Some("t.csv")).unwrap(),
+            ResolvedDocument::Text("a,b\n1,2".to_string())
+        );
+
+        let binary = base64::engine::general_purpose::STANDARD.encode([0u8, 159, 146, 150]);
+        let err = resolve_base64_document(&binary, Some("application/x-msdownload"), Some("a.exe"))
+            .unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+        assert!(err.to_string().contains("unsupported type"));
+    }
+
+    #[test]
+    fn test_mime_from_name() {
+        assert_eq!(mime_from_name("report.PDF"), Some(PDF_MIME));
+        assert_eq!(
+            mime_from_name("https://example.com/a/slides.png?sig=x.pdf#p=1"),
+            Some("image/png")
+        );
+        assert_eq!(mime_from_name("https://example.com/v1.2/download"), None);
+        assert_eq!(mime_from_name("archive.tar.gz"), None);
+    }
+
+    #[test]
+    fn test_size_limit_is_enforced() {
+        let oversized = "A".repeat((MAX_DOCUMENT_BYTES / 3 + 16) * 4);
+        let err = resolve_base64_document(&oversized, Some(PDF_MIME), Some("big.pdf")).unwrap_err();
+        assert_eq!(err.status_code(), StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(err.to_string().contains("big.pdf"));
+    }
+}
@@ -1,7 +1,10 @@
 use serde_json::{json, Value};
 use once_cell::sync::Lazy;
+use super::client_adapter::ToolNameStyle;
 use super::tool_adapter::ToolAdapter;
-use super::tool_adapters::PencilAdapter;
+use super::tool_adapters::{
+    Context7Adapter, FilesystemAdapter, GithubAdapter, PencilAdapter, PlaywrightAdapter,
+};
 
 /// 不被 Gemini 支持但包含重要语义信息的约束字段
 /// 这些字段将在删除前被转化为 description 提示
@@ -19,15 +22,31 @@ const CONSTRAINT_FIELDS: &[(&str, &str)] = &[
     ("format", "format"),
 ];
 
+/// 白名单之外、但可用于识别 Schema 节点的关键字
+const SCHEMA_KEYWORDS: &[&str] = &[
+    "const",
+    "default",
+    "examples",
+    "additionalProperties",
+    "nullable",
+    "anyOf",
+    "oneOf",
+    "allOf",
+    "not",
+    "$ref",
+    "$schema",
+];
+
 /// 全局工具适配器注册表
 /// 
 /// 所有注册的适配器都会在 Schema 清洗时被检查和应用
 static TOOL_ADAPTERS: Lazy<Vec<Box<dyn ToolAdapter>>> = Lazy::new(|| {
     vec![
         Box::new(PencilAdapter),
-        // 未来可以轻松添加更多适配器:
-        // Box::new(FilesystemAdapter),
-        // Box::new(DatabaseAdapter),
+        Box::new(FilesystemAdapter),
+        Box::new(PlaywrightAdapter),
+        Box::new(GithubAdapter),
+        Box::new(Context7Adapter),
     ]
 });
 
@@ -93,6 +112,14 @@ pub fn clean_json_schema_for_tool(value: &mut Value, tool_name: &str) {
     }
 }
 
+/// 按客户端的 MCP 工具命名约定清洗工具 Schema
+///
+/// 工具名先还原为 `mcp__server__tool` 规范形式再匹配工具适配器，无法还原时按原名匹配
+pub fn clean_json_schema_for_client_tool(value: &mut Value, tool_name: &str, style: ToolNameStyle) {
+    let canonical = style.to_canonical(tool_name);
+    clean_json_schema_for_tool(value, canonical.as_deref().unwrap_or(tool_name));
+}
+
 /// [NEW #952] 递归收集所有层级的 $defs 和 definitions
 ///
 /// MCP 工具的 schema 可能在任意嵌套层级定义 $defs，而非仅在根层级。
@@ -346,7 +373,12 @@ fn clean_json_schema_recursive(value: &mut Value, is_schema_node: bool) -> bool
             // 我们推测这是一个“简写”的对象定义，尝试将其内部 Key 移动到 properties 中。
             // 补充：必须确保它不是工具调用或结果 (含有 functionCall/functionResponse)，防止结构被破坏。
             let is_not_schema_payload = map.contains_key("functionCall") || map.contains_key("functionResponse");
-            if is_schema_node && !has_standard_keyword && !map.is_empty() && !is_not_schema_payload {
+            // 含有其他 JSON Schema 关键字 (如仅有 const/default 的节点) 时不是简写对象
+            let has_other_schema_keyword = map.keys().any(|k| {
+                SCHEMA_KEYWORDS.contains(&k.as_str())
+                    || CONSTRAINT_FIELDS.iter().any(|(field, _)| field == k)
+            });
+            if is_schema_node && !has_standard_keyword && !has_other_schema_keyword && !map.is_empty() && !is_not_schema_payload {
                 let mut properties = serde_json::Map::new();
                 let keys: Vec<String> = map.keys().cloned().collect();
                 for k in keys {
@@ -368,6 +400,12 @@ fn clean_json_schema_recursive(value: &mut Value, is_schema_node: bool) -> bool
             let looks_like_schema = (is_schema_node || has_standard_keyword) && !is_not_schema_payload;
 
             if looks_like_schema {
+                // 3.5 const 转为单值 enum，保留取值语义 (是否改写为字符串见步骤 9)
+                if let Some(const_val) = map.remove("const") {
+                    map.entry("enum".to_string())
+                        .or_insert_with(|| Value::Array(vec![const_val]));
+                }
+
                 // 4. [ROBUST] 约束迁移：在被白名单过滤前，将校验项转为描述 Hint
                 // [NEW] 使用统一的约束回填函数
                 move_constraints_to_description(map);
@@ -411,12 +449,21 @@ fn clean_json_schema_recursive(value: &mut Value, is_schema_node: bool) -> bool
                 }
 
                 if !map.contains_key("type") {
-                    if map.contains_key("enum") {
-                        map.insert("type".to_string(), Value::String("string".to_string()));
+                    // enum 取值类型一致时沿用其类型 (数值/布尔不应被当作字符串)
+                    let enum_type = map
+                        .get("enum")
+                        .and_then(|v| v.as_array())
+                        .map(Vec::as_slice)
+                        .and_then(enum_value_type);
+                    if let Some(inferred) = enum_type {
+                        map.insert("type".to_string(), Value::String(inferred.to_string()));
                     } else if map.contains_key("properties") {
                         map.insert("type".to_string(), Value::String("object".to_string()));
                     } else if map.contains_key("items") {
                         map.insert("type".to_string(), Value::String("array".to_string()));
+                    } else {
+                        // Gemini 要求每个 Schema 节点都声明 type (如 `{}` 或仅有 description 的节点)
+                        map.insert("type".to_string(), Value::String("string".to_string()));
                     }
                 }
 
@@ -474,18 +521,39 @@ fn clean_json_schema_recursive(value: &mut Value, is_schema_node: bool) -> bool
                     }
                 }
 
-                // 9. Enum 值强制转字符串
-                if let Some(Value::Array(arr)) = map.get_mut("enum") {
-                    for item in arr {
-                        if !item.is_string() {
-                            *item = Value::String(if item.is_null() {
-                                "null".to_string()
-                            } else {
-                                item.to_string()
-                            });
-                        }
+                // 9. Enum 处理: Gemini 仅接受 STRING 类型的 enum
+                // 只有 string 节点保留字符串 enum；数值/布尔/对象节点保留原类型，
+                // 取值移入描述，避免模型输出 "1" / "true" 导致工具参数校验失败
+                if let Some(Value::Array(values)) = map.remove("enum") {
+                    if map.get("type").and_then(|t| t.as_str()) == Some("string") {
+                        let values: Vec<Value> = values
+                            .into_iter()
+                            .map(|item| match item {
+                                Value::String(_) => item,
+                                Value::Null => Value::String("null".to_string()),
+                                other => Value::String(other.to_string()),
+                            })
+                            .collect();
+                        map.insert("enum".to_string(), Value::Array(values));
+                    } else {
+                        let allowed: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                        append_hint_to_description(map, format!("[Allowed values: {}]", allowed.join(", ")));
                     }
                 }
+
+                // 10. [SAFETY] 数组必须声明单个 items Schema，否则 Gemini 返回 "items: missing field"
+                // 元组形式 (items 为数组) 取第一个分支
+                if map.get("type").and_then(|t| t.as_str()) == Some("array") {
+                    let items = match map.remove("items") {
+                        Some(Value::Object(obj)) => Some(Value::Object(obj)),
+                        Some(Value::Array(arr)) => arr.into_iter().find(|v| v.is_object()),
+                        _ => None,
+                    };
+                    map.insert(
+                        "items".to_string(),
+                        items.unwrap_or_else(|| json!({ "type": "string" })),
+                    );
+                }
             }
         }
         Value::Array(arr) => {
@@ -616,6 +684,23 @@ fn move_constraints_to_description(map: &mut serde_json::Map<String, Value>) {
     }
 }
 
+/// enum 取值全部为同一标量类型时返回该 JSON Schema 类型
+fn enum_value_type(values: &[Value]) -> Option<&'static str> {
+    if values.is_empty() {
+        None
+    } else if values.iter().all(|v| v.is_string()) {
+        Some("string")
+    } else if values.iter().all(|v| v.is_i64() || v.is_u64()) {
+        Some("integer")
+    } else if values.iter().all(|v| v.is_number()) {
+        Some("number")
+    } else if values.iter().all(|v| v.is_boolean()) {
+        Some("boolean")
+    } else {
+        None
+    }
+}
+
 /// [NEW] 计算 Schema 分支的复杂度得分 (用于 anyOf/oneOf 择优)
 /// 评分标准: Object (3) > Array (2) > Scalar (1) > Null (0)
 fn score_schema_option(val: &Value) -> i32 {
@@ -792,6 +877,26 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn test_clean_json_schema_for_client_tool_canonicalizes_name() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "opacity": { "type": "number" } }
+        });
+        let opacity_desc = |mut s: Value, style: ToolNameStyle| {
+            clean_json_schema_for_client_tool(&mut s, "mcp_pencil_update_shape", style);
+            s["properties"]["opacity"]["description"].as_str().map(|d| d.to_string())
+        };
+
+        // Cursor 风格的工具名还原后命中 Pencil 适配器
+        assert_eq!(
+            opacity_desc(schema.clone(), ToolNameStyle::McpSingleUnderscore).as_deref(),
+            Some("Visual property for UI elements")
+        );
+        // 按默认约定无法识别，只做通用清洗
+        assert_eq!(opacity_desc(schema, ToolNameStyle::McpDoubleUnderscore), None);
+    }
+
     #[test]
     fn test_clean_json_schema_draft_2020_12() {
         let mut schema = json!({
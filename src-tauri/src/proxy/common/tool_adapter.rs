@@ -41,6 +41,30 @@ pub trait ToolAdapter: Send + Sync {
     }
 }
 
+/// 辅助函数: 判断工具名是否属于指定的 MCP 服务器
+///
+/// 兼容不同客户端的命名约定:
+/// `mcp__server__tool` (Claude Code)、`mcp_server_tool` (Cursor)、`server__tool` (Gemini CLI)
+pub fn is_mcp_tool_of(tool_name: &str, servers: &[&str]) -> bool {
+    servers.iter().any(|server| {
+        tool_name.starts_with(&format!("mcp__{}__", server))
+            || tool_name.starts_with(&format!("mcp_{}_", server))
+            || tool_name.starts_with(&format!("{}__", server))
+    })
+}
+
+/// 辅助函数: 向 properties 中指定参数的 description 追加提示
+///
+/// 参数不存在时不做任何处理
+pub fn append_hint_to_property(schema: &mut Value, property: &str, hint: &str) {
+    if let Some(prop) = schema
+        .get_mut("properties")
+        .and_then(|p| p.get_mut(property))
+    {
+        append_hint_to_schema(prop, hint);
+    }
+}
+
 /// 辅助函数: 向 Schema 的 description 字段追加提示
 pub fn append_hint_to_schema(schema: &mut Value, hint: &str) {
     if let Value::Object(map) = schema {
@@ -83,6 +107,25 @@ mod tests {
         assert!(!adapter.matches("other__function"));
     }
 
+    #[test]
+    fn test_is_mcp_tool_of() {
+        let servers = ["github"];
+        assert!(is_mcp_tool_of("mcp__github__create_issue", &servers));
+        assert!(is_mcp_tool_of("mcp_github_create_issue", &servers));
+        assert!(is_mcp_tool_of("github__create_issue", &servers));
+        assert!(!is_mcp_tool_of("mcp__gitlab__create_issue", &servers));
+        assert!(!is_mcp_tool_of("create_issue", &servers));
+    }
+
+    #[test]
+    fn test_append_hint_to_property() {
+        let mut schema = json!({"type": "object", "properties": {"ref": {"type": "string"}}});
+        append_hint_to_property(&mut schema, "ref", "From snapshot.");
+        append_hint_to_property(&mut schema, "missing", "Ignored.");
+        assert_eq!(schema["properties"]["ref"]["description"], "From snapshot.");
+        assert!(schema["properties"].get("missing").is_none());
+    }
+
     #[test]
     fn test_append_hint() {
         let mut schema = json!({"type": "string"});
@@ -0,0 +1,55 @@
+use serde_json::Value;
+use super::super::tool_adapter::{ToolAdapter, append_hint_to_property, is_mcp_tool_of};
+
+/// Context7 MCP 工具适配器 (@upstash/context7-mcp)
+///
+/// 1. 库 ID 参数追加提示: 模型常把库名直接传给 `get-library-docs`，而非 resolve 得到的 ID
+/// 2. 兼容不带服务器前缀的工具名 (`resolve-library-id` / `get-library-docs`)
+pub struct Context7Adapter;
+
+const LIBRARY_ID_HINT: &str =
+    "Must be the exact ID returned by resolve-library-id (format: /org/project), not a plain library name.";
+
+impl ToolAdapter for Context7Adapter {
+    fn matches(&self, tool_name: &str) -> bool {
+        is_mcp_tool_of(tool_name, &["context7"])
+            || tool_name == "resolve-library-id"
+            || tool_name == "get-library-docs"
+    }
+
+    fn pre_process(&self, schema: &mut Value) -> Result<(), String> {
+        append_hint_to_property(schema, "context7CompatibleLibraryID", LIBRARY_ID_HINT);
+        append_hint_to_property(schema, "libraryId", LIBRARY_ID_HINT);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_context7_adapter_matches() {
+        let adapter = Context7Adapter;
+        assert!(adapter.matches("mcp__context7__get-library-docs"));
+        assert!(adapter.matches("resolve-library-id"));
+        assert!(!adapter.matches("mcp__filesystem__read_file"));
+    }
+
+    #[test]
+    fn test_context7_library_id_hint() {
+        let adapter = Context7Adapter;
+        let mut schema = json!({
+            "type": "object",
+            "properties": {"context7CompatibleLibraryID": {"type": "string"}}
+        });
+
+        adapter.pre_process(&mut schema).unwrap();
+
+        assert!(schema["properties"]["context7CompatibleLibraryID"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("resolve-library-id"));
+    }
+}
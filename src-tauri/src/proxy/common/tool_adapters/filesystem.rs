@@ -0,0 +1,84 @@
+use serde_json::Value;
+use super::super::tool_adapter::{ToolAdapter, append_hint_to_schema, is_mcp_tool_of};
+
+/// Filesystem MCP 工具适配器 (@modelcontextprotocol/server-filesystem)
+///
+/// 1. 路径参数追加提示: 服务器只允许访问白名单目录，模型常传入相对路径导致调用失败
+/// 2. `edit_file` 的 `edits` 补全条目必填字段，避免模型生成缺少 oldText 的编辑
+pub struct FilesystemAdapter;
+
+const PATH_PARAMS: &[&str] = &["path", "paths", "source", "destination"];
+const PATH_HINT: &str =
+    "Use absolute paths inside the allowed directories (see list_allowed_directories).";
+
+impl ToolAdapter for FilesystemAdapter {
+    fn matches(&self, tool_name: &str) -> bool {
+        is_mcp_tool_of(tool_name, &["filesystem", "server-filesystem", "fs"])
+    }
+
+    fn pre_process(&self, schema: &mut Value) -> Result<(), String> {
+        if let Some(Value::Object(props)) = schema.get_mut("properties") {
+            for (key, value) in props.iter_mut() {
+                if PATH_PARAMS.contains(&key.as_str()) {
+                    append_hint_to_schema(value, PATH_HINT);
+                }
+            }
+
+            // edits: [{ oldText, newText }]
+            if let Some(Value::Object(item_map)) =
+                props.get_mut("edits").and_then(|e| e.get_mut("items"))
+            {
+                if !item_map.contains_key("required") {
+                    item_map.insert(
+                        "required".to_string(),
+                        serde_json::json!(["oldText", "newText"]),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filesystem_adapter_matches() {
+        let adapter = FilesystemAdapter;
+        assert!(adapter.matches("mcp__filesystem__read_file"));
+        assert!(adapter.matches("filesystem__write_file"));
+        assert!(!adapter.matches("mcp__github__get_file_contents"));
+    }
+
+    #[test]
+    fn test_filesystem_path_hints_and_edits() {
+        let adapter = FilesystemAdapter;
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "path": {"type": "string"},
+                "edits": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {"oldText": {"type": "string"}, "newText": {"type": "string"}}
+                    }
+                }
+            }
+        });
+
+        adapter.pre_process(&mut schema).unwrap();
+
+        assert!(schema["properties"]["path"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("absolute paths"));
+        assert_eq!(
+            schema["properties"]["edits"]["items"]["required"],
+            json!(["oldText", "newText"])
+        );
+    }
+}
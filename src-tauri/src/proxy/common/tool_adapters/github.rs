@@ -0,0 +1,67 @@
+use serde_json::{json, Value};
+use super::super::tool_adapter::{ToolAdapter, append_hint_to_property, is_mcp_tool_of};
+
+/// GitHub MCP 工具适配器 (github-mcp-server)
+///
+/// 1. 部分版本的数组参数 (labels, assignees, reviewers 等) 缺少 `items`，
+///    Gemini 会以 "items: missing field" 拒绝整个请求，此处补全为字符串数组
+/// 2. `sha` 参数追加提示: 更新已有文件时必须提供，否则上游返回 409
+pub struct GithubAdapter;
+
+const STRING_ARRAY_PARAMS: &[&str] = &["labels", "assignees", "reviewers", "team_reviewers"];
+
+impl ToolAdapter for GithubAdapter {
+    fn matches(&self, tool_name: &str) -> bool {
+        is_mcp_tool_of(tool_name, &["github"])
+    }
+
+    fn pre_process(&self, schema: &mut Value) -> Result<(), String> {
+        if let Some(Value::Object(props)) = schema.get_mut("properties") {
+            for key in STRING_ARRAY_PARAMS {
+                if let Some(Value::Object(prop)) = props.get_mut(*key) {
+                    let is_array = prop.get("type").and_then(|t| t.as_str()) == Some("array");
+                    if is_array && !prop.contains_key("items") {
+                        prop.insert("items".to_string(), json!({"type": "string"}));
+                    }
+                }
+            }
+        }
+
+        append_hint_to_property(
+            schema,
+            "sha",
+            "Required when updating an existing file: the blob SHA of the file being replaced.",
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_github_adapter_matches() {
+        let adapter = GithubAdapter;
+        assert!(adapter.matches("mcp__github__create_issue"));
+        assert!(adapter.matches("mcp_github_list_issues"));
+        assert!(!adapter.matches("mcp__gitlab__create_issue"));
+    }
+
+    #[test]
+    fn test_github_array_items_filled() {
+        let adapter = GithubAdapter;
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "labels": {"type": "array", "description": "Labels to apply"},
+                "assignees": {"type": "array", "items": {"type": "string"}}
+            }
+        });
+
+        adapter.pre_process(&mut schema).unwrap();
+
+        assert_eq!(schema["properties"]["labels"]["items"], json!({"type": "string"}));
+        assert_eq!(schema["properties"]["assignees"]["items"], json!({"type": "string"}));
+    }
+}
@@ -1,3 +1,11 @@
+pub mod context7;
+pub mod filesystem;
+pub mod github;
 pub mod pencil;
+pub mod playwright;
 
+pub use context7::Context7Adapter;
+pub use filesystem::FilesystemAdapter;
+pub use github::GithubAdapter;
 pub use pencil::PencilAdapter;
+pub use playwright::PlaywrightAdapter;
@@ -0,0 +1,59 @@
+use serde_json::Value;
+use super::super::tool_adapter::{ToolAdapter, append_hint_to_property, is_mcp_tool_of};
+
+/// Playwright MCP 工具适配器 (@playwright/mcp)
+///
+/// 1. `ref` / `element` 参数追加提示: 模型常凭空编造 ref 或传入 CSS 选择器
+/// 2. 兼容不带服务器前缀的工具名 (`browser_click` 等)，部分客户端不加前缀
+pub struct PlaywrightAdapter;
+
+impl ToolAdapter for PlaywrightAdapter {
+    fn matches(&self, tool_name: &str) -> bool {
+        is_mcp_tool_of(tool_name, &["playwright"]) || tool_name.starts_with("browser_")
+    }
+
+    fn pre_process(&self, schema: &mut Value) -> Result<(), String> {
+        append_hint_to_property(
+            schema,
+            "ref",
+            "Exact element reference (e.g. 'e12') copied from the latest browser_snapshot; not a CSS selector.",
+        );
+        append_hint_to_property(
+            schema,
+            "element",
+            "Human-readable element description used to obtain permission to interact with the element.",
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_playwright_adapter_matches() {
+        let adapter = PlaywrightAdapter;
+        assert!(adapter.matches("mcp__playwright__browser_click"));
+        assert!(adapter.matches("browser_snapshot"));
+        assert!(!adapter.matches("mcp__pencil__create_shape"));
+    }
+
+    #[test]
+    fn test_playwright_ref_hint() {
+        let adapter = PlaywrightAdapter;
+        let mut schema = json!({
+            "type": "object",
+            "properties": {
+                "ref": {"type": "string", "description": "Exact target element reference from the page snapshot"}
+            }
+        });
+
+        adapter.pre_process(&mut schema).unwrap();
+
+        let desc = schema["properties"]["ref"]["description"].as_str().unwrap();
+        assert!(desc.starts_with("Exact target element reference"));
+        assert!(desc.contains("not a CSS selector"));
+    }
+}
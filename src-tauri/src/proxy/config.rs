@@ -1,3 +1,4 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 // use std::path::PathBuf;
 use std::collections::HashMap;
@@ -40,7 +41,130 @@ pub fn update_thinking_budget_config(config: ThinkingBudgetConfig) {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+// ============================================================================
+// 全局工具结果压缩配置存储
+// ============================================================================
+static GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG: OnceLock<RwLock<ToolResultCompressionConfig>> =
+    OnceLock::new();
+
+/// 获取当前工具结果压缩配置
+pub fn get_tool_result_compression_config() -> ToolResultCompressionConfig {
+    GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局工具结果压缩配置
+pub fn update_tool_result_compression_config(config: ToolResultCompressionConfig) {
+    if let Some(lock) = GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config.clone();
+        }
+    } else {
+        let _ = GLOBAL_TOOL_RESULT_COMPRESSION_CONFIG.set(RwLock::new(config.clone()));
+    }
+    tracing::info!(
+        "[ToolCompressor] Config updated: {} per-tool strategies",
+        config.tool_strategies.len()
+    );
+}
+
+// ============================================================================
+// 全局日志保留策略存储
+// 供 monitor 记录请求体与后台清理任务读取
+// ============================================================================
+static GLOBAL_LOG_RETENTION_CONFIG: OnceLock<RwLock<LogRetentionConfig>> = OnceLock::new();
+
+/// 获取当前日志保留策略
+pub fn get_log_retention_config() -> LogRetentionConfig {
+    GLOBAL_LOG_RETENTION_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局日志保留策略
+pub fn update_log_retention_config(config: LogRetentionConfig) {
+    if let Some(lock) = GLOBAL_LOG_RETENTION_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config.clone();
+        }
+    } else {
+        let _ = GLOBAL_LOG_RETENTION_CONFIG.set(RwLock::new(config.clone()));
+    }
+    tracing::info!(
+        "[Log-Retention] Config updated: body_capture={:?}, enabled={}",
+        config.body_capture,
+        config.enabled
+    );
+}
+
+// ============================================================================
+// 全局服务端工具配置存储
+// 供 Claude 请求映射与服务端工具循环读取
+// ============================================================================
+static GLOBAL_SERVER_TOOLS_CONFIG: OnceLock<RwLock<ServerToolsConfig>> = OnceLock::new();
+
+/// 获取当前服务端工具配置
+pub fn get_server_tools_config() -> ServerToolsConfig {
+    GLOBAL_SERVER_TOOLS_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局服务端工具配置
+pub fn update_server_tools_config(config: ServerToolsConfig) {
+    if let Some(lock) = GLOBAL_SERVER_TOOLS_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config.clone();
+        }
+    } else {
+        let _ = GLOBAL_SERVER_TOOLS_CONFIG.set(RwLock::new(config.clone()));
+    }
+    tracing::info!(
+        "[Server-Tools] Config updated: enabled={}, web_fetch={}, max_iterations={}",
+        config.enabled,
+        config.web_fetch,
+        config.max_iterations
+    );
+}
+
+// ============================================================================
+// 全局 Message Batches 配置存储
+// 供批次 worker 派发请求时读取并发预算
+// ============================================================================
+static GLOBAL_MESSAGE_BATCHES_CONFIG: OnceLock<RwLock<MessageBatchesConfig>> = OnceLock::new();
+
+/// 获取当前 Message Batches 配置
+pub fn get_message_batches_config() -> MessageBatchesConfig {
+    GLOBAL_MESSAGE_BATCHES_CONFIG
+        .get()
+        .and_then(|lock| lock.read().ok())
+        .map(|cfg| cfg.clone())
+        .unwrap_or_default()
+}
+
+/// 更新全局 Message Batches 配置
+pub fn update_message_batches_config(config: MessageBatchesConfig) {
+    if let Some(lock) = GLOBAL_MESSAGE_BATCHES_CONFIG.get() {
+        if let Ok(mut cfg) = lock.write() {
+            *cfg = config.clone();
+        }
+    } else {
+        let _ = GLOBAL_MESSAGE_BATCHES_CONFIG.set(RwLock::new(config.clone()));
+    }
+    tracing::info!(
+        "[Batches] Config updated: max_concurrency={}",
+        config.max_concurrency
+    );
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ProxyAuthMode {
     Off,
@@ -55,7 +179,7 @@ impl Default for ProxyAuthMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ZaiDispatchMode {
     /// Never use z.ai.
@@ -74,7 +198,7 @@ impl Default for ZaiDispatchMode {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ZaiModelDefaults {
     /// Default model for "opus" family (when the incoming model is a Claude id).
     #[serde(default = "default_zai_opus_model")]
@@ -97,7 +221,7 @@ impl Default for ZaiModelDefaults {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ZaiMcpConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -120,7 +244,7 @@ impl Default for ZaiMcpConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ZaiConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -155,7 +279,7 @@ impl Default for ZaiConfig {
 }
 
 /// 实验性功能配置 (Feature Flags)
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ExperimentalConfig {
     /// 启用双层签名缓存 (Signature Cache)
     #[serde(default = "default_true")]
@@ -175,6 +299,12 @@ pub struct ExperimentalConfig {
     #[serde(default = "default_false")]
     pub enable_usage_scaling: bool,
 
+    /// 启用 OpenAI / Gemini 协议的上下文压缩管线
+    /// (Claude 协议仍由 enable_usage_scaling 联动控制)
+    /// 默认关闭: 压缩会改写历史，需用户显式开启
+    #[serde(default = "default_false")]
+    pub enable_context_compression: bool,
+
     /// 上下文压缩阈值 L1 (Tool Trimming)
     #[serde(default = "default_threshold_l1")]
     pub context_compression_threshold_l1: f32,
@@ -195,6 +325,7 @@ impl Default for ExperimentalConfig {
             enable_tool_loop_recovery: true,
             enable_cross_model_checks: true,
             enable_usage_scaling: false, // 默认关闭,回归透明模式
+            enable_context_compression: false, // 默认关闭,需显式开启
             context_compression_threshold_l1: 0.4,
             context_compression_threshold_l2: 0.55,
             context_compression_threshold_l3: 0.7,
@@ -214,7 +345,7 @@ fn default_threshold_l3() -> f32 {
 
 /// Thinking Budget 模式
 /// 控制如何处理调用方传入的 thinking_budget 参数
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ThinkingBudgetMode {
     /// 自动限制：对特定模型（Flash/Thinking）应用 24576 上限
@@ -232,7 +363,7 @@ impl Default for ThinkingBudgetMode {
 }
 
 /// Thinking Budget 配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ThinkingBudgetConfig {
     /// 模式选择
     #[serde(default)]
@@ -240,6 +371,9 @@ pub struct ThinkingBudgetConfig {
     /// 自定义固定值（仅在 mode=Custom 时生效）
     #[serde(default = "default_thinking_budget_custom_value")]
     pub custom_value: u32,
+    /// 按模型设置的预算上限（支持 * 通配符，Auto 模式下覆盖模型族默认上限，Passthrough 模式不生效）
+    #[serde(default)]
+    pub model_caps: HashMap<String, u32>,
 }
 
 impl Default for ThinkingBudgetConfig {
@@ -247,6 +381,7 @@ impl Default for ThinkingBudgetConfig {
         Self {
             mode: ThinkingBudgetMode::Auto,
             custom_value: default_thinking_budget_custom_value(),
+            model_caps: HashMap::new(),
         }
     }
 }
@@ -255,6 +390,51 @@ fn default_thinking_budget_custom_value() -> u32 {
     24576
 }
 
+/// 工具结果压缩策略
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolResultStrategy {
+    /// 根据内容自动识别 (JSON / Diff / 日志 / 表格)
+    #[default]
+    Auto,
+    /// 保留 JSON 结构，长数组与长字符串省略并标注数量
+    Json,
+    /// 合并重复行，保留首个与最后一个错误块
+    Log,
+    /// 保留文件头与 hunk 头，按预算保留 hunk 内容
+    Diff,
+    /// 保留表头与首尾样本行
+    Table,
+    /// 直接截断
+    Truncate,
+}
+
+/// 工具结果压缩配置
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
+pub struct ToolResultCompressionConfig {
+    /// 按工具名指定压缩策略 (key 支持以 `*` 结尾的前缀匹配，如 `mcp__github__*`)
+    #[serde(default)]
+    pub tool_strategies: HashMap<String, ToolResultStrategy>,
+}
+
+impl ToolResultCompressionConfig {
+    /// 查找工具对应的策略: 精确匹配优先，其次是最长的前缀匹配
+    pub fn strategy_for(&self, tool_name: &str) -> ToolResultStrategy {
+        if let Some(strategy) = self.tool_strategies.get(tool_name) {
+            return *strategy;
+        }
+        self.tool_strategies
+            .iter()
+            .filter_map(|(pattern, strategy)| {
+                let prefix = pattern.strip_suffix('*')?;
+                tool_name.starts_with(prefix).then_some((prefix.len(), *strategy))
+            })
+            .max_by_key(|(len, _)| *len)
+            .map(|(_, strategy)| strategy)
+            .unwrap_or_default()
+    }
+}
+
 fn default_true() -> bool {
     true
 }
@@ -263,7 +443,7 @@ fn default_false() -> bool {
     false
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct DebugLoggingConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -280,8 +460,185 @@ impl Default for DebugLoggingConfig {
     }
 }
 
+/// 请求 / 响应体记录方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BodyCaptureMode {
+    /// 不记录请求 / 响应体
+    None,
+    /// 仅记录大小
+    MetadataOnly,
+    /// 截断至 body_max_kb
+    Truncated,
+    /// 完整记录
+    #[default]
+    Full,
+    /// 失败请求完整记录，成功请求仅记录大小
+    ErrorsOnlyFull,
+}
+
+/// 单个日志存储的保留上限 (None 表示不限制)
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct StoreRetention {
+    /// 最长保留天数
+    #[serde(default)]
+    pub max_age_days: Option<u32>,
+    /// 最多保留条数 (调试日志目录为文件数)
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+    /// 最大磁盘占用 (MB)
+    #[serde(default)]
+    pub max_size_mb: Option<u64>,
+}
+
+impl StoreRetention {
+    pub fn max_age(days: u32) -> Self {
+        Self {
+            max_age_days: Some(days),
+            ..Default::default()
+        }
+    }
+
+    pub fn max_size_bytes(&self) -> Option<u64> {
+        self.max_size_mb.map(|mb| mb * 1024 * 1024)
+    }
+}
+
+/// 日志保留策略 (请求日志 / IP 访问日志 / 调试日志目录)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct LogRetentionConfig {
+    /// 是否启用后台清理任务 (默认开启: 请求日志保留 30 天，与旧版启动清理一致)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// 请求日志 (proxy_logs.db)
+    #[serde(default = "default_request_log_retention")]
+    pub request_logs: StoreRetention,
+
+    /// IP 访问日志 (security.db)
+    #[serde(default = "default_request_log_retention")]
+    pub ip_access_logs: StoreRetention,
+
+    /// 调试日志目录 (debug_logging.output_dir)
+    #[serde(default = "default_debug_log_retention")]
+    pub debug_logs: StoreRetention,
+
+    /// Message Batches 结果 (默认 29 天，与 Anthropic 一致)
+    #[serde(default = "default_message_batch_retention")]
+    pub message_batches: StoreRetention,
+
+    /// 请求日志中请求 / 响应体的记录方式
+    #[serde(default)]
+    pub body_capture: BodyCaptureMode,
+
+    /// Truncated 模式下每个请求 / 响应体保留的大小 (KB)
+    #[serde(default = "default_body_max_kb")]
+    pub body_max_kb: usize,
+
+    /// 定期 VACUUM 间隔 (小时，0 表示仅在超出磁盘上限时执行)
+    #[serde(default = "default_vacuum_interval_hours")]
+    pub vacuum_interval_hours: u64,
+
+    /// 清理任务执行间隔 (分钟)
+    #[serde(default = "default_retention_check_minutes")]
+    pub check_interval_minutes: u64,
+}
+
+fn default_request_log_retention() -> StoreRetention {
+    StoreRetention::max_age(30)
+}
+
+fn default_debug_log_retention() -> StoreRetention {
+    StoreRetention::max_age(7)
+}
+
+fn default_message_batch_retention() -> StoreRetention {
+    StoreRetention::max_age(29)
+}
+
+fn default_body_max_kb() -> usize {
+    64
+}
+
+fn default_vacuum_interval_hours() -> u64 {
+    24
+}
+
+fn default_retention_check_minutes() -> u64 {
+    60
+}
+
+impl Default for LogRetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            request_logs: default_request_log_retention(),
+            ip_access_logs: default_request_log_retention(),
+            debug_logs: default_debug_log_retention(),
+            message_batches: default_message_batch_retention(),
+            body_capture: BodyCaptureMode::default(),
+            body_max_kb: default_body_max_kb(),
+            vacuum_interval_hours: default_vacuum_interval_hours(),
+            check_interval_minutes: default_retention_check_minutes(),
+        }
+    }
+}
+
+/// 服务端工具循环配置
+///
+/// 客户端同时声明 web_search 与本地函数工具时，由代理以合成函数暴露联网搜索，
+/// 拦截模型的调用并通过独立的 grounding 子请求执行，再把结果回填给模型
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ServerToolsConfig {
+    /// 是否启用服务端工具循环 (关闭时保持旧行为: 有函数工具则放弃联网)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// 客户端声明 web_fetch 工具时是否一并暴露
+    #[serde(default = "default_true")]
+    pub web_fetch: bool,
+
+    /// 单个请求内最多执行的服务端工具轮次
+    #[serde(default = "default_server_tool_iterations")]
+    pub max_iterations: u32,
+}
+
+fn default_server_tool_iterations() -> u32 {
+    5
+}
+
+impl Default for ServerToolsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            web_fetch: true,
+            max_iterations: default_server_tool_iterations(),
+        }
+    }
+}
+
+/// Message Batches 配置
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct MessageBatchesConfig {
+    /// 所有批次共享的并发请求上限 (避免批处理挤占交互流量)，实际并发还受可用账号数限制
+    #[serde(default = "default_batch_concurrency")]
+    pub max_concurrency: usize,
+}
+
+fn default_batch_concurrency() -> usize {
+    4
+}
+
+impl Default for MessageBatchesConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrency: default_batch_concurrency(),
+        }
+    }
+}
+
 /// IP 黑名单配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpBlacklistConfig {
     /// 是否启用黑名单
     #[serde(default)]
@@ -306,7 +663,7 @@ fn default_block_message() -> String {
 }
 
 /// IP 白名单配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct IpWhitelistConfig {
     /// 是否启用白名单模式 (启用后只允许白名单IP访问)
     #[serde(default)]
@@ -327,7 +684,7 @@ impl Default for IpWhitelistConfig {
 }
 
 /// 安全监控配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SecurityMonitorConfig {
     /// IP 黑名单配置
     #[serde(default)]
@@ -347,8 +704,65 @@ impl Default for SecurityMonitorConfig {
     }
 }
 
+/// 请求头匹配规则
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
+pub struct HeaderMatchRule {
+    /// 请求头名称 (不区分大小写)
+    pub name: String,
+    /// 请求头值的正则 (不区分大小写)，为空时只要求该请求头存在
+    #[serde(default)]
+    pub value_pattern: Option<String>,
+}
+
+/// 自定义客户端适配器
+/// 无需重新编译即可为新客户端声明匹配规则与行为开关
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
+pub struct CustomClientAdapterConfig {
+    /// 适配器名称 (用于日志)
+    pub name: String,
+
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// User-Agent 正则 (不区分大小写)
+    #[serde(default)]
+    pub user_agent_pattern: Option<String>,
+
+    /// 请求头匹配规则 (需全部满足，且与 user_agent_pattern 同时生效)
+    #[serde(default)]
+    pub header_matches: Vec<HeaderMatchRule>,
+
+    /// 绕过 thinking 签名校验
+    #[serde(default)]
+    pub bypass_signature_matching: bool,
+
+    /// 快速失败，不做多轮重试
+    #[serde(default)]
+    pub let_it_crash: bool,
+
+    /// 签名缓存策略
+    #[serde(default)]
+    pub signature_buffer_strategy: crate::proxy::common::client_adapter::SignatureBufferStrategy,
+
+    /// 需要注入的 anthropic-beta 值
+    #[serde(default)]
+    pub beta_headers: Vec<String>,
+
+    /// 流式错误事件格式
+    #[serde(default)]
+    pub sse_error_format: crate::proxy::common::client_adapter::SseErrorFormat,
+
+    /// MCP 工具命名约定
+    #[serde(default)]
+    pub tool_name_style: crate::proxy::common::client_adapter::ToolNameStyle,
+
+    /// 支持的协议 (为空时仅 Anthropic)
+    #[serde(default)]
+    pub protocols: Vec<crate::proxy::common::client_adapter::Protocol>,
+}
+
 /// 反代服务配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProxyConfig {
     /// 是否启用反代服务
     pub enabled: bool,
@@ -395,6 +809,10 @@ pub struct ProxyConfig {
     #[serde(default)]
     pub debug_logging: DebugLoggingConfig,
 
+    /// 日志保留策略
+    #[serde(default)]
+    pub log_retention: LogRetentionConfig,
+
     /// 上游代理配置
     #[serde(default)]
     pub upstream_proxy: UpstreamProxyConfig,
@@ -437,10 +855,26 @@ pub struct ProxyConfig {
     /// 代理池配置
     #[serde(default)]
     pub proxy_pool: ProxyPoolConfig,
+
+    /// 自定义客户端适配器
+    #[serde(default)]
+    pub client_adapters: Vec<CustomClientAdapterConfig>,
+
+    /// 工具结果压缩配置 (按工具名选择压缩策略)
+    #[serde(default)]
+    pub tool_result_compression: ToolResultCompressionConfig,
+
+    /// 服务端工具循环配置 (web_search / web_fetch 与函数工具共存)
+    #[serde(default)]
+    pub server_tools: ServerToolsConfig,
+
+    /// Message Batches 配置 (批处理并发预算)
+    #[serde(default)]
+    pub message_batches: MessageBatchesConfig,
 }
 
 /// 上游代理配置
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct UpstreamProxyConfig {
     /// 是否启用
     pub enabled: bool,
@@ -462,6 +896,7 @@ impl Default for ProxyConfig {
             request_timeout: default_request_timeout(),
             enable_logging: true, // 默认开启，支持 token 统计功能
             debug_logging: DebugLoggingConfig::default(),
+            log_retention: LogRetentionConfig::default(),
             upstream_proxy: UpstreamProxyConfig::default(),
             zai: ZaiConfig::default(),
             scheduling: crate::proxy::sticky_config::StickySessionConfig::default(),
@@ -472,6 +907,10 @@ impl Default for ProxyConfig {
             saved_user_agent: None,
             thinking_budget: ThinkingBudgetConfig::default(),
             proxy_pool: ProxyPoolConfig::default(),
+            client_adapters: Vec::new(),
+            tool_result_compression: ToolResultCompressionConfig::default(),
+            server_tools: ServerToolsConfig::default(),
+            message_batches: MessageBatchesConfig::default(),
         }
     }
 }
@@ -511,7 +950,7 @@ impl ProxyConfig {
 
 
 /// 代理认证信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProxyAuth {
     pub username: String,
     #[serde(serialize_with = "crate::utils::crypto::serialize_password", deserialize_with = "crate::utils::crypto::deserialize_password")]
@@ -519,7 +958,7 @@ pub struct ProxyAuth {
 }
 
 /// 单个代理配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProxyEntry {
     pub id: String,                    // 唯一标识
     pub name: String,                  // 显示名称
@@ -536,7 +975,7 @@ pub struct ProxyEntry {
 }
 
 /// 代理池配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProxyPoolConfig {
     pub enabled: bool,                 // 是否启用代理池
     // pub mode: ProxyPoolMode,        // [REMOVED] 代理池模式，统一为 Hybrid 逻辑
@@ -566,7 +1005,7 @@ impl Default for ProxyPoolConfig {
 
 
 /// 代理选择策略
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ProxySelectionStrategy {
     /// 轮询: 依次使用
@@ -11,7 +11,7 @@ fn build_filename(prefix: &str, trace_id: Option<&str>) -> String {
     format!("{}_{}_{}.json", ts, tid, prefix)
 }
 
-fn resolve_output_dir(cfg: &DebugLoggingConfig) -> Option<PathBuf> {
+pub(crate) fn resolve_output_dir(cfg: &DebugLoggingConfig) -> Option<PathBuf> {
     if let Some(dir) = cfg.output_dir.as_ref() {
         return Some(PathBuf::from(dir));
     }
@@ -0,0 +1,521 @@
+// 本地文件存储 (Files API)
+// 客户端上传一次，后续请求通过 file_id 引用，发送上游时再解析为 inlineData / 文本，
+// 避免每轮对话重复携带大体积 base64。每次上传生成独立的元数据 (带归属与过期时间)，
+// 内容按哈希去重存放在数据目录下，不再被任何元数据引用时删除。
+
+use crate::proxy::common::document_input::{
+    resolve_base64_document, DocumentInputError, ResolvedDocument,
+};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+const FILES_DIR: &str = "files";
+const BLOBS_DIR: &str = "blobs";
+/// 默认保留时长 (与 Gemini Files API 一致)
+pub const DEFAULT_FILE_TTL_SECS: i64 = 48 * 3600;
+/// 最长保留时长
+pub const MAX_FILE_TTL_SECS: i64 = 30 * 24 * 3600;
+/// 过期清理间隔
+const CLEANUP_INTERVAL_SECS: u64 = 3600;
+
+/// 串行化元数据写入与内容回收，避免并发上传时内容被误删
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 已存储文件的元数据
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StoredFile {
+    pub id: String,
+    pub filename: String,
+    pub mime_type: String,
+    pub bytes: u64,
+    pub purpose: String,
+    pub sha256: String,
+    /// 秒级时间戳
+    pub created_at: i64,
+    pub expires_at: i64,
+    /// 上传者的 User Token ID (主 API Key 或免鉴权上传为 None)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+/// 文件存储: <id>.json 存放元数据，blobs/<sha256>.bin 存放去重后的内容
+///
+/// 所有读写接口都按 owner 过滤，其他归属的文件视为不存在
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 数据目录下的默认存储
+    pub fn open_default() -> Result<Self, String> {
+        let dir = crate::modules::account::get_data_dir()?.join(FILES_DIR);
+        Ok(Self::new(dir))
+    }
+
+    fn blob_dir(&self) -> PathBuf {
+        self.dir.join(BLOBS_DIR)
+    }
+
+    fn blob_path(&self, sha256: &str) -> PathBuf {
+        self.blob_dir().join(format!("{}.bin", sha256))
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    /// 写入文件。每次上传得到新的 file_id，相同内容只保存一份
+    pub fn put(
+        &self,
+        content: &[u8],
+        filename: &str,
+        mime_type: &str,
+        purpose: &str,
+        ttl_secs: i64,
+        owner: Option<&str>,
+    ) -> Result<StoredFile, String> {
+        let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(self.blob_dir()).map_err(|e| format!("创建文件目录失败: {}", e))?;
+
+        let sha256 = format!("{:x}", Sha256::digest(content));
+        let blob = self.blob_path(&sha256);
+        if !blob.exists() {
+            // 先写临时文件再重命名，避免并发读取到半截内容
+            let tmp = self
+                .blob_dir()
+                .join(format!("{}.tmp-{}", sha256, uuid::Uuid::new_v4()));
+            fs::write(&tmp, content).map_err(|e| format!("写入文件失败: {}", e))?;
+            fs::rename(&tmp, &blob).map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                format!("写入文件失败: {}", e)
+            })?;
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        let meta = StoredFile {
+            id: format!("file-{}", uuid::Uuid::new_v4().simple()),
+            filename: filename.to_string(),
+            mime_type: mime_type.to_string(),
+            bytes: content.len() as u64,
+            purpose: purpose.to_string(),
+            sha256,
+            created_at: now,
+            expires_at: now + ttl_secs.clamp(1, MAX_FILE_TTL_SECS),
+            owner: owner.map(|o| o.to_string()),
+        };
+        let json = serde_json::to_vec_pretty(&meta).map_err(|e| e.to_string())?;
+        fs::write(self.meta_path(&meta.id), json)
+            .map_err(|e| format!("写入文件元数据失败: {}", e))?;
+        Ok(meta)
+    }
+
+    /// 读取元数据 (不校验归属)，已过期的文件会被顺带删除
+    fn load(&self, id: &str) -> Result<Option<StoredFile>, String> {
+        if !is_valid_file_id(id) {
+            return Ok(None);
+        }
+        let raw = match fs::read(self.meta_path(id)) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(format!("读取文件元数据失败: {}", e)),
+        };
+        let meta: StoredFile =
+            serde_json::from_slice(&raw).map_err(|e| format!("文件元数据损坏: {}", e))?;
+        if meta.expires_at <= chrono::Utc::now().timestamp() {
+            let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+            self.remove(&meta)?;
+            return Ok(None);
+        }
+        Ok(Some(meta))
+    }
+
+    /// 读取 owner 名下文件的元数据
+    pub fn get(&self, id: &str, owner: Option<&str>) -> Result<Option<StoredFile>, String> {
+        Ok(self.load(id)?.filter(|meta| meta.owner.as_deref() == owner))
+    }
+
+    /// 读取 owner 名下文件的内容
+    pub fn read(
+        &self,
+        id: &str,
+        owner: Option<&str>,
+    ) -> Result<Option<(StoredFile, Vec<u8>)>, String> {
+        let Some(meta) = self.get(id, owner)? else {
+            return Ok(None);
+        };
+        match fs::read(self.blob_path(&meta.sha256)) {
+            Ok(content) => Ok(Some((meta, content))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("读取文件失败: {}", e)),
+        }
+    }
+
+    /// 列出 owner 名下未过期的文件 (按创建时间倒序)
+    pub fn list(&self, owner: Option<&str>) -> Result<Vec<StoredFile>, String> {
+        let mut files: Vec<StoredFile> = self
+            .file_ids()?
+            .iter()
+            .filter_map(|id| self.get(id, owner).ok().flatten())
+            .collect();
+        files.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        Ok(files)
+    }
+
+    /// 删除 owner 名下的文件，返回是否存在
+    pub fn delete(&self, id: &str, owner: Option<&str>) -> Result<bool, String> {
+        let Some(meta) = self.get(id, owner)? else {
+            return Ok(false);
+        };
+        let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        self.remove(&meta)?;
+        Ok(true)
+    }
+
+    /// 删除元数据，内容不再被引用时一并删除 (调用方需持有 STORE_LOCK)
+    fn remove(&self, meta: &StoredFile) -> Result<(), String> {
+        match fs::remove_file(self.meta_path(&meta.id)) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => return Err(format!("删除文件失败: {}", e)),
+        }
+        if !self.referenced_blobs()?.contains(&meta.sha256) {
+            match fs::remove_file(self.blob_path(&meta.sha256)) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("删除文件失败: {}", e)),
+            }
+        }
+        Ok(())
+    }
+
+    /// 清理过期文件与无引用的内容，返回删除的文件数量
+    pub fn prune_expired(&self) -> Result<usize, String> {
+        let _guard = STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let now = chrono::Utc::now().timestamp();
+        let mut removed = 0;
+        for id in self.file_ids()? {
+            let meta = fs::read(self.meta_path(&id))
+                .ok()
+                .and_then(|raw| serde_json::from_slice::<StoredFile>(&raw).ok());
+            let expired = match &meta {
+                Some(meta) => meta.expires_at <= now,
+                // 损坏的元数据同样清理
+                None => true,
+            };
+            if expired {
+                fs::remove_file(self.meta_path(&id)).map_err(|e| format!("删除文件失败: {}", e))?;
+                removed += 1;
+            }
+        }
+
+        let referenced = self.referenced_blobs()?;
+        let entries = match fs::read_dir(self.blob_dir()) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(removed),
+            Err(e) => return Err(format!("读取文件目录失败: {}", e)),
+        };
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let orphan = match name.strip_suffix(".bin") {
+                Some(sha256) => !referenced.contains(sha256),
+                // 中断的上传留下的临时文件
+                None => name.contains(".tmp-"),
+            };
+            if orphan {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        Ok(removed)
+    }
+
+    /// 将 owner 名下的文件引用解析为可发送给上游的内容
+    ///
+    /// 图片 / 音视频与 PDF 作为 inlineData 发送，Office 与纯文本在本地提取为文本
+    pub fn resolve(
+        &self,
+        reference: &str,
+        owner: Option<&str>,
+    ) -> Result<ResolvedDocument, DocumentInputError> {
+        let not_found = || {
+            DocumentInputError::Invalid(format!(
+                "File '{}' was not found or has expired; upload it via /v1/files first",
+                reference
+            ))
+        };
+        let id = parse_file_reference(reference).ok_or_else(not_found)?;
+        let (meta, content) = self
+            .read(id, owner)
+            .map_err(DocumentInputError::Invalid)?
+            .ok_or_else(not_found)?;
+
+        let data = base64::engine::general_purpose::STANDARD.encode(&content);
+        if is_media_mime(&meta.mime_type) {
+            return Ok(ResolvedDocument::Inline {
+                mime_type: meta.mime_type,
+                data,
+            });
+        }
+        resolve_base64_document(&data, Some(&meta.mime_type), Some(&meta.filename))
+    }
+
+    fn file_ids(&self) -> Result<Vec<String>, String> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("读取文件目录失败: {}", e)),
+        };
+        let mut ids: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().to_string();
+                let id = name.strip_suffix(".json")?;
+                is_valid_file_id(id).then(|| id.to_string())
+            })
+            .collect();
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// 仍被元数据引用的内容哈希 (含已过期但尚未清理的文件)
+    fn referenced_blobs(&self) -> Result<std::collections::HashSet<String>, String> {
+        Ok(self
+            .file_ids()?
+            .iter()
+            .filter_map(|id| fs::read(self.meta_path(id)).ok())
+            .filter_map(|raw| serde_json::from_slice::<StoredFile>(&raw).ok())
+            .map(|meta| meta.sha256)
+            .collect())
+    }
+}
+
+/// file_id 格式: file-<32 位十六进制> (同时防止路径穿越)
+pub fn is_valid_file_id(id: &str) -> bool {
+    id.strip_prefix("file-")
+        .map(|hex| hex.len() == 32 && hex.chars().all(|c| c.is_ascii_hexdigit()))
+        .unwrap_or(false)
+}
+
+/// 从引用中提取本地 file_id
+///
+/// 支持裸 file_id、`files/<id>` 以及指向本代理 `/v1/files/<id>` 的 URL (Gemini fileData.fileUri)
+pub fn parse_file_reference(reference: &str) -> Option<&str> {
+    let trimmed = reference.trim().trim_end_matches('/');
+    let trimmed = trimmed.strip_suffix("/content").unwrap_or(trimmed);
+    let id = trimmed.rsplit('/').next()?;
+    is_valid_file_id(id).then_some(id)
+}
+
+/// 根据文件名推断 MIME 类型
+pub fn guess_mime_type(filename: &str) -> Option<&'static str> {
+    let ext = filename.rsplit_once('.')?.1.to_ascii_lowercase();
+    let mime = match ext.as_str() {
+        "pdf" => "application/pdf",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "heic" => "image/heic",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "m4a" => "audio/mp4",
+        "ogg" => "audio/ogg",
+        "flac" => "audio/flac",
+        "aac" => "audio/aac",
+        "mp4" => "video/mp4",
+        "mov" => "video/quicktime",
+        "webm" => "video/webm",
+        "docx" => crate::proxy::common::document_input::DOCX_MIME,
+        "xlsx" => crate::proxy::common::document_input::XLSX_MIME,
+        "txt" | "log" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "json" => "application/json",
+        _ => return None,
+    };
+    Some(mime)
+}
+
+fn is_media_mime(mime: &str) -> bool {
+    mime.starts_with("image/") || mime.starts_with("audio/") || mime.starts_with("video/")
+}
+
+/// 将默认存储中 owner 名下的文件解析为可发送给上游的内容
+pub fn resolve_file_reference(
+    reference: &str,
+    owner: Option<&str>,
+) -> Result<ResolvedDocument, DocumentInputError> {
+    FileStore::open_default()
+        .map_err(DocumentInputError::Invalid)?
+        .resolve(reference, owner)
+}
+
+/// 启动过期文件清理任务
+pub fn start_cleanup_task() {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match FileStore::open_default().and_then(|store| store.prune_expired()) {
+                Ok(0) => {}
+                Ok(removed) => tracing::info!("[Files] Pruned {} expired file(s)", removed),
+                Err(e) => tracing::warn!("[Files] Cleanup failed: {}", e),
+            }
+            tokio::time::sleep(Duration::from_secs(CLEANUP_INTERVAL_SECS)).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (FileStore, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("abv-files-{}", uuid::Uuid::new_v4()));
+        (FileStore::new(dir.clone()), dir)
+    }
+
+    #[test]
+    fn test_put_deduplicates_content_only() {
+        let (store, dir) = temp_store();
+        let a = store
+            .put(b"hello", "a.txt", "text/plain", "user_data", 60, None)
+            .unwrap();
+        let b = store
+            .put(b"hello", "b.txt", "text/plain", "user_data", 60, None)
+            .unwrap();
+        assert_ne!(a.id, b.id);
+        assert!(is_valid_file_id(&a.id) && is_valid_file_id(&b.id));
+        assert_eq!(a.sha256, b.sha256);
+
+        // 重复上传不会改写已有文件的元数据
+        let (meta, content) = store.read(&a.id, None).unwrap().unwrap();
+        assert_eq!(meta.filename, "a.txt");
+        assert_eq!(content, b"hello");
+        assert_eq!(store.list(None).unwrap().len(), 2);
+
+        // 内容仍被 b 引用
+        assert!(store.delete(&a.id, None).unwrap());
+        assert!(store.read(&a.id, None).unwrap().is_none());
+        assert_eq!(store.read(&b.id, None).unwrap().unwrap().1, b"hello");
+
+        assert!(store.delete(&b.id, None).unwrap());
+        assert!(!store.blob_path(&b.sha256).exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_files_are_scoped_to_owner() {
+        let (store, dir) = temp_store();
+        let alice = store
+            .put(
+                b"secret",
+                "a.txt",
+                "text/plain",
+                "user_data",
+                60,
+                Some("alice"),
+            )
+            .unwrap();
+        let bob = store
+            .put(
+                b"secret",
+                "b.txt",
+                "text/plain",
+                "user_data",
+                600,
+                Some("bob"),
+            )
+            .unwrap();
+        assert_eq!(alice.owner.as_deref(), Some("alice"));
+
+        assert!(store.get(&alice.id, Some("bob")).unwrap().is_none());
+        assert!(store.get(&alice.id, None).unwrap().is_none());
+        assert!(store.read(&alice.id, Some("bob")).unwrap().is_none());
+        assert!(store.resolve(&alice.id, Some("bob")).is_err());
+        assert!(!store.delete(&alice.id, Some("bob")).unwrap());
+
+        let listed = store.list(Some("alice")).unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, alice.id);
+        assert!(store.list(None).unwrap().is_empty());
+
+        // bob 的上传不影响 alice 的文件名与过期时间
+        let kept = store.get(&alice.id, Some("alice")).unwrap().unwrap();
+        assert_eq!(kept.filename, "a.txt");
+        assert_eq!(kept.expires_at, alice.expires_at);
+        assert!(store.delete(&bob.id, Some("bob")).unwrap());
+        assert!(store.read(&alice.id, Some("alice")).unwrap().is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_expired_files_are_pruned() {
+        let (store, dir) = temp_store();
+        let meta = store
+            .put(b"stale", "s.txt", "text/plain", "user_data", 60, None)
+            .unwrap();
+
+        // 手动改写过期时间
+        let mut expired = meta.clone();
+        expired.expires_at = chrono::Utc::now().timestamp() - 1;
+        fs::write(
+            store.meta_path(&meta.id),
+            serde_json::to_vec(&expired).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(store.prune_expired().unwrap(), 1);
+        assert!(!store.meta_path(&meta.id).exists());
+        assert!(!store.blob_path(&meta.sha256).exists());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_resolve_media_and_text_files() {
+        let (store, dir) = temp_store();
+        let image = store
+            .put(b"\x89PNG", "a.png", "image/png", "vision", 60, None)
+            .unwrap();
+        let notes = store
+            .put(b"line one", "n.md", "text/markdown", "user_data", 60, None)
+            .unwrap();
+
+        match store.resolve(&format!("files/{}", image.id), None).unwrap() {
+            ResolvedDocument::Inline { mime_type, .. } => assert_eq!(mime_type, "image/png"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            store.resolve(&notes.id, None).unwrap(),
+            ResolvedDocument::Text("line one".to_string())
+        );
+        let err = store
+            .resolve("file-ffffffffffffffffffffffffffffffff", None)
+            .unwrap_err();
+        assert!(err.to_string().contains("not found"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_parse_file_reference() {
+        let id = "file-0123456789abcdef0123456789abcdef";
+        assert_eq!(parse_file_reference(id), Some(id));
+        assert_eq!(parse_file_reference(&format!("files/{}", id)), Some(id));
+        assert_eq!(
+            parse_file_reference(&format!("http://127.0.0.1:8045/v1/files/{}/content", id)),
+            Some(id)
+        );
+        assert_eq!(
+            parse_file_reference("https://generativelanguage.googleapis.com/v1beta/files/abc"),
+            None
+        );
+        assert_eq!(parse_file_reference("file-../../etc/passwd"), None);
+    }
+}
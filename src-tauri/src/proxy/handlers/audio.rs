@@ -1,29 +1,63 @@
 use axum::{
     extract::{Multipart, State},
-    http::StatusCode,
-    response::IntoResponse,
-    Json,
+    http::{header, HeaderName, StatusCode},
+    response::{IntoResponse, Response},
 };
+use futures::stream::{self, StreamExt};
 use serde_json::{json, Value};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 use uuid::Uuid;
 
 use crate::proxy::{
-    audio::AudioProcessor,
+    audio::{
+        chunker::{self, AudioChunk, ChunkingOptions},
+        transcript::{self, AudioTask, ChunkTranscript, ResponseFormat},
+        AudioProcessor,
+    },
     server::AppState,
 };
 
+/// 同时转录的分片数上限
+const MAX_PARALLEL_CHUNKS: usize = 4;
+/// 单个分片的最大尝试次数 (重试时强制轮换账号)
+const CHUNK_MAX_ATTEMPTS: usize = 2;
+
+/// 解析后的音频请求表单
+struct AudioForm {
+    audio: Vec<u8>,
+    filename: String,
+    model: String,
+    prompt: Option<String>,
+    language: Option<String>,
+    response_format: ResponseFormat,
+    temperature: Option<f64>,
+}
+
 /// 处理音频转录请求 (OpenAI Whisper API 兼容)
 pub async fn handle_audio_transcription(
     State(state): State<AppState>,
-    mut multipart: Multipart,
-) -> Result<impl IntoResponse, (StatusCode, String)> {
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    process_audio_request(state, multipart, AudioTask::Transcribe).await
+}
+
+/// 处理音频翻译请求 (翻译为英文, OpenAI Whisper API 兼容)
+pub async fn handle_audio_translation(
+    State(state): State<AppState>,
+    multipart: Multipart,
+) -> Result<Response, (StatusCode, String)> {
+    process_audio_request(state, multipart, AudioTask::Translate).await
+}
+
+async fn parse_audio_form(mut multipart: Multipart) -> Result<AudioForm, (StatusCode, String)> {
     let mut audio_data: Option<Vec<u8>> = None;
     let mut filename: Option<String> = None;
     let mut model = "gemini-2.0-flash-exp".to_string();
-    let mut prompt = "Generate a transcript of the speech.".to_string();
+    let mut prompt: Option<String> = None;
+    let mut language: Option<String> = None;
+    let mut response_format = ResponseFormat::Json;
+    let mut temperature: Option<f64> = None;
 
-    // 1. 解析 multipart/form-data
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         (StatusCode::BAD_REQUEST, format!("解析表单失败: {}", e))
     })? {
@@ -40,54 +74,198 @@ pub async fn handle_audio_transcription(
                 model = field.text().await.unwrap_or(model);
             }
             "prompt" => {
-                prompt = field.text().await.unwrap_or(prompt);
+                prompt = field.text().await.ok();
+            }
+            "language" => {
+                language = field.text().await.ok().filter(|l| !l.trim().is_empty());
+            }
+            "response_format" => {
+                let value = field.text().await.unwrap_or_default();
+                response_format = ResponseFormat::parse(&value)
+                    .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+            }
+            "temperature" => {
+                temperature = field
+                    .text()
+                    .await
+                    .ok()
+                    .and_then(|t| t.trim().parse::<f64>().ok());
             }
             _ => {}
         }
     }
 
-    let audio_bytes = audio_data.ok_or((
+    let audio = audio_data.ok_or((
         StatusCode::BAD_REQUEST,
         "缺少音频文件".to_string(),
     ))?;
 
-    let file_name = filename.ok_or((
+    let filename = filename.ok_or((
         StatusCode::BAD_REQUEST,
         "无法获取文件名".to_string(),
     ))?;
 
+    Ok(AudioForm {
+        audio,
+        filename,
+        model,
+        prompt,
+        language,
+        response_format,
+        temperature,
+    })
+}
+
+async fn process_audio_request(
+    state: AppState,
+    multipart: Multipart,
+    task: AudioTask,
+) -> Result<Response, (StatusCode, String)> {
+    // 1. 解析 multipart/form-data
+    let form = parse_audio_form(multipart).await?;
+
     info!(
-        "收到音频转录请求: 文件={}, 大小={} bytes, 模型={}",
-        file_name,
-        audio_bytes.len(),
-        model
+        "收到音频{}请求: 文件={}, 大小={} bytes, 模型={}, 格式={:?}",
+        if task == AudioTask::Translate { "翻译" } else { "转录" },
+        form.filename,
+        form.audio.len(),
+        form.model,
+        form.response_format
     );
 
     // 2. 检测 MIME 类型
-    let mime_type = AudioProcessor::detect_mime_type(&file_name)
+    let mime_type = AudioProcessor::detect_mime_type(&form.filename)
         .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
 
-    // 3. 验证文件大小
-    if AudioProcessor::exceeds_size_limit(audio_bytes.len()) {
-        let size_mb = audio_bytes.len() as f64 / (1024.0 * 1024.0);
+    // 3. 长录音自动分片 (仅 MP3/WAV 可无损切分)
+    let duration = chunker::probe_duration(&form.audio, &mime_type);
+    let chunks = if chunker::supports_chunking(&mime_type) {
+        match chunker::split_audio(&form.audio, &mime_type, &ChunkingOptions::default()) {
+            Ok(chunks) => chunks,
+            Err(e) if AudioProcessor::exceeds_size_limit(form.audio.len()) => {
+                return Err((StatusCode::BAD_REQUEST, format!("音频分片失败: {}", e)));
+            }
+            Err(e) => {
+                debug!("音频分片失败，整体处理: {}", e);
+                vec![whole_audio_chunk(form.audio, duration)]
+            }
+        }
+    } else if AudioProcessor::exceeds_size_limit(form.audio.len()) {
+        let size_mb = form.audio.len() as f64 / (1024.0 * 1024.0);
         return Err((
             StatusCode::PAYLOAD_TOO_LARGE,
             format!(
-                "音频文件过大 ({:.1} MB)。最大支持 15 MB (约 16 分钟 MP3)。建议: 1) 压缩音频质量 2) 分段上传",
+                "音频文件过大 ({:.1} MB)。该格式最大支持 15 MB，仅 MP3/WAV 支持自动分片。建议: 1) 转换为 MP3 2) 压缩音频质量",
                 size_mb
             ),
         ));
+    } else {
+        vec![whole_audio_chunk(form.audio, duration)]
+    };
+
+    if chunks.len() > 1 {
+        info!(
+            "音频时长 {:.1}s，切分为 {} 个分片并行处理",
+            duration.unwrap_or(0.0),
+            chunks.len()
+        );
+    }
+
+    // 4. 并行转录各分片 (每个分片独立选取账号)
+    let instruction = transcript::build_instruction(
+        task,
+        form.language.as_deref(),
+        form.prompt.as_deref(),
+    );
+    let results: Vec<Result<(ChunkTranscript, String), (StatusCode, String)>> =
+        stream::iter(chunks.iter())
+            .map(|chunk| {
+                transcribe_chunk(
+                    &state,
+                    &form.model,
+                    &instruction,
+                    &mime_type,
+                    form.temperature,
+                    chunk,
+                )
+            })
+            .buffered(MAX_PARALLEL_CHUNKS)
+            .collect()
+            .await;
+
+    let mut transcripts = Vec::with_capacity(results.len());
+    let mut account_email: Option<String> = None;
+    for (chunk, result) in chunks.iter().zip(results) {
+        let (chunk_transcript, email) = result?;
+        // 监控面板按单个账号记录，使用首个分片的账号
+        account_email.get_or_insert(email);
+        transcripts.push((chunk.start_secs, chunk.duration_secs, chunk_transcript));
     }
 
-    // 4. 使用 Inline Data 方式
-    debug!("使用 Inline Data 方式处理");
-    let base64_audio = AudioProcessor::encode_to_base64(&audio_bytes);
+    // 5. 拼接分片结果并按 response_format 渲染
+    let detected_language = transcripts
+        .iter()
+        .find_map(|(_, _, t)| t.language.clone());
+    let language = form.language.clone().or(detected_language);
+    let segments = transcript::stitch_segments(&transcripts);
+    let body = transcript::render(
+        form.response_format,
+        task,
+        language.as_deref(),
+        duration,
+        &segments,
+    );
 
-    // 5. 构建 Gemini 请求
+    info!(
+        "音频{}完成，{} 个分段，返回 {} 字符",
+        if task == AudioTask::Translate { "翻译" } else { "转录" },
+        segments.len(),
+        body.len()
+    );
+
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, form.response_format.content_type().to_string()),
+            (HeaderName::from_static("x-account-email"), account_email.unwrap_or_default()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn whole_audio_chunk(data: Vec<u8>, duration: Option<f64>) -> AudioChunk {
+    AudioChunk {
+        index: 0,
+        data,
+        start_secs: 0.0,
+        duration_secs: duration,
+    }
+}
+
+/// 转录单个分片，失败时轮换账号重试
+async fn transcribe_chunk(
+    state: &AppState,
+    model: &str,
+    instruction: &str,
+    mime_type: &str,
+    temperature: Option<f64>,
+    chunk: &AudioChunk,
+) -> Result<(ChunkTranscript, String), (StatusCode, String)> {
+    // 使用 Inline Data 方式
+    let base64_audio = AudioProcessor::encode_to_base64(&chunk.data);
+
+    let mut generation_config = json!({ "responseMimeType": "application/json" });
+    if let Some(t) = temperature {
+        generation_config["temperature"] = json!(t);
+    }
+
+    // 构建 Gemini 请求
     let gemini_request = json!({
         "contents": [{
+            "role": "user",
             "parts": [
-                {"text": prompt},
+                {"text": instruction},
                 {
                     "inlineData": {
                         "mimeType": mime_type,
@@ -95,68 +273,81 @@ pub async fn handle_audio_transcription(
                     }
                 }
             ]
-        }]
+        }],
+        "generationConfig": generation_config
     });
 
-    // 6. 获取 Token 和上游客户端
-    let token_manager = state.token_manager;
-    let (access_token, project_id, email, account_id, _wait_ms) = token_manager
-        .get_token("text", false, None, &model)
-        .await
-        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
-
-    info!("使用账号: {}", email);
-
-    // 7. 包装请求为 v1internal 格式
-    let wrapped_body = json!({
-        "project": project_id,
-        "requestId": format!("audio-{}", Uuid::new_v4()),
-        "request": gemini_request,
-        "model": model,
-        "userAgent": "antigravity",
-        "requestType": "text"
-    });
+    let mut last_error = (StatusCode::BAD_GATEWAY, "上游请求失败".to_string());
+    for attempt in 0..CHUNK_MAX_ATTEMPTS {
+        // 获取 Token (重试时强制轮换账号)
+        let (access_token, project_id, email, account_id, _wait_ms) = state
+            .token_manager
+            .get_token("text", attempt > 0, None, model)
+            .await
+            .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e))?;
 
-    // 8. 发送请求到 Gemini
-    let upstream = state.upstream.clone();
-    let response = upstream
-        .call_v1_internal("generateContent", &access_token, wrapped_body, None, Some(account_id.as_str()))
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e)))?;
+        debug!("分片 #{} 使用账号: {}", chunk.index, email);
 
-    if !response.status().is_success() {
-        let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
-        return Err((
-            StatusCode::BAD_GATEWAY,
-            format!("Gemini API 错误: {}", error_text),
+        // 包装请求为 v1internal 格式
+        let wrapped_body = json!({
+            "project": project_id,
+            "requestId": format!("audio-{}", Uuid::new_v4()),
+            "request": gemini_request.clone(),
+            "model": model,
+            "userAgent": "antigravity",
+            "requestType": "text"
+        });
+
+        let response = match state
+            .upstream
+            .call_v1_internal("generateContent", &access_token, wrapped_body, None, Some(account_id.as_str()))
+            .await
+        {
+            Ok(r) => r,
+            Err(e) => {
+                warn!("分片 #{} 上游请求失败 (账号 {}): {}", chunk.index, email, e);
+                last_error = (StatusCode::BAD_GATEWAY, format!("上游请求失败: {}", e));
+                continue;
+            }
+        };
+
+        if !response.status().is_success() {
+            let error_text = response.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+            warn!("分片 #{} Gemini API 错误 (账号 {}): {}", chunk.index, email, error_text);
+            last_error = (
+                StatusCode::BAD_GATEWAY,
+                format!("Gemini API 错误: {}", error_text),
+            );
+            continue;
+        }
+
+        let result: Value = response
+            .json()
+            .await
+            .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
+
+        // 提取文本响应（解包 v1internal 响应，跳过思维链部分）
+        let inner_response = result.get("response").unwrap_or(&result);
+        let text: String = inner_response
+            .get("candidates")
+            .and_then(|c| c.get(0))
+            .and_then(|c| c.get("content"))
+            .and_then(|c| c.get("parts"))
+            .and_then(|p| p.as_array())
+            .map(|parts| {
+                parts
+                    .iter()
+                    .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+                    .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        return Ok((
+            transcript::parse_chunk_transcript(&text, chunk.duration_secs),
+            email,
         ));
     }
 
-    let result: Value = response
-        .json()
-        .await
-        .map_err(|e| (StatusCode::BAD_GATEWAY, format!("解析响应失败: {}", e)))?;
-
-    // 9. 提取文本响应（解包 v1internal 响应）
-    let inner_response = result.get("response").unwrap_or(&result);
-    let text = inner_response
-        .get("candidates")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("content"))
-        .and_then(|c| c.get("parts"))
-        .and_then(|p| p.get(0))
-        .and_then(|p| p.get("text"))
-        .and_then(|t| t.as_str())
-        .unwrap_or("");
-
-    info!("音频转录完成，返回 {} 字符", text.len());
-
-    // 10. 返回标准格式响应
-    Ok((
-        StatusCode::OK,
-        [("X-Account-Email", email.as_str())],
-        Json(json!({
-            "text": text
-        }))
-    ).into_response())
+    Err(last_error)
 }
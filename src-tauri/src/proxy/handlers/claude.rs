@@ -5,6 +5,7 @@ use axum::{
     extract::{Json, State},
     http::{header, StatusCode},
     response::{IntoResponse, Response},
+    Extension,
 };
 use bytes::Bytes;
 use futures::StreamExt;
@@ -13,18 +14,29 @@ use tokio::time::Duration;
 use tracing::{debug, error, info};
 
 use crate::proxy::mappers::claude::{
-    transform_claude_request_in, transform_response, create_claude_sse_stream, ClaudeRequest,
+    transform_claude_request_for_client, transform_response, create_claude_sse_stream, ClaudeRequest,
     filter_invalid_thinking_blocks_with_family, close_tool_loop_for_thinking,
-    clean_cache_control_from_messages, merge_consecutive_messages,
-    models::{Message, MessageContent},
+    clean_cache_control_from_messages, merge_consecutive_messages, resolve_document_sources,
 };
+use crate::proxy::mappers::claude::server_tools::{
+    self, ServerToolBudget, ServerToolCall, ServerToolError, ServerToolOutcome, ServerToolPlan,
+};
+use crate::proxy::mappers::claude::document_citations::DocumentCitations;
+use crate::proxy::mappers::claude::{ClaudeResponse, GeminiResponse};
+use crate::proxy::monitor::UpstreamUsage;
+use crate::proxy::middleware::auth::{resource_owner, UserTokenIdentity};
 use crate::proxy::server::AppState;
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::TokenManager;
+use std::collections::HashMap;
+use std::sync::Arc;
 use crate::proxy::mappers::context_manager::ContextManager;
 use crate::proxy::mappers::estimation_calibrator::get_calibrator;
 use crate::proxy::debug_logger;
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Import Adapter Registry
+use crate::proxy::common::client_adapter::{find_client_adapter, merge_beta_header, Protocol}; // [NEW] Import Adapter Registry
+use crate::proxy::mappers::context_compression::{CompressionReport, CompressionThresholds};
 use axum::http::HeaderMap;
-use std::sync::{atomic::Ordering, Arc};
+use std::sync::atomic::Ordering;
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 
@@ -32,66 +44,6 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 // These can be adjusted for performance/cost optimization or overridden by custom_mapping
 const INTERNAL_BACKGROUND_TASK: &str = "internal-background-task";  // Unified virtual ID for all background tasks
 
-// ===== Layer 3: XML Summary Prompt Template =====
-// Borrowed from Practical-Guide-to-Context-Engineering + Claude Code official practice
-// This prompt generates a structured 8-section XML summary for context compression
-const CONTEXT_SUMMARY_PROMPT: &str = r#"You are a context compression specialist. Your task is to create a structured XML snapshot of the conversation history.
-
-This snapshot will become the Agent's ONLY memory of the past. All key details, plans, errors, and user instructions MUST be preserved.
-
-First, think through the entire history in a private <scratchpad>. Review the user's overall goal, the agent's actions, tool outputs, file modifications, and any unresolved issues. Identify every piece of information critical for future actions.
-
-After reasoning, generate the final <state_snapshot> XML object. Information must be extremely dense. Omit any irrelevant conversational filler.
-
-The structure MUST be as follows:
-
-<state_snapshot>
-  <overall_goal>
-    <!-- Describe the user's high-level goal in one concise sentence -->
-  </overall_goal>
-  
-  <technical_context>
-    <!-- Tech stack: frameworks, languages, toolchain, dependency versions -->
-  </technical_context>
-  
-  <file_system_state>
-    <!-- List files that were created, read, modified, or deleted. Note their status -->
-  </file_system_state>
-  
-  <code_changes>
-    <!-- Key code snippets (preserve function signatures and important logic) -->
-  </code_changes>
-  
-  <debugging_history>
-    <!-- List all errors encountered, with stack traces, and how they were fixed -->
-  </debugging_history>
-  
-  <current_plan>
-    <!-- Step-by-step plan. Mark completed steps -->
-  </current_plan>
-  
-  <user_preferences>
-    <!-- User's work preferences for this project (test commands, code style, etc.) -->
-  </user_preferences>
-  
-  <key_decisions>
-    <!-- Critical architectural decisions and design choices -->
-  </key_decisions>
-  
-  <latest_thinking_signature>
-    <!-- [CRITICAL] Preserve the last valid thinking signature -->
-    <!-- Format: base64-encoded signature string -->
-    <!-- This MUST be copied exactly as-is, no modifications -->
-  </latest_thinking_signature>
-</state_snapshot>
-
-**IMPORTANT**:
-1. Code snippets must be complete, including function signatures and key logic
-2. Error messages must be preserved verbatim, including line numbers and stacks
-3. File paths must use absolute paths
-4. The thinking signature must be copied exactly, no modifications
-"#;
-
 // ===== Jitter Configuration (REMOVED) =====
 // Jitter was causing connection instability, reverted to fixed delays
 // const JITTER_FACTOR: f64 = 0.2;
@@ -105,6 +57,8 @@ The structure MUST be as follows:
 // ===== 统一退避策略模块 =====
 // 移除本地重复定义，使用 common 中的统一实现
 use super::common::{determine_retry_strategy, apply_retry_strategy, should_rotate_account, RetryStrategy};
+use super::common::{apply_context_compression, CONTEXT_SUMMARY_MODEL};
+use super::common::run_server_tool_call;
 
 // ===== 退避策略模块结束 =====
 
@@ -113,8 +67,19 @@ use super::common::{determine_retry_strategy, apply_retry_strategy, should_rotat
 /// 处理 Chat 消息请求流程
 pub async fn handle_messages(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
     headers: HeaderMap,
     Json(body): Json<Value>,
+) -> Response {
+    handle_messages_as(state, resource_owner(identity), headers, body).await
+}
+
+/// 以指定归属处理 messages 请求 (owner 用于解析 Files API 文件引用，Message Batches 以批次创建者身份调用)
+pub async fn handle_messages_as(
+    state: AppState,
+    owner: Option<String>,
+    headers: HeaderMap,
+    body: Value,
 ) -> Response {
     // [FIX] 保存原始请求体的完整副本，用于日志记录
     // 这确保了即使结构体定义遗漏字段，日志也能完整记录所有参数
@@ -131,9 +96,9 @@ pub async fn handle_messages(
     
     // [NEW] Detect Client Adapter
     // 检查是否有匹配的客户端适配器（如 opencode）
-    let client_adapter = CLIENT_ADAPTERS.iter().find(|a| a.matches(&headers)).cloned();
-    if let Some(_adapter) = &client_adapter {
-        tracing::debug!("[{}] Client Adapter detected: Applying custom strategies", trace_id);
+    let client_adapter = find_client_adapter(&headers, Protocol::Anthropic);
+    if let Some(adapter) = &client_adapter {
+        tracing::debug!("[{}] Client Adapter detected ({}): Applying custom strategies", trace_id, adapter.name());
     }
         
     // Decide whether this request should be handled by z.ai (Anthropic passthrough) or the existing Google flow.
@@ -158,6 +123,25 @@ pub async fn handle_messages(
         }
     };
 
+    // 文档输入预处理: 校验大小，Office / 纯文本在本地提取为文本
+    if let Err(e) = resolve_document_sources(&mut request.messages, owner.as_deref()) {
+        let error_type = if e.status_code() == StatusCode::PAYLOAD_TOO_LARGE {
+            "request_too_large"
+        } else {
+            "invalid_request_error"
+        };
+        return (
+            e.status_code(),
+            Json(json!({
+                "type": "error",
+                "error": {
+                    "type": error_type,
+                    "message": e.to_string()
+                }
+            }))
+        ).into_response();
+    }
+
     if debug_logger::is_enabled(&debug_cfg) {
         // [FIX] 使用原始 body 副本记录日志，确保不丢失任何字段
         let original_payload = json!({
@@ -271,13 +255,15 @@ pub async fn handle_messages(
     
     // Google Flow 继续使用 request 对象
     // (后续代码不需要再次 filter_invalid_thinking_blocks)
+
+    // cache_control 已从 request 中清理，断点从原始请求体提取
+    let cache_plan = crate::proxy::prompt_cache::PromptCachePlan::from_request(&original_body);
     
     // [NEW] 获取上下文控制配置
     let experimental = state.experimental.read().await;
     let scaling_enabled = experimental.enable_usage_scaling;
-    let threshold_l1 = experimental.context_compression_threshold_l1;
-    let threshold_l2 = experimental.context_compression_threshold_l2;
-    let threshold_l3 = experimental.context_compression_threshold_l3;
+    let compression_enabled = experimental.enable_context_compression;
+    let compression_thresholds = CompressionThresholds::from(&*experimental);
 
     // 获取最新一条“有意义”的消息内容（用于日志记录和后台任务检测）
     // 策略：反向遍历，首先筛选出所有角色为 "user" 的消息，然后从中找到第一条非 "Warmup" 且非空的文本消息
@@ -293,7 +279,7 @@ pub async fn handle_messages(
                     // 对于数组，提取所有 Text 块并拼接，忽略 ToolResult
                     arr.iter()
                         .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
+                            crate::proxy::mappers::claude::models::ContentBlock::Text { text, .. } => Some(text.as_str()),
                             _ => None,
                         })
                         .collect::<Vec<_>>()
@@ -391,6 +377,8 @@ pub async fn handle_messages(
     let mut last_email: Option<String> = None;
     let mut last_mapped_model: Option<String> = None;
     let mut last_status = StatusCode::SERVICE_UNAVAILABLE; // Default to 503 if no response reached
+    // 压缩后的 contents 与报告：只在首次尝试计算，换号重试直接复用 (请求体被签名修复改写时失效)
+    let mut compressed_context: Option<(Value, Option<CompressionReport>)> = None;
     
     for attempt in 0..max_attempts {
         // 2. 模型路由解析
@@ -400,10 +388,11 @@ pub async fn handle_messages(
         );
         last_mapped_model = Some(mapped_model.clone());
         
-        // 将 Claude 工具转为 Value 数组以便探测联网
-        let tools_val: Option<Vec<Value>> = request_for_body.tools.as_ref().map(|list| {
-            list.iter().map(|t| serde_json::to_value(t).unwrap_or(json!({}))).collect()
-        });
+        // 将 Claude 工具转为 Value 数组以便探测联网 (服务端工具循环接管的联网工具不参与路由)
+        let tools_val = server_tools::routing_tools(
+            &request_for_body.tools,
+            server_tools::resolve_plan(&request_for_body.tools),
+        );
 
         let config = crate::proxy::mappers::common_utils::resolve_request_config(
             &request_for_body.model,
@@ -420,7 +409,23 @@ pub async fn handle_messages(
         let session_id = Some(session_id_str.as_str());
 
         let force_rotate_token = attempt > 0;
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager.get_token(&config.request_type, force_rotate_token, session_id, &config.final_model).await {
+        // 带 cache_control 断点的请求首次尝试优先使用最近服务该前缀的账号 (命中上游隐式前缀缓存)
+        let cache_account = if attempt == 0 {
+            cache_plan.as_ref().and_then(crate::proxy::prompt_cache::preferred_account)
+        } else {
+            None
+        };
+        let token_result = match cache_account {
+            Some(cached) => match token_manager.get_token_for_account(&cached, &config.final_model).await {
+                Ok(t) => Ok(t),
+                Err(e) => {
+                    debug!("[{}] Prompt cache account unavailable ({}), falling back to scheduler", trace_id, e);
+                    token_manager.get_token(&config.request_type, false, session_id, &config.final_model).await
+                }
+            },
+            None => token_manager.get_token(&config.request_type, force_rotate_token, session_id, &config.final_model).await,
+        };
+        let (access_token, project_id, email, account_id, _wait_ms) = match token_result {
             Ok(t) => t,
             Err(e) => {
                 let safe_message = if e.contains("invalid_grant") {
@@ -495,169 +500,18 @@ pub async fn handle_messages(
             );
         }
 
-        // ===== [3-Layer Progressive Compression + Calibrated Estimation] Context Management =====
-        // [ENHANCED] 整合 3.3.47 的三层压缩框架 + PR #925 的动态校准机制
-        // [NEW] 只有当 scaling_enabled 为 true 时才执行压缩逻辑 (联动机制)
-        // Layer 1 (60%): Tool message trimming - Does NOT break cache
-        // Layer 2 (75%): Thinking purification - Breaks cache but preserves signatures
-        // Layer 3 (90%): Fork conversation + XML summary - Ultimate optimization
-        let mut is_purified = false;
-        let mut compression_applied = false;
-        
-        if !retried_without_thinking && scaling_enabled {  // 新增 scaling_enabled 联动判断
-            // 1. Determine context limit (Flash: ~1M, Pro: ~2M)
-            let context_limit = if mapped_model.contains("flash") {
-                1_000_000
-            } else {
-                2_000_000
-            };
-
-            // 2. [ENHANCED] 使用校准器提高估算准确度 (PR #925)
-            let raw_estimated = ContextManager::estimate_token_usage(&request_with_mapped);
-            let calibrator = get_calibrator();
-            let mut estimated_usage = calibrator.calibrate(raw_estimated);
-            let mut usage_ratio = estimated_usage as f32 / context_limit as f32;
-            
-            info!(
-                "[{}] [ContextManager] Context pressure: {:.1}% (raw: {}, calibrated: {} / {}), Calibration factor: {:.2}",
-                trace_id, usage_ratio * 100.0, raw_estimated, estimated_usage, context_limit, calibrator.get_factor()
-            );
-
-            // ===== Layer 1: Tool Message Trimming (L1 threshold) =====
-            // Borrowed from Practical-Guide-to-Context-Engineering
-            // Advantage: Completely cache-friendly (only removes messages, doesn't modify content)
-            if usage_ratio > threshold_l1 && !compression_applied {
-                if ContextManager::trim_tool_messages(&mut request_with_mapped.messages, 5) {
-                    info!(
-                        "[{}] [Layer-1] Tool trimming triggered (usage: {:.1}%, threshold: {:.1}%)",
-                        trace_id, usage_ratio * 100.0, threshold_l1 * 100.0
-                    );
-                    compression_applied = true;
-                    
-                    // Re-estimate after trimming (with calibration)
-                    let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
-                    let new_usage = calibrator.calibrate(new_raw);
-                    let new_ratio = new_usage as f32 / context_limit as f32;
-                    
-                    info!(
-                        "[{}] [Layer-1] Compression result: {:.1}% → {:.1}% (saved {} tokens)",
-                        trace_id, usage_ratio * 100.0, new_ratio * 100.0, estimated_usage - new_usage
-                    );
-                    
-                    // If compression is sufficient, skip further layers
-                    if new_ratio < 0.7 {
-                        estimated_usage = new_usage;
-                        usage_ratio = new_ratio;
-                        // Success, no need for Layer 2
-                    } else {
-                        // Still high pressure, update for Layer 2
-                        usage_ratio = new_ratio;
-                        compression_applied = false; // Allow Layer 2 to run
-                    }
-                }
-            }
-
-            // ===== Layer 2: Thinking Content Compression (L2 threshold) =====
-            // NEW: Preserve signatures while compressing thinking text
-            // This prevents signature chain breakage (Issue #902)
-            if usage_ratio > threshold_l2 && !compression_applied {
-                info!(
-                    "[{}] [Layer-2] Thinking compression triggered (usage: {:.1}%, threshold: {:.1}%)",
-                    trace_id, usage_ratio * 100.0, threshold_l2 * 100.0
-                );
-                
-                // Use new signature-preserving compression
-                if ContextManager::compress_thinking_preserve_signature(
-                    &mut request_with_mapped.messages, 
-                    4 // Protect last 4 messages (~2 turns)
-                ) {
-                    is_purified = true; // Still breaks cache, but preserves signatures
-                    compression_applied = true;
-                    
-                    let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
-                    let new_usage = calibrator.calibrate(new_raw);
-                    let new_ratio = new_usage as f32 / context_limit as f32;
-                    
-                    info!(
-                        "[{}] [Layer-2] Compression result: {:.1}% → {:.1}% (saved {} tokens)",
-                        trace_id, usage_ratio * 100.0, new_ratio * 100.0, estimated_usage - new_usage
-                    );
-                    
-                    usage_ratio = new_ratio;
-                }
-            }
-
-            // ===== Layer 3: Fork Conversation + XML Summary (L3 threshold) =====
-            // Ultimate optimization: Generate structured summary and start fresh conversation
-            // Advantage: Completely cache-friendly (append-only), extreme compression ratio
-            if usage_ratio > threshold_l3 && !compression_applied {
-                info!(
-                    "[{}] [Layer-3] Context pressure ({:.1}%) exceeded threshold ({:.1}%), attempting Fork+Summary",
-                    trace_id, usage_ratio * 100.0, threshold_l3 * 100.0
-                );
-                
-                // Clone token_manager Arc to avoid borrow issues
-                let token_manager_clone = token_manager.clone();
-                
-                match try_compress_with_summary(&request_with_mapped, &trace_id, &token_manager_clone).await {
-                    Ok(forked_request) => {
-                        info!(
-                            "[{}] [Layer-3] Fork successful: {} → {} messages",
-                            trace_id,
-                            request_with_mapped.messages.len(),
-                            forked_request.messages.len()
-                        );
-                        
-                        request_with_mapped = forked_request;
-                        is_purified = false; // Fork doesn't break cache!
-                        
-                        // Re-estimate after fork (with calibration)
-                        let new_raw = ContextManager::estimate_token_usage(&request_with_mapped);
-                        let new_usage = calibrator.calibrate(new_raw);
-                        let new_ratio = new_usage as f32 / context_limit as f32;
-                        
-                        info!(
-                            "[{}] [Layer-3] Compression result: {:.1}% → {:.1}% (saved {} tokens)",
-                            trace_id, usage_ratio * 100.0, new_ratio * 100.0, estimated_usage - new_usage
-                        );
-                    }
-                    Err(e) => {
-                        error!(
-                            "[{}] [Layer-3] Fork+Summary failed: {}, falling back to error response",
-                            trace_id, e
-                        );
-                        
-                        // Return friendly error to user
-                        return (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "type": "error",
-                                "error": {
-                                    "type": "invalid_request_error",
-                                    "message": format!("Context too long and automatic compression failed: {}", e),
-                                    "suggestion": "Please use /compact or /clear command in Claude Code, or switch to a model with larger context window."
-                                }
-                            }))
-                        ).into_response();
-                    }
-                }
-            }
-        }
-
-        // [FIX] Estimate AFTER purification to get accurate token count for calibrator learning
-        // Only estimate for calibrator when content was not purified, to avoid skewed learning
-        let raw_estimated = if !is_purified {
-            ContextManager::estimate_token_usage(&request_with_mapped)
-        } else {
-            0 // Don't record calibration data when content was purified
-        };
-
         request_with_mapped.model = mapped_model.clone();
+        let server_tool_plan = server_tools::resolve_plan(&request_with_mapped.tools);
+        let document_citations = DocumentCitations::from_messages(&request_with_mapped.messages);
 
         // 生成 Trace ID (简单用时间戳后缀)
         // let _trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
-        let gemini_body = match transform_claude_request_in(&request_with_mapped, &project_id, retried_without_thinking) {
+        let tool_name_style = client_adapter
+            .as_ref()
+            .map(|a| a.tool_name_style())
+            .unwrap_or_default();
+        let mut gemini_body = match transform_claude_request_for_client(&request_with_mapped, &project_id, retried_without_thinking, tool_name_style) {
             Ok(b) => {
                 debug!("[{}] Transformed Gemini Body: {}", trace_id, serde_json::to_string_pretty(&b).unwrap_or_default());
                 b
@@ -681,6 +535,76 @@ pub async fn handle_messages(
             }
         };
 
+        // ===== [Context Compression] 跨协议上下文压缩管线 =====
+        // 在转换后的 v1internal 请求体上统一执行 (见 mappers::context_compression)
+        // 与其他协议一致，受 experimental.enable_context_compression 控制
+        // Layer 1: Tool result elision - keeps functionCall/functionResponse pairing
+        // Layer 2: Thinking compression - Breaks cache but preserves signatures
+        // Layer 3: Summary via cheap model - pinned first task, cached per session
+        let mut compression_report = None;
+        if let Some((contents, report)) = compressed_context.as_ref() {
+            gemini_body["request"]["contents"] = contents.clone();
+            compression_report = report.clone();
+        } else if !retried_without_thinking && compression_enabled {
+            // 1. 上下文上限按映射后的模型计算 (与 compress_for_protocol 相同)
+            let context_limit = crate::proxy::mappers::claude::utils::get_context_limit_for_model(&mapped_model);
+            // 2. [ENHANCED] 按校准系数缩放上限，等价于使用校准后的估算值 (PR #925)
+            let calibrated_limit = (context_limit as f32 / get_calibrator().get_factor().max(0.1)) as u32;
+            let summary_model = crate::proxy::common::model_mapping::resolve_model_route(
+                CONTEXT_SUMMARY_MODEL,
+                &*state.custom_mapping.read().await,
+            );
+
+            match apply_context_compression(
+                &token_manager,
+                &upstream,
+                &summary_model,
+                &mut gemini_body,
+                &session_id_str,
+                calibrated_limit,
+                compression_thresholds,
+                &trace_id,
+            )
+            .await
+            {
+                Ok(report) => {
+                    compressed_context = Some((gemini_body["request"]["contents"].clone(), report.clone()));
+                    compression_report = report;
+                }
+                Err(e) => {
+                    error!(
+                        "[{}] [Layer-3] Summary compression failed: {}, falling back to error response",
+                        trace_id, e
+                    );
+
+                    // Return friendly error to user
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(json!({
+                            "type": "error",
+                            "error": {
+                                "type": "invalid_request_error",
+                                "message": format!("Context too long and automatic compression failed: {}", e),
+                                "suggestion": "Please use /compact or /clear command in Claude Code, or switch to a model with larger context window."
+                            }
+                        }))
+                    ).into_response();
+                }
+            }
+        }
+        let is_purified = compression_report.as_ref().map(|r| r.is_purified()).unwrap_or(false);
+        let compression_header = compression_report
+            .as_ref()
+            .map(|r| r.header_value())
+            .unwrap_or_default();
+
+        // [FIX] Only estimate for calibrator when content was not compressed, to avoid skewed learning
+        let raw_estimated = if compression_report.is_none() {
+            ContextManager::estimate_token_usage(&request_with_mapped)
+        } else {
+            0
+        };
+
         if debug_logger::is_enabled(&debug_cfg) {
             let payload = json!({
                 "kind": "v1internal_request",
@@ -721,7 +645,12 @@ pub async fn handle_messages(
             for (k, v) in temp_headers {
                 if let Some(name) = k {
                     if let Ok(v_str) = v.to_str() {
-                        extra_headers.insert(name.to_string(), v_str.to_string());
+                        // anthropic-beta 与已有值合并，而不是覆盖 claude-code-20250219
+                        let value = match extra_headers.get(name.as_str()) {
+                            Some(existing) if name == "anthropic-beta" => merge_beta_header(existing, v_str),
+                            _ => v_str.to_string(),
+                        };
+                        extra_headers.insert(name.to_string(), value);
                         tracing::debug!("[{}] Added Adapter Header: {}: {}", trace_id, name, v_str);
                     }
                 }
@@ -730,6 +659,9 @@ pub async fn handle_messages(
 
         // Upstream call configuration continued...
 
+        // [Server Tools] 服务端工具循环需要在后续轮次中追加对话，保留一份请求体
+        let server_tool_body = server_tool_plan.is_active().then(|| gemini_body.clone());
+
         let response = match upstream
             .call_v1_internal_with_headers(method, &access_token, gemini_body, query, extra_headers.clone(), Some(account_id.as_str()))
             .await {
@@ -748,10 +680,133 @@ pub async fn handle_messages(
         if status.is_success() {
             // [智能限流] 请求成功，重置该账号的连续失败计数
             token_manager.mark_account_success(&email);
+            if let Some(plan) = &cache_plan {
+                crate::proxy::prompt_cache::record(plan, &account_id);
+            }
             
                 // Determine context limit based on model
                 let context_limit = crate::proxy::mappers::claude::utils::get_context_limit_for_model(&request_with_mapped.model);
 
+            // [Server Tools] 合成 web_search / web_fetch 调用由代理执行后继续生成
+            // 流式请求逐轮转发 (合成调用替换为 server_tool_use 块)，非流式请求汇总后一次性返回
+            if let Some(loop_body) = server_tool_body {
+                let upstream_usage = UpstreamUsage::default();
+                let target = UpstreamTarget {
+                    access_token: access_token.clone(),
+                    account_id: account_id.clone(),
+                    extra_headers: extra_headers.clone(),
+                };
+
+                if client_wants_stream {
+                    let turn_stream = {
+                        let trace_id = trace_id.clone();
+                        let email = email.clone();
+                        let session_id = session_id_str.clone();
+                        let message_count = request_with_mapped.messages.len();
+                        let client_adapter = client_adapter.clone();
+                        let document_citations = document_citations.clone();
+                        move |gemini_stream| {
+                            create_claude_sse_stream(
+                                gemini_stream,
+                                trace_id.clone(),
+                                email.clone(),
+                                Some(session_id.clone()),
+                                scaling_enabled,
+                                context_limit,
+                                None, // 多轮拼接的用量不参与估算校准
+                                message_count,
+                                client_adapter.clone(),
+                                document_citations.clone(),
+                            )
+                        }
+                    };
+                    let sse_error_format = client_adapter
+                        .as_ref()
+                        .map(|a| a.sse_error_format())
+                        .unwrap_or_default();
+                    let stream = stream_server_tool_loop(
+                        token_manager.clone(),
+                        upstream.clone(),
+                        response,
+                        loop_body,
+                        server_tool_plan,
+                        target,
+                        session_id_str.clone(),
+                        trace_id.clone(),
+                        upstream_usage.clone(),
+                        turn_stream,
+                    )
+                    .map(move |result| -> Result<Bytes, std::io::Error> {
+                        Ok(result.unwrap_or_else(|e| server_tools::error_event(sse_error_format, &e)))
+                    });
+                    return Response::builder()
+                        .status(StatusCode::OK)
+                        .header(header::CONTENT_TYPE, "text/event-stream")
+                        .header(header::CACHE_CONTROL, "no-cache")
+                        .header(header::CONNECTION, "keep-alive")
+                        .header("X-Accel-Buffering", "no")
+                        .header("X-Account-Email", &email)
+                        .header("X-Mapped-Model", &request_with_mapped.model)
+                        .header("X-Context-Purified", if is_purified { "true" } else { "false" })
+                        .header("X-Context-Compression", compression_header.as_str())
+                        .extension(upstream_usage)
+                        .body(Body::from_stream(stream))
+                        .unwrap();
+                }
+
+                let convert = |gemini_response: &GeminiResponse| {
+                    transform_response(
+                        gemini_response,
+                        scaling_enabled,
+                        context_limit,
+                        Some(session_id_str.clone()),
+                        request_with_mapped.model.clone(),
+                        request_with_mapped.messages.len(),
+                        document_citations.clone(),
+                    )
+                };
+
+                match run_server_tool_loop(
+                    &token_manager,
+                    &upstream,
+                    response,
+                    loop_body,
+                    server_tool_plan,
+                    target,
+                    &session_id_str,
+                    &trace_id,
+                    &upstream_usage,
+                    convert,
+                )
+                .await
+                {
+                    Ok(claude_response) => {
+                        info!(
+                            "[{}] ✓ Server tool loop finished. Model: {}, Tokens: In {}, Out {}",
+                            trace_id,
+                            request_with_mapped.model,
+                            claude_response.usage.input_tokens,
+                            claude_response.usage.output_tokens
+                        );
+                        return (
+                            StatusCode::OK,
+                            [
+                                ("X-Account-Email", email.as_str()),
+                                ("X-Mapped-Model", request_with_mapped.model.as_str()),
+                                ("X-Context-Compression", compression_header.as_str()),
+                            ],
+                            Extension(upstream_usage),
+                            Json(claude_response),
+                        ).into_response();
+                    }
+                    Err(e) => {
+                        tracing::warn!("[{}] Server tool loop failed: {}, retrying...", trace_id, e);
+                        last_error = e;
+                        continue;
+                    }
+                }
+            }
+
             // 处理流式响应
             if actual_stream {
                 let meta = json!({
@@ -763,13 +818,15 @@ pub async fn handle_messages(
                     "attempt": attempt,
                     "status": status.as_u16(),
                 });
-                let gemini_stream = debug_logger::wrap_reqwest_stream_with_debug(
+                // 记录上游原始 usageMetadata，供监控统计思考 Token (客户端 output_tokens 已含思考部分)
+                let upstream_usage = UpstreamUsage::default();
+                let gemini_stream = upstream_usage.observe(debug_logger::wrap_reqwest_stream_with_debug(
                     Box::pin(response.bytes_stream()),
                     debug_cfg.clone(),
                     trace_id.clone(),
                     "upstream_response",
                     meta,
-                );
+                ));
 
                 let current_message_count = request_with_mapped.messages.len();
 
@@ -786,6 +843,7 @@ pub async fn handle_messages(
                     Some(raw_estimated), // [FIX] Pass estimated tokens for calibrator learning
                     current_message_count, // [NEW v4.0.0] Pass message count for rewind detection
                     client_adapter.clone(), // [NEW] Pass client adapter
+                    document_citations.clone(),
                 );
 
                 let mut first_data_chunk = None;
@@ -839,11 +897,15 @@ pub async fn handle_messages(
                     Some(bytes) => {
                         // We have data! Construct the combined stream
                         let stream_rest = claude_stream;
+                        let sse_error_format = client_adapter
+                            .as_ref()
+                            .map(|a| a.sse_error_format())
+                            .unwrap_or_default();
                         let combined_stream = Box::pin(futures::stream::once(async move { Ok(bytes) })
-                            .chain(stream_rest.map(|result| -> Result<Bytes, std::io::Error> {
+                            .chain(stream_rest.map(move |result| -> Result<Bytes, std::io::Error> {
                                 match result {
                                     Ok(b) => Ok(b),
-                                    Err(e) => Ok(Bytes::from(format!("data: {{\"error\":\"{}\"}}\n\n", e))),
+                                    Err(e) => Ok(Bytes::from(sse_error_format.render("api_error", &e.to_string()))),
                                 }
                             })));
 
@@ -859,6 +921,8 @@ pub async fn handle_messages(
                                 .header("X-Account-Email", &email)
                                 .header("X-Mapped-Model", &request_with_mapped.model)
                                 .header("X-Context-Purified", if is_purified { "true" } else { "false" })
+                                .header("X-Context-Compression", compression_header.as_str())
+                                .extension(upstream_usage)
                                 .body(Body::from_stream(combined_stream))
                                 .unwrap();
                         } else {
@@ -874,6 +938,8 @@ pub async fn handle_messages(
                                         .header("X-Account-Email", &email)
                                         .header("X-Mapped-Model", &request_with_mapped.model)
                                         .header("X-Context-Purified", if is_purified { "true" } else { "false" })
+                                        .header("X-Context-Compression", compression_header.as_str())
+                                        .extension(upstream_usage)
                                         .body(Body::from(serde_json::to_string(&full_response).unwrap()))
                                         .unwrap();
                                 }
@@ -909,6 +975,10 @@ pub async fn handle_messages(
 
                 // 解包 response 字段（v1internal 格式）
                 let raw = gemini_resp.get("response").unwrap_or(&gemini_resp);
+                let upstream_usage = UpstreamUsage::default();
+                if let Some(usage) = raw.get("usageMetadata") {
+                    upstream_usage.add(usage);
+                }
 
                 // 转换为 Gemini Response 结构
                 let gemini_response: crate::proxy::mappers::claude::models::GeminiResponse = match serde_json::from_value(raw.clone()) {
@@ -930,6 +1000,7 @@ pub async fn handle_messages(
                     s_id_owned,
                     request_with_mapped.model.clone(),
                     request_with_mapped.messages.len(), // [NEW v4.0.0] Pass message count for rewind detection
+                    document_citations.clone(),
                 ) {
                     Ok(r) => r,
                     Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, format!("Transform error: {}", e)).into_response(),
@@ -951,7 +1022,16 @@ pub async fn handle_messages(
                     cache_info
                 );
 
-                return (StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", request_with_mapped.model.as_str())], Json(claude_response)).into_response();
+                return (
+                    StatusCode::OK,
+                    [
+                        ("X-Account-Email", email.as_str()),
+                        ("X-Mapped-Model", request_with_mapped.model.as_str()),
+                        ("X-Context-Compression", compression_header.as_str()),
+                    ],
+                    Extension(upstream_usage),
+                    Json(claude_response),
+                ).into_response();
             }
         }
         
@@ -1024,6 +1104,7 @@ pub async fn handle_messages(
                         crate::proxy::mappers::claude::models::MessageContent::Array(blocks) => {
                             blocks.push(crate::proxy::mappers::claude::models::ContentBlock::Text {
                                 text: repair_prompt.to_string(),
+                                citations: None,
                             });
                         }
                     }
@@ -1047,7 +1128,8 @@ pub async fn handle_messages(
                                 if !thinking.is_empty() {
                                     tracing::debug!("[Fallback] Converting thinking block to text (len={})", thinking.len());
                                     new_blocks.push(crate::proxy::mappers::claude::models::ContentBlock::Text { 
-                                        text: thinking 
+                                        text: thinking,
+                                        citations: None,
                                     });
                                 }
                             },
@@ -1065,6 +1147,8 @@ pub async fn handle_messages(
             // This ensures that any ToolResult in history is properly "closed" with synthetic messages
             // if its preceding Thinking block was just converted to Text.
             crate::proxy::mappers::claude::thinking_utils::close_tool_loop_for_thinking(&mut request_for_body.messages);
+            // 历史已改写，下一次尝试需重新压缩
+            compressed_context = None;
             
             // 清理模型名中的 -thinking 后缀
             if request_for_body.model.contains("claude-") {
@@ -1187,6 +1271,275 @@ pub async fn handle_messages(
     }
 }
 
+/// 服务端工具循环中后续轮次复用的上游调用参数
+struct UpstreamTarget {
+    access_token: String,
+    account_id: String,
+    extra_headers: HashMap<String, String>,
+}
+
+impl UpstreamTarget {
+    /// 在同一账号上发起下一轮生成
+    async fn continue_generation(
+        &self,
+        upstream: &UpstreamClient,
+        gemini_body: &Value,
+    ) -> Result<reqwest::Response, String> {
+        let response = upstream
+            .call_v1_internal_with_headers(
+                "streamGenerateContent",
+                &self.access_token,
+                gemini_body.clone(),
+                Some("alt=sse"),
+                self.extra_headers.clone(),
+                Some(self.account_id.as_str()),
+            )
+            .await?;
+        if !response.status().is_success() {
+            let status = response.status().as_u16();
+            return Err(format!("HTTP {}: {}", status, response.text().await.unwrap_or_default()));
+        }
+        Ok(response)
+    }
+}
+
+/// 执行一轮合成函数调用 (超出工具的 max_uses 或全局轮次上限时以 max_uses_exceeded 拒绝)
+async fn execute_server_calls(
+    token_manager: &Arc<TokenManager>,
+    upstream: &Arc<UpstreamClient>,
+    calls: Vec<ServerToolCall>,
+    plan: ServerToolPlan,
+    budget: &mut ServerToolBudget,
+    over_iteration_limit: bool,
+    trace_id: &str,
+) -> Vec<ServerToolOutcome> {
+    let mut outcomes = Vec::with_capacity(calls.len());
+    for call in calls {
+        let result = if over_iteration_limit || !budget.try_use(&plan, &call.name) {
+            Err(ServerToolError::MaxUsesExceeded)
+        } else {
+            run_server_tool_call(token_manager, upstream, &call, trace_id).await
+        };
+        if let Err(e) = &result {
+            tracing::warn!("[{}] [ServerTools] {} failed: {:?}", trace_id, call.name, e);
+        }
+        outcomes.push(ServerToolOutcome::new(call, result));
+    }
+    outcomes
+}
+
+/// 服务端工具循环
+///
+/// 拦截模型对合成 web_search / web_fetch 函数的调用，经 grounding 子请求执行后回填 functionResponse
+/// 并在同一账号上继续生成，直到模型给出最终回复、发起客户端工具调用或达到轮次上限
+#[allow(clippy::too_many_arguments)]
+async fn run_server_tool_loop<F>(
+    token_manager: &Arc<TokenManager>,
+    upstream: &Arc<UpstreamClient>,
+    first_response: reqwest::Response,
+    mut gemini_body: Value,
+    plan: ServerToolPlan,
+    target: UpstreamTarget,
+    session_id: &str,
+    trace_id: &str,
+    usage: &UpstreamUsage,
+    convert: F,
+) -> Result<ClaudeResponse, String>
+where
+    F: Fn(&GeminiResponse) -> Result<ClaudeResponse, String>,
+{
+    let max_iterations = crate::proxy::get_server_tools_config().max_iterations;
+    let mut response = first_response;
+    let mut content = Vec::new();
+    let mut all_outcomes: Vec<ServerToolOutcome> = Vec::new();
+    let mut budget = ServerToolBudget::default();
+    let mut earlier_output_tokens = 0u32;
+    let mut iteration = 0u32;
+
+    loop {
+        let collected = crate::proxy::mappers::gemini::collector::collect_stream_to_json(
+            Box::pin(response.bytes_stream()),
+            session_id,
+        )
+        .await?;
+        if let Some(turn_usage) = collected.get("usageMetadata") {
+            usage.add(turn_usage);
+        }
+        let gemini_response: GeminiResponse = serde_json::from_value(collected.clone())
+            .map_err(|e| format!("Convert error: {}", e))?;
+        let turn = convert(&gemini_response)?;
+        let (calls, has_client_calls) = server_tools::split_function_calls(&collected, plan);
+
+        if calls.is_empty() {
+            content.extend(turn.content.clone());
+            return Ok(server_tools::finish_response(turn, content, earlier_output_tokens, &all_outcomes));
+        }
+
+        let outcomes = execute_server_calls(
+            token_manager,
+            upstream,
+            calls,
+            plan,
+            &mut budget,
+            iteration >= max_iterations,
+            trace_id,
+        )
+        .await;
+        iteration += 1;
+        content.extend(server_tools::replace_server_calls(turn.content.clone(), plan, &outcomes));
+        all_outcomes.extend(outcomes.iter().cloned());
+
+        // 同时发起了客户端工具调用 (交由客户端执行) 或已达上限: 结束循环
+        if has_client_calls || iteration > max_iterations {
+            return Ok(server_tools::finish_response(turn, content, earlier_output_tokens, &all_outcomes));
+        }
+
+        earlier_output_tokens += turn.usage.output_tokens;
+        server_tools::append_turn(&mut gemini_body, &collected, &outcomes);
+        info!(
+            "[{}] [ServerTools] Round {} executed {} call(s), continuing generation",
+            trace_id,
+            iteration,
+            outcomes.len()
+        );
+
+        response = target.continue_generation(upstream, &gemini_body).await?;
+    }
+}
+
+/// 流式服务端工具循环
+///
+/// 每轮上游输出经 create_claude_sse_stream 转换后立即转发，合成函数的 tool_use 块不下发；
+/// 轮次结束后执行合成调用，以 server_tool_use + 结果块插入同一条消息，再在同一账号上继续生成
+#[allow(clippy::too_many_arguments)]
+fn stream_server_tool_loop<F>(
+    token_manager: Arc<TokenManager>,
+    upstream: Arc<UpstreamClient>,
+    first_response: reqwest::Response,
+    mut gemini_body: Value,
+    plan: ServerToolPlan,
+    target: UpstreamTarget,
+    session_id: String,
+    trace_id: String,
+    usage: UpstreamUsage,
+    turn_stream: F,
+) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>>
+where
+    F: Fn(
+            std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, reqwest::Error>> + Send>>,
+        ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, String>> + Send>>
+        + Send
+        + 'static,
+{
+    Box::pin(async_stream::stream! {
+        let max_iterations = crate::proxy::get_server_tools_config().max_iterations;
+        let mut splicer = server_tools::SseSplicer::new(plan);
+        let mut budget = ServerToolBudget::default();
+        let mut all_outcomes: Vec<ServerToolOutcome> = Vec::new();
+        let mut earlier_output_tokens = 0u32;
+        let mut iteration = 0u32;
+        let mut response = first_response;
+
+        loop {
+            // 转发的同时保留原始上游数据，轮次结束后汇总出完整的函数调用
+            let recorded = Arc::new(std::sync::Mutex::new(Vec::<Bytes>::new()));
+            let tee: std::pin::Pin<Box<dyn futures::Stream<Item = Result<Bytes, reqwest::Error>> + Send>> = {
+                let recorded = recorded.clone();
+                usage.observe(Box::pin(response.bytes_stream())).map(move |item| {
+                    if let (Ok(chunk), Ok(mut chunks)) = (&item, recorded.lock()) {
+                        chunks.push(chunk.clone());
+                    }
+                    item
+                })
+                .boxed()
+            };
+            let mut claude_stream = turn_stream(tee);
+            while let Some(item) = claude_stream.next().await {
+                match item {
+                    Ok(chunk) => {
+                        for event in splicer.push(&chunk) {
+                            yield Ok(event);
+                        }
+                    }
+                    Err(e) => {
+                        yield Err(e);
+                        return;
+                    }
+                }
+            }
+            // 上游中断时已下发 error 事件，不再补发结束事件
+            if splicer.failed() {
+                return;
+            }
+
+            let raw = recorded.lock().map(|chunks| chunks.concat()).unwrap_or_default();
+            let collected = match crate::proxy::mappers::gemini::collector::collect_stream_to_json(
+                futures::stream::iter([Ok::<Bytes, String>(Bytes::from(raw))]),
+                &session_id,
+            )
+            .await
+            {
+                Ok(collected) => collected,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+            let (calls, has_client_calls) = server_tools::split_function_calls(&collected, plan);
+
+            if calls.is_empty() {
+                for event in splicer.finish(None, earlier_output_tokens, &all_outcomes) {
+                    yield Ok(event);
+                }
+                return;
+            }
+
+            let outcomes = execute_server_calls(
+                &token_manager,
+                &upstream,
+                calls,
+                plan,
+                &mut budget,
+                iteration >= max_iterations,
+                &trace_id,
+            )
+            .await;
+            iteration += 1;
+            let blocks: Vec<crate::proxy::mappers::claude::models::ContentBlock> = outcomes.iter().flat_map(server_tools::client_blocks).collect();
+            for event in splicer.emit_blocks(&blocks) {
+                yield Ok(event);
+            }
+            all_outcomes.extend(outcomes.iter().cloned());
+
+            // 同时发起了客户端工具调用 (交由客户端执行) 或已达上限: 结束消息
+            if has_client_calls || iteration > max_iterations {
+                let stop_reason = if has_client_calls { "tool_use" } else { "end_turn" };
+                for event in splicer.finish(Some(stop_reason), earlier_output_tokens, &all_outcomes) {
+                    yield Ok(event);
+                }
+                return;
+            }
+
+            earlier_output_tokens += splicer.end_turn();
+            server_tools::append_turn(&mut gemini_body, &collected, &outcomes);
+            info!(
+                "[{}] [ServerTools] Round {} executed {} call(s), continuing generation (stream)",
+                trace_id,
+                iteration,
+                outcomes.len()
+            );
+
+            response = match target.continue_generation(&upstream, &gemini_body).await {
+                Ok(response) => response,
+                Err(e) => {
+                    yield Err(e);
+                    return;
+                }
+            };
+        }
+    })
+}
+
 /// 列出可用模型
 pub async fn handle_list_models(State(state): State<AppState>) -> impl IntoResponse {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
@@ -1368,7 +1721,7 @@ fn extract_last_user_message_for_detection(request: &ClaudeRequest) -> Option<St
                 crate::proxy::mappers::claude::models::MessageContent::Array(arr) => {
                     arr.iter()
                         .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
+                            crate::proxy::mappers::claude::models::ContentBlock::Text { text, .. } => Some(text.as_str()),
                             _ => None,
                         })
                         .collect::<Vec<_>>()
@@ -1425,7 +1778,7 @@ fn is_warmup_request(request: &ClaudeRequest) -> bool {
             crate::proxy::mappers::claude::models::MessageContent::Array(arr) => {
                 for block in arr {
                     match block {
-                        crate::proxy::mappers::claude::models::ContentBlock::Text { text } => {
+                        crate::proxy::mappers::claude::models::ContentBlock::Text { text, .. } => {
                             let trimmed = text.trim();
                             if trimmed == "Warmup" || trimmed.starts_with("Warmup\n") {
                                 return true;
@@ -1521,197 +1874,3 @@ fn create_warmup_response(request: &ClaudeRequest, is_stream: bool) -> Response
         ).into_response()
     }
 }
-
-// ===== [Helper] Synchronous Upstream Call =====
-// Reusable function for making non-streaming calls to Gemini API
-// Used by Layer 3 and potentially other internal operations
-
-/// Call Gemini API synchronously and return the response text
-/// 
-/// This is used for internal operations that need to wait for a complete response,
-/// such as generating summaries or other background tasks.
-async fn call_gemini_sync(
-    model: &str,
-    request: &ClaudeRequest,
-    token_manager: &Arc<crate::proxy::TokenManager>,
-    trace_id: &str,
-) -> Result<String, String> {
-    // Get token and transform request
-    let (access_token, project_id, _, _, _wait_ms) = token_manager
-        .get_token("gemini", false, None, model)
-        .await
-        .map_err(|e| format!("Failed to get account: {}", e))?;
-    
-    let gemini_body = crate::proxy::mappers::claude::transform_claude_request_in(request, &project_id, false)
-        .map_err(|e| format!("Failed to transform request: {}", e))?;
-    
-    // Call Gemini API
-    let upstream_url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{}:generateContent",
-        model
-    );
-    
-    debug!("[{}] Calling Gemini API: {}", trace_id, model);
-    
-    let response = reqwest::Client::new()
-        .post(&upstream_url)
-        .header("Authorization", format!("Bearer {}", access_token))
-        .header("Content-Type", "application/json")
-        .json(&gemini_body)
-        .send()
-        .await
-        .map_err(|e| format!("API call failed: {}", e))?;
-    
-    if !response.status().is_success() {
-        return Err(format!(
-            "API returned {}: {}", 
-            response.status(), 
-            response.text().await.unwrap_or_default()
-        ));
-    }
-    
-    let gemini_response: Value = response.json().await
-        .map_err(|e| format!("Failed to parse response: {}", e))?;
-    
-    // Extract text from response
-    gemini_response
-        .get("candidates")
-        .and_then(|c| c.get(0))
-        .and_then(|c| c.get("content"))
-        .and_then(|c| c.get("parts"))
-        .and_then(|p| p.get(0))
-        .and_then(|p| p.get("text"))
-        .and_then(|t| t.as_str())
-        .map(|s| s.to_string())
-        .ok_or_else(|| "Failed to extract text from response".to_string())
-}
-
-// ===== [Layer 3] Fork Conversation + XML Summary =====
-// This is the ultimate context compression strategy
-// Borrowed from Practical-Guide-to-Context-Engineering + Claude Code official practice
-
-/// Try to compress context by generating an XML summary and forking the conversation
-/// 
-/// This function:
-/// 1. Extracts the last valid thinking signature
-/// 2. Calls a cheap model (gemini-2.5-flash-lite) to generate XML summary
-/// 3. Creates a new message sequence with summary as prefix
-/// 4. Preserves the signature in the summary
-/// 5. Returns the forked request
-/// 
-/// Returns Ok(forked_request) on success, Err(error_message) on failure
-async fn try_compress_with_summary(
-    original_request: &ClaudeRequest,
-    trace_id: &str,
-    token_manager: &Arc<crate::proxy::TokenManager>,
-) -> Result<ClaudeRequest, String> {
-    info!("[{}] [Layer-3] Starting context compression with XML summary", trace_id);
-    
-    // 1. Extract last valid signature
-    let last_signature = ContextManager::extract_last_valid_signature(&original_request.messages);
-    
-    if let Some(ref sig) = last_signature {
-        debug!("[{}] [Layer-3] Extracted signature (len: {})", trace_id, sig.len());
-    }
-    
-    // 2. Build summary request
-    let mut summary_messages = original_request.messages.clone();
-    
-    // Add instruction to include signature in summary
-    let signature_instruction = if let Some(ref sig) = last_signature {
-        format!("\n\n**CRITICAL**: The last thinking signature is:\n```\n{}\n```\nYou MUST include this EXACTLY in the <latest_thinking_signature> section.", sig)
-    } else {
-        "\n\n**Note**: No thinking signature found in history. Leave <latest_thinking_signature> empty.".to_string()
-    };
-    
-    // Append summary request as the last user message
-    summary_messages.push(Message {
-        role: "user".to_string(),
-        content: MessageContent::String(format!(
-            "{}{}",
-            CONTEXT_SUMMARY_PROMPT,
-            signature_instruction
-        )),
-    });
-    
-    let summary_request = ClaudeRequest {
-        model: INTERNAL_BACKGROUND_TASK.to_string(),
-        messages: summary_messages,
-        system: None,
-        stream: false,
-        max_tokens: Some(8000),
-        temperature: Some(0.3),
-        tools: None,
-        thinking: None,
-        metadata: None,
-        top_p: None,
-        top_k: None,
-        output_config: None,
-        size: None,
-        quality: None,
-    };
-    
-    debug!("[{}] [Layer-3] Calling {} for summary generation", trace_id, INTERNAL_BACKGROUND_TASK);
-    
-    // 3. Call upstream using helper function (reuse existing infrastructure)
-    let xml_summary = call_gemini_sync(
-        INTERNAL_BACKGROUND_TASK,
-        &summary_request,
-        token_manager,
-        trace_id,
-    ).await?;
-    
-    info!("[{}] [Layer-3] Generated XML summary (len: {} chars)", trace_id, xml_summary.len());
-    
-    // 4. Create forked conversation with summary as prefix
-    let mut forked_messages = vec![
-        Message {
-            role: "user".to_string(),
-            content: MessageContent::String(format!(
-                "Context has been compressed. Here is the structured summary of our conversation history:\n\n{}",
-                xml_summary
-            )),
-        },
-        Message {
-            role: "assistant".to_string(),
-            content: MessageContent::String(
-                "I have reviewed the compressed context summary. I understand the current state and will continue from here.".to_string()
-            ),
-        },
-    ];
-    
-    // 5. Append the user's latest message (if exists and is not the summary request)
-    if let Some(last_msg) = original_request.messages.last() {
-        if last_msg.role == "user" {
-            // Check if it's not the summary instruction we just added
-            if !matches!(&last_msg.content, MessageContent::String(s) if s.contains(CONTEXT_SUMMARY_PROMPT)) {
-                forked_messages.push(last_msg.clone());
-            }
-        }
-    }
-    
-    info!(
-        "[{}] [Layer-3] Fork successful: {} messages → {} messages",
-        trace_id,
-        original_request.messages.len(),
-        forked_messages.len()
-    );
-    
-    // 6. Return forked request
-    Ok(ClaudeRequest {
-        model: original_request.model.clone(),
-        messages: forked_messages,
-        system: original_request.system.clone(),
-        stream: original_request.stream,
-        max_tokens: original_request.max_tokens,
-        temperature: original_request.temperature,
-        tools: original_request.tools.clone(),
-        thinking: original_request.thinking.clone(),
-        metadata: original_request.metadata.clone(),
-        top_p: original_request.top_p,
-        top_k: original_request.top_k,
-        output_config: original_request.output_config.clone(),
-        size: original_request.size.clone(),
-        quality: original_request.quality.clone(),
-    })
-}
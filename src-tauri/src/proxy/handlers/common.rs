@@ -3,6 +3,11 @@ use tracing::{debug, info};
 use axum::{http::StatusCode, response::{IntoResponse, Response}, Json, extract::State};
 use serde_json::{json, Value};
 use crate::proxy::server::AppState;
+use crate::proxy::mappers::context_compression::{self, CompressionReport, CompressionThresholds};
+use crate::proxy::mappers::claude::server_tools::{self, GroundingResult, ServerToolCall, ServerToolError};
+use crate::proxy::upstream::client::UpstreamClient;
+use crate::proxy::TokenManager;
+use std::sync::Arc;
 
 // ===== 统一重试与退避策略 =====
 
@@ -187,3 +192,205 @@ pub async fn handle_detect_model(
 
     Json(response).into_response()
 }
+
+// ===== 跨协议上下文压缩 =====
+
+/// 摘要使用的虚拟模型 ID (默认路由到廉价的 Flash 模型，可通过自定义映射覆盖)
+pub const CONTEXT_SUMMARY_MODEL: &str = "internal-background-task";
+
+/// 对转换后的 v1internal 请求体执行上下文压缩 (L1 工具折叠 / L2 思维压缩 / L3 摘要)
+///
+/// 三种协议共用；L3 摘要按会话缓存，只有新增的历史才会送去摘要模型
+#[allow(clippy::too_many_arguments)]
+pub async fn apply_context_compression(
+    token_manager: &Arc<TokenManager>,
+    upstream: &Arc<UpstreamClient>,
+    summary_model: &str,
+    body: &mut Value,
+    session_id: &str,
+    context_limit: u32,
+    thresholds: CompressionThresholds,
+    trace_id: &str,
+) -> Result<Option<CompressionReport>, String> {
+    let report = context_compression::compress_request(
+        body,
+        session_id,
+        context_limit,
+        thresholds,
+        |prompt| summarize_with_cheap_model(token_manager, upstream, summary_model, prompt, trace_id),
+    )
+    .await?;
+
+    if let Some(report) = &report {
+        info!(
+            "[{}] [ContextCompression] {:?}: {} → {} tokens ({:.1}% → {:.1}% of {})",
+            trace_id,
+            report.layers,
+            report.tokens_before,
+            report.tokens_after,
+            report.tokens_before as f32 * 100.0 / report.context_limit.max(1) as f32,
+            report.tokens_after as f32 * 100.0 / report.context_limit.max(1) as f32,
+            report.context_limit
+        );
+    }
+    Ok(report)
+}
+
+/// OpenAI / Gemini 协议的上下文压缩入口
+///
+/// 受 `experimental.enable_context_compression` 控制，上下文上限按映射后的模型计算
+pub async fn compress_for_protocol(
+    state: &AppState,
+    body: &mut Value,
+    session_id: &str,
+    mapped_model: &str,
+    trace_id: &str,
+) -> Result<Option<CompressionReport>, String> {
+    let thresholds = {
+        let experimental = state.experimental.read().await;
+        if !experimental.enable_context_compression {
+            return Ok(None);
+        }
+        CompressionThresholds::from(&*experimental)
+    };
+    let summary_model = crate::proxy::common::model_mapping::resolve_model_route(
+        CONTEXT_SUMMARY_MODEL,
+        &*state.custom_mapping.read().await,
+    );
+    let context_limit = crate::proxy::mappers::claude::utils::get_context_limit_for_model(mapped_model);
+
+    apply_context_compression(
+        &state.token_manager,
+        &state.upstream,
+        &summary_model,
+        body,
+        session_id,
+        context_limit,
+        thresholds,
+        trace_id,
+    )
+    .await
+}
+
+/// 调用廉价模型生成上下文摘要
+async fn summarize_with_cheap_model(
+    token_manager: &Arc<TokenManager>,
+    upstream: &Arc<UpstreamClient>,
+    model: &str,
+    prompt: String,
+    trace_id: &str,
+) -> Result<String, String> {
+    let (access_token, project_id, email, account_id, _wait_ms) = token_manager
+        .get_token("text", false, None, model)
+        .await
+        .map_err(|e| format!("Failed to get account: {}", e))?;
+
+    debug!("[{}] [ContextCompression] Generating summary with {} ({})", trace_id, model, email);
+
+    let wrapped_body = json!({
+        "project": project_id,
+        "requestId": format!("compress-{}", uuid::Uuid::new_v4()),
+        "request": {
+            "contents": [{ "role": "user", "parts": [{ "text": prompt }] }],
+            "generationConfig": { "maxOutputTokens": 8000, "temperature": 0.3 }
+        },
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "text"
+    });
+
+    let response = upstream
+        .call_v1_internal("generateContent", &access_token, wrapped_body, None, Some(account_id.as_str()))
+        .await
+        .map_err(|e| format!("Summary request failed: {}", e))?;
+
+    if !response.status().is_success() {
+        return Err(format!(
+            "Summary model returned {}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        ));
+    }
+
+    let result: Value = response
+        .json()
+        .await
+        .map_err(|e| format!("Failed to parse summary response: {}", e))?;
+    let inner = result.get("response").unwrap_or(&result);
+    let text: String = inner
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .map(|parts| {
+            parts
+                .iter()
+                .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+                .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let snapshot = context_compression::extract_snapshot(&text);
+    if snapshot.is_empty() {
+        return Err("Summary model returned empty text".to_string());
+    }
+    info!("[{}] [ContextCompression] Generated summary ({} chars)", trace_id, snapshot.len());
+    Ok(snapshot)
+}
+
+// ===== 服务端工具 (web_search / web_fetch) =====
+
+/// 执行一次服务端工具调用: 在账号池上发起独立的 grounding 子请求
+///
+/// 合成函数调用由 Claude 处理器的服务端工具循环拦截后交给这里执行，失败不会中断主请求，
+/// 而是作为工具错误回填给模型
+pub async fn run_server_tool_call(
+    token_manager: &Arc<TokenManager>,
+    upstream: &Arc<UpstreamClient>,
+    call: &ServerToolCall,
+    trace_id: &str,
+) -> Result<GroundingResult, ServerToolError> {
+    let prompt = server_tools::grounding_prompt(call)?;
+    let model = server_tools::GROUNDING_MODEL;
+
+    let (access_token, project_id, email, account_id, _wait_ms) = token_manager
+        .get_token("web_search", false, None, model)
+        .await
+        .map_err(|e| ServerToolError::Unavailable(format!("Failed to get account: {}", e)))?;
+
+    debug!("[{}] [ServerTools] Running {} via {} ({})", trace_id, call.name, model, email);
+
+    let body = server_tools::build_grounding_request(call, &prompt, &project_id, model);
+    let response = upstream
+        .call_v1_internal("generateContent", &access_token, body, None, Some(account_id.as_str()))
+        .await
+        .map_err(|e| ServerToolError::Unavailable(format!("Grounding request failed: {}", e)))?;
+
+    if !response.status().is_success() {
+        return Err(ServerToolError::Unavailable(format!(
+            "Grounding model returned {}: {}",
+            response.status(),
+            response.text().await.unwrap_or_default()
+        )));
+    }
+
+    let result: Value = response
+        .json()
+        .await
+        .map_err(|e| ServerToolError::Unavailable(format!("Failed to parse grounding response: {}", e)))?;
+    let grounding = server_tools::parse_grounding_response(result.get("response").unwrap_or(&result));
+
+    if grounding.summary.is_empty() && grounding.sources.is_empty() {
+        return Err(ServerToolError::Unavailable("Grounding model returned no results".to_string()));
+    }
+    info!(
+        "[{}] [ServerTools] {} returned {} sources ({} chars)",
+        trace_id,
+        call.name,
+        grounding.sources.len(),
+        grounding.summary.len()
+    );
+    Ok(grounding)
+}
@@ -0,0 +1,332 @@
+// Files API 处理器 (OpenAI / Anthropic 兼容)
+// 带 anthropic-version 请求头时按 Anthropic Files API 格式返回，否则按 OpenAI 格式返回。
+// 文件按上传者的 User Token 隔离，其他 Token 的文件一律视为不存在 (404)
+
+use axum::{
+    extract::{Multipart, Path},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::proxy::common::document_input::MAX_DOCUMENT_BYTES;
+use crate::proxy::files::{guess_mime_type, FileStore, StoredFile, DEFAULT_FILE_TTL_SECS};
+use crate::proxy::middleware::auth::{resource_owner, UserTokenIdentity};
+
+/// 解析后的上传表单
+struct UploadForm {
+    content: Vec<u8>,
+    filename: String,
+    mime_type: String,
+    purpose: String,
+    ttl_secs: i64,
+}
+
+fn is_anthropic(headers: &HeaderMap) -> bool {
+    headers.contains_key("anthropic-version") || headers.contains_key("anthropic-beta")
+}
+
+fn file_object(file: &StoredFile, anthropic: bool) -> Value {
+    if anthropic {
+        let created_at = chrono::DateTime::from_timestamp(file.created_at, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default();
+        json!({
+            "type": "file",
+            "id": file.id,
+            "filename": file.filename,
+            "mime_type": file.mime_type,
+            "size_bytes": file.bytes,
+            "created_at": created_at,
+            "downloadable": true
+        })
+    } else {
+        json!({
+            "id": file.id,
+            "object": "file",
+            "bytes": file.bytes,
+            "created_at": file.created_at,
+            "expires_at": file.expires_at,
+            "filename": file.filename,
+            "purpose": file.purpose,
+            "status": "processed"
+        })
+    }
+}
+
+/// 错误响应: Anthropic 客户端使用 {"type":"error",...}，其余使用 OpenAI {"error":{...}}
+fn error_response(status: StatusCode, message: String, anthropic: bool) -> Response {
+    let body = if anthropic {
+        let error_type = match status {
+            StatusCode::NOT_FOUND => "not_found_error",
+            StatusCode::PAYLOAD_TOO_LARGE => "request_too_large",
+            s if s.is_client_error() => "invalid_request_error",
+            _ => "api_error",
+        };
+        json!({
+            "type": "error",
+            "error": { "type": error_type, "message": message }
+        })
+    } else {
+        let (error_type, code) = match status {
+            StatusCode::NOT_FOUND => ("invalid_request_error", Some("file_not_found")),
+            s if s.is_client_error() => ("invalid_request_error", None),
+            _ => ("server_error", None),
+        };
+        json!({
+            "error": { "message": message, "type": error_type, "param": null, "code": code }
+        })
+    };
+    (status, Json(body)).into_response()
+}
+
+fn not_found(file_id: &str) -> (StatusCode, String) {
+    (
+        StatusCode::NOT_FOUND,
+        format!("File '{}' was not found or has expired", file_id),
+    )
+}
+
+/// 在阻塞线程池中访问文件存储，避免同步文件 I/O 占用异步运行时
+async fn with_store<T, F>(f: F) -> Result<T, (StatusCode, String)>
+where
+    F: FnOnce(&FileStore) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    let error = match tokio::task::spawn_blocking(move || {
+        FileStore::open_default().and_then(|store| f(&store))
+    })
+    .await
+    {
+        Ok(Ok(value)) => return Ok(value),
+        Ok(Err(e)) => e,
+        Err(e) => e.to_string(),
+    };
+    tracing::error!("[Files] Store operation failed: {}", error);
+    Err((
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to access the file store".to_string(),
+    ))
+}
+
+async fn parse_upload_form(mut multipart: Multipart) -> Result<UploadForm, (StatusCode, String)> {
+    let mut content: Option<Vec<u8>> = None;
+    let mut filename: Option<String> = None;
+    let mut content_type: Option<String> = None;
+    let mut purpose = "user_data".to_string();
+    let mut ttl_secs = DEFAULT_FILE_TTL_SECS;
+
+    while let Some(field) = multipart.next_field().await.map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            format!("Invalid multipart body: {}", e),
+        )
+    })? {
+        let name = field.name().unwrap_or("").to_string();
+
+        match name.as_str() {
+            "file" => {
+                filename = field.file_name().map(|s| s.to_string());
+                content_type = field.content_type().map(|s| s.to_string());
+                let bytes = field.bytes().await.map_err(|e| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("Failed to read uploaded file: {}", e),
+                    )
+                })?;
+                if bytes.len() > MAX_DOCUMENT_BYTES {
+                    return Err((
+                        StatusCode::PAYLOAD_TOO_LARGE,
+                        format!(
+                            "File is {:.1} MB, exceeding the {} MB upload limit",
+                            bytes.len() as f64 / (1024.0 * 1024.0),
+                            MAX_DOCUMENT_BYTES / (1024 * 1024)
+                        ),
+                    ));
+                }
+                content = Some(bytes.to_vec());
+            }
+            "purpose" => {
+                purpose = field
+                    .text()
+                    .await
+                    .ok()
+                    .filter(|p| !p.trim().is_empty())
+                    .unwrap_or(purpose);
+            }
+            "expires_after[seconds]" => {
+                let value = field.text().await.unwrap_or_default();
+                ttl_secs = value.trim().parse::<i64>().map_err(|_| {
+                    (
+                        StatusCode::BAD_REQUEST,
+                        format!("Invalid expires_after[seconds]: {}", value),
+                    )
+                })?;
+            }
+            _ => {}
+        }
+    }
+
+    let content = content.ok_or((
+        StatusCode::BAD_REQUEST,
+        "Missing required multipart field 'file'".to_string(),
+    ))?;
+    let filename = filename
+        .filter(|f| !f.is_empty())
+        .unwrap_or_else(|| "upload".to_string());
+    // 客户端未声明具体类型时按扩展名推断，解析时再按文件头识别
+    let mime_type = content_type
+        .filter(|t| !t.is_empty() && t != "application/octet-stream")
+        .or_else(|| guess_mime_type(&filename).map(|m| m.to_string()))
+        .unwrap_or_else(|| "application/octet-stream".to_string());
+
+    Ok(UploadForm {
+        content,
+        filename,
+        mime_type,
+        purpose,
+        ttl_secs,
+    })
+}
+
+/// 上传文件 (POST /v1/files)，文件归属于当前 User Token
+pub async fn handle_upload_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Response {
+    let anthropic = is_anthropic(&headers);
+    let owner = resource_owner(identity);
+    let result = async {
+        let form = parse_upload_form(multipart).await?;
+        with_store(move |store| {
+            store.put(
+                &form.content,
+                &form.filename,
+                &form.mime_type,
+                &form.purpose,
+                form.ttl_secs,
+                owner.as_deref(),
+            )
+        })
+        .await
+    }
+    .await;
+
+    match result {
+        Ok(file) => {
+            info!(
+                "[Files] Stored {} ({}, {} bytes) as {}",
+                file.filename, file.mime_type, file.bytes, file.id
+            );
+            Json(file_object(&file, anthropic)).into_response()
+        }
+        Err((status, message)) => error_response(status, message, anthropic),
+    }
+}
+
+/// 列出当前 User Token 的文件 (GET /v1/files)
+pub async fn handle_list_files(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+) -> Response {
+    let anthropic = is_anthropic(&headers);
+    let owner = resource_owner(identity);
+    let files = match with_store(move |store| store.list(owner.as_deref())).await {
+        Ok(files) => files,
+        Err((status, message)) => return error_response(status, message, anthropic),
+    };
+    let data: Vec<Value> = files.iter().map(|f| file_object(f, anthropic)).collect();
+
+    let body = if anthropic {
+        json!({
+            "data": data,
+            "has_more": false,
+            "first_id": files.first().map(|f| f.id.clone()),
+            "last_id": files.last().map(|f| f.id.clone())
+        })
+    } else {
+        json!({ "object": "list", "data": data, "has_more": false })
+    };
+    Json(body).into_response()
+}
+
+/// 获取文件元数据 (GET /v1/files/:file_id)
+pub async fn handle_get_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let anthropic = is_anthropic(&headers);
+    let owner = resource_owner(identity);
+    let id = file_id.clone();
+    match with_store(move |store| store.get(&id, owner.as_deref())).await {
+        Ok(Some(file)) => Json(file_object(&file, anthropic)).into_response(),
+        Ok(None) => {
+            let (status, message) = not_found(&file_id);
+            error_response(status, message, anthropic)
+        }
+        Err((status, message)) => error_response(status, message, anthropic),
+    }
+}
+
+/// 下载文件内容 (GET /v1/files/:file_id/content)
+pub async fn handle_get_file_content(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let anthropic = is_anthropic(&headers);
+    let owner = resource_owner(identity);
+    let id = file_id.clone();
+    let (file, content) = match with_store(move |store| store.read(&id, owner.as_deref())).await {
+        Ok(Some(found)) => found,
+        Ok(None) => {
+            let (status, message) = not_found(&file_id);
+            return error_response(status, message, anthropic);
+        }
+        Err((status, message)) => return error_response(status, message, anthropic),
+    };
+    (
+        [
+            (header::CONTENT_TYPE, file.mime_type.clone()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}\"",
+                    file.filename.replace(['"', '\r', '\n'], "")
+                ),
+            ),
+        ],
+        content,
+    )
+        .into_response()
+}
+
+/// 删除文件 (DELETE /v1/files/:file_id)
+pub async fn handle_delete_file(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Path(file_id): Path<String>,
+) -> Response {
+    let anthropic = is_anthropic(&headers);
+    let owner = resource_owner(identity);
+    let id = file_id.clone();
+    match with_store(move |store| store.delete(&id, owner.as_deref())).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let (status, message) = not_found(&file_id);
+            return error_response(status, message, anthropic);
+        }
+        Err((status, message)) => return error_response(status, message, anthropic),
+    }
+
+    let body = if anthropic {
+        json!({ "id": file_id, "type": "file_deleted" })
+    } else {
+        json!({ "id": file_id, "object": "file", "deleted": true })
+    };
+    Json(body).into_response()
+}
@@ -1,16 +1,18 @@
 // Gemini Handler
-use axum::{extract::State, extract::{Json, Path}, http::StatusCode, response::IntoResponse};
+use axum::{extract::State, extract::{Json, Path, Query}, http::StatusCode, response::IntoResponse, Extension};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
-use crate::proxy::mappers::gemini::{wrap_request, unwrap_response};
+use crate::proxy::cached_contents::CachedContentStore;
+use crate::proxy::mappers::gemini::{wrap_request_for_client, unwrap_response, resolve_inline_documents, sse_chunk_to_array_elements};
 use crate::proxy::server::AppState;
+use crate::proxy::middleware::auth::{resource_owner, UserTokenIdentity};
 use crate::proxy::session_manager::SessionManager;
-use crate::proxy::handlers::common::{determine_retry_strategy, apply_retry_strategy, should_rotate_account, RetryStrategy};
+use crate::proxy::handlers::common::{determine_retry_strategy, apply_retry_strategy, compress_for_protocol, should_rotate_account, RetryStrategy};
 use crate::proxy::debug_logger;
 use tokio::time::Duration;
 use axum::http::HeaderMap;
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Adapter Registry
+use crate::proxy::common::client_adapter::{find_client_adapter, Protocol}; // [NEW] Adapter Registry
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
  
@@ -19,7 +21,9 @@ const MAX_RETRY_ATTEMPTS: usize = 3;
 pub async fn handle_generate(
     State(state): State<AppState>,
     Path(model_action): Path<String>,
+    identity: Option<Extension<UserTokenIdentity>>,
     headers: HeaderMap, // [NEW] Extract headers for adapter detection
+    Query(query): Query<std::collections::HashMap<String, String>>,
     Json(mut body): Json<Value>  // 改为 mut 以支持修复提示词注入
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
     // 解析 model:method
@@ -34,9 +38,9 @@ pub async fn handle_generate(
     let debug_cfg = state.debug_logging.read().await.clone();
 
     // [NEW] Detect Client Adapter
-    let client_adapter = CLIENT_ADAPTERS.iter().find(|a| a.matches(&headers)).cloned();
-    if client_adapter.is_some() {
-        debug!("[{}] Client Adapter detected", trace_id);
+    let client_adapter = find_client_adapter(&headers, Protocol::GoogleGemini);
+    if let Some(adapter) = &client_adapter {
+        debug!("[{}] Client Adapter detected ({})", trace_id, adapter.name());
     }
 
     // 1. 验证方法
@@ -54,7 +58,17 @@ pub async fn handle_generate(
         });
         debug_logger::write_debug_payload(&debug_cfg, Some(&trace_id), "original_request", &original_payload).await;
     }
+
+    // cachedContent 引用展开 (本地模拟层，需在文档预处理前展开以解析缓存中的文件引用)
+    let cached_content = CachedContentStore::global().apply(&mut body, &model_name)?;
+
+    // 文档预处理 (本地文件引用解析 + 大小校验 + Office / 纯文本提取)
+    resolve_inline_documents(&mut body, resource_owner(identity).as_deref())
+        .map_err(|e| (e.status_code(), format!("Invalid document input: {}", e)))?;
+
     let client_wants_stream = method == "streamGenerateContent";
+    // 与官方 API 一致: 带 alt=sse 时返回 SSE，否则流式返回 JSON 数组
+    let client_wants_sse = query.get("alt").map(|a| a == "sse").unwrap_or(false);
     // [AUTO-CONVERSION] 强制内部流式化
     let force_stream_internally = !client_wants_stream;
     let is_stream = client_wants_stream || force_stream_internally;
@@ -65,19 +79,33 @@ pub async fn handle_generate(
 
     // 2. 获取 UpstreamClient 和 TokenManager
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size).max(1);
     
     let mut last_error = String::new();
     let mut last_email: Option<String> = None;
 
+    // 3. 模型路由解析
+    let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
+        &model_name,
+        &*state.custom_mapping.read().await,
+    );
+
+    // 提取 SessionId (粘性指纹)，需在压缩改写历史之前提取
+    let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
+
+    // [NEW] 上下文压缩管线 (长 Agent 会话自动折叠/摘要历史)
+    // 在重试循环外只执行一次，换号重试复用压缩后的请求体
+    let compression_header = match compress_for_protocol(&state, &mut body, &session_id, &mapped_model, &trace_id).await {
+        Ok(report) => report.map(|r| r.header_value()).unwrap_or_default(),
+        Err(e) => {
+            error!("[{}] Context compression failed: {}", trace_id, e);
+            return Err((StatusCode::BAD_REQUEST, format!("Context too long and automatic compression failed: {}", e)));
+        }
+    };
+
     for attempt in 0..max_attempts {
-        // 3. 模型路由解析
-        let mapped_model = crate::proxy::common::model_mapping::resolve_model_route(
-            &model_name,
-            &*state.custom_mapping.read().await,
-        );
         // 提取 tools 列表以进行联网探测 (Gemini 风格可能是嵌套的)
         let tools_val: Option<Vec<Value>> = body.get("tools").and_then(|t| t.as_array()).map(|arr| {
             let mut flattened = Vec::new();
@@ -101,11 +129,24 @@ pub async fn handle_generate(
         );
 
         // 4. 获取 Token (使用准确的 request_type)
-        // 提取 SessionId (粘性指纹)
-        let session_id = SessionManager::extract_gemini_session_id(&body, &model_name);
-
-        // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
-        let (access_token, project_id, email, account_id, _wait_ms) = match token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id), &config.final_model).await {
+        // 引用 cachedContent 的请求首次尝试优先使用缓存绑定的账号
+        let pinned_account = if attempt == 0 {
+            cached_content.as_ref().and_then(|c| c.account_id.clone())
+        } else {
+            None
+        };
+        let token_result = match pinned_account {
+            Some(pinned) => match token_manager.get_token_for_account(&pinned, &config.final_model).await {
+                Ok(t) => Ok(t),
+                Err(e) => {
+                    debug!("[{}] Cached content account unavailable ({}), falling back to scheduler", trace_id, e);
+                    token_manager.get_token(&config.request_type, false, Some(&session_id), &config.final_model).await
+                }
+            },
+            // 关键：在重试尝试 (attempt > 0) 时强制轮换账号
+            None => token_manager.get_token(&config.request_type, attempt > 0, Some(&session_id), &config.final_model).await,
+        };
+        let (access_token, project_id, email, account_id, _wait_ms) = match token_result {
             Ok(t) => t,
             Err(e) => {
                 return Err((StatusCode::SERVICE_UNAVAILABLE, format!("Token error: {}", e)));
@@ -117,7 +158,8 @@ pub async fn handle_generate(
 
         // 5. 包装请求 (project injection)
         // [FIX #765] Pass session_id to wrap_request for signature injection
-        let wrapped_body = wrap_request(&body, &project_id, &mapped_model, Some(&session_id));
+        let tool_name_style = client_adapter.as_ref().map(|a| a.tool_name_style()).unwrap_or_default();
+        let wrapped_body = wrap_request_for_client(&body, &project_id, &mapped_model, Some(&session_id), tool_name_style);
 
         if debug_logger::is_enabled(&debug_cfg) {
             let payload = json!({
@@ -157,6 +199,10 @@ pub async fn handle_generate(
 
         let status = response.status();
         if status.is_success() {
+            if let Some(cache) = &cached_content {
+                CachedContentStore::global().pin_account(&cache.id, &account_id);
+            }
+
             // 6. 响应处理
             if is_stream {
                 use axum::body::Body;
@@ -219,6 +265,8 @@ pub async fn handle_generate(
 
                 let s_id_for_stream = s_id.clone();
                 let model_name_for_stream = mapped_model.clone();
+                // 匹配到客户端适配器时，中途断流以其声明的 SSE 错误格式告知客户端
+                let sse_error_format = client_adapter.as_ref().map(|a| a.sse_error_format());
                 let stream = async_stream::stream! {
                     let mut first_data = first_chunk;
                     loop {
@@ -232,7 +280,13 @@ pub async fn handle_generate(
                             Some(Ok(b)) => b,
                             Some(Err(e)) => {
                                 error!("[Gemini-SSE] Connection error: {}", e);
-                                yield Err(format!("Stream error: {}", e));
+                                match sse_error_format {
+                                    Some(format) => yield Ok(Bytes::from(format.render(
+                                        "api_error",
+                                        &format!("Upstream stream interrupted: {}", e),
+                                    ))),
+                                    None => yield Err(format!("Stream error: {}", e)),
+                                }
                                 break;
                             }
                             None => break,
@@ -308,14 +362,19 @@ pub async fn handle_generate(
                 };
                 
                 if client_wants_stream {
-                    let body = Body::from_stream(stream);
+                    let (body, content_type) = if client_wants_sse {
+                        (Body::from_stream(stream), "text/event-stream")
+                    } else {
+                        (Body::from_stream(sse_to_json_array_stream(stream)), "application/json")
+                    };
                     return Ok(Response::builder()
-                        .header("Content-Type", "text/event-stream")
+                        .header("Content-Type", content_type)
                         .header("Cache-Control", "no-cache")
                         .header("Connection", "keep-alive")
                         .header("X-Accel-Buffering", "no")
                         .header("X-Account-Email", &email)
                         .header("X-Mapped-Model", &mapped_model)
+                        .header("X-Context-Compression", compression_header.as_str())
                         .body(body)
                         .unwrap()
                         .into_response());
@@ -326,7 +385,7 @@ pub async fn handle_generate(
                          Ok(gemini_resp) => {
                              info!("[{}] ✓ Stream collected and converted to JSON (Gemini)", session_id);
                              let unwrapped = unwrap_response(&gemini_resp);
-                             return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(unwrapped)).into_response());
+                             return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str()), ("X-Context-Compression", compression_header.as_str())], Json(unwrapped)).into_response());
                          },
                          Err(e) => {
                              error!("Stream collection error: {}", e);
@@ -368,7 +427,7 @@ pub async fn handle_generate(
             }
 
             let unwrapped = unwrap_response(&gemini_resp);
-            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str())], Json(unwrapped)).into_response());
+            return Ok((StatusCode::OK, [("X-Account-Email", email.as_str()), ("X-Mapped-Model", mapped_model.as_str()), ("X-Context-Compression", compression_header.as_str())], Json(unwrapped)).into_response());
         }
 
         // 处理错误并重试
@@ -462,6 +521,36 @@ pub async fn handle_generate(
     }
 }
 
+/// 将 SSE 输出转换为 JSON 数组流 (streamGenerateContent 未指定 alt=sse 时的官方格式)
+fn sse_to_json_array_stream<S>(stream: S) -> impl futures::Stream<Item = Result<bytes::Bytes, String>>
+where
+    S: futures::Stream<Item = Result<bytes::Bytes, String>> + Send + 'static,
+{
+    use futures::StreamExt;
+
+    async_stream::stream! {
+        let mut stream = Box::pin(stream);
+        let mut first = true;
+        yield Ok(bytes::Bytes::from_static(b"["));
+        while let Some(item) = stream.next().await {
+            match item {
+                Ok(chunk) => {
+                    for element in sse_chunk_to_array_elements(&String::from_utf8_lossy(&chunk)) {
+                        let separator = if first { "" } else { ",\r\n" };
+                        first = false;
+                        yield Ok(bytes::Bytes::from(format!("{}{}", separator, element)));
+                    }
+                }
+                Err(e) => {
+                    yield Err(e);
+                    break;
+                }
+            }
+        }
+        yield Ok(bytes::Bytes::from_static(b"]"));
+    }
+}
+
 pub async fn handle_list_models(State(state): State<AppState>) -> Result<impl IntoResponse, (StatusCode, String)> {
     use crate::proxy::common::model_mapping::get_all_dynamic_models;
 
@@ -503,3 +592,68 @@ pub async fn handle_count_tokens(State(state): State<AppState>, Path(_model_name
     
     Ok(Json(json!({"totalTokens": 0})))
 }
+
+/// 创建 cachedContent (POST /v1beta/cachedContents)
+pub async fn handle_create_cached_content(Json(body): Json<Value>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let cache = CachedContentStore::global()
+        .create(&body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    info!(
+        "[CachedContents] Created {} for {} (~{} tokens)",
+        cache.name(), cache.model, cache.total_token_count
+    );
+    Ok(Json(cache.to_api()))
+}
+
+/// 列出 cachedContents (GET /v1beta/cachedContents?pageSize=&pageToken=)
+pub async fn handle_list_cached_contents(
+    Query(query): Query<std::collections::HashMap<String, String>>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let caches = CachedContentStore::global().list();
+    let page_size = query
+        .get("pageSize")
+        .and_then(|s| s.parse::<usize>().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(100)
+        .min(1000);
+    let offset = query
+        .get("pageToken")
+        .and_then(|s| s.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let page: Vec<Value> = caches.iter().skip(offset).take(page_size).map(|c| c.to_api()).collect();
+    let mut resp = json!({ "cachedContents": page });
+    if offset + page_size < caches.len() {
+        resp["nextPageToken"] = json!((offset + page_size).to_string());
+    }
+    Ok(Json(resp))
+}
+
+/// 获取 cachedContent (GET /v1beta/cachedContents/:id)
+pub async fn handle_get_cached_content(Path(id): Path<String>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    CachedContentStore::global()
+        .get(&id)
+        .map(|cache| Json(cache.to_api()))
+        .ok_or((StatusCode::NOT_FOUND, format!("CachedContent not found: {}", id)))
+}
+
+/// 更新 cachedContent 过期时间 (PATCH /v1beta/cachedContents/:id)
+pub async fn handle_update_cached_content(
+    Path(id): Path<String>,
+    Json(body): Json<Value>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    CachedContentStore::global()
+        .update(&id, &body)
+        .map_err(|e| (StatusCode::BAD_REQUEST, e))?
+        .map(|cache| Json(cache.to_api()))
+        .ok_or((StatusCode::NOT_FOUND, format!("CachedContent not found: {}", id)))
+}
+
+/// 删除 cachedContent (DELETE /v1beta/cachedContents/:id)
+pub async fn handle_delete_cached_content(Path(id): Path<String>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    if CachedContentStore::global().delete(&id) {
+        Ok(Json(json!({})))
+    } else {
+        Err((StatusCode::NOT_FOUND, format!("CachedContent not found: {}", id)))
+    }
+}
@@ -0,0 +1,241 @@
+// Anthropic Message Batches API 处理器
+// 批次存储与后台处理见 proxy::message_batches
+// 批次按创建者的 User Token 隔离，其他 Token 的批次一律视为不存在 (404)
+
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tracing::info;
+
+use crate::proxy::message_batches::{
+    parse_batch_requests, spawn_batch_worker, MessageBatch, MessageBatchStore, ProcessingStatus,
+};
+use crate::proxy::middleware::auth::{resource_owner, UserTokenIdentity};
+use crate::proxy::server::AppState;
+
+const DEFAULT_LIST_LIMIT: usize = 20;
+const MAX_LIST_LIMIT: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct ListBatchesQuery {
+    limit: Option<usize>,
+    before_id: Option<String>,
+    after_id: Option<String>,
+}
+
+fn error_response(status: StatusCode, error_type: &str, message: &str) -> Response {
+    (
+        status,
+        Json(json!({
+            "type": "error",
+            "error": { "type": error_type, "message": message }
+        })),
+    )
+        .into_response()
+}
+
+fn internal_error(e: String) -> Response {
+    error_response(StatusCode::INTERNAL_SERVER_ERROR, "api_error", &e)
+}
+
+fn not_found(batch_id: &str) -> Response {
+    error_response(
+        StatusCode::NOT_FOUND,
+        "not_found_error",
+        &format!("Message batch '{}' not found", batch_id),
+    )
+}
+
+/// 客户端可访问的服务地址 (用于 results_url)；优先使用 ABV_PUBLIC_URL
+fn public_base_url(headers: &HeaderMap) -> String {
+    if let Ok(public_url) = std::env::var("ABV_PUBLIC_URL") {
+        return public_url.trim_end_matches('/').to_string();
+    }
+    let host = headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("localhost");
+    let proto = headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("http");
+    format!("{}://{}", proto, host)
+}
+
+/// 在阻塞线程池中访问批次存储
+async fn with_store<T, F>(f: F) -> Result<T, Response>
+where
+    F: FnOnce(&MessageBatchStore) -> Result<T, String> + Send + 'static,
+    T: Send + 'static,
+{
+    MessageBatchStore::open_default()
+        .map_err(internal_error)?
+        .run(f)
+        .await
+        .map_err(internal_error)
+}
+
+async fn load_batch(batch_id: &str, owner: Option<String>) -> Result<MessageBatch, Response> {
+    let id = batch_id.to_string();
+    with_store(move |s| s.get_owned(&id, owner.as_deref()))
+        .await?
+        .ok_or_else(|| not_found(batch_id))
+}
+
+/// 创建批次 (POST /v1/messages/batches)
+pub async fn handle_create_batch(
+    State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Json(body): Json<Value>,
+) -> Response {
+    let requests = match parse_batch_requests(&body) {
+        Ok(requests) => requests,
+        Err(e) => return error_response(StatusCode::BAD_REQUEST, "invalid_request_error", &e),
+    };
+    let anthropic_beta = headers
+        .get("anthropic-beta")
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+
+    let count = requests.len();
+    let owner = resource_owner(identity);
+    let batch = match with_store(move |s| s.create(&requests, anthropic_beta, owner)).await {
+        Ok(batch) => batch,
+        Err(resp) => return resp,
+    };
+
+    info!("[Batches] Created {} with {} requests", batch.id, count);
+    spawn_batch_worker(state, batch.id.clone());
+    Json(batch.to_api(&public_base_url(&headers))).into_response()
+}
+
+/// 列出批次 (GET /v1/messages/batches)，新批次在前
+pub async fn handle_list_batches(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Query(query): Query<ListBatchesQuery>,
+) -> Response {
+    let owner = resource_owner(identity);
+    let batches = match with_store(move |s| s.list_owned(owner.as_deref())).await {
+        Ok(batches) => batches,
+        Err(resp) => return resp,
+    };
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let position = |id: &str| batches.iter().position(|b| b.id == id);
+
+    let (page, has_more) = if let Some(before_id) = query.before_id.as_deref() {
+        let end = position(before_id).unwrap_or(0);
+        let start = end.saturating_sub(limit);
+        (&batches[start..end], start > 0)
+    } else {
+        let start = query
+            .after_id
+            .as_deref()
+            .and_then(position)
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        let end = (start + limit).min(batches.len());
+        (&batches[start..end], end < batches.len())
+    };
+
+    let base_url = public_base_url(&headers);
+    Json(json!({
+        "data": page.iter().map(|b| b.to_api(&base_url)).collect::<Vec<_>>(),
+        "has_more": has_more,
+        "first_id": page.first().map(|b| b.id.clone()),
+        "last_id": page.last().map(|b| b.id.clone())
+    }))
+    .into_response()
+}
+
+/// 获取批次 (GET /v1/messages/batches/:batch_id)
+pub async fn handle_get_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Path(batch_id): Path<String>,
+) -> Response {
+    match load_batch(&batch_id, resource_owner(identity)).await {
+        Ok(batch) => Json(batch.to_api(&public_base_url(&headers))).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// 取消批次 (POST /v1/messages/batches/:batch_id/cancel)
+pub async fn handle_cancel_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    headers: HeaderMap,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let owner = resource_owner(identity);
+    let id = batch_id.clone();
+    let batch = match with_store(move |s| s.cancel(&id, owner.as_deref())).await {
+        Ok(Some(batch)) => batch,
+        Ok(None) => return not_found(&batch_id),
+        Err(resp) => return resp,
+    };
+    info!("[Batches] Cancel requested for {}", batch_id);
+    Json(batch.to_api(&public_base_url(&headers))).into_response()
+}
+
+/// 下载结果 JSONL (GET /v1/messages/batches/:batch_id/results)
+pub async fn handle_get_batch_results(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let batch = match load_batch(&batch_id, resource_owner(identity)).await {
+        Ok(batch) => batch,
+        Err(resp) => return resp,
+    };
+    if batch.processing_status != ProcessingStatus::Ended {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!(
+                "Message batch '{}' is still processing; results are available once it has ended",
+                batch_id
+            ),
+        );
+    }
+
+    let id = batch_id.clone();
+    match with_store(move |s| s.results(&id)).await {
+        Ok(results) => ([(header::CONTENT_TYPE, "application/x-jsonl")], results).into_response(),
+        Err(resp) => resp,
+    }
+}
+
+/// 删除批次 (DELETE /v1/messages/batches/:batch_id)，进行中的批次需先取消
+pub async fn handle_delete_batch(
+    identity: Option<Extension<UserTokenIdentity>>,
+    Path(batch_id): Path<String>,
+) -> Response {
+    let batch = match load_batch(&batch_id, resource_owner(identity)).await {
+        Ok(batch) => batch,
+        Err(resp) => return resp,
+    };
+    if batch.processing_status != ProcessingStatus::Ended {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            "invalid_request_error",
+            &format!(
+                "Message batch '{}' is still processing; cancel it before deleting",
+                batch_id
+            ),
+        );
+    }
+
+    let id = batch_id.clone();
+    match with_store(move |s| s.delete(&id)).await {
+        Ok(_) => Json(json!({ "id": batch_id, "type": "message_batch_deleted" })).into_response(),
+        Err(resp) => resp,
+    }
+}
@@ -7,5 +7,7 @@ pub mod gemini;
 pub mod mcp;
 pub mod common;
 pub mod audio;  // 音频转录处理器
+pub mod files;  // Files API
+pub mod message_batches; // Anthropic Message Batches API
 pub mod warmup; // 预热处理器
 
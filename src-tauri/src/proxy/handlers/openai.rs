@@ -1,6 +1,7 @@
 // OpenAI Handler
 use axum::{
     extract::Json, extract::State, http::StatusCode, response::IntoResponse, response::Response,
+    Extension,
 };
 use base64::Engine as _;
 use bytes::Bytes;
@@ -8,26 +9,49 @@ use serde_json::{json, Value};
 use tracing::{debug, error, info}; // Import Engine trait for encode method
 
 use crate::proxy::mappers::openai::{
-    transform_openai_request, transform_openai_response, OpenAIRequest,
+    probe_remote_file_types, resolve_document_inputs, transform_openai_request,
+    transform_openai_request_for_client, transform_openai_response, validate_sampling_params,
+    OpenAIRequest,
 };
 // use crate::proxy::upstream::client::UpstreamClient; // 通过 state 获取
 use crate::proxy::server::AppState;
 use crate::proxy::debug_logger;
+use crate::proxy::middleware::auth::{resource_owner, UserTokenIdentity};
 
 const MAX_RETRY_ATTEMPTS: usize = 3;
 use super::common::{
-    apply_retry_strategy, determine_retry_strategy, should_rotate_account, RetryStrategy,
+    apply_retry_strategy, compress_for_protocol, determine_retry_strategy, should_rotate_account,
+    RetryStrategy,
 };
 use crate::proxy::session_manager::SessionManager;
 use tokio::time::Duration;
-use crate::proxy::common::client_adapter::CLIENT_ADAPTERS; // [NEW] Adapter Registry
+use crate::proxy::common::client_adapter::{find_client_adapter, Protocol}; // [NEW] Adapter Registry
 use axum::http::HeaderMap;
 use crate::proxy::clients::chatgpt::ChatGPTClient;
+use crate::proxy::mappers::openai::logprobs::legacy_logprobs;
 use crate::proxy::mappers::openai::OpenAIContent;
+use crate::proxy::mappers::reasoning_policy;
 use uuid::Uuid;
 
+/// 无法映射到上游的采样参数 (logit_bias / 超范围的 n 等) 直接返回 400
+fn unsupported_parameter_response(mapped_model: &str, message: String) -> Response {
+    (
+        StatusCode::BAD_REQUEST,
+        [("X-Mapped-Model", mapped_model)],
+        Json(json!({
+            "error": {
+                "message": message,
+                "type": "invalid_request_error",
+                "code": "unsupported_parameter"
+            }
+        })),
+    )
+        .into_response()
+}
+
 pub async fn handle_chat_completions(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
     headers: HeaderMap, // [CHANGED] Extract headers
     Json(mut body): Json<Value>,
 ) -> Result<impl IntoResponse, (StatusCode, String)> {
@@ -87,6 +111,11 @@ pub async fn handle_chat_completions(
     let mut openai_req: OpenAIRequest = serde_json::from_value(body)
         .map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?;
 
+    // 文件输入 (PDF / Office / 纯文本) 预处理，格式或大小不符时直接返回 4xx
+    resolve_document_inputs(&mut openai_req, resource_owner(identity).as_deref())
+        .map_err(|e| (e.status_code(), format!("Invalid file input: {}", e)))?;
+    probe_remote_file_types(&mut openai_req).await;
+
     // Safety: Ensure messages is not empty
     if openai_req.messages.is_empty() {
         debug!("Received request with empty messages, injecting fallback...");
@@ -101,6 +130,7 @@ pub async fn handle_chat_completions(
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             });
     }
 
@@ -123,14 +153,14 @@ pub async fn handle_chat_completions(
     }
 
     // [NEW] Detect Client Adapter
-    let client_adapter = CLIENT_ADAPTERS.iter().find(|a| a.matches(&headers)).cloned();
-    if client_adapter.is_some() {
-        debug!("[{}] Client Adapter detected", trace_id);
+    let client_adapter = find_client_adapter(&headers, Protocol::OpenAI);
+    if let Some(adapter) = &client_adapter {
+        debug!("[{}] Client Adapter detected ({})", trace_id, adapter.name());
     }
 
     // 1. 获取 UpstreamClient (Clone handle)
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size.saturating_add(1)).max(2);
@@ -143,6 +173,38 @@ pub async fn handle_chat_completions(
         &openai_req.model,
         &*state.custom_mapping.read().await,
     );
+    if let Err(e) = validate_sampling_params(&openai_req, &mapped_model) {
+        return Ok(unsupported_parameter_response(&mapped_model, e));
+    }
+
+    // 转换请求 (返回内容包含 session_id 和 message_count)，project 在每次尝试时注入
+    let tool_name_style = client_adapter
+        .as_ref()
+        .map(|a| a.tool_name_style())
+        .unwrap_or_default();
+    let (mut transformed_body, transformed_session_id, message_count) =
+        transform_openai_request_for_client(&openai_req, "", &mapped_model, tool_name_style);
+
+    // [NEW] 上下文压缩管线 (长 Agent 会话自动折叠/摘要历史)
+    // 在重试循环外只执行一次，换号重试复用压缩后的请求体
+    let compression_header = match compress_for_protocol(&state, &mut transformed_body, &transformed_session_id, &mapped_model, &trace_id).await {
+        Ok(report) => report.map(|r| r.header_value()).unwrap_or_default(),
+        Err(e) => {
+            error!("[{}] Context compression failed: {}", trace_id, e);
+            return Ok((
+                StatusCode::BAD_REQUEST,
+                [("X-Mapped-Model", mapped_model.as_str())],
+                Json(json!({
+                    "error": {
+                        "message": format!("Context too long and automatic compression failed: {}", e),
+                        "type": "invalid_request_error",
+                        "code": "context_length_exceeded"
+                    }
+                })),
+            )
+                .into_response());
+        }
+    };
 
     for attempt in 0..max_attempts {
         // 将 OpenAI 工具转为 Value 数组以便探测联网
@@ -334,8 +396,10 @@ pub async fn handle_chat_completions(
             }
         }
 
-        // 4. 转换请求 (返回内容包含 session_id 和 message_count)
-        let (gemini_body, session_id, message_count) = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        // 4. 请求体已在循环外转换并压缩，这里只注入当前账号的 project
+        let mut gemini_body = transformed_body.clone();
+        gemini_body["project"] = json!(project_id);
+        let session_id = transformed_session_id.clone();
 
         if debug_logger::is_enabled(&debug_cfg) {
             let payload = json!({
@@ -511,6 +575,7 @@ pub async fn handle_chat_completions(
                         .header("X-Accel-Buffering", "no")
                         .header("X-Account-Email", &email)
                         .header("X-Mapped-Model", &mapped_model)
+                        .header("X-Context-Compression", compression_header.as_str())
                         .body(body)
                         .unwrap()
                         .into_response());
@@ -527,6 +592,7 @@ pub async fn handle_chat_completions(
                                 [
                                     ("X-Account-Email", email.as_str()),
                                     ("X-Mapped-Model", mapped_model.as_str()),
+                                    ("X-Context-Compression", compression_header.as_str()),
                                 ],
                                 Json(full_response),
                             )
@@ -555,6 +621,7 @@ pub async fn handle_chat_completions(
                 [
                     ("X-Account-Email", email.as_str()),
                     ("X-Mapped-Model", mapped_model.as_str()),
+                    ("X-Context-Compression", compression_header.as_str()),
                 ],
                 Json(openai_response),
             )
@@ -791,10 +858,43 @@ pub async fn handle_chat_completions(
     }
 }
 
+/// Responses 风格的输出项: 请求 reasoning.summary 时先输出 reasoning 摘要，再输出助手消息
+fn responses_output_items(
+    chat_resp: &crate::proxy::mappers::openai::OpenAIResponse,
+    request: &OpenAIRequest,
+) -> Vec<Value> {
+    use crate::proxy::mappers::openai::streaming::reasoning_output_item;
+
+    let reasoning_summary = reasoning_policy::wants_reasoning_summary(request.reasoning.as_ref());
+    let mut items = Vec::new();
+    for choice in &chat_resp.choices {
+        if let Some(reasoning) = choice
+            .message
+            .reasoning_content
+            .as_deref()
+            .filter(|r| reasoning_summary && !r.is_empty())
+        {
+            items.push(reasoning_output_item(&format!("rs_{}", chat_resp.id), reasoning));
+        }
+        let text = match &choice.message.content {
+            Some(OpenAIContent::String(s)) => s.clone(),
+            _ => String::new(),
+        };
+        items.push(json!({
+            "type": "message",
+            "role": "assistant",
+            "status": "completed",
+            "content": [{ "type": "output_text", "text": text, "annotations": [] }]
+        }));
+    }
+    items
+}
+
 /// 处理 Legacy Completions API (/v1/completions)
 /// 将 Prompt 转换为 Chat Message 格式，复用 handle_chat_completions
 pub async fn handle_completions(
     State(state): State<AppState>,
+    identity: Option<Extension<UserTokenIdentity>>,
     Json(mut body): Json<Value>,
 ) -> Response {
     debug!(
@@ -860,7 +960,7 @@ pub async fn handle_completions(
                         let role = item.get("role").and_then(|v| v.as_str()).unwrap_or("user");
                         let content = item.get("content").and_then(|v| v.as_array());
                         let mut text_parts = Vec::new();
-                        let mut image_parts: Vec<Value> = Vec::new();
+                        let mut media_parts: Vec<Value> = Vec::new();
 
                         if let Some(parts) = content {
                             for part in parts {
@@ -875,11 +975,19 @@ pub async fn handle_completions(
                                     if let Some(image_url) =
                                         part.get("image_url").and_then(|v| v.as_str())
                                     {
-                                        image_parts.push(json!({
+                                        media_parts.push(json!({
                                             "type": "image_url",
                                             "image_url": { "url": image_url }
                                         }));
                                         debug!("[Codex] Found input_image: {}", image_url);
+                                    } else if let Some(file_id) =
+                                        part.get("file_id").and_then(|v| v.as_str())
+                                    {
+                                        // 引用 Files API 上传的图片，按文件块解析
+                                        media_parts.push(json!({
+                                            "type": "input_file",
+                                            "file_id": file_id
+                                        }));
                                     }
                                 }
                                 // [NEW] 兼容标准 OpenAI image_url 格式
@@ -887,17 +995,24 @@ pub async fn handle_completions(
                                     == Some("image_url")
                                 {
                                     if let Some(url_obj) = part.get("image_url") {
-                                        image_parts.push(json!({
+                                        media_parts.push(json!({
                                             "type": "image_url",
                                             "image_url": url_obj.clone()
                                         }));
                                     }
                                 }
+                                // 文件块 (Responses input_file / Chat file)，交由 resolve_document_inputs 处理
+                                else if matches!(
+                                    part.get("type").and_then(|v| v.as_str()),
+                                    Some("input_file") | Some("file")
+                                ) {
+                                    media_parts.push(part.clone());
+                                }
                             }
                         }
 
-                        // 构造消息内容：如果有图像则使用数组格式
-                        if image_parts.is_empty() {
+                        // 构造消息内容：如果有图像或文件则使用数组格式
+                        if media_parts.is_empty() {
                             messages.push(json!({
                                 "role": role,
                                 "content": text_parts.join("\n")
@@ -910,7 +1025,7 @@ pub async fn handle_completions(
                                     "text": text_parts.join("\n")
                                 }));
                             }
-                            content_blocks.extend(image_parts);
+                            content_blocks.extend(media_parts);
                             messages.push(json!({
                                 "role": role,
                                 "content": content_blocks
@@ -1153,6 +1268,11 @@ pub async fn handle_completions(
         }
     };
 
+    if let Err(e) = resolve_document_inputs(&mut openai_req, resource_owner(identity).as_deref()) {
+        return (e.status_code(), format!("Invalid file input: {}", e)).into_response();
+    }
+    probe_remote_file_types(&mut openai_req).await;
+
     // Safety: Inject empty message if needed
     if openai_req.messages.is_empty() {
         openai_req
@@ -1166,11 +1286,12 @@ pub async fn handle_completions(
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             });
     }
 
     let upstream = state.upstream.clone();
-    let token_manager = state.token_manager;
+    let token_manager = state.token_manager.clone();
     let pool_size = token_manager.len();
     // [FIX] Ensure max_attempts is at least 2 to allow for internal retries
     let max_attempts = MAX_RETRY_ATTEMPTS.min(pool_size.saturating_add(1)).max(2);
@@ -1183,8 +1304,36 @@ pub async fn handle_completions(
         &openai_req.model,
         &*state.custom_mapping.read().await,
     );
+    if let Err(e) = validate_sampling_params(&openai_req, &mapped_model) {
+        return unsupported_parameter_response(&mapped_model, e);
+    }
     let trace_id = format!("req_{}", chrono::Utc::now().timestamp_subsec_millis());
 
+    // 转换请求，project 在每次尝试时注入
+    let (mut transformed_body, transformed_session_id, message_count) =
+        transform_openai_request(&openai_req, "", &mapped_model);
+
+    // [NEW] 上下文压缩管线 (长 Agent 会话自动折叠/摘要历史)
+    // 在重试循环外只执行一次，换号重试复用压缩后的请求体
+    let compression_header = match compress_for_protocol(&state, &mut transformed_body, &transformed_session_id, &mapped_model, &trace_id).await {
+        Ok(report) => report.map(|r| r.header_value()).unwrap_or_default(),
+        Err(e) => {
+            error!("[{}] Context compression failed: {}", trace_id, e);
+            return (
+                StatusCode::BAD_REQUEST,
+                [("X-Mapped-Model", mapped_model.as_str())],
+                Json(json!({
+                    "error": {
+                        "message": format!("Context too long and automatic compression failed: {}", e),
+                        "type": "invalid_request_error",
+                        "code": "context_length_exceeded"
+                    }
+                })),
+            )
+                .into_response();
+        }
+    };
+
     for attempt in 0..max_attempts {
         // 3. 模型配置解析
         // 将 OpenAI 工具转为 Value 数组以便探测联网
@@ -1233,7 +1382,10 @@ pub async fn handle_completions(
 
         info!("✓ Using account: {} (type: {})", email, config.request_type);
 
-        let (gemini_body, session_id, message_count) = transform_openai_request(&openai_req, &project_id, &mapped_model);
+        // 请求体已在循环外转换并压缩，这里只注入当前账号的 project
+        let mut gemini_body = transformed_body.clone();
+        gemini_body["project"] = json!(project_id);
+        let session_id = transformed_session_id.clone();
 
         // [New] 打印转换后的报文 (Gemini Body) 供调试 (Codex 路径) ———— 缩减为 simple debug
         debug!(
@@ -1292,7 +1444,8 @@ pub async fn handle_completions(
                 if client_wants_stream {
                     let mut openai_stream = if is_codex_style {
                         use crate::proxy::mappers::openai::streaming::create_codex_sse_stream;
-                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id, message_count)
+                        let reasoning_summary = reasoning_policy::wants_reasoning_summary(openai_req.reasoning.as_ref());
+                        create_codex_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id, message_count, reasoning_summary)
                     } else {
                         use crate::proxy::mappers::openai::streaming::create_legacy_sse_stream;
                         create_legacy_sse_stream(Box::pin(gemini_stream), openai_req.model.clone(), session_id, message_count)
@@ -1360,6 +1513,7 @@ pub async fn handle_completions(
                         .header("Connection", "keep-alive")
                         .header("X-Account-Email", &email)
                         .header("X-Mapped-Model", &mapped_model)
+                        .header("X-Context-Compression", compression_header.as_str())
                         .body(Body::from_stream(combined_stream))
                         .unwrap()
                         .into_response();
@@ -1438,12 +1592,12 @@ pub async fn handle_completions(
                                         _ => "".to_string()
                                     },
                                     "index": c.index,
-                                    "logprobs": null,
+                                    "logprobs": c.logprobs.as_ref().map(|lp| legacy_logprobs(lp, 0).0),
                                     "finish_reason": c.finish_reason
                                 })
                             }).collect::<Vec<_>>();
 
-                            let legacy_resp = json!({
+                            let mut legacy_resp = json!({
                                 "id": chat_resp.id,
                                 "object": "text_completion",
                                 "created": chat_resp.created,
@@ -1451,12 +1605,16 @@ pub async fn handle_completions(
                                 "choices": choices,
                                 "usage": chat_resp.usage
                             });
+                            if is_codex_style {
+                                legacy_resp["output"] = json!(responses_output_items(&chat_resp, &openai_req));
+                            }
 
                             return (
                                 StatusCode::OK,
                                 [
                                     ("X-Account-Email", email.as_str()),
                                     ("X-Mapped-Model", mapped_model.as_str()),
+                                    ("X-Context-Compression", compression_header.as_str()),
                                 ],
                                 Json(legacy_resp),
                             )
@@ -1495,12 +1653,12 @@ pub async fn handle_completions(
                         _ => "".to_string()
                     },
                     "index": c.index,
-                    "logprobs": null,
+                    "logprobs": c.logprobs.as_ref().map(|lp| legacy_logprobs(lp, 0).0),
                     "finish_reason": c.finish_reason
                 })
             }).collect::<Vec<_>>();
 
-            let legacy_resp = json!({
+            let mut legacy_resp = json!({
                 "id": chat_resp.id,
                 "object": "text_completion",
                 "created": chat_resp.created,
@@ -1508,12 +1666,16 @@ pub async fn handle_completions(
                 "choices": choices,
                 "usage": chat_resp.usage
             });
+            if is_codex_style {
+                legacy_resp["output"] = json!(responses_output_items(&chat_resp, &openai_req));
+            }
 
             return (
                 StatusCode::OK,
                 [
                     ("X-Account-Email", email.as_str()),
                     ("X-Mapped-Model", mapped_model.as_str()),
+                    ("X-Context-Compression", compression_header.as_str()),
                 ],
                 Json(legacy_resp),
             )
@@ -227,8 +227,16 @@ pub async fn handle_warmup(
                 response_body: None,
                 input_tokens: Some(0),
                 output_tokens: Some(0),
+                cached_tokens: None,
+                thinking_tokens: None,
+                tool_tokens: None,
                 protocol: Some("warmup".to_string()),
                 username: None,
+                context_compression: None,
+                ttft_ms: None,
+                stream_duration_ms: None,
+                tokens_per_sec: None,
+                proxy_id: None,
             };
             state.monitor.log_request(log).await;
 
@@ -292,8 +300,16 @@ pub async fn handle_warmup(
                 response_body: None,
                 input_tokens: None,
                 output_tokens: None,
+                cached_tokens: None,
+                thinking_tokens: None,
+                tool_tokens: None,
                 protocol: Some("warmup".to_string()),
                 username: None,
+                context_compression: None,
+                ttft_ms: None,
+                stream_duration_ms: None,
+                tokens_per_sec: None,
+                proxy_id: None,
             };
             state.monitor.log_request(log).await;
 
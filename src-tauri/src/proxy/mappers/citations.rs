@@ -0,0 +1,291 @@
+// Grounding 引用映射
+// 将 Gemini groundingMetadata (groundingChunks / groundingSupports) 转换为
+// Claude 文本块 citations 与 OpenAI url_citation annotations
+
+use base64::Engine;
+use serde_json::{json, Value};
+
+/// groundingChunks 中的网页来源
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundingSource {
+    pub url: String,
+    pub title: String,
+}
+
+/// 一段被来源支撑的文本 (start/end 为 text 中的字节偏移)
+#[derive(Debug, Clone, PartialEq)]
+pub struct GroundingSpan {
+    pub start: usize,
+    pub end: usize,
+    pub sources: Vec<GroundingSource>,
+}
+
+/// 按 groundingChunks 下标提取来源 (非 web 来源保留为 None 以维持下标对齐)
+pub fn grounding_sources(chunks: &[Value]) -> Vec<Option<GroundingSource>> {
+    chunks
+        .iter()
+        .map(|chunk| {
+            let web = chunk.get("web")?;
+            let url = web.get("uri").and_then(|v| v.as_str())?;
+            if url.is_empty() {
+                return None;
+            }
+            let title = web
+                .get("title")
+                .and_then(|v| v.as_str())
+                .unwrap_or(url)
+                .to_string();
+            Some(GroundingSource {
+                url: url.to_string(),
+                title,
+            })
+        })
+        .collect()
+}
+
+/// 从完整的 groundingMetadata 中定位被支撑的文本片段
+pub fn grounding_spans_from_metadata(text: &str, grounding: &Value) -> Vec<GroundingSpan> {
+    let chunks = grounding
+        .get("groundingChunks")
+        .and_then(|v| v.as_array())
+        .map(|v| v.as_slice())
+        .unwrap_or(&[]);
+    let supports = grounding
+        .get("groundingSupports")
+        .and_then(|v| v.as_array())
+        .map(|v| v.as_slice())
+        .unwrap_or(&[]);
+    grounding_spans(text, chunks, supports)
+}
+
+/// 将 groundingSupports 映射到 text 中的字节区间
+///
+/// Gemini 的 startIndex/endIndex 是基于原始输出的字节偏移，而代理可能已改写过文本
+/// (例如 MCP XML 标签被剥离)，因此偏移与 segment.text 不一致时回退为按原文搜索。
+pub fn grounding_spans(text: &str, chunks: &[Value], supports: &[Value]) -> Vec<GroundingSpan> {
+    let sources = grounding_sources(chunks);
+    let mut spans: Vec<GroundingSpan> = Vec::new();
+    let mut cursor = 0usize;
+
+    for support in supports {
+        let span_sources: Vec<GroundingSource> = support
+            .get("groundingChunkIndices")
+            .and_then(|v| v.as_array())
+            .map(|indices| {
+                indices
+                    .iter()
+                    .filter_map(|i| i.as_u64())
+                    .filter_map(|i| sources.get(i as usize).cloned().flatten())
+                    .collect()
+            })
+            .unwrap_or_default();
+        if span_sources.is_empty() {
+            continue;
+        }
+
+        let Some(segment) = support.get("segment") else {
+            continue;
+        };
+        let segment_text = segment.get("text").and_then(|v| v.as_str()).unwrap_or("");
+        let start = segment
+            .get("startIndex")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0) as usize;
+        let end = segment
+            .get("endIndex")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as usize);
+
+        let offsets_valid = end
+            .map(|end| {
+                start < end
+                    && end <= text.len()
+                    && text.is_char_boundary(start)
+                    && text.is_char_boundary(end)
+                    && (segment_text.is_empty() || &text[start..end] == segment_text)
+            })
+            .unwrap_or(false);
+
+        let located = if offsets_valid {
+            end.map(|end| (start, end))
+        } else if !segment_text.is_empty() {
+            text[cursor..]
+                .find(segment_text)
+                .map(|pos| cursor + pos)
+                .or_else(|| text.find(segment_text))
+                .map(|pos| (pos, pos + segment_text.len()))
+        } else {
+            None
+        };
+
+        if let Some((start, end)) = located {
+            cursor = end;
+            spans.push(GroundingSpan {
+                start,
+                end,
+                sources: span_sources,
+            });
+        }
+    }
+
+    // 按位置排序并丢弃重叠区间，保证可以据此切分文本
+    spans.sort_by_key(|s| (s.start, s.end));
+    let mut result: Vec<GroundingSpan> = Vec::with_capacity(spans.len());
+    for span in spans {
+        if result
+            .last()
+            .map(|last| span.start >= last.end)
+            .unwrap_or(true)
+        {
+            result.push(span);
+        }
+    }
+    result
+}
+
+/// Claude web_search_result_location 引用
+pub fn web_search_citation(source: &GroundingSource, cited_text: &str) -> Value {
+    json!({
+        "type": "web_search_result_location",
+        "url": source.url,
+        "title": source.title,
+        "encrypted_index": base64::engine::general_purpose::STANDARD.encode(source.url.as_bytes()),
+        "cited_text": cited_text
+    })
+}
+
+/// 将一段文本按引用区间切分为 (文本, 引用) 片段，未被引用的片段 citations 为 None
+pub fn split_cited_text(text: &str, spans: &[GroundingSpan]) -> Vec<(String, Option<Vec<Value>>)> {
+    let mut pieces = Vec::new();
+    let mut pos = 0usize;
+
+    for span in spans {
+        if span.start < pos || span.end > text.len() {
+            continue;
+        }
+        if span.start > pos {
+            pieces.push((text[pos..span.start].to_string(), None));
+        }
+        let cited = &text[span.start..span.end];
+        let citations = span
+            .sources
+            .iter()
+            .map(|source| web_search_citation(source, cited))
+            .collect();
+        pieces.push((cited.to_string(), Some(citations)));
+        pos = span.end;
+    }
+
+    if pos < text.len() {
+        pieces.push((text[pos..].to_string(), None));
+    }
+    pieces
+}
+
+/// 汇总所有区间的 Claude 引用 (流式场景下正文已发送，引用统一挂在来源块上)
+pub fn span_citations(text: &str, spans: &[GroundingSpan]) -> Vec<Value> {
+    spans
+        .iter()
+        .flat_map(|span| {
+            let cited = &text[span.start..span.end];
+            span.sources
+                .iter()
+                .map(move |source| web_search_citation(source, cited))
+        })
+        .collect()
+}
+
+/// OpenAI url_citation annotations (start_index/end_index 为字符下标)
+pub fn url_citation_annotations(text: &str, spans: &[GroundingSpan]) -> Vec<Value> {
+    let mut annotations = Vec::new();
+    for span in spans {
+        let start_index = text[..span.start].chars().count();
+        let end_index = start_index + text[span.start..span.end].chars().count();
+        for source in &span.sources {
+            annotations.push(json!({
+                "type": "url_citation",
+                "url_citation": {
+                    "url": source.url,
+                    "title": source.title,
+                    "start_index": start_index,
+                    "end_index": end_index
+                }
+            }));
+        }
+    }
+    annotations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata() -> Value {
+        json!({
+            "groundingChunks": [
+                { "web": { "uri": "https://a.example", "title": "A" } },
+                { "web": { "uri": "https://b.example", "title": "B" } }
+            ],
+            "groundingSupports": [
+                {
+                    "segment": { "startIndex": 0, "endIndex": 11, "text": "Rust is 10." },
+                    "groundingChunkIndices": [0]
+                },
+                {
+                    "segment": { "startIndex": 12, "endIndex": 27, "text": "It is fast too." },
+                    "groundingChunkIndices": [0, 1]
+                }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_spans_use_offsets_when_they_match() {
+        let text = "Rust is 10. It is fast too. Done.";
+        let spans = grounding_spans_from_metadata(text, &metadata());
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&text[spans[0].start..spans[0].end], "Rust is 10.");
+        assert_eq!(spans[1].sources.len(), 2);
+    }
+
+    #[test]
+    fn test_spans_fall_back_to_segment_text_search() {
+        // 文本前缀被改写后偏移失效，按 segment.text 重新定位
+        let text = "Intro. Rust is 10. It is fast too.";
+        let spans = grounding_spans_from_metadata(text, &metadata());
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&text[spans[1].start..spans[1].end], "It is fast too.");
+    }
+
+    #[test]
+    fn test_split_cited_text_produces_web_search_citations() {
+        let text = "Rust is 10. It is fast too. Done.";
+        let spans = grounding_spans_from_metadata(text, &metadata());
+        let pieces = split_cited_text(text, &spans);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[0].0, "Rust is 10.");
+        let citations = pieces[0].1.as_ref().unwrap();
+        assert_eq!(citations[0]["type"], "web_search_result_location");
+        assert_eq!(citations[0]["url"], "https://a.example");
+        assert_eq!(citations[0]["cited_text"], "Rust is 10.");
+        assert!(pieces[1].1.is_none());
+        assert_eq!(pieces[3].0, " Done.");
+    }
+
+    #[test]
+    fn test_url_citation_annotations_use_char_indices() {
+        let text = "你好。Rust is 10.";
+        let grounding = json!({
+            "groundingChunks": [{ "web": { "uri": "https://a.example", "title": "A" } }],
+            "groundingSupports": [{
+                "segment": { "startIndex": 9, "endIndex": 20, "text": "Rust is 10." },
+                "groundingChunkIndices": [0]
+            }]
+        });
+        let spans = grounding_spans_from_metadata(text, &grounding);
+        let annotations = url_citation_annotations(text, &spans);
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0]["url_citation"]["start_index"], 3);
+        assert_eq!(annotations[0]["url_citation"]["end_index"], 14);
+    }
+}
@@ -25,6 +25,27 @@ fn parse_sse_line(line: &str) -> Option<(String, String)> {
     }
 }
 
+/// 合并 message_delta 中的 usage (可能只包含部分字段，如仅有 output_tokens)，
+/// 保留 message_start 中已有的缓存命中等统计
+fn merge_usage(usage: &mut Usage, delta: &Value) {
+    let count = |key: &str| delta.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+    if let Some(v) = count("input_tokens") {
+        usage.input_tokens = v;
+    }
+    if let Some(v) = count("output_tokens") {
+        usage.output_tokens = v;
+    }
+    if let Some(v) = count("cache_read_input_tokens") {
+        usage.cache_read_input_tokens = Some(v);
+    }
+    if let Some(v) = count("cache_creation_input_tokens") {
+        usage.cache_creation_input_tokens = Some(v);
+    }
+    if let Some(v) = delta.get("server_tool_use") {
+        usage.server_tool_use = Some(v.clone());
+    }
+}
+
 /// 将 SSE Stream 收集为完整的 Claude Response
 ///
 /// 此函数接收一个 SSE 字节流，解析所有事件，并重建完整的 ClaudeResponse 对象。
@@ -87,6 +108,7 @@ where
 
     // 用于累积内容块
     let mut current_text = String::new();
+    let mut current_citations: Vec<Value> = Vec::new();
     let mut current_thinking = String::new();
     let mut current_signature: Option<String> = None;
     let mut current_tool_use: Option<Value> = None;
@@ -115,7 +137,14 @@ where
                 if let Some(content_block) = event.data.get("content_block") {
                     if let Some(block_type) = content_block.get("type").and_then(|v| v.as_str()) {
                         match block_type {
-                            "text" => current_text.clear(),
+                            "text" => {
+                                current_text.clear();
+                                current_citations = content_block
+                                    .get("citations")
+                                    .and_then(|v| v.as_array())
+                                    .cloned()
+                                    .unwrap_or_default();
+                            }
                             "thinking" => {
                                 current_thinking.clear();
                                 // Extract signature from content_block
@@ -142,6 +171,11 @@ where
                                     current_text.push_str(text);
                                 }
                             }
+                            "citations_delta" => {
+                                if let Some(citation) = delta.get("citation") {
+                                    current_citations.push(citation.clone());
+                                }
+                            }
                             "thinking_delta" => {
                                 if let Some(thinking) = delta.get("thinking").and_then(|v| v.as_str()) {
                                     current_thinking.push_str(thinking);
@@ -165,8 +199,10 @@ where
             "content_block_stop" => {
                 // 完成当前块
                 if !current_text.is_empty() {
+                    let citations = std::mem::take(&mut current_citations);
                     response.content.push(ContentBlock::Text {
                         text: current_text.clone(),
+                        citations: (!citations.is_empty()).then_some(citations),
                     });
                     current_text.clear();
                 } else if !current_thinking.is_empty() {
@@ -204,9 +240,7 @@ where
                     }
                 }
                 if let Some(usage) = event.data.get("usage") {
-                    if let Ok(u) = serde_json::from_value::<Usage>(usage.clone()) {
-                        response.usage = u;
-                    }
+                    merge_usage(&mut response.usage, usage);
                 }
             }
 
@@ -259,9 +293,11 @@ mod tests {
         let response = result.unwrap();
         assert_eq!(response.id, "msg_123");
         assert_eq!(response.model, "claude-3-5-sonnet");
+        assert_eq!(response.usage.input_tokens, 10);
+        assert_eq!(response.usage.output_tokens, 5);
         assert_eq!(response.content.len(), 1);
         
-        if let ContentBlock::Text { text } = &response.content[0] {
+        if let ContentBlock::Text { text, .. } = &response.content[0] {
             assert_eq!(text, "Hello World");
         } else {
             panic!("Expected Text block");
@@ -299,4 +335,54 @@ mod tests {
             panic!("Expected Thinking block");
         }
     }
+
+    #[tokio::test]
+    async fn test_collect_keeps_cache_usage_from_message_start() {
+        let sse_data = vec![
+            "event: message_start\ndata: {\"type\":\"message_start\",\"message\":{\"id\":\"msg_cache\",\"type\":\"message\",\"role\":\"assistant\",\"model\":\"claude-sonnet-4-5\",\"content\":[],\"stop_reason\":null,\"usage\":{\"input_tokens\":10,\"output_tokens\":0,\"cache_read_input_tokens\":90}}}\n\n",
+            "event: message_delta\ndata: {\"type\":\"message_delta\",\"delta\":{\"stop_reason\":\"end_turn\"},\"usage\":{\"output_tokens\":7}}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        ];
+
+        let byte_stream = stream::iter(
+            sse_data.into_iter().map(|s| Ok::<Bytes, io::Error>(Bytes::from(s)))
+        );
+
+        let response = collect_stream_to_json(byte_stream).await.unwrap();
+        assert_eq!(response.usage.input_tokens, 10);
+        assert_eq!(response.usage.output_tokens, 7);
+        assert_eq!(response.usage.cache_read_input_tokens, Some(90));
+    }
+
+    #[tokio::test]
+    async fn test_collect_citations_delta() {
+        let sse_data = vec![
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":0,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"citations_delta\",\"citation\":{\"type\":\"char_location\",\"cited_text\":\"The sky is blue.\",\"document_index\":0,\"document_title\":null,\"start_char_index\":0,\"end_char_index\":16}}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"The sky is blue.\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":0}\n\n",
+            "event: content_block_start\ndata: {\"type\":\"content_block_start\",\"index\":1,\"content_block\":{\"type\":\"text\",\"text\":\"\"}}\n\n",
+            "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":1,\"delta\":{\"type\":\"text_delta\",\"text\":\" Indeed.\"}}\n\n",
+            "event: content_block_stop\ndata: {\"type\":\"content_block_stop\",\"index\":1}\n\n",
+            "event: message_stop\ndata: {\"type\":\"message_stop\"}\n\n",
+        ];
+
+        let byte_stream = stream::iter(
+            sse_data.into_iter().map(|s| Ok::<Bytes, io::Error>(Bytes::from(s)))
+        );
+
+        let response = collect_stream_to_json(byte_stream).await.unwrap();
+        assert_eq!(response.content.len(), 2);
+
+        match &response.content[0] {
+            ContentBlock::Text { text, citations } => {
+                assert_eq!(text, "The sky is blue.");
+                let citations = citations.as_ref().expect("citations");
+                assert_eq!(citations[0]["type"], "char_location");
+                assert_eq!(citations[0]["end_char_index"], 16);
+            }
+            other => panic!("Expected Text block, got {:?}", other),
+        }
+        assert!(matches!(&response.content[1], ContentBlock::Text { citations: None, .. }));
+    }
 }
@@ -0,0 +1,491 @@
+// Claude 文档引用 (documents with citations.enabled)
+// 请求侧: 为可引用文档标注序号并注入 <cite> 引用协议
+// 响应侧: 解析模型输出中的 <cite> 标签, 转换为 char_location / page_location 引用
+
+use super::models::*;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+/// 注入到系统提示词中的引用协议
+pub const CITATION_PROMPT: &str = "\n\
+==== 文档引用协议 ====\n\
+对话中的文档以 <document index=\"N\"> 标注序号。当回答引用文档内容时：\n\
+1) 将逐字摘录的原文包裹为 `<cite doc=\"N\">原文</cite>`；PDF 文档附加页码 `<cite doc=\"N\" page=\"P\">原文</cite>`。\n\
+2) 标签内必须是文档中逐字出现的文本，不得改写、不得嵌套标签，也不要用 markdown 包装。\n\
+3) 标签之外正常作答。\n\
+======================";
+
+/// 未闭合的 <cite> 标签最多缓冲的字节数，超出后按普通文本输出，避免流式输出卡住
+const MAX_PENDING_CITE_BYTES: usize = 4096;
+
+const CITE_OPEN: &str = "<cite";
+const CITE_CLOSE: &str = "</cite>";
+
+/// 可引用的文档
+#[derive(Debug, Clone)]
+pub struct CitableDocument {
+    pub index: usize,
+    pub title: Option<String>,
+    /// 纯文本文档的原文，用于计算 char_location
+    pub text: Option<String>,
+    /// PDF 等分页文档，使用 page_location
+    pub paged: bool,
+}
+
+/// 一次请求中所有启用了 citations 的文档
+#[derive(Debug, Clone, Default)]
+pub struct DocumentCitations {
+    documents: Vec<CitableDocument>,
+}
+
+fn citations_enabled(citations: &Option<Value>) -> bool {
+    citations
+        .as_ref()
+        .and_then(|c| c.get("enabled"))
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+fn is_paged(source: &DocumentSource) -> bool {
+    source.source_type == "base64" && source.media_type == "application/pdf"
+}
+
+impl DocumentCitations {
+    /// 收集请求中的可引用文档; document_index 按全部文档块在请求中出现的顺序计数
+    pub fn from_messages(messages: &[Message]) -> Option<Arc<Self>> {
+        let mut documents = Vec::new();
+        let mut index = 0usize;
+
+        for msg in messages {
+            let MessageContent::Array(blocks) = &msg.content else {
+                continue;
+            };
+            for block in blocks {
+                if let ContentBlock::Document {
+                    source,
+                    title,
+                    citations,
+                    ..
+                } = block
+                {
+                    if citations_enabled(citations) {
+                        documents.push(CitableDocument {
+                            index,
+                            title: title.clone(),
+                            text: (source.source_type == "text").then(|| source.data.clone()),
+                            paged: is_paged(source),
+                        });
+                    }
+                    index += 1;
+                }
+            }
+        }
+
+        if documents.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Self { documents }))
+        }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&CitableDocument> {
+        self.documents.iter().find(|d| d.index == index)
+    }
+
+    /// 为一段引用文本构造 Claude citation; 无法定位时返回 None (按普通文本处理)
+    pub fn citation_for(&self, index: usize, page: Option<u32>, cited_text: &str) -> Option<Value> {
+        let doc = self.get(index)?;
+
+        if let Some(text) = &doc.text {
+            let quote = cited_text.trim();
+            if quote.is_empty() {
+                return None;
+            }
+            let start = text.find(quote)?;
+            let start_char_index = text[..start].chars().count();
+            let end_char_index = start_char_index + quote.chars().count();
+            return Some(json!({
+                "type": "char_location",
+                "cited_text": quote,
+                "document_index": doc.index,
+                "document_title": doc.title,
+                "start_char_index": start_char_index,
+                "end_char_index": end_char_index
+            }));
+        }
+
+        if doc.paged {
+            let page = page.unwrap_or(1).max(1);
+            return Some(json!({
+                "type": "page_location",
+                "cited_text": cited_text,
+                "document_index": doc.index,
+                "document_title": doc.title,
+                "start_page_number": page,
+                "end_page_number": page + 1
+            }));
+        }
+
+        None
+    }
+}
+
+/// 请求预处理: 为可引用文档标注序号，纯文本文档转换为带标注的文本块
+///
+/// 返回是否存在可引用文档 (需要注入 CITATION_PROMPT)
+pub fn prepare_citable_documents(messages: &mut [Message]) -> bool {
+    let mut index = 0usize;
+    let mut found = false;
+
+    for msg in messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut msg.content else {
+            continue;
+        };
+
+        let mut rewritten = Vec::with_capacity(blocks.len());
+        for block in blocks.drain(..) {
+            let ContentBlock::Document {
+                source,
+                title,
+                context,
+                citations,
+                cache_control,
+            } = block
+            else {
+                rewritten.push(block);
+                continue;
+            };
+            let doc_index = index;
+            index += 1;
+
+            if !citations_enabled(&citations) {
+                rewritten.push(ContentBlock::Document {
+                    source,
+                    title,
+                    context,
+                    citations,
+                    cache_control,
+                });
+                continue;
+            }
+            found = true;
+
+            let mut open_tag = format!("<document index=\"{}\"", doc_index);
+            if let Some(t) = &title {
+                open_tag.push_str(&format!(" title=\"{}\"", t.replace('"', "'")));
+            }
+            open_tag.push('>');
+            if let Some(ctx) = &context {
+                open_tag.push_str(&format!("\n<context>{}</context>", ctx));
+            }
+
+            if source.source_type == "text" {
+                rewritten.push(ContentBlock::Text {
+                    text: format!("{}\n{}\n</document>", open_tag, source.data),
+                    citations: None,
+                });
+            } else {
+                rewritten.push(ContentBlock::Text {
+                    text: open_tag,
+                    citations: None,
+                });
+                rewritten.push(ContentBlock::Document {
+                    source,
+                    title,
+                    context,
+                    citations,
+                    cache_control,
+                });
+                rewritten.push(ContentBlock::Text {
+                    text: "</document>".to_string(),
+                    citations: None,
+                });
+            }
+        }
+        *blocks = rewritten;
+    }
+
+    found
+}
+
+/// 解析后的输出片段
+#[derive(Debug, Clone, PartialEq)]
+pub enum CiteSegment {
+    Text(String),
+    Cited {
+        doc: usize,
+        page: Option<u32>,
+        text: String,
+    },
+}
+
+fn attr_value<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let key = format!("{}=\"", name);
+    let start = tag.find(&key)? + key.len();
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
+
+/// buffer 末尾可能是被截断的 "<cite" 前缀，返回其起始位置
+fn partial_open_start(buffer: &str) -> Option<usize> {
+    (1..CITE_OPEN.len())
+        .rev()
+        .filter(|len| *len <= buffer.len())
+        .find(|len| buffer.ends_with(&CITE_OPEN[..*len]))
+        .map(|len| buffer.len() - len)
+}
+
+/// 增量解析 <cite> 标签 (流式输出中标签可能跨 chunk)
+#[derive(Debug, Default)]
+pub struct CiteTagParser {
+    buffer: String,
+}
+
+impl CiteTagParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 追加文本并返回已确定的片段
+    pub fn push(&mut self, text: &str) -> Vec<CiteSegment> {
+        self.buffer.push_str(text);
+        let mut segments = Vec::new();
+
+        loop {
+            let Some(open) = self.buffer.find(CITE_OPEN) else {
+                // 保留可能被截断的标签前缀
+                let keep_from = partial_open_start(&self.buffer).unwrap_or(self.buffer.len());
+                if keep_from > 0 {
+                    segments.push(CiteSegment::Text(self.buffer[..keep_from].to_string()));
+                    self.buffer.drain(..keep_from);
+                }
+                break;
+            };
+
+            if open > 0 {
+                segments.push(CiteSegment::Text(self.buffer[..open].to_string()));
+                self.buffer.drain(..open);
+            }
+
+            // 仅匹配 <cite> / <cite ...>，避免误伤 <citation> 等文本
+            match self.buffer[CITE_OPEN.len()..].chars().next() {
+                None => break,
+                Some(c) if c == '>' || c.is_whitespace() => {}
+                Some(_) => {
+                    segments.push(CiteSegment::Text(CITE_OPEN.to_string()));
+                    self.buffer.drain(..CITE_OPEN.len());
+                    continue;
+                }
+            }
+
+            let complete = self.buffer.find('>').and_then(|tag_end| {
+                self.buffer[tag_end..]
+                    .find(CITE_CLOSE)
+                    .map(|close| (tag_end, tag_end + close))
+            });
+            let Some((tag_end, close)) = complete else {
+                if self.buffer.len() > MAX_PENDING_CITE_BYTES {
+                    // 标签迟迟未闭合，按普通文本放行
+                    segments.push(CiteSegment::Text(CITE_OPEN.to_string()));
+                    self.buffer.drain(..CITE_OPEN.len());
+                    continue;
+                }
+                break;
+            };
+
+            let tag = &self.buffer[..tag_end];
+            let inner = self.buffer[tag_end + 1..close].to_string();
+            let doc = attr_value(tag, "doc").and_then(|v| v.trim().parse::<usize>().ok());
+            let page = attr_value(tag, "page").and_then(|v| v.trim().parse::<u32>().ok());
+            match doc {
+                Some(doc) => segments.push(CiteSegment::Cited {
+                    doc,
+                    page,
+                    text: inner,
+                }),
+                None => segments.push(CiteSegment::Text(inner)),
+            }
+            self.buffer.drain(..close + CITE_CLOSE.len());
+        }
+
+        segments
+    }
+
+    /// 输出结束时放行剩余缓冲
+    pub fn finish(&mut self) -> Vec<CiteSegment> {
+        if self.buffer.is_empty() {
+            return Vec::new();
+        }
+        vec![CiteSegment::Text(std::mem::take(&mut self.buffer))]
+    }
+}
+
+/// 非流式: 一次性解析完整文本
+pub fn parse_cite_tags(text: &str) -> Vec<CiteSegment> {
+    let mut parser = CiteTagParser::new();
+    let mut segments = parser.push(text);
+    segments.extend(parser.finish());
+    segments
+}
+
+/// 将片段转换为 Claude 文本块: 引用片段独立成块并携带 citations，相邻普通文本合并
+pub fn segments_to_blocks(
+    segments: Vec<CiteSegment>,
+    docs: &DocumentCitations,
+) -> Vec<ContentBlock> {
+    let mut blocks = Vec::new();
+    let mut plain = String::new();
+
+    for segment in segments {
+        match segment {
+            CiteSegment::Text(text) => plain.push_str(&text),
+            CiteSegment::Cited { doc, page, text } => match docs.citation_for(doc, page, &text) {
+                Some(citation) => {
+                    if !plain.is_empty() {
+                        blocks.push(ContentBlock::Text {
+                            text: std::mem::take(&mut plain),
+                            citations: None,
+                        });
+                    }
+                    blocks.push(ContentBlock::Text {
+                        text,
+                        citations: Some(vec![citation]),
+                    });
+                }
+                None => plain.push_str(&text),
+            },
+        }
+    }
+
+    if !plain.is_empty() {
+        blocks.push(ContentBlock::Text {
+            text: plain,
+            citations: None,
+        });
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages() -> Vec<Message> {
+        serde_json::from_value(json!([{
+            "role": "user",
+            "content": [
+                {
+                    "type": "document",
+                    "source": { "type": "base64", "media_type": "application/pdf", "data": "JVBERi0x" },
+                    "title": "Plain PDF"
+                },
+                {
+                    "type": "document",
+                    "source": { "type": "text", "media_type": "text/plain", "data": "The grass is green. The sky is blue." },
+                    "title": "Colors",
+                    "citations": { "enabled": true }
+                },
+                {
+                    "type": "document",
+                    "source": { "type": "base64", "media_type": "application/pdf", "data": "JVBERi0x" },
+                    "title": "Report",
+                    "citations": { "enabled": true }
+                },
+                { "type": "text", "text": "What color is the sky?" }
+            ]
+        }]))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_citation_locations() {
+        let docs = DocumentCitations::from_messages(&messages()).unwrap();
+        assert!(docs.get(0).is_none());
+
+        let c = docs.citation_for(1, None, "The sky is blue.").unwrap();
+        assert_eq!(c["type"], "char_location");
+        assert_eq!(c["document_index"], 1);
+        assert_eq!(c["document_title"], "Colors");
+        assert_eq!(c["start_char_index"], 20);
+        assert_eq!(c["end_char_index"], 36);
+
+        // 原文中不存在的摘录不生成引用
+        assert!(docs.citation_for(1, None, "The sky is red.").is_none());
+
+        let p = docs.citation_for(2, Some(3), "Revenue grew").unwrap();
+        assert_eq!(p["type"], "page_location");
+        assert_eq!(p["start_page_number"], 3);
+        assert_eq!(p["end_page_number"], 4);
+    }
+
+    #[test]
+    fn test_prepare_citable_documents() {
+        let mut msgs = messages();
+        assert!(prepare_citable_documents(&mut msgs));
+
+        let MessageContent::Array(blocks) = &msgs[0].content else {
+            panic!("expected array content");
+        };
+        // 未启用引用的文档保持原样
+        assert!(matches!(blocks[0], ContentBlock::Document { .. }));
+        match &blocks[1] {
+            ContentBlock::Text { text, .. } => {
+                assert!(text.starts_with("<document index=\"1\" title=\"Colors\">"));
+                assert!(text.contains("The sky is blue."));
+                assert!(text.ends_with("</document>"));
+            }
+            other => panic!("unexpected block: {:?}", other),
+        }
+        assert!(
+            matches!(&blocks[2], ContentBlock::Text { text, .. } if text == "<document index=\"2\" title=\"Report\">")
+        );
+        assert!(matches!(blocks[3], ContentBlock::Document { .. }));
+        assert!(matches!(&blocks[4], ContentBlock::Text { text, .. } if text == "</document>"));
+    }
+
+    #[test]
+    fn test_parser_handles_tags_split_across_chunks() {
+        let mut parser = CiteTagParser::new();
+        let mut segments = parser.push("Answer: <ci");
+        segments.extend(parser.push("te doc=\"1\">The sky"));
+        segments.extend(parser.push(" is blue.</cite> Done <citation>"));
+        segments.extend(parser.finish());
+
+        let text: String = segments
+            .iter()
+            .filter_map(|s| match s {
+                CiteSegment::Text(t) => Some(t.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(text, "Answer:  Done <citation>");
+        assert!(segments.contains(&CiteSegment::Cited {
+            doc: 1,
+            page: None,
+            text: "The sky is blue.".to_string(),
+        }));
+    }
+
+    #[test]
+    fn test_segments_to_blocks() {
+        let docs = DocumentCitations::from_messages(&messages()).unwrap();
+        let blocks = segments_to_blocks(
+            parse_cite_tags(
+                "It is <cite doc=\"1\">The sky is blue.</cite> per <cite doc=\"1\">made up</cite>.",
+            ),
+            &docs,
+        );
+        assert_eq!(blocks.len(), 3);
+        match &blocks[1] {
+            ContentBlock::Text { text, citations } => {
+                assert_eq!(text, "The sky is blue.");
+                assert_eq!(citations.as_ref().unwrap()[0]["type"], "char_location");
+            }
+            other => panic!("unexpected block: {:?}", other),
+        }
+        // 无法定位的摘录回退为普通文本
+        assert!(
+            matches!(&blocks[2], ContentBlock::Text { text, citations: None } if text == " per made up.")
+        );
+    }
+}
@@ -8,9 +8,11 @@ pub mod streaming;
 pub mod utils;
 pub mod thinking_utils;
 pub mod collector;
+pub mod server_tools;
+pub mod document_citations;
 
 pub use models::*;
-pub use request::{transform_claude_request_in, clean_cache_control_from_messages, merge_consecutive_messages};
+pub use request::{transform_claude_request_in, transform_claude_request_for_client, clean_cache_control_from_messages, merge_consecutive_messages, resolve_document_sources};
 pub use response::transform_response;
 pub use streaming::{PartProcessor, StreamingState};
 pub use thinking_utils::{close_tool_loop_for_thinking, filter_invalid_thinking_blocks_with_family};
@@ -32,6 +34,7 @@ pub fn create_claude_sse_stream(
     estimated_prompt_tokens: Option<u32>, // [FIX] Estimated tokens for calibrator learning
     message_count: usize, // [NEW v4.0.0] Message count for rewind detection
     client_adapter: Option<std::sync::Arc<dyn ClientAdapter>>, // [NEW] Adapter reference
+    document_citations: Option<std::sync::Arc<document_citations::DocumentCitations>>, // 启用 citations 的文档
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     use async_stream::stream;
     use bytes::BytesMut;
@@ -45,6 +48,7 @@ pub fn create_claude_sse_stream(
         state.context_limit = context_limit;
         state.estimated_prompt_tokens = estimated_prompt_tokens; // [FIX] Pass estimated tokens
         state.set_client_adapter(client_adapter); // [NEW] Set adapter
+        state.document_citations = document_citations;
         let mut buffer = BytesMut::new();
 
         loop {
@@ -76,8 +80,14 @@ pub fn create_claude_sse_stream(
                             }
                         }
                         Err(e) => {
-                            yield Err(format!("Stream error: {}", e));
-                            break;
+                            // 上游中断: 以 error 事件结束流，不补发 end_turn / message_stop，
+                            // 避免客户端把截断的回复当作正常结束
+                            tracing::warn!("[{}] Upstream stream interrupted: {}", trace_id, e);
+                            yield Ok(state.emit_error(
+                                "api_error",
+                                &format!("Upstream stream interrupted: {}", e),
+                            ));
+                            return;
                         }
                     }
                 }
@@ -197,6 +207,11 @@ fn process_sse_line(line: &str, state: &mut StreamingState, trace_id: &str, emai
             } else if let Some(chunks_arr) = grounding.get("grounding_metadata").and_then(|m| m.get("groundingChunks")).and_then(|v| v.as_array()) {
                 state.grounding_chunks = Some(chunks_arr.clone());
             }
+
+            // 提取引用片段 (groundingSupports)
+            if let Some(supports) = grounding.get("groundingSupports").and_then(|v| v.as_array()) {
+                state.grounding_supports = Some(supports.clone());
+            }
         }
     }
 
@@ -451,6 +466,67 @@ mod tests {
         assert!(all_text.contains("Hello"));
     }
 
+    #[test]
+    fn test_grounding_supports_emit_citations_delta() {
+        let mut state = StreamingState::new();
+
+        let text_line = r#"data: {"candidates":[{"content":{"parts":[{"text":"Rust 1.0 shipped in 2015."}]}}],"modelVersion":"test","responseId":"123"}"#;
+        let finish_line = r#"data: {"candidates":[{"content":{"parts":[{"text":""}]},"finishReason":"STOP","groundingMetadata":{"webSearchQueries":["rust 1.0"],"groundingChunks":[{"web":{"uri":"https://blog.rust-lang.org","title":"Rust Blog"}}],"groundingSupports":[{"segment":{"startIndex":0,"endIndex":25,"text":"Rust 1.0 shipped in 2015."},"groundingChunkIndices":[0]}]}}]}"#;
+
+        process_sse_line(text_line, &mut state, "test_id", "test@example.com");
+        let chunks = process_sse_line(finish_line, &mut state, "test_id", "test@example.com").unwrap();
+        let all_text: String = chunks
+            .iter()
+            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_default())
+            .collect();
+
+        assert!(all_text.contains("\"type\":\"citations_delta\""));
+        assert!(all_text.contains("\"type\":\"web_search_result_location\""));
+        assert!(all_text.contains("\"cited_text\":\"Rust 1.0 shipped in 2015.\""));
+
+        // 引用挂在已流式输出的正文块 (index 0) 上, 且在该块结束之前
+        let citation_pos = all_text.find("\"type\":\"citations_delta\"").unwrap();
+        let first_stop = all_text.find("content_block_stop").unwrap();
+        assert!(citation_pos < first_stop);
+        let citation_event: serde_json::Value = all_text
+            .lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .filter_map(|data| serde_json::from_str::<serde_json::Value>(data).ok())
+            .find(|event| event["delta"]["type"] == "citations_delta")
+            .unwrap();
+        assert_eq!(citation_event["index"], 0);
+    }
+
+    #[test]
+    fn test_cite_tags_stream_as_cited_text_blocks() {
+        let messages: Vec<Message> = serde_json::from_value(serde_json::json!([{
+            "role": "user",
+            "content": [{
+                "type": "document",
+                "source": { "type": "text", "media_type": "text/plain", "data": "The sky is blue." },
+                "citations": { "enabled": true }
+            }]
+        }]))
+        .unwrap();
+        let mut state = StreamingState::new();
+        state.document_citations = document_citations::DocumentCitations::from_messages(&messages);
+
+        let mut output = String::new();
+        for text in ["Per the doc, <cite doc=\\\"0\\\">The sky", " is blue.</cite>"] {
+            let line = format!(
+                r#"data: {{"candidates":[{{"content":{{"parts":[{{"text":"{}"}}]}}}}],"modelVersion":"test","responseId":"123"}}"#,
+                text
+            );
+            for chunk in process_sse_line(&line, &mut state, "test_id", "test@example.com").unwrap_or_default() {
+                output.push_str(&String::from_utf8(chunk.to_vec()).unwrap());
+            }
+        }
+
+        assert!(!output.contains("<cite"));
+        assert!(output.contains("\"type\":\"char_location\""));
+        assert!(output.contains("\"text\":\"The sky is blue.\""));
+    }
+
     #[tokio::test]
     async fn test_thinking_only_interruption_recovery() {
         use futures::StreamExt;
@@ -483,6 +559,7 @@ mod tests {
             None,
             1, // message_count
             None, // client_adapter
+            None, // document_citations
         );
 
         // 3. 收集输出
@@ -79,7 +79,11 @@ pub enum MessageContent {
 #[serde(tag = "type")]
 pub enum ContentBlock {
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<Vec<serde_json::Value>>,
+    },
 
     #[serde(rename = "thinking")]
     Thinking {
@@ -100,6 +104,12 @@ pub enum ContentBlock {
     #[serde(rename = "document")]
     Document {
         source: DocumentSource,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        title: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        context: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        citations: Option<serde_json::Value>,
         #[serde(skip_serializing_if = "Option::is_none")]
         cache_control: Option<serde_json::Value>,
     },
@@ -140,22 +150,38 @@ pub enum ContentBlock {
         tool_use_id: String,
         content: serde_json::Value,
     },
+
+    #[serde(rename = "web_fetch_tool_result")]
+    WebFetchToolResult {
+        tool_use_id: String,
+        content: serde_json::Value,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ImageSource {
     #[serde(rename = "type")]
     pub source_type: String,
+    #[serde(default)]
     pub media_type: String,
+    #[serde(default)]
     pub data: String,
+    /// source.type = "file" 时引用 Files API 上传的文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentSource {
     #[serde(rename = "type")]
     pub source_type: String, // "base64"
+    #[serde(default)]
     pub media_type: String,  // e.g. "application/pdf"
+    #[serde(default)]
     pub data: String,        // base64 data
+    /// source.type = "file" 时引用 Files API 上传的文件
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
 }
 
 /// Tool - supports both client tools (with input_schema) and server tools (like web_search)
@@ -173,6 +199,9 @@ pub struct Tool {
     /// Input schema - required for client tools, absent for server tools
     #[serde(skip_serializing_if = "Option::is_none")]
     pub input_schema: Option<serde_json::Value>,
+    /// Server tools only: maximum number of uses per request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_uses: Option<u32>,
 }
 
 impl Tool {
@@ -355,6 +384,14 @@ pub struct UsageMetadata {
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "cachedContentTokenCount")]
     pub cached_content_token_count: Option<u32>,
+    /// 思考 Token (不含在 candidatesTokenCount 中)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "thoughtsTokenCount")]
+    pub thoughts_token_count: Option<u32>,
+    /// 工具调用结果注入的提示词 Token (不含在 promptTokenCount 中)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "toolUsePromptTokenCount")]
+    pub tool_use_prompt_token_count: Option<u32>,
 }
 
 // ========== Grounding Metadata (for googleSearch results) ==========
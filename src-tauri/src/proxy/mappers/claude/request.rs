@@ -2,7 +2,11 @@
 // 对应 transformClaudeRequestIn
 
 use super::models::*;
+use super::document_citations;
+use super::server_tools;
+use crate::proxy::common::client_adapter::ToolNameStyle;
 use crate::proxy::mappers::signature_store::get_thought_signature; // Deprecated, kept for fallback
+use crate::proxy::mappers::reasoning_policy::{self, ReasoningEffort, ReasoningRequest};
 use crate::proxy::mappers::tool_result_compressor;
 use crate::proxy::session_manager::SessionManager;
 use serde_json::{json, Value};
@@ -73,6 +77,8 @@ fn build_safety_settings() -> Value {
 /// 2. Anthropic API 不接受请求中包含 cache_control 字段
 /// 3. 即使是转发到 Gemini,也应该清理以保持协议纯净性
 ///
+/// 断点语义 (前缀路由) 由 `proxy::prompt_cache` 从原始请求体提取，不依赖这里的字段。
+///
 /// [FIX #593] 增强版本:添加详细日志用于调试 MCP 工具兼容性问题
 pub fn clean_cache_control_from_messages(messages: &mut [Message]) {
     tracing::info!(
@@ -219,7 +225,7 @@ fn sort_thinking_blocks_first(messages: &mut [Message]) {
                             | ContentBlock::RedactedThinking { .. } => {
                                 thinking_blocks.push(block);
                             }
-                            ContentBlock::Text { text } => {
+                            ContentBlock::Text { text, .. } => {
                                 // Filter out purely empty or structural text like "(no content)"
                                 if !text.trim().is_empty() && text != "(no content)" {
                                     text_blocks.push(block);
@@ -251,6 +257,75 @@ fn sort_thinking_blocks_first(messages: &mut [Message]) {
     }
 }
 
+/// 预处理文档与图片块
+///
+/// - source.type = "file": 从本地 Files API 存储中 owner 名下的文件读取，图片 / PDF 转为 base64 源，Office / 纯文本转为 text 源
+/// - base64 文档: PDF 仅校验大小，docx / xlsx / 纯文本在本地提取为 text 源文档
+///
+/// 需在构造 DocumentCitations 之前调用，提取后的文档按 text 源参与 char_location 引用。
+pub fn resolve_document_sources(
+    messages: &mut [Message],
+    owner: Option<&str>,
+) -> Result<(), crate::proxy::common::document_input::DocumentInputError> {
+    use crate::proxy::common::document_input::{
+        resolve_base64_document, DocumentInputError, ResolvedDocument,
+    };
+
+    for msg in messages.iter_mut() {
+        let MessageContent::Array(blocks) = &mut msg.content else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            match block {
+                ContentBlock::Image { source, .. } if source.source_type == "file" => {
+                    let file_id = source.file_id.take().unwrap_or_default();
+                    match crate::proxy::files::resolve_file_reference(&file_id, owner)? {
+                        ResolvedDocument::Inline { mime_type, data } if mime_type.starts_with("image/") => {
+                            source.source_type = "base64".to_string();
+                            source.media_type = mime_type;
+                            source.data = data;
+                        }
+                        _ => {
+                            return Err(DocumentInputError::Invalid(format!(
+                                "File '{}' is not an image",
+                                file_id
+                            )))
+                        }
+                    }
+                }
+                ContentBlock::Document { source, title, .. } => {
+                    let resolved = match source.source_type.as_str() {
+                        "file" => {
+                            let file_id = source.file_id.take().unwrap_or_default();
+                            crate::proxy::files::resolve_file_reference(&file_id, owner)?
+                        }
+                        "base64" => resolve_base64_document(
+                            &source.data,
+                            Some(&source.media_type),
+                            title.as_deref(),
+                        )?,
+                        _ => continue,
+                    };
+                    match resolved {
+                        ResolvedDocument::Inline { mime_type, data } => {
+                            source.source_type = "base64".to_string();
+                            source.media_type = mime_type;
+                            source.data = data;
+                        }
+                        ResolvedDocument::Text(text) => {
+                            source.source_type = "text".to_string();
+                            source.media_type = "text/plain".to_string();
+                            source.data = text;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 /// 合并 ClaudeRequest 中连续的同角色消息
 ///
 /// 场景: 当从 Spec/Plan 模式切换回编码模式时，可能出现连续两条 "user" 消息
@@ -274,7 +349,7 @@ pub fn merge_consecutive_messages(messages: &mut Vec<Message>) {
                         current_blocks.extend(next_blocks);
                     }
                     (MessageContent::Array(current_blocks), MessageContent::String(next_text)) => {
-                        current_blocks.push(ContentBlock::Text { text: next_text });
+                        current_blocks.push(ContentBlock::Text { text: next_text, citations: None });
                     }
                     (MessageContent::String(current_text), MessageContent::String(next_text)) => {
                         *current_text = format!("{}\n\n{}", current_text, next_text);
@@ -282,6 +357,7 @@ pub fn merge_consecutive_messages(messages: &mut Vec<Message>) {
                     (MessageContent::String(current_text), MessageContent::Array(next_blocks)) => {
                         let mut new_blocks = vec![ContentBlock::Text {
                             text: current_text.clone(),
+                            citations: None,
                         }];
                         new_blocks.extend(next_blocks);
                         current.content = MessageContent::Array(new_blocks);
@@ -336,6 +412,16 @@ pub fn transform_claude_request_in(
     claude_req: &ClaudeRequest,
     project_id: &str,
     is_retry: bool,
+) -> Result<Value, String> {
+    transform_claude_request_for_client(claude_req, project_id, is_retry, ToolNameStyle::default())
+}
+
+/// 同 [`transform_claude_request_in`]，工具 Schema 按客户端的 MCP 工具命名约定匹配工具适配器
+pub fn transform_claude_request_for_client(
+    claude_req: &ClaudeRequest,
+    project_id: &str,
+    is_retry: bool,
+    tool_name_style: ToolNameStyle,
 ) -> Result<Value, String> {
     // [CRITICAL FIX] 预先清理所有消息中的 cache_control 字段
     // 这解决了 VS Code 插件等客户端在多轮对话中将历史消息的 cache_control 字段
@@ -352,6 +438,10 @@ pub fn transform_claude_request_in(
     // This handles cases where context compression (kilo) incorrectly reorders blocks
     sort_thinking_blocks_first(&mut cleaned_req.messages);
 
+    // 启用 citations 的文档: 标注序号并在系统提示词中注入 <cite> 引用协议
+    let has_citable_documents =
+        document_citations::prepare_citable_documents(&mut cleaned_req.messages);
+
     let claude_req = &cleaned_req; // 后续使用清理后的请求
 
     // [NEW] Generate session ID for signature tracking
@@ -359,18 +449,22 @@ pub fn transform_claude_request_in(
     let session_id = SessionManager::extract_session_id(claude_req);
     tracing::debug!("[Claude-Request] Session ID: {}", session_id);
 
+    // 联网工具与函数工具共存时，由代理以合成函数接管 (见 server_tools)
+    let server_tool_plan = server_tools::resolve_plan(&claude_req.tools);
+
     // 检测是否有联网工具 (server tool or built-in tool)
-    let has_web_search_tool = claude_req
-        .tools
-        .as_ref()
-        .map(|tools| {
-            tools.iter().any(|t| {
-                t.is_web_search()
-                    || t.name.as_deref() == Some("google_search")
-                    || t.type_.as_deref() == Some("web_search_20250305")
+    let has_web_search_tool = !server_tool_plan.is_active()
+        && claude_req
+            .tools
+            .as_ref()
+            .map(|tools| {
+                tools.iter().any(|t| {
+                    t.is_web_search()
+                        || t.name.as_deref() == Some("google_search")
+                        || t.type_.as_deref() == Some("web_search_20250305")
+                })
             })
-        })
-        .unwrap_or(false);
+            .unwrap_or(false);
 
     // 用于存储 tool_use id -> name 映射
     let mut tool_id_to_name: HashMap<String, String> = HashMap::new();
@@ -400,8 +494,17 @@ pub fn transform_claude_request_in(
     }
 
     // 1. System Instruction (注入动态身份防护 & MCP XML 协议)
-    let system_instruction =
+    let mut system_instruction =
         build_system_instruction(&claude_req.system, &claude_req.model, has_mcp_tools);
+    if has_citable_documents {
+        if let Some(parts) = system_instruction
+            .as_mut()
+            .and_then(|si| si.get_mut("parts"))
+            .and_then(|p| p.as_array_mut())
+        {
+            parts.push(json!({ "text": document_citations::CITATION_PROMPT }));
+        }
+    }
 
     //  Map model name (Use standard mapping)
     // [IMPROVED] 提取 web search 模型为常量，便于维护
@@ -418,11 +521,7 @@ pub fn transform_claude_request_in(
     };
 
     // 将 Claude 工具转为 Value 数组以便探测联网
-    let tools_val: Option<Vec<Value>> = claude_req.tools.as_ref().map(|list| {
-        list.iter()
-            .map(|t| serde_json::to_value(t).unwrap_or(json!({})))
-            .collect()
-    });
+    let tools_val = server_tools::routing_tools(&claude_req.tools, server_tool_plan);
 
     // Resolve grounding config
     let config = crate::proxy::mappers::common_utils::resolve_request_config(
@@ -551,7 +650,7 @@ pub fn transform_claude_request_in(
     )?;
 
     // 3. Tools
-    let tools = build_tools(&claude_req.tools, has_web_search_tool)?;
+    let tools = build_tools(&claude_req.tools, has_web_search_tool, server_tool_plan, tool_name_style)?;
 
     // 5. Safety Settings (configurable via GEMINI_SAFETY_THRESHOLD env var)
     let safety_settings = build_safety_settings();
@@ -890,7 +989,7 @@ fn build_contents(
         MessageContent::Array(blocks) => {
             for item in blocks {
                 match item {
-                    ContentBlock::Text { text } => {
+                    ContentBlock::Text { text, .. } => {
                         if text != "(no content)" {
                             // [NEW] 任务去重逻辑: 如果当前是 User 消息，且紧跟在 ToolResult 之后，
                             // 检查该文本是否与上一轮任务描述完全一致。
@@ -1067,7 +1166,7 @@ fn build_contents(
                             saw_non_thinking = true;
                         }
                     }
-                    ContentBlock::Document { source, .. } => {
+                    ContentBlock::Document { source, title, .. } => {
                         if source.source_type == "base64" {
                             parts.push(json!({
                                 "inlineData": {
@@ -1076,6 +1175,15 @@ fn build_contents(
                                 }
                             }));
                             saw_non_thinking = true;
+                        } else if source.source_type == "text" {
+                            // 未开启引用的纯文本文档 (含本地提取的 Office 文档) 直接作为文本
+                            parts.push(json!({
+                                "text": crate::proxy::common::document_input::wrap_extracted_text(
+                                    title.as_deref(),
+                                    &source.data
+                                )
+                            }));
+                            saw_non_thinking = true;
                         }
                     }
                     ContentBlock::ToolUse {
@@ -1237,11 +1345,21 @@ fn build_contents(
                             .cloned()
                             .unwrap_or_else(|| tool_use_id.clone());
 
+                        const MAX_TOOL_RESULT_CHARS: usize = 200_000;
+
                         // [FIX #593] 工具输出压缩: 处理超大工具输出
-                        // 使用智能压缩策略(浏览器快照、大文件提示等)
+                        // 使用智能压缩策略(浏览器快照、大文件提示、按工具配置的语义压缩等)
                         let mut compacted_content = content.clone();
                         if let Some(blocks) = compacted_content.as_array_mut() {
-                            tool_result_compressor::sanitize_tool_result_blocks(blocks);
+                            tool_result_compressor::sanitize_tool_result_blocks_for_tool(blocks, &func_name);
+                        } else if let Some(text) = compacted_content.as_str() {
+                            compacted_content = serde_json::Value::String(
+                                tool_result_compressor::compact_tool_result_text_for_tool(
+                                    text,
+                                    MAX_TOOL_RESULT_CHARS,
+                                    &func_name,
+                                ),
+                            );
                         }
 
                         // Smart Truncation: strict image removal
@@ -1273,7 +1391,6 @@ fn build_contents(
                         };
 
                         // Smart Truncation: max chars limit
-                        const MAX_TOOL_RESULT_CHARS: usize = 200_000;
                         if merged_content.len() > MAX_TOOL_RESULT_CHARS {
                             tracing::warn!(
                                 "Truncating tool result from {} chars to {}",
@@ -1318,7 +1435,8 @@ fn build_contents(
                     }
                     // ContentBlock::RedactedThinking handled above at line 583
                     ContentBlock::ServerToolUse { .. }
-                    | ContentBlock::WebSearchToolResult { .. } => {
+                    | ContentBlock::WebSearchToolResult { .. }
+                    | ContentBlock::WebFetchToolResult { .. } => {
                         // 搜索结果 block 不应由客户端发回给上游 (已由 tool_result 替代)
                         continue;
                     }
@@ -1614,12 +1732,22 @@ fn merge_adjacent_roles(mut contents: Vec<Value>) -> Vec<Value> {
 }
 
 /// 构建 Tools
-fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option<Value>, String> {
+fn build_tools(
+    tools: &Option<Vec<Tool>>,
+    has_web_search: bool,
+    server_tool_plan: server_tools::ServerToolPlan,
+    tool_name_style: ToolNameStyle,
+) -> Result<Option<Value>, String> {
     if let Some(tools_list) = tools {
         let mut function_declarations: Vec<Value> = Vec::new();
         let mut has_google_search = has_web_search;
 
         for tool in tools_list {
+            // 0. web_fetch 由服务端工具循环接管，改用合成函数声明
+            if server_tool_plan.web_fetch && server_tools::is_web_fetch_tool(tool) {
+                continue;
+            }
+
             // 1. Detect server tools / built-in tools like web_search
             if tool.is_web_search() {
                 has_google_search = true;
@@ -1645,7 +1773,11 @@ fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option
                     "type": "object",
                     "properties": {}
                 }));
-                crate::proxy::common::json_schema::clean_json_schema(&mut input_schema);
+                crate::proxy::common::json_schema::clean_json_schema_for_client_tool(
+                    &mut input_schema,
+                    name,
+                    tool_name_style,
+                );
 
                 function_declarations.push(json!({
                     "name": name,
@@ -1655,6 +1787,12 @@ fn build_tools(tools: &Option<Vec<Tool>>, has_web_search: bool) -> Result<Option
             }
         }
 
+        // 服务端工具循环: 以合成函数暴露 web_search / web_fetch，与本地工具共存
+        if server_tool_plan.is_active() {
+            function_declarations.extend(server_tools::function_declarations(server_tool_plan));
+            has_google_search = false;
+        }
+
         let mut tool_obj = serde_json::Map::new();
 
         // [修复] 解决 "Multiple tools are supported only when they are all search tools" 400 错误
@@ -1696,29 +1834,25 @@ fn build_generation_config(
 ) -> Value {
     let mut config = json!({});
 
-    // Thinking 配置
-    if is_thinking_enabled {
-        let mut thinking_config = json!({"includeThoughts": true});
-        let budget_tokens = claude_req.thinking.as_ref().and_then(|t| t.budget_tokens).unwrap_or(16000);
-
-        let tb_config = crate::proxy::config::get_thinking_budget_config();
-        let budget = match tb_config.mode {
-            crate::proxy::config::ThinkingBudgetMode::Passthrough => budget_tokens,
-            crate::proxy::config::ThinkingBudgetMode::Custom => tb_config.custom_value,
-            crate::proxy::config::ThinkingBudgetMode::Auto => {
-                let model_lower = claude_req.model.to_lowercase();
-                let is_gemini_limited = has_web_search
-                    || model_lower.contains("flash")
-                    || model_lower.ends_with("-thinking");
-                if is_gemini_limited {
-                    budget_tokens.min(24576)
-                } else {
-                    budget_tokens
-                }
-            }
+    // Effort level (Claude API v2.0.67+): output_config.effort，未知取值按 HIGH 处理
+    let effort = claude_req
+        .output_config
+        .as_ref()
+        .and_then(|c| c.effort.as_deref())
+        .map(|e| ReasoningEffort::parse(e).unwrap_or(ReasoningEffort::High));
+
+    // Thinking 配置 (预算优先级: budget_tokens > effort > 默认 16000)
+    let thinking_plan = if is_thinking_enabled {
+        let reasoning = ReasoningRequest {
+            budget_tokens: claude_req.thinking.as_ref().and_then(|t| t.budget_tokens),
+            effort,
         };
-        thinking_config["thinkingBudget"] = json!(budget);
-        config["thinkingConfig"] = thinking_config;
+        reasoning_policy::resolve_thinking(&claude_req.model, &reasoning, 16000, has_web_search)
+    } else {
+        None
+    };
+    if let Some(plan) = thinking_plan {
+        config["thinkingConfig"] = plan.to_gemini();
     }
 
     // 其他参数
@@ -1732,22 +1866,14 @@ fn build_generation_config(
         config["topK"] = json!(top_k);
     }
 
-    // Effort level mapping (Claude API v2.0.67+)
     // Maps Claude's output_config.effort to Gemini's effortLevel
-    if let Some(output_config) = &claude_req.output_config {
-        if let Some(effort) = &output_config.effort {
-            config["effortLevel"] = json!(match effort.to_lowercase().as_str() {
-                "high" => "HIGH",
-                "medium" => "MEDIUM",
-                "low" => "LOW",
-                _ => "HIGH", // Default to HIGH for unknown values
-            });
-            tracing::debug!(
-                "[Generation-Config] Effort level set: {} -> {}",
-                effort,
-                config["effortLevel"]
-            );
-        }
+    if let Some(effort) = effort {
+        config["effortLevel"] = json!(effort.gemini_level());
+        tracing::debug!(
+            "[Generation-Config] Effort level set: {:?} -> {}",
+            effort,
+            config["effortLevel"]
+        );
     }
 
     // web_search 强制 candidateCount=1
@@ -1760,19 +1886,13 @@ fn build_generation_config(
     let mut final_max_tokens: Option<i64> = claude_req.max_tokens.map(|t| t as i64);
 
     // [NEW] 确保 maxOutputTokens 大于 thinkingBudget (API 强约束)
-    if let Some(thinking_config) = config.get("thinkingConfig") {
-        if let Some(budget) = thinking_config
-            .get("thinkingBudget")
-            .and_then(|t| t.as_u64())
-        {
-            let current = final_max_tokens.unwrap_or(0);
-            if current <= budget as i64 {
-                final_max_tokens = Some((budget + 8192) as i64);
-                tracing::info!(
-                    "[Generation-Config] Bumping maxOutputTokens to {} due to thinking budget of {}", 
-                    final_max_tokens.unwrap(), budget
-                );
-            }
+    if let Some(plan) = thinking_plan {
+        if let Some(bumped) = plan.max_output_tokens(final_max_tokens) {
+            final_max_tokens = Some(bumped);
+            tracing::info!(
+                "[Generation-Config] Bumping maxOutputTokens to {} due to thinking budget of {}",
+                bumped, plan.budget
+            );
         }
     }
 
@@ -2060,6 +2180,7 @@ mod tests {
                         },
                         ContentBlock::Text {
                             text: "Here is my response".to_string(),
+                            citations: None,
                         },
                     ]),
                 },
@@ -2070,6 +2191,7 @@ mod tests {
                             source_type: "base64".to_string(),
                             media_type: "image/png".to_string(),
                             data: "iVBORw0KGgo=".to_string(),
+                            file_id: None,
                         },
                         cache_control: Some(json!({"type": "ephemeral"})), // 这个也应该被清理
                     }]),
@@ -2118,6 +2240,7 @@ mod tests {
                     content: MessageContent::Array(vec![
                         ContentBlock::Text {
                             text: "Checking...".to_string(),
+                            citations: None,
                         },
                         ContentBlock::ToolUse {
                             id: "tool_1".to_string(),
@@ -2145,6 +2268,7 @@ mod tests {
                 description: Some("List files".to_string()),
                 input_schema: Some(json!({"type": "object"})),
                 type_: None,
+                max_uses: None,
                 // cache_control: None, // removed
             }]),
             stream: false,
@@ -2195,6 +2319,7 @@ mod tests {
                     role: "assistant".to_string(),
                     content: MessageContent::Array(vec![ContentBlock::Text {
                         text: "Response".to_string(),
+                        citations: None,
                     }]),
                 },
             ],
@@ -2247,6 +2372,7 @@ mod tests {
                     },
                     ContentBlock::Text {
                         text: "Hi".to_string(),
+                        citations: None,
                     },
                 ]),
             }],
@@ -2298,6 +2424,7 @@ mod tests {
                     },
                     ContentBlock::Text {
                         text: "Hi".to_string(),
+                        citations: None,
                     },
                 ]),
             }],
@@ -2341,6 +2468,7 @@ mod tests {
                 // Wrong order: Text before Thinking (simulates kilo compression)
                 ContentBlock::Text {
                     text: "Some regular text".to_string(),
+                    citations: None,
                 },
                 ContentBlock::Thinking {
                     thinking: "My thinking process".to_string(),
@@ -2351,6 +2479,7 @@ mod tests {
                 },
                 ContentBlock::Text {
                     text: "More text".to_string(),
+                    citations: None,
                 },
             ]),
         }];
@@ -2396,6 +2525,7 @@ mod tests {
                 },
                 ContentBlock::Text {
                     text: "Some text".to_string(),
+                    citations: None,
                 },
             ]),
         }];
@@ -2427,6 +2557,7 @@ mod tests {
                 role: "user".to_string(),
                 content: MessageContent::Array(vec![ContentBlock::Text {
                     text: "World".to_string(),
+                    citations: None,
                 }]),
             },
             Message {
@@ -2445,6 +2576,7 @@ mod tests {
                 role: "user".to_string(),
                 content: MessageContent::Array(vec![ContentBlock::Text {
                     text: "System Reminder".to_string(),
+                    citations: None,
                 }]),
             },
         ];
@@ -2456,11 +2588,11 @@ mod tests {
         if let MessageContent::Array(blocks) = &messages[0].content {
             assert_eq!(blocks.len(), 2);
             match &blocks[0] {
-                ContentBlock::Text { text } => assert_eq!(text, "Hello"),
+                ContentBlock::Text { text, .. } => assert_eq!(text, "Hello"),
                 _ => panic!("Expected text block"),
             }
             match &blocks[1] {
-                ContentBlock::Text { text } => assert_eq!(text, "World"),
+                ContentBlock::Text { text, .. } => assert_eq!(text, "World"),
                 _ => panic!("Expected text block"),
             }
         } else {
@@ -2477,7 +2609,7 @@ mod tests {
                 _ => panic!("Expected tool_result block"),
             }
             match &blocks[1] {
-                ContentBlock::Text { text } => assert_eq!(text, "System Reminder"),
+                ContentBlock::Text { text, .. } => assert_eq!(text, "System Reminder"),
                 _ => panic!("Expected text block"),
             }
         } else {
@@ -2648,4 +2780,60 @@ mod tests {
         // thinkingConfig SHOULD be injected because of default-on logic
         assert!(gen_config.get("thinkingConfig").is_some(), "thinkingConfig should be auto-enabled for gemini-3-pro");
     }
+
+    #[test]
+    fn test_web_search_with_function_tools_uses_server_tool_loop() {
+        // [场景] 同时声明 web_search 与本地函数工具
+        // 期望: 不降级到搜索模型，web_search 以合成函数形式与本地工具共存
+        let req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{ "role": "user", "content": "What changed in the latest release?" }],
+            "tools": [
+                { "type": "web_search_20250305", "name": "web_search", "max_uses": 3 },
+                { "name": "Read", "description": "Read a file", "input_schema": { "type": "object" } }
+            ]
+        }))
+        .unwrap();
+
+        let body = transform_claude_request_in(&req, "test-project", false).unwrap();
+        assert_ne!(body["model"], "gemini-2.5-flash");
+        assert_ne!(body["requestType"], "web_search");
+
+        let tools = body["request"]["tools"].as_array().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert!(tools[0].get("googleSearch").is_none());
+        let names: Vec<&str> = tools[0]["functionDeclarations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|d| d["name"].as_str())
+            .collect();
+        assert_eq!(names, vec!["Read", "web_search"]);
+    }
+
+    #[test]
+    fn test_plain_text_document_is_extracted_to_text_part() {
+        use base64::Engine as _;
+        let data = base64::engine::general_purpose::STANDARD.encode("quarterly notes");
+        let mut req: ClaudeRequest = serde_json::from_value(json!({
+            "model": "claude-sonnet-4-5",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "document", "title": "notes.md", "source": { "type": "base64", "media_type": "text/markdown", "data": data } },
+                    { "type": "text", "text": "Summarize" }
+                ]
+            }]
+        }))
+        .unwrap();
+
+        resolve_document_sources(&mut req.messages, None).unwrap();
+        let body = transform_claude_request_in(&req, "test-project", false).unwrap();
+        let parts = body["request"]["contents"][0]["parts"].as_array().unwrap();
+        assert_eq!(
+            parts[0]["text"],
+            "<document name=\"notes.md\">\nquarterly notes\n</document>"
+        );
+        assert!(parts.iter().all(|p| p.get("inlineData").is_none()));
+    }
 }
@@ -1,9 +1,12 @@
 // Claude 非流式响应转换 (Gemini → Claude)
 // 对应 NonStreamingProcessor
 
+use super::document_citations::{self, DocumentCitations};
 use super::models::*;
 use super::utils::to_claude_usage;
+use crate::proxy::mappers::citations::{self, GroundingSpan};
 use serde_json::json;
+use std::sync::Arc;
 
 /// Known parameter remappings for Gemini → Claude compatibility
 /// [FIX] Gemini sometimes uses different parameter names than specified in tool schema
@@ -158,6 +161,7 @@ pub struct NonStreamingProcessor {
     pub session_id: Option<String>,
     pub model_name: String,
     pub message_count: usize, // [NEW v4.0.0] Message count for rewind detection
+    pub document_citations: Option<Arc<DocumentCitations>>, // 启用 citations 的文档
 }
 
 impl NonStreamingProcessor {
@@ -174,6 +178,7 @@ impl NonStreamingProcessor {
             session_id,
             model_name,
             message_count,
+            document_citations: None,
         }
     }
 
@@ -204,6 +209,9 @@ impl NonStreamingProcessor {
         // 处理 grounding(web search) -> 转换为 server_tool_use / web_search_tool_result
         if let Some(candidate) = gemini_response.candidates.as_ref().and_then(|c| c.get(0)) {
             if let Some(grounding) = &candidate.grounding_metadata {
+                self.flush_thinking();
+                self.flush_text();
+                self.apply_grounding_citations(grounding);
                 self.process_grounding(grounding);
             }
         }
@@ -423,6 +431,72 @@ impl NonStreamingProcessor {
         }
     }
 
+    /// 将 groundingSupports 转换为文本块上的 web_search_result_location 引用
+    /// 被支撑的片段拆分为独立的文本块并携带 citations
+    fn apply_grounding_citations(&mut self, grounding: &GroundingMetadata) {
+        let Ok(grounding) = serde_json::to_value(grounding) else {
+            return;
+        };
+
+        // 按输出顺序拼接文本，与 Gemini 的 segment 偏移对齐
+        let full_text: String = self
+            .content_blocks
+            .iter()
+            .filter_map(|block| match block {
+                ContentBlock::Text {
+                    text,
+                    citations: None,
+                } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        let spans = citations::grounding_spans_from_metadata(&full_text, &grounding);
+        if spans.is_empty() {
+            return;
+        }
+
+        let mut offset = 0usize;
+        let mut blocks = Vec::with_capacity(self.content_blocks.len() + spans.len() * 2);
+        for block in self.content_blocks.drain(..) {
+            let ContentBlock::Text {
+                text,
+                citations: None,
+            } = block
+            else {
+                blocks.push(block);
+                continue;
+            };
+
+            let block_end = offset + text.len();
+            // 跨越多个文本块的片段无法拆分，直接忽略
+            let local: Vec<GroundingSpan> = spans
+                .iter()
+                .filter(|span| span.start >= offset && span.end <= block_end)
+                .map(|span| GroundingSpan {
+                    start: span.start - offset,
+                    end: span.end - offset,
+                    sources: span.sources.clone(),
+                })
+                .collect();
+            offset = block_end;
+
+            if local.is_empty() {
+                blocks.push(ContentBlock::Text {
+                    text,
+                    citations: None,
+                });
+                continue;
+            }
+            for (piece, citations) in citations::split_cited_text(&text, &local) {
+                blocks.push(ContentBlock::Text {
+                    text: piece,
+                    citations,
+                });
+            }
+        }
+        self.content_blocks = blocks;
+    }
+
     /// 刷新 text builder
     fn flush_text(&mut self) {
         if self.text_builder.is_empty() {
@@ -444,6 +518,7 @@ impl NonStreamingProcessor {
                     if start_idx > 0 {
                         self.content_blocks.push(ContentBlock::Text {
                             text: current_text[..start_idx].to_string(),
+                            citations: None,
                         });
                     }
 
@@ -470,9 +545,21 @@ impl NonStreamingProcessor {
             break;
         }
 
-        if !current_text.is_empty() {
-            self.content_blocks
-                .push(ContentBlock::Text { text: current_text });
+        if current_text.is_empty() {
+            return;
+        }
+
+        // 文档引用: 将 <cite> 标签转换为携带 char_location / page_location 的文本块
+        match &self.document_citations {
+            Some(docs) if current_text.contains("<cite") => {
+                let segments = document_citations::parse_cite_tags(&current_text);
+                self.content_blocks
+                    .extend(document_citations::segments_to_blocks(segments, docs));
+            }
+            _ => {
+                self.content_blocks
+                    .push(ContentBlock::Text { text: current_text, citations: None });
+            }
         }
     }
 
@@ -544,8 +631,10 @@ pub fn transform_response(
     session_id: Option<String>,
     model_name: String,
     message_count: usize, // [NEW v4.0.0] Message count for rewind detection
+    document_citations: Option<Arc<DocumentCitations>>,
 ) -> Result<ClaudeResponse, String> {
     let mut processor = NonStreamingProcessor::new(session_id, model_name, message_count);
+    processor.document_citations = document_citations;
     Ok(processor.process(gemini_response, scaling_enabled, context_limit))
 }
 
@@ -577,6 +666,8 @@ mod tests {
                 candidates_token_count: Some(5),
                 total_token_count: Some(15),
                 cached_content_token_count: None,
+                thoughts_token_count: None,
+                tool_use_prompt_token_count: None,
             }),
             model_version: Some("gemini-2.5-flash".to_string()),
             response_id: Some("resp_123".to_string()),
@@ -589,6 +680,7 @@ mod tests {
             None,
             "gemini-2.5-flash".to_string(),
             1,
+            None,
         );
         assert!(result.is_ok());
 
@@ -598,7 +690,7 @@ mod tests {
         assert_eq!(claude_resp.content.len(), 1);
 
         match &claude_resp.content[0] {
-            ContentBlock::Text { text } => {
+            ContentBlock::Text { text, .. } => {
                 assert_eq!(text, "Hello, world!");
             }
             _ => panic!("Expected Text block"),
@@ -646,6 +738,7 @@ mod tests {
             None,
             "gemini-2.5-flash".to_string(),
             1,
+            None,
         );
         assert!(result.is_ok());
 
@@ -665,10 +758,99 @@ mod tests {
         }
 
         match &claude_resp.content[1] {
-            ContentBlock::Text { text } => {
+            ContentBlock::Text { text, .. } => {
                 assert_eq!(text, "The answer is 42");
             }
             _ => panic!("Expected Text block"),
         }
     }
+
+    #[test]
+    fn test_grounding_supports_become_text_citations() {
+        let gemini_resp: GeminiResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "Rust 1.0 shipped in 2015. It is fast." }] },
+                "finishReason": "STOP",
+                "groundingMetadata": {
+                    "groundingChunks": [{ "web": { "uri": "https://blog.rust-lang.org", "title": "Rust Blog" } }],
+                    "groundingSupports": [{
+                        "segment": { "startIndex": 0, "endIndex": 25, "text": "Rust 1.0 shipped in 2015." },
+                        "groundingChunkIndices": [0]
+                    }]
+                }
+            }]
+        }))
+        .unwrap();
+
+        let claude_resp = transform_response(
+            &gemini_resp,
+            false,
+            1_000_000,
+            None,
+            "gemini-2.5-flash".to_string(),
+            1,
+            None,
+        )
+        .unwrap();
+
+        match &claude_resp.content[0] {
+            ContentBlock::Text { text, citations } => {
+                assert_eq!(text, "Rust 1.0 shipped in 2015.");
+                let citation = &citations.as_ref().expect("citations")[0];
+                assert_eq!(citation["type"], "web_search_result_location");
+                assert_eq!(citation["url"], "https://blog.rust-lang.org");
+                assert_eq!(citation["cited_text"], "Rust 1.0 shipped in 2015.");
+            }
+            other => panic!("Expected cited Text block, got {:?}", other),
+        }
+        assert!(matches!(
+            &claude_resp.content[1],
+            ContentBlock::Text { text, citations: None } if text == " It is fast."
+        ));
+    }
+
+    #[test]
+    fn test_cite_tags_become_document_citations() {
+        let messages: Vec<Message> = serde_json::from_value(json!([{
+            "role": "user",
+            "content": [{
+                "type": "document",
+                "source": { "type": "text", "media_type": "text/plain", "data": "The sky is blue." },
+                "title": "Sky",
+                "citations": { "enabled": true }
+            }]
+        }]))
+        .unwrap();
+        let gemini_resp: GeminiResponse = serde_json::from_value(json!({
+            "candidates": [{
+                "content": { "role": "model", "parts": [{ "text": "Per the doc, <cite doc=\"0\">The sky is blue.</cite>" }] },
+                "finishReason": "STOP"
+            }]
+        }))
+        .unwrap();
+
+        let claude_resp = transform_response(
+            &gemini_resp,
+            false,
+            1_000_000,
+            None,
+            "gemini-2.5-flash".to_string(),
+            1,
+            DocumentCitations::from_messages(&messages),
+        )
+        .unwrap();
+
+        assert_eq!(claude_resp.content.len(), 2);
+        match &claude_resp.content[1] {
+            ContentBlock::Text { text, citations } => {
+                assert_eq!(text, "The sky is blue.");
+                let citation = &citations.as_ref().expect("citations")[0];
+                assert_eq!(citation["type"], "char_location");
+                assert_eq!(citation["document_title"], "Sky");
+                assert_eq!(citation["start_char_index"], 0);
+                assert_eq!(citation["end_char_index"], 16);
+            }
+            other => panic!("Expected cited Text block, got {:?}", other),
+        }
+    }
 }
@@ -0,0 +1,1191 @@
+// 服务端工具循环 (Server-side tool loop)
+//
+// Gemini v1internal 不允许在同一请求中混用 googleSearch 与 functionDeclarations，
+// 因此当客户端同时声明 web_search 与本地函数工具时，由代理把联网能力暴露为合成函数：
+// 模型调用合成函数 -> 代理执行独立的 grounding 子请求 -> functionResponse 回填 -> 继续生成。
+// 客户端最终看到的是标准的 server_tool_use / web_search_tool_result 块。
+
+use bytes::Bytes;
+use serde_json::{json, Value};
+
+use super::models::{ClaudeResponse, ContentBlock, Tool};
+use crate::proxy::common::client_adapter::SseErrorFormat;
+use crate::proxy::config::ServerToolsConfig;
+
+/// 合成的联网搜索函数名 (与 Anthropic server tool 同名，便于模型理解)
+pub const WEB_SEARCH_FUNCTION: &str = "web_search";
+/// 合成的网页抓取函数名
+pub const WEB_FETCH_FUNCTION: &str = "web_fetch";
+/// grounding 子请求使用的模型 (目前只有 gemini-2.5-flash 支持 googleSearch)
+pub const GROUNDING_MODEL: &str = "gemini-2.5-flash";
+
+/// 本次请求需要由代理接管的服务端工具
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ServerToolPlan {
+    pub web_search: bool,
+    pub web_fetch: bool,
+    /// 客户端工具定义中的 max_uses (单个请求内的调用次数上限)
+    pub web_search_max_uses: Option<u32>,
+    pub web_fetch_max_uses: Option<u32>,
+}
+
+impl ServerToolPlan {
+    pub fn is_active(&self) -> bool {
+        self.web_search || self.web_fetch
+    }
+
+    /// 函数名是否为本计划中的合成函数
+    pub fn handles(&self, name: &str) -> bool {
+        (self.web_search && name == WEB_SEARCH_FUNCTION)
+            || (self.web_fetch && name == WEB_FETCH_FUNCTION)
+    }
+
+    fn max_uses(&self, name: &str) -> Option<u32> {
+        match name {
+            WEB_SEARCH_FUNCTION => self.web_search_max_uses,
+            WEB_FETCH_FUNCTION => self.web_fetch_max_uses,
+            _ => None,
+        }
+    }
+}
+
+/// 单个请求内各服务端工具的已用次数
+#[derive(Debug, Default)]
+pub struct ServerToolBudget {
+    searches: u32,
+    fetches: u32,
+}
+
+impl ServerToolBudget {
+    /// 登记一次调用；超出该工具的 max_uses 时返回 false (调用以 max_uses_exceeded 拒绝)
+    pub fn try_use(&mut self, plan: &ServerToolPlan, name: &str) -> bool {
+        let used = match name {
+            WEB_SEARCH_FUNCTION => &mut self.searches,
+            WEB_FETCH_FUNCTION => &mut self.fetches,
+            _ => return false,
+        };
+        if plan.max_uses(name).is_some_and(|max| *used >= max) {
+            return false;
+        }
+        *used += 1;
+        true
+    }
+}
+
+/// 是否为 Anthropic web_fetch server tool (如 web_fetch_20250910)
+pub fn is_web_fetch_tool(tool: &Tool) -> bool {
+    tool.type_
+        .as_deref()
+        .map(|t| t.starts_with("web_fetch"))
+        .unwrap_or(false)
+}
+
+fn is_web_search_tool(tool: &Tool) -> bool {
+    tool.is_web_search() || tool.name.as_deref() == Some("google_search")
+}
+
+fn is_server_tool(tool: &Tool) -> bool {
+    is_web_search_tool(tool) || is_web_fetch_tool(tool)
+}
+
+/// 根据全局配置解析服务端工具计划
+pub fn resolve_plan(tools: &Option<Vec<Tool>>) -> ServerToolPlan {
+    resolve_plan_with(tools, &crate::proxy::get_server_tools_config())
+}
+
+/// 解析服务端工具计划
+///
+/// - web_search 仅在同时存在客户端函数工具时接管 (否则原生 googleSearch 更合适)
+/// - web_fetch 没有原生等价物，声明即接管
+pub fn resolve_plan_with(tools: &Option<Vec<Tool>>, config: &ServerToolsConfig) -> ServerToolPlan {
+    let Some(list) = tools else {
+        return ServerToolPlan::default();
+    };
+    if !config.enabled {
+        return ServerToolPlan::default();
+    }
+
+    let has_search = list.iter().any(is_web_search_tool);
+    let has_fetch = config.web_fetch && list.iter().any(is_web_fetch_tool);
+    let has_functions = list.iter().any(|t| !is_server_tool(t) && t.name.is_some());
+
+    if !has_fetch && !(has_search && has_functions) {
+        return ServerToolPlan::default();
+    }
+
+    let max_uses =
+        |matches: fn(&Tool) -> bool| list.iter().find(|t| matches(t)).and_then(|t| t.max_uses);
+    ServerToolPlan {
+        web_search: has_search,
+        web_fetch: has_fetch,
+        web_search_max_uses: max_uses(is_web_search_tool),
+        web_fetch_max_uses: max_uses(is_web_fetch_tool),
+    }
+}
+
+/// 用于模型路由 / 联网探测的工具列表
+///
+/// 服务端循环接管时剔除联网工具，避免 resolve_request_config 把主模型降级为搜索模型
+pub fn routing_tools(tools: &Option<Vec<Tool>>, plan: ServerToolPlan) -> Option<Vec<Value>> {
+    tools.as_ref().map(|list| {
+        list.iter()
+            .filter(|t| !(plan.is_active() && is_server_tool(t)))
+            .map(|t| serde_json::to_value(t).unwrap_or(json!({})))
+            .collect()
+    })
+}
+
+/// 合成函数声明
+pub fn function_declarations(plan: ServerToolPlan) -> Vec<Value> {
+    let mut declarations = Vec::new();
+    if plan.web_search {
+        declarations.push(json!({
+            "name": WEB_SEARCH_FUNCTION,
+            "description": "Search the web for up-to-date information. Returns a summary of the findings together with the source URLs.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "query": { "type": "string", "description": "The search query" }
+                },
+                "required": ["query"]
+            }
+        }));
+    }
+    if plan.web_fetch {
+        declarations.push(json!({
+            "name": WEB_FETCH_FUNCTION,
+            "description": "Fetch a web page by URL and return its main content as text.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "url": { "type": "string", "description": "The absolute URL to fetch" }
+                },
+                "required": ["url"]
+            }
+        }));
+    }
+    declarations
+}
+
+/// 模型发起的一次合成函数调用
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerToolCall {
+    pub id: Option<String>,
+    pub name: String,
+    pub args: Value,
+}
+
+impl ServerToolCall {
+    fn arg(&self, key: &str) -> Option<&str> {
+        self.args
+            .get(key)
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    }
+}
+
+/// grounding 子请求的执行结果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GroundingResult {
+    pub summary: String,
+    /// (title, url)
+    pub sources: Vec<(String, String)>,
+}
+
+/// 服务端工具执行失败的原因 (映射到 Anthropic 的 error_code)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ServerToolError {
+    InvalidInput(String),
+    MaxUsesExceeded,
+    Unavailable(String),
+}
+
+impl ServerToolError {
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            ServerToolError::InvalidInput(_) => "invalid_tool_input",
+            ServerToolError::MaxUsesExceeded => "max_uses_exceeded",
+            ServerToolError::Unavailable(_) => "unavailable",
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            ServerToolError::InvalidInput(msg) | ServerToolError::Unavailable(msg) => msg.clone(),
+            ServerToolError::MaxUsesExceeded => {
+                "Server tool call limit reached for this request".to_string()
+            }
+        }
+    }
+}
+
+/// 一次已处理的合成函数调用
+#[derive(Debug, Clone)]
+pub struct ServerToolOutcome {
+    pub call: ServerToolCall,
+    pub tool_use_id: String,
+    pub result: Result<GroundingResult, ServerToolError>,
+}
+
+impl ServerToolOutcome {
+    pub fn new(call: ServerToolCall, result: Result<GroundingResult, ServerToolError>) -> Self {
+        Self {
+            call,
+            tool_use_id: format!(
+                "srvtoolu_{}",
+                crate::proxy::common::utils::generate_random_id()
+            ),
+            result,
+        }
+    }
+}
+
+fn candidate_parts(gemini_response: &Value) -> Vec<Value> {
+    gemini_response
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("content"))
+        .and_then(|c| c.get("parts"))
+        .and_then(|p| p.as_array())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// 拆分模型本轮的函数调用: (合成函数调用, 是否还有客户端函数调用)
+pub fn split_function_calls(
+    gemini_response: &Value,
+    plan: ServerToolPlan,
+) -> (Vec<ServerToolCall>, bool) {
+    let mut server_calls = Vec::new();
+    let mut has_client_calls = false;
+
+    for part in candidate_parts(gemini_response) {
+        let Some(fc) = part.get("functionCall") else {
+            continue;
+        };
+        let name = fc.get("name").and_then(|n| n.as_str()).unwrap_or_default();
+        if plan.handles(name) {
+            server_calls.push(ServerToolCall {
+                id: fc.get("id").and_then(|v| v.as_str()).map(|s| s.to_string()),
+                name: name.to_string(),
+                args: fc.get("args").cloned().unwrap_or(json!({})),
+            });
+        } else {
+            has_client_calls = true;
+        }
+    }
+
+    (server_calls, has_client_calls)
+}
+
+/// 校验调用参数，返回子请求的提示词
+pub fn grounding_prompt(call: &ServerToolCall) -> Result<String, ServerToolError> {
+    match call.name.as_str() {
+        WEB_SEARCH_FUNCTION => call
+            .arg("query")
+            .map(|query| {
+                format!(
+                    "Search the web for: {}\n\nReport the relevant findings as concise factual notes.",
+                    query
+                )
+            })
+            .ok_or_else(|| ServerToolError::InvalidInput("Missing required parameter: query".to_string())),
+        WEB_FETCH_FUNCTION => call
+            .arg("url")
+            .map(|url| {
+                format!(
+                    "Retrieve the content of {}\n\nReproduce the main content of the page faithfully as plain text.",
+                    url
+                )
+            })
+            .ok_or_else(|| ServerToolError::InvalidInput("Missing required parameter: url".to_string())),
+        other => Err(ServerToolError::InvalidInput(format!("Unknown server tool: {}", other))),
+    }
+}
+
+/// 构建 grounding 子请求体 (web_search -> googleSearch, web_fetch -> urlContext)
+pub fn build_grounding_request(
+    call: &ServerToolCall,
+    prompt: &str,
+    project_id: &str,
+    model: &str,
+) -> Value {
+    let tool = if call.name == WEB_FETCH_FUNCTION {
+        json!({ "urlContext": {} })
+    } else {
+        json!({ "googleSearch": {} })
+    };
+
+    json!({
+        "project": project_id,
+        "requestId": format!("search-{}", uuid::Uuid::new_v4()),
+        "request": {
+            "contents": [{ "role": "user", "parts": [{ "text": prompt }] }],
+            "tools": [tool],
+            "generationConfig": { "maxOutputTokens": 8192, "temperature": 0.2 }
+        },
+        "model": model,
+        "userAgent": "antigravity",
+        "requestType": "web_search"
+    })
+}
+
+/// 解析 grounding 子请求的响应 (已解包 v1internal 的 response 字段)
+pub fn parse_grounding_response(response: &Value) -> GroundingResult {
+    let summary: String = candidate_parts(response)
+        .iter()
+        .filter(|p| !p.get("thought").and_then(|t| t.as_bool()).unwrap_or(false))
+        .filter_map(|p| p.get("text").and_then(|t| t.as_str()))
+        .collect();
+
+    let sources = response
+        .get("candidates")
+        .and_then(|c| c.get(0))
+        .and_then(|c| c.get("groundingMetadata"))
+        .and_then(|m| m.get("groundingChunks"))
+        .and_then(|c| c.as_array())
+        .map(|chunks| {
+            chunks
+                .iter()
+                .filter_map(|chunk| chunk.get("web"))
+                .filter_map(|web| {
+                    let url = web
+                        .get("uri")
+                        .and_then(|u| u.as_str())
+                        .filter(|u| !u.is_empty())?;
+                    let title = web
+                        .get("title")
+                        .and_then(|t| t.as_str())
+                        .unwrap_or("Source");
+                    Some((title.to_string(), url.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    GroundingResult {
+        summary: summary.trim().to_string(),
+        sources,
+    }
+}
+
+/// 回填给模型的 functionResponse part
+pub fn function_response_part(outcome: &ServerToolOutcome) -> Value {
+    let response = match &outcome.result {
+        Ok(result) => json!({
+            "result": {
+                "content": result.summary,
+                "sources": result
+                    .sources
+                    .iter()
+                    .map(|(title, url)| json!({ "title": title, "url": url }))
+                    .collect::<Vec<_>>()
+            }
+        }),
+        Err(e) => json!({ "error": e.message() }),
+    };
+
+    let mut function_response = json!({
+        "name": outcome.call.name,
+        "response": response
+    });
+    if let Some(id) = &outcome.call.id {
+        function_response["id"] = json!(id);
+    }
+    json!({ "functionResponse": function_response })
+}
+
+/// 客户端可见的 server_tool_use + 结果块
+pub fn client_blocks(outcome: &ServerToolOutcome) -> [ContentBlock; 2] {
+    let tool_use = ContentBlock::ServerToolUse {
+        id: outcome.tool_use_id.clone(),
+        name: outcome.call.name.clone(),
+        input: outcome.call.args.clone(),
+    };
+    let tool_use_id = outcome.tool_use_id.clone();
+
+    let result = if outcome.call.name == WEB_FETCH_FUNCTION {
+        let content = match &outcome.result {
+            Ok(result) => json!({
+                "type": "web_fetch_result",
+                "url": outcome.call.arg("url").unwrap_or_default(),
+                "content": {
+                    "type": "document",
+                    "source": {
+                        "type": "text",
+                        "media_type": "text/plain",
+                        "data": result.summary
+                    },
+                    "title": result.sources.first().map(|(title, _)| title.as_str())
+                },
+                "retrieved_at": chrono::Utc::now().to_rfc3339()
+            }),
+            Err(e) => json!({ "type": "web_fetch_tool_error", "error_code": e.error_code() }),
+        };
+        ContentBlock::WebFetchToolResult {
+            tool_use_id,
+            content,
+        }
+    } else {
+        let content = match &outcome.result {
+            Ok(result) => json!(result
+                .sources
+                .iter()
+                .map(|(title, url)| json!({
+                    "type": "web_search_result",
+                    "url": url,
+                    "title": title,
+                    "encrypted_content": "", // Gemini doesn't provide this
+                    "page_age": null
+                }))
+                .collect::<Vec<_>>()),
+            Err(e) => {
+                json!({ "type": "web_search_tool_result_error", "error_code": e.error_code() })
+            }
+        };
+        ContentBlock::WebSearchToolResult {
+            tool_use_id,
+            content,
+        }
+    };
+
+    [tool_use, result]
+}
+
+/// 把转换后内容中的合成函数 tool_use 按顺序替换为 server_tool_use + 结果块
+pub fn replace_server_calls(
+    content: Vec<ContentBlock>,
+    plan: ServerToolPlan,
+    outcomes: &[ServerToolOutcome],
+) -> Vec<ContentBlock> {
+    let mut pending = outcomes.iter();
+    let mut blocks = Vec::with_capacity(content.len() + outcomes.len());
+
+    for block in content {
+        match &block {
+            ContentBlock::ToolUse { name, .. } if plan.handles(name) => {
+                if let Some(outcome) = pending.next() {
+                    blocks.extend(client_blocks(outcome));
+                }
+            }
+            _ => blocks.push(block),
+        }
+    }
+    blocks
+}
+
+/// 追加一轮 model(functionCall) / user(functionResponse) 到 v1internal 请求体
+pub fn append_turn(body: &mut Value, gemini_response: &Value, outcomes: &[ServerToolOutcome]) {
+    let model_parts = candidate_parts(gemini_response);
+    let response_parts: Vec<Value> = outcomes.iter().map(function_response_part).collect();
+
+    if let Some(contents) = body
+        .get_mut("request")
+        .and_then(|r| r.get_mut("contents"))
+        .and_then(|c| c.as_array_mut())
+    {
+        contents.push(json!({ "role": "model", "parts": model_parts }));
+        contents.push(json!({ "role": "user", "parts": response_parts }));
+    }
+}
+
+/// 组装最终响应: 合并各轮内容块、累计输出 Token 与服务端工具调用次数
+pub fn finish_response(
+    mut last: ClaudeResponse,
+    content: Vec<ContentBlock>,
+    earlier_output_tokens: u32,
+    outcomes: &[ServerToolOutcome],
+) -> ClaudeResponse {
+    let has_client_tool_use = content
+        .iter()
+        .any(|b| matches!(b, ContentBlock::ToolUse { .. }));
+    if has_client_tool_use {
+        last.stop_reason = "tool_use".to_string();
+    } else if last.stop_reason == "tool_use" {
+        last.stop_reason = "end_turn".to_string();
+    }
+
+    if let Some(usage) = server_tool_usage(outcomes) {
+        last.usage.server_tool_use = Some(usage);
+    }
+
+    last.usage.output_tokens += earlier_output_tokens;
+    last.content = content;
+    last
+}
+
+/// usage.server_tool_use: 统计实际执行的服务端工具调用次数
+fn server_tool_usage(outcomes: &[ServerToolOutcome]) -> Option<Value> {
+    let count = |name: &str| {
+        outcomes
+            .iter()
+            .filter(|o| o.call.name == name)
+            .filter(|o| !matches!(o.result, Err(ServerToolError::MaxUsesExceeded)))
+            .count()
+    };
+    let (searches, fetches) = (count(WEB_SEARCH_FUNCTION), count(WEB_FETCH_FUNCTION));
+    if searches + fetches == 0 {
+        return None;
+    }
+    let mut usage = json!({ "web_search_requests": searches });
+    if fetches > 0 {
+        usage["web_fetch_requests"] = json!(fetches);
+    }
+    Some(usage)
+}
+
+fn sse(event: &str, data: Value) -> Bytes {
+    Bytes::from(format!("event: {}\ndata: {}\n\n", event, data))
+}
+
+/// 流式服务端工具循环中途失败时下发的 error 事件 (格式由客户端适配器决定)
+pub fn error_event(format: SseErrorFormat, message: &str) -> Bytes {
+    Bytes::from(format.render("api_error", message))
+}
+
+/// 单个内容块的 content_block_start / delta / stop 事件
+fn block_events(index: usize, block: &ContentBlock) -> Vec<Bytes> {
+    let (start, deltas) = match block {
+        ContentBlock::Text { text, citations } => {
+            let mut deltas: Vec<Value> = citations
+                .iter()
+                .flatten()
+                .map(|c| json!({ "type": "citations_delta", "citation": c }))
+                .collect();
+            deltas.push(json!({ "type": "text_delta", "text": text }));
+            (json!({ "type": "text", "text": "" }), deltas)
+        }
+        ContentBlock::Thinking {
+            thinking,
+            signature,
+            ..
+        } => {
+            let mut deltas = vec![json!({ "type": "thinking_delta", "thinking": thinking })];
+            if let Some(sig) = signature {
+                deltas.push(json!({ "type": "signature_delta", "signature": sig }));
+            }
+            (json!({ "type": "thinking", "thinking": "" }), deltas)
+        }
+        ContentBlock::ToolUse {
+            id, name, input, ..
+        } => (
+            json!({ "type": "tool_use", "id": id, "name": name, "input": {} }),
+            vec![json!({ "type": "input_json_delta", "partial_json": input.to_string() })],
+        ),
+        other => (serde_json::to_value(other).unwrap_or_default(), Vec::new()),
+    };
+
+    let mut chunks = vec![sse(
+        "content_block_start",
+        json!({ "type": "content_block_start", "index": index, "content_block": start }),
+    )];
+    for delta in deltas {
+        chunks.push(sse(
+            "content_block_delta",
+            json!({ "type": "content_block_delta", "index": index, "delta": delta }),
+        ));
+    }
+    chunks.push(sse(
+        "content_block_stop",
+        json!({ "type": "content_block_stop", "index": index }),
+    ));
+    chunks
+}
+
+/// 流式服务端工具循环: 把多轮上游生成拼接为客户端看到的一条 SSE 消息
+///
+/// 每轮的 Claude SSE 输出经此处理: 只保留首轮的 message_start，内容块按全局序号重新编号，
+/// 合成函数的 tool_use 块不下发 (由 server_tool_use + 结果块代替)，
+/// message_delta / message_stop 暂存到确定本轮是否为最后一轮之后再发出
+#[derive(Debug, Default)]
+pub struct SseSplicer {
+    plan: ServerToolPlan,
+    buffer: String,
+    message_started: bool,
+    next_index: usize,
+    /// 本轮内容块序号 -> 全局序号 (None 表示被隐藏的合成函数调用)
+    index_map: std::collections::HashMap<u64, Option<usize>>,
+    held_delta: Option<Value>,
+    /// 本轮收到 error 事件 (上游中断)，消息不再继续
+    failed: bool,
+}
+
+impl SseSplicer {
+    pub fn new(plan: ServerToolPlan) -> Self {
+        Self {
+            plan,
+            ..Default::default()
+        }
+    }
+
+    /// 处理本轮 Claude SSE 输出的一个数据块，返回可立即下发的事件
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Bytes> {
+        self.buffer.push_str(&String::from_utf8_lossy(chunk));
+        let mut out = Vec::new();
+        while let Some(pos) = self.buffer.find("\n\n") {
+            let raw: String = self.buffer.drain(..pos + 2).collect();
+            out.extend(self.splice_event(&raw));
+        }
+        out
+    }
+
+    fn splice_event(&mut self, raw: &str) -> Option<Bytes> {
+        let data = raw
+            .lines()
+            .find_map(|l| l.strip_prefix("data:"))
+            .and_then(|d| serde_json::from_str::<Value>(d.trim()).ok());
+        let Some(mut data) = data else {
+            // 心跳注释等非 JSON 事件原样透传
+            return Some(Bytes::from(raw.to_string()));
+        };
+        let event_type = data
+            .get("type")
+            .and_then(|t| t.as_str())
+            .unwrap_or_default()
+            .to_string();
+
+        match event_type.as_str() {
+            "message_start" => {
+                if std::mem::replace(&mut self.message_started, true) {
+                    return None;
+                }
+            }
+            "message_delta" => {
+                self.held_delta = Some(data);
+                return None;
+            }
+            "message_stop" => return None,
+            // OpenAI / Gemini 格式的错误帧没有 type 字段，只有顶层 error 对象
+            "error" | "" if data.get("error").is_some() => self.failed = true,
+            "content_block_start" | "content_block_delta" | "content_block_stop" => {
+                let local = data.get("index").and_then(|i| i.as_u64()).unwrap_or(0);
+                let global = if event_type == "content_block_start" {
+                    let block = &data["content_block"];
+                    let hidden = block.get("type").and_then(|t| t.as_str()) == Some("tool_use")
+                        && block
+                            .get("name")
+                            .and_then(|n| n.as_str())
+                            .is_some_and(|n| self.plan.handles(n));
+                    let global = if hidden {
+                        None
+                    } else {
+                        self.next_index += 1;
+                        Some(self.next_index - 1)
+                    };
+                    self.index_map.insert(local, global);
+                    global
+                } else {
+                    self.index_map.get(&local).copied().flatten()
+                };
+                data["index"] = json!(global?);
+                return Some(sse(&event_type, data));
+            }
+            _ => {}
+        }
+        Some(Bytes::from(raw.to_string()))
+    }
+
+    /// 是否已下发 error 事件 (此后不应再发出 message_delta / message_stop)
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+
+    /// 结束一轮生成 (后续还有轮次)，返回本轮的输出 Token 数
+    pub fn end_turn(&mut self) -> u32 {
+        self.index_map.clear();
+        self.held_delta
+            .take()
+            .and_then(|d| d.pointer("/usage/output_tokens").and_then(|v| v.as_u64()))
+            .unwrap_or(0) as u32
+    }
+
+    /// 在当前位置插入完整的内容块 (server_tool_use + 结果块)
+    pub fn emit_blocks(&mut self, blocks: &[ContentBlock]) -> Vec<Bytes> {
+        let mut out = Vec::new();
+        for block in blocks {
+            out.extend(block_events(self.next_index, block));
+            self.next_index += 1;
+        }
+        out
+    }
+
+    /// 结束整条消息: 发出合并后的 message_delta 与 message_stop
+    ///
+    /// stop_reason 为 None 时沿用最后一轮的停止原因；输出 Token 累加此前各轮
+    pub fn finish(
+        &mut self,
+        stop_reason: Option<&str>,
+        earlier_output_tokens: u32,
+        outcomes: &[ServerToolOutcome],
+    ) -> Vec<Bytes> {
+        let mut delta = self.held_delta.take().unwrap_or_else(|| {
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": "end_turn", "stop_sequence": null },
+                "usage": { "output_tokens": 0 }
+            })
+        });
+        if let Some(reason) = stop_reason {
+            delta["delta"]["stop_reason"] = json!(reason);
+        }
+        let output_tokens = delta
+            .pointer("/usage/output_tokens")
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        delta["usage"]["output_tokens"] = json!(output_tokens + earlier_output_tokens as u64);
+        if let Some(usage) = server_tool_usage(outcomes) {
+            delta["usage"]["server_tool_use"] = usage;
+        }
+
+        let mut out = Vec::new();
+        if !self.message_started {
+            // 所有轮次都没有产生输出 (极少见)，补发 message_start 以保持事件序列完整
+            self.message_started = true;
+            out.push(sse(
+                "message_start",
+                json!({
+                    "type": "message_start",
+                    "message": {
+                        "id": format!("msg_{}", crate::proxy::common::utils::generate_random_id()),
+                        "type": "message",
+                        "role": "assistant",
+                        "content": [],
+                        "model": "",
+                        "stop_reason": null,
+                        "stop_sequence": null,
+                        "usage": { "input_tokens": 0, "output_tokens": 0 }
+                    }
+                }),
+            ));
+        }
+        out.push(sse("message_delta", delta));
+        out.push(sse("message_stop", json!({ "type": "message_stop" })));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::mappers::claude::models::Usage;
+
+    fn tools(value: Value) -> Option<Vec<Tool>> {
+        Some(serde_json::from_value(value).unwrap())
+    }
+
+    fn search_and_function_tools() -> Option<Vec<Tool>> {
+        tools(json!([
+            { "type": "web_search_20250305", "name": "web_search" },
+            { "name": "Read", "description": "Read a file", "input_schema": { "type": "object" } }
+        ]))
+    }
+
+    fn response(content: Vec<ContentBlock>, stop_reason: &str) -> ClaudeResponse {
+        ClaudeResponse {
+            id: "msg_1".to_string(),
+            type_: "message".to_string(),
+            role: "assistant".to_string(),
+            model: "gemini-3-pro-high".to_string(),
+            content,
+            stop_reason: stop_reason.to_string(),
+            stop_sequence: None,
+            usage: Usage {
+                input_tokens: 100,
+                output_tokens: 20,
+                cache_read_input_tokens: None,
+                cache_creation_input_tokens: None,
+                server_tool_use: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_resolve_plan() {
+        let config = ServerToolsConfig::default();
+
+        // 仅联网工具: 保持原生 googleSearch
+        let only_search = tools(json!([{ "type": "web_search_20250305", "name": "web_search" }]));
+        assert!(!resolve_plan_with(&only_search, &config).is_active());
+
+        // 联网 + 函数工具: 由代理接管
+        let plan = resolve_plan_with(&search_and_function_tools(), &config);
+        assert_eq!(
+            plan,
+            ServerToolPlan {
+                web_search: true,
+                ..Default::default()
+            }
+        );
+
+        // max_uses 取自对应的工具定义
+        let limited = tools(json!([
+            { "type": "web_search_20250305", "name": "web_search", "max_uses": 2 },
+            { "type": "web_fetch_20250910", "name": "web_fetch" },
+            { "name": "Read", "input_schema": { "type": "object" } }
+        ]));
+        let plan = resolve_plan_with(&limited, &config);
+        assert_eq!(plan.web_search_max_uses, Some(2));
+        assert_eq!(plan.web_fetch_max_uses, None);
+
+        // web_fetch 声明即接管，可单独关闭
+        let fetch = tools(json!([{ "type": "web_fetch_20250910", "name": "web_fetch" }]));
+        assert!(resolve_plan_with(&fetch, &config).web_fetch);
+        let no_fetch = ServerToolsConfig {
+            web_fetch: false,
+            ..Default::default()
+        };
+        assert!(!resolve_plan_with(&fetch, &no_fetch).is_active());
+
+        let disabled = ServerToolsConfig {
+            enabled: false,
+            ..Default::default()
+        };
+        assert!(!resolve_plan_with(&search_and_function_tools(), &disabled).is_active());
+    }
+
+    #[test]
+    fn test_routing_tools_strip_server_tools_only_when_active() {
+        let tools = search_and_function_tools();
+        let active = routing_tools(
+            &tools,
+            ServerToolPlan {
+                web_search: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0]["name"], "Read");
+
+        let inactive = routing_tools(&tools, ServerToolPlan::default()).unwrap();
+        assert_eq!(inactive.len(), 2);
+    }
+
+    #[test]
+    fn test_split_function_calls() {
+        let plan = ServerToolPlan {
+            web_search: true,
+            ..Default::default()
+        };
+        let gemini = json!({
+            "candidates": [{ "content": { "role": "model", "parts": [
+                { "text": "Let me look that up." },
+                { "functionCall": { "name": "web_search", "args": { "query": "rust 2024 edition" }, "id": "call_1" } }
+            ]}}]
+        });
+        let (calls, has_client) = split_function_calls(&gemini, plan);
+        assert!(!has_client);
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].id.as_deref(), Some("call_1"));
+        assert!(grounding_prompt(&calls[0])
+            .unwrap()
+            .contains("rust 2024 edition"));
+
+        let mixed = json!({
+            "candidates": [{ "content": { "parts": [
+                { "functionCall": { "name": "web_search", "args": {} } },
+                { "functionCall": { "name": "Read", "args": { "path": "a.rs" } } }
+            ]}}]
+        });
+        let (calls, has_client) = split_function_calls(&mixed, plan);
+        assert!(has_client);
+        assert_eq!(
+            grounding_prompt(&calls[0]).unwrap_err().error_code(),
+            "invalid_tool_input"
+        );
+    }
+
+    #[test]
+    fn test_parse_grounding_response() {
+        let raw = json!({
+            "candidates": [{
+                "content": { "parts": [
+                    { "text": "thinking...", "thought": true },
+                    { "text": "Rust 2024 shipped with 1.85." }
+                ]},
+                "groundingMetadata": { "groundingChunks": [
+                    { "web": { "uri": "https://blog.rust-lang.org", "title": "Rust Blog" } },
+                    { "web": { "uri": "" } }
+                ]}
+            }]
+        });
+        let result = parse_grounding_response(&raw);
+        assert_eq!(result.summary, "Rust 2024 shipped with 1.85.");
+        assert_eq!(
+            result.sources,
+            vec![(
+                "Rust Blog".to_string(),
+                "https://blog.rust-lang.org".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_append_turn_and_function_response() {
+        let call = ServerToolCall {
+            id: Some("call_1".to_string()),
+            name: WEB_SEARCH_FUNCTION.to_string(),
+            args: json!({ "query": "q" }),
+        };
+        let outcome = ServerToolOutcome::new(
+            call,
+            Ok(GroundingResult {
+                summary: "answer".to_string(),
+                sources: vec![("T".to_string(), "https://t.example".to_string())],
+            }),
+        );
+        let gemini = json!({
+            "candidates": [{ "content": { "parts": [
+                { "functionCall": { "name": "web_search", "args": { "query": "q" }, "id": "call_1" }, "thoughtSignature": "sig" }
+            ]}}]
+        });
+        let mut body =
+            json!({ "request": { "contents": [{ "role": "user", "parts": [{ "text": "hi" }] }] } });
+
+        append_turn(&mut body, &gemini, std::slice::from_ref(&outcome));
+
+        let contents = body["request"]["contents"].as_array().unwrap();
+        assert_eq!(contents.len(), 3);
+        assert_eq!(contents[1]["role"], "model");
+        assert_eq!(contents[1]["parts"][0]["thoughtSignature"], "sig");
+        let fr = &contents[2]["parts"][0]["functionResponse"];
+        assert_eq!(fr["id"], "call_1");
+        assert_eq!(fr["response"]["result"]["content"], "answer");
+        assert_eq!(
+            fr["response"]["result"]["sources"][0]["url"],
+            "https://t.example"
+        );
+    }
+
+    #[test]
+    fn test_replace_server_calls_and_finish() {
+        let plan = ServerToolPlan {
+            web_search: true,
+            ..Default::default()
+        };
+        let outcomes = vec![ServerToolOutcome::new(
+            ServerToolCall {
+                id: None,
+                name: "web_search".to_string(),
+                args: json!({ "query": "q" }),
+            },
+            Ok(GroundingResult {
+                summary: "s".to_string(),
+                sources: vec![("T".to_string(), "https://t.example".to_string())],
+            }),
+        )];
+        let first = vec![
+            ContentBlock::Text {
+                text: "Searching".to_string(),
+                citations: None,
+            },
+            ContentBlock::ToolUse {
+                id: "web_search-1".to_string(),
+                name: "web_search".to_string(),
+                input: json!({ "query": "q" }),
+                signature: None,
+                cache_control: None,
+            },
+        ];
+        let mut blocks = replace_server_calls(first, plan, &outcomes);
+        assert_eq!(blocks.len(), 3);
+        match (&blocks[1], &blocks[2]) {
+            (
+                ContentBlock::ServerToolUse { id, name, .. },
+                ContentBlock::WebSearchToolResult {
+                    tool_use_id,
+                    content,
+                },
+            ) => {
+                assert_eq!(name, "web_search");
+                assert_eq!(id, tool_use_id);
+                assert_eq!(content[0]["type"], "web_search_result");
+                assert_eq!(content[0]["url"], "https://t.example");
+            }
+            other => panic!("unexpected blocks: {:?}", other),
+        }
+
+        blocks.push(ContentBlock::Text {
+            text: "Done".to_string(),
+            citations: None,
+        });
+        let last = response(
+            vec![ContentBlock::Text {
+                text: "Done".to_string(),
+                citations: None,
+            }],
+            "end_turn",
+        );
+        let finished = finish_response(last, blocks, 15, &outcomes);
+        assert_eq!(finished.content.len(), 4);
+        assert_eq!(finished.stop_reason, "end_turn");
+        assert_eq!(finished.usage.output_tokens, 35);
+        assert_eq!(
+            finished.usage.server_tool_use,
+            Some(json!({ "web_search_requests": 1 }))
+        );
+    }
+
+    #[test]
+    fn test_max_uses_error_block() {
+        let outcome = ServerToolOutcome::new(
+            ServerToolCall {
+                id: None,
+                name: "web_fetch".to_string(),
+                args: json!({ "url": "https://a.example" }),
+            },
+            Err(ServerToolError::MaxUsesExceeded),
+        );
+        match &client_blocks(&outcome)[1] {
+            ContentBlock::WebFetchToolResult { content, .. } => {
+                assert_eq!(content["type"], "web_fetch_tool_error");
+                assert_eq!(content["error_code"], "max_uses_exceeded");
+            }
+            other => panic!("unexpected block: {:?}", other),
+        }
+        assert_eq!(
+            function_response_part(&outcome)["functionResponse"]["response"]["error"],
+            "Server tool call limit reached for this request"
+        );
+    }
+
+    #[test]
+    fn test_budget_honors_max_uses() {
+        let plan = ServerToolPlan {
+            web_search: true,
+            web_fetch: true,
+            web_search_max_uses: Some(1),
+            web_fetch_max_uses: None,
+        };
+        let mut budget = ServerToolBudget::default();
+        assert!(budget.try_use(&plan, "web_search"));
+        assert!(!budget.try_use(&plan, "web_search"));
+        // 未声明 max_uses 的工具不受限
+        for _ in 0..5 {
+            assert!(budget.try_use(&plan, "web_fetch"));
+        }
+        assert!(!budget.try_use(&plan, "Read"));
+    }
+
+    fn events(chunks: &[Bytes]) -> Vec<Value> {
+        chunks
+            .iter()
+            .filter_map(|b| {
+                let text = String::from_utf8_lossy(b).to_string();
+                let data = text.lines().find_map(|l| l.strip_prefix("data: "))?;
+                serde_json::from_str(data).ok()
+            })
+            .collect()
+    }
+
+    fn turn(blocks: &[Value], stop_reason: &str, output_tokens: u32) -> Vec<u8> {
+        let mut out = String::new();
+        let mut push = |event: &str, data: Value| {
+            out.push_str(&format!("event: {}\ndata: {}\n\n", event, data));
+        };
+        push(
+            "message_start",
+            json!({ "type": "message_start", "message": { "id": "msg_1", "usage": { "input_tokens": 10 } } }),
+        );
+        for (i, block) in blocks.iter().enumerate() {
+            push(
+                "content_block_start",
+                json!({ "type": "content_block_start", "index": i, "content_block": block }),
+            );
+            push(
+                "content_block_stop",
+                json!({ "type": "content_block_stop", "index": i }),
+            );
+        }
+        push(
+            "message_delta",
+            json!({
+                "type": "message_delta",
+                "delta": { "stop_reason": stop_reason },
+                "usage": { "output_tokens": output_tokens }
+            }),
+        );
+        push("message_stop", json!({ "type": "message_stop" }));
+        out.into_bytes()
+    }
+
+    #[test]
+    fn test_sse_splicer_merges_turns() {
+        let plan = ServerToolPlan {
+            web_search: true,
+            ..Default::default()
+        };
+        let mut splicer = SseSplicer::new(plan);
+
+        // 第一轮: 文本 + 合成 web_search 调用 (分两段到达)
+        let first = turn(
+            &[
+                json!({ "type": "text", "text": "" }),
+                json!({ "type": "tool_use", "id": "t1", "name": "web_search", "input": {} }),
+            ],
+            "tool_use",
+            7,
+        );
+        let (a, b) = first.split_at(first.len() / 2);
+        let mut out = splicer.push(a);
+        out.extend(splicer.push(b));
+        let first_events = events(&out);
+        assert_eq!(first_events[0]["type"], "message_start");
+        assert_eq!(first_events.len(), 3);
+        assert!(first_events[1..].iter().all(|e| e["index"] == 0));
+
+        let outcome = ServerToolOutcome::new(
+            ServerToolCall {
+                id: Some("t1".to_string()),
+                name: "web_search".to_string(),
+                args: json!({ "query": "q" }),
+            },
+            Ok(GroundingResult::default()),
+        );
+        let inserted = events(&splicer.emit_blocks(&client_blocks(&outcome)));
+        assert_eq!(inserted[0]["content_block"]["type"], "server_tool_use");
+        assert_eq!(inserted[0]["index"], 1);
+        assert_eq!(inserted.last().unwrap()["index"], 2);
+        assert_eq!(splicer.end_turn(), 7);
+
+        // 第二轮: 最终回答，message_start 不再重复
+        let second = turn(&[json!({ "type": "text", "text": "" })], "end_turn", 5);
+        let second_events = events(&splicer.push(&second));
+        assert_eq!(second_events.len(), 2);
+        assert!(second_events.iter().all(|e| e["index"] == 3));
+
+        let tail = events(&splicer.finish(None, 7, &[outcome]));
+        assert_eq!(tail[0]["delta"]["stop_reason"], "end_turn");
+        assert_eq!(tail[0]["usage"]["output_tokens"], 12);
+        assert_eq!(
+            tail[0]["usage"]["server_tool_use"]["web_search_requests"],
+            1
+        );
+        assert_eq!(tail[1]["type"], "message_stop");
+    }
+
+    #[test]
+    fn test_sse_splicer_finish_without_output() {
+        let mut splicer = SseSplicer::new(ServerToolPlan::default());
+        let tail = events(&splicer.finish(Some("tool_use"), 0, &[]));
+        assert_eq!(tail[0]["type"], "message_start");
+        assert_eq!(tail[1]["delta"]["stop_reason"], "tool_use");
+        assert_eq!(tail[2]["type"], "message_stop");
+    }
+
+    #[test]
+    fn test_sse_splicer_passes_error_through() {
+        let mut splicer = SseSplicer::new(ServerToolPlan::default());
+        let out = events(&splicer.push(
+            b"event: error\ndata: {\"type\":\"error\",\"error\":{\"type\":\"api_error\",\"message\":\"x\"}}\n\n",
+        ));
+        assert_eq!(out[0]["error"]["type"], "api_error");
+        assert!(splicer.failed());
+
+        // 适配器声明 OpenAI 格式时错误帧只有 data 行
+        let mut splicer = SseSplicer::new(ServerToolPlan::default());
+        splicer.push(&error_event(SseErrorFormat::OpenAI, "x"));
+        assert!(splicer.failed());
+    }
+}
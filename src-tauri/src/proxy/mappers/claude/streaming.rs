@@ -1,8 +1,10 @@
 // Claude 流式响应转换 (Gemini SSE → Claude SSE)
 // 对应 StreamingState + PartProcessor
 
+use super::document_citations::{CiteSegment, CiteTagParser, DocumentCitations};
 use super::models::*;
 use super::utils::to_claude_usage;
+use crate::proxy::mappers::citations;
 use crate::proxy::mappers::estimation_calibrator::get_calibrator;
 // use crate::proxy::mappers::signature_store::store_thought_signature; // Deprecated
 use crate::proxy::SignatureCache;
@@ -209,6 +211,14 @@ pub struct StreamingState {
     trailing_signature: Option<String>,
     pub web_search_query: Option<String>,
     pub grounding_chunks: Option<Vec<serde_json::Value>>,
+    pub grounding_supports: Option<Vec<serde_json::Value>>,
+    // 已输出的正文, 用于定位 groundingSupports 引用片段
+    pub streamed_text: String,
+    // 当前 text 块在 streamed_text 中的起始偏移 (之前的正文属于更早的块)
+    text_block_start: usize,
+    // 启用 citations 的文档及 <cite> 标签解析器
+    pub document_citations: Option<std::sync::Arc<DocumentCitations>>,
+    pub cite_parser: CiteTagParser,
     // [IMPROVED] Error recovery 状态追踪 (prepared for future use)
     #[allow(dead_code)]
     parse_error_count: usize,
@@ -246,6 +256,11 @@ impl StreamingState {
             trailing_signature: None,
             web_search_query: None,
             grounding_chunks: None,
+            grounding_supports: None,
+            streamed_text: String::new(),
+            text_block_start: 0,
+            document_citations: None,
+            cite_parser: CiteTagParser::new(),
             // [IMPROVED] 初始化 error recovery 字段
             parse_error_count: 0,
             last_valid_state: None,
@@ -278,6 +293,18 @@ impl StreamingState {
         Bytes::from(sse)
     }
 
+    /// 发送流内 error 事件 (上游中断等)；与 Anthropic 一致，之后不再发送 message_delta / message_stop
+    ///
+    /// 错误帧结构由客户端适配器决定，未匹配适配器时使用 Anthropic 标准格式
+    pub fn emit_error(&self, error_type: &str, message: &str) -> Bytes {
+        let format = self
+            .client_adapter
+            .as_ref()
+            .map(|a| a.sse_error_format())
+            .unwrap_or_default();
+        Bytes::from(format.render(error_type, message))
+    }
+
     /// 发送 message_start 事件
     pub fn emit_message_start(&mut self, raw_json: &serde_json::Value) -> Bytes {
         if self.message_start_sent {
@@ -344,6 +371,9 @@ impl StreamingState {
             }),
         ));
 
+        if block_type != BlockType::Text {
+            self.text_block_start = self.streamed_text.len();
+        }
         self.block_type = block_type;
         chunks
     }
@@ -396,6 +426,48 @@ impl StreamingState {
         )
     }
 
+    /// 发送 <cite> 解析结果: 引用片段独立成块, 先发 citations_delta 再发 text_delta
+    pub fn emit_cite_segments(&mut self, segments: Vec<CiteSegment>) -> Vec<Bytes> {
+        let mut chunks = Vec::new();
+
+        for segment in segments {
+            let (text, citation) = match segment {
+                CiteSegment::Text(text) => (text, None),
+                CiteSegment::Cited { doc, page, text } => {
+                    let citation = self
+                        .document_citations
+                        .as_ref()
+                        .and_then(|docs| docs.citation_for(doc, page, &text));
+                    (text, citation)
+                }
+            };
+            if text.is_empty() {
+                continue;
+            }
+
+            match citation {
+                Some(citation) => {
+                    chunks.extend(
+                        self.start_block(BlockType::Text, json!({ "type": "text", "text": "" })),
+                    );
+                    chunks.push(self.emit_delta("citations_delta", json!({ "citation": citation })));
+                    chunks.push(self.emit_delta("text_delta", json!({ "text": text })));
+                    chunks.extend(self.end_block());
+                }
+                None => {
+                    if self.block_type != BlockType::Text {
+                        chunks.extend(
+                            self.start_block(BlockType::Text, json!({ "type": "text", "text": "" })),
+                        );
+                    }
+                    chunks.push(self.emit_delta("text_delta", json!({ "text": text })));
+                }
+            }
+        }
+
+        chunks
+    }
+
     /// 发送结束事件
     pub fn emit_finish(
         &mut self,
@@ -404,6 +476,27 @@ impl StreamingState {
     ) -> Vec<Bytes> {
         let mut chunks = Vec::new();
 
+        // 放行尚未闭合的 <cite> 缓冲
+        let pending = self.cite_parser.finish();
+        chunks.extend(self.emit_cite_segments(pending));
+
+        // groundingSupports -> web_search_result_location, 挂在仍未关闭的正文块上
+        if self.block_type == BlockType::Text {
+            if let Some(supports) = self.grounding_supports.as_deref() {
+                let spans: Vec<_> = citations::grounding_spans(
+                    &self.streamed_text,
+                    self.grounding_chunks.as_deref().unwrap_or(&[]),
+                    supports,
+                )
+                .into_iter()
+                .filter(|span| span.start >= self.text_block_start)
+                .collect();
+                for citation in citations::span_citations(&self.streamed_text, &spans) {
+                    chunks.push(self.emit_delta("citations_delta", json!({ "citation": citation })));
+                }
+            }
+        }
+
         // 关闭最后一个块
         chunks.extend(self.end_block());
 
@@ -601,17 +694,10 @@ impl StreamingState {
             );
 
             // [FIX] Explicitly signal error to client to prevent UI freeze
-            // using standard SSE error event format
-            // data: {"type": "error", "error": {...}}
-            chunks.push(self.emit(
-                "error",
-                json!({
-                    "type": "error",
-                    "error": {
-                        "type": "overloaded_error", // Use standard type
-                        "message": "网络连接不稳定，请检查您的网络或代理设置。",
-                    }
-                }),
+            // 按客户端适配器声明的 SSE 错误格式发送
+            chunks.push(self.emit_error(
+                "overloaded_error",
+                "网络连接不稳定，请检查您的网络或代理设置。",
             ));
         }
 
@@ -830,6 +916,7 @@ impl<'a> PartProcessor<'a> {
 
         // [FIX #859] Mark that we have received actual content (text)
         self.state.has_content = true;
+        self.state.streamed_text.push_str(text);
 
         // 处理之前的 trailingSignature
         if self.state.has_trailing_signature() {
@@ -943,6 +1030,13 @@ impl<'a> PartProcessor<'a> {
             return vec![];
         }
 
+        // 文档引用: 解析 <cite> 标签并转换为 citations_delta
+        if self.state.document_citations.is_some() {
+            let segments = self.state.cite_parser.push(text);
+            chunks.extend(self.state.emit_cite_segments(segments));
+            return chunks;
+        }
+
         if self.state.current_block_type() != BlockType::Text {
             chunks.extend(
                 self.state
@@ -1067,6 +1161,26 @@ mod tests {
         assert!(s.contains("\"foo\":\"bar\""));
     }
 
+    #[test]
+    fn test_emit_error_follows_client_adapter() {
+        use crate::proxy::common::client_adapters::{ClaudeCodeAdapter, CursorAdapter};
+
+        let mut state = StreamingState::new();
+        let text = |b: Bytes| String::from_utf8(b.to_vec()).unwrap();
+        assert!(text(state.emit_error("api_error", "boom")).starts_with("event: error\n"));
+
+        state.set_client_adapter(Some(std::sync::Arc::new(ClaudeCodeAdapter)));
+        assert!(text(state.emit_error("api_error", "boom")).starts_with("event: error\n"));
+
+        // Cursor 只解析 data 行，错误帧不带 event 行且为 OpenAI 结构
+        state.set_client_adapter(Some(std::sync::Arc::new(CursorAdapter)));
+        let frame = text(state.emit_error("api_error", "boom"));
+        assert!(frame.starts_with("data: "));
+        let data: Value = serde_json::from_str(frame.trim_start_matches("data: ").trim()).unwrap();
+        assert_eq!(data["error"]["message"], "boom");
+        assert!(data.get("type").is_none());
+    }
+
     #[test]
     fn test_process_function_call_deltas() {
         let mut state = StreamingState::new();
@@ -89,9 +89,13 @@ pub fn to_claude_usage(usage_metadata: &super::models::UsageMetadata, scaling_en
         (scaled_total, None)
     };
     
+    // Claude 口径的 output_tokens 包含思考 Token
+    let output_tokens = usage_metadata.candidates_token_count.unwrap_or(0)
+        + usage_metadata.thoughts_token_count.unwrap_or(0);
+
     super::models::Usage {
         input_tokens: reported_input,
-        output_tokens: usage_metadata.candidates_token_count.unwrap_or(0),
+        output_tokens,
         cache_read_input_tokens: reported_cache,
         cache_creation_input_tokens: Some(0),
         server_tool_use: None,
@@ -114,6 +118,8 @@ mod tests {
             candidates_token_count: Some(50),
             total_token_count: Some(150),
             cached_content_token_count: None,
+            thoughts_token_count: None,
+            tool_use_prompt_token_count: None,
         };
 
         let claude_usage = to_claude_usage(&usage, true, 1_000_000);
@@ -127,6 +133,8 @@ mod tests {
             candidates_token_count: Some(10),
             total_token_count: Some(500_010),
             cached_content_token_count: None,
+            thoughts_token_count: None,
+            tool_use_prompt_token_count: None,
         };
         let res_50 = to_claude_usage(&usage_50, true, 1_000_000);
         // 50% * 0.6 = 30% of 195k = 58,500
@@ -138,6 +146,8 @@ mod tests {
             candidates_token_count: Some(10),
             total_token_count: Some(700_010),
             cached_content_token_count: None,
+            thoughts_token_count: None,
+            tool_use_prompt_token_count: None,
         };
         let res_70 = to_claude_usage(&usage_70, true, 1_000_000);
         // 50% of 195k = 97,500
@@ -149,6 +159,8 @@ mod tests {
             candidates_token_count: Some(10),
             total_token_count: Some(850_010),
             cached_content_token_count: None,
+            thoughts_token_count: None,
+            tool_use_prompt_token_count: None,
         };
         let res_85 = to_claude_usage(&usage_85, true, 1_000_000);
         // 70% of 195k = 136,500
@@ -160,9 +172,31 @@ mod tests {
             candidates_token_count: Some(10),
             total_token_count: Some(1_000_010),
             cached_content_token_count: None,
+            thoughts_token_count: None,
+            tool_use_prompt_token_count: None,
         };
         let res_100 = to_claude_usage(&usage_100, true, 1_000_000);
         // 97% of 195k = 189,150
         assert!(res_100.input_tokens > 185_000 && res_100.input_tokens <= 190_000);
     }
+
+    #[test]
+    fn test_to_claude_usage_includes_thinking_in_output() {
+        use super::super::models::UsageMetadata;
+
+        let usage = UsageMetadata {
+            prompt_token_count: Some(100),
+            candidates_token_count: Some(50),
+            total_token_count: Some(250),
+            cached_content_token_count: Some(40),
+            thoughts_token_count: Some(80),
+            tool_use_prompt_token_count: Some(20),
+        };
+
+        let claude_usage = to_claude_usage(&usage, false, 1_000_000);
+        assert_eq!(claude_usage.output_tokens, 130);
+        assert_eq!(claude_usage.input_tokens, 60);
+        assert_eq!(claude_usage.cache_read_input_tokens, Some(40));
+        assert_eq!(claude_usage.cache_creation_input_tokens, Some(0));
+    }
 }
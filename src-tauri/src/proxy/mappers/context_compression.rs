@@ -0,0 +1,746 @@
+//! 跨协议上下文压缩管线
+//!
+//! Claude / OpenAI / Gemini 三种协议在转换后都汇聚为 v1internal 包装的 Gemini 请求体,
+//! 压缩统一在这一层进行:
+//! - L1: 折叠较早轮次的工具结果 (保留 functionCall/functionResponse 配对)
+//! - L2: 压缩较早的思维内容，保留 thoughtSignature
+//! - L3: 调用廉价模型生成结构化摘要替换中段历史，摘要按会话缓存，避免每轮重新生成
+//!
+//! systemInstruction 与首条用户任务消息始终保留 (pinned)。
+
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+use super::context_manager::estimate_tokens_from_str;
+use crate::proxy::config::ExperimentalConfig;
+
+// ===== Layer 3: XML Summary Prompt Template =====
+// Borrowed from Practical-Guide-to-Context-Engineering + Claude Code official practice
+// This prompt generates a structured 8-section XML summary for context compression
+pub const CONTEXT_SUMMARY_PROMPT: &str = r#"You are a context compression specialist. Your task is to create a structured XML snapshot of the conversation history.
+
+This snapshot will become the Agent's ONLY memory of the past. All key details, plans, errors, and user instructions MUST be preserved.
+
+First, think through the entire history in a private <scratchpad>. Review the user's overall goal, the agent's actions, tool outputs, file modifications, and any unresolved issues. Identify every piece of information critical for future actions.
+
+After reasoning, generate the final <state_snapshot> XML object. Information must be extremely dense. Omit any irrelevant conversational filler.
+
+The structure MUST be as follows:
+
+<state_snapshot>
+  <overall_goal>
+    <!-- Describe the user's high-level goal in one concise sentence -->
+  </overall_goal>
+
+  <technical_context>
+    <!-- Tech stack: frameworks, languages, toolchain, dependency versions -->
+  </technical_context>
+
+  <file_system_state>
+    <!-- List files that were created, read, modified, or deleted. Note their status -->
+  </file_system_state>
+
+  <code_changes>
+    <!-- Key code snippets (preserve function signatures and important logic) -->
+  </code_changes>
+
+  <debugging_history>
+    <!-- List all errors encountered, with stack traces, and how they were fixed -->
+  </debugging_history>
+
+  <current_plan>
+    <!-- Step-by-step plan. Mark completed steps -->
+  </current_plan>
+
+  <user_preferences>
+    <!-- User's work preferences for this project (test commands, code style, etc.) -->
+  </user_preferences>
+
+  <key_decisions>
+    <!-- Critical architectural decisions and design choices -->
+  </key_decisions>
+</state_snapshot>
+
+**IMPORTANT**:
+1. Code snippets must be complete, including function signatures and key logic
+2. Error messages must be preserved verbatim, including line numbers and stacks
+3. File paths must use absolute paths
+4. If a previous snapshot is provided, merge it with the new history instead of discarding it
+"#;
+
+/// L1 保留最近的工具结果轮数
+const KEEP_TOOL_ROUNDS: usize = 5;
+/// L1 小于该长度的工具结果不折叠 (收益太小)
+const MIN_ELIDE_RESULT_CHARS: usize = 200;
+/// L2 保护最近的消息数 (~2 轮)
+const PROTECTED_THOUGHT_MESSAGES: usize = 4;
+/// L3 至少原样保留的尾部消息数
+const KEEP_TAIL_MESSAGES: usize = 6;
+/// 摘要转录中单个工具结果的最大字符数
+const TRANSCRIPT_TOOL_RESULT_CHARS: usize = 4_000;
+/// 多媒体 part 的估算 token 数
+const MEDIA_PART_TOKENS: u32 = 258;
+
+/// 会话摘要缓存容量与有效期
+const SUMMARY_CACHE_CAPACITY: usize = 256;
+const SUMMARY_CACHE_TTL: Duration = Duration::from_secs(6 * 3600);
+
+/// 压缩阈值 (占上下文上限的比例)
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionThresholds {
+    pub l1: f32,
+    pub l2: f32,
+    pub l3: f32,
+}
+
+impl From<&ExperimentalConfig> for CompressionThresholds {
+    fn from(config: &ExperimentalConfig) -> Self {
+        Self {
+            l1: config.context_compression_threshold_l1,
+            l2: config.context_compression_threshold_l2,
+            l3: config.context_compression_threshold_l3,
+        }
+    }
+}
+
+/// 已执行的压缩层
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompressionLayer {
+    /// 复用会话缓存中的摘要
+    CachedSummary,
+    ToolTrim,
+    ThinkingCompression,
+    Summary,
+}
+
+/// 单次请求的压缩报告 (写入监控日志)
+#[derive(Debug, Clone, Serialize)]
+pub struct CompressionReport {
+    pub context_limit: u32,
+    pub tokens_before: u32,
+    pub tokens_after: u32,
+    pub layers: Vec<CompressionLayer>,
+    /// 被摘要替换的历史消息数
+    pub summarized_messages: usize,
+}
+
+impl CompressionReport {
+    /// 是否修改了思维内容 (会破坏 Prompt Cache，且不应用于估算校准)
+    pub fn is_purified(&self) -> bool {
+        self.layers.contains(&CompressionLayer::ThinkingCompression)
+    }
+
+    /// 用于响应头 `X-Context-Compression` 的紧凑表示
+    pub fn header_value(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct CachedSummary {
+    /// 被摘要覆盖的消息 (pinned 之后) 的数量与哈希
+    covered_len: usize,
+    covered_hash: u64,
+    summary: String,
+    created_at: Instant,
+}
+
+static SUMMARY_CACHE: Lazy<Mutex<HashMap<String, CachedSummary>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn cache_get(session_id: &str) -> Option<CachedSummary> {
+    let mut cache = SUMMARY_CACHE.lock().ok()?;
+    match cache.get(session_id) {
+        Some(entry) if entry.created_at.elapsed() < SUMMARY_CACHE_TTL => Some(entry.clone()),
+        Some(_) => {
+            cache.remove(session_id);
+            None
+        }
+        None => None,
+    }
+}
+
+fn cache_put(session_id: &str, entry: CachedSummary) {
+    if let Ok(mut cache) = SUMMARY_CACHE.lock() {
+        if cache.len() >= SUMMARY_CACHE_CAPACITY && !cache.contains_key(session_id) {
+            if let Some(oldest) = cache
+                .iter()
+                .min_by_key(|(_, v)| v.created_at)
+                .map(|(k, _)| k.clone())
+            {
+                cache.remove(&oldest);
+            }
+        }
+        cache.insert(session_id.to_string(), entry);
+    }
+}
+
+/// 取出 v1internal 包装体中的 Gemini 请求 (未包装时返回自身)
+fn inner_request_mut(body: &mut Value) -> &mut Value {
+    if body.get("request").map(|r| r.is_object()).unwrap_or(false) {
+        &mut body["request"]
+    } else {
+        body
+    }
+}
+
+fn estimate_part_tokens(part: &Value) -> u32 {
+    if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+        return estimate_tokens_from_str(text);
+    }
+    if part.get("inlineData").is_some() || part.get("fileData").is_some() {
+        return MEDIA_PART_TOKENS;
+    }
+    serde_json::to_string(part)
+        .map(|s| estimate_tokens_from_str(&s))
+        .unwrap_or(0)
+}
+
+/// 估算 Gemini 请求的 token 用量
+pub fn estimate_request_tokens(request: &Value) -> u32 {
+    let mut total = 0;
+    if let Some(contents) = request.get("contents").and_then(|c| c.as_array()) {
+        for content in contents {
+            total += 4;
+            if let Some(parts) = content.get("parts").and_then(|p| p.as_array()) {
+                total += parts.iter().map(estimate_part_tokens).sum::<u32>();
+            }
+        }
+    }
+    if let Some(parts) = request
+        .get("systemInstruction")
+        .and_then(|s| s.get("parts"))
+        .and_then(|p| p.as_array())
+    {
+        total += parts.iter().map(estimate_part_tokens).sum::<u32>();
+    }
+    if let Some(tools) = request.get("tools") {
+        if let Ok(s) = serde_json::to_string(tools) {
+            total += estimate_tokens_from_str(&s);
+        }
+    }
+    total
+}
+
+fn parts_of(content: &Value) -> &[Value] {
+    content
+        .get("parts")
+        .and_then(|p| p.as_array())
+        .map(|a| a.as_slice())
+        .unwrap_or(&[])
+}
+
+fn is_role(content: &Value, role: &str) -> bool {
+    content.get("role").and_then(|r| r.as_str()) == Some(role)
+}
+
+fn has_function_response(content: &Value) -> bool {
+    parts_of(content).iter().any(|p| p.get("functionResponse").is_some())
+}
+
+// ===== [Layer 1] 工具结果折叠 =====
+
+/// 折叠较早轮次的工具结果，保留最近 N 个工具结果消息
+///
+/// 与直接删除消息不同，这里保留 functionCall/functionResponse 的配对结构，
+/// 只替换结果内容，因此不会破坏工具调用链。返回折叠的结果数。
+pub fn elide_old_tool_results(contents: &mut [Value], keep_last_n_rounds: usize) -> usize {
+    let round_indices: Vec<usize> = contents
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| has_function_response(c))
+        .map(|(i, _)| i)
+        .collect();
+    let elide_count = round_indices.len().saturating_sub(keep_last_n_rounds);
+
+    let mut elided = 0;
+    for &idx in round_indices.iter().take(elide_count) {
+        let Some(parts) = contents[idx].get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        for part in parts.iter_mut() {
+            let Some(resp) = part.get_mut("functionResponse") else {
+                continue;
+            };
+            let original_len = resp
+                .get("response")
+                .and_then(|r| serde_json::to_string(r).ok())
+                .map(|s| s.len())
+                .unwrap_or(0);
+            if original_len < MIN_ELIDE_RESULT_CHARS {
+                continue;
+            }
+            resp["response"] = json!({
+                "result": format!(
+                    "[tool result elided by context compression; originally {} chars]",
+                    original_len
+                )
+            });
+            elided += 1;
+        }
+    }
+
+    if elided > 0 {
+        info!(
+            "[ContextCompression] [Layer-1] Elided {} old tool results, kept last {} rounds",
+            elided, keep_last_n_rounds
+        );
+    }
+    elided
+}
+
+// ===== [Layer 2] 思维内容压缩 =====
+
+/// 压缩受保护范围之外的思维内容，保留 thoughtSignature
+///
+/// 返回压缩的思维块数
+pub fn compress_old_thoughts(contents: &mut [Value], protected_last_n: usize) -> usize {
+    let protect_from = contents.len().saturating_sub(protected_last_n);
+    let mut compressed = 0;
+
+    for content in contents.iter_mut().take(protect_from) {
+        if !is_role(content, "model") {
+            continue;
+        }
+        let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        for part in parts.iter_mut() {
+            let is_thought = part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false);
+            let long_enough = part
+                .get("text")
+                .and_then(|t| t.as_str())
+                .map(|t| t.len() > 10)
+                .unwrap_or(false);
+            if is_thought && long_enough {
+                part["text"] = json!("...");
+                compressed += 1;
+            }
+        }
+    }
+
+    if compressed > 0 {
+        info!(
+            "[ContextCompression] [Layer-2] Compressed {} thinking blocks (signatures preserved)",
+            compressed
+        );
+    }
+    compressed
+}
+
+// ===== [Layer 3] 摘要替换 =====
+
+/// 固定保留的消息数: 首条用户任务消息及其之前的内容
+fn pinned_len(contents: &[Value]) -> usize {
+    contents
+        .iter()
+        .position(|c| is_role(c, "user"))
+        .map(|i| i + 1)
+        .unwrap_or(0)
+}
+
+/// 计算尾部起点: 必须是不含 functionResponse 的用户消息，避免拆散工具调用配对
+fn tail_start(contents: &[Value], pinned: usize, keep_tail: usize) -> Option<usize> {
+    let mut idx = contents.len().saturating_sub(keep_tail);
+    while idx > pinned {
+        if is_role(&contents[idx], "user") && !has_function_response(&contents[idx]) {
+            return Some(idx);
+        }
+        idx -= 1;
+    }
+    None
+}
+
+fn hash_messages(messages: &[Value]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for msg in messages {
+        serde_json::to_string(msg).unwrap_or_default().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn truncate_chars(s: &str, max_chars: usize) -> String {
+    if s.chars().count() <= max_chars {
+        return s.to_string();
+    }
+    let kept: String = s.chars().take(max_chars).collect();
+    format!("{}...[truncated]", kept)
+}
+
+/// 将历史消息渲染为纯文本转录，供摘要模型阅读
+///
+/// 使用纯文本而非原始 contents，避免摘要请求受签名/工具配对校验影响
+pub fn render_transcript(messages: &[Value]) -> String {
+    let mut out = Vec::new();
+    for msg in messages {
+        let role = msg.get("role").and_then(|r| r.as_str()).unwrap_or("user");
+        for part in parts_of(msg) {
+            if part.get("thought").and_then(|t| t.as_bool()).unwrap_or(false) {
+                continue;
+            }
+            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                if !text.trim().is_empty() {
+                    out.push(format!("[{}]: {}", role, text));
+                }
+            } else if let Some(call) = part.get("functionCall") {
+                out.push(format!(
+                    "[{} tool_call] {}({})",
+                    role,
+                    call.get("name").and_then(|n| n.as_str()).unwrap_or("unknown"),
+                    call.get("args").map(|a| a.to_string()).unwrap_or_default()
+                ));
+            } else if let Some(resp) = part.get("functionResponse") {
+                let body = resp.get("response").map(|r| r.to_string()).unwrap_or_default();
+                out.push(format!(
+                    "[tool_result {}]: {}",
+                    resp.get("name").and_then(|n| n.as_str()).unwrap_or("unknown"),
+                    truncate_chars(&body, TRANSCRIPT_TOOL_RESULT_CHARS)
+                ));
+            } else if part.get("inlineData").is_some() || part.get("fileData").is_some() {
+                out.push(format!("[{}]: [media omitted]", role));
+            }
+        }
+    }
+    out.join("\n")
+}
+
+/// 构建发送给摘要模型的提示词
+pub fn build_summary_prompt(previous_summary: Option<&str>, transcript: &str) -> String {
+    let previous = previous_summary
+        .map(|s| format!("<previous_snapshot>\n{}\n</previous_snapshot>\n\n", s))
+        .unwrap_or_default();
+    format!(
+        "{}<conversation_history>\n{}\n</conversation_history>\n\n{}",
+        previous, transcript, CONTEXT_SUMMARY_PROMPT
+    )
+}
+
+/// 从摘要模型输出中提取 <state_snapshot>，丢弃 <scratchpad> 推理部分
+pub fn extract_snapshot(text: &str) -> String {
+    const OPEN: &str = "<state_snapshot>";
+    const CLOSE: &str = "</state_snapshot>";
+    if let Some(start) = text.find(OPEN) {
+        if let Some(len) = text[start..].find(CLOSE) {
+            return text[start..start + len + CLOSE.len()].to_string();
+        }
+        return text[start..].trim().to_string();
+    }
+    match text.find("</scratchpad>") {
+        Some(end) => text[end + "</scratchpad>".len()..].trim().to_string(),
+        None => text.trim().to_string(),
+    }
+}
+
+/// 用摘要替换 pinned 与尾部之间的历史
+///
+/// 摘要追加到首条用户消息中 (保持 user/model 交替)，随后插入一条模型确认消息
+pub fn apply_summary(contents: &[Value], pinned: usize, tail: usize, summary: &str) -> Vec<Value> {
+    let summary_text = format!(
+        "Context has been compressed. Here is the structured summary of our conversation history:\n\n{}",
+        summary
+    );
+    let mut result: Vec<Value> = contents[..pinned].to_vec();
+    match result.last_mut() {
+        Some(first_task) if is_role(first_task, "user") => {
+            if let Some(parts) = first_task.get_mut("parts").and_then(|p| p.as_array_mut()) {
+                parts.push(json!({ "text": summary_text }));
+            }
+        }
+        _ => result.push(json!({ "role": "user", "parts": [{ "text": summary_text }] })),
+    }
+    result.push(json!({
+        "role": "model",
+        "parts": [{ "text": "I have reviewed the compressed context summary. I understand the current state and will continue from here." }]
+    }));
+    result.extend_from_slice(&contents[tail..]);
+    result
+}
+
+/// 执行压缩管线
+///
+/// - `summarize`: 接收摘要提示词，调用廉价模型返回摘要文本
+/// - 返回 Ok(None) 表示未触发任何压缩；L3 摘要失败时返回 Err
+pub async fn compress_request<F, Fut>(
+    body: &mut Value,
+    session_id: &str,
+    context_limit: u32,
+    thresholds: CompressionThresholds,
+    summarize: F,
+) -> Result<Option<CompressionReport>, String>
+where
+    F: FnOnce(String) -> Fut,
+    Fut: Future<Output = Result<String, String>>,
+{
+    let request = inner_request_mut(body);
+    let Some(original) = request.get("contents").and_then(|c| c.as_array()).cloned() else {
+        return Ok(None);
+    };
+    let limit = context_limit.max(1) as f32;
+    let tokens_before = estimate_request_tokens(request);
+    let mut layers = Vec::new();
+    let mut summarized_messages = 0;
+    let pinned = pinned_len(&original);
+
+    // 0. 复用会话缓存的摘要 (前缀未变时)，保持该会话的前缀稳定
+    let cached = cache_get(session_id).filter(|entry| {
+        original.len() > pinned + entry.covered_len
+            && hash_messages(&original[pinned..pinned + entry.covered_len]) == entry.covered_hash
+    });
+    if let Some(entry) = &cached {
+        let tail = pinned + entry.covered_len;
+        request["contents"] = json!(apply_summary(&original, pinned, tail, &entry.summary));
+        layers.push(CompressionLayer::CachedSummary);
+        summarized_messages = entry.covered_len;
+        debug!(
+            "[ContextCompression] Reused cached summary for session {} ({} messages)",
+            session_id, entry.covered_len
+        );
+    }
+
+    let mut ratio = estimate_request_tokens(request) as f32 / limit;
+
+    // 1. L1: 工具结果折叠
+    if ratio > thresholds.l1 {
+        if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+            if elide_old_tool_results(contents, KEEP_TOOL_ROUNDS) > 0 {
+                layers.push(CompressionLayer::ToolTrim);
+                ratio = estimate_request_tokens(request) as f32 / limit;
+            }
+        }
+    }
+
+    // 2. L2: 思维内容压缩
+    if ratio > thresholds.l2 {
+        if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+            if compress_old_thoughts(contents, PROTECTED_THOUGHT_MESSAGES) > 0 {
+                layers.push(CompressionLayer::ThinkingCompression);
+                ratio = estimate_request_tokens(request) as f32 / limit;
+            }
+        }
+    }
+
+    // 3. L3: 摘要替换 (基于原始消息，增量合并已缓存的摘要)
+    if ratio > thresholds.l3 {
+        let covered_from = pinned + cached.as_ref().map(|e| e.covered_len).unwrap_or(0);
+        match tail_start(&original, covered_from, KEEP_TAIL_MESSAGES) {
+            Some(tail) => {
+                info!(
+                    "[ContextCompression] [Layer-3] Summarizing messages {}..{} (usage {:.1}%)",
+                    covered_from,
+                    tail,
+                    ratio * 100.0
+                );
+                let transcript = render_transcript(&original[covered_from..tail]);
+                let prompt = build_summary_prompt(
+                    cached.as_ref().map(|e| e.summary.as_str()),
+                    &transcript,
+                );
+                let summary = summarize(prompt).await?;
+                let covered_len = tail - pinned;
+                cache_put(
+                    session_id,
+                    CachedSummary {
+                        covered_len,
+                        covered_hash: hash_messages(&original[pinned..tail]),
+                        summary: summary.clone(),
+                        created_at: Instant::now(),
+                    },
+                );
+                request["contents"] = json!(apply_summary(&original, pinned, tail, &summary));
+                // 摘要基于原始消息重建，对保留的尾部重新应用已触发的 L1/L2
+                if let Some(contents) = request.get_mut("contents").and_then(|c| c.as_array_mut()) {
+                    if layers.contains(&CompressionLayer::ToolTrim) {
+                        elide_old_tool_results(contents, KEEP_TOOL_ROUNDS);
+                    }
+                    if layers.contains(&CompressionLayer::ThinkingCompression) {
+                        compress_old_thoughts(contents, PROTECTED_THOUGHT_MESSAGES);
+                    }
+                }
+                layers.retain(|l| *l != CompressionLayer::CachedSummary);
+                layers.push(CompressionLayer::Summary);
+                summarized_messages = covered_len;
+            }
+            None => {
+                warn!("[ContextCompression] [Layer-3] Not enough history to summarize");
+            }
+        }
+    }
+
+    if layers.is_empty() {
+        return Ok(None);
+    }
+
+    let report = CompressionReport {
+        context_limit,
+        tokens_before,
+        tokens_after: estimate_request_tokens(request),
+        layers,
+        summarized_messages,
+    };
+    info!(
+        "[ContextCompression] {:?}: {} → {} tokens (limit {})",
+        report.layers, report.tokens_before, report.tokens_after, report.context_limit
+    );
+    Ok(Some(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(text: &str) -> Value {
+        json!({"role": "user", "parts": [{"text": text}]})
+    }
+
+    fn model(text: &str) -> Value {
+        json!({"role": "model", "parts": [{"text": text}]})
+    }
+
+    fn tool_round(i: usize, result_len: usize) -> Vec<Value> {
+        vec![
+            json!({"role": "model", "parts": [
+                {"text": format!("thinking about step {}", i), "thought": true, "thoughtSignature": "sig"},
+                {"functionCall": {"name": "read_file", "args": {"path": format!("/f{}", i)}}}
+            ]}),
+            json!({"role": "user", "parts": [
+                {"functionResponse": {"name": "read_file", "response": {"result": "x".repeat(result_len)}}}
+            ]}),
+        ]
+    }
+
+    fn long_session(rounds: usize) -> Vec<Value> {
+        let mut contents = vec![user("Build the feature")];
+        for i in 0..rounds {
+            contents.extend(tool_round(i, 1000));
+            contents.push(model(&format!("done step {}", i)));
+            contents.push(user(&format!("continue {}", i)));
+        }
+        contents
+    }
+
+    const ALL_LAYERS: CompressionThresholds = CompressionThresholds { l1: 0.0, l2: 0.0, l3: 0.0 };
+
+    #[test]
+    fn test_elide_keeps_recent_rounds_and_pairing() {
+        let mut contents = long_session(8);
+        let elided = elide_old_tool_results(&mut contents, 5);
+        assert_eq!(elided, 3);
+
+        let responses: Vec<&Value> = contents
+            .iter()
+            .flat_map(|c| parts_of(c))
+            .filter_map(|p| p.get("functionResponse"))
+            .collect();
+        assert_eq!(responses.len(), 8);
+        assert!(responses[0]["response"]["result"].as_str().unwrap().contains("elided"));
+        assert_eq!(responses[7]["response"]["result"].as_str().unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn test_compress_thoughts_preserves_signature() {
+        let mut contents = long_session(4);
+        assert!(compress_old_thoughts(&mut contents, 4) > 0);
+        let first_thought = &contents[1]["parts"][0];
+        assert_eq!(first_thought["text"], "...");
+        assert_eq!(first_thought["thoughtSignature"], "sig");
+    }
+
+    #[test]
+    fn test_tail_start_never_splits_tool_pairs() {
+        let contents = long_session(4);
+        let pinned = pinned_len(&contents);
+        assert_eq!(pinned, 1);
+        let tail = tail_start(&contents, pinned, 3).unwrap();
+        assert!(is_role(&contents[tail], "user"));
+        assert!(!has_function_response(&contents[tail]));
+    }
+
+    #[test]
+    fn test_apply_summary_keeps_pinned_task_and_alternation() {
+        let contents = long_session(4);
+        let tail = tail_start(&contents, 1, 3).unwrap();
+        let result = apply_summary(&contents, 1, tail, "<state_snapshot/>");
+
+        assert_eq!(result[0]["parts"][0]["text"], "Build the feature");
+        assert!(result[0]["parts"][1]["text"].as_str().unwrap().contains("<state_snapshot/>"));
+        assert!(is_role(&result[1], "model"));
+        assert!(is_role(&result[2], "user"));
+        assert_eq!(result.len(), 2 + contents.len() - tail);
+    }
+
+    #[test]
+    fn test_render_transcript_skips_thoughts_and_truncates_results() {
+        let transcript = render_transcript(&tool_round(0, 10_000));
+        assert!(!transcript.contains("thinking about step"));
+        assert!(transcript.contains("read_file"));
+        assert!(transcript.contains("[truncated]"));
+        assert!(transcript.len() < 5_000);
+    }
+
+    #[test]
+    fn test_extract_snapshot_drops_scratchpad() {
+        let text = "<scratchpad>private notes</scratchpad>\n<state_snapshot><overall_goal>x</overall_goal></state_snapshot>\ntrailing";
+        assert_eq!(
+            extract_snapshot(text),
+            "<state_snapshot><overall_goal>x</overall_goal></state_snapshot>"
+        );
+        assert_eq!(extract_snapshot("<scratchpad>a</scratchpad> plain summary"), "plain summary");
+        assert_eq!(extract_snapshot(" plain "), "plain");
+    }
+
+    #[tokio::test]
+    async fn test_compress_request_caches_summary_per_session() {
+        let session = format!("test-session-{}", uuid::Uuid::new_v4());
+        let mut body = json!({"project": "p", "request": {"contents": long_session(10)}});
+
+        let report = compress_request(&mut body, &session, 1000, ALL_LAYERS, |_| async {
+            Ok("<state_snapshot>first</state_snapshot>".to_string())
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert!(report.layers.contains(&CompressionLayer::Summary));
+        assert!(report.tokens_after < report.tokens_before);
+        assert!(report.summarized_messages > 0);
+
+        // 下一轮: 同一会话追加消息，摘要直接复用，不触发摘要模型
+        let mut next = long_session(10);
+        next.push(model("ok"));
+        next.push(user("next question"));
+        let mut body = json!({"request": {"contents": next}});
+        let relaxed = CompressionThresholds { l1: 10.0, l2: 10.0, l3: 10.0 };
+        let report = compress_request(&mut body, &session, 1000, relaxed, |_| async {
+            Err::<String, String>("summarizer must not be called".to_string())
+        })
+        .await
+        .unwrap()
+        .unwrap();
+        assert_eq!(report.layers, vec![CompressionLayer::CachedSummary]);
+        let contents = body["request"]["contents"].as_array().unwrap();
+        assert!(contents[0]["parts"][1]["text"].as_str().unwrap().contains("first"));
+        assert_eq!(contents.last().unwrap()["parts"][0]["text"], "next question");
+    }
+
+    #[tokio::test]
+    async fn test_compress_request_no_pressure_is_noop() {
+        let mut body = json!({"request": {"contents": long_session(2)}});
+        let before = body.clone();
+        let relaxed = CompressionThresholds { l1: 0.9, l2: 0.9, l3: 0.9 };
+        let report = compress_request(&mut body, "noop", 10_000_000, relaxed, |_| async {
+            Ok(String::new())
+        })
+        .await
+        .unwrap();
+        assert!(report.is_none());
+        assert_eq!(body, before);
+    }
+}
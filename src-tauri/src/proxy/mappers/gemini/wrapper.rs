@@ -1,4 +1,5 @@
 // Gemini v1internal 包装/解包
+use crate::proxy::common::client_adapter::ToolNameStyle;
 use serde_json::{json, Value};
 
 /// 包装请求体为 v1internal 格式
@@ -7,6 +8,17 @@ pub fn wrap_request(
     project_id: &str,
     mapped_model: &str,
     session_id: Option<&str>,
+) -> Value {
+    wrap_request_for_client(body, project_id, mapped_model, session_id, ToolNameStyle::default())
+}
+
+/// 同 [`wrap_request`]，工具 Schema 按客户端的 MCP 工具命名约定匹配工具适配器
+pub fn wrap_request_for_client(
+    body: &Value,
+    project_id: &str,
+    mapped_model: &str,
+    session_id: Option<&str>,
+    tool_name_style: ToolNameStyle,
 ) -> Value {
     // 优先使用传入的 mapped_model，其次尝试从 body 获取
     let original_model = body
@@ -92,6 +104,14 @@ pub fn wrap_request(
         || lower_model.contains("pro")
         || lower_model.contains("thinking")
     {
+        // 联网请求在 Auto 模式下同样受预算上限约束
+        let has_web_search = crate::proxy::mappers::common_utils::detects_networking_tool(
+            &inner_request
+                .get("tools")
+                .and_then(|t| t.as_array())
+                .cloned(),
+        );
+
         // Ensure generationConfig exists
         let gen_config = inner_request
             .as_object_mut()
@@ -134,40 +154,14 @@ pub fn wrap_request(
         if let Some(thinking_config) = gen_config.get_mut("thinkingConfig") {
             if let Some(budget_val) = thinking_config.get("thinkingBudget") {
                 if let Some(budget) = budget_val.as_u64() {
-                    let tb_config = crate::proxy::config::get_thinking_budget_config();
-                    let final_budget = match tb_config.mode {
-                        crate::proxy::config::ThinkingBudgetMode::Passthrough => {
-                            // 透传模式：不做任何修改，完全使用上游传入值
-                            tracing::debug!(
-                                "[Gemini-Wrap] Passthrough mode: keeping budget {} for model {}",
-                                budget, final_model_name
-                            );
-                            budget
-                        }
-                        crate::proxy::config::ThinkingBudgetMode::Custom => {
-                            // 自定义模式：使用全局配置的固定值
-                            let custom_value = tb_config.custom_value as u64;
-                            if custom_value != budget {
-                                tracing::debug!(
-                                    "[Gemini-Wrap] Custom mode: overriding {} with {} for model {}",
-                                    budget, custom_value, final_model_name
-                                );
-                            }
-                            custom_value
-                        }
-                        crate::proxy::config::ThinkingBudgetMode::Auto => {
-                            // 自动模式：应用 24576 capping (向后兼容)
-                            if budget > 24576 {
-                                tracing::info!(
-                                    "[Gemini-Wrap] Auto mode: capping thinking_budget from {} to 24576 for model {}", 
-                                    budget, final_model_name
-                                );
-                                24576
-                            } else {
-                                budget
-                            }
-                        }
-                    };
+                    // 统一推理策略: 遵循全局 Thinking Budget 配置 (模式 + 按模型上限)
+                    // 注意: Auto 模式与其他协议一致，仅对 Flash / 1.5 / -thinking / 联网请求限制到 24576，
+                    // Pro 等模型不再一律截断 (见 test_gemini_flash_thinking_budget_capping)
+                    let final_budget = crate::proxy::mappers::reasoning_policy::apply_budget_config(
+                        &final_model_name,
+                        budget.min(u32::MAX as u64) as u32,
+                        has_web_search,
+                    ) as u64;
 
                     if final_budget != budget {
                         thinking_config["thinkingBudget"] = json!(final_budget);
@@ -219,18 +213,29 @@ pub fn wrap_request(
                         for decl in decls_arr {
                             // 检测并转换字段名
                             if let Some(decl_obj) = decl.as_object_mut() {
+                                let name = decl_obj
+                                    .get("name")
+                                    .and_then(|v| v.as_str())
+                                    .unwrap_or_default()
+                                    .to_string();
                                 // 如果存在 parametersJsonSchema，将其重命名为 parameters
                                 if let Some(params_json_schema) =
                                     decl_obj.remove("parametersJsonSchema")
                                 {
                                     let mut params = params_json_schema;
-                                    crate::proxy::common::json_schema::clean_json_schema(
+                                    crate::proxy::common::json_schema::clean_json_schema_for_client_tool(
                                         &mut params,
+                                        &name,
+                                        tool_name_style,
                                     );
                                     decl_obj.insert("parameters".to_string(), params);
                                 } else if let Some(params) = decl_obj.get_mut("parameters") {
                                     // 标准 parameters 字段
-                                    crate::proxy::common::json_schema::clean_json_schema(params);
+                                    crate::proxy::common::json_schema::clean_json_schema_for_client_tool(
+                                        params,
+                                        &name,
+                                        tool_name_style,
+                                    );
                                 }
                             }
                         }
@@ -361,6 +366,72 @@ mod test_fixes {
     }
 }
 
+/// 预处理 contents 中的文档 inlineData 与本地文件引用
+///
+/// fileData.fileUri 指向本地 Files API 中 owner 名下的文件时替换为 inlineData / 文本 part;
+/// PDF 仅校验大小; docx / xlsx 与 text/* 在本地提取为文本 part (Gemini 不支持 Office 格式)。
+/// 图片、音视频等其他类型以及 Google 托管的 fileUri 保持原样透传。
+pub fn resolve_inline_documents(
+    body: &mut Value,
+    owner: Option<&str>,
+) -> Result<(), crate::proxy::common::document_input::DocumentInputError> {
+    use crate::proxy::common::document_input::{
+        resolve_base64_document, wrap_extracted_text, ResolvedDocument, DOCX_MIME, PDF_MIME,
+        XLSX_MIME,
+    };
+    use crate::proxy::files::{parse_file_reference, resolve_file_reference};
+
+    let Some(contents) = body.get_mut("contents").and_then(|c| c.as_array_mut()) else {
+        return Ok(());
+    };
+    for content in contents.iter_mut() {
+        let Some(parts) = content.get_mut("parts").and_then(|p| p.as_array_mut()) else {
+            continue;
+        };
+        for part in parts.iter_mut() {
+            let local_file = part
+                .get("fileData")
+                .and_then(|f| f.get("fileUri"))
+                .and_then(|v| v.as_str())
+                .and_then(parse_file_reference)
+                .map(|id| id.to_string());
+            if let Some(file_id) = local_file {
+                *part = match resolve_file_reference(&file_id, owner)? {
+                    ResolvedDocument::Inline { mime_type, data } => {
+                        json!({ "inlineData": { "mimeType": mime_type, "data": data } })
+                    }
+                    ResolvedDocument::Text(text) => json!({ "text": wrap_extracted_text(None, &text) }),
+                };
+                continue;
+            }
+
+            let Some(inline) = part.get("inlineData") else {
+                continue;
+            };
+            let mime = inline.get("mimeType").and_then(|v| v.as_str()).unwrap_or("");
+            if !(mime == PDF_MIME || mime == DOCX_MIME || mime == XLSX_MIME || mime.starts_with("text/")) {
+                continue;
+            }
+            let data = inline.get("data").and_then(|v| v.as_str()).unwrap_or("");
+            if let ResolvedDocument::Text(text) = resolve_base64_document(data, Some(mime), None)? {
+                *part = json!({ "text": wrap_extracted_text(None, &text) });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 提取 SSE 片段中的 JSON 数据 (用于转换为 streamGenerateContent 的 JSON 数组格式)
+pub fn sse_chunk_to_array_elements(chunk: &str) -> Vec<String> {
+    chunk
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("data:"))
+        .map(|data| data.trim())
+        .filter(|data| !data.is_empty() && *data != "[DONE]")
+        .map(|data| data.to_string())
+        .collect()
+}
+
 /// 解包响应（提取 response 字段）
 pub fn unwrap_response(response: &Value) -> Value {
     response.get("response").unwrap_or(response).clone()
@@ -486,6 +557,27 @@ mod tests {
         assert_eq!(budget_pro, 32000);
     }
 
+    #[test]
+    fn test_gemini_pro_web_search_thinking_budget_capping() {
+        let body = json!({
+            "model": "gemini-2.0-pro-exp",
+            "tools": [{"googleSearch": {}}],
+            "generationConfig": {
+                "thinkingConfig": {
+                    "includeThoughts": true,
+                    "thinkingBudget": 32000
+                }
+            }
+        });
+
+        // 联网请求即使是 Pro 模型也应限制到 24576
+        let result = wrap_request(&body, "test-proj", "gemini-2.0-pro-exp", None);
+        let budget = result["request"]["generationConfig"]["thinkingConfig"]["thinkingBudget"]
+            .as_u64()
+            .unwrap();
+        assert_eq!(budget, 24576);
+    }
+
     #[test]
     fn test_user_instruction_preservation() {
         let body = json!({
@@ -578,6 +670,7 @@ mod tests {
         update_thinking_budget_config(ThinkingBudgetConfig {
             mode: ThinkingBudgetMode::Custom,
             custom_value: 1024, // Distinct value
+            model_caps: Default::default(),
         });
 
         let body = json!({
@@ -631,4 +724,27 @@ mod tests {
         // Default injected value is 16000
         assert_eq!(budget, 16000);
     }
+
+    #[test]
+    fn test_resolve_inline_documents_extracts_text_types() {
+        use base64::Engine as _;
+        let text = base64::engine::general_purpose::STANDARD.encode("a,b");
+        let mut body = json!({
+            "contents": [{"role": "user", "parts": [
+                {"inlineData": {"mimeType": "text/csv", "data": text}},
+                {"inlineData": {"mimeType": "image/png", "data": "iVBORw0KGgo="}}
+            ]}]
+        });
+
+        resolve_inline_documents(&mut body, None).unwrap();
+        let parts = &body["contents"][0]["parts"];
+        assert_eq!(parts[0]["text"], "<document name=\"document\">\na,b\n</document>");
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "image/png");
+    }
+
+    #[test]
+    fn test_sse_chunk_to_array_elements() {
+        let chunk = "data: {\"a\":1}\n\n: keep-alive\n\ndata: [DONE]\n\n";
+        assert_eq!(sse_chunk_to_array_elements(chunk), vec!["{\"a\":1}".to_string()]);
+    }
 }
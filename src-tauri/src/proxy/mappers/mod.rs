@@ -1,12 +1,16 @@
 // Mappers 模块 - 协议转换器
 // 协议转换器模块
 
+pub mod citations;
 pub mod claude;
 pub mod common_utils;
+pub mod context_compression;
 pub mod context_manager;
 pub mod error_classifier;
 pub mod estimation_calibrator;
 pub mod gemini;
 pub mod openai;
+pub mod reasoning_policy;
 pub mod signature_store;
 pub mod tool_result_compressor;
+pub mod tool_result_strategies;
@@ -5,9 +5,139 @@ use super::models::*;
 use bytes::Bytes;
 use futures::StreamExt;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::io;
 
+/// 单个 choice 的流式增量累积
+#[derive(Default)]
+struct ChoiceAccumulator {
+    role: Option<String>,
+    content_parts: Vec<String>,
+    reasoning_parts: Vec<String>,
+    annotations: Vec<Value>,
+    finish_reason: Option<String>,
+    logprobs: Option<Value>,
+    // Tool calls aggregation: index -> (id, type, name, arguments_parts)
+    tool_calls_map: HashMap<u32, (String, String, String, Vec<String>)>,
+}
+
+impl ChoiceAccumulator {
+    fn apply(&mut self, choice: &Value) {
+        if let Some(delta) = choice.get("delta") {
+            // Role
+            if let Some(r) = delta.get("role").and_then(|v| v.as_str()) {
+                self.role = Some(r.to_string());
+            }
+
+            // Content
+            if let Some(c) = delta.get("content").and_then(|v| v.as_str()) {
+                self.content_parts.push(c.to_string());
+            }
+
+            // Reasoning Content
+            if let Some(rc) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
+                self.reasoning_parts.push(rc.to_string());
+            }
+
+            // url_citation annotations
+            if let Some(items) = delta.get("annotations").and_then(|v| v.as_array()) {
+                self.annotations.extend(items.iter().cloned());
+            }
+
+            // Tool Calls aggregation by index
+            if let Some(tcs) = delta.get("tool_calls").and_then(|v| v.as_array()) {
+                for tc in tcs {
+                    let index = tc.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+
+                    let entry = self.tool_calls_map.entry(index).or_insert_with(|| {
+                        (String::new(), String::from("function"), String::new(), Vec::new())
+                    });
+
+                    if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
+                        if !id.is_empty() {
+                            entry.0 = id.to_string();
+                        }
+                    }
+
+                    if let Some(tc_type) = tc.get("type").and_then(|v| v.as_str()) {
+                        if !tc_type.is_empty() {
+                            entry.1 = tc_type.to_string();
+                        }
+                    }
+
+                    if let Some(func) = tc.get("function") {
+                        if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
+                            if !name.is_empty() {
+                                entry.2 = name.to_string();
+                            }
+                        }
+                        if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
+                            entry.3.push(args.to_string());
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(lp) = choice.get("logprobs").filter(|v| !v.is_null()) {
+            super::logprobs::merge_chat_logprobs(&mut self.logprobs, lp);
+        }
+
+        if let Some(fr) = choice.get("finish_reason").and_then(|v| v.as_str()) {
+            self.finish_reason = Some(fr.to_string());
+        }
+    }
+
+    fn into_choice(self, index: u32) -> Choice {
+        // Construct final message
+        let full_content = self.content_parts.join("");
+        let full_reasoning = if self.reasoning_parts.is_empty() {
+            None
+        } else {
+            Some(self.reasoning_parts.join(""))
+        };
+
+        // Build aggregated tool_calls
+        let final_tool_calls: Option<Vec<ToolCall>> = if self.tool_calls_map.is_empty() {
+            None
+        } else {
+            let mut calls: Vec<(u32, ToolCall)> = self
+                .tool_calls_map
+                .into_iter()
+                .map(|(index, (id, tc_type, name, args_parts))| {
+                    (index, ToolCall {
+                        id,
+                        r#type: tc_type,
+                        function: ToolFunction {
+                            name,
+                            arguments: args_parts.join(""),
+                        },
+                    })
+                })
+                .collect();
+            calls.sort_by_key(|(index, _)| *index);
+            Some(calls.into_iter().map(|(_, tc)| tc).collect())
+        };
+
+        let message = OpenAIMessage {
+            role: self.role.unwrap_or("assistant".to_string()),
+            content: Some(OpenAIContent::String(full_content)),
+            reasoning_content: full_reasoning,
+            tool_calls: final_tool_calls,
+            tool_call_id: None,
+            name: None,
+            annotations: if self.annotations.is_empty() { None } else { Some(self.annotations) },
+        };
+
+        Choice {
+            index,
+            message,
+            finish_reason: self.finish_reason.or(Some("stop".to_string())),
+            logprobs: self.logprobs,
+        }
+    }
+}
+
 /// Collects an OpenAI SSE stream into a complete OpenAIResponse
 pub async fn collect_stream_to_json<S, E>(
     mut stream: S,
@@ -25,12 +155,8 @@ where
         usage: None,
     };
 
-    let mut role: Option<String> = None;
-    let mut content_parts: Vec<String> = Vec::new();
-    let mut reasoning_parts: Vec<String> = Vec::new();
-    let mut finish_reason: Option<String> = None;
-    // Tool calls aggregation: index -> (id, type, name, arguments_parts)
-    let mut tool_calls_map: HashMap<u32, (String, String, String, Vec<String>)> = HashMap::new();
+    // 按 choice index 聚合 (n > 1 时各候选交错输出)
+    let mut accumulators: BTreeMap<u32, ChoiceAccumulator> = BTreeMap::new();
 
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result.map_err(|e| format!("Stream error: {}", e))?;
@@ -65,61 +191,10 @@ where
 
                     // Collect Choices Delta
                     if let Some(choices) = json.get("choices").and_then(|v| v.as_array()) {
-                        if let Some(choice) = choices.first() {
-                            if let Some(delta) = choice.get("delta") {
-                                // Role
-                                if let Some(r) = delta.get("role").and_then(|v| v.as_str()) {
-                                    role = Some(r.to_string());
-                                }
-                                
-                                // Content
-                                if let Some(c) = delta.get("content").and_then(|v| v.as_str()) {
-                                    content_parts.push(c.to_string());
-                                }
-
-                                // Reasoning Content
-                                if let Some(rc) = delta.get("reasoning_content").and_then(|v| v.as_str()) {
-                                    reasoning_parts.push(rc.to_string());
-                                }
-
-                                // Tool Calls aggregation by index
-                                if let Some(tcs) = delta.get("tool_calls").and_then(|v| v.as_array()) {
-                                    for tc in tcs {
-                                        let index = tc.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
-                                        
-                                        let entry = tool_calls_map.entry(index).or_insert_with(|| {
-                                            (String::new(), String::from("function"), String::new(), Vec::new())
-                                        });
-                                        
-                                        if let Some(id) = tc.get("id").and_then(|v| v.as_str()) {
-                                            if !id.is_empty() {
-                                                entry.0 = id.to_string();
-                                            }
-                                        }
-                                        
-                                        if let Some(tc_type) = tc.get("type").and_then(|v| v.as_str()) {
-                                            if !tc_type.is_empty() {
-                                                entry.1 = tc_type.to_string();
-                                            }
-                                        }
-                                        
-                                        if let Some(func) = tc.get("function") {
-                                            if let Some(name) = func.get("name").and_then(|v| v.as_str()) {
-                                                if !name.is_empty() {
-                                                    entry.2 = name.to_string();
-                                                }
-                                            }
-                                            if let Some(args) = func.get("arguments").and_then(|v| v.as_str()) {
-                                                entry.3.push(args.to_string());
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-
-                            if let Some(fr) = choice.get("finish_reason").and_then(|v| v.as_str()) {
-                                finish_reason = Some(fr.to_string());
-                            }
+                        for choice in choices {
+                            let choice_index =
+                                choice.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+                            accumulators.entry(choice_index).or_default().apply(choice);
                         }
                     }
                 }
@@ -127,49 +202,46 @@ where
         }
     }
 
-    // Construct final message
-    let full_content = content_parts.join("");
-    let full_reasoning = if reasoning_parts.is_empty() {
-        None
-    } else {
-        Some(reasoning_parts.join(""))
-    };
-
-    // Build aggregated tool_calls
-    let final_tool_calls: Option<Vec<ToolCall>> = if tool_calls_map.is_empty() {
-        None
-    } else {
-        let mut calls: Vec<(u32, ToolCall)> = tool_calls_map
-            .into_iter()
-            .map(|(index, (id, tc_type, name, args_parts))| {
-                (index, ToolCall {
-                    id,
-                    r#type: tc_type,
-                    function: ToolFunction {
-                        name,
-                        arguments: args_parts.join(""),
-                    },
-                })
-            })
-            .collect();
-        calls.sort_by_key(|(index, _)| *index);
-        Some(calls.into_iter().map(|(_, tc)| tc).collect())
-    };
+    if accumulators.is_empty() {
+        accumulators.insert(0, ChoiceAccumulator::default());
+    }
+    response.choices = accumulators
+        .into_iter()
+        .map(|(index, acc)| acc.into_choice(index))
+        .collect();
 
-    let message = OpenAIMessage {
-        role: role.unwrap_or("assistant".to_string()),
-        content: Some(OpenAIContent::String(full_content)),
-        reasoning_content: full_reasoning,
-        tool_calls: final_tool_calls,
-        tool_call_id: None,
-        name: None,
-    };
+    Ok(response)
+}
 
-    response.choices.push(Choice {
-        index: 0,
-        message,
-        finish_reason: finish_reason.or(Some("stop".to_string())),
-    });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_collects_interleaved_choices() {
+        let chunks = [
+            json!({ "id": "chatcmpl-1", "model": "m", "choices": [{ "index": 1, "delta": { "content": "B" }, "finish_reason": null }] }),
+            json!({ "choices": [{ "index": 0, "delta": { "role": "assistant", "content": "A" }, "finish_reason": null,
+                "logprobs": { "content": [{ "token": "A", "logprob": -0.1, "top_logprobs": [] }] } }] }),
+            json!({ "choices": [{ "index": 1, "delta": { "content": "b" }, "finish_reason": "length" }] }),
+            json!({ "choices": [{ "index": 0, "delta": { "content": "a" }, "finish_reason": "stop",
+                "logprobs": { "content": [{ "token": "a", "logprob": -0.2, "top_logprobs": [] }] } }] }),
+        ];
+        let frames: Vec<Result<Bytes, String>> = chunks
+            .iter()
+            .map(|c| Ok(Bytes::from(format!("data: {}\n\n", c))))
+            .chain(std::iter::once(Ok(Bytes::from("data: [DONE]\n\n"))))
+            .collect();
 
-    Ok(response)
+        let resp = collect_stream_to_json(futures::stream::iter(frames)).await.unwrap();
+        assert_eq!(resp.choices.len(), 2);
+        assert_eq!(resp.choices[0].index, 0);
+        assert_eq!(resp.choices[0].message.content, Some(OpenAIContent::String("Aa".to_string())));
+        assert_eq!(resp.choices[0].finish_reason.as_deref(), Some("stop"));
+        let logprobs = resp.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs["content"].as_array().unwrap().len(), 2);
+        assert_eq!(resp.choices[1].message.content, Some(OpenAIContent::String("Bb".to_string())));
+        assert_eq!(resp.choices[1].finish_reason.as_deref(), Some("length"));
+        assert!(resp.choices[1].logprobs.is_none());
+    }
 }
@@ -0,0 +1,173 @@
+// OpenAI logprobs 映射
+// Gemini candidate.logprobsResult (chosenCandidates / topCandidates) -> OpenAI Chat choice.logprobs，
+// 以及旧版 Completions 的 { tokens, token_logprobs, top_logprobs, text_offset } 格式。
+
+use serde_json::{json, Map, Value};
+
+/// top_logprobs 上限 (OpenAI 与 Gemini generationConfig.logprobs 均为 20)
+pub const MAX_TOP_LOGPROBS: u32 = 20;
+
+fn token_entry(candidate: &Value) -> Value {
+    let token = candidate
+        .get("token")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default();
+    json!({
+        "token": token,
+        "logprob": candidate
+            .get("logProbability")
+            .and_then(|v| v.as_f64())
+            .unwrap_or(0.0),
+        "bytes": token.as_bytes()
+    })
+}
+
+/// Gemini logprobsResult -> OpenAI Chat logprobs ({ content: [...] })，没有 Token 时返回 None
+pub fn chat_logprobs(logprobs_result: &Value) -> Option<Value> {
+    let chosen = logprobs_result.get("chosenCandidates")?.as_array()?;
+    if chosen.is_empty() {
+        return None;
+    }
+    let top = logprobs_result
+        .get("topCandidates")
+        .and_then(|v| v.as_array());
+
+    let content: Vec<Value> = chosen
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            let top_logprobs: Vec<Value> = top
+                .and_then(|t| t.get(i))
+                .and_then(|t| t.get("candidates"))
+                .and_then(|v| v.as_array())
+                .map(|list| list.iter().map(token_entry).collect())
+                .unwrap_or_default();
+            let mut entry = token_entry(candidate);
+            entry["top_logprobs"] = json!(top_logprobs);
+            entry
+        })
+        .collect();
+
+    Some(json!({ "content": content, "refusal": null }))
+}
+
+/// 合并同一 choice 的流式 logprobs 分片
+pub fn merge_chat_logprobs(target: &mut Option<Value>, chunk: &Value) {
+    let Some(items) = chunk.get("content").and_then(|v| v.as_array()) else {
+        return;
+    };
+    match target
+        .as_mut()
+        .and_then(|t| t.get_mut("content"))
+        .and_then(|v| v.as_array_mut())
+    {
+        Some(content) => content.extend(items.iter().cloned()),
+        None => *target = Some(chunk.clone()),
+    }
+}
+
+/// Chat logprobs -> 旧版 Completions logprobs
+///
+/// text_offset 从 start_offset 开始按字符累加，返回值同时给出下一个 Token 的起始位置 (流式续接)。
+pub fn legacy_logprobs(chat_logprobs: &Value, start_offset: usize) -> (Value, usize) {
+    let mut tokens = Vec::new();
+    let mut token_logprobs = Vec::new();
+    let mut top_logprobs = Vec::new();
+    let mut text_offset = Vec::new();
+    let mut offset = start_offset;
+
+    for entry in chat_logprobs
+        .get("content")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let token = entry
+            .get("token")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default();
+        let top: Map<String, Value> = entry
+            .get("top_logprobs")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|alt| {
+                let alt_token = alt.get("token")?.as_str()?;
+                Some((alt_token.to_string(), alt.get("logprob")?.clone()))
+            })
+            .collect();
+
+        tokens.push(json!(token));
+        token_logprobs.push(entry.get("logprob").cloned().unwrap_or(Value::Null));
+        top_logprobs.push(Value::Object(top));
+        text_offset.push(json!(offset));
+        offset += token.chars().count();
+    }
+
+    (
+        json!({
+            "tokens": tokens,
+            "token_logprobs": token_logprobs,
+            "top_logprobs": top_logprobs,
+            "text_offset": text_offset
+        }),
+        offset,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gemini_logprobs() -> Value {
+        json!({
+            "chosenCandidates": [
+                { "token": "Hi", "tokenId": 1, "logProbability": -0.1 },
+                { "token": "!", "tokenId": 2, "logProbability": -0.5 }
+            ],
+            "topCandidates": [
+                { "candidates": [
+                    { "token": "Hi", "logProbability": -0.1 },
+                    { "token": "Hello", "logProbability": -2.3 }
+                ]},
+                { "candidates": [{ "token": "!", "logProbability": -0.5 }] }
+            ]
+        })
+    }
+
+    #[test]
+    fn test_chat_logprobs_mapping() {
+        let lp = chat_logprobs(&gemini_logprobs()).unwrap();
+        let content = lp["content"].as_array().unwrap();
+        assert_eq!(content.len(), 2);
+        assert_eq!(content[0]["token"], "Hi");
+        assert_eq!(content[0]["logprob"], -0.1);
+        assert_eq!(content[0]["bytes"], json!([72, 105]));
+        assert_eq!(content[0]["top_logprobs"][1]["token"], "Hello");
+        assert_eq!(content[1]["top_logprobs"].as_array().unwrap().len(), 1);
+
+        assert!(chat_logprobs(&json!({ "chosenCandidates": [] })).is_none());
+    }
+
+    #[test]
+    fn test_merge_and_legacy_logprobs() {
+        let lp = chat_logprobs(&gemini_logprobs()).unwrap();
+        let mut merged = None;
+        merge_chat_logprobs(&mut merged, &lp);
+        merge_chat_logprobs(&mut merged, &lp);
+        assert_eq!(
+            merged.as_ref().unwrap()["content"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let (legacy, next) = legacy_logprobs(&lp, 5);
+        assert_eq!(legacy["tokens"], json!(["Hi", "!"]));
+        assert_eq!(legacy["token_logprobs"], json!([-0.1, -0.5]));
+        assert_eq!(legacy["top_logprobs"][0]["Hello"], -2.3);
+        assert_eq!(legacy["text_offset"], json!([5, 7]));
+        assert_eq!(next, 8);
+    }
+}
@@ -2,6 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OpenAIRequest {
@@ -40,6 +41,26 @@ pub struct OpenAIRequest {
     // [NEW] Thinking/Extended Thinking 支持 (兼容 Anthropic/Claude 协议)
     #[serde(default)]
     pub thinking: Option<ThinkingConfig>,
+    /// Chat Completions 推理强度 (none / minimal / low / medium / high)
+    #[serde(default)]
+    pub reasoning_effort: Option<String>,
+    /// Responses 推理配置 ({ effort, summary })
+    #[serde(default)]
+    pub reasoning: Option<Value>,
+    /// Chat 为布尔值；旧版 Completions 为整数 (返回的候选 Token 数)
+    #[serde(default)]
+    pub logprobs: Option<Value>,
+    #[serde(default)]
+    pub top_logprobs: Option<u32>,
+    #[serde(default)]
+    pub seed: Option<i64>,
+    #[serde(default)]
+    pub presence_penalty: Option<f32>,
+    #[serde(default)]
+    pub frequency_penalty: Option<f32>,
+    /// Gemini 无对应能力，非空时拒绝请求
+    #[serde(default)]
+    pub logit_bias: Option<HashMap<String, f32>>,
 }
 
 /// Thinking 配置 (兼容 Anthropic 和 OpenAI 扩展协议)
@@ -72,6 +93,34 @@ pub enum OpenAIContentBlock {
     ImageUrl { image_url: OpenAIImageUrl },
     #[serde(rename = "audio_url")]
     AudioUrl { audio_url: AudioUrlContent },
+    #[serde(rename = "file")]
+    File { file: OpenAIFile },
+    /// Responses 风格输入中的文件
+    #[serde(rename = "input_file")]
+    InputFile {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_data: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        filename: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        file_url: Option<String>,
+        /// http(s) file_url 的 MIME，由 probe_remote_file_types 按扩展名或响应头补全
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        mime_type: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OpenAIFile {
+    /// data URL 或裸 base64
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_data: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
@@ -99,6 +148,9 @@ pub struct OpenAIMessage {
     pub tool_call_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+    /// 联网搜索引用 (url_citation)，仅出现在响应中
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -130,6 +182,9 @@ pub struct Choice {
     pub index: u32,
     pub message: OpenAIMessage,
     pub finish_reason: Option<String>,
+    /// 请求 logprobs 时返回 ({ content: [...] })
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -1,12 +1,30 @@
 // OpenAI → Gemini 请求转换
+use super::logprobs::MAX_TOP_LOGPROBS;
 use super::models::*;
 use super::streaming::get_thought_signature;
+use crate::proxy::common::client_adapter::ToolNameStyle;
+use crate::proxy::mappers::reasoning_policy::{self, ReasoningEffort, ReasoningRequest};
 use serde_json::{json, Value};
 
+/// 单个工具结果的最大字符数 (与 Claude 协议保持一致)
+const MAX_TOOL_RESULT_CHARS: usize = 200_000;
+/// Gemini candidateCount 上限
+const MAX_CANDIDATE_COUNT: u32 = 8;
+
 pub fn transform_openai_request(
     request: &OpenAIRequest,
     project_id: &str,
     mapped_model: &str,
+) -> (Value, String, usize) {
+    transform_openai_request_for_client(request, project_id, mapped_model, ToolNameStyle::default())
+}
+
+/// 同 [`transform_openai_request`]，工具 Schema 按客户端的 MCP 工具命名约定匹配工具适配器
+pub fn transform_openai_request_for_client(
+    request: &OpenAIRequest,
+    project_id: &str,
+    mapped_model: &str,
+    tool_name_style: ToolNameStyle,
 ) -> (Value, String, usize) {
     let session_id = crate::proxy::session_manager::SessionManager::extract_openai_session_id(request);
     let message_count = request.messages.len();
@@ -42,10 +60,15 @@ pub fn transform_openai_request(
     let is_claude_thinking = mapped_model_lower.ends_with("-thinking");
     let is_thinking_model = is_gemini_3_thinking || is_claude_thinking;
 
-    // [NEW] 检查用户是否在请求中显式启用 thinking
+    // [NEW] 检查用户是否在请求中显式启用 thinking (扩展 thinking 字段或 reasoning_effort / reasoning.effort)
+    let reasoning_effort = reasoning_policy::openai_effort(
+        request.reasoning_effort.as_deref(),
+        request.reasoning.as_ref(),
+    );
     let user_enabled_thinking = request.thinking.as_ref()
         .map(|t| t.thinking_type.as_deref() == Some("enabled"))
-        .unwrap_or(false);
+        .unwrap_or(false)
+        || reasoning_effort.is_some_and(|e| e != ReasoningEffort::None);
     let user_thinking_budget = request.thinking.as_ref()
         .and_then(|t| t.budget_tokens);
 
@@ -79,11 +102,24 @@ pub fn transform_openai_request(
     // [NEW] 日志：用户显式设置 thinking
     if user_enabled_thinking {
         tracing::info!(
-            "[OpenAI-Thinking] User explicitly enabled thinking with budget: {:?}",
-            user_thinking_budget
+            "[OpenAI-Thinking] User explicitly enabled thinking with budget: {:?}, effort: {:?}",
+            user_thinking_budget,
+            reasoning_effort
         );
     }
 
+    // 统一推理策略: budget_tokens > effort > 默认 32000；effort = none 时关闭思考
+    let reasoning = ReasoningRequest {
+        budget_tokens: user_thinking_budget,
+        effort: reasoning_effort,
+    };
+    let thinking_plan = if actual_include_thinking || reasoning_effort == Some(ReasoningEffort::None) {
+        reasoning_policy::resolve_thinking(mapped_model, &reasoning, 32000, false)
+    } else {
+        None
+    };
+    actual_include_thinking = thinking_plan.is_some_and(|plan| plan.include_thoughts);
+
     tracing::debug!(
         "[Debug] OpenAI Request: original='{}', mapped='{}', type='{}', has_image_config={}",
         request.model,
@@ -296,6 +332,20 @@ pub fn transform_openai_request(
                                     // 这会与 v3.3.16 的 thinkingConfig 逻辑冲突，留待后续版本实现
                                     tracing::debug!("[OpenAI-Request] Skipping audio_url (not yet implemented in v3.3.16)");
                                 }
+                                OpenAIContentBlock::File { file } => {
+                                    if let Some(part) = file_block_to_part(file.file_data.as_deref(), None, None) {
+                                        parts.push(part);
+                                    }
+                                }
+                                OpenAIContentBlock::InputFile { file_data, file_url, mime_type, .. } => {
+                                    if let Some(part) = file_block_to_part(
+                                        file_data.as_deref(),
+                                        file_url.as_deref(),
+                                        mime_type.as_deref(),
+                                    ) {
+                                        parts.push(part);
+                                    }
+                                }
                             }
                         }
                     }
@@ -357,6 +407,12 @@ pub fn transform_openai_request(
                     Some(OpenAIContent::Array(blocks)) => blocks.iter().filter_map(|b| if let OpenAIContentBlock::Text { text } = b { Some(text.clone()) } else { None }).collect::<Vec<_>>().join("\n"),
                     None => "".to_string()
                 };
+                // 超大工具输出按工具配置的策略压缩 (JSON / 日志 / Diff / 表格)
+                let content_val = crate::proxy::mappers::tool_result_compressor::compact_tool_result_text_for_tool(
+                    &content_val,
+                    MAX_TOOL_RESULT_CHARS,
+                    final_name,
+                );
 
                 parts.push(json!({
                     "functionResponse": {
@@ -408,69 +464,40 @@ pub fn transform_openai_request(
         gen_config["candidateCount"] = json!(n);
     }
 
-    // 为 thinking 模型注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
-    if actual_include_thinking {
-        // [CONFIGURABLE] 根据用户配置决定 thinking_budget 处理方式
-        let tb_config = crate::proxy::config::get_thinking_budget_config();
-        let user_budget: i64 = user_thinking_budget.map(|b| b as i64).unwrap_or(32000);
-        
-        let budget = match tb_config.mode {
-            crate::proxy::config::ThinkingBudgetMode::Passthrough => {
-                // 透传模式：使用用户传入的值，不做任何限制
-                tracing::debug!(
-                    "[OpenAI-Request] Passthrough mode: using caller's budget {}",
-                    user_budget
-                );
-                user_budget
-            }
-            crate::proxy::config::ThinkingBudgetMode::Custom => {
-                // 自定义模式：使用全局配置的固定值
-                let custom_value = tb_config.custom_value as i64;
-                tracing::debug!(
-                    "[OpenAI-Request] Custom mode: overriding {} with fixed value {}",
-                    user_budget,
-                    custom_value
-                );
-                custom_value
-            }
-            crate::proxy::config::ThinkingBudgetMode::Auto => {
-                // 自动模式：保持原有 Flash capping 逻辑 (向后兼容)
-                let is_gemini_limited = mapped_model_lower.contains("flash") 
-                    || mapped_model_lower.contains("gemini-1.5")
-                    || is_claude_thinking;  // Claude thinking 模型转发到 Gemini，同样需要限流
-                
-                if is_gemini_limited && user_budget > 24576 {
-                    tracing::info!(
-                        "[OpenAI-Request] Auto mode: capping thinking budget from {} to 24576 for model: {}", 
-                        user_budget, mapped_model
-                    );
-                    24576
-                } else {
-                    user_budget
-                }
-            }
-        };
+    // 采样参数 (取值范围与模型能力已由 validate_sampling_params 校验)
+    if let Some(seed) = request.seed.and_then(|seed| i32::try_from(seed).ok()) {
+        gen_config["seed"] = json!(seed);
+    }
+    if let Some(penalty) = request.presence_penalty {
+        gen_config["presencePenalty"] = json!(penalty);
+    }
+    if let Some(penalty) = request.frequency_penalty {
+        gen_config["frequencyPenalty"] = json!(penalty);
+    }
+    if let Ok(Some(top_logprobs)) = requested_logprobs(request) {
+        gen_config["responseLogprobs"] = json!(true);
+        if top_logprobs > 0 {
+            gen_config["logprobs"] = json!(top_logprobs.min(MAX_TOP_LOGPROBS));
+        }
+    }
 
-        gen_config["thinkingConfig"] = json!({
-            "includeThoughts": true,
-            "thinkingBudget": budget
-        });
+    // 为 thinking 模型注入 thinkingConfig (使用 thinkingBudget 而非 thinkingLevel)
+    if let Some(plan) = thinking_plan {
+        gen_config["thinkingConfig"] = plan.to_gemini();
 
         // [CRITICAL] 思维模型的 maxOutputTokens 必须大于 thinkingBudget
         // 如果当前 maxOutputTokens 未设置或小于预算，强制提升
-        let current_max = gen_config["maxOutputTokens"].as_i64().unwrap_or(0);
-        if current_max <= budget {
-            let new_max = budget + 8192; // 预留 8k 给实际回答
+        if let Some(new_max) = plan.max_output_tokens(gen_config["maxOutputTokens"].as_i64()) {
             gen_config["maxOutputTokens"] = json!(new_max);
             tracing::debug!(
                 "[OpenAI-Request] Adjusted maxOutputTokens to {} for thinking model (budget={})",
-                new_max, budget
+                new_max, plan.budget
             );
         }
-        
+
         tracing::debug!(
-            "[OpenAI-Request] Injected thinkingConfig for model {}: thinkingBudget={} (mode={:?})",
-            mapped_model, budget, tb_config.mode
+            "[OpenAI-Request] Injected thinkingConfig for model {}: thinkingBudget={}, includeThoughts={}",
+            mapped_model, plan.budget, plan.include_thoughts
         );
     }
 
@@ -550,7 +577,11 @@ pub fn transform_openai_request(
 
             if let Some(params) = gemini_func.get_mut("parameters") {
                 // [DEEP FIX] 统一调用公共库清洗：展开 $ref 并剔除所有层级的 format/definitions
-                crate::proxy::common::json_schema::clean_json_schema(params);
+                crate::proxy::common::json_schema::clean_json_schema_for_client_tool(
+                    params,
+                    name_opt.as_deref().unwrap_or_default(),
+                    tool_name_style,
+                );
 
                 // Gemini v1internal 要求：
                 // 1. type 必须是大写 (OBJECT, STRING 等)
@@ -655,6 +686,258 @@ pub fn transform_openai_request(
     (final_body, session_id, message_count)
 }
 
+/// 解析 logprobs 请求，返回需要的候选 Token 数 (None 表示未请求)
+///
+/// Chat Completions 使用 logprobs=true + top_logprobs，旧版 Completions 的 logprobs 直接是整数。
+pub fn requested_logprobs(request: &OpenAIRequest) -> Result<Option<u32>, String> {
+    let top_logprobs = match &request.logprobs {
+        None | Some(Value::Null) | Some(Value::Bool(false)) => None,
+        Some(Value::Bool(true)) => Some(request.top_logprobs.unwrap_or(0)),
+        Some(Value::Number(n)) => Some(
+            n.as_u64()
+                .map(|v| v.min(u32::MAX as u64) as u32)
+                .ok_or("`logprobs` must be a boolean or a non-negative integer")?,
+        ),
+        Some(_) => return Err("`logprobs` must be a boolean or a non-negative integer".to_string()),
+    };
+
+    match top_logprobs {
+        None if request.top_logprobs.is_some_and(|n| n > 0) => {
+            Err("`top_logprobs` requires `logprobs` to be true".to_string())
+        }
+        Some(n) if n > MAX_TOP_LOGPROBS => Err(format!(
+            "At most {} top logprobs can be requested, got {}",
+            MAX_TOP_LOGPROBS, n
+        )),
+        _ => Ok(top_logprobs),
+    }
+}
+
+/// 校验无法映射到上游的采样参数 (n / logprobs / seed / penalty / logit_bias)
+///
+/// 返回的错误信息应以 400 invalid_request_error 返回给客户端，需在进入重试循环前调用。
+pub fn validate_sampling_params(request: &OpenAIRequest, mapped_model: &str) -> Result<(), String> {
+    if request.logit_bias.as_ref().is_some_and(|bias| !bias.is_empty()) {
+        return Err("`logit_bias` is not supported: the upstream Gemini API has no token bias control".to_string());
+    }
+
+    let n = request.n.unwrap_or(1);
+    if n == 0 || n > MAX_CANDIDATE_COUNT {
+        return Err(format!("`n` must be between 1 and {}, got {}", MAX_CANDIDATE_COUNT, n));
+    }
+
+    for (param, value) in [
+        ("presence_penalty", request.presence_penalty),
+        ("frequency_penalty", request.frequency_penalty),
+    ] {
+        if let Some(v) = value.filter(|v| !(-2.0..=2.0).contains(v)) {
+            return Err(format!("`{}` must be between -2.0 and 2.0, got {}", param, v));
+        }
+    }
+
+    // Gemini seed 为 int32，超出范围的值不能静默截断
+    if let Some(seed) = request.seed.filter(|seed| i32::try_from(*seed).is_err()) {
+        return Err(format!("`seed` must be a 32-bit signed integer, got {}", seed));
+    }
+
+    let logprobs = requested_logprobs(request)?;
+    let has_penalty = request.presence_penalty.is_some_and(|v| v != 0.0)
+        || request.frequency_penalty.is_some_and(|v| v != 0.0);
+
+    // 目标模型能力: Claude 系列只接受基础采样参数；图像模型不返回 logprobs，也不支持惩罚项
+    let lower = mapped_model.to_lowercase();
+    let unsupported = if lower.contains("claude") {
+        [
+            ("n", n > 1),
+            ("logprobs", logprobs.is_some()),
+            ("presence_penalty / frequency_penalty", has_penalty),
+            ("seed", request.seed.is_some()),
+        ]
+        .into_iter()
+        .find(|(_, used)| *used)
+    } else if lower.contains("image") {
+        [
+            ("logprobs", logprobs.is_some()),
+            ("presence_penalty / frequency_penalty", has_penalty),
+        ]
+        .into_iter()
+        .find(|(_, used)| *used)
+    } else {
+        None
+    };
+
+    match unsupported {
+        Some((param, _)) => Err(format!(
+            "`{}` is not supported for model '{}'",
+            param, mapped_model
+        )),
+        None => Ok(()),
+    }
+}
+
+/// 文件块 → Gemini part (仅处理经 resolve_document_inputs 归一化后的 PDF / 媒体文件)
+fn file_block_to_part(
+    file_data: Option<&str>,
+    file_url: Option<&str>,
+    mime_type: Option<&str>,
+) -> Option<Value> {
+    use crate::proxy::common::document_input::{mime_from_name, parse_data_url, PDF_MIME};
+
+    if let Some((mime, data)) = file_data.and_then(parse_data_url) {
+        let mime_type = if mime.is_empty() { PDF_MIME } else { mime };
+        return Some(json!({ "inlineData": { "mimeType": mime_type, "data": data } }));
+    }
+    if let Some(url) = file_url.filter(|u| u.starts_with("http")) {
+        // 扩展名与响应头均无法确定类型时按 PDF 处理 (input_file 最常见的类型)
+        let mime_type = mime_type
+            .or_else(|| mime_from_name(url))
+            .unwrap_or(PDF_MIME);
+        return Some(json!({ "fileData": { "fileUri": url, "mimeType": mime_type } }));
+    }
+    tracing::debug!("[OpenAI-Request] Skipping unresolved file part");
+    None
+}
+
+/// 预处理请求中的文件输入 (file / input_file)
+///
+/// file_id 从本地 Files API 存储中 owner 名下的文件读取。PDF 与图片等媒体归一化为 data URL 后由
+/// transform_openai_request 映射为 inlineData；docx / xlsx / 纯文本在本地提取为文本块。需在进入重试循环前调用，失败时应返回 4xx。
+pub fn resolve_document_inputs(
+    request: &mut OpenAIRequest,
+    owner: Option<&str>,
+) -> Result<(), crate::proxy::common::document_input::DocumentInputError> {
+    resolve_document_inputs_with(request, |id| {
+        crate::proxy::files::resolve_file_reference(id, owner)
+    })
+}
+
+/// 为 http(s) file_url 文件块补全 MIME: 优先按 filename / URL 扩展名推断，
+/// 无法推断时以 HEAD 请求读取响应的 Content-Type。需在 resolve_document_inputs 之后调用。
+pub async fn probe_remote_file_types(request: &mut OpenAIRequest) {
+    use crate::proxy::common::document_input::mime_from_name;
+
+    for msg in request.messages.iter_mut() {
+        let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let OpenAIContentBlock::InputFile {
+                filename,
+                file_url: Some(url),
+                mime_type,
+                ..
+            } = block
+            else {
+                continue;
+            };
+            if mime_type.is_some() || !url.starts_with("http") {
+                continue;
+            }
+            let guessed = filename
+                .as_deref()
+                .and_then(mime_from_name)
+                .or_else(|| mime_from_name(url));
+            *mime_type = match guessed {
+                Some(mime) => Some(mime.to_string()),
+                None => head_content_type(url).await,
+            };
+        }
+    }
+}
+
+/// HEAD 请求读取远程文件的 Content-Type (失败或为通用二进制类型时返回 None)
+async fn head_content_type(url: &str) -> Option<String> {
+    let resp = match crate::utils::http::get_client().head(url).send().await {
+        Ok(resp) if resp.status().is_success() => resp,
+        Ok(resp) => {
+            tracing::debug!("[OpenAI-Request] HEAD {} returned {}", url, resp.status());
+            return None;
+        }
+        Err(e) => {
+            tracing::debug!("[OpenAI-Request] HEAD {} failed: {}", url, e);
+            return None;
+        }
+    };
+    let value = resp.headers().get(reqwest::header::CONTENT_TYPE)?.to_str().ok()?;
+    let mime = value.split(';').next()?.trim().to_ascii_lowercase();
+    (!mime.is_empty() && mime != "application/octet-stream").then_some(mime)
+}
+
+/// 同 resolve_document_inputs，file_id 由 resolve_file 解析
+fn resolve_document_inputs_with<F>(
+    request: &mut OpenAIRequest,
+    resolve_file: F,
+) -> Result<(), crate::proxy::common::document_input::DocumentInputError>
+where
+    F: Fn(&str) -> Result<
+        crate::proxy::common::document_input::ResolvedDocument,
+        crate::proxy::common::document_input::DocumentInputError,
+    >,
+{
+    use crate::proxy::common::document_input::{
+        resolve_document_data, wrap_extracted_text, DocumentInputError, ResolvedDocument,
+    };
+
+    for msg in request.messages.iter_mut() {
+        let Some(OpenAIContent::Array(blocks)) = msg.content.as_mut() else {
+            continue;
+        };
+        for block in blocks.iter_mut() {
+            let (file_data, file_id, filename, file_url) = match block {
+                OpenAIContentBlock::File { file } => (
+                    file.file_data.clone(),
+                    file.file_id.clone(),
+                    file.filename.clone(),
+                    None,
+                ),
+                OpenAIContentBlock::InputFile {
+                    file_data,
+                    file_id,
+                    filename,
+                    file_url,
+                    ..
+                } => (
+                    file_data.clone(),
+                    file_id.clone(),
+                    filename.clone(),
+                    file_url.clone(),
+                ),
+                _ => continue,
+            };
+
+            // file_url 为 data URL 时与 file_data 等价；http(s) 链接交由上游拉取
+            let file_data = file_data.or_else(|| file_url.clone().filter(|u| u.starts_with("data:")));
+            let resolved = if let Some(file_data) = file_data {
+                resolve_document_data(&file_data, None, filename.as_deref())?
+            } else if let Some(id) = file_id.as_deref() {
+                // 通过本地 Files API 上传的文件
+                resolve_file(id)?
+            } else if file_url.as_deref().map(|u| u.starts_with("http")).unwrap_or(false) {
+                continue;
+            } else {
+                return Err(DocumentInputError::Invalid(format!(
+                    "File '{}' has no file_data or file_id",
+                    filename.as_deref().unwrap_or("document")
+                )));
+            };
+
+            *block = match resolved {
+                ResolvedDocument::Inline { mime_type, data } => OpenAIContentBlock::File {
+                    file: OpenAIFile {
+                        file_data: Some(format!("data:{};base64,{}", mime_type, data)),
+                        file_id: None,
+                        filename,
+                    },
+                },
+                ResolvedDocument::Text(text) => OpenAIContentBlock::Text {
+                    text: wrap_extracted_text(filename.as_deref(), &text),
+                },
+            };
+        }
+    }
+    Ok(())
+}
+
 fn enforce_uppercase_types(value: &mut Value) {
     if let Value::Object(map) = value {
         if let Some(type_val) = map.get_mut("type") {
@@ -700,6 +983,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             }],
             stream: false,
             n: None,
@@ -717,6 +1001,14 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            reasoning_effort: None,
+            reasoning: None,
+            logprobs: None,
+            top_logprobs: None,
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
             thinking: None,
         };
 
@@ -741,6 +1033,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             }],
             stream: false,
             n: None,
@@ -763,6 +1056,14 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            reasoning_effort: None,
+            reasoning: None,
+            logprobs: None,
+            top_logprobs: None,
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
         };
 
         // Pass explicit gemini-3-pro-preview which doesn't have "-thinking" suffix
@@ -787,6 +1088,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             }],
             stream: false,
             n: None,
@@ -804,6 +1106,14 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            reasoning_effort: None,
+            reasoning: None,
+            logprobs: None,
+            top_logprobs: None,
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
             thinking: None,
         };
 
@@ -831,6 +1141,7 @@ mod tests {
                 tool_calls: None,
                 tool_call_id: None,
                 name: None,
+                annotations: None,
             }],
             stream: false,
             n: None,
@@ -853,6 +1164,14 @@ mod tests {
             size: None,
             quality: None,
             person_generation: None,
+            reasoning_effort: None,
+            reasoning: None,
+            logprobs: None,
+            top_logprobs: None,
+            seed: None,
+            presence_penalty: None,
+            frequency_penalty: None,
+            logit_bias: None,
         };
 
         // Test with Flash model
@@ -867,4 +1186,199 @@ mod tests {
         let max_output = gen_config["maxOutputTokens"].as_i64().unwrap();
         assert_eq!(max_output, 32768);
     }
+
+    #[test]
+    fn test_sampling_params_map_to_generation_config() {
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-4o",
+            "messages": [{ "role": "user", "content": "Hello" }],
+            "n": 3,
+            "logprobs": true,
+            "top_logprobs": 5,
+            "seed": 42,
+            "presence_penalty": 0.5,
+            "frequency_penalty": -0.5
+        }))
+        .unwrap();
+        assert!(validate_sampling_params(&req, "gemini-2.5-flash").is_ok());
+
+        let (result, _sid, _msg_count) = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["candidateCount"], 3);
+        assert_eq!(gen_config["responseLogprobs"], true);
+        assert_eq!(gen_config["logprobs"], 5);
+        assert_eq!(gen_config["seed"], 42);
+        assert_eq!(gen_config["presencePenalty"], 0.5);
+        assert_eq!(gen_config["frequencyPenalty"], -0.5);
+
+        // 旧版 Completions: logprobs 为整数
+        let legacy: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-3.5-turbo-instruct",
+            "prompt": "Hello",
+            "logprobs": 2
+        }))
+        .unwrap();
+        assert_eq!(requested_logprobs(&legacy), Ok(Some(2)));
+    }
+
+    #[test]
+    fn test_unsupported_sampling_params_are_rejected() {
+        let request = |extra: Value| -> OpenAIRequest {
+            let mut body = json!({
+                "model": "gpt-4o",
+                "messages": [{ "role": "user", "content": "Hello" }]
+            });
+            body.as_object_mut()
+                .unwrap()
+                .extend(extra.as_object().unwrap().clone());
+            serde_json::from_value(body).unwrap()
+        };
+
+        let err = validate_sampling_params(&request(json!({ "logit_bias": { "50256": -100 } })), "gemini-2.5-flash").unwrap_err();
+        assert!(err.contains("logit_bias"));
+        assert!(validate_sampling_params(&request(json!({ "logit_bias": {} })), "gemini-2.5-flash").is_ok());
+
+        assert!(validate_sampling_params(&request(json!({ "n": 0 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "n": 9 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "top_logprobs": 3 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "logprobs": true, "top_logprobs": 21 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "presence_penalty": 2.5 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "seed": 4_294_967_296_i64 })), "gemini-2.5-flash").is_err());
+        assert!(validate_sampling_params(&request(json!({ "seed": -2_147_483_648_i64 })), "gemini-2.5-flash").is_ok());
+
+        // 目标模型不支持
+        let err = validate_sampling_params(&request(json!({ "logprobs": true })), "claude-sonnet-4-5").unwrap_err();
+        assert!(err.contains("logprobs") && err.contains("claude-sonnet-4-5"));
+        assert!(validate_sampling_params(&request(json!({ "n": 2 })), "claude-sonnet-4-5").is_err());
+        assert!(validate_sampling_params(&request(json!({ "presence_penalty": 0.0 })), "claude-sonnet-4-5").is_ok());
+        assert!(validate_sampling_params(&request(json!({ "logprobs": true })), "gemini-3-pro-image").is_err());
+    }
+
+    #[test]
+    fn test_reasoning_effort_maps_to_thinking_budget() {
+        // Chat reasoning_effort 开启思考并换算预算
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-5",
+            "messages": [{ "role": "user", "content": "Hello" }],
+            "reasoning_effort": "low"
+        }))
+        .unwrap();
+        let (result, _sid, _msg_count) = transform_openai_request(&req, "test-p", "gemini-3-pro-preview");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["thinkingConfig"]["thinkingBudget"], 4096);
+        assert_eq!(gen_config["thinkingConfig"]["includeThoughts"], true);
+
+        // Responses reasoning.effort = none: Flash 模型显式关闭思考
+        let req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gpt-5",
+            "messages": [{ "role": "user", "content": "Hello" }],
+            "reasoning": { "effort": "none" }
+        }))
+        .unwrap();
+        let (result, _sid, _msg_count) = transform_openai_request(&req, "test-p", "gemini-2.5-flash");
+        let gen_config = &result["request"]["generationConfig"];
+        assert_eq!(gen_config["thinkingConfig"]["thinkingBudget"], 0);
+        assert_eq!(gen_config["thinkingConfig"]["includeThoughts"], false);
+    }
+
+    #[test]
+    fn test_file_parts_are_resolved_to_inline_data_and_text() {
+        use base64::Engine as _;
+        let pdf = base64::engine::general_purpose::STANDARD.encode(b"%PDF-1.4 demo");
+        let notes = base64::engine::general_purpose::STANDARD.encode("hello notes");
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "text", "text": "Summarize" },
+                    { "type": "file", "file": { "filename": "a.pdf", "file_data": format!("data:application/pdf;base64,{}", pdf) } },
+                    { "type": "input_file", "filename": "notes.txt", "file_data": notes }
+                ]
+            }]
+        }))
+        .unwrap();
+
+        resolve_document_inputs(&mut req, None).unwrap();
+        let (result, _sid, _msg_count) = transform_openai_request(&req, "test-v", "gemini-2.5-flash");
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts[1]["inlineData"]["mimeType"], "application/pdf");
+        assert_eq!(parts[1]["inlineData"]["data"], pdf.as_str());
+        assert_eq!(parts[2]["text"], "<document name=\"notes.txt\">\nhello notes\n</document>");
+    }
+
+    #[tokio::test]
+    async fn test_remote_file_url_mime_type() {
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [
+                    { "type": "input_file", "file_url": "https://example.com/files/chart.png?v=2" },
+                    { "type": "input_file", "filename": "clip.mp4", "file_url": "https://example.com/download/7" },
+                    { "type": "input_file", "file_url": "https://example.com/doc", "mime_type": "text/html" }
+                ]
+            }]
+        }))
+        .unwrap();
+
+        // 均可由扩展名或显式 mime_type 确定，不发起 HEAD 请求
+        resolve_document_inputs(&mut req, None).unwrap();
+        probe_remote_file_types(&mut req).await;
+        let (result, _sid, _msg_count) = transform_openai_request(&req, "test-u", "gemini-2.5-flash");
+        let parts = &result["request"]["contents"][0]["parts"];
+        assert_eq!(parts[0]["fileData"]["mimeType"], "image/png");
+        assert_eq!(parts[1]["fileData"]["mimeType"], "video/mp4");
+        assert_eq!(parts[2]["fileData"]["mimeType"], "text/html");
+        assert_eq!(parts[2]["fileData"]["fileUri"], "https://example.com/doc");
+    }
+
+    #[test]
+    fn test_unknown_file_id_is_rejected() {
+        let mut req: OpenAIRequest = serde_json::from_value(json!({
+            "model": "gemini-2.5-flash",
+            "messages": [{
+                "role": "user",
+                "content": [{ "type": "file", "file": { "file_id": "file-abc" } }]
+            }]
+        }))
+        .unwrap();
+
+        // 使用临时目录下的存储，不读取用户数据目录
+        let dir = std::env::temp_dir().join(format!("abv-files-{}", uuid::Uuid::new_v4()));
+        let store = crate::proxy::files::FileStore::new(dir.clone());
+        let err = resolve_document_inputs_with(&mut req, |id| store.resolve(id, Some("alice")))
+            .unwrap_err();
+        assert!(err.to_string().contains("file-abc"));
+
+        // 已上传的文件可按 file_id 解析
+        let stored = store
+            .put(
+                b"uploaded notes",
+                "notes.txt",
+                "text/plain",
+                "user_data",
+                60,
+                Some("alice"),
+            )
+            .unwrap();
+        req.messages[0].content = serde_json::from_value(json!([
+            { "type": "file", "file": { "file_id": stored.id, "filename": "notes.txt" } }
+        ]))
+        .unwrap();
+        // 其他 User Token 无法引用该文件
+        let mut other = req.clone();
+        assert!(
+            resolve_document_inputs_with(&mut other, |id| store.resolve(id, Some("bob"))).is_err()
+        );
+        resolve_document_inputs_with(&mut req, |id| store.resolve(id, Some("alice"))).unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+        match req.messages[0].content.as_ref() {
+            Some(OpenAIContent::Array(blocks)) => match &blocks[0] {
+                OpenAIContentBlock::Text { text } => assert!(text.contains("uploaded notes")),
+                other => panic!("unexpected block: {:?}", other),
+            },
+            other => panic!("unexpected content: {:?}", other),
+        }
+    }
 }
@@ -89,7 +89,16 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
             }
 
             // 提取并处理该候选结果的联网搜索引文 (Grounding Metadata)
+            let mut annotations = Vec::new();
             if let Some(grounding) = candidate.get("groundingMetadata") {
+                // groundingSupports -> url_citation annotations (在追加来源列表前定位)
+                let spans = crate::proxy::mappers::citations::grounding_spans_from_metadata(
+                    &content_out,
+                    grounding,
+                );
+                annotations =
+                    crate::proxy::mappers::citations::url_citation_annotations(&content_out, &spans);
+
                 let mut grounding_text = String::new();
 
                 // 1. 处理搜索词
@@ -141,7 +150,10 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
                 .unwrap_or("stop");
 
             choices.push(Choice {
-                index: idx as u32,
+                index: candidate
+                    .get("index")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(idx as u64) as u32,
                 message: OpenAIMessage {
                     role: "assistant".to_string(),
                     content: if content_out.is_empty() {
@@ -161,8 +173,16 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
                     },
                     tool_call_id: None,
                     name: None,
+                    annotations: if annotations.is_empty() {
+                        None
+                    } else {
+                        Some(annotations)
+                    },
                 },
                 finish_reason: Some(finish_reason.to_string()),
+                logprobs: candidate
+                    .get("logprobsResult")
+                    .and_then(super::logprobs::chat_logprobs),
             });
         }
     }
@@ -173,10 +193,16 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
             .get("promptTokenCount")
             .and_then(|v| v.as_u64())
             .unwrap_or(0) as u32;
+        let reasoning_tokens = u
+            .get("thoughtsTokenCount")
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32);
+        // OpenAI 口径的 completion_tokens 包含 reasoning_tokens
         let completion_tokens = u
             .get("candidatesTokenCount")
             .and_then(|v| v.as_u64())
-            .unwrap_or(0) as u32;
+            .unwrap_or(0) as u32
+            + reasoning_tokens.unwrap_or(0);
         let total_tokens = u
             .get("totalTokenCount")
             .and_then(|v| v.as_u64())
@@ -193,7 +219,11 @@ pub fn transform_openai_response(gemini_response: &Value, session_id: Option<&st
             prompt_tokens_details: cached_tokens.map(|ct| super::models::PromptTokensDetails {
                 cached_tokens: Some(ct),
             }),
-            completion_tokens_details: None,
+            completion_tokens_details: reasoning_tokens.map(|rt| {
+                super::models::CompletionTokensDetails {
+                    reasoning_tokens: Some(rt),
+                }
+            }),
         })
     });
 
@@ -243,6 +273,37 @@ mod tests {
         assert_eq!(result.choices[0].finish_reason, Some("stop".to_string()));
     }
 
+    #[test]
+    fn test_multiple_candidates_with_logprobs() {
+        let gemini_resp = json!({
+            "candidates": [
+                {
+                    "index": 0,
+                    "content": {"parts": [{"text": "A"}]},
+                    "finishReason": "STOP",
+                    "logprobsResult": {
+                        "chosenCandidates": [{"token": "A", "logProbability": -0.2}],
+                        "topCandidates": [{"candidates": [{"token": "A", "logProbability": -0.2}]}]
+                    }
+                },
+                {
+                    "index": 1,
+                    "content": {"parts": [{"text": "B"}]},
+                    "finishReason": "MAX_TOKENS"
+                }
+            ]
+        });
+
+        let result = transform_openai_response(&gemini_resp, None, 1);
+        assert_eq!(result.choices.len(), 2);
+        assert_eq!(result.choices[1].index, 1);
+        assert_eq!(result.choices[1].finish_reason.as_deref(), Some("length"));
+        let logprobs = result.choices[0].logprobs.as_ref().unwrap();
+        assert_eq!(logprobs["content"][0]["token"], "A");
+        assert_eq!(logprobs["content"][0]["logprob"], -0.2);
+        assert!(result.choices[1].logprobs.is_none());
+    }
+
     #[test]
     fn test_usage_metadata_mapping() {
         let gemini_resp = json!({
@@ -271,6 +332,27 @@ mod tests {
         assert_eq!(usage.prompt_tokens_details.unwrap().cached_tokens, Some(25));
     }
 
+    #[test]
+    fn test_usage_metadata_reasoning_tokens() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Hello!"}]},
+                "finishReason": "STOP"
+            }],
+            "usageMetadata": {
+                "promptTokenCount": 100,
+                "candidatesTokenCount": 50,
+                "thoughtsTokenCount": 30,
+                "totalTokenCount": 180
+            }
+        });
+
+        let usage = transform_openai_response(&gemini_resp, None, 1).usage.unwrap();
+        assert_eq!(usage.completion_tokens, 80);
+        assert_eq!(usage.total_tokens, 180);
+        assert_eq!(usage.completion_tokens_details.unwrap().reasoning_tokens, Some(30));
+    }
+
     #[test]
     fn test_response_without_usage_metadata() {
         let gemini_resp = json!({
@@ -285,4 +367,29 @@ mod tests {
         let result = transform_openai_response(&gemini_resp, Some("session-123"), 1);
         assert!(result.usage.is_none());
     }
+
+    #[test]
+    fn test_grounding_supports_become_url_citation_annotations() {
+        let gemini_resp = json!({
+            "candidates": [{
+                "content": {"parts": [{"text": "Rust 1.0 shipped in 2015. It is fast."}]},
+                "finishReason": "STOP",
+                "groundingMetadata": {
+                    "groundingChunks": [{"web": {"uri": "https://blog.rust-lang.org", "title": "Rust Blog"}}],
+                    "groundingSupports": [{
+                        "segment": {"startIndex": 0, "endIndex": 25, "text": "Rust 1.0 shipped in 2015."},
+                        "groundingChunkIndices": [0]
+                    }]
+                }
+            }]
+        });
+
+        let result = transform_openai_response(&gemini_resp, None, 1);
+        let annotations = result.choices[0].message.annotations.as_ref().unwrap();
+        assert_eq!(annotations.len(), 1);
+        assert_eq!(annotations[0]["type"], "url_citation");
+        assert_eq!(annotations[0]["url_citation"]["url"], "https://blog.rust-lang.org");
+        assert_eq!(annotations[0]["url_citation"]["start_index"], 0);
+        assert_eq!(annotations[0]["url_citation"]["end_index"], 25);
+    }
 }
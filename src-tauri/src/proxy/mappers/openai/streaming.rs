@@ -57,16 +57,22 @@ pub fn get_thought_signature() -> Option<String> {
 
 /// Extract and convert Gemini usageMetadata to OpenAI usage format
 fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
-    use super::models::{OpenAIUsage, PromptTokensDetails};
+    use super::models::{CompletionTokensDetails, OpenAIUsage, PromptTokensDetails};
 
     let prompt_tokens = u
         .get("promptTokenCount")
         .and_then(|v| v.as_u64())
         .unwrap_or(0) as u32;
+    let reasoning_tokens = u
+        .get("thoughtsTokenCount")
+        .and_then(|v| v.as_u64())
+        .map(|v| v as u32);
+    // OpenAI 口径的 completion_tokens 包含 reasoning_tokens
     let completion_tokens = u
         .get("candidatesTokenCount")
         .and_then(|v| v.as_u64())
-        .unwrap_or(0) as u32;
+        .unwrap_or(0) as u32
+        + reasoning_tokens.unwrap_or(0);
     let total_tokens = u
         .get("totalTokenCount")
         .and_then(|v| v.as_u64())
@@ -83,7 +89,9 @@ fn extract_usage_metadata(u: &Value) -> Option<super::models::OpenAIUsage> {
         prompt_tokens_details: cached_tokens.map(|ct| PromptTokensDetails {
             cached_tokens: Some(ct),
         }),
-        completion_tokens_details: None,
+        completion_tokens_details: reasoning_tokens.map(|rt| CompletionTokensDetails {
+            reasoning_tokens: Some(rt),
+        }),
     })
 }
 
@@ -99,8 +107,15 @@ pub fn create_openai_sse_stream(
 
     let stream = async_stream::stream! {
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        // 每个候选已发出的工具调用数量 (tool_calls[].index 与 finish_reason 按候选区分)
+        let mut tool_call_counts: std::collections::HashMap<usize, u32> = std::collections::HashMap::new();
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
         let mut error_occurred = false;
+        // 每个候选已输出的正文, 用于计算 url_citation 的字符下标
+        let mut candidate_texts: std::collections::HashMap<usize, String> = std::collections::HashMap::new();
+        // n > 1 时 usage 只在全部候选结束后随最后一个 choice 输出一次
+        let mut seen_candidates: std::collections::HashSet<usize> = std::collections::HashSet::new();
+        let mut finished_candidates: std::collections::HashSet<usize> = std::collections::HashSet::new();
 
         let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -126,7 +141,10 @@ pub fn create_openai_sse_stream(
                                             }
 
                                             if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                                for (idx, candidate) in candidates.iter().enumerate() {
+                                                for (pos, candidate) in candidates.iter().enumerate() {
+                                                    // n > 1 时分片可能只包含部分候选，以 candidate.index 为准
+                                                    let idx = candidate.get("index").and_then(|v| v.as_u64()).map(|i| i as usize).unwrap_or(pos);
+                                                    seen_candidates.insert(idx);
                                                     let parts = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array());
                                                     let mut content_out = String::new();
                                                     let mut thought_out = String::new();
@@ -149,7 +167,7 @@ pub fn create_openai_sse_stream(
                                                                 }
                                                             }
                                                             if let Some(func_call) = part.get("functionCall") {
-                                                                let call_key = serde_json::to_string(func_call).unwrap_or_default();
+                                                                let call_key = format!("{}:{}", idx, serde_json::to_string(func_call).unwrap_or_default());
                                                                 if !emitted_tool_calls.contains(&call_key) {
                                                                     emitted_tool_calls.insert(call_key);
                                                                     let name = func_call.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
@@ -176,6 +194,9 @@ pub fn create_openai_sse_stream(
                                                                     use std::hash::{Hash, Hasher};
                                                                     serde_json::to_string(func_call).unwrap_or_default().hash(&mut hasher);
                                                                     let call_id = format!("call_{:x}", hasher.finish());
+                                                                    let call_count = tool_call_counts.entry(idx).or_insert(0);
+                                                                    let tool_call_index = *call_count;
+                                                                    *call_count += 1;
 
                                                                     let tool_call_chunk = json!({
                                                                         "id": &stream_id,
@@ -187,7 +208,7 @@ pub fn create_openai_sse_stream(
                                                                             "delta": {
                                                                                 "role": "assistant",
                                                                                 "tool_calls": [{
-                                                                                    "index": 0,
+                                                                                    "index": tool_call_index,
                                                                                     "id": call_id,
                                                                                     "type": "function",
                                                                                     "function": { "name": name, "arguments": args_str }
@@ -203,7 +224,13 @@ pub fn create_openai_sse_stream(
                                                         }
                                                     }
 
+                                                    let mut annotations: Vec<Value> = Vec::new();
                                                     if let Some(grounding) = candidate.get("groundingMetadata") {
+                                                        // groundingSupports -> url_citation annotations (下标基于该候选的完整正文)
+                                                        let full_text = format!("{}{}", candidate_texts.get(&idx).map(|s| s.as_str()).unwrap_or(""), content_out);
+                                                        let spans = crate::proxy::mappers::citations::grounding_spans_from_metadata(&full_text, grounding);
+                                                        annotations = crate::proxy::mappers::citations::url_citation_annotations(&full_text, &spans);
+
                                                         let mut grounding_text = String::new();
                                                         if let Some(queries) = grounding.get("webSearchQueries").and_then(|q| q.as_array()) {
                                                             let query_list: Vec<&str> = queries.iter().filter_map(|v| v.as_str()).collect();
@@ -228,6 +255,7 @@ pub fn create_openai_sse_stream(
                                                         }
                                                         if !grounding_text.is_empty() { content_out.push_str(&grounding_text); }
                                                     }
+                                                    candidate_texts.entry(idx).or_default().push_str(&content_out);
 
                                                     let gemini_finish_reason = candidate.get("finishReason").and_then(|f| f.as_str()).map(|f| match f {
                                                         "STOP" => "stop",
@@ -239,7 +267,7 @@ pub fn create_openai_sse_stream(
 
                                                     // [FIX #1575] 如果发射了工具调用，强制设置为 tool_calls
                                                     // 解决 Gemini 返回 STOP 但有工具调用时，OpenAI 客户端认为对话已结束的问题
-                                                    let finish_reason = if !emitted_tool_calls.is_empty() && gemini_finish_reason.is_some() {
+                                                    let finish_reason = if tool_call_counts.contains_key(&idx) && gemini_finish_reason.is_some() {
                                                         Some("tool_calls")
                                                     } else {
                                                         gemini_finish_reason
@@ -261,7 +289,9 @@ pub fn create_openai_sse_stream(
                                                         yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                                     }
 
-                                                    if !content_out.is_empty() || finish_reason.is_some() {
+                                                    let logprobs = candidate.get("logprobsResult").and_then(super::logprobs::chat_logprobs);
+
+                                                    if !content_out.is_empty() || finish_reason.is_some() || logprobs.is_some() {
                                                         let mut openai_chunk = json!({
                                                             "id": &stream_id,
                                                             "object": "chat.completion.chunk",
@@ -273,10 +303,20 @@ pub fn create_openai_sse_stream(
                                                                 "finish_reason": finish_reason
                                                             }]
                                                         });
-                                                        if let Some(ref usage) = final_usage {
-                                                            openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
+                                                        if !annotations.is_empty() {
+                                                            openai_chunk["choices"][0]["delta"]["annotations"] = json!(annotations);
+                                                        }
+                                                        if let Some(logprobs) = logprobs {
+                                                            openai_chunk["choices"][0]["logprobs"] = logprobs;
+                                                        }
+                                                        if finish_reason.is_some() {
+                                                            finished_candidates.insert(idx);
+                                                            if finished_candidates.len() == seen_candidates.len() {
+                                                                if let Some(usage) = final_usage.take() {
+                                                                    openai_chunk["usage"] = serde_json::to_value(usage).unwrap();
+                                                                }
+                                                            }
                                                         }
-                                                        if finish_reason.is_some() { final_usage = None; }
                                                         let sse_out = format!("data: {}\n\n", serde_json::to_string(&openai_chunk).unwrap_or_default());
                                                         yield Ok::<Bytes, String>(Bytes::from(sse_out));
                                                     }
@@ -333,6 +373,8 @@ pub fn create_legacy_sse_stream(
 
     let stream = async_stream::stream! {
         let mut final_usage: Option<super::models::OpenAIUsage> = None;
+        // 每个候选 logprobs.text_offset 的续接位置
+        let mut text_offsets: std::collections::HashMap<u64, usize> = std::collections::HashMap::new();
         let mut error_occurred = false;
         let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
@@ -355,33 +397,48 @@ pub fn create_legacy_sse_stream(
                                             let actual_data = if let Some(inner) = json.get_mut("response").map(|v| v.take()) { inner } else { json };
                                             if let Some(u) = actual_data.get("usageMetadata") { final_usage = extract_usage_metadata(u); }
 
-                                            let mut content_out = String::new();
-                                            if let Some(candidates) = actual_data.get("candidates").and_then(|c| c.as_array()) {
-                                                if let Some(candidate) = candidates.get(0) {
-                                                    if let Some(parts) = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
-                                                        for part in parts {
-                                                            if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                                content_out.push_str(text);
-                                                            }
-                                                            if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
-                                                                store_thought_signature(sig, &session_id, message_count);
-                                                            }
+                                            // n > 1 时每个候选单独输出一个 choice 分片 (index 取 candidate.index)
+                                            let empty_candidate = json!({});
+                                            let mut candidate_list: Vec<&Value> = actual_data.get("candidates").and_then(|c| c.as_array()).map(|c| c.iter().collect()).unwrap_or_default();
+                                            if candidate_list.is_empty() { candidate_list.push(&empty_candidate); }
+                                            // usage 只在全部候选结束的分片中随最后一个 choice 输出一次
+                                            let frame_finished = candidate_list.iter().all(|c| c.get("finishReason").is_some());
+                                            let last_pos = candidate_list.len() - 1;
+
+                                            for (pos, candidate) in candidate_list.into_iter().enumerate() {
+                                                let idx = candidate.get("index").and_then(|v| v.as_u64()).unwrap_or(pos as u64);
+                                                let mut content_out = String::new();
+                                                if let Some(parts) = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
+                                                    for part in parts {
+                                                        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                                            content_out.push_str(text);
+                                                        }
+                                                        if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
+                                                            store_thought_signature(sig, &session_id, message_count);
                                                         }
                                                     }
                                                 }
-                                            }
 
-                                            let finish_reason = actual_data.get("candidates").and_then(|c| c.as_array()).and_then(|c| c.get(0)).and_then(|c| c.get("finishReason")).and_then(|f| f.as_str()).map(|f| match f {
-                                                "STOP" => "stop", "MAX_TOKENS" => "length", "SAFETY" => "content_filter", _ => f,
-                                            });
-
-                                            let mut legacy_chunk = json!({
-                                                "id": &stream_id, "object": "text_completion", "created": created_ts, "model": &model,
-                                                "choices": [{ "text": content_out, "index": 0, "logprobs": null, "finish_reason": finish_reason }]
-                                            });
-                                            if let Some(ref usage) = final_usage { legacy_chunk["usage"] = serde_json::to_value(usage).unwrap(); }
-                                            if finish_reason.is_some() { final_usage = None; }
-                                            yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&legacy_chunk).unwrap_or_default())));
+                                                let finish_reason = candidate.get("finishReason").and_then(|f| f.as_str()).map(|f| match f {
+                                                    "STOP" => "stop", "MAX_TOKENS" => "length", "SAFETY" => "content_filter", _ => f,
+                                                });
+
+                                                let logprobs = candidate.get("logprobsResult").and_then(super::logprobs::chat_logprobs).map(|lp| {
+                                                    let offset = text_offsets.entry(idx).or_insert(0);
+                                                    let (legacy, next_offset) = super::logprobs::legacy_logprobs(&lp, *offset);
+                                                    *offset = next_offset;
+                                                    legacy
+                                                });
+
+                                                let mut legacy_chunk = json!({
+                                                    "id": &stream_id, "object": "text_completion", "created": created_ts, "model": &model,
+                                                    "choices": [{ "text": content_out, "index": idx, "logprobs": logprobs, "finish_reason": finish_reason }]
+                                                });
+                                                if frame_finished && pos == last_pos {
+                                                    if let Some(usage) = final_usage.take() { legacy_chunk["usage"] = serde_json::to_value(usage).unwrap(); }
+                                                }
+                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&legacy_chunk).unwrap_or_default())));
+                                            }
                                         }
                                     }
                                 }
@@ -418,6 +475,7 @@ pub fn create_codex_sse_stream(
     _model: String,
     session_id: String,
     message_count: usize,
+    reasoning_summary: bool, // Responses reasoning.summary: 以 reasoning 输出项返回思考摘要
 ) -> Pin<Box<dyn Stream<Item = Result<Bytes, String>> + Send>> {
     let mut buffer = BytesMut::new();
     let charset = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
@@ -427,12 +485,19 @@ pub fn create_codex_sse_stream(
         charset.chars().nth(idx).unwrap()
     }).collect();
     let response_id = format!("resp-{}", random_str);
+    let reasoning_id = format!("rs_{}", random_str);
 
     let stream = async_stream::stream! {
         let created_ev = json!({ "type": "response.created", "response": { "id": &response_id, "object": "response" } });
         yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&created_ev).unwrap())));
 
         let mut emitted_tool_calls = std::collections::HashSet::new();
+        // 思考内容只进入 reasoning 摘要 (output_index 0)，不混入 output_text；
+        // 存在 reasoning 项时助手消息使用 output_index 1
+        let mut reasoning_text = String::new();
+        let mut reasoning_started = false;
+        let mut reasoning_closed = false;
+        let mut message_started = false;
         let mut heartbeat_interval = tokio::time::interval(std::time::Duration::from_secs(15));
         heartbeat_interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
 
@@ -456,8 +521,29 @@ pub fn create_codex_sse_stream(
                                             if let Some(candidate) = candidates.get(0) {
                                                 if let Some(parts) = candidate.get("content").and_then(|c| c.get("parts")).and_then(|p| p.as_array()) {
                                                     for part in parts {
-                                                        if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
-                                                            let delta_ev = json!({ "type": "response.output_text.delta", "delta": text });
+                                                        let is_thought = part.get("thought").and_then(|v| v.as_bool()).unwrap_or(false);
+                                                        if is_thought {
+                                                            if let Some(text) = part.get("text").and_then(|t| t.as_str()).filter(|t| reasoning_summary && !message_started && !t.is_empty()) {
+                                                                if !reasoning_started {
+                                                                    reasoning_started = true;
+                                                                    for event in reasoning_added_events(&reasoning_id) {
+                                                                        yield Ok::<Bytes, String>(event);
+                                                                    }
+                                                                }
+                                                                reasoning_text.push_str(text);
+                                                                let delta_ev = json!({ "type": "response.reasoning_summary_text.delta", "item_id": &reasoning_id, "output_index": 0, "summary_index": 0, "delta": text });
+                                                                yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&delta_ev).unwrap())));
+                                                            }
+                                                        } else if let Some(text) = part.get("text").and_then(|t| t.as_str()) {
+                                                            if reasoning_started && !reasoning_closed {
+                                                                reasoning_closed = true;
+                                                                for event in reasoning_done_events(&reasoning_id, &reasoning_text) {
+                                                                    yield Ok::<Bytes, String>(event);
+                                                                }
+                                                            }
+                                                            message_started = true;
+                                                            let message_index = if reasoning_started { 1 } else { 0 };
+                                                            let delta_ev = json!({ "type": "response.output_text.delta", "output_index": message_index, "content_index": 0, "delta": text });
                                                             yield Ok::<Bytes, String>(Bytes::from(format!("data: {}\n\n", serde_json::to_string(&delta_ev).unwrap())));
                                                         }
                                                         if let Some(sig) = part.get("thoughtSignature").or(part.get("thought_signature")).and_then(|s| s.as_str()) {
@@ -485,6 +571,196 @@ pub fn create_codex_sse_stream(
                 _ = heartbeat_interval.tick() => { yield Ok::<Bytes, String>(Bytes::from(": ping\n\n")); }
             }
         }
+
+        if reasoning_started && !reasoning_closed {
+            for event in reasoning_done_events(&reasoning_id, &reasoning_text) {
+                yield Ok::<Bytes, String>(event);
+            }
+        }
     };
     Box::pin(stream)
 }
+
+/// Responses reasoning 输出项 (summary_text 形式的思考摘要)
+pub fn reasoning_output_item(id: &str, summary: &str) -> Value {
+    json!({
+        "id": id,
+        "type": "reasoning",
+        "summary": [{ "type": "summary_text", "text": summary }]
+    })
+}
+
+/// 思考摘要开始时的 Responses 事件 (先于任何 reasoning_summary_text.delta)
+fn reasoning_added_events(id: &str) -> Vec<Bytes> {
+    [
+        json!({ "type": "response.output_item.added", "output_index": 0, "item": { "id": id, "type": "reasoning", "summary": [] } }),
+        json!({ "type": "response.reasoning_summary_part.added", "item_id": id, "output_index": 0, "summary_index": 0, "part": { "type": "summary_text", "text": "" } }),
+    ]
+    .iter()
+    .map(|event| Bytes::from(format!("data: {}\n\n", event)))
+    .collect()
+}
+
+/// 思考摘要结束时的 Responses 事件
+fn reasoning_done_events(id: &str, summary: &str) -> Vec<Bytes> {
+    [
+        json!({ "type": "response.reasoning_summary_text.done", "item_id": id, "output_index": 0, "summary_index": 0, "text": summary }),
+        json!({ "type": "response.reasoning_summary_part.done", "item_id": id, "output_index": 0, "summary_index": 0, "part": { "type": "summary_text", "text": summary } }),
+        json!({ "type": "response.output_item.done", "output_index": 0, "item": reasoning_output_item(id, summary) }),
+    ]
+    .iter()
+    .map(|event| Bytes::from(format!("data: {}\n\n", event)))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_legacy_stream_emits_usage_once_for_multiple_choices() {
+        let candidate = |index: u32, text: &str, finish: bool| {
+            let mut c = json!({ "index": index, "content": { "role": "model", "parts": [{ "text": text }] } });
+            if finish {
+                c["finishReason"] = json!("STOP");
+            }
+            c
+        };
+        let usage = json!({ "promptTokenCount": 4, "candidatesTokenCount": 6, "totalTokenCount": 10 });
+        let frames = [
+            json!({ "response": { "candidates": [candidate(0, "A", false), candidate(1, "B", false)], "usageMetadata": usage } }),
+            json!({ "response": { "candidates": [candidate(0, "a", true), candidate(1, "b", true)], "usageMetadata": usage } }),
+        ];
+        let upstream: Vec<Result<Bytes, reqwest::Error>> = frames
+            .iter()
+            .map(|f| Ok(Bytes::from(format!("data: {}\n\n", f))))
+            .collect();
+
+        let mut stream = create_legacy_sse_stream(
+            Box::pin(futures::stream::iter(upstream)),
+            "gpt-3.5-turbo-instruct".to_string(),
+            "session".to_string(),
+            1,
+        );
+        let mut chunks = Vec::new();
+        while let Some(item) = stream.next().await {
+            let text = String::from_utf8(item.unwrap().to_vec()).unwrap();
+            if let Some(data) = text.trim().strip_prefix("data: ") {
+                if let Ok(value) = serde_json::from_str::<Value>(data) {
+                    chunks.push(value);
+                }
+            }
+        }
+
+        assert_eq!(chunks.len(), 4);
+        let with_usage: Vec<&Value> = chunks.iter().filter(|c| c.get("usage").is_some()).collect();
+        assert_eq!(with_usage.len(), 1);
+        assert_eq!(with_usage[0]["choices"][0]["index"], 1);
+        assert_eq!(with_usage[0]["usage"]["total_tokens"], 10);
+    }
+
+    #[tokio::test]
+    async fn test_chat_stream_emits_usage_once_for_multiple_choices() {
+        let candidate = |index: u32, text: &str, finish: bool| {
+            let mut c = json!({ "index": index, "content": { "role": "model", "parts": [{ "text": text }] } });
+            if finish {
+                c["finishReason"] = json!("STOP");
+            }
+            c
+        };
+        let usage = json!({ "promptTokenCount": 4, "candidatesTokenCount": 6, "totalTokenCount": 10 });
+        // 候选 0 先结束，候选 1 在下一分片结束
+        let frames = [
+            json!({ "response": { "candidates": [candidate(0, "A", false), candidate(1, "B", false)], "usageMetadata": usage } }),
+            json!({ "response": { "candidates": [candidate(0, "a", true)], "usageMetadata": usage } }),
+            json!({ "response": { "candidates": [candidate(1, "b", true)], "usageMetadata": usage } }),
+        ];
+        let upstream: Vec<Result<Bytes, reqwest::Error>> = frames
+            .iter()
+            .map(|f| Ok(Bytes::from(format!("data: {}\n\n", f))))
+            .collect();
+
+        let mut stream = create_openai_sse_stream(
+            Box::pin(futures::stream::iter(upstream)),
+            "gpt-4o".to_string(),
+            "session".to_string(),
+            1,
+        );
+        let mut chunks = Vec::new();
+        while let Some(item) = stream.next().await {
+            let text = String::from_utf8(item.unwrap().to_vec()).unwrap();
+            if let Some(data) = text.trim().strip_prefix("data: ") {
+                if let Ok(value) = serde_json::from_str::<Value>(data) {
+                    chunks.push(value);
+                }
+            }
+        }
+
+        assert_eq!(chunks.len(), 4);
+        let with_usage: Vec<&Value> = chunks.iter().filter(|c| c.get("usage").is_some()).collect();
+        assert_eq!(with_usage.len(), 1);
+        assert_eq!(with_usage[0]["choices"][0]["index"], 1);
+        assert_eq!(with_usage[0]["choices"][0]["finish_reason"], "stop");
+        assert_eq!(with_usage[0]["usage"]["total_tokens"], 10);
+    }
+
+    async fn collect_codex_events(frames: &[Value], reasoning_summary: bool) -> Vec<Value> {
+        let upstream: Vec<Result<Bytes, reqwest::Error>> = frames
+            .iter()
+            .map(|f| Ok(Bytes::from(format!("data: {}\n\n", f))))
+            .collect();
+        let mut stream = create_codex_sse_stream(
+            Box::pin(futures::stream::iter(upstream)),
+            "gpt-5-codex".to_string(),
+            "session".to_string(),
+            1,
+            reasoning_summary,
+        );
+        let mut events = Vec::new();
+        while let Some(item) = stream.next().await {
+            let text = String::from_utf8(item.unwrap().to_vec()).unwrap();
+            if let Some(data) = text.trim().strip_prefix("data: ") {
+                events.push(serde_json::from_str::<Value>(data).unwrap());
+            }
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn test_codex_stream_reasoning_event_order() {
+        let parts = |parts: Value| json!({ "response": { "candidates": [{ "content": { "role": "model", "parts": parts } }] } });
+        let frames = [
+            parts(json!([{ "text": "Think", "thought": true }])),
+            parts(json!([{ "text": "ing", "thought": true }, { "text": "Hello" }])),
+            parts(json!([{ "text": " world" }])),
+        ];
+
+        let events = collect_codex_events(&frames, true).await;
+        let types: Vec<&str> = events.iter().map(|e| e["type"].as_str().unwrap()).collect();
+        assert_eq!(
+            types,
+            vec![
+                "response.created",
+                "response.output_item.added",
+                "response.reasoning_summary_part.added",
+                "response.reasoning_summary_text.delta",
+                "response.reasoning_summary_text.delta",
+                "response.reasoning_summary_text.done",
+                "response.reasoning_summary_part.done",
+                "response.output_item.done",
+                "response.output_text.delta",
+                "response.output_text.delta",
+            ]
+        );
+        assert_eq!(events[1]["item"]["type"], "reasoning");
+        assert_eq!(events[7]["item"]["summary"][0]["text"], "Thinking");
+        assert!(events[1..8].iter().all(|e| e["output_index"] == 0));
+        assert!(events[8..].iter().all(|e| e["output_index"] == 1));
+
+        // 未请求 reasoning 摘要时，消息占据 output_index 0
+        let events = collect_codex_events(&frames, false).await;
+        let deltas: Vec<&Value> = events.iter().skip(1).collect();
+        assert_eq!(deltas.len(), 2);
+        assert!(deltas.iter().all(|e| e["type"] == "response.output_text.delta" && e["output_index"] == 0));
+    }
+}
@@ -0,0 +1,382 @@
+// 推理强度统一策略
+// Anthropic thinking.budget_tokens / output_config.effort、OpenAI reasoning_effort / Responses reasoning.effort
+// 以及 OpenAI 扩展 thinking 字段统一在这里换算为 Gemini thinkingConfig。
+// 预算按全局 ThinkingBudgetConfig 处理：Auto 模式按模型族限制，model_caps 可按模型覆盖上限。
+
+use crate::proxy::common::model_mapping::wildcard_match;
+use crate::proxy::config::{get_thinking_budget_config, ThinkingBudgetConfig, ThinkingBudgetMode};
+use serde_json::{json, Value};
+
+/// Auto 模式下受限模型族 (Flash / 1.5 / 转发的 -thinking 模型 / 联网) 的预算上限
+pub const AUTO_BUDGET_CAP: u32 = 24576;
+/// 思考模型 maxOutputTokens 需要在 thinkingBudget 之外预留的回答空间
+pub const OUTPUT_HEADROOM: u32 = 8192;
+
+/// 推理强度 (OpenAI reasoning_effort / Claude output_config.effort)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReasoningEffort {
+    None,
+    Minimal,
+    Low,
+    Medium,
+    High,
+}
+
+impl ReasoningEffort {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "none" => Some(Self::None),
+            "minimal" => Some(Self::Minimal),
+            "low" => Some(Self::Low),
+            "medium" => Some(Self::Medium),
+            "high" | "xhigh" | "max" => Some(Self::High),
+            _ => None,
+        }
+    }
+
+    /// 对应的 thinkingBudget (None 表示关闭思考)
+    pub fn budget(self) -> Option<u32> {
+        match self {
+            Self::None => None,
+            Self::Minimal => Some(1024),
+            Self::Low => Some(4096),
+            Self::Medium => Some(16384),
+            Self::High => Some(32768),
+        }
+    }
+
+    /// generationConfig.effortLevel 取值
+    pub fn gemini_level(self) -> &'static str {
+        match self {
+            Self::None | Self::Minimal | Self::Low => "LOW",
+            Self::Medium => "MEDIUM",
+            Self::High => "HIGH",
+        }
+    }
+}
+
+/// 各协议解析后的推理参数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ReasoningRequest {
+    /// 显式预算 (优先于 effort)
+    pub budget_tokens: Option<u32>,
+    pub effort: Option<ReasoningEffort>,
+}
+
+/// 最终下发的 Gemini thinkingConfig
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ThinkingPlan {
+    pub budget: u32,
+    pub include_thoughts: bool,
+}
+
+impl ThinkingPlan {
+    pub fn to_gemini(self) -> Value {
+        json!({
+            "includeThoughts": self.include_thoughts,
+            "thinkingBudget": self.budget
+        })
+    }
+
+    /// 确保 maxOutputTokens 大于 thinkingBudget (API 强约束)，需要调整时返回新值
+    pub fn max_output_tokens(self, current: Option<i64>) -> Option<i64> {
+        if self.budget == 0 {
+            return None;
+        }
+        let budget = self.budget as i64;
+        if current.unwrap_or(0) <= budget {
+            Some(budget + OUTPUT_HEADROOM as i64)
+        } else {
+            None
+        }
+    }
+}
+
+fn is_flash(model_lower: &str) -> bool {
+    model_lower.contains("flash")
+}
+
+/// Auto 模式下的模型族上限
+fn auto_cap(model: &str, has_web_search: bool) -> Option<u32> {
+    let lower = model.to_lowercase();
+    let limited = has_web_search
+        || is_flash(&lower)
+        || lower.contains("gemini-1.5")
+        || lower.ends_with("-thinking");
+    limited.then_some(AUTO_BUDGET_CAP)
+}
+
+/// 配置中按模型设置的上限 (精确匹配优先，其次通配符)
+fn model_cap(config: &ThinkingBudgetConfig, model: &str) -> Option<u32> {
+    config.model_caps.get(model).copied().or_else(|| {
+        config
+            .model_caps
+            .iter()
+            .filter(|(pattern, _)| pattern.contains('*') && wildcard_match(pattern, model))
+            .map(|(_, cap)| *cap)
+            .min()
+    })
+}
+
+/// 按给定配置处理预算
+pub fn apply_budget_policy(
+    config: &ThinkingBudgetConfig,
+    model: &str,
+    budget: u32,
+    has_web_search: bool,
+) -> u32 {
+    let cap = model_cap(config, model);
+    match config.mode {
+        ThinkingBudgetMode::Passthrough => budget,
+        ThinkingBudgetMode::Custom => {
+            cap.map_or(config.custom_value, |c| config.custom_value.min(c))
+        }
+        ThinkingBudgetMode::Auto => match cap.or_else(|| auto_cap(model, has_web_search)) {
+            Some(c) => budget.min(c),
+            None => budget,
+        },
+    }
+}
+
+/// 按全局 Thinking Budget 配置处理预算
+pub fn apply_budget_config(model: &str, budget: u32, has_web_search: bool) -> u32 {
+    apply_budget_logged(&get_thinking_budget_config(), model, budget, has_web_search)
+}
+
+fn apply_budget_logged(
+    config: &ThinkingBudgetConfig,
+    model: &str,
+    budget: u32,
+    has_web_search: bool,
+) -> u32 {
+    let final_budget = apply_budget_policy(config, model, budget, has_web_search);
+    if final_budget != budget {
+        tracing::debug!(
+            "[Reasoning-Policy] {:?} mode: budget {} -> {} for model {}",
+            config.mode,
+            budget,
+            final_budget,
+            model
+        );
+    }
+    final_budget
+}
+
+/// 统一入口：推理参数 -> thinkingConfig (None 表示不注入)
+///
+/// 预算优先级：显式 budget_tokens > effort > 协议默认值。
+/// effort = none 时 Flash 模型下发 thinkingBudget=0 关闭思考，其它模型不注入。
+pub fn resolve_thinking(
+    model: &str,
+    request: &ReasoningRequest,
+    default_budget: u32,
+    has_web_search: bool,
+) -> Option<ThinkingPlan> {
+    let config = get_thinking_budget_config();
+    resolve_thinking_with(&config, model, request, default_budget, has_web_search)
+}
+
+/// 使用给定配置的 resolve_thinking
+pub fn resolve_thinking_with(
+    config: &ThinkingBudgetConfig,
+    model: &str,
+    request: &ReasoningRequest,
+    default_budget: u32,
+    has_web_search: bool,
+) -> Option<ThinkingPlan> {
+    let budget = match (request.budget_tokens, request.effort) {
+        (Some(budget), _) => budget,
+        (None, Some(effort)) => match effort.budget() {
+            Some(budget) => budget,
+            None => {
+                return is_flash(&model.to_lowercase()).then_some(ThinkingPlan {
+                    budget: 0,
+                    include_thoughts: false,
+                })
+            }
+        },
+        (None, None) => default_budget,
+    };
+
+    Some(ThinkingPlan {
+        budget: apply_budget_logged(config, model, budget, has_web_search),
+        include_thoughts: true,
+    })
+}
+
+/// OpenAI 请求中的推理强度 (Chat reasoning_effort 或 Responses reasoning.effort)
+pub fn openai_effort(
+    reasoning_effort: Option<&str>,
+    reasoning: Option<&Value>,
+) -> Option<ReasoningEffort> {
+    reasoning_effort
+        .or_else(|| {
+            reasoning
+                .and_then(|r| r.get("effort"))
+                .and_then(|v| v.as_str())
+        })
+        .and_then(ReasoningEffort::parse)
+}
+
+/// Responses reasoning.summary 是否要求返回推理摘要 (auto / concise / detailed)
+pub fn wants_reasoning_summary(reasoning: Option<&Value>) -> bool {
+    matches!(
+        reasoning
+            .and_then(|r| r.get("summary"))
+            .and_then(|v| v.as_str()),
+        Some("auto") | Some("concise") | Some("detailed")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(mode: ThinkingBudgetMode, caps: &[(&str, u32)]) -> ThinkingBudgetConfig {
+        ThinkingBudgetConfig {
+            mode,
+            custom_value: 8192,
+            model_caps: caps
+                .iter()
+                .map(|(model, cap)| (model.to_string(), *cap))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_effort_levels() {
+        assert_eq!(ReasoningEffort::parse("HIGH"), Some(ReasoningEffort::High));
+        assert_eq!(ReasoningEffort::parse("unknown"), None);
+        assert_eq!(ReasoningEffort::Low.budget(), Some(4096));
+        assert_eq!(ReasoningEffort::None.budget(), None);
+        assert_eq!(ReasoningEffort::Medium.gemini_level(), "MEDIUM");
+    }
+
+    #[test]
+    fn test_budget_policy_modes_and_model_caps() {
+        let auto = config(ThinkingBudgetMode::Auto, &[]);
+        assert_eq!(
+            apply_budget_policy(&auto, "gemini-2.5-flash", 32768, false),
+            24576
+        );
+        assert_eq!(
+            apply_budget_policy(&auto, "gemini-3-pro-preview", 32768, false),
+            32768
+        );
+        assert_eq!(
+            apply_budget_policy(&auto, "gemini-3-pro-preview", 32768, true),
+            24576
+        );
+        assert_eq!(
+            apply_budget_policy(&auto, "claude-opus-4-5-thinking", 32768, false),
+            24576
+        );
+
+        // 按模型上限优先于模型族上限
+        let capped = config(
+            ThinkingBudgetMode::Auto,
+            &[("gemini-3-pro-*", 2048), ("gemini-2.5-flash", 30000)],
+        );
+        assert_eq!(
+            apply_budget_policy(&capped, "gemini-3-pro-preview", 32768, false),
+            2048
+        );
+        assert_eq!(
+            apply_budget_policy(&capped, "gemini-2.5-flash", 32768, false),
+            30000
+        );
+
+        let custom = config(ThinkingBudgetMode::Custom, &[("gemini-3-pro-*", 2048)]);
+        assert_eq!(
+            apply_budget_policy(&custom, "gemini-2.5-flash", 100, false),
+            8192
+        );
+        assert_eq!(
+            apply_budget_policy(&custom, "gemini-3-pro-preview", 100, false),
+            2048
+        );
+
+        let passthrough = config(ThinkingBudgetMode::Passthrough, &[("gemini-3-pro-*", 2048)]);
+        assert_eq!(
+            apply_budget_policy(&passthrough, "gemini-3-pro-preview", 32768, false),
+            32768
+        );
+    }
+
+    #[test]
+    fn test_resolve_thinking_priority() {
+        let auto = config(ThinkingBudgetMode::Auto, &[]);
+        let explicit = ReasoningRequest {
+            budget_tokens: Some(2000),
+            effort: Some(ReasoningEffort::High),
+        };
+        assert_eq!(
+            resolve_thinking_with(&auto, "gemini-3-pro-preview", &explicit, 16000, false)
+                .unwrap()
+                .budget,
+            2000
+        );
+
+        let effort = ReasoningRequest {
+            budget_tokens: None,
+            effort: Some(ReasoningEffort::Low),
+        };
+        assert_eq!(
+            resolve_thinking_with(&auto, "gemini-3-pro-preview", &effort, 16000, false)
+                .unwrap()
+                .budget,
+            4096
+        );
+
+        let default = ReasoningRequest::default();
+        assert_eq!(
+            resolve_thinking_with(&auto, "gemini-3-pro-preview", &default, 16000, false)
+                .unwrap()
+                .budget,
+            16000
+        );
+
+        // effort = none: Flash 显式关闭，其它模型不注入
+        let none = ReasoningRequest {
+            budget_tokens: None,
+            effort: Some(ReasoningEffort::None),
+        };
+        let flash = resolve_thinking_with(&auto, "gemini-2.5-flash", &none, 16000, false).unwrap();
+        assert_eq!(
+            flash.to_gemini(),
+            json!({ "includeThoughts": false, "thinkingBudget": 0 })
+        );
+        assert_eq!(flash.max_output_tokens(Some(100)), None);
+        assert!(
+            resolve_thinking_with(&auto, "gemini-3-pro-preview", &none, 16000, false).is_none()
+        );
+    }
+
+    #[test]
+    fn test_max_output_headroom() {
+        let plan = ThinkingPlan {
+            budget: 16000,
+            include_thoughts: true,
+        };
+        assert_eq!(plan.max_output_tokens(None), Some(24192));
+        assert_eq!(plan.max_output_tokens(Some(16000)), Some(24192));
+        assert_eq!(plan.max_output_tokens(Some(64000)), None);
+    }
+
+    #[test]
+    fn test_openai_reasoning_fields() {
+        let responses = json!({ "effort": "minimal", "summary": "auto" });
+        assert_eq!(
+            openai_effort(None, Some(&responses)),
+            Some(ReasoningEffort::Minimal)
+        );
+        assert_eq!(
+            openai_effort(Some("high"), Some(&responses)),
+            Some(ReasoningEffort::High)
+        );
+        assert!(wants_reasoning_summary(Some(&responses)));
+        assert!(!wants_reasoning_summary(Some(&json!({ "effort": "low" }))));
+        assert!(!wants_reasoning_summary(None));
+    }
+}
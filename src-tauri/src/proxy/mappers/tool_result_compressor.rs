@@ -3,12 +3,16 @@
 //! 提供智能压缩功能:
 //! - 浏览器快照压缩 (头+尾保留)
 //! - 大文件提示压缩 (提取关键信息)
+//! - 语义压缩 (JSON / 日志 / Diff / 表格，见 tool_result_strategies)
 //! - 通用截断 (200,000 字符限制)
 
 use regex::Regex;
 use serde_json::Value;
 use tracing::{debug, info};
 
+use super::tool_result_strategies;
+use crate::proxy::config::{get_tool_result_compression_config, ToolResultStrategy};
+
 /// 最大工具结果字符数 (约 20 万,防止 prompt 超长)
 const MAX_TOOL_RESULT_CHARS: usize = 200_000;
 
@@ -30,8 +34,27 @@ const SNAPSHOT_TAIL_RATIO: f64 = 0.3;
 /// 根据内容类型自动选择最佳压缩策略:
 /// 1. 大文件提示 → 提取关键信息
 /// 2. 浏览器快照 → 头+尾保留
-/// 3. 其他 → 简单截断
+/// 3. JSON / 日志 / Diff / 表格 → 语义压缩
+/// 4. 其他 → 简单截断
 pub fn compact_tool_result_text(text: &str, max_chars: usize) -> String {
+    compact_tool_result_text_with_strategy(text, max_chars, ToolResultStrategy::Auto)
+}
+
+/// 按工具名压缩工具结果文本 (策略来自配置 `tool_result_compression`)
+pub fn compact_tool_result_text_for_tool(text: &str, max_chars: usize, tool_name: &str) -> String {
+    let strategy = get_tool_result_compression_config().strategy_for(tool_name);
+    compact_tool_result_text_with_strategy(text, max_chars, strategy)
+}
+
+/// 使用指定策略压缩工具结果文本
+///
+/// `Auto` 时先尝试大文件提示与浏览器快照，再按内容识别语义策略;
+/// 显式指定的策略无法处理时回退到截断
+pub fn compact_tool_result_text_with_strategy(
+    text: &str,
+    max_chars: usize,
+    strategy: ToolResultStrategy,
+) -> String {
     if text.is_empty() || text.len() <= max_chars {
         return text.to_string();
     }
@@ -49,6 +72,14 @@ pub fn compact_tool_result_text(text: &str, max_chars: usize) -> String {
         return cleaned_text;
     }
 
+    if strategy != ToolResultStrategy::Auto {
+        if let Some(compacted) = tool_result_strategies::apply_strategy(&cleaned_text, max_chars, strategy) {
+            debug!("[ToolCompressor] Applied {:?} strategy, compacted to {} chars", strategy, compacted.len());
+            return truncate_text_safe(&compacted, max_chars);
+        }
+        return truncate_text_safe(&cleaned_text, max_chars);
+    }
+
     // 1. 检测大文件提示模式
     if let Some(compacted) = compact_saved_output_notice(&cleaned_text, max_chars) {
         debug!("[ToolCompressor] Detected saved output notice, compacted to {} chars", compacted.len());
@@ -63,7 +94,14 @@ pub fn compact_tool_result_text(text: &str, max_chars: usize) -> String {
         }
     }
     
-    // 3. 结构化截断
+    // 3. 语义压缩 (JSON / 日志 / Diff / 表格)
+    let detected = tool_result_strategies::detect_strategy(&cleaned_text);
+    if let Some(compacted) = tool_result_strategies::apply_strategy(&cleaned_text, max_chars, detected) {
+        debug!("[ToolCompressor] Detected {:?} content, compacted to {} chars", detected, compacted.len());
+        return truncate_text_safe(&compacted, max_chars);
+    }
+
+    // 4. 结构化截断
     debug!("[ToolCompressor] Using structured truncation for {} chars", cleaned_text.len());
     truncate_text_safe(&cleaned_text, max_chars)
 }
@@ -253,6 +291,16 @@ fn deep_clean_html(html: &str) -> String {
 /// 
 /// 清理并截断工具调用结果内容块
 pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
+    sanitize_blocks_with_strategy(blocks, ToolResultStrategy::Auto);
+}
+
+/// 按工具名清理工具结果内容块 (策略来自配置 `tool_result_compression`)
+pub fn sanitize_tool_result_blocks_for_tool(blocks: &mut Vec<Value>, tool_name: &str) {
+    let strategy = get_tool_result_compression_config().strategy_for(tool_name);
+    sanitize_blocks_with_strategy(blocks, strategy);
+}
+
+fn sanitize_blocks_with_strategy(blocks: &mut Vec<Value>, strategy: ToolResultStrategy) {
     let mut used_chars = 0;
     let mut cleaned_blocks = Vec::new();
     let mut removed_image = false;
@@ -281,7 +329,7 @@ pub fn sanitize_tool_result_blocks(blocks: &mut Vec<Value>) {
                 break;
             }
             
-            let compacted = compact_tool_result_text(text, remaining);
+            let compacted = compact_tool_result_text_with_strategy(text, remaining, strategy);
             let mut new_block = block.clone();
             new_block["text"] = Value::String(compacted.clone());
             cleaned_blocks.push(new_block);
@@ -420,6 +468,37 @@ Please read the file locally."#;
         assert!(blocks[1]["text"].as_str().unwrap().contains("[image omitted"));
     }
 
+    #[test]
+    fn test_compact_json_result_keeps_structure() {
+        let rows: Vec<Value> = (0..20_000).map(|i| serde_json::json!({"id": i})).collect();
+        let text = serde_json::json!({"rows": rows}).to_string();
+        let result = compact_tool_result_text(&text, 5_000);
+        assert!(result.len() <= 5_000);
+        assert!(result.contains("[json compacted"));
+        assert!(result.contains("more items omitted (20000 total)"));
+    }
+
+    #[test]
+    fn test_explicit_strategy_falls_back_to_truncation() {
+        let text = "x".repeat(10_000);
+        let result = compact_tool_result_text_with_strategy(&text, 1_000, ToolResultStrategy::Json);
+        assert!(result.contains("[truncated"));
+    }
+
+    #[test]
+    fn test_strategy_lookup_by_tool_name() {
+        use crate::proxy::config::ToolResultCompressionConfig;
+        let mut config = ToolResultCompressionConfig::default();
+        config.tool_strategies.insert("mcp__github__*".to_string(), ToolResultStrategy::Json);
+        config.tool_strategies.insert("mcp__github__get_diff".to_string(), ToolResultStrategy::Diff);
+        config.tool_strategies.insert("mcp__*".to_string(), ToolResultStrategy::Truncate);
+
+        assert_eq!(config.strategy_for("mcp__github__get_diff"), ToolResultStrategy::Diff);
+        assert_eq!(config.strategy_for("mcp__github__list_issues"), ToolResultStrategy::Json);
+        assert_eq!(config.strategy_for("mcp__db__query"), ToolResultStrategy::Truncate);
+        assert_eq!(config.strategy_for("Bash"), ToolResultStrategy::Auto);
+    }
+
     #[test]
     fn test_is_base64_image() {
         let image_block = serde_json::json!({
@@ -0,0 +1,528 @@
+//! 工具结果语义压缩策略
+//!
+//! 盲目截断经常恰好切掉模型最需要的部分，这里按内容类型保留关键信息:
+//! - JSON: 保留结构，长数组只保留样本并标注省略数量
+//! - 日志/堆栈: 合并重复行，保留首个与最后一个错误块
+//! - Diff: 保留文件头与 hunk 头，按预算保留 hunk 内容
+//! - CSV/表格: 保留表头与首尾样本行
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::proxy::config::ToolResultStrategy;
+
+/// JSON 逐级收紧的省略参数: (数组保留条数, 字符串保留字符数, 最大展开深度)
+const JSON_ELISION_LEVELS: &[(usize, usize, usize)] = &[
+    (20, 2000, usize::MAX),
+    (10, 500, usize::MAX),
+    (5, 200, 8),
+    (3, 120, 5),
+    (1, 80, 3),
+];
+
+/// 单个错误块最多向后延伸的行数
+const MAX_ERROR_BLOCK_LINES: usize = 60;
+
+/// 日志头部预算占比 (尾部通常更重要)
+const LOG_HEAD_RATIO: f64 = 0.3;
+
+/// 表格头部样本行预算占比
+const TABLE_HEAD_RATIO: f64 = 0.7;
+
+static ERROR_LINE_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)\b(error|exception|panic(ked)?|fatal|traceback|failed|failure)\b").unwrap()
+});
+
+static STACK_FRAME_RE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"^(\s+(at |File "|\d+: |\.\.\. \d+ more)|Caused by:|\s*--> |stack backtrace:)"#)
+        .unwrap()
+});
+
+static LOG_LEVEL_RE: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"\b(TRACE|DEBUG|INFO|WARN|WARNING|ERROR|FATAL)\b").unwrap());
+
+static DIGITS_RE: Lazy<Regex> = Lazy::new(|| Regex::new(r"\d+").unwrap());
+
+/// 根据内容自动识别压缩策略
+pub fn detect_strategy(text: &str) -> ToolResultStrategy {
+    if looks_like_json(text) {
+        ToolResultStrategy::Json
+    } else if looks_like_diff(text) {
+        ToolResultStrategy::Diff
+    } else if looks_like_log(text) {
+        ToolResultStrategy::Log
+    } else if detect_table_delimiter(text).is_some() {
+        ToolResultStrategy::Table
+    } else {
+        ToolResultStrategy::Truncate
+    }
+}
+
+/// 按策略压缩文本
+///
+/// 返回 None 表示该策略无法处理此内容 (如 JSON 解析失败)，由调用方回退到截断
+pub fn apply_strategy(text: &str, max_chars: usize, strategy: ToolResultStrategy) -> Option<String> {
+    match strategy {
+        ToolResultStrategy::Auto => apply_strategy(text, max_chars, detect_strategy(text)),
+        ToolResultStrategy::Json => compact_json(text, max_chars),
+        ToolResultStrategy::Log => compact_log(text, max_chars),
+        ToolResultStrategy::Diff => compact_diff(text, max_chars),
+        ToolResultStrategy::Table => compact_table(text, max_chars),
+        ToolResultStrategy::Truncate => None,
+    }
+}
+
+// ===== JSON =====
+
+fn looks_like_json(text: &str) -> bool {
+    let trimmed = text.trim_start();
+    (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<Value>(text.trim()).is_ok()
+}
+
+/// 保留 JSON 结构，逐级收紧数组/字符串/深度直到满足预算
+pub fn compact_json(text: &str, max_chars: usize) -> Option<String> {
+    let value: Value = serde_json::from_str(text.trim()).ok()?;
+    let meta = format!(
+        "[json compacted to reduce prompt size; original {} chars, long arrays and strings elided]",
+        text.len()
+    );
+    let budget = max_chars.saturating_sub(meta.len() + 1);
+
+    for &(max_items, max_string, max_depth) in JSON_ELISION_LEVELS {
+        let elided = elide_json(&value, max_items, max_string, max_depth);
+        let pretty = serde_json::to_string_pretty(&elided).ok()?;
+        if pretty.len() <= budget {
+            return Some(format!("{}\n{}", meta, pretty));
+        }
+        let compact = serde_json::to_string(&elided).ok()?;
+        if compact.len() <= budget {
+            return Some(format!("{}\n{}", meta, compact));
+        }
+    }
+    None
+}
+
+fn elide_json(value: &Value, max_items: usize, max_string: usize, depth: usize) -> Value {
+    match value {
+        Value::Array(items) => {
+            if depth == 0 && !items.is_empty() {
+                return Value::String(format!("[... {} items omitted]", items.len()));
+            }
+            let mut out: Vec<Value> = items
+                .iter()
+                .take(max_items)
+                .map(|v| elide_json(v, max_items, max_string, depth.saturating_sub(1)))
+                .collect();
+            if items.len() > max_items {
+                out.push(Value::String(format!(
+                    "... {} more items omitted ({} total)",
+                    items.len() - max_items,
+                    items.len()
+                )));
+            }
+            Value::Array(out)
+        }
+        Value::Object(map) => {
+            if depth == 0 && !map.is_empty() {
+                return Value::String(format!("{{... {} keys omitted}}", map.len()));
+            }
+            Value::Object(
+                map.iter()
+                    .map(|(k, v)| {
+                        (
+                            k.clone(),
+                            elide_json(v, max_items, max_string, depth.saturating_sub(1)),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        Value::String(s) if s.chars().count() > max_string => {
+            let kept: String = s.chars().take(max_string).collect();
+            let omitted = s.chars().count() - max_string;
+            Value::String(format!("{}...[{} chars omitted]", kept, omitted))
+        }
+        other => other.clone(),
+    }
+}
+
+// ===== 日志 / 堆栈 =====
+
+fn looks_like_log(text: &str) -> bool {
+    let lines: Vec<&str> = text.lines().collect();
+    if lines.len() < 5 {
+        return false;
+    }
+    let signal = lines
+        .iter()
+        .filter(|l| LOG_LEVEL_RE.is_match(l) || STACK_FRAME_RE.is_match(l))
+        .count();
+    signal >= 3.max(lines.len() / 5)
+}
+
+/// 合并相邻的重复行 (忽略数字差异，如时间戳、计数)
+fn dedupe_lines(text: &str) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    let mut last_key: Option<String> = None;
+    let mut repeats = 0usize;
+
+    for line in text.lines() {
+        let key = DIGITS_RE.replace_all(line.trim_end(), "#").to_string();
+        if last_key.as_deref() == Some(key.as_str()) {
+            repeats += 1;
+            continue;
+        }
+        if repeats > 0 {
+            out.push(format!("    [previous line repeated {} more times]", repeats));
+            repeats = 0;
+        }
+        out.push(line.to_string());
+        last_key = Some(key);
+    }
+    if repeats > 0 {
+        out.push(format!("    [previous line repeated {} more times]", repeats));
+    }
+    out
+}
+
+/// 查找错误块: 以错误行开始，延续其后的堆栈帧/缩进行
+fn find_error_blocks(lines: &[String]) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if ERROR_LINE_RE.is_match(&lines[i]) {
+            let start = i;
+            let mut end = i + 1;
+            while end < lines.len()
+                && end - start < MAX_ERROR_BLOCK_LINES
+                && (STACK_FRAME_RE.is_match(&lines[end])
+                    || lines[end].starts_with(char::is_whitespace))
+            {
+                end += 1;
+            }
+            blocks.push((start, end));
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    blocks
+}
+
+/// 在预算内标记一段行，返回消耗的字符数
+fn mark_lines(
+    lines: &[String],
+    keep: &mut [bool],
+    indices: impl Iterator<Item = usize>,
+    budget: usize,
+) -> usize {
+    let mut used = 0;
+    for i in indices {
+        if keep[i] {
+            continue;
+        }
+        let cost = lines[i].len() + 1;
+        if used + cost > budget {
+            break;
+        }
+        keep[i] = true;
+        used += cost;
+    }
+    used
+}
+
+/// 按原始顺序输出保留的行，省略部分用标记替代
+fn render_kept_lines(lines: &[String], keep: &[bool], unit: &str) -> String {
+    let mut out: Vec<String> = Vec::new();
+    let mut omitted = 0;
+    for (line, kept) in lines.iter().zip(keep) {
+        if *kept {
+            if omitted > 0 {
+                out.push(format!("... [{} {} omitted] ...", omitted, unit));
+                omitted = 0;
+            }
+            out.push(line.clone());
+        } else {
+            omitted += 1;
+        }
+    }
+    if omitted > 0 {
+        out.push(format!("... [{} {} omitted] ...", omitted, unit));
+    }
+    out.join("\n")
+}
+
+/// 合并重复行，保留首个与最后一个错误块，再用剩余预算填充头尾
+pub fn compact_log(text: &str, max_chars: usize) -> Option<String> {
+    let original_lines = text.lines().count();
+    let lines = dedupe_lines(text);
+    let deduped = lines.join("\n");
+    if deduped.len() <= max_chars {
+        return Some(deduped);
+    }
+
+    let meta = format!(
+        "[log compacted to reduce prompt size; original {} lines, repeated lines merged, first/last error blocks kept]",
+        original_lines
+    );
+    // 为省略标记预留空间
+    let mut budget = max_chars.saturating_sub(meta.len() + 200);
+    let mut keep = vec![false; lines.len()];
+
+    let blocks = find_error_blocks(&lines);
+    if let Some(&(start, end)) = blocks.first() {
+        budget -= mark_lines(&lines, &mut keep, start..end, budget / 2);
+    }
+    if let Some(&(start, end)) = blocks.last() {
+        budget -= mark_lines(&lines, &mut keep, start..end, budget / 2);
+    }
+
+    let head_budget = (budget as f64 * LOG_HEAD_RATIO) as usize;
+    budget -= mark_lines(&lines, &mut keep, 0..lines.len(), head_budget);
+    mark_lines(&lines, &mut keep, (0..lines.len()).rev(), budget);
+
+    Some(format!("{}\n{}", meta, render_kept_lines(&lines, &keep, "lines")))
+}
+
+// ===== Diff =====
+
+fn looks_like_diff(text: &str) -> bool {
+    text.starts_with("diff --git")
+        || text.contains("\ndiff --git ")
+        || ((text.starts_with("--- ") || text.contains("\n--- "))
+            && text.contains("\n+++ ")
+            && text.contains("\n@@ "))
+}
+
+fn is_diff_header(line: &str) -> bool {
+    const PREFIXES: &[&str] = &[
+        "diff --git",
+        "index ",
+        "--- ",
+        "+++ ",
+        "@@",
+        "new file mode",
+        "deleted file mode",
+        "similarity index",
+        "rename from",
+        "rename to",
+        "Binary files",
+    ];
+    PREFIXES.iter().any(|p| line.starts_with(p))
+}
+
+/// 保留所有文件头与 hunk 头，hunk 内容按剩余预算平均分配
+pub fn compact_diff(text: &str, max_chars: usize) -> Option<String> {
+    let lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+    let mut keep: Vec<bool> = lines.iter().map(|l| is_diff_header(l)).collect();
+
+    let header_cost: usize = lines
+        .iter()
+        .zip(&keep)
+        .filter(|(_, k)| **k)
+        .map(|(l, _)| l.len() + 1)
+        .sum();
+
+    // hunk 内容: 相邻两个头部之间的行
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    let mut start: Option<usize> = None;
+    for (i, is_header) in keep.iter().enumerate() {
+        match (is_header, start) {
+            (true, Some(s)) => {
+                hunks.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        hunks.push((s, lines.len()));
+    }
+
+    let meta = format!(
+        "[diff compacted to reduce prompt size; original {} chars, all file and hunk headers kept]",
+        text.len()
+    );
+    let mut budget = max_chars.saturating_sub(meta.len() + header_cost + 40 * hunks.len());
+    let total = hunks.len();
+    for (n, &(s, e)) in hunks.iter().enumerate() {
+        let share = budget / (total - n);
+        budget -= mark_lines(&lines, &mut keep, s..e, share);
+    }
+
+    Some(format!("{}\n{}", meta, render_kept_lines(&lines, &keep, "diff lines")))
+}
+
+// ===== CSV / 表格 =====
+
+/// 检测表格分隔符: 前若干行的分隔符数量一致
+fn detect_table_delimiter(text: &str) -> Option<char> {
+    let sample: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).take(10).collect();
+    if sample.len() < 5 {
+        return None;
+    }
+    ['\t', '|', ',', ';'].into_iter().find(|&d| {
+        let expected = sample[0].matches(d).count();
+        expected >= 1
+            && sample.iter().filter(|l| l.matches(d).count() == expected).count() * 10
+                >= sample.len() * 8
+    })
+}
+
+/// 保留表头与首尾样本行
+pub fn compact_table(text: &str, max_chars: usize) -> Option<String> {
+    let delimiter = detect_table_delimiter(text)?;
+    let lines: Vec<String> = text.lines().map(|l| l.to_string()).collect();
+
+    // Markdown 表格的分隔行 (|---|---|) 也属于表头
+    let header_len = match lines.get(1) {
+        Some(l) if delimiter == '|' && l.trim().trim_matches('|').chars().all(|c| "-:| ".contains(c)) => 2,
+        _ => 1,
+    };
+    let row_count = lines.len().saturating_sub(header_len);
+
+    let meta = format!(
+        "[table compacted to reduce prompt size; {} rows total, header and sample rows kept]",
+        row_count
+    );
+    let mut keep = vec![false; lines.len()];
+    let mut budget = max_chars.saturating_sub(meta.len() + 100);
+    budget -= mark_lines(&lines, &mut keep, 0..header_len, budget);
+
+    let head_budget = (budget as f64 * TABLE_HEAD_RATIO) as usize;
+    budget -= mark_lines(&lines, &mut keep, header_len..lines.len(), head_budget);
+    mark_lines(&lines, &mut keep, (header_len..lines.len()).rev(), budget);
+
+    Some(format!("{}\n{}", meta, render_kept_lines(&lines, &keep, "rows")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_strategy() {
+        assert_eq!(detect_strategy(r#"{"a": [1, 2, 3]}"#), ToolResultStrategy::Json);
+        assert_eq!(
+            detect_strategy("diff --git a/x b/x\n--- a/x\n+++ b/x\n@@ -1 +1 @@\n-a\n+b"),
+            ToolResultStrategy::Diff
+        );
+        let log = (0..10)
+            .map(|i| format!("2024-01-01 INFO worker {} started", i))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detect_strategy(&log), ToolResultStrategy::Log);
+        let csv = (0..10)
+            .map(|i| format!("{},name{},{}", i, i, i * 2))
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(detect_strategy(&csv), ToolResultStrategy::Table);
+        assert_eq!(detect_strategy("plain prose text"), ToolResultStrategy::Truncate);
+    }
+
+    #[test]
+    fn test_compact_json_keeps_structure() {
+        let items: Vec<Value> = (0..5000)
+            .map(|i| serde_json::json!({"id": i, "name": format!("item-{}", i)}))
+            .collect();
+        let text = serde_json::json!({"total": 5000, "items": items}).to_string();
+
+        let result = compact_json(&text, 4000).unwrap();
+        assert!(result.len() <= 4000);
+        let body = result.split_once('\n').unwrap().1;
+        let parsed: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(parsed["total"], 5000);
+        assert_eq!(parsed["items"][0]["name"], "item-0");
+        assert!(body.contains("more items omitted (5000 total)"));
+    }
+
+    #[test]
+    fn test_compact_json_invalid_returns_none() {
+        assert!(compact_json("{not json", 100).is_none());
+    }
+
+    #[test]
+    fn test_compact_log_dedupes_and_keeps_errors() {
+        let mut lines = vec!["ERROR first failure: connection refused".to_string()];
+        lines.push("    at db.connect (db.js:10)".to_string());
+        for i in 0..3000 {
+            lines.push(format!("2024-01-01 00:00:{:02} INFO heartbeat ok {}", i % 60, i));
+            lines.push(format!("INFO processing batch {}", i));
+        }
+        lines.push("ERROR last failure: disk full".to_string());
+        lines.push("    at fs.write (fs.js:42)".to_string());
+        lines.push("shutdown".to_string());
+        let text = lines.join("\n");
+
+        let result = compact_log(&text, 3000).unwrap();
+        assert!(result.len() <= 3000);
+        assert!(result.contains("first failure: connection refused"));
+        assert!(result.contains("db.js:10"));
+        assert!(result.contains("last failure: disk full"));
+        assert!(result.contains("fs.js:42"));
+        assert!(result.contains("lines omitted"));
+    }
+
+    #[test]
+    fn test_dedupe_lines_ignores_numbers() {
+        let text = "tick 1\ntick 2\ntick 3\ndone";
+        let lines = dedupe_lines(text);
+        assert_eq!(lines[0], "tick 1");
+        assert!(lines[1].contains("repeated 2 more times"));
+        assert_eq!(lines[2], "done");
+    }
+
+    #[test]
+    fn test_compact_diff_keeps_all_hunk_headers() {
+        let mut diff = String::new();
+        for f in 0..5 {
+            diff.push_str(&format!(
+                "diff --git a/f{0}.rs b/f{0}.rs\n--- a/f{0}.rs\n+++ b/f{0}.rs\n",
+                f
+            ));
+            for h in 0..3 {
+                diff.push_str(&format!("@@ -{0},50 +{0},50 @@ fn hunk_{1}\n", h * 100, h));
+                for l in 0..200 {
+                    diff.push_str(&format!("+added line {} in file {}\n", l, f));
+                }
+            }
+        }
+
+        let result = compact_diff(&diff, 6000).unwrap();
+        assert!(result.len() <= 6000);
+        for f in 0..5 {
+            assert!(result.contains(&format!("+++ b/f{}.rs", f)));
+        }
+        assert_eq!(result.matches("@@ -").count(), 15);
+        assert!(result.contains("diff lines omitted"));
+    }
+
+    #[test]
+    fn test_compact_table_keeps_header_and_samples() {
+        let mut csv = String::from("id,name,email\n");
+        for i in 0..10_000 {
+            csv.push_str(&format!("{},user{},user{}@example.com\n", i, i, i));
+        }
+
+        let result = compact_table(&csv, 2000).unwrap();
+        assert!(result.len() <= 2000);
+        assert!(result.contains("10000 rows total"));
+        assert!(result.contains("\nid,name,email\n"));
+        assert!(result.contains("0,user0,"));
+        assert!(result.contains("9999,user9999,"));
+        assert!(result.contains("rows omitted"));
+    }
+
+    #[test]
+    fn test_compact_markdown_table_keeps_separator() {
+        let mut table = String::from("| a | b |\n|---|---|\n");
+        for i in 0..2000 {
+            table.push_str(&format!("| {} | {} |\n", i, i * 2));
+        }
+        let result = compact_table(&table, 500).unwrap();
+        assert!(result.contains("| a | b |\n|---|---|"));
+    }
+}
@@ -0,0 +1,990 @@
+// Anthropic Message Batches 模拟层
+// v1internal 没有批处理接口：批次在本地持久化为任务队列，由后台 worker 逐条交给 handle_messages 处理。
+// 并发受全局预算 (proxy.message_batches.max_concurrency) 约束，且只在存在未限流 / 未被配额保护的账号时派发，避免批量任务把账号池打满。
+// 结果以 JSONL 追加保存，进程重启后从未完成的请求继续；已结束的批次按日志保留策略清理。
+// 存储为同步文件 I/O，异步上下文中一律经 MessageBatchStore::run 在阻塞线程池中执行。
+
+use crate::proxy::common::model_mapping::normalize_to_standard_id;
+use crate::proxy::config::StoreRetention;
+use crate::proxy::server::AppState;
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use futures::FutureExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const BATCHES_DIR: &str = "message_batches";
+const ID_PREFIX: &str = "msgbatch_";
+/// 批次处理时限 (与 Anthropic 一致: 24 小时内未完成的请求记为 expired)
+pub const BATCH_PROCESSING_WINDOW_MS: i64 = 24 * 3600 * 1000;
+/// 单个批次最多请求数
+pub const MAX_BATCH_REQUESTS: usize = 100_000;
+/// 无可用账号或预算耗尽时的轮询间隔
+const DISPATCH_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// 限流 / 过载等瞬时错误最多重新排队的次数
+const MAX_TRANSIENT_RETRIES: u32 = 5;
+/// worker 落盘结果与刷新元数据 (取消状态) 的间隔；进程中断时未落盘的请求在恢复后重新执行
+const STORE_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 所有批次进行中的请求数 (与配置的并发预算比较，修改配置后立即生效)
+static BATCH_IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+/// 正在运行 worker 的批次
+static RUNNING_BATCHES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+/// 元数据读改写锁 (worker 记录结果与 cancel / delete 并发)，仅在阻塞线程中持有
+static STORE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    InProgress,
+    Canceling,
+    Ended,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RequestCounts {
+    pub processing: u64,
+    pub succeeded: u64,
+    pub errored: u64,
+    pub canceled: u64,
+    pub expired: u64,
+}
+
+/// 批次元数据
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageBatch {
+    pub id: String,
+    pub processing_status: ProcessingStatus,
+    pub request_counts: RequestCounts,
+    /// 毫秒时间戳
+    pub created_at: i64,
+    pub expires_at: i64,
+    #[serde(default)]
+    pub ended_at: Option<i64>,
+    #[serde(default)]
+    pub cancel_initiated_at: Option<i64>,
+    /// 创建批次时携带的 anthropic-beta，派发每条请求时原样带上
+    #[serde(default)]
+    pub anthropic_beta: Option<String>,
+    /// 创建者的 User Token ID (主 API Key 或免鉴权创建为 None)，派发请求时以该身份解析文件引用
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+}
+
+impl MessageBatch {
+    /// Anthropic API 格式；results_url 仅在批次结束后返回
+    pub fn to_api(&self, base_url: &str) -> Value {
+        let results_url = (self.processing_status == ProcessingStatus::Ended)
+            .then(|| format!("{}/v1/messages/batches/{}/results", base_url, self.id));
+        json!({
+            "id": self.id,
+            "type": "message_batch",
+            "processing_status": self.processing_status,
+            "request_counts": self.request_counts,
+            "ended_at": self.ended_at.map(rfc3339),
+            "created_at": rfc3339(self.created_at),
+            "expires_at": rfc3339(self.expires_at),
+            "archived_at": null,
+            "cancel_initiated_at": self.cancel_initiated_at.map(rfc3339),
+            "results_url": results_url
+        })
+    }
+}
+
+/// 批次中的单条请求
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BatchRequest {
+    pub custom_id: String,
+    pub params: Value,
+}
+
+fn rfc3339(ms: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(ms)
+        .map(|t| t.to_rfc3339_opts(chrono::SecondsFormat::Millis, true))
+        .unwrap_or_default()
+}
+
+fn now_ms() -> i64 {
+    chrono::Utc::now().timestamp_millis()
+}
+
+fn lock_store() -> std::sync::MutexGuard<'static, ()> {
+    STORE_LOCK.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn is_valid_batch_id(id: &str) -> bool {
+    id.strip_prefix(ID_PREFIX)
+        .map(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_alphanumeric()))
+        .unwrap_or(false)
+}
+
+fn is_valid_custom_id(id: &str) -> bool {
+    (1..=64).contains(&id.len())
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// 校验创建批次的请求体，返回请求列表
+pub fn parse_batch_requests(body: &Value) -> Result<Vec<BatchRequest>, String> {
+    let items = body
+        .get("requests")
+        .and_then(|v| v.as_array())
+        .ok_or("requests: Field required (expected an array)")?;
+    if items.is_empty() {
+        return Err("requests: must contain at least one request".to_string());
+    }
+    if items.len() > MAX_BATCH_REQUESTS {
+        return Err(format!(
+            "requests: a batch may contain at most {} requests",
+            MAX_BATCH_REQUESTS
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    let mut requests = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        let custom_id = item
+            .get("custom_id")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("requests.{}.custom_id: Field required", index))?;
+        if !is_valid_custom_id(custom_id) {
+            return Err(format!(
+                "requests.{}.custom_id: must be 1-64 characters of letters, digits, '_' or '-'",
+                index
+            ));
+        }
+        if !seen.insert(custom_id) {
+            return Err(format!(
+                "requests.{}.custom_id: duplicate custom_id '{}'",
+                index, custom_id
+            ));
+        }
+
+        let params = item
+            .get("params")
+            .filter(|v| v.is_object())
+            .ok_or_else(|| format!("requests.{}.params: Field required", index))?;
+        if params.get("model").and_then(|v| v.as_str()).is_none() {
+            return Err(format!("requests.{}.params.model: Field required", index));
+        }
+        if params.get("messages").and_then(|v| v.as_array()).is_none() {
+            return Err(format!(
+                "requests.{}.params.messages: Field required",
+                index
+            ));
+        }
+
+        requests.push(BatchRequest {
+            custom_id: custom_id.to_string(),
+            params: params.clone(),
+        });
+    }
+    Ok(requests)
+}
+
+fn errored_result(error_type: &str, message: &str) -> Value {
+    json!({
+        "type": "errored",
+        "error": {
+            "type": "error",
+            "error": { "type": error_type, "message": message }
+        }
+    })
+}
+
+/// 批次存储: <id>.json 元数据，<id>.requests.jsonl 输入，<id>.results.jsonl 结果 (追加写入)
+#[derive(Clone)]
+pub struct MessageBatchStore {
+    dir: PathBuf,
+}
+
+impl MessageBatchStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// 数据目录下的默认存储
+    pub fn open_default() -> Result<Self, String> {
+        let dir = crate::modules::account::get_data_dir()?.join(BATCHES_DIR);
+        Ok(Self::new(dir))
+    }
+
+    /// 在阻塞线程池中执行存储操作，避免文件 I/O 与 STORE_LOCK 占用 tokio worker
+    pub async fn run<T, F>(&self, f: F) -> Result<T, String>
+    where
+        F: FnOnce(&MessageBatchStore) -> Result<T, String> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || f(&store))
+            .await
+            .map_err(|e| format!("批次存储任务失败: {}", e))?
+    }
+
+    fn meta_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn requests_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.requests.jsonl", id))
+    }
+
+    fn results_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.results.jsonl", id))
+    }
+
+    fn write_meta(&self, batch: &MessageBatch) -> Result<(), String> {
+        let raw = serde_json::to_vec_pretty(batch).map_err(|e| e.to_string())?;
+        // 先写临时文件再重命名，避免读到半截元数据
+        let tmp = self
+            .dir
+            .join(format!("{}.tmp-{}", batch.id, uuid::Uuid::new_v4()));
+        fs::write(&tmp, raw).map_err(|e| format!("写入批次元数据失败: {}", e))?;
+        fs::rename(&tmp, self.meta_path(&batch.id))
+            .map_err(|e| format!("写入批次元数据失败: {}", e))
+    }
+
+    /// 创建批次 (状态为 in_progress，需另行启动 worker)
+    pub fn create(
+        &self,
+        requests: &[BatchRequest],
+        anthropic_beta: Option<String>,
+        owner: Option<String>,
+    ) -> Result<MessageBatch, String> {
+        fs::create_dir_all(&self.dir).map_err(|e| format!("创建批次目录失败: {}", e))?;
+
+        let id = format!("{}{}", ID_PREFIX, uuid::Uuid::new_v4().simple());
+        let mut lines = String::new();
+        for request in requests {
+            lines.push_str(&serde_json::to_string(request).map_err(|e| e.to_string())?);
+            lines.push('\n');
+        }
+        fs::write(self.requests_path(&id), lines)
+            .map_err(|e| format!("写入批次请求失败: {}", e))?;
+
+        let now = now_ms();
+        let batch = MessageBatch {
+            id,
+            processing_status: ProcessingStatus::InProgress,
+            request_counts: RequestCounts {
+                processing: requests.len() as u64,
+                ..Default::default()
+            },
+            created_at: now,
+            expires_at: now + BATCH_PROCESSING_WINDOW_MS,
+            ended_at: None,
+            cancel_initiated_at: None,
+            anthropic_beta,
+            owner,
+        };
+        self.write_meta(&batch)?;
+        Ok(batch)
+    }
+
+    pub fn get(&self, id: &str) -> Result<Option<MessageBatch>, String> {
+        if !is_valid_batch_id(id) {
+            return Ok(None);
+        }
+        match fs::read(self.meta_path(id)) {
+            Ok(raw) => serde_json::from_slice(&raw)
+                .map(Some)
+                .map_err(|e| format!("解析批次元数据失败: {}", e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(format!("读取批次元数据失败: {}", e)),
+        }
+    }
+
+    /// owner 名下的批次，其他归属的批次视为不存在
+    pub fn get_owned(&self, id: &str, owner: Option<&str>) -> Result<Option<MessageBatch>, String> {
+        Ok(self
+            .get(id)?
+            .filter(|batch| batch.owner.as_deref() == owner))
+    }
+
+    /// owner 名下的批次 (新批次在前)
+    pub fn list_owned(&self, owner: Option<&str>) -> Result<Vec<MessageBatch>, String> {
+        let mut batches = self.list()?;
+        batches.retain(|batch| batch.owner.as_deref() == owner);
+        Ok(batches)
+    }
+
+    /// 全部批次 (新批次在前)
+    pub fn list(&self) -> Result<Vec<MessageBatch>, String> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let entries = fs::read_dir(&self.dir).map_err(|e| e.to_string())?;
+        let mut batches: Vec<MessageBatch> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                let id = name.strip_suffix(".json")?;
+                self.get(id).ok().flatten()
+            })
+            .collect();
+        batches.sort_by(|a, b| (b.created_at, &b.id).cmp(&(a.created_at, &a.id)));
+        Ok(batches)
+    }
+
+    pub fn requests(&self, id: &str) -> Result<Vec<BatchRequest>, String> {
+        let raw = fs::read_to_string(self.requests_path(id))
+            .map_err(|e| format!("读取批次请求失败: {}", e))?;
+        raw.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    /// 结果 JSONL 原文
+    pub fn results(&self, id: &str) -> Result<String, String> {
+        match fs::read_to_string(self.results_path(id)) {
+            Ok(raw) => Ok(raw),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(format!("读取批次结果失败: {}", e)),
+        }
+    }
+
+    fn completed_ids(&self, id: &str) -> Result<HashSet<String>, String> {
+        Ok(self
+            .results(id)?
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .filter_map(|v| v.get("custom_id")?.as_str().map(|s| s.to_string()))
+            .collect())
+    }
+
+    /// 追加一组结果并更新计数 (元数据只读写一次)
+    pub fn record_results(&self, id: &str, results: &[(String, Value)]) -> Result<(), String> {
+        if results.is_empty() {
+            return Ok(());
+        }
+        let _guard = lock_store();
+        let mut batch = self.get(id)?.ok_or_else(|| format!("批次 {} 不存在", id))?;
+
+        let mut lines = String::new();
+        let counts = &mut batch.request_counts;
+        for (custom_id, result) in results {
+            let line = json!({ "custom_id": custom_id, "result": result });
+            lines.push_str(&line.to_string());
+            lines.push('\n');
+
+            counts.processing = counts.processing.saturating_sub(1);
+            match result["type"].as_str().unwrap_or_default() {
+                "succeeded" => counts.succeeded += 1,
+                "canceled" => counts.canceled += 1,
+                "expired" => counts.expired += 1,
+                _ => counts.errored += 1,
+            }
+        }
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.results_path(id))
+            .map_err(|e| format!("写入批次结果失败: {}", e))?;
+        file.write_all(lines.as_bytes())
+            .map_err(|e| format!("写入批次结果失败: {}", e))?;
+        self.write_meta(&batch)
+    }
+
+    /// 发起取消：未派发的请求由 worker 记为 canceled，进行中的请求照常完成
+    pub fn cancel(&self, id: &str, owner: Option<&str>) -> Result<Option<MessageBatch>, String> {
+        let _guard = lock_store();
+        let Some(mut batch) = self.get_owned(id, owner)? else {
+            return Ok(None);
+        };
+        if batch.processing_status == ProcessingStatus::InProgress {
+            batch.processing_status = ProcessingStatus::Canceling;
+            batch.cancel_initiated_at = Some(now_ms());
+            self.write_meta(&batch)?;
+        }
+        Ok(Some(batch))
+    }
+
+    pub fn finish(&self, id: &str) -> Result<(), String> {
+        let _guard = lock_store();
+        if let Some(mut batch) = self.get(id)? {
+            batch.processing_status = ProcessingStatus::Ended;
+            batch.ended_at = Some(now_ms());
+            self.write_meta(&batch)?;
+        }
+        Ok(())
+    }
+
+    fn remove_files(&self, id: &str) -> Result<bool, String> {
+        let existed = self.meta_path(id).exists();
+        for path in [
+            self.meta_path(id),
+            self.requests_path(id),
+            self.results_path(id),
+        ] {
+            match fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(format!("删除 {} 失败: {}", path.display(), e)),
+            }
+        }
+        Ok(existed)
+    }
+
+    /// 删除批次 (调用方需确认批次已结束)
+    pub fn delete(&self, id: &str) -> Result<bool, String> {
+        if !is_valid_batch_id(id) {
+            return Ok(false);
+        }
+        let _guard = lock_store();
+        self.remove_files(id)
+    }
+
+    fn batch_bytes(&self, id: &str) -> u64 {
+        [
+            self.meta_path(id),
+            self.requests_path(id),
+            self.results_path(id),
+        ]
+        .iter()
+        .filter_map(|p| fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum()
+    }
+
+    /// 磁盘占用 (字节)
+    pub fn disk_usage(&self) -> u64 {
+        self.list()
+            .map(|batches| batches.iter().map(|b| self.batch_bytes(&b.id)).sum())
+            .unwrap_or(0)
+    }
+
+    /// 按保留策略删除已结束的批次 (按创建时间计龄，max_rows 为批次数)，返回删除的批次数
+    pub fn enforce_retention(&self, limits: &StoreRetention, now_ms: i64) -> Result<u64, String> {
+        let _guard = lock_store();
+        let cutoff = limits
+            .max_age_days
+            .map(|days| now_ms - i64::from(days) * 24 * 3600 * 1000);
+        let max_bytes = limits.max_size_bytes();
+
+        let mut kept_bytes = 0u64;
+        let mut deleted = 0u64;
+        let ended = self
+            .list()?
+            .into_iter()
+            .filter(|b| b.processing_status == ProcessingStatus::Ended);
+        for (index, batch) in ended.enumerate() {
+            let size = self.batch_bytes(&batch.id);
+            let expired = cutoff.map(|c| batch.created_at < c).unwrap_or(false);
+            let over_count = limits.max_rows.map(|n| index as u64 >= n).unwrap_or(false);
+            let over_size = max_bytes
+                .map(|max| kept_bytes + size > max)
+                .unwrap_or(false);
+            if expired || over_count || over_size {
+                self.remove_files(&batch.id)?;
+                deleted += 1;
+            } else {
+                kept_bytes += size;
+            }
+        }
+        Ok(deleted)
+    }
+}
+
+// ===== 后台 worker =====
+
+enum Outcome {
+    /// 最终结果
+    Done(Value),
+    /// 限流 / 过载等瞬时错误，可重新排队
+    Transient(Value),
+}
+
+/// 占用一个并发名额，drop 时归还
+struct BatchPermit;
+
+impl Drop for BatchPermit {
+    fn drop(&mut self) {
+        BATCH_IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// 进行中的请求未达到并发预算时占用一个名额
+fn try_acquire_permit() -> Option<BatchPermit> {
+    let max = crate::proxy::get_message_batches_config()
+        .max_concurrency
+        .max(1);
+    BATCH_IN_FLIGHT
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+            (n < max).then_some(n + 1)
+        })
+        .ok()
+        .map(|_| BatchPermit)
+}
+
+fn error_type_for_status(status: StatusCode) -> &'static str {
+    match status.as_u16() {
+        400 => "invalid_request_error",
+        401 => "authentication_error",
+        403 => "permission_error",
+        404 => "not_found_error",
+        413 => "request_too_large",
+        429 => "rate_limit_error",
+        529 => "overloaded_error",
+        _ => "api_error",
+    }
+}
+
+fn is_transient(status: StatusCode) -> bool {
+    matches!(status.as_u16(), 429 | 500 | 502 | 503 | 504 | 529)
+}
+
+/// 存在未限流且未被配额保护的账号时才派发
+async fn has_capacity(state: &AppState, model: &str) -> bool {
+    // 账号池为空时直接派发，由管线返回错误
+    if state.token_manager.len() == 0 {
+        return true;
+    }
+    let target = normalize_to_standard_id(model).unwrap_or_else(|| model.to_string());
+    state
+        .token_manager
+        .has_available_account("claude", &target)
+        .await
+}
+
+/// 以非流式请求走完整的 /v1/messages 管线
+async fn execute_request(
+    state: AppState,
+    mut params: Value,
+    anthropic_beta: Option<String>,
+    owner: Option<String>,
+) -> Outcome {
+    if let Some(obj) = params.as_object_mut() {
+        obj.insert("stream".to_string(), Value::Bool(false));
+    }
+    let mut headers = HeaderMap::new();
+    headers.insert("anthropic-version", HeaderValue::from_static("2023-06-01"));
+    if let Some(beta) = anthropic_beta.and_then(|b| HeaderValue::from_str(&b).ok()) {
+        headers.insert("anthropic-beta", beta);
+    }
+
+    let response =
+        crate::proxy::handlers::claude::handle_messages_as(state, owner, headers, params).await;
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap_or_default();
+
+    if status.is_success() {
+        return match serde_json::from_slice::<Value>(&body) {
+            Ok(message) => Outcome::Done(json!({ "type": "succeeded", "message": message })),
+            Err(e) => Outcome::Done(errored_result(
+                "api_error",
+                &format!("Invalid response from upstream: {}", e),
+            )),
+        };
+    }
+
+    let error = serde_json::from_slice::<Value>(&body)
+        .ok()
+        .and_then(|v| v.get("error").cloned())
+        .filter(|e| e.is_object())
+        .unwrap_or_else(|| {
+            json!({
+                "type": error_type_for_status(status),
+                "message": String::from_utf8_lossy(&body)
+            })
+        });
+    let result = json!({ "type": "errored", "error": { "type": "error", "error": error } });
+    if is_transient(status) {
+        Outcome::Transient(result)
+    } else {
+        Outcome::Done(result)
+    }
+}
+
+async fn run_batch(state: &AppState, batch_id: &str) -> Result<(), String> {
+    let store = MessageBatchStore::open_default()?;
+    let id = batch_id.to_string();
+    let (batch, done, requests) = store
+        .run(move |s| Ok((s.get(&id)?, s.completed_ids(&id)?, s.requests(&id)?)))
+        .await?;
+    let Some(mut batch) = batch else {
+        return Ok(());
+    };
+    let mut pending: VecDeque<BatchRequest> = requests
+        .into_iter()
+        .filter(|r| !done.contains(&r.custom_id))
+        .collect();
+    let mut retries: HashMap<String, u32> = HashMap::new();
+    let mut in_flight = tokio::task::JoinSet::new();
+    // 已完成但尚未落盘的结果，按 STORE_SYNC_INTERVAL 批量写入
+    let mut unflushed: Vec<(String, Value)> = Vec::new();
+    let mut last_sync = Instant::now();
+
+    loop {
+        if last_sync.elapsed() >= STORE_SYNC_INTERVAL {
+            let id = batch_id.to_string();
+            let results = std::mem::take(&mut unflushed);
+            let latest = store
+                .run(move |s| {
+                    s.record_results(&id, &results)?;
+                    s.get(&id)
+                })
+                .await?;
+            let Some(latest) = latest else {
+                // 批次已被删除
+                in_flight.abort_all();
+                return Ok(());
+            };
+            batch = latest;
+            last_sync = Instant::now();
+        }
+
+        let stop_kind = if batch.processing_status == ProcessingStatus::Canceling {
+            Some("canceled")
+        } else if now_ms() >= batch.expires_at {
+            Some("expired")
+        } else {
+            None
+        };
+        if let Some(kind) = stop_kind {
+            unflushed.extend(
+                pending
+                    .drain(..)
+                    .map(|request| (request.custom_id, json!({ "type": kind }))),
+            );
+        }
+
+        // 并发不超过全局预算与账号数
+        while !pending.is_empty() && in_flight.len() < state.token_manager.len().max(1) {
+            let model = pending
+                .front()
+                .and_then(|r| r.params.get("model"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string();
+            if !has_capacity(state, &model).await {
+                break;
+            }
+            let Some(permit) = try_acquire_permit() else {
+                break;
+            };
+            let Some(request) = pending.pop_front() else {
+                break;
+            };
+
+            let state = state.clone();
+            let beta = batch.anthropic_beta.clone();
+            let owner = batch.owner.clone();
+            in_flight.spawn(async move {
+                let _permit = permit;
+                let outcome = std::panic::AssertUnwindSafe(execute_request(
+                    state,
+                    request.params.clone(),
+                    beta,
+                    owner,
+                ))
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| {
+                    Outcome::Done(errored_result(
+                        "api_error",
+                        "Internal error while processing request",
+                    ))
+                });
+                (request, outcome)
+            });
+        }
+
+        if pending.is_empty() && in_flight.is_empty() {
+            let id = batch_id.to_string();
+            store
+                .run(move |s| {
+                    s.record_results(&id, &unflushed)?;
+                    s.finish(&id)
+                })
+                .await?;
+            tracing::info!("[Batches] Batch {} ended", batch_id);
+            return Ok(());
+        }
+
+        tokio::select! {
+            Some(joined) = in_flight.join_next(), if !in_flight.is_empty() => match joined {
+                Ok((request, Outcome::Done(result))) => {
+                    unflushed.push((request.custom_id, result));
+                }
+                Ok((request, Outcome::Transient(result))) => {
+                    let attempts = retries.entry(request.custom_id.clone()).or_insert(0);
+                    *attempts += 1;
+                    if *attempts > MAX_TRANSIENT_RETRIES {
+                        unflushed.push((request.custom_id, result));
+                    } else {
+                        tracing::debug!(
+                            "[Batches] Requeue {} in {} after transient error (attempt {})",
+                            request.custom_id,
+                            batch_id,
+                            attempts
+                        );
+                        pending.push_back(request);
+                    }
+                }
+                Err(e) => tracing::error!("[Batches] Worker task failed in {}: {}", batch_id, e),
+            },
+            _ = tokio::time::sleep(DISPATCH_POLL_INTERVAL) => {}
+        }
+    }
+}
+
+/// 启动批次 worker (同一批次同时只运行一个)
+pub fn spawn_batch_worker(state: AppState, batch_id: String) {
+    {
+        let mut running = RUNNING_BATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        if running.contains(&batch_id) {
+            return;
+        }
+        running.push(batch_id.clone());
+    }
+
+    tokio::spawn(async move {
+        if let Err(e) = run_batch(&state, &batch_id).await {
+            tracing::error!("[Batches] Batch {} worker stopped: {}", batch_id, e);
+        }
+        RUNNING_BATCHES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .retain(|id| id != &batch_id);
+    });
+}
+
+/// 服务启动时恢复未结束的批次
+pub fn resume_pending_batches(state: AppState) {
+    let batches = match MessageBatchStore::open_default().and_then(|store| store.list()) {
+        Ok(batches) => batches,
+        Err(e) => {
+            tracing::warn!("[Batches] Failed to load batches: {}", e);
+            return;
+        }
+    };
+    for batch in batches
+        .into_iter()
+        .filter(|b| b.processing_status != ProcessingStatus::Ended)
+    {
+        tracing::info!(
+            "[Batches] Resuming batch {} ({} requests pending)",
+            batch.id,
+            batch.request_counts.processing
+        );
+        spawn_batch_worker(state.clone(), batch.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> (MessageBatchStore, PathBuf) {
+        let dir =
+            std::env::temp_dir().join(format!("antigravity-batches-{}", uuid::Uuid::new_v4()));
+        (MessageBatchStore::new(dir.clone()), dir)
+    }
+
+    fn sample_requests(n: usize) -> Vec<BatchRequest> {
+        (0..n)
+            .map(|i| BatchRequest {
+                custom_id: format!("req-{}", i),
+                params: json!({
+                    "model": "claude-sonnet-4-5",
+                    "max_tokens": 16,
+                    "messages": [{ "role": "user", "content": "hi" }]
+                }),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_batch_requests_validation() {
+        let ok = json!({ "requests": [
+            { "custom_id": "a-1", "params": { "model": "m", "messages": [] } },
+            { "custom_id": "b_2", "params": { "model": "m", "messages": [] } }
+        ]});
+        assert_eq!(parse_batch_requests(&ok).unwrap().len(), 2);
+
+        let duplicate = json!({ "requests": [
+            { "custom_id": "a", "params": { "model": "m", "messages": [] } },
+            { "custom_id": "a", "params": { "model": "m", "messages": [] } }
+        ]});
+        assert!(parse_batch_requests(&duplicate)
+            .unwrap_err()
+            .contains("duplicate"));
+
+        let bad_id = json!({ "requests": [
+            { "custom_id": "has space", "params": { "model": "m", "messages": [] } }
+        ]});
+        assert!(parse_batch_requests(&bad_id).is_err());
+
+        let missing_model = json!({ "requests": [
+            { "custom_id": "a", "params": { "messages": [] } }
+        ]});
+        assert!(parse_batch_requests(&missing_model)
+            .unwrap_err()
+            .contains("params.model"));
+
+        assert!(parse_batch_requests(&json!({ "requests": [] })).is_err());
+    }
+
+    #[test]
+    fn test_store_lifecycle_and_counts() {
+        let (store, dir) = temp_store();
+        let batch = store.create(&sample_requests(3), None, None).unwrap();
+        assert!(is_valid_batch_id(&batch.id));
+        assert_eq!(batch.request_counts.processing, 3);
+        assert_eq!(store.requests(&batch.id).unwrap().len(), 3);
+
+        store
+            .record_results(
+                &batch.id,
+                &[
+                    (
+                        "req-0".to_string(),
+                        json!({ "type": "succeeded", "message": {} }),
+                    ),
+                    (
+                        "req-1".to_string(),
+                        errored_result("invalid_request_error", "bad"),
+                    ),
+                ],
+            )
+            .unwrap();
+        assert_eq!(
+            store
+                .get(&batch.id)
+                .unwrap()
+                .unwrap()
+                .request_counts
+                .processing,
+            1
+        );
+
+        let canceling = store.cancel(&batch.id, None).unwrap().unwrap();
+        assert_eq!(canceling.processing_status, ProcessingStatus::Canceling);
+        assert!(canceling.cancel_initiated_at.is_some());
+
+        store
+            .record_results(
+                &batch.id,
+                &[("req-2".to_string(), json!({ "type": "canceled" }))],
+            )
+            .unwrap();
+        store.finish(&batch.id).unwrap();
+
+        let ended = store.get(&batch.id).unwrap().unwrap();
+        assert_eq!(ended.processing_status, ProcessingStatus::Ended);
+        assert_eq!(
+            ended.request_counts,
+            RequestCounts {
+                processing: 0,
+                succeeded: 1,
+                errored: 1,
+                canceled: 1,
+                expired: 0
+            }
+        );
+        assert_eq!(store.completed_ids(&batch.id).unwrap().len(), 3);
+        assert_eq!(store.results(&batch.id).unwrap().lines().count(), 3);
+
+        let api = ended.to_api("http://localhost:8045");
+        assert_eq!(api["type"], "message_batch");
+        assert_eq!(api["processing_status"], "ended");
+        assert_eq!(
+            api["results_url"],
+            format!(
+                "http://localhost:8045/v1/messages/batches/{}/results",
+                batch.id
+            )
+        );
+
+        assert!(store.delete(&batch.id).unwrap());
+        assert!(store.get(&batch.id).unwrap().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_results_url_hidden_until_ended() {
+        let (store, dir) = temp_store();
+        let batch = store.create(&sample_requests(1), None, None).unwrap();
+        let api = batch.to_api("http://localhost:8045");
+        assert_eq!(api["processing_status"], "in_progress");
+        assert!(api["results_url"].is_null());
+        assert!(store.get("../etc/passwd").unwrap().is_none());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_batches_are_scoped_to_owner() {
+        let (store, dir) = temp_store();
+        let alice = store
+            .create(&sample_requests(1), None, Some("alice".to_string()))
+            .unwrap();
+        let shared = store.create(&sample_requests(1), None, None).unwrap();
+
+        assert!(store.get_owned(&alice.id, Some("alice")).unwrap().is_some());
+        assert!(store.get_owned(&alice.id, Some("bob")).unwrap().is_none());
+        assert!(store.get_owned(&alice.id, None).unwrap().is_none());
+        assert!(store.cancel(&alice.id, Some("bob")).unwrap().is_none());
+        assert_eq!(
+            store.get(&alice.id).unwrap().unwrap().processing_status,
+            ProcessingStatus::InProgress
+        );
+
+        let listed: Vec<String> = store
+            .list_owned(Some("alice"))
+            .unwrap()
+            .into_iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(listed, vec![alice.id.clone()]);
+        let listed: Vec<String> = store
+            .list_owned(None)
+            .unwrap()
+            .into_iter()
+            .map(|b| b.id)
+            .collect();
+        assert_eq!(listed, vec![shared.id.clone()]);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_permits_follow_configured_budget() {
+        use crate::proxy::config::MessageBatchesConfig;
+
+        crate::proxy::update_message_batches_config(MessageBatchesConfig { max_concurrency: 2 });
+        let first = try_acquire_permit().unwrap();
+        let _second = try_acquire_permit().unwrap();
+        assert!(try_acquire_permit().is_none());
+
+        drop(first);
+        assert!(try_acquire_permit().is_some());
+        crate::proxy::update_message_batches_config(MessageBatchesConfig::default());
+    }
+
+    #[test]
+    fn test_retention_only_prunes_ended_batches() {
+        let (store, dir) = temp_store();
+        let old = store.create(&sample_requests(1), None, None).unwrap();
+        let running = store.create(&sample_requests(1), None, None).unwrap();
+        store.finish(&old.id).unwrap();
+
+        // 30 天后按 29 天保留期清理：仅删除已结束的批次
+        let later = now_ms() + 30 * 24 * 3600 * 1000;
+        let deleted = store
+            .enforce_retention(&StoreRetention::max_age(29), later)
+            .unwrap();
+        assert_eq!(deleted, 1);
+        assert!(store.get(&old.id).unwrap().is_none());
+        assert!(store.get(&running.id).unwrap().is_some());
+        let _ = fs::remove_dir_all(dir);
+    }
+}
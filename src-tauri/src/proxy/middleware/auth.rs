@@ -231,6 +231,12 @@ pub struct UserTokenIdentity {
     pub username: String,
 }
 
+/// 本地资源 (Files / Message Batches) 的归属: User Token 请求按 token_id 隔离，
+/// 主 API Key 与免鉴权请求为 None (共用同一空间)
+pub fn resource_owner(identity: Option<axum::Extension<UserTokenIdentity>>) -> Option<String> {
+    identity.map(|axum::Extension(identity)| identity.token_id)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
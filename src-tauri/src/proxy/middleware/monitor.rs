@@ -4,15 +4,17 @@ use axum::{
     response::Response,
     body::Body,
 };
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use crate::proxy::server::AppState;
-use crate::proxy::monitor::ProxyRequestLog;
+use crate::proxy::monitor::{ProxyRequestLog, UpstreamUsage};
 use serde_json::Value;
 use crate::proxy::middleware::auth::UserTokenIdentity;
 use futures::StreamExt;
 
 const MAX_REQUEST_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB
 const MAX_RESPONSE_LOG_SIZE: usize = 100 * 1024 * 1024; // 100MB for image responses
+/// 生成耗时过短 (如整段响应一次性到达) 时不计算输出速度，避免异常大的值
+const MIN_RATE_WINDOW: Duration = Duration::from_millis(100);
 
 /// Helper function to record User Token usage
 fn record_user_token_usage(
@@ -33,6 +35,74 @@ fn record_user_token_usage(
     }
 }
 
+/// 从客户端响应的 usage 对象提取 Token 用量 (兼容 OpenAI / Claude / Gemini 格式)
+/// 统一口径: input_tokens 含缓存命中部分，output_tokens 不含思考 Token；
+/// 仅覆盖本次出现的字段 (流式响应中 usage 可能分多个事件下发)
+fn apply_usage(log: &mut ProxyRequestLog, usage: &Value) {
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .find_map(|k| usage.get(*k))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32)
+    };
+    let nested = |parent: &str, key: &str| {
+        usage
+            .get(parent)
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u32)
+    };
+
+    let cached = field(&["cache_read_input_tokens", "cachedContentTokenCount"])
+        .or_else(|| nested("prompt_tokens_details", "cached_tokens"));
+    let thinking = field(&["thoughtsTokenCount"])
+        .or_else(|| nested("completion_tokens_details", "reasoning_tokens"));
+    let tool = field(&["toolUsePromptTokenCount"]);
+
+    let mut input = field(&["prompt_tokens", "input_tokens", "promptTokenCount"]);
+    let mut output = field(&["completion_tokens", "output_tokens", "candidatesTokenCount"]);
+    // Claude: input_tokens 不含缓存读取/写入部分
+    if usage.get("input_tokens").is_some() {
+        let cache_creation = field(&["cache_creation_input_tokens"]).unwrap_or(0);
+        input = input.map(|i| i + cached.unwrap_or(0) + cache_creation);
+    }
+    // OpenAI: completion_tokens 含 reasoning_tokens
+    if usage.get("completion_tokens").is_some() {
+        output = output.map(|o| o.saturating_sub(thinking.unwrap_or(0)));
+    }
+    if input.is_none() && output.is_none() {
+        output = field(&["total_tokens", "totalTokenCount"]);
+    }
+
+    log.input_tokens = input.or(log.input_tokens);
+    log.output_tokens = output.or(log.output_tokens);
+    log.cached_tokens = cached.or(log.cached_tokens);
+    log.thinking_tokens = thinking.or(log.thinking_tokens);
+    log.tool_tokens = tool.or(log.tool_tokens);
+}
+
+/// SSE 数据块是否仅包含注释行 (心跳 / ping)，心跳不计入首 Token 时间
+fn is_heartbeat_chunk(chunk: &[u8]) -> bool {
+    std::str::from_utf8(chunk)
+        .map(|s| s.lines().map(str::trim).all(|l| l.is_empty() || l.starts_with(':')))
+        .unwrap_or(false)
+}
+
+/// 写入流式响应的延迟指标：duration 为整个流的墙钟时间，
+/// stream_duration_ms 为首 Token 到流结束的生成耗时，tokens_per_sec 按输出 + 思考 Token 计算
+fn apply_stream_timing(log: &mut ProxyRequestLog, ttft: Option<Duration>, total: Duration) {
+    log.duration = total.as_millis() as u64;
+    if let Some(ttft) = ttft {
+        let generation = total.saturating_sub(ttft);
+        log.ttft_ms = Some(ttft.as_millis() as u64);
+        log.stream_duration_ms = Some(generation.as_millis() as u64);
+        let tokens = log.output_tokens.unwrap_or(0) + log.thinking_tokens.unwrap_or(0);
+        if tokens > 0 && generation >= MIN_RATE_WINDOW {
+            log.tokens_per_sec = Some(tokens as f64 / generation.as_secs_f64());
+        }
+    }
+}
+
 pub async fn monitor_middleware(
     State(state): State<AppState>,
     request: Request,
@@ -88,7 +158,18 @@ pub async fn monitor_middleware(
     
     let request = if method == "POST" {
         let (parts, body) = request.into_parts();
+        let is_multipart = parts
+            .headers
+            .get("content-type")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.starts_with("multipart/"))
+            .unwrap_or(false);
         match axum::body::to_bytes(body, MAX_REQUEST_LOG_SIZE).await {
+            // 文件上传只记录大小，不落盘原始内容
+            Ok(bytes) if is_multipart => {
+                request_body_str = Some(format!("[Multipart Upload: {} bytes]", bytes.len()));
+                Request::from_parts(parts, Body::from(bytes))
+            }
             Ok(bytes) => {
                 if model.is_none() {
                     model = serde_json::from_slice::<Value>(&bytes).ok().and_then(|v|
@@ -112,13 +193,18 @@ pub async fn monitor_middleware(
         request
     };
     
-    let response = next.run(request).await;
+    // 在作用域内执行，以获取上游调用实际使用的代理池节点
+    let (response, proxy_id) =
+        crate::proxy::proxy_pool::with_request_proxy_scope(next.run(request)).await;
     
     // user_token_identity 已在上面从请求 extensions 中提取
     
     let duration = start.elapsed().as_millis() as u64;
     let status = response.status().as_u16();
     
+    // 上游原始用量明细 (由协议处理器写入响应扩展)，优先于客户端口径的 usage
+    let upstream_usage = response.extensions().get::<UpstreamUsage>().cloned();
+
     let content_type = response.headers().get("content-type")
         .and_then(|v| v.to_str().ok())
         .unwrap_or("")
@@ -138,10 +224,18 @@ pub async fn monitor_middleware(
         .and_then(|v| v.to_str().ok())
         .map(|s| s.to_string());
 
+    // Extract context compression report from X-Context-Compression header if present
+    let context_compression = response
+        .headers()
+        .get("X-Context-Compression")
+        .and_then(|v| v.to_str().ok())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+
     // Determine protocol from URL path
     let protocol = if uri.contains("/v1/messages") {
         Some("anthropic".to_string())
-    } else if uri.contains("/v1beta/models") {
+    } else if uri.contains("/v1beta/") {
         Some("gemini".to_string())
     } else if uri.starts_with("/v1/") {
         Some("openai".to_string())
@@ -171,8 +265,16 @@ pub async fn monitor_middleware(
         response_body: None,
         input_tokens: None,
         output_tokens: None,
+        cached_tokens: None,
+        thinking_tokens: None,
+        tool_tokens: None,
         protocol,
         username,
+        context_compression,
+        ttft_ms: None,
+        stream_duration_ms: None,
+        tokens_per_sec: None,
+        proxy_id,
     };
 
 
@@ -184,9 +286,13 @@ pub async fn monitor_middleware(
         tokio::spawn(async move {
             let mut all_stream_data = Vec::new();
             let mut last_few_bytes = Vec::new();
+            let mut ttft = None;
             
             while let Some(chunk_res) = stream.next().await {
                 if let Ok(chunk) = chunk_res {
+                    if ttft.is_none() && !chunk.is_empty() && !is_heartbeat_chunk(&chunk) {
+                        ttft = Some(start.elapsed());
+                    }
                     all_stream_data.extend_from_slice(&chunk);
                     
                     if chunk.len() > 8192 {
@@ -202,6 +308,7 @@ pub async fn monitor_middleware(
                     let _ = tx.send(Err(axum::Error::new(e))).await;
                 }
             }
+            let stream_elapsed = start.elapsed();
             
             // Parse and consolidate stream data into readable format
             if let Ok(full_response) = std::str::from_utf8(&all_stream_data) {
@@ -256,26 +363,13 @@ pub async fn monitor_middleware(
                             .or(json.get("usageMetadata"))
                             .or(json.get("response").and_then(|r| r.get("usage")))
                         {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
+                            apply_usage(&mut log, usage);
                         }
                     }
                 }
+                if let Some(usage) = upstream_usage.as_ref().and_then(|u| u.snapshot()) {
+                    apply_usage(&mut log, &usage);
+                }
                 
                 // Build consolidated response object
                 let mut consolidated = serde_json::Map::new();
@@ -295,6 +389,15 @@ pub async fn monitor_middleware(
                 if let Some(output) = log.output_tokens {
                     consolidated.insert("output_tokens".to_string(), Value::Number(output.into()));
                 }
+                for (key, value) in [
+                    ("cached_tokens", log.cached_tokens),
+                    ("thinking_tokens", log.thinking_tokens),
+                    ("tool_tokens", log.tool_tokens),
+                ] {
+                    if let Some(count) = value {
+                        consolidated.insert(key.to_string(), Value::Number(count.into()));
+                    }
+                }
                 
                 if consolidated.is_empty() {
                     // Fallback: store raw SSE data if parsing failed
@@ -317,16 +420,7 @@ pub async fn monitor_middleware(
                                     .or(json.get("usageMetadata"))
                                     .or(json.get("response").and_then(|r| r.get("usage")))
                                 {
-                                    log.input_tokens = usage.get("prompt_tokens")
-                                        .or(usage.get("input_tokens"))
-                                        .or(usage.get("promptTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
-                                    log.output_tokens = usage.get("completion_tokens")
-                                        .or(usage.get("output_tokens"))
-                                        .or(usage.get("candidatesTokenCount"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|v| v as u32);
+                                    apply_usage(&mut log, usage);
                                     break;
                                 }
                             }
@@ -335,6 +429,8 @@ pub async fn monitor_middleware(
                 }
             }
             
+            apply_stream_timing(&mut log, ttft, stream_elapsed);
+
             if log.status >= 400 {
                 log.error = Some("Stream Error or Failed".to_string());
             }
@@ -354,25 +450,12 @@ pub async fn monitor_middleware(
                     if let Ok(json) = serde_json::from_str::<Value>(&s) {
                         // 支持 OpenAI "usage" 或 Gemini "usageMetadata"
                         if let Some(usage) = json.get("usage").or(json.get("usageMetadata")) {
-                            log.input_tokens = usage.get("prompt_tokens")
-                                .or(usage.get("input_tokens"))
-                                .or(usage.get("promptTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                            log.output_tokens = usage.get("completion_tokens")
-                                .or(usage.get("output_tokens"))
-                                .or(usage.get("candidatesTokenCount"))
-                                .and_then(|v| v.as_u64())
-                                .map(|v| v as u32);
-                                
-                            if log.input_tokens.is_none() && log.output_tokens.is_none() {
-                                log.output_tokens = usage.get("total_tokens")
-                                    .or(usage.get("totalTokenCount"))
-                                    .and_then(|v| v.as_u64())
-                                    .map(|v| v as u32);
-                            }
+                            apply_usage(&mut log, usage);
                         }
                     }
+                    if let Some(usage) = upstream_usage.as_ref().and_then(|u| u.snapshot()) {
+                        apply_usage(&mut log, &usage);
+                    }
                     log.response_body = Some(s.to_string());
                 } else {
                     log.response_body = Some("[Binary Response Data]".to_string());
@@ -408,3 +491,166 @@ pub async fn monitor_middleware(
         response
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn empty_log() -> ProxyRequestLog {
+        ProxyRequestLog {
+            id: "test".to_string(),
+            timestamp: 0,
+            method: "POST".to_string(),
+            url: "/v1/chat/completions".to_string(),
+            status: 200,
+            duration: 0,
+            model: None,
+            mapped_model: None,
+            account_email: None,
+            client_ip: None,
+            error: None,
+            request_body: None,
+            response_body: None,
+            input_tokens: None,
+            output_tokens: None,
+            cached_tokens: None,
+            thinking_tokens: None,
+            tool_tokens: None,
+            protocol: None,
+            username: None,
+            context_compression: None,
+            ttft_ms: None,
+            stream_duration_ms: None,
+            tokens_per_sec: None,
+            proxy_id: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_usage_gemini() {
+        let mut log = empty_log();
+        apply_usage(
+            &mut log,
+            &json!({
+                "promptTokenCount": 100,
+                "candidatesTokenCount": 20,
+                "cachedContentTokenCount": 60,
+                "thoughtsTokenCount": 30,
+                "toolUsePromptTokenCount": 15,
+                "totalTokenCount": 165
+            }),
+        );
+        assert_eq!(log.input_tokens, Some(100));
+        assert_eq!(log.output_tokens, Some(20));
+        assert_eq!(log.cached_tokens, Some(60));
+        assert_eq!(log.thinking_tokens, Some(30));
+        assert_eq!(log.tool_tokens, Some(15));
+    }
+
+    #[test]
+    fn test_apply_usage_openai_excludes_reasoning_from_output() {
+        let mut log = empty_log();
+        apply_usage(
+            &mut log,
+            &json!({
+                "prompt_tokens": 100,
+                "completion_tokens": 50,
+                "total_tokens": 150,
+                "prompt_tokens_details": { "cached_tokens": 40 },
+                "completion_tokens_details": { "reasoning_tokens": 30 }
+            }),
+        );
+        assert_eq!(log.input_tokens, Some(100));
+        assert_eq!(log.output_tokens, Some(20));
+        assert_eq!(log.cached_tokens, Some(40));
+        assert_eq!(log.thinking_tokens, Some(30));
+    }
+
+    #[test]
+    fn test_apply_usage_claude_includes_cache_in_input() {
+        let mut log = empty_log();
+        apply_usage(
+            &mut log,
+            &json!({
+                "input_tokens": 10,
+                "output_tokens": 0,
+                "cache_read_input_tokens": 90,
+                "cache_creation_input_tokens": 0
+            }),
+        );
+        // message_delta 只携带 output_tokens 时保留已有字段
+        apply_usage(&mut log, &json!({ "output_tokens": 42 }));
+        assert_eq!(log.input_tokens, Some(100));
+        assert_eq!(log.output_tokens, Some(42));
+        assert_eq!(log.cached_tokens, Some(90));
+        assert_eq!(log.thinking_tokens, None);
+    }
+
+    #[tokio::test]
+    async fn test_upstream_usage_overrides_claude_output() {
+        let upstream_usage = UpstreamUsage::default();
+        let chunks: Vec<Result<bytes::Bytes, String>> = vec![
+            Ok(bytes::Bytes::from("data: {\"response\":{\"usageMetadata\":{\"promptTokenCount\":100,")),
+            Ok(bytes::Bytes::from("\"candidatesTokenCount\":5}}}\n\n")),
+            Ok(bytes::Bytes::from(
+                "data: {\"response\":{\"usageMetadata\":{\"promptTokenCount\":100,\"candidatesTokenCount\":40,\"thoughtsTokenCount\":60}}}\n\n",
+            )),
+        ];
+        let observed: Vec<_> = upstream_usage
+            .observe(Box::pin(futures::stream::iter(chunks)))
+            .collect()
+            .await;
+        assert_eq!(observed.len(), 3);
+
+        // 客户端 (Claude) 口径: output_tokens = candidates + thoughts
+        let mut log = empty_log();
+        apply_usage(&mut log, &json!({ "input_tokens": 100, "output_tokens": 100 }));
+        assert_eq!(log.thinking_tokens, None);
+
+        // 上游明细覆盖后拆分出思考 Token，且只计入最后一个 (累计) usageMetadata
+        apply_usage(&mut log, &upstream_usage.snapshot().unwrap());
+        assert_eq!(log.input_tokens, Some(100));
+        assert_eq!(log.output_tokens, Some(40));
+        assert_eq!(log.thinking_tokens, Some(60));
+
+        // 多轮上游调用按字段累加
+        upstream_usage.add(&json!({ "promptTokenCount": 150, "candidatesTokenCount": 10 }));
+        let total = upstream_usage.snapshot().unwrap();
+        assert_eq!(total["promptTokenCount"], 250);
+        assert_eq!(total["candidatesTokenCount"], 50);
+        assert_eq!(total["thoughtsTokenCount"], 60);
+    }
+
+    #[test]
+    fn test_heartbeat_chunks_do_not_count_as_first_token() {
+        assert!(is_heartbeat_chunk(b": ping\n\n"));
+        assert!(is_heartbeat_chunk(b"\n"));
+        assert!(!is_heartbeat_chunk(b": ping\n\ndata: {\"x\":1}\n\n"));
+    }
+
+    #[test]
+    fn test_apply_stream_timing() {
+        let mut log = empty_log();
+        log.output_tokens = Some(150);
+        log.thinking_tokens = Some(50);
+        apply_stream_timing(&mut log, Some(Duration::from_millis(800)), Duration::from_millis(2800));
+        assert_eq!(log.duration, 2800);
+        assert_eq!(log.ttft_ms, Some(800));
+        assert_eq!(log.stream_duration_ms, Some(2000));
+        assert_eq!(log.tokens_per_sec, Some(100.0));
+
+        // 整段响应一次性到达：不计算速度
+        let mut log = empty_log();
+        log.output_tokens = Some(10);
+        apply_stream_timing(&mut log, Some(Duration::from_millis(500)), Duration::from_millis(510));
+        assert_eq!(log.stream_duration_ms, Some(10));
+        assert_eq!(log.tokens_per_sec, None);
+
+        // 没有任何数据块
+        let mut log = empty_log();
+        apply_stream_timing(&mut log, None, Duration::from_millis(300));
+        assert_eq!(log.duration, 300);
+        assert_eq!(log.ttft_ms, None);
+    }
+}
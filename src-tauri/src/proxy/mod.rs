@@ -9,17 +9,23 @@ pub mod token_manager;
 
 // 新架构模块
 pub mod audio; // 音频处理模块
+pub mod cached_contents; // Gemini cachedContents 模拟层
 pub mod cli_sync; // CLI 配置同步 (v3.3.35)
 pub mod clients; // HTTP clients for different providers
 pub mod common; // 公共工具
 pub mod debug_logger;
+pub mod files; // 本地文件存储 (Files API)
 pub mod handlers; // API 端点处理器
 pub mod mappers; // 协议转换器
+pub mod message_batches; // Anthropic Message Batches 模拟层
 pub mod middleware; // Axum 中间件
 pub mod monitor; // 监控
+pub mod openapi; // 管理 API OpenAPI 文档
+pub mod prompt_cache; // Claude prompt caching 语义 (断点前缀路由 + 缓存用量)
 pub mod providers; // Extra upstream providers (z.ai, etc.)
 pub mod rate_limit; // 限流跟踪
 pub mod session_manager; // 会话指纹管理
+pub mod session_snapshot; // 会话快照持久化 (签名缓存 + 粘性绑定)
 pub mod signature_cache; // Signature Cache (v3.3.16)
 pub mod sticky_config; // 粘性调度配置
 pub mod upstream; // 上游客户端
@@ -29,11 +35,21 @@ pub mod zai_vision_tools; // Built-in Vision MCP tools (z.ai vision API) // 调
 
 pub use config::get_thinking_budget_config;
 pub use config::update_thinking_budget_config;
+pub use config::get_tool_result_compression_config;
+pub use config::update_tool_result_compression_config;
+pub use config::get_log_retention_config;
+pub use config::update_log_retention_config;
+pub use config::get_server_tools_config;
+pub use config::update_server_tools_config;
+pub use config::get_message_batches_config;
+pub use config::update_message_batches_config;
 pub use config::ProxyAuthMode;
 pub use config::ProxyConfig;
 pub use config::ProxyPoolConfig;
 pub use config::ThinkingBudgetConfig;
 pub use config::ThinkingBudgetMode;
+pub use config::ToolResultCompressionConfig;
+pub use config::ToolResultStrategy;
 pub use config::ZaiConfig;
 pub use config::ZaiDispatchMode;
 pub use security::ProxySecurityConfig;
@@ -1,10 +1,11 @@
+use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 use std::collections::VecDeque;
 use tokio::sync::RwLock;
 use tauri::Emitter;
 use std::sync::atomic::{AtomicBool, Ordering};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ProxyRequestLog {
     pub id: String,
     pub timestamp: i64,
@@ -21,11 +22,98 @@ pub struct ProxyRequestLog {
     pub response_body: Option<String>,
     pub input_tokens: Option<u32>,
     pub output_tokens: Option<u32>,
+    pub cached_tokens: Option<u32>,   // 缓存命中的输入 Token (已包含在 input_tokens 中)
+    pub thinking_tokens: Option<u32>, // 思考 Token (不含在 output_tokens 中)
+    pub tool_tokens: Option<u32>,     // 工具调用结果注入的提示词 Token (如搜索 grounding)
     pub protocol: Option<String>,     // 协议类型: "openai", "anthropic", "gemini"
     pub username: Option<String>,     // User token username
+    pub context_compression: Option<String>, // 上下文压缩报告 (JSON)
+    pub ttft_ms: Option<u64>,            // 流式响应首个 Token 耗时 (ms)
+    pub stream_duration_ms: Option<u64>, // 首个 Token 至流结束的生成耗时 (ms)
+    pub tokens_per_sec: Option<f64>,     // 生成速度 (输出 + 思考 Token / 生成耗时)
+    pub proxy_id: Option<String>,        // 实际使用的代理池节点 ID
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+/// 上游 (v1internal) 原始 usageMetadata 明细，由协议处理器写入响应扩展
+///
+/// 客户端可见的 usage 可能经过口径换算 (Claude 的 output_tokens 含思考 Token，且可能被上下文缩放)，
+/// 监控中间件优先使用这里记录的原始明细
+#[derive(Debug, Clone, Default)]
+pub struct UpstreamUsage(std::sync::Arc<std::sync::Mutex<Option<serde_json::Value>>>);
+
+impl UpstreamUsage {
+    const FIELDS: [&'static str; 6] = [
+        "promptTokenCount",
+        "candidatesTokenCount",
+        "thoughtsTokenCount",
+        "cachedContentTokenCount",
+        "toolUsePromptTokenCount",
+        "totalTokenCount",
+    ];
+
+    /// 计入一次上游响应的最终 usageMetadata (服务端工具循环等多轮上游调用按字段累加)
+    pub fn add(&self, usage: &serde_json::Value) {
+        let Ok(mut slot) = self.0.lock() else {
+            return;
+        };
+        let total = slot.get_or_insert_with(|| serde_json::json!({}));
+        for key in Self::FIELDS {
+            if let Some(count) = usage.get(key).and_then(|v| v.as_u64()) {
+                let current = total.get(key).and_then(|v| v.as_u64()).unwrap_or(0);
+                total[key] = serde_json::json!(current + count);
+            }
+        }
+    }
+
+    pub fn snapshot(&self) -> Option<serde_json::Value> {
+        self.0.lock().ok().and_then(|slot| slot.clone())
+    }
+
+    /// 包装上游 SSE 字节流: 数据原样透传，跟踪最后一个 usageMetadata 并在流结束时计入
+    pub fn observe<E: Send + 'static>(
+        &self,
+        upstream: std::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, E>> + Send>>,
+    ) -> std::pin::Pin<Box<dyn futures::Stream<Item = Result<bytes::Bytes, E>> + Send>> {
+        use futures::StreamExt;
+
+        let recorder = self.clone();
+        Box::pin(async_stream::stream! {
+            let mut upstream = upstream;
+            let mut pending: Vec<u8> = Vec::new();
+            let mut last_usage = None;
+            while let Some(item) = upstream.next().await {
+                if let Ok(chunk) = &item {
+                    pending.extend_from_slice(chunk);
+                    while let Some(pos) = pending.iter().position(|&b| b == b'\n') {
+                        let line: Vec<u8> = pending.drain(..=pos).collect();
+                        if let Some(usage) = Self::usage_from_line(&line) {
+                            last_usage = Some(usage);
+                        }
+                    }
+                }
+                yield item;
+            }
+            if let Some(usage) = Self::usage_from_line(&pending).or(last_usage) {
+                recorder.add(&usage);
+            }
+        })
+    }
+
+    fn usage_from_line(line: &[u8]) -> Option<serde_json::Value> {
+        let line = std::str::from_utf8(line).ok()?.trim();
+        let data = line.strip_prefix("data:")?.trim();
+        if !data.contains("usageMetadata") {
+            return None;
+        }
+        let json: serde_json::Value = serde_json::from_str(data).ok()?;
+        json.get("response")
+            .unwrap_or(&json)
+            .get("usageMetadata")
+            .cloned()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct ProxyStats {
     pub total_requests: u64,
     pub success_count: u64,
@@ -47,20 +135,6 @@ impl ProxyMonitor {
             tracing::error!("Failed to initialize proxy DB: {}", e);
         }
 
-        // Auto cleanup old logs (keep last 30 days)
-        tokio::spawn(async {
-            match crate::modules::proxy_db::cleanup_old_logs(30) {
-                Ok(deleted) => {
-                    if deleted > 0 {
-                        tracing::info!("Auto cleanup: removed {} old logs (>30 days)", deleted);
-                    }
-                }
-                Err(e) => {
-                    tracing::error!("Failed to cleanup old logs: {}", e);
-                }
-            }
-        });
-
         Self {
             logs: RwLock::new(VecDeque::with_capacity(max_logs)),
             stats: RwLock::new(ProxyStats::default()),
@@ -78,16 +152,25 @@ impl ProxyMonitor {
         self.enabled.load(Ordering::Relaxed)
     }
 
-    pub async fn log_request(&self, log: ProxyRequestLog) {
+    pub async fn log_request(&self, mut log: ProxyRequestLog) {
         if let (Some(account), Some(input), Some(output)) = (
             &log.account_email,
             log.input_tokens,
             log.output_tokens,
         ) {
-            let model = log.model.clone().unwrap_or_else(|| "unknown".to_string());
-            let account = account.clone();
+            let usage = crate::modules::token_stats::UsageRecord {
+                account_email: account.clone(),
+                model: log.model.clone().unwrap_or_else(|| "unknown".to_string()),
+                mapped_model: log.mapped_model.clone(),
+                username: log.username.clone(),
+                input_tokens: input,
+                output_tokens: output,
+                cached_tokens: log.cached_tokens.unwrap_or(0),
+                thinking_tokens: log.thinking_tokens.unwrap_or(0),
+                tool_tokens: log.tool_tokens.unwrap_or(0),
+            };
             tokio::spawn(async move {
-                if let Err(e) = crate::modules::token_stats::record_usage(&account, &model, input, output) {
+                if let Err(e) = crate::modules::token_stats::record_usage(&usage) {
                     tracing::debug!("Failed to record token stats: {}", e);
                 }
             });
@@ -96,6 +179,11 @@ impl ProxyMonitor {
         if !self.is_enabled() {
             return;
         }
+        // 按保留策略裁剪请求 / 响应体 (内存与数据库中均不保留超出部分)
+        crate::modules::log_retention::apply_body_capture(
+            &mut log,
+            &crate::proxy::get_log_retention_config(),
+        );
         tracing::info!("[Monitor] Logging request: {} {}", log.method, log.url);
         // Update stats
         {
@@ -145,22 +233,10 @@ impl ProxyMonitor {
                      tracing::error!("Failed to save security log: {}", e);
                 }
             }
-
-            // Record token stats if available
-            if let (Some(account), Some(input), Some(output)) = (
-                &log_to_save.account_email,
-                log_to_save.input_tokens,
-                log_to_save.output_tokens,
-            ) {
-                let model = log_to_save.model.clone().unwrap_or_else(|| "unknown".to_string());
-                if let Err(e) = crate::modules::token_stats::record_usage(account, &model, input, output) {
-                    tracing::debug!("Failed to record token stats: {}", e);
-                }
-            }
         });
 
         // Emit event (send summary only, without body to reduce memory)
-        if let Some(app) = &self.app_handle {
+        if self.app_handle.is_some() || crate::modules::event_bus::has_subscribers() {
             let log_summary = ProxyRequestLog {
                 id: log.id.clone(),
                 timestamp: log.timestamp,
@@ -177,10 +253,25 @@ impl ProxyMonitor {
                 response_body: None, // Don't send body in event
                 input_tokens: log.input_tokens,
                 output_tokens: log.output_tokens,
+                cached_tokens: log.cached_tokens,
+                thinking_tokens: log.thinking_tokens,
+                tool_tokens: log.tool_tokens,
                 protocol: log.protocol.clone(),
                 username: log.username.clone(),
+                context_compression: log.context_compression.clone(),
+                ttft_ms: log.ttft_ms,
+                stream_duration_ms: log.stream_duration_ms,
+                tokens_per_sec: log.tokens_per_sec,
+                proxy_id: log.proxy_id.clone(),
             };
-            let _ = app.emit("proxy://request", &log_summary);
+            crate::modules::event_bus::publish(
+                crate::modules::event_bus::EventTopic::Request,
+                "logged",
+                &log_summary,
+            );
+            if let Some(app) = &self.app_handle {
+                let _ = app.emit("proxy://request", &log_summary);
+            }
         }
     }
 
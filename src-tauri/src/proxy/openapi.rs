@@ -0,0 +1,387 @@
+// 管理 API OpenAPI 3.1 文档生成
+// 请求/响应结构由 schemars 从 Rust 类型派生 (遵循 serde rename/default 等属性)，
+// 引用类型统一登记在 #/components/schemas 下。路由表见 server::admin_api_operations。
+
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::Schema;
+use schemars::JsonSchema;
+use serde_json::{json, Map, Value};
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+const API_TITLE: &str = "Antigravity Manager Admin API";
+
+type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+/// 成功响应的描述
+#[derive(Clone, Copy)]
+enum Success {
+    /// 未声明 (仅生成 200 描述)
+    Unspecified,
+    /// 200 JSON 响应体
+    Json(SchemaFn),
+    /// 无响应体 (状态码, 描述)
+    Empty(&'static str, &'static str),
+    /// 200 非 JSON 响应体 (可选的媒体类型)
+    Raw(&'static [&'static str]),
+}
+
+fn reference<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+fn inline<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    T::json_schema(gen)
+}
+
+/// 单个管理接口的文档描述
+pub struct Operation {
+    pub method: &'static str,
+    /// axum 风格路径 (如 /accounts/:accountId)，不含 /api 前缀
+    pub path: &'static str,
+    /// 对应的 handler 函数名，兼作 operationId
+    pub operation_id: &'static str,
+    pub summary: &'static str,
+    query: Option<SchemaFn>,
+    body: Option<SchemaFn>,
+    success: Success,
+    public: bool,
+}
+
+impl Operation {
+    fn new(
+        method: &'static str,
+        path: &'static str,
+        operation_id: &'static str,
+        summary: &'static str,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            operation_id,
+            summary,
+            query: None,
+            body: None,
+            success: Success::Unspecified,
+            public: false,
+        }
+    }
+
+    pub fn get(path: &'static str, operation_id: &'static str, summary: &'static str) -> Self {
+        Self::new("get", path, operation_id, summary)
+    }
+
+    pub fn post(path: &'static str, operation_id: &'static str, summary: &'static str) -> Self {
+        Self::new("post", path, operation_id, summary)
+    }
+
+    pub fn delete(path: &'static str, operation_id: &'static str, summary: &'static str) -> Self {
+        Self::new("delete", path, operation_id, summary)
+    }
+
+    pub fn patch(path: &'static str, operation_id: &'static str, summary: &'static str) -> Self {
+        Self::new("patch", path, operation_id, summary)
+    }
+
+    /// Query 参数 (结构体字段展开为独立的 query parameter)
+    pub fn query<T: JsonSchema>(mut self) -> Self {
+        self.query = Some(inline::<T>);
+        self
+    }
+
+    /// JSON 请求体
+    pub fn body<T: JsonSchema>(mut self) -> Self {
+        self.body = Some(reference::<T>);
+        self
+    }
+
+    /// 200 响应的 JSON 结构
+    pub fn returns<T: JsonSchema>(mut self) -> Self {
+        self.success = Success::Json(reference::<T>);
+        self
+    }
+
+    /// 成功时返回 204 No Content
+    pub fn no_content(self) -> Self {
+        self.status("204", "No Content")
+    }
+
+    /// 成功时返回 200 空响应体
+    pub fn empty(self) -> Self {
+        self.status("200", "OK")
+    }
+
+    /// 成功时返回指定状态码的空响应体
+    pub fn status(mut self, status: &'static str, description: &'static str) -> Self {
+        self.success = Success::Empty(status, description);
+        self
+    }
+
+    /// 200 响应为非 JSON 内容 (文件下载、SSE 等)，列出可能的媒体类型
+    pub fn produces(mut self, media_types: &'static [&'static str]) -> Self {
+        self.success = Success::Raw(media_types);
+        self
+    }
+
+    /// 免鉴权接口
+    pub fn public(mut self) -> Self {
+        self.public = true;
+        self
+    }
+}
+
+/// 将 axum 路径 (/a/:id) 转为 OpenAPI 路径 (/a/{id})，并返回路径参数名
+pub fn to_openapi_path(path: &str) -> (String, Vec<String>) {
+    let mut params = Vec::new();
+    let converted = path
+        .split('/')
+        .map(|segment| match segment.strip_prefix(':') {
+            Some(name) => {
+                params.push(name.to_string());
+                format!("{{{}}}", name)
+            }
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/");
+    (converted, params)
+}
+
+fn tag_of(path: &str) -> &str {
+    path.trim_start_matches('/').split('/').next().unwrap_or("")
+}
+
+fn to_value(schema: Schema) -> Value {
+    serde_json::to_value(schema).unwrap_or(Value::Bool(true))
+}
+
+/// 将内联对象 schema 展开为 query parameters
+fn query_parameters(schema: &Value) -> Vec<Value> {
+    let required: Vec<&str> = schema
+        .get("required")
+        .and_then(|r| r.as_array())
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+    schema
+        .get("properties")
+        .and_then(|p| p.as_object())
+        .map(|props| {
+            props
+                .iter()
+                .map(|(name, prop)| {
+                    json!({
+                        "name": name,
+                        "in": "query",
+                        "required": required.contains(&name.as_str()),
+                        "schema": prop,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 生成完整的 OpenAPI 3.1 文档
+pub fn build_document<E: JsonSchema>(base_path: &str, operations: &[Operation]) -> Value {
+    // draft 2019-09 输出 (type: [T, "null"]) 与 OpenAPI 3.1 的 JSON Schema 方言兼容
+    let mut gen = SchemaSettings::draft2019_09()
+        .with(|s| {
+            s.definitions_path = "#/components/schemas/".to_string();
+            s.meta_schema = None;
+        })
+        .into_generator();
+    let error_schema = to_value(gen.subschema_for::<E>());
+
+    let mut paths = Map::new();
+    for op in operations {
+        let (path, path_params) = to_openapi_path(op.path);
+
+        let mut parameters: Vec<Value> = path_params
+            .iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        if let Some(query) = op.query {
+            parameters.extend(query_parameters(&to_value(query(&mut gen))));
+        }
+
+        let (status, success) = match op.success {
+            Success::Json(response) => (
+                "200",
+                json!({
+                    "description": "OK",
+                    "content": { "application/json": { "schema": to_value(response(&mut gen)) } },
+                }),
+            ),
+            Success::Empty(status, description) => (status, json!({ "description": description })),
+            Success::Raw(media_types) => {
+                let content: Map<String, Value> = media_types
+                    .iter()
+                    .map(|t| (t.to_string(), json!({ "schema": { "type": "string" } })))
+                    .collect();
+                ("200", json!({ "description": "OK", "content": content }))
+            }
+            Success::Unspecified => ("200", json!({ "description": "OK" })),
+        };
+
+        let mut operation = Map::new();
+        operation.insert("operationId".into(), json!(op.operation_id));
+        operation.insert("summary".into(), json!(op.summary));
+        operation.insert("tags".into(), json!([tag_of(op.path)]));
+        if !parameters.is_empty() {
+            operation.insert("parameters".into(), Value::Array(parameters));
+        }
+        if let Some(body) = op.body {
+            operation.insert(
+                "requestBody".into(),
+                json!({
+                    "required": true,
+                    "content": { "application/json": { "schema": to_value(body(&mut gen)) } },
+                }),
+            );
+        }
+        operation.insert(
+            "responses".into(),
+            json!({
+                status: success,
+                "default": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": error_schema.clone() } },
+                },
+            }),
+        );
+        if op.public {
+            operation.insert("security".into(), json!([]));
+        }
+
+        if let Value::Object(item) = paths
+            .entry(path)
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            item.insert(op.method.to_string(), Value::Object(operation));
+        }
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": API_TITLE,
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "servers": [{ "url": base_path }],
+        "paths": paths,
+        "components": {
+            "schemas": gen.take_definitions(),
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer" },
+                "apiKeyHeader": { "type": "apiKey", "in": "header", "name": "x-api-key" },
+            },
+        },
+        // 管理密码 (admin_password) 或 API Key，二选一
+        "security": [{ "bearerAuth": [] }, { "apiKeyHeader": [] }],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct PageQuery {
+        page: usize,
+        search: Option<String>,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct ItemBody {
+        name: String,
+    }
+
+    #[derive(JsonSchema)]
+    #[allow(dead_code)]
+    struct Failure {
+        error: String,
+    }
+
+    #[test]
+    fn test_to_openapi_path() {
+        let (path, params) = to_openapi_path("/accounts/:accountId/device-versions/:versionId");
+        assert_eq!(path, "/accounts/{accountId}/device-versions/{versionId}");
+        assert_eq!(params, vec!["accountId", "versionId"]);
+    }
+
+    #[test]
+    fn test_build_document_operations() {
+        let ops = vec![
+            Operation::get("/items/:id", "get_item", "Get item")
+                .query::<PageQuery>()
+                .returns::<ItemBody>(),
+            Operation::post("/items", "create_item", "Create item").body::<ItemBody>(),
+            Operation::delete("/items/:id", "delete_item", "Delete item").no_content(),
+            Operation::get("/health", "health", "Health").public(),
+        ];
+        let doc = build_document::<Failure>("/api", &ops);
+
+        assert_eq!(doc["openapi"], "3.1.0");
+        let get = &doc["paths"]["/items/{id}"]["get"];
+        assert_eq!(get["tags"], json!(["items"]));
+        let params = get["parameters"].as_array().unwrap();
+        assert_eq!(params[0]["in"], "path");
+        let page = params.iter().find(|p| p["name"] == "page").unwrap();
+        assert_eq!(page["required"], true);
+        let search = params.iter().find(|p| p["name"] == "search").unwrap();
+        assert_eq!(search["required"], false);
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ItemBody"
+        );
+        assert_eq!(
+            get["responses"]["default"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/Failure"
+        );
+
+        assert!(doc["paths"]["/items/{id}"]["delete"]["responses"]["204"].is_object());
+        assert_eq!(doc["paths"]["/health"]["get"]["security"], json!([]));
+        assert!(doc["components"]["schemas"]["ItemBody"].is_object());
+    }
+
+    #[test]
+    fn test_success_variants() {
+        let ops = vec![
+            Operation::post("/items/:id/touch", "touch_item", "Touch item").empty(),
+            Operation::post("/items", "create_item", "Create item")
+                .body::<ItemBody>()
+                .status("201", "Created"),
+            Operation::get("/items/export", "export_items", "Export items")
+                .produces(&["text/csv", "application/x-ndjson"]),
+        ];
+        let doc = build_document::<Failure>("/api", &ops);
+
+        let touch = &doc["paths"]["/items/{id}/touch"]["post"]["responses"]["200"];
+        assert!(touch.get("content").is_none());
+        assert_eq!(
+            doc["paths"]["/items"]["post"]["responses"]["201"]["description"],
+            "Created"
+        );
+        let export = &doc["paths"]["/items/export"]["get"]["responses"]["200"]["content"];
+        assert_eq!(export["text/csv"]["schema"]["type"], "string");
+        assert!(export["application/x-ndjson"].is_object());
+    }
+
+    #[test]
+    fn test_admin_api_operations_declare_success() {
+        let undeclared: Vec<String> = crate::proxy::server::admin_api_operations()
+            .iter()
+            .filter(|op| matches!(op.success, Success::Unspecified))
+            .map(|op| format!("{} {}", op.method, op.path))
+            .collect();
+        assert!(undeclared.is_empty(), "缺少成功响应声明: {:?}", undeclared);
+    }
+}
@@ -0,0 +1,218 @@
+// Claude prompt caching 语义
+// Gemini 不接受 cache_control，上游只有隐式前缀缓存 (同一账号 + 相同前缀才会命中)。
+// 这里把客户端的 cache_control 断点当作提示：按 Anthropic 的前缀顺序 (tools → system → messages)
+// 计算每个断点处的稳定前缀哈希，记录最近服务该前缀的账号，后续请求优先路由回该账号；
+// usage 中的 cache_read_input_tokens 取自 Gemini 的 cachedContentTokenCount；
+// 隐式缓存没有写入计数，cache_creation_input_tokens 恒为 0。
+
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// 默认缓存有效期 (与 Anthropic 一致: 5 分钟)
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+/// cache_control.ttl = "1h" 时的有效期
+pub const EXTENDED_TTL: Duration = Duration::from_secs(3600);
+/// 前缀登记表上限 (超过后先清理过期条目，仍超出则淘汰最早过期的条目)
+const MAX_ENTRIES: usize = 10_000;
+
+static REGISTRY: OnceLock<Mutex<HashMap<String, PrefixEntry>>> = OnceLock::new();
+
+#[derive(Debug, Clone)]
+struct PrefixEntry {
+    account_id: String,
+    expires_at: Instant,
+}
+
+/// 单个 cache_control 断点
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheBreakpoint {
+    /// 模型 + 断点之前 (含断点所在块) 全部内容的哈希
+    pub hash: String,
+    pub ttl: Duration,
+}
+
+/// 请求中的缓存断点 (按前缀从短到长排列)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptCachePlan {
+    pub breakpoints: Vec<CacheBreakpoint>,
+}
+
+impl PromptCachePlan {
+    /// 从原始请求体提取断点 (必须在 cache_control 被清理之前调用)，无断点时返回 None
+    pub fn from_request(body: &Value) -> Option<Self> {
+        let mut hasher = Sha256::new();
+        hasher.update(
+            body.get("model")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default(),
+        );
+
+        let mut blocks: Vec<Value> = Vec::new();
+        if let Some(tools) = body.get("tools").and_then(|v| v.as_array()) {
+            blocks.extend(tools.iter().cloned());
+        }
+        match body.get("system") {
+            Some(Value::String(text)) => blocks.push(Value::String(text.clone())),
+            Some(Value::Array(system)) => blocks.extend(system.iter().cloned()),
+            _ => {}
+        }
+        for message in body
+            .get("messages")
+            .and_then(|v| v.as_array())
+            .into_iter()
+            .flatten()
+        {
+            let role = message
+                .get("role")
+                .and_then(|v| v.as_str())
+                .unwrap_or_default();
+            match message.get("content") {
+                Some(Value::Array(content)) => blocks.extend(
+                    content
+                        .iter()
+                        .map(|block| serde_json::json!({ "role": role, "block": block })),
+                ),
+                Some(content) => blocks.push(serde_json::json!({ "role": role, "block": content })),
+                None => {}
+            }
+        }
+
+        let mut breakpoints = Vec::new();
+        for mut block in blocks {
+            let cache_control = take_cache_control(&mut block);
+            let serialized = serde_json::to_string(&block).unwrap_or_default();
+            hasher.update(serialized.as_bytes());
+
+            if let Some(cache_control) = cache_control {
+                let ttl = match cache_control.get("ttl").and_then(|v| v.as_str()) {
+                    Some("1h") => EXTENDED_TTL,
+                    _ => DEFAULT_TTL,
+                };
+                breakpoints.push(CacheBreakpoint {
+                    hash: format!("{:x}", hasher.clone().finalize()),
+                    ttl,
+                });
+            }
+        }
+
+        if breakpoints.is_empty() {
+            None
+        } else {
+            Some(Self { breakpoints })
+        }
+    }
+}
+
+/// 移除块 (及 {role, block} 包装) 上的 cache_control，使标记本身不影响哈希
+fn take_cache_control(block: &mut Value) -> Option<Value> {
+    let target = if block.get("block").is_some_and(|inner| inner.is_object()) {
+        &mut block["block"]
+    } else {
+        block
+    };
+    target.as_object_mut()?.remove("cache_control")
+}
+
+fn registry() -> &'static Mutex<HashMap<String, PrefixEntry>> {
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// 最近服务过最长有效前缀的账号
+pub fn preferred_account(plan: &PromptCachePlan) -> Option<String> {
+    let now = Instant::now();
+    let entries = registry().lock().unwrap_or_else(|e| e.into_inner());
+    plan.breakpoints.iter().rev().find_map(|bp| {
+        entries
+            .get(&bp.hash)
+            .filter(|entry| entry.expires_at > now)
+            .map(|entry| entry.account_id.clone())
+    })
+}
+
+/// 请求成功后登记 (或刷新) 所有断点前缀对应的账号
+pub fn record(plan: &PromptCachePlan, account_id: &str) {
+    let now = Instant::now();
+    let mut entries = registry().lock().unwrap_or_else(|e| e.into_inner());
+    for bp in &plan.breakpoints {
+        entries.insert(
+            bp.hash.clone(),
+            PrefixEntry {
+                account_id: account_id.to_string(),
+                expires_at: now + bp.ttl,
+            },
+        );
+    }
+
+    if entries.len() > MAX_ENTRIES {
+        entries.retain(|_, entry| entry.expires_at > now);
+        if entries.len() > MAX_ENTRIES {
+            let mut by_expiry: Vec<(String, Instant)> = entries
+                .iter()
+                .map(|(hash, entry)| (hash.clone(), entry.expires_at))
+                .collect();
+            by_expiry.sort_by_key(|(_, expires_at)| *expires_at);
+            let excess = entries.len() - MAX_ENTRIES;
+            for (hash, _) in by_expiry.into_iter().take(excess) {
+                entries.remove(&hash);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(system_text: &str, question: &str) -> Value {
+        json!({
+            "model": "claude-sonnet-4-5",
+            "system": [
+                { "type": "text", "text": system_text, "cache_control": { "type": "ephemeral" } }
+            ],
+            "messages": [
+                { "role": "user", "content": [
+                    { "type": "text", "text": question, "cache_control": { "type": "ephemeral", "ttl": "1h" } }
+                ]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_breakpoints_are_stable_prefix_hashes() {
+        let plan = PromptCachePlan::from_request(&request("You are helpful.", "Hi")).unwrap();
+        assert_eq!(plan.breakpoints.len(), 2);
+        assert_eq!(plan.breakpoints[0].ttl, DEFAULT_TTL);
+        assert_eq!(plan.breakpoints[1].ttl, EXTENDED_TTL);
+
+        // 相同 system 前缀、不同问题: 第一个断点哈希不变，第二个变化
+        let other = PromptCachePlan::from_request(&request("You are helpful.", "Bye")).unwrap();
+        assert_eq!(plan.breakpoints[0].hash, other.breakpoints[0].hash);
+        assert_ne!(plan.breakpoints[1].hash, other.breakpoints[1].hash);
+
+        // 去掉 cache_control 后无断点
+        let plain = json!({ "model": "m", "messages": [{ "role": "user", "content": "Hi" }] });
+        assert!(PromptCachePlan::from_request(&plain).is_none());
+    }
+
+    #[test]
+    fn test_preferred_account_uses_longest_live_prefix() {
+        let first =
+            PromptCachePlan::from_request(&request("Shared system prompt A", "Q1")).unwrap();
+        record(&first, "acc-1");
+
+        let second =
+            PromptCachePlan::from_request(&request("Shared system prompt A", "Q2")).unwrap();
+        assert_eq!(preferred_account(&second).as_deref(), Some("acc-1"));
+
+        record(&second, "acc-2");
+        assert_eq!(preferred_account(&second).as_deref(), Some("acc-2"));
+
+        let unrelated =
+            PromptCachePlan::from_request(&request("Other system prompt", "Q1")).unwrap();
+        assert_eq!(preferred_account(&unrelated), None);
+    }
+}
@@ -24,6 +24,28 @@ pub fn init_global_proxy_pool(config: Arc<RwLock<ProxyPoolConfig>>) -> Arc<Proxy
     manager
 }
 
+tokio::task_local! {
+    /// 当前请求实际使用的代理池节点 ID (由 monitor 中间件建立作用域，用于按代理统计延迟)
+    static REQUEST_PROXY: Arc<std::sync::Mutex<Option<String>>>;
+}
+
+/// 在作用域内执行请求处理，返回处理结果及最后一次上游调用所用的代理 ID
+pub async fn with_request_proxy_scope<F: std::future::Future>(fut: F) -> (F::Output, Option<String>) {
+    let slot = Arc::new(std::sync::Mutex::new(None));
+    let output = REQUEST_PROXY.scope(slot.clone(), fut).await;
+    let proxy_id = slot.lock().ok().and_then(|mut guard| guard.take());
+    (output, proxy_id)
+}
+
+/// 记录本次上游调用所用的代理 (None 表示直连或全局上游代理)；不在作用域内时忽略
+pub fn note_request_proxy(proxy_id: Option<&str>) {
+    let _ = REQUEST_PROXY.try_with(|slot| {
+        if let Ok(mut guard) = slot.lock() {
+            *guard = proxy_id.map(str::to_string);
+        }
+    });
+}
+
 /// 代理配置 (用于构建 reqwest Client)
 /// 注意：重命名为 PoolProxyConfig 以避免与 config::ProxyConfig 冲突
 #[derive(Debug, Clone)]
@@ -390,6 +412,18 @@ impl ProxyPoolManager {
         let mut config = self.config.write().await;
         for (id, is_healthy, latency) in results {
             if let Some(proxy) = config.proxies.iter_mut().find(|p| p.id == id) {
+                // 仅在健康状态翻转时推送事件 (/api/events)
+                if proxy.is_healthy != is_healthy {
+                    crate::modules::event_bus::publish(
+                        crate::modules::event_bus::EventTopic::ProxyPool,
+                        if is_healthy { "healthy" } else { "unhealthy" },
+                        &serde_json::json!({
+                            "proxy_id": proxy.id,
+                            "name": proxy.name,
+                            "latency": latency,
+                        }),
+                    );
+                }
                 proxy.is_healthy = is_healthy;
                 proxy.latency = latency;
                 proxy.last_check_time = Some(chrono::Utc::now().timestamp());
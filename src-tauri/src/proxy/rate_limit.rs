@@ -1,9 +1,11 @@
 use dashmap::DashMap;
 use std::time::{SystemTime, Duration};
 use regex::Regex;
+use serde::Serialize;
 
 /// 限流原因类型
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
 pub enum RateLimitReason {
     /// 配额耗尽 (QUOTA_EXHAUSTED)
     QuotaExhausted,
@@ -129,7 +131,7 @@ impl RateLimitTracker {
         };
         
         let key = self.get_limit_key(account_id, model.as_deref());
-        self.limits.insert(key, info);
+        self.record_lockout(account_id, key, info);
         
         if let Some(m) = &model {
             tracing::info!(
@@ -311,7 +313,7 @@ impl RateLimitTracker {
             account_id.to_string()
         };
 
-        self.limits.insert(key, info.clone());
+        self.record_lockout(account_id, key, info.clone());
         
         tracing::warn!(
             "账号 {} [{}] 限流类型: {:?}, 重置延时: {}秒",
@@ -324,6 +326,21 @@ impl RateLimitTracker {
         Some(info)
     }
     
+    /// 写入限流记录，并推送账号状态事件 (/api/events)
+    fn record_lockout(&self, account_id: &str, key: String, info: RateLimitInfo) {
+        crate::modules::event_bus::publish(
+            crate::modules::event_bus::EventTopic::Account,
+            "rate_limited",
+            &serde_json::json!({
+                "account_id": account_id,
+                "reason": info.reason,
+                "model": info.model,
+                "retry_after_sec": info.retry_after_sec,
+            }),
+        );
+        self.limits.insert(key, info);
+    }
+
     /// 解析限流原因类型
     fn parse_rate_limit_reason(&self, body: &str) -> RateLimitReason {
         // 尝试从 JSON 中提取 reason 字段
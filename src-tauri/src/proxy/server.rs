@@ -1,5 +1,7 @@
 use crate::models::AppConfig;
-use crate::modules::{account, config, logger, migration, proxy_db, security_db, token_stats};
+use crate::modules::{
+    account, config, logger, migration, pricing, proxy_db, security_db, token_stats,
+};
 use crate::proxy::TokenManager;
 use axum::{
     extract::{DefaultBodyLimit, Path, Query, State},
@@ -9,6 +11,7 @@ use axum::{
     Router,
 };
 use futures::TryFutureExt;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::sync::atomic::AtomicUsize;
@@ -125,12 +128,12 @@ impl axum::extract::FromRef<AppState> for Arc<RwLock<crate::proxy::ProxySecurity
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct ErrorResponse {
     error: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct AccountResponse {
     id: String,
     email: String,
@@ -152,7 +155,7 @@ struct AccountResponse {
     last_used: i64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct QuotaResponse {
     models: Vec<ModelQuota>,
     last_updated: i64,
@@ -160,14 +163,15 @@ struct QuotaResponse {
     is_forbidden: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
+#[schemars(rename = "AccountModelQuota")]
 struct ModelQuota {
     name: String,
     percentage: i32,
     reset_time: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct AccountListResponse {
     accounts: Vec<AccountResponse>,
     current_account_id: Option<String>,
@@ -228,6 +232,7 @@ pub struct AxumServer {
     pub token_manager: Arc<TokenManager>, // [NEW] 暴露出 TokenManager 供反代服务复用
     pub proxy_pool_state: Arc<tokio::sync::RwLock<crate::proxy::config::ProxyPoolConfig>>, // [NEW] 代理池配置状态
     pub proxy_pool_manager: Arc<crate::proxy::proxy_pool::ProxyPoolManager>, // [NEW] 暴露代理池管理器供命令调用
+    pub local_addr: std::net::SocketAddr, // 实际监听地址 (port 为 0 时由系统分配)
 }
 
 impl AxumServer {
@@ -284,6 +289,11 @@ impl AxumServer {
         tracing::info!("User-Agent 配置已热更新: {:?}", config.user_agent_override);
     }
 
+    /// 覆盖 v1internal 上游端点 (None 恢复内置 fallback 链)，供一致性测试指向本地 mock
+    pub async fn update_upstream_base_urls(&self, urls: Option<Vec<String>>) {
+        self.upstream.set_base_url_override(urls).await;
+    }
+
     pub async fn set_running(&self, running: bool) {
         let mut r = self.is_running.write().await;
         *r = running;
@@ -362,6 +372,9 @@ impl AxumServer {
             proxy_pool_manager: proxy_pool_manager.clone(),
         };
 
+        // 恢复上次退出时未完成的 Message Batches
+        crate::proxy::message_batches::resume_pending_batches(state.clone());
+
         // 构建路由 - 使用新架构的 handlers！
         use crate::proxy::handlers;
         use crate::proxy::middleware::{
@@ -396,12 +409,48 @@ impl AxumServer {
                 "/v1/audio/transcriptions",
                 post(handlers::audio::handle_audio_transcription),
             ) // 音频转录 API
+            .route(
+                "/v1/audio/translations",
+                post(handlers::audio::handle_audio_translation),
+            ) // 音频翻译 API
+            // Files API (OpenAI / Anthropic 兼容)
+            .route(
+                "/v1/files",
+                get(handlers::files::handle_list_files).post(handlers::files::handle_upload_file),
+            )
+            .route(
+                "/v1/files/:file_id",
+                get(handlers::files::handle_get_file).delete(handlers::files::handle_delete_file),
+            )
+            .route(
+                "/v1/files/:file_id/content",
+                get(handlers::files::handle_get_file_content),
+            )
             // Claude Protocol
             .route("/v1/messages", post(handlers::claude::handle_messages))
             .route(
                 "/v1/messages/count_tokens",
                 post(handlers::claude::handle_count_tokens),
             )
+            // Message Batches API (本地队列模拟)
+            .route(
+                "/v1/messages/batches",
+                get(handlers::message_batches::handle_list_batches)
+                    .post(handlers::message_batches::handle_create_batch),
+            )
+            .route(
+                "/v1/messages/batches/:batch_id",
+                get(handlers::message_batches::handle_get_batch)
+                    .delete(handlers::message_batches::handle_delete_batch),
+            )
+            .route(
+                "/v1/messages/batches/:batch_id/cancel",
+                post(handlers::message_batches::handle_cancel_batch),
+            )
+            .route(
+                "/v1/messages/batches/:batch_id/results",
+                get(handlers::message_batches::handle_get_batch_results),
+            )
             .route(
                 "/v1/models/claude",
                 get(handlers::claude::handle_list_models),
@@ -427,6 +476,18 @@ impl AxumServer {
                 "/v1beta/models/:model/countTokens",
                 post(handlers::gemini::handle_count_tokens),
             ) // Specific route priority
+            // Gemini cachedContents (本地模拟层)
+            .route(
+                "/v1beta/cachedContents",
+                get(handlers::gemini::handle_list_cached_contents)
+                    .post(handlers::gemini::handle_create_cached_content),
+            )
+            .route(
+                "/v1beta/cachedContents/:id",
+                get(handlers::gemini::handle_get_cached_content)
+                    .patch(handlers::gemini::handle_update_cached_content)
+                    .delete(handlers::gemini::handle_delete_cached_content),
+            )
             .route(
                 "/v1/models/detect",
                 post(handlers::common::handle_detect_model),
@@ -455,6 +516,7 @@ impl AxumServer {
         // 2. 构建管理 API (强制鉴权)
         let admin_routes = Router::new()
             .route("/health", get(health_check_handler))
+            .route("/openapi.json", get(admin_get_openapi_spec))
             .route(
                 "/accounts",
                 get(admin_list_accounts).post(admin_add_account),
@@ -556,6 +618,12 @@ impl AxumServer {
             .route("/logs", get(admin_get_proxy_logs_filtered))
             .route("/logs/count", get(admin_get_proxy_logs_count_filtered))
             .route("/logs/clear", post(admin_clear_proxy_logs))
+            .route("/logs/search", post(admin_search_proxy_logs))
+            .route("/logs/aggregate", post(admin_aggregate_proxy_logs))
+            .route("/logs/latency", post(admin_get_latency_report))
+            .route("/logs/export", post(admin_export_proxy_logs))
+            .route("/logs/retention", get(admin_get_log_retention_report))
+            .route("/logs/retention/run", post(admin_run_log_retention))
             .route("/logs/:logId", get(admin_get_proxy_log_detail))
             // Debug Console (Log Bridge)
             .route("/debug/enable", post(admin_enable_debug_console))
@@ -563,6 +631,7 @@ impl AxumServer {
             .route("/debug/enabled", get(admin_is_debug_console_enabled))
             .route("/debug/logs", get(admin_get_debug_console_logs))
             .route("/debug/logs/clear", post(admin_clear_debug_console_logs))
+            .route("/events", get(admin_stream_events))
             .route("/stats/token/clear", post(admin_clear_token_stats))
             .route("/stats/token/hourly", get(admin_get_token_stats_hourly))
             .route("/stats/token/daily", get(admin_get_token_stats_daily))
@@ -589,6 +658,13 @@ impl AxumServer {
                 "/stats/token/account-trend/daily",
                 get(admin_get_token_stats_account_trend_daily),
             )
+            .route("/stats/cost", get(admin_get_cost_report))
+            .route("/stats/cost/recompute", post(admin_recompute_costs))
+            .route(
+                "/stats/prices",
+                get(admin_get_model_prices).post(admin_save_model_price),
+            )
+            .route("/stats/prices/:priceId", delete(admin_delete_model_price))
             .route("/accounts/bulk-delete", post(admin_delete_accounts))
             .route("/accounts/export", post(admin_export_accounts))
             .route("/accounts/reorder", post(admin_reorder_accounts))
@@ -646,6 +722,12 @@ impl AxumServer {
             .route("/user-tokens/summary", get(admin_get_user_token_summary))
             .route("/user-tokens/:id/renew", post(admin_renew_user_token))
             .route("/user-tokens/:id", delete(admin_delete_user_token).patch(admin_update_user_token))
+            // Account Vault
+            .route("/vault/status", get(admin_get_vault_status))
+            .route("/vault/enable", post(admin_enable_vault))
+            .route("/vault/unlock", post(admin_unlock_vault))
+            .route("/vault/rekey", post(admin_rekey_vault))
+            .route("/vault/disable", post(admin_disable_vault))
             // OAuth (Web) - Admin 接口
             .route("/auth/url", get(admin_prepare_oauth_url_web))
             // 应用管理特定鉴权层 (强制校验)
@@ -692,6 +774,9 @@ impl AxumServer {
         let listener = tokio::net::TcpListener::bind(&addr)
             .await
             .map_err(|e| format!("地址 {} 绑定失败: {}", addr, e))?;
+        let local_addr = listener
+            .local_addr()
+            .map_err(|e| format!("获取监听地址失败: {}", e))?;
 
         tracing::info!("反代服务器启动在 http://{}", addr);
 
@@ -712,6 +797,7 @@ impl AxumServer {
             token_manager: token_manager.clone(),
             proxy_pool_state,
             proxy_pool_manager,
+            local_addr,
         };
 
         // 在新任务中启动服务器
@@ -779,14 +865,436 @@ impl AxumServer {
 // ===== API 处理器 (旧代码已移除，由 src/proxy/handlers/* 接管) =====
 
 /// 健康检查处理器
+#[derive(Serialize, JsonSchema)]
+struct HealthResponse {
+    status: &'static str,
+    version: &'static str,
+}
+
 async fn health_check_handler() -> Response {
-    Json(serde_json::json!({
-        "status": "ok",
-        "version": env!("CARGO_PKG_VERSION")
-    }))
+    Json(HealthResponse {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+    })
     .into_response()
 }
 
+/// 管理 API 路由文档表 (与 admin_routes 一一对应，由 openapi_conformance 测试保证同步)
+pub(crate) fn admin_api_operations() -> Vec<crate::proxy::openapi::Operation> {
+    use crate::auth::openai_web::UserInfo;
+    use crate::models::account::DeviceProfile;
+    use crate::modules::{
+        account::{DeviceProfiles, RefreshStats}, cache::ClearResult, cloudflared::CloudflaredStatus,
+        http_api::HttpApiSettings, log_bridge::LogEntry, proxy_db::IpTokenStats,
+        security_db::{IpBlacklistEntry, IpWhitelistEntry},
+        token_stats::{
+            AccountTokenStats, AccountTrendPoint, ModelTokenStats, ModelTrendPoint,
+            TokenStatsAggregated, TokenStatsSummary,
+        },
+        update_checker::{UpdateInfo, UpdateSettings},
+        user_token_db::UserToken,
+        vault::VaultStatus,
+    };
+    use crate::proxy::cli_sync::CliStatus;
+    use crate::proxy::config::{ProxyPoolConfig, SecurityMonitorConfig};
+    use crate::proxy::monitor::{ProxyRequestLog, ProxyStats};
+    use crate::proxy::openapi::Operation as Op;
+    use crate::commands::user_token::{CreateTokenRequest, UpdateTokenRequest, UserTokenStats};
+    use serde_json::Value;
+    use std::collections::HashMap;
+
+    vec![
+        Op::get("/health", "health_check_handler", "Health check")
+            .public()
+            .returns::<HealthResponse>(),
+        Op::get("/openapi.json", "admin_get_openapi_spec", "OpenAPI document of the admin API")
+            .returns::<Value>(),
+        // 账号
+        Op::get("/accounts", "admin_list_accounts", "List accounts").returns::<AccountListResponse>(),
+        Op::post("/accounts", "admin_add_account", "Add account by refresh token")
+            .body::<AddAccountRequest>()
+            .returns::<AccountResponse>(),
+        Op::get("/accounts/current", "admin_get_current_account", "Get current account")
+            .returns::<Option<AccountResponse>>(),
+        Op::post("/accounts/switch", "admin_switch_account", "Switch current account")
+            .body::<SwitchRequest>()
+            .empty(),
+        Op::post("/accounts/refresh", "admin_refresh_all_quotas", "Refresh quotas of all accounts")
+            .returns::<RefreshStats>(),
+        Op::delete("/accounts/:accountId", "admin_delete_account", "Delete account").no_content(),
+        Op::post("/accounts/:accountId/bind-device", "admin_bind_device", "Bind device fingerprint")
+            .body::<BindDeviceRequest>()
+            .returns::<BindDeviceResponse>(),
+        Op::get("/accounts/:accountId/device-profiles", "admin_get_device_profiles", "Get device profiles")
+            .returns::<DeviceProfiles>(),
+        Op::get("/accounts/:accountId/device-versions", "admin_list_device_versions", "List device profile versions")
+            .returns::<DeviceProfiles>(),
+        Op::post("/accounts/device-preview", "admin_preview_generate_profile", "Preview a generated device profile")
+            .returns::<DeviceProfile>(),
+        Op::post(
+            "/accounts/:accountId/bind-device-profile",
+            "admin_bind_device_profile_with_profile",
+            "Bind a given device profile",
+        )
+        .body::<BindDeviceProfileWrapper>()
+        .returns::<DeviceProfile>(),
+        Op::post("/accounts/restore-original", "admin_restore_original_device", "Restore original device profile")
+            .returns::<String>(),
+        Op::post(
+            "/accounts/:accountId/device-versions/:versionId/restore",
+            "admin_restore_device_version",
+            "Restore a device profile version",
+        )
+        .returns::<DeviceProfile>(),
+        Op::delete(
+            "/accounts/:accountId/device-versions/:versionId",
+            "admin_delete_device_version",
+            "Delete a device profile version",
+        )
+        .no_content(),
+        Op::post("/accounts/import/v1", "admin_import_v1_accounts", "Import accounts from v1 data")
+            .returns::<Vec<AccountResponse>>(),
+        Op::post("/accounts/import/db", "admin_import_from_db", "Import account from IDE database")
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/import/db-custom", "admin_import_custom_db", "Import account from custom database path")
+            .body::<CustomDbRequest>()
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/sync/db", "admin_sync_account_from_db", "Sync account from IDE database")
+            .returns::<Option<AccountResponse>>(),
+        Op::post("/accounts/bulk-delete", "admin_delete_accounts", "Delete accounts in bulk")
+            .body::<BulkDeleteRequest>()
+            .empty(),
+        Op::post("/accounts/export", "admin_export_accounts", "Export accounts with refresh tokens")
+            .body::<ExportAccountsRequest>()
+            .returns::<crate::models::AccountExportResponse>(),
+        Op::post("/accounts/reorder", "admin_reorder_accounts", "Reorder accounts")
+            .body::<ReorderRequest>()
+            .empty(),
+        Op::get("/accounts/:accountId/quota", "admin_fetch_account_quota", "Fetch account quota")
+            .returns::<crate::models::QuotaData>(),
+        Op::post("/accounts/:accountId/toggle-proxy", "admin_toggle_proxy_status", "Enable or disable account for proxy")
+            .body::<ToggleProxyRequest>()
+            .empty(),
+        Op::post("/accounts/warmup", "admin_warm_up_all_accounts", "Warm up all accounts")
+            .returns::<String>(),
+        Op::post("/accounts/:accountId/warmup", "admin_warm_up_account", "Warm up account")
+            .returns::<String>(),
+        Op::post("/accounts/openai/web", "admin_add_openai_web_account", "Add OpenAI web session account")
+            .body::<AddOpenAIWebAccountRequest>()
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/openai/api", "admin_add_openai_api_account", "Add OpenAI API key account")
+            .body::<AddOpenAIAPIAccountRequest>()
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/openai/validate", "admin_validate_openai_session", "Validate OpenAI web session")
+            .body::<ValidateOpenAISessionRequest>()
+            .returns::<UserInfo>(),
+        // OAuth
+        Op::post("/accounts/oauth/prepare", "admin_prepare_oauth_url", "Prepare OAuth URL")
+            .returns::<OAuthUrlResponse>(),
+        Op::post("/accounts/oauth/start", "admin_start_oauth_login", "Start OAuth login")
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/oauth/complete", "admin_complete_oauth_login", "Complete OAuth login")
+            .returns::<AccountResponse>(),
+        Op::post("/accounts/oauth/cancel", "admin_cancel_oauth_login", "Cancel OAuth login")
+            .empty(),
+        Op::post("/accounts/oauth/submit-code", "admin_submit_oauth_code", "Submit OAuth authorization code")
+            .body::<SubmitCodeRequest>()
+            .empty(),
+        Op::get("/auth/url", "admin_prepare_oauth_url_web", "Prepare OAuth URL for web callback")
+            .returns::<WebOAuthUrlResponse>(),
+        // 统计
+        Op::get("/stats/summary", "admin_get_token_stats_summary", "Token usage summary")
+            .query::<StatsPeriodQuery>()
+            .returns::<TokenStatsSummary>(),
+        Op::get("/stats/hourly", "admin_get_token_stats_hourly", "Hourly token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/daily", "admin_get_token_stats_daily", "Daily token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/weekly", "admin_get_token_stats_weekly", "Weekly token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/accounts", "admin_get_token_stats_by_account", "Token usage by account")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<AccountTokenStats>>(),
+        Op::get("/stats/models", "admin_get_token_stats_by_model", "Token usage by model")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<ModelTokenStats>>(),
+        Op::post("/stats/token/clear", "admin_clear_token_stats", "Clear token statistics")
+            .empty(),
+        Op::get("/stats/token/hourly", "admin_get_token_stats_hourly", "Hourly token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/token/daily", "admin_get_token_stats_daily", "Daily token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/token/weekly", "admin_get_token_stats_weekly", "Weekly token usage")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<TokenStatsAggregated>>(),
+        Op::get("/stats/token/by-account", "admin_get_token_stats_by_account", "Token usage by account")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<AccountTokenStats>>(),
+        Op::get("/stats/token/summary", "admin_get_token_stats_summary", "Token usage summary")
+            .query::<StatsPeriodQuery>()
+            .returns::<TokenStatsSummary>(),
+        Op::get("/stats/token/by-model", "admin_get_token_stats_by_model", "Token usage by model")
+            .query::<StatsPeriodQuery>()
+            .returns::<Vec<ModelTokenStats>>(),
+        Op::get(
+            "/stats/token/model-trend/hourly",
+            "admin_get_token_stats_model_trend_hourly",
+            "Hourly token trend by model",
+        )
+        .returns::<Vec<ModelTrendPoint>>(),
+        Op::get(
+            "/stats/token/model-trend/daily",
+            "admin_get_token_stats_model_trend_daily",
+            "Daily token trend by model",
+        )
+        .returns::<Vec<ModelTrendPoint>>(),
+        Op::get(
+            "/stats/token/account-trend/hourly",
+            "admin_get_token_stats_account_trend_hourly",
+            "Hourly token trend by account",
+        )
+        .returns::<Vec<AccountTrendPoint>>(),
+        Op::get(
+            "/stats/token/account-trend/daily",
+            "admin_get_token_stats_account_trend_daily",
+            "Daily token trend by account",
+        )
+        .returns::<Vec<AccountTrendPoint>>(),
+        Op::get("/stats/cost", "admin_get_cost_report", "Equivalent API spend rollup")
+            .query::<CostReportQuery>()
+            .returns::<token_stats::CostReport>(),
+        Op::post("/stats/cost/recompute", "admin_recompute_costs", "Re-price recorded usage")
+            .body::<RecomputeCostsRequest>()
+            .returns::<RecomputeCostsResponse>(),
+        Op::get("/stats/prices", "admin_get_model_prices", "Get model price table")
+            .returns::<pricing::PriceTable>(),
+        Op::post("/stats/prices", "admin_save_model_price", "Create or update a model price version")
+            .body::<SaveModelPriceRequest>()
+            .returns::<pricing::ModelPrice>(),
+        Op::delete("/stats/prices/:priceId", "admin_delete_model_price", "Delete a model price version")
+            .no_content(),
+        // 配置
+        Op::get("/config", "admin_get_config", "Get application config").returns::<AppConfig>(),
+        Op::post("/config", "admin_save_config", "Save application config")
+            .body::<SaveConfigWrapper>()
+            .empty(),
+        // 反代服务
+        Op::post("/proxy/cli/status", "admin_get_cli_sync_status", "Get CLI config sync status")
+            .body::<CliSyncStatusRequest>()
+            .returns::<CliStatus>(),
+        Op::post("/proxy/cli/sync", "admin_execute_cli_sync", "Sync proxy settings into CLI config")
+            .body::<CliSyncRequest>()
+            .empty(),
+        Op::post("/proxy/cli/restore", "admin_execute_cli_restore", "Restore CLI config")
+            .body::<CliRestoreRequest>()
+            .empty(),
+        Op::post("/proxy/cli/config", "admin_get_cli_config_content", "Read CLI config content")
+            .body::<CliConfigContentRequest>()
+            .returns::<String>(),
+        Op::get("/proxy/status", "admin_get_proxy_status", "Get proxy service status")
+            .returns::<ProxyStatusResponse>(),
+        Op::get("/proxy/pool/config", "admin_get_proxy_pool_config", "Get proxy pool config")
+            .returns::<ProxyPoolConfig>(),
+        Op::get("/proxy/pool/bindings", "admin_get_all_account_bindings", "List account proxy bindings")
+            .returns::<HashMap<String, String>>(),
+        Op::post("/proxy/pool/bind", "admin_bind_account_proxy", "Bind account to proxy")
+            .body::<BindAccountProxyRequest>()
+            .empty(),
+        Op::post("/proxy/pool/unbind", "admin_unbind_account_proxy", "Unbind account from proxy")
+            .body::<UnbindAccountProxyRequest>()
+            .empty(),
+        Op::get("/proxy/pool/binding/:accountId", "admin_get_account_proxy_binding", "Get account proxy binding")
+            .returns::<Option<String>>(),
+        Op::post("/proxy/health-check/trigger", "admin_trigger_proxy_health_check", "Trigger proxy health check")
+            .returns::<ProxyHealthCheckResponse>(),
+        Op::post("/proxy/start", "admin_start_proxy_service", "Start proxy service").empty(),
+        Op::post("/proxy/stop", "admin_stop_proxy_service", "Stop proxy service").empty(),
+        Op::post("/proxy/mapping", "admin_update_model_mapping", "Update model mapping")
+            .body::<UpdateMappingWrapper>()
+            .empty(),
+        Op::post("/proxy/api-key/generate", "admin_generate_api_key", "Generate a new API key").returns::<String>(),
+        Op::post("/proxy/session-bindings/clear", "admin_clear_proxy_session_bindings", "Clear sticky session bindings")
+            .empty(),
+        Op::delete("/proxy/rate-limits", "admin_clear_all_rate_limits", "Clear all rate limits").empty(),
+        Op::delete("/proxy/rate-limits/:accountId", "admin_clear_rate_limit", "Clear account rate limit")
+            .empty(),
+        Op::get("/proxy/preferred-account", "admin_get_preferred_account", "Get preferred account")
+            .returns::<Option<String>>(),
+        Op::post("/proxy/preferred-account", "admin_set_preferred_account", "Set preferred account")
+            .body::<SetPreferredAccountRequest>()
+            .empty(),
+        Op::post("/proxy/monitor/toggle", "admin_set_proxy_monitor_enabled", "Toggle request monitor")
+            .body::<MonitorToggleRequest>()
+            .empty(),
+        Op::get("/proxy/stats", "admin_get_proxy_stats", "Get request monitor stats").returns::<ProxyStats>(),
+        Op::get("/proxy/cloudflared/status", "admin_cloudflared_get_status", "Get cloudflared tunnel status")
+            .returns::<CloudflaredStatus>(),
+        Op::post("/proxy/cloudflared/install", "admin_cloudflared_install", "Install cloudflared")
+            .returns::<CloudflaredStatus>(),
+        Op::post("/proxy/cloudflared/start", "admin_cloudflared_start", "Start cloudflared tunnel")
+            .body::<CloudflaredStartRequest>()
+            .returns::<CloudflaredStatus>(),
+        Op::post("/proxy/cloudflared/stop", "admin_cloudflared_stop", "Stop cloudflared tunnel")
+            .returns::<CloudflaredStatus>(),
+        Op::post("/zai/models/fetch", "admin_fetch_zai_models", "Fetch z.ai models")
+            .body::<FetchZaiModelsRequest>()
+            .returns::<Vec<String>>(),
+        // 请求日志
+        Op::get("/logs", "admin_get_proxy_logs_filtered", "List request logs")
+            .query::<LogsFilterQuery>()
+            .returns::<Vec<ProxyRequestLog>>(),
+        Op::get("/logs/count", "admin_get_proxy_logs_count_filtered", "Count request logs")
+            .query::<LogsRequest>()
+            .returns::<u64>(),
+        Op::post("/logs/clear", "admin_clear_proxy_logs", "Clear request logs").empty(),
+        Op::post("/logs/search", "admin_search_proxy_logs", "Full-text and structured log search")
+            .body::<LogSearchRequest>()
+            .returns::<crate::modules::log_query::LogPage>(),
+        Op::post("/logs/aggregate", "admin_aggregate_proxy_logs", "Aggregate logs by a dimension")
+            .body::<LogAggregateRequest>()
+            .returns::<Vec<crate::modules::log_query::LogAggregate>>(),
+        Op::post("/logs/latency", "admin_get_latency_report", "Latency percentiles (TTFT, duration, tokens/sec) by a dimension")
+            .body::<LogAggregateRequest>()
+            .returns::<Vec<crate::modules::log_query::LatencyStats>>(),
+        Op::post("/logs/export", "admin_export_proxy_logs", "Export matching logs as JSONL or CSV")
+            .body::<LogExportRequest>()
+            .produces(&["application/x-ndjson", "text/csv"]),
+        Op::get("/logs/retention", "admin_get_log_retention_report", "Get the last log retention run report")
+            .returns::<Option<crate::modules::log_retention::RetentionReport>>(),
+        Op::post("/logs/retention/run", "admin_run_log_retention", "Enforce the log retention policy now")
+            .body::<RunLogRetentionRequest>()
+            .returns::<crate::modules::log_retention::RetentionReport>(),
+        Op::get("/logs/:logId", "admin_get_proxy_log_detail", "Get request log detail").returns::<ProxyRequestLog>(),
+        // 调试控制台
+        Op::post("/debug/enable", "admin_enable_debug_console", "Enable debug console").empty(),
+        Op::post("/debug/disable", "admin_disable_debug_console", "Disable debug console").empty(),
+        Op::get("/debug/enabled", "admin_is_debug_console_enabled", "Is debug console enabled").returns::<bool>(),
+        Op::get("/debug/logs", "admin_get_debug_console_logs", "Get debug console logs")
+            .returns::<Vec<LogEntry>>(),
+        Op::post("/debug/logs/clear", "admin_clear_debug_console_logs", "Clear debug console logs")
+            .empty(),
+        // 实时事件流
+        Op::get("/events", "admin_stream_events", "Live admin event stream (text/event-stream)")
+            .query::<EventsQuery>()
+            .produces(&["text/event-stream"]),
+        // 系统
+        Op::post("/system/open-folder", "admin_open_folder", "Open data folder").empty(),
+        Op::get("/system/data-dir", "admin_get_data_dir_path", "Get data directory").returns::<String>(),
+        Op::get("/system/updates/settings", "admin_get_update_settings", "Get update settings")
+            .returns::<UpdateSettings>(),
+        Op::get("/system/updates/check-status", "admin_should_check_updates", "Should check for updates")
+            .returns::<bool>(),
+        Op::post("/system/updates/check", "admin_check_for_updates", "Check for updates")
+            .returns::<UpdateInfo>(),
+        Op::post("/system/updates/touch", "admin_update_last_check_time", "Update last check time")
+            .empty(),
+        Op::post("/system/updates/save", "admin_save_update_settings", "Save update settings")
+            .body::<SaveUpdateSettingsWrapper>()
+            .empty(),
+        Op::get("/system/autostart/status", "admin_is_auto_launch_enabled", "Is autostart enabled")
+            .returns::<bool>(),
+        Op::post("/system/autostart/toggle", "admin_toggle_auto_launch", "Toggle autostart")
+            .body::<ToggleAutoLaunchRequest>()
+            .status("501", "Not Implemented"),
+        Op::get("/system/http-api/settings", "admin_get_http_api_settings", "Get HTTP API settings")
+            .returns::<HttpApiSettings>(),
+        Op::post("/system/http-api/settings", "admin_save_http_api_settings", "Save HTTP API settings")
+            .body::<HttpApiSettings>()
+            .empty(),
+        Op::get("/system/antigravity/path", "admin_get_antigravity_path", "Get Antigravity executable path")
+            .returns::<String>(),
+        Op::get("/system/antigravity/args", "admin_get_antigravity_args", "Get Antigravity launch args")
+            .returns::<Vec<String>>(),
+        Op::post("/system/cache/clear", "admin_clear_antigravity_cache", "Clear Antigravity cache")
+            .returns::<ClearResult>(),
+        Op::get("/system/cache/paths", "admin_get_antigravity_cache_paths", "Get Antigravity cache paths")
+            .returns::<Vec<String>>(),
+        Op::post("/system/logs/clear-cache", "admin_clear_log_cache", "Clear log cache").empty(),
+        // 安全
+        Op::get("/security/logs", "admin_get_ip_access_logs", "List IP access logs")
+            .query::<IpAccessLogQuery>()
+            .returns::<IpAccessLogResponse>(),
+        Op::post("/security/logs/clear", "admin_clear_ip_access_logs", "Clear IP access logs").empty(),
+        Op::get("/security/stats", "admin_get_ip_stats", "Get IP statistics").returns::<IpStatsResponse>(),
+        Op::get("/security/token-stats", "admin_get_ip_token_stats", "Get token usage by IP")
+            .query::<IpTokenStatsQuery>()
+            .returns::<Vec<IpTokenStats>>(),
+        Op::get("/security/blacklist", "admin_get_ip_blacklist", "List IP blacklist")
+            .returns::<Vec<IpBlacklistEntry>>(),
+        Op::post("/security/blacklist", "admin_add_ip_to_blacklist", "Add IP to blacklist")
+            .body::<AddBlacklistWrapper>()
+            .status("201", "Created"),
+        Op::delete("/security/blacklist", "admin_remove_ip_from_blacklist", "Remove IP from blacklist")
+            .query::<RemoveIpRequest>()
+            .empty(),
+        Op::post("/security/blacklist/clear", "admin_clear_ip_blacklist", "Clear IP blacklist").empty(),
+        Op::get("/security/blacklist/check", "admin_check_ip_in_blacklist", "Check IP against blacklist")
+            .query::<CheckIpQuery>()
+            .returns::<IpCheckResponse>(),
+        Op::get("/security/whitelist", "admin_get_ip_whitelist", "List IP whitelist")
+            .returns::<Vec<IpWhitelistEntry>>(),
+        Op::post("/security/whitelist", "admin_add_ip_to_whitelist", "Add IP to whitelist")
+            .body::<AddWhitelistWrapper>()
+            .status("201", "Created"),
+        Op::delete("/security/whitelist", "admin_remove_ip_from_whitelist", "Remove IP from whitelist")
+            .query::<RemoveIpRequest>()
+            .empty(),
+        Op::post("/security/whitelist/clear", "admin_clear_ip_whitelist", "Clear IP whitelist").empty(),
+        Op::get("/security/whitelist/check", "admin_check_ip_in_whitelist", "Check IP against whitelist")
+            .query::<CheckIpQuery>()
+            .returns::<IpCheckResponse>(),
+        Op::get("/security/config", "admin_get_security_config", "Get security config")
+            .returns::<SecurityMonitorConfig>(),
+        Op::post("/security/config", "admin_update_security_config", "Update security config")
+            .body::<UpdateSecurityConfigWrapper>()
+            .empty(),
+        // 用户令牌
+        Op::get("/user-tokens", "admin_list_user_tokens", "List user tokens").returns::<Vec<UserToken>>(),
+        Op::post("/user-tokens", "admin_create_user_token", "Create user token")
+            .body::<CreateTokenRequest>()
+            .returns::<UserToken>(),
+        Op::get("/user-tokens/summary", "admin_get_user_token_summary", "User token summary")
+            .returns::<UserTokenStats>(),
+        Op::post("/user-tokens/:id/renew", "admin_renew_user_token", "Renew user token")
+            .body::<RenewTokenRequest>()
+            .empty(),
+        Op::delete("/user-tokens/:id", "admin_delete_user_token", "Delete user token").no_content(),
+        Op::patch("/user-tokens/:id", "admin_update_user_token", "Update user token")
+            .body::<UpdateTokenRequest>()
+            .empty(),
+        // 账号库加密
+        Op::get("/vault/status", "admin_get_vault_status", "Get vault status").returns::<VaultStatus>(),
+        Op::post("/vault/enable", "admin_enable_vault", "Enable account vault encryption")
+            .body::<VaultPassphraseRequest>()
+            .returns::<usize>(),
+        Op::post("/vault/unlock", "admin_unlock_vault", "Unlock account vault")
+            .body::<VaultPassphraseRequest>()
+            .returns::<usize>(),
+        Op::post("/vault/rekey", "admin_rekey_vault", "Rotate vault key")
+            .body::<VaultRekeyRequest>()
+            .returns::<usize>(),
+        Op::post("/vault/disable", "admin_disable_vault", "Disable account vault encryption").returns::<usize>(),
+    ]
+}
+
+static ADMIN_OPENAPI_DOCUMENT: OnceLock<serde_json::Value> = OnceLock::new();
+
+/// 管理 API 的 OpenAPI 3.1 文档 (首次访问时生成并缓存)
+pub(crate) fn admin_openapi_document() -> &'static serde_json::Value {
+    ADMIN_OPENAPI_DOCUMENT.get_or_init(|| {
+        crate::proxy::openapi::build_document::<ErrorResponse>("/api", &admin_api_operations())
+    })
+}
+
+async fn admin_get_openapi_spec() -> impl IntoResponse {
+    Json(admin_openapi_document().clone())
+}
+
 /// 静默成功处理器 (用于拦截遥测日志等)
 async fn silent_ok_handler() -> Response {
     StatusCode::OK.into_response()
@@ -861,7 +1369,7 @@ async fn admin_list_accounts(
 }
 
 /// Export accounts with refresh tokens (for backup/migration)
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct ExportAccountsRequest {
     account_ids: Vec<String>,
@@ -939,13 +1447,13 @@ async fn admin_get_current_account(
     Ok(Json(response))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct AddAccountRequest {
     refresh_token: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct AddOpenAIWebAccountRequest {
     email: String,
@@ -953,14 +1461,14 @@ struct AddOpenAIWebAccountRequest {
     session_token: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct AddOpenAIAPIAccountRequest {
     email: String,
     api_key: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct ValidateOpenAISessionRequest {
     access_token: String,
@@ -1169,7 +1677,7 @@ async fn admin_delete_account(
     Ok(StatusCode::NO_CONTENT)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct SwitchRequest {
     account_id: String,
@@ -1246,6 +1754,11 @@ async fn admin_refresh_all_quotas() -> Result<impl IntoResponse, (StatusCode, Js
 
 // --- OAuth Handlers ---
 
+#[derive(Serialize, JsonSchema)]
+struct OAuthUrlResponse {
+    url: String,
+}
+
 async fn admin_prepare_oauth_url(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -1259,7 +1772,7 @@ async fn admin_prepare_oauth_url(
                 Json(ErrorResponse { error: e }),
             )
         })?;
-    Ok(Json(serde_json::json!({ "url": url })))
+    Ok(Json(OAuthUrlResponse { url }))
 }
 
 async fn admin_start_oauth_login(
@@ -1313,7 +1826,7 @@ async fn admin_cancel_oauth_login(
     Ok(StatusCode::OK)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct SubmitCodeRequest {
     code: String,
     state: Option<String>,
@@ -1336,7 +1849,7 @@ async fn admin_submit_oauth_code(
     Ok(StatusCode::OK)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct BindDeviceRequest {
     #[serde(default = "default_bind_mode")]
     mode: String,
@@ -1346,6 +1859,13 @@ fn default_bind_mode() -> String {
     "generate".to_string()
 }
 
+#[derive(Serialize, JsonSchema)]
+struct BindDeviceResponse {
+    success: bool,
+    message: String,
+    device_profile: crate::models::account::DeviceProfile,
+}
+
 async fn admin_bind_device(
     Path(account_id): Path<String>,
     Json(payload): Json<BindDeviceRequest>,
@@ -1357,14 +1877,14 @@ async fn admin_bind_device(
         )
     })?;
 
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Device fingerprint bound successfully",
-        "device_profile": result,
-    })))
+    Ok(Json(BindDeviceResponse {
+        success: true,
+        message: "Device fingerprint bound successfully".to_string(),
+        device_profile: result,
+    }))
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct LogsRequest {
     #[serde(default)]
@@ -1413,7 +1933,7 @@ async fn admin_get_config() -> Result<impl IntoResponse, (StatusCode, Json<Error
     Ok(Json(cfg))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct SaveConfigWrapper {
     config: AppConfig,
@@ -1470,6 +1990,21 @@ async fn admin_save_config(
         *exp = new_config.clone().proxy.experimental;
     }
 
+    // 更新自定义客户端适配器
+    crate::proxy::common::client_adapter::update_custom_client_adapters(&new_config.proxy.client_adapters);
+
+    // 更新工具结果压缩配置
+    crate::proxy::update_tool_result_compression_config(new_config.proxy.tool_result_compression.clone());
+
+    // 更新日志保留策略
+    crate::proxy::update_log_retention_config(new_config.proxy.log_retention.clone());
+
+    // 更新服务端工具配置
+    crate::proxy::update_server_tools_config(new_config.proxy.server_tools.clone());
+
+    // 更新 Message Batches 配置
+    crate::proxy::update_message_batches_config(new_config.proxy.message_batches.clone());
+
     Ok(StatusCode::OK)
 }
 
@@ -1490,7 +2025,7 @@ async fn admin_get_all_account_bindings(
 }
 
 // [FIX Web Mode] Bind account to proxy
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct BindAccountProxyRequest {
     account_id: String,
@@ -1509,7 +2044,7 @@ async fn admin_bind_account_proxy(
 }
 
 // [FIX Web Mode] Unbind account from proxy
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct UnbindAccountProxyRequest {
     account_id: String,
@@ -1533,6 +2068,13 @@ async fn admin_get_account_proxy_binding(
 }
 
 // [FIX Web Mode] Trigger proxy pool health check
+#[derive(Serialize, JsonSchema)]
+struct ProxyHealthCheckResponse {
+    success: bool,
+    message: String,
+    proxies: Vec<crate::proxy::config::ProxyEntry>,
+}
+
 async fn admin_trigger_proxy_health_check(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -1545,11 +2087,19 @@ async fn admin_trigger_proxy_health_check(
 
     // 返回更新后的代理池配置（包含健康状态）
     let config = state.proxy_pool_state.read().await;
-    Ok(Json(serde_json::json!({
-        "success": true,
-        "message": "Health check completed",
-        "proxies": config.proxies,
-    })))
+    Ok(Json(ProxyHealthCheckResponse {
+        success: true,
+        message: "Health check completed".to_string(),
+        proxies: config.proxies.clone(),
+    }))
+}
+
+#[derive(Serialize, JsonSchema)]
+struct ProxyStatusResponse {
+    running: bool,
+    port: u16,
+    base_url: String,
+    active_accounts: usize,
 }
 
 async fn admin_get_proxy_status(
@@ -1559,12 +2109,12 @@ async fn admin_get_proxy_status(
     let active_accounts = state.token_manager.len();
 
     let is_running = { *state.is_running.read().await };
-    Ok(Json(serde_json::json!({
-        "running": is_running,
-        "port": state.port,
-        "base_url": format!("http://127.0.0.1:{}", state.port),
-        "active_accounts": active_accounts,
-    })))
+    Ok(Json(ProxyStatusResponse {
+        running: is_running,
+        port: state.port,
+        base_url: format!("http://127.0.0.1:{}", state.port),
+        active_accounts,
+    }))
 }
 
 async fn admin_start_proxy_service(State(state): State<AppState>) -> impl IntoResponse {
@@ -1598,7 +2148,7 @@ async fn admin_stop_proxy_service(State(state): State<AppState>) -> impl IntoRes
     StatusCode::OK
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct UpdateMappingWrapper {
     config: crate::proxy::config::ProxyConfig,
@@ -1673,7 +2223,7 @@ async fn admin_get_preferred_account(State(state): State<AppState>) -> impl Into
     Json(pref)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct SetPreferredAccountRequest {
     account_id: Option<String>,
@@ -1690,30 +2240,20 @@ async fn admin_set_preferred_account(
     StatusCode::OK
 }
 
+// 复用前端 fetch_zai_models 传来的参数 (upstreamProxy 等其余字段忽略)
+#[derive(Deserialize, JsonSchema)]
+struct FetchZaiModelsRequest {
+    zai: crate::proxy::config::ZaiConfig,
+}
+
 async fn admin_fetch_zai_models(
-    Path(id): Path<String>,
-    Json(payload): Json<serde_json::Value>, // 复用前端传来的参数
+    Json(payload): Json<FetchZaiModelsRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     // 这里简单实现，如果需要更复杂的抓取逻辑，可以调用 zai 模块
     // 目前前端 fetch_zai_models 本质上也是一个工具函数，
     // 我们可以在后端通过 reqwest 代理抓取。
-    let zai_config = payload.get("zai").ok_or_else(|| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(ErrorResponse {
-                error: "Missing zai config".to_string(),
-            }),
-        )
-    })?;
-
-    let api_key = zai_config
-        .get("api_key")
-        .and_then(|v| v.as_str())
-        .unwrap_or("");
-    let base_url = zai_config
-        .get("base_url")
-        .and_then(|v| v.as_str())
-        .unwrap_or("https://api.z.ai");
+    let api_key = &payload.zai.api_key;
+    let base_url = &payload.zai.base_url;
 
     // 尝试从 z.ai 获取模型
     let client = reqwest::Client::new();
@@ -1757,14 +2297,17 @@ async fn admin_fetch_zai_models(
     Ok(Json(models))
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct MonitorToggleRequest {
+    #[serde(default)]
+    enabled: bool,
+}
+
 async fn admin_set_proxy_monitor_enabled(
     State(state): State<AppState>,
-    Json(payload): Json<serde_json::Value>,
+    Json(payload): Json<MonitorToggleRequest>,
 ) -> impl IntoResponse {
-    let enabled = payload
-        .get("enabled")
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false);
+    let enabled = payload.enabled;
 
     // [FIX #1269] 只有在状态真正改变时才记录日志并设置，避免重复触发导致的"重启"错觉
     if state.monitor.is_enabled() != enabled {
@@ -1831,7 +2374,7 @@ async fn admin_get_proxy_log_detail(
     }
 }
 
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct LogsFilterQuery {
     #[serde(default)]
@@ -1872,6 +2415,178 @@ async fn admin_get_proxy_logs_filtered(
     }
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct LogSearchRequest {
+    #[serde(default)]
+    query: crate::modules::log_query::LogQuery,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct LogAggregateRequest {
+    #[serde(default)]
+    query: crate::modules::log_query::LogQuery,
+    group_by: crate::modules::log_query::LogGroupBy,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct LogExportRequest {
+    #[serde(default)]
+    query: crate::modules::log_query::LogQuery,
+    #[serde(default)]
+    format: crate::modules::log_query::LogExportFormat,
+}
+
+/// 检索条件非法 (状态码 / 游标格式错误) 时返回 400
+fn validate_log_query(
+    query: &crate::modules::log_query::LogQuery,
+) -> Result<(), (StatusCode, Json<ErrorResponse>)> {
+    query
+        .build_where(true)
+        .map(|_| ())
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))
+}
+
+async fn admin_search_proxy_logs(
+    Json(payload): Json<LogSearchRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    validate_log_query(&payload.query)?;
+    let res = tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::search_logs(&payload.query)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(page)) => Ok(Json(page)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_aggregate_proxy_logs(
+    Json(payload): Json<LogAggregateRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    validate_log_query(&payload.query)?;
+    let res = tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::aggregate_logs(&payload.query, payload.group_by)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Ok(Json(rows)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_get_latency_report(
+    Json(payload): Json<LogAggregateRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    validate_log_query(&payload.query)?;
+    let res = tokio::task::spawn_blocking(move || {
+        crate::modules::proxy_db::latency_report(&payload.query, payload.group_by)
+    })
+    .await;
+
+    match res {
+        Ok(Ok(rows)) => Ok(Json(rows)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct RunLogRetentionRequest {
+    /// 忽略 VACUUM 间隔，立即回收空闲页
+    #[serde(default)]
+    force_vacuum: bool,
+}
+
+async fn admin_get_log_retention_report() -> impl IntoResponse {
+    Json(crate::modules::log_retention::last_report())
+}
+
+async fn admin_run_log_retention(
+    Json(payload): Json<RunLogRetentionRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    crate::modules::log_retention::run_from_app_config(payload.force_vacuum)
+        .await
+        .map(Json)
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(ErrorResponse { error: e }),
+            )
+        })
+}
+
+/// 将阻塞写出转为异步响应流 (导出日志时逐块推送，避免整份文件驻留内存)
+struct ChannelWriter(tokio::sync::mpsc::Sender<Result<Vec<u8>, std::io::Error>>);
+
+impl std::io::Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.blocking_send(Ok(buf.to_vec())).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "client disconnected")
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+async fn admin_export_proxy_logs(
+    Json(payload): Json<LogExportRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    validate_log_query(&payload.query)?;
+    let format = payload.format;
+
+    let (tx, rx) = tokio::sync::mpsc::channel::<Result<Vec<u8>, std::io::Error>>(16);
+    tokio::task::spawn_blocking(move || {
+        let mut writer = std::io::BufWriter::with_capacity(64 * 1024, ChannelWriter(tx.clone()));
+        if let Err(e) = crate::modules::proxy_db::export_logs(&payload.query, format, &mut writer) {
+            tracing::warn!("Log export aborted: {}", e);
+            let _ = tx.blocking_send(Err(std::io::Error::other(e)));
+        }
+    });
+
+    let headers = [
+        (axum::http::header::CONTENT_TYPE, format.content_type().to_string()),
+        (
+            axum::http::header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"proxy_logs.{}\"", format.extension()),
+        ),
+    ];
+    let body = axum::body::Body::from_stream(tokio_stream::wrappers::ReceiverStream::new(rx));
+    Ok((headers, body))
+}
+
 async fn admin_get_proxy_stats(
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -1920,7 +2635,7 @@ async fn admin_create_user_token(
     Ok(Json(token))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct RenewTokenRequest {
     expires_type: String,
@@ -1964,6 +2679,61 @@ async fn admin_update_user_token(
     Ok(StatusCode::OK)
 }
 
+// --- Account Vault ---
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct VaultPassphraseRequest {
+    passphrase: Option<String>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct VaultRekeyRequest {
+    new_passphrase: Option<String>,
+}
+
+fn vault_error(e: String) -> (StatusCode, Json<ErrorResponse>) {
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e }))
+}
+
+async fn admin_get_vault_status() -> impl IntoResponse {
+    Json(crate::modules::vault::get_status())
+}
+
+async fn admin_enable_vault(
+    Json(payload): Json<VaultPassphraseRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let migrated = crate::modules::vault::enable(payload.passphrase.as_deref()).map_err(vault_error)?;
+    Ok(Json(migrated))
+}
+
+async fn admin_unlock_vault(
+    State(state): State<AppState>,
+    Json(payload): Json<VaultPassphraseRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let migrated = crate::modules::vault::unlock(payload.passphrase.as_deref().unwrap_or_default())
+        .map_err(vault_error)?;
+
+    // 解锁后凭据可读，重新加载账号池
+    if let Err(e) = state.token_manager.load_accounts().await {
+        logger::log_error(&format!("[API] Failed to reload accounts after vault unlock: {}", e));
+    }
+    Ok(Json(migrated))
+}
+
+async fn admin_rekey_vault(
+    Json(payload): Json<VaultRekeyRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let count = crate::modules::vault::rekey(payload.new_passphrase.as_deref()).map_err(vault_error)?;
+    Ok(Json(count))
+}
+
+async fn admin_disable_vault() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let count = crate::modules::vault::disable().map_err(vault_error)?;
+    Ok(Json(count))
+}
+
 async fn admin_should_check_updates() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)>
 {
     let settings = crate::modules::update_checker::load_update_settings().map_err(|e| {
@@ -2035,7 +2805,7 @@ async fn admin_clear_log_cache() -> Result<impl IntoResponse, (StatusCode, Json<
 }
 
 // Token Stats Handlers
-#[derive(Deserialize, Debug, Default)]
+#[derive(Deserialize, Debug, Default, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct StatsPeriodQuery {
     hours: Option<i64>,
@@ -2279,16 +3049,108 @@ async fn admin_clear_token_stats() -> impl IntoResponse {
     }
 }
 
+// 费用统计 / 价格表
+#[derive(Deserialize, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct CostReportQuery {
+    hours: Option<i64>,
+    days: Option<i64>,
+    /// 汇总维度 (默认 model)
+    group_by: Option<token_stats::CostGroupBy>,
+}
+
+#[derive(Deserialize, Debug, Default, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct RecomputeCostsRequest {
+    hours: Option<i64>,
+    days: Option<i64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+struct RecomputeCostsResponse {
+    updated: u64,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SaveModelPriceRequest {
+    price: pricing::ModelPrice,
+}
+
+/// 统计范围 (小时)，默认 30 天
+fn cost_range_hours(hours: Option<i64>, days: Option<i64>) -> i64 {
+    hours.or(days.map(|d| d * 24)).unwrap_or(30 * 24)
+}
+
+async fn admin_get_cost_report(
+    Query(p): Query<CostReportQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let hours = cost_range_hours(p.hours, p.days);
+    let group_by = p.group_by.unwrap_or_default();
+    let res =
+        tokio::task::spawn_blocking(move || token_stats::get_cost_report(hours, group_by)).await;
+
+    match res {
+        Ok(Ok(report)) => Ok(Json(report)),
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_recompute_costs(
+    Json(payload): Json<RecomputeCostsRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    let hours = cost_range_hours(payload.hours, payload.days);
+    let res = tokio::task::spawn_blocking(move || token_stats::recompute_costs(hours)).await;
+
+    match res {
+        Ok(Ok(updated)) => {
+            logger::log_info(&format!("[API] 已按当前价格表重算 {} 条用量费用", updated));
+            Ok(Json(RecomputeCostsResponse { updated }))
+        }
+        Ok(Err(e)) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )),
+        Err(e) => Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse {
+                error: e.to_string(),
+            }),
+        )),
+    }
+}
+
+async fn admin_get_model_prices() -> impl IntoResponse {
+    Json(pricing::get_price_table())
+}
+
+async fn admin_save_model_price(
+    Json(payload): Json<SaveModelPriceRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    pricing::save_price(payload.price)
+        .map(Json)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))
+}
+
+async fn admin_delete_model_price(
+    Path(price_id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    pricing::delete_price(&price_id)
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
 async fn admin_get_update_settings() -> impl IntoResponse {
     // 從真實模組加載設置
-    match crate::modules::update_checker::load_update_settings() {
-        Ok(s) => Json(serde_json::to_value(s).unwrap_or_default()),
-        Err(_) => Json(serde_json::json!({
-            "auto_check": true,
-            "last_check_time": 0,
-            "check_interval_hours": 24
-        })),
-    }
+    Json(crate::modules::update_checker::load_update_settings().unwrap_or_default())
 }
 
 async fn admin_check_for_updates() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -2314,15 +3176,22 @@ async fn admin_update_last_check_time(
     Ok(StatusCode::OK)
 }
 
-async fn admin_save_update_settings(Json(settings): Json<serde_json::Value>) -> impl IntoResponse {
-    if let Ok(s) =
-        serde_json::from_value::<crate::modules::update_checker::UpdateSettings>(settings)
-    {
-        let _ = crate::modules::update_checker::save_update_settings(&s);
-        StatusCode::OK
-    } else {
-        StatusCode::BAD_REQUEST
-    }
+// 与前端 invoke 参数一致: { settings: {...} }
+#[derive(Deserialize, JsonSchema)]
+struct SaveUpdateSettingsWrapper {
+    settings: crate::modules::update_checker::UpdateSettings,
+}
+
+async fn admin_save_update_settings(
+    Json(payload): Json<SaveUpdateSettingsWrapper>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    crate::modules::update_checker::save_update_settings(&payload.settings).map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ErrorResponse { error: e }),
+        )
+    })?;
+    Ok(StatusCode::OK)
 }
 
 async fn admin_is_auto_launch_enabled() -> impl IntoResponse {
@@ -2331,7 +3200,15 @@ async fn admin_is_auto_launch_enabled() -> impl IntoResponse {
     Json(false)
 }
 
-async fn admin_toggle_auto_launch(Json(_payload): Json<serde_json::Value>) -> impl IntoResponse {
+#[derive(Deserialize, JsonSchema)]
+#[allow(dead_code)]
+struct ToggleAutoLaunchRequest {
+    enable: bool,
+}
+
+async fn admin_toggle_auto_launch(
+    Json(_payload): Json<ToggleAutoLaunchRequest>,
+) -> impl IntoResponse {
     // Note: Autostart requires tauri::AppHandle.
     StatusCode::NOT_IMPLEMENTED
 }
@@ -2342,7 +3219,7 @@ async fn admin_get_http_api_settings(State(state): State<AppState>) -> impl Into
 
 // [整合清理] 冗餘導入已移除
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct BulkDeleteRequest {
     #[serde(rename = "accountIds")]
     account_ids: Vec<String>,
@@ -2360,7 +3237,7 @@ async fn admin_delete_accounts(
     Ok(StatusCode::OK)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct ReorderRequest {
     account_ids: Vec<String>,
@@ -2419,7 +3296,7 @@ async fn admin_fetch_account_quota(
     Ok(Json(quota))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct ToggleProxyRequest {
     enable: bool,
@@ -2553,7 +3430,7 @@ async fn admin_cloudflared_install(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CloudflaredStartRequest {
     config: crate::modules::cloudflared::CloudflaredConfig,
@@ -2660,7 +3537,7 @@ async fn admin_preview_generate_profile(
     Ok(Json(profile))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct BindDeviceProfileWrapper {
     account_id: String,
@@ -2669,7 +3546,7 @@ struct BindDeviceProfileWrapper {
 }
 
 // 用于 API 的 DeviceProfile 包装器，支持 camelCase 输入
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct DeviceProfileApiWrapper {
     #[serde(alias = "machineId")]
     machine_id: String,
@@ -2815,7 +3692,7 @@ async fn admin_import_from_db(
     Ok(Json(to_account_response(&account, &current_id)))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct CustomDbRequest {
     path: String,
 }
@@ -2899,7 +3776,7 @@ async fn admin_sync_account_from_db(
 
 // --- CLI Sync Handlers ---
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CliSyncStatusRequest {
     app_type: crate::proxy::cli_sync::CliApp,
@@ -2920,7 +3797,7 @@ async fn admin_get_cli_sync_status(
         })
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CliSyncRequest {
     app_type: crate::proxy::cli_sync::CliApp,
@@ -2942,7 +3819,7 @@ async fn admin_execute_cli_sync(
         })
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CliRestoreRequest {
     app_type: crate::proxy::cli_sync::CliApp,
@@ -2962,7 +3839,7 @@ async fn admin_execute_cli_restore(
         })
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CliConfigContentRequest {
     app_type: crate::proxy::cli_sync::CliApp,
@@ -3106,10 +3983,16 @@ async fn handle_oauth_callback(
     }
 }
 
+#[derive(Serialize, JsonSchema)]
+struct WebOAuthUrlResponse {
+    url: String,
+    state: String,
+}
+
 async fn admin_prepare_oauth_url_web(
     headers: HeaderMap,
     State(state): State<AppState>,
-) -> Result<Json<serde_json::Value>, (StatusCode, Json<ErrorResponse>)> {
+) -> Result<Json<WebOAuthUrlResponse>, (StatusCode, Json<ErrorResponse>)> {
     let port = state.security.read().await.port;
     let host = headers.get("host").and_then(|h| h.to_str().ok());
     let proto = headers
@@ -3192,10 +4075,10 @@ async fn admin_prepare_oauth_url_web(
         }
     });
 
-    Ok(Json(serde_json::json!({
-        "url": auth_url,
-        "state": state_str
-    })))
+    Ok(Json(WebOAuthUrlResponse {
+        url: auth_url,
+        state: state_str,
+    }))
 }
 
 /// 辅助函数：获取 OAuth 重定向 URI
@@ -3215,7 +4098,7 @@ fn get_oauth_redirect_uri(port: u16, _host: Option<&str>, _proto: Option<&str>)
 // Security / IP Management Handlers
 // ============================================================================
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct IpAccessLogQuery {
     page: usize,
@@ -3225,7 +4108,7 @@ struct IpAccessLogQuery {
     blocked_only: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct IpAccessLogResponse {
     logs: Vec<crate::modules::security_db::IpAccessLog>,
     total: usize,
@@ -3253,7 +4136,7 @@ async fn admin_clear_ip_access_logs() -> Result<impl IntoResponse, (StatusCode,
     Ok(StatusCode::OK)
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct IpStatsResponse {
     total_requests: usize,
     unique_ips: usize,
@@ -3276,7 +4159,7 @@ async fn admin_get_ip_stats() -> Result<impl IntoResponse, (StatusCode, Json<Err
     Ok(Json(response))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct IpTokenStatsQuery {
     limit: Option<usize>,
     hours: Option<i64>,
@@ -3298,14 +4181,14 @@ async fn admin_get_ip_blacklist() -> Result<impl IntoResponse, (StatusCode, Json
     Ok(Json(list))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct AddBlacklistRequest {
     ip_pattern: String,
     reason: Option<String>,
     expires_at: Option<i64>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct AddBlacklistWrapper {
     request: AddBlacklistRequest,
 }
@@ -3324,7 +4207,7 @@ async fn admin_add_ip_to_blacklist(
     Ok(StatusCode::CREATED)
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct RemoveIpRequest {
     ip_pattern: String,
@@ -3356,7 +4239,12 @@ async fn admin_clear_ip_blacklist() -> Result<impl IntoResponse, (StatusCode, Js
     Ok(StatusCode::OK)
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, JsonSchema)]
+struct IpCheckResponse {
+    result: bool,
+}
+
+#[derive(Deserialize, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 struct CheckIpQuery {
     ip: String,
@@ -3367,7 +4255,7 @@ async fn admin_check_ip_in_blacklist(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = security_db::is_ip_in_blacklist(&q.ip)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
-    Ok(Json(serde_json::json!({ "result": result })))
+    Ok(Json(IpCheckResponse { result }))
 }
 
 async fn admin_get_ip_whitelist() -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
@@ -3376,13 +4264,13 @@ async fn admin_get_ip_whitelist() -> Result<impl IntoResponse, (StatusCode, Json
     Ok(Json(list))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct AddWhitelistRequest {
     ip_pattern: String,
     description: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct AddWhitelistWrapper {
     request: AddWhitelistRequest,
 }
@@ -3428,7 +4316,7 @@ async fn admin_check_ip_in_whitelist(
 ) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
     let result = security_db::is_ip_in_whitelist(&q.ip)
         .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ErrorResponse { error: e })))?;
-    Ok(Json(serde_json::json!({ "result": result })))
+    Ok(Json(IpCheckResponse { result }))
 }
 
 async fn admin_get_security_config(
@@ -3440,7 +4328,7 @@ async fn admin_get_security_config(
     Ok(Json(app_config.proxy.security_monitor))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 struct UpdateSecurityConfigWrapper {
     config: crate::proxy::config::SecurityMonitorConfig,
 }
@@ -3493,4 +4381,42 @@ async fn admin_clear_debug_console_logs() -> impl IntoResponse {
     StatusCode::OK
 }
 
+// ============================================================================
+// 实时事件流 (/api/events)
+// ============================================================================
+
+#[derive(Deserialize, JsonSchema)]
+struct EventsQuery {
+    /// 逗号分隔的主题 (request,account,quota,proxy_pool,debug)，缺省订阅全部
+    topics: Option<String>,
+}
+
+/// SSE 推送管理事件：event 字段为主题名，data 为 AdminEvent JSON。
+/// 订阅者处理过慢时会收到 lagged 事件 (data 为丢弃的事件数)。
+async fn admin_stream_events(
+    Query(q): Query<EventsQuery>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ErrorResponse>)> {
+    use axum::response::sse::{Event, KeepAlive, Sse};
+    use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+    use tokio_stream::StreamExt;
+
+    let topics = crate::modules::event_bus::parse_topics(q.topics.as_deref().unwrap_or(""))
+        .map_err(|e| (StatusCode::BAD_REQUEST, Json(ErrorResponse { error: e })))?;
+
+    let stream = BroadcastStream::new(crate::modules::event_bus::subscribe()).filter_map(
+        move |item| match item {
+            Ok(event) if topics.contains(&event.topic) => Event::default()
+                .event(event.topic.as_str())
+                .id(event.id.to_string())
+                .json_data(&event)
+                .ok()
+                .map(Ok::<Event, std::convert::Infallible>),
+            Ok(_) => None,
+            Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+                Some(Ok(Event::default().event("lagged").data(skipped.to_string())))
+            }
+        },
+    );
 
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
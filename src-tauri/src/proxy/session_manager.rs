@@ -40,7 +40,7 @@ impl SessionManager {
                 MessageContent::Array(blocks) => {
                     blocks.iter()
                         .filter_map(|block| match block {
-                            crate::proxy::mappers::claude::models::ContentBlock::Text { text } => Some(text.as_str()),
+                            crate::proxy::mappers::claude::models::ContentBlock::Text { text, .. } => Some(text.as_str()),
                             _ => None,
                         })
                         .collect::<Vec<_>>()
@@ -0,0 +1,181 @@
+// 会话快照持久化
+// 将签名缓存 (三层) 与粘性会话绑定写入 SQLite，使代理重启后思维链会话与缓存路由得以延续。
+// 恢复时沿用原始缓存时间戳，因此 TTL 与各层容量上限保持不变。
+
+use rusqlite::{params, Connection};
+use std::path::Path;
+
+use crate::proxy::signature_cache::{SignatureSnapshot, SnapshotEntry};
+
+const SNAPSHOT_DB: &str = "session_snapshot.db";
+
+const LAYER_TOOL: &str = "tool";
+const LAYER_FAMILY: &str = "family";
+const LAYER_SESSION: &str = "session";
+
+/// 磁盘快照内容
+#[derive(Debug, Clone, Default)]
+pub struct SessionSnapshot {
+    pub signatures: SignatureSnapshot,
+    /// (session_id, account_id)
+    pub session_bindings: Vec<(String, String)>,
+}
+
+fn connect_db(data_dir: &Path) -> Result<Connection, String> {
+    let conn = Connection::open(data_dir.join(SNAPSHOT_DB)).map_err(|e| e.to_string())?;
+
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS signature_cache (
+            layer TEXT NOT NULL,
+            key TEXT NOT NULL,
+            value TEXT NOT NULL,
+            message_count INTEGER NOT NULL DEFAULT 0,
+            timestamp INTEGER NOT NULL,
+            PRIMARY KEY (layer, key)
+        );
+        CREATE TABLE IF NOT EXISTS session_bindings (
+            session_id TEXT PRIMARY KEY,
+            account_id TEXT NOT NULL
+        );",
+    )
+    .map_err(|e| e.to_string())?;
+
+    Ok(conn)
+}
+
+/// 覆盖写入快照 (单事务，中途失败不会留下半份数据)
+pub fn save_snapshot(data_dir: &Path, snapshot: &SessionSnapshot) -> Result<(), String> {
+    let mut conn = connect_db(data_dir)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+
+    tx.execute("DELETE FROM signature_cache", [])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM session_bindings", [])
+        .map_err(|e| e.to_string())?;
+
+    {
+        let mut stmt = tx
+            .prepare(
+                "INSERT OR REPLACE INTO signature_cache (layer, key, value, message_count, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+            )
+            .map_err(|e| e.to_string())?;
+        let layers = [
+            (LAYER_TOOL, &snapshot.signatures.tool_signatures),
+            (LAYER_FAMILY, &snapshot.signatures.thinking_families),
+            (LAYER_SESSION, &snapshot.signatures.session_signatures),
+        ];
+        for (layer, entries) in layers {
+            for entry in entries.iter() {
+                stmt.execute(params![
+                    layer,
+                    entry.key,
+                    entry.value,
+                    entry.message_count as i64,
+                    entry.timestamp
+                ])
+                .map_err(|e| e.to_string())?;
+            }
+        }
+
+        let mut stmt = tx
+            .prepare("INSERT OR REPLACE INTO session_bindings (session_id, account_id) VALUES (?1, ?2)")
+            .map_err(|e| e.to_string())?;
+        for (session_id, account_id) in &snapshot.session_bindings {
+            stmt.execute(params![session_id, account_id])
+                .map_err(|e| e.to_string())?;
+        }
+    }
+
+    tx.commit().map_err(|e| e.to_string())
+}
+
+/// 读取快照，文件不存在时返回空快照
+pub fn load_snapshot(data_dir: &Path) -> Result<SessionSnapshot, String> {
+    if !data_dir.join(SNAPSHOT_DB).exists() {
+        return Ok(SessionSnapshot::default());
+    }
+    let conn = connect_db(data_dir)?;
+    let mut snapshot = SessionSnapshot::default();
+
+    let mut stmt = conn
+        .prepare("SELECT layer, key, value, message_count, timestamp FROM signature_cache")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([], |row| {
+            let layer: String = row.get(0)?;
+            let message_count: i64 = row.get(3)?;
+            Ok((
+                layer,
+                SnapshotEntry {
+                    key: row.get(1)?,
+                    value: row.get(2)?,
+                    message_count: message_count.max(0) as usize,
+                    timestamp: row.get(4)?,
+                },
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    for (layer, entry) in rows.filter_map(|r| r.ok()) {
+        match layer.as_str() {
+            LAYER_TOOL => snapshot.signatures.tool_signatures.push(entry),
+            LAYER_FAMILY => snapshot.signatures.thinking_families.push(entry),
+            LAYER_SESSION => snapshot.signatures.session_signatures.push(entry),
+            _ => {}
+        }
+    }
+
+    let mut stmt = conn
+        .prepare("SELECT session_id, account_id FROM session_bindings")
+        .map_err(|e| e.to_string())?;
+    snapshot.session_bindings = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(snapshot)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &str, message_count: usize) -> SnapshotEntry {
+        SnapshotEntry {
+            key: key.to_string(),
+            value: "sig".repeat(20),
+            message_count,
+            timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("abv-snapshot-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        assert!(load_snapshot(&dir).unwrap().session_bindings.is_empty());
+
+        let snapshot = SessionSnapshot {
+            signatures: SignatureSnapshot {
+                tool_signatures: vec![entry("toolu_1", 0)],
+                thinking_families: vec![entry("sig", 0)],
+                session_signatures: vec![entry("sid-1", 12)],
+            },
+            session_bindings: vec![("sid-1".to_string(), "acc-1".to_string())],
+        };
+        save_snapshot(&dir, &snapshot).unwrap();
+
+        // 第二次写入覆盖而非追加
+        save_snapshot(&dir, &snapshot).unwrap();
+        let loaded = load_snapshot(&dir).unwrap();
+
+        assert_eq!(loaded.signatures.tool_signatures, snapshot.signatures.tool_signatures);
+        assert_eq!(loaded.signatures.thinking_families, snapshot.signatures.thinking_families);
+        assert_eq!(loaded.signatures.session_signatures, snapshot.signatures.session_signatures);
+        assert_eq!(loaded.session_bindings, snapshot.session_bindings);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::sync::{Mutex, OnceLock};
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // Node.js proxy uses 2 hours TTL
 const SIGNATURE_TTL: Duration = Duration::from_secs(2 * 60 * 60);
@@ -33,11 +33,100 @@ impl<T> CacheEntry<T> {
         }
     }
 
+    fn with_timestamp(data: T, unix_secs: i64) -> Self {
+        Self {
+            data,
+            timestamp: UNIX_EPOCH + Duration::from_secs(unix_secs.max(0) as u64),
+        }
+    }
+
+    fn unix_timestamp(&self) -> i64 {
+        self.timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0)
+    }
+
     fn is_expired(&self) -> bool {
         self.timestamp.elapsed().unwrap_or(Duration::ZERO) > SIGNATURE_TTL
     }
 }
 
+/// A single cache record in an on-disk snapshot
+#[derive(Clone, Debug, PartialEq)]
+pub struct SnapshotEntry {
+    pub key: String,
+    pub value: String,
+    /// Only meaningful for the session layer (0 for the other layers)
+    pub message_count: usize,
+    /// Unix timestamp (seconds) when the entry was cached; TTL keeps counting from here
+    pub timestamp: i64,
+}
+
+/// Serializable copy of all three cache layers (expired entries excluded)
+#[derive(Clone, Debug, Default)]
+pub struct SignatureSnapshot {
+    pub tool_signatures: Vec<SnapshotEntry>,
+    pub thinking_families: Vec<SnapshotEntry>,
+    pub session_signatures: Vec<SnapshotEntry>,
+}
+
+fn export_layer<T>(
+    cache: &Mutex<HashMap<String, CacheEntry<T>>>,
+    to_record: impl Fn(&T) -> (String, usize),
+) -> Vec<SnapshotEntry> {
+    let Ok(cache) = cache.lock() else {
+        return Vec::new();
+    };
+    cache
+        .iter()
+        .filter(|(_, entry)| !entry.is_expired())
+        .map(|(key, entry)| {
+            let (value, message_count) = to_record(&entry.data);
+            SnapshotEntry {
+                key: key.clone(),
+                value,
+                message_count,
+                timestamp: entry.unix_timestamp(),
+            }
+        })
+        .collect()
+}
+
+/// Merge snapshot entries into a layer, keeping the original timestamps (so TTLs still apply)
+/// and at most `limit` of the most recent entries. Newer in-memory entries win.
+fn import_layer<T>(
+    cache: &Mutex<HashMap<String, CacheEntry<T>>>,
+    mut entries: Vec<SnapshotEntry>,
+    limit: usize,
+    from_record: impl Fn(String, usize) -> T,
+) -> usize {
+    let Ok(mut cache) = cache.lock() else {
+        return 0;
+    };
+    entries.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+    let mut restored = 0;
+    for record in entries.into_iter().take(limit) {
+        let entry = CacheEntry::with_timestamp(
+            from_record(record.value, record.message_count),
+            record.timestamp,
+        );
+        if entry.is_expired() {
+            continue;
+        }
+        let is_newer = match cache.get(&record.key) {
+            Some(existing) => existing.timestamp < entry.timestamp,
+            None => true,
+        };
+        if is_newer {
+            cache.insert(record.key, entry);
+            restored += 1;
+        }
+    }
+    restored
+}
+
 /// Triple-layer signature cache to handle:
 /// 1. Signature recovery for tool calls (when clients strip them)
 /// 2. Cross-model compatibility checks (preventing Claude signatures on Gemini models)
@@ -239,6 +328,40 @@ impl SignatureCache {
         None
     }
 
+    // ===== Persistence =====
+
+    /// Export all non-expired entries for an on-disk snapshot
+    pub fn export_snapshot(&self) -> SignatureSnapshot {
+        SignatureSnapshot {
+            tool_signatures: export_layer(&self.tool_signatures, |sig| (sig.clone(), 0)),
+            thinking_families: export_layer(&self.thinking_families, |family| (family.clone(), 0)),
+            session_signatures: export_layer(&self.session_signatures, |entry| {
+                (entry.signature.clone(), entry.message_count)
+            }),
+        }
+    }
+
+    /// Restore entries from a snapshot, honoring TTLs and per-layer limits.
+    /// Returns the number of restored entries.
+    pub fn import_snapshot(&self, snapshot: SignatureSnapshot) -> usize {
+        import_layer(&self.tool_signatures, snapshot.tool_signatures, TOOL_CACHE_LIMIT, |sig, _| sig)
+            + import_layer(
+                &self.thinking_families,
+                snapshot.thinking_families,
+                FAMILY_CACHE_LIMIT,
+                |family, _| family,
+            )
+            + import_layer(
+                &self.session_signatures,
+                snapshot.session_signatures,
+                SESSION_CACHE_LIMIT,
+                |signature, message_count| SessionSignatureEntry {
+                    signature,
+                    message_count,
+                },
+            )
+    }
+
     /// Clear all caches (for testing or manual reset)
     #[allow(dead_code)] // Used in tests
     pub fn clear(&self) {
@@ -319,6 +442,56 @@ mod tests {
         assert!(cache.get_session_signature("sid-other").is_none());
     }
 
+    #[test]
+    fn test_snapshot_roundtrip() {
+        let cache = SignatureCache::new();
+        let sig = "s".repeat(60);
+        cache.cache_tool_signature("tool_1", sig.clone());
+        cache.cache_thinking_family(sig.clone(), "claude".to_string());
+        cache.cache_session_signature("sid-1", sig.clone(), 7);
+
+        let snapshot = cache.export_snapshot();
+        assert_eq!(snapshot.session_signatures[0].message_count, 7);
+
+        let restored = SignatureCache::new();
+        assert_eq!(restored.import_snapshot(snapshot), 3);
+        assert_eq!(restored.get_tool_signature("tool_1"), Some(sig.clone()));
+        assert_eq!(restored.get_signature_family(&sig), Some("claude".to_string()));
+        assert_eq!(restored.get_session_signature("sid-1"), Some(sig.clone()));
+
+        // Rewind detection still works on restored entries
+        restored.cache_session_signature("sid-1", "r".repeat(55), 3);
+        assert_eq!(restored.get_session_signature("sid-1"), Some("r".repeat(55)));
+    }
+
+    #[test]
+    fn test_snapshot_import_skips_expired_and_respects_limit() {
+        let now = CacheEntry::new(()).unix_timestamp();
+        let expired = now - SIGNATURE_TTL.as_secs() as i64 - 10;
+        let record = |key: String, timestamp: i64| SnapshotEntry {
+            key,
+            value: "v".repeat(60),
+            message_count: 0,
+            timestamp,
+        };
+
+        let mut tools: Vec<SnapshotEntry> = (0..TOOL_CACHE_LIMIT + 20)
+            .map(|i| record(format!("tool_{}", i), now - i as i64))
+            .collect();
+        tools.push(record("tool_old".to_string(), expired));
+
+        let cache = SignatureCache::new();
+        let restored = cache.import_snapshot(SignatureSnapshot {
+            tool_signatures: tools,
+            ..Default::default()
+        });
+
+        assert_eq!(restored, TOOL_CACHE_LIMIT);
+        assert!(cache.get_tool_signature("tool_0").is_some());
+        assert!(cache.get_tool_signature(&format!("tool_{}", TOOL_CACHE_LIMIT + 10)).is_none());
+        assert!(cache.get_tool_signature("tool_old").is_none());
+    }
+
     #[test]
     fn test_clear_all_caches() {
         let cache = SignatureCache::new();
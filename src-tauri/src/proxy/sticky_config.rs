@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// 调度模式枚举
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum SchedulingMode {
     /// 缓存优先 (Cache-first): 尽可能锁定同一账号，限流时优先等待，极大提升 Prompt Caching 命中率
     CacheFirst,
@@ -18,13 +19,17 @@ impl Default for SchedulingMode {
 }
 
 /// 粘性会话配置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(default)]
 pub struct StickySessionConfig {
     /// 当前调度模式
     pub mode: SchedulingMode,
     /// 缓存优先模式下的最大等待时间 (秒)
     pub max_wait_seconds: u64,
+    /// 是否将签名缓存与会话绑定持久化到磁盘 (重启后恢复)
+    pub persist_sessions: bool,
+    /// 会话快照写入间隔 (秒)
+    pub snapshot_interval_seconds: u64,
 }
 
 impl Default for StickySessionConfig {
@@ -32,6 +37,8 @@ impl Default for StickySessionConfig {
         Self {
             mode: SchedulingMode::Balance,
             max_wait_seconds: 60,
+            persist_sessions: true,
+            snapshot_interval_seconds: 60,
         }
     }
 }
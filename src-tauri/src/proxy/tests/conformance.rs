@@ -0,0 +1,833 @@
+// 跨协议一致性测试 (Conformance Harness)
+// 在临时端口上启动完整的 AxumServer，并将 v1internal 上游指向本地 mock，
+// 从 OpenAI / Anthropic / Gemini 三个入口端到端驱动请求 (JSON / SSE / 429 / 403 / 流中断)。
+// 响应经去随机化后与 fixtures/conformance/*.golden 比对；golden 缺失视为失败，
+// 设置 ABV_UPDATE_GOLDEN=1 时重新生成，提交前请检查 diff。
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{Body, Bytes};
+    use axum::extract::State;
+    use axum::http::{header, HeaderMap, StatusCode, Uri};
+    use axum::response::{IntoResponse, Response};
+    use axum::Router;
+    use futures::StreamExt;
+    use serde_json::{json, Value};
+    use std::collections::VecDeque;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use crate::proxy::config::{
+        DebugLoggingConfig, ExperimentalConfig, ProxyPoolConfig, UpstreamProxyConfig, ZaiConfig,
+    };
+    use crate::proxy::server::AxumServer;
+    use crate::proxy::token_manager::TokenManager;
+    use crate::proxy::{ProxyAuthMode, ProxyConfig, ProxySecurityConfig};
+
+    /// 需要随机化处理的字段 (响应 ID、时间戳、签名等)
+    const VOLATILE_KEYS: &[&str] = &[
+        "id",
+        "created",
+        "created_at",
+        "responseId",
+        "system_fingerprint",
+        "signature",
+    ];
+
+    const GOLDEN_DIR: &str = "src/proxy/tests/fixtures/conformance";
+
+    // ==================================================================================
+    // Mock v1internal 上游
+    // ==================================================================================
+
+    /// 预置的上游回复，按调用顺序依次消费
+    enum MockReply {
+        /// 正常回复：generateContent 返回单个 JSON，streamGenerateContent 每个 part 一个 SSE 分片
+        Parts(Vec<Value>),
+        /// 原样返回错误状态码与 JSON
+        Error(StatusCode, Value),
+        /// 输出部分 SSE 分片后中断连接
+        Disconnect(Vec<Value>),
+    }
+
+    impl MockReply {
+        fn text(chunks: &[&str]) -> Self {
+            MockReply::Parts(chunks.iter().map(|t| json!({ "text": t })).collect())
+        }
+
+        fn function_call(name: &str, args: Value) -> Self {
+            MockReply::Parts(vec![
+                json!({ "functionCall": { "name": name, "args": args } }),
+            ])
+        }
+
+        /// 429 RESOURCE_EXHAUSTED，携带 RetryInfo.retryDelay 与 quotaResetDelay
+        fn rate_limited(retry_delay: &str) -> Self {
+            MockReply::Error(
+                StatusCode::TOO_MANY_REQUESTS,
+                json!({
+                    "error": {
+                        "code": 429,
+                        "message": "Resource has been exhausted (e.g. check quota).",
+                        "status": "RESOURCE_EXHAUSTED",
+                        "details": [
+                            {
+                                "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                                "reason": "RATE_LIMIT_EXCEEDED",
+                                "domain": "cloudcode-pa.googleapis.com",
+                                "metadata": { "quotaResetDelay": retry_delay }
+                            },
+                            {
+                                "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                                "retryDelay": retry_delay
+                            }
+                        ]
+                    }
+                }),
+            )
+        }
+
+        /// 403 VALIDATION_REQUIRED (账号需要人工验证)
+        fn validation_required() -> Self {
+            MockReply::Error(
+                StatusCode::FORBIDDEN,
+                json!({
+                    "error": {
+                        "code": 403,
+                        "message": "Please verify your account to continue.",
+                        "status": "PERMISSION_DENIED",
+                        "details": [{
+                            "@type": "type.googleapis.com/google.rpc.ErrorInfo",
+                            "reason": "VALIDATION_REQUIRED",
+                            "metadata": { "validation_url": "https://accounts.google.com/signin/continue" }
+                        }]
+                    }
+                }),
+            )
+        }
+    }
+
+    /// mock 收到的一次上游调用
+    #[derive(Debug, Clone)]
+    struct RecordedCall {
+        method: String,
+        query: Option<String>,
+        authorization: String,
+        body: Value,
+    }
+
+    #[derive(Default)]
+    struct MockState {
+        script: Mutex<VecDeque<MockReply>>,
+        calls: Mutex<Vec<RecordedCall>>,
+    }
+
+    fn candidate_chunk(parts: Vec<Value>, finish: bool) -> Value {
+        let mut candidate = json!({ "content": { "role": "model", "parts": parts }, "index": 0 });
+        if finish {
+            candidate["finishReason"] = json!("STOP");
+        }
+        let mut response = json!({
+            "candidates": [candidate],
+            "modelVersion": "mock-model",
+            "responseId": "mock-response"
+        });
+        if finish {
+            response["usageMetadata"] = json!({
+                "promptTokenCount": 12,
+                "candidatesTokenCount": 5,
+                "totalTokenCount": 17
+            });
+        }
+        json!({ "response": response })
+    }
+
+    fn sse_frames(parts: &[Value], finish: bool) -> Vec<Result<Bytes, std::io::Error>> {
+        parts
+            .iter()
+            .enumerate()
+            .map(|(idx, part)| {
+                let chunk = candidate_chunk(vec![part.clone()], finish && idx + 1 == parts.len());
+                Ok(Bytes::from(format!("data: {}\n\n", chunk)))
+            })
+            .collect()
+    }
+
+    fn sse_response(body: Body) -> Response {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, "text/event-stream")
+            .body(body)
+            .unwrap()
+    }
+
+    /// 路径形如 /v1internal:streamGenerateContent
+    async fn mock_v1internal(
+        State(state): State<Arc<MockState>>,
+        uri: Uri,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        let method = uri
+            .path()
+            .rsplit(':')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        if method != "generateContent" && method != "streamGenerateContent" {
+            return (
+                StatusCode::NOT_FOUND,
+                format!("mock: unsupported method {}", method),
+            )
+                .into_response();
+        }
+
+        state.calls.lock().unwrap().push(RecordedCall {
+            method: method.clone(),
+            query: uri.query().map(|q| q.to_string()),
+            authorization: headers
+                .get(header::AUTHORIZATION)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        });
+
+        let reply = state.script.lock().unwrap().pop_front();
+        let is_stream = method == "streamGenerateContent";
+
+        match reply {
+            Some(MockReply::Parts(parts)) if is_stream => sse_response(Body::from_stream(
+                futures::stream::iter(sse_frames(&parts, true)),
+            )),
+            Some(MockReply::Parts(parts)) => {
+                axum::Json(candidate_chunk(parts, true)).into_response()
+            }
+            Some(MockReply::Error(status, body)) => (status, axum::Json(body)).into_response(),
+            Some(MockReply::Disconnect(parts)) => {
+                let tail = futures::stream::once(async {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                    Err::<Bytes, _>(std::io::Error::new(
+                        std::io::ErrorKind::ConnectionReset,
+                        "mock upstream disconnect",
+                    ))
+                });
+                let frames = futures::stream::iter(sse_frames(&parts, false)).chain(tail);
+                sse_response(Body::from_stream(frames))
+            }
+            // 脚本耗尽时返回不可重试的 400，避免测试卡在退避重试中
+            None => (
+                StatusCode::BAD_REQUEST,
+                axum::Json(json!({
+                    "error": {
+                        "code": 400,
+                        "message": "mock upstream: no scripted reply left",
+                        "status": "INVALID_ARGUMENT"
+                    }
+                })),
+            )
+                .into_response(),
+        }
+    }
+
+    async fn start_mock_upstream(script: Vec<MockReply>) -> (String, Arc<MockState>) {
+        let state = Arc::new(MockState {
+            script: Mutex::new(script.into()),
+            calls: Mutex::new(Vec::new()),
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new()
+            .fallback(mock_v1internal)
+            .with_state(state.clone());
+        tokio::spawn(async move {
+            let _ = axum::serve(listener, app).await;
+        });
+        (format!("http://{}/v1internal", addr), state)
+    }
+
+    // ==================================================================================
+    // Harness：临时账号目录 + 临时端口上的 AxumServer
+    // ==================================================================================
+
+    struct Harness {
+        base_url: String,
+        mock: Arc<MockState>,
+        server: AxumServer,
+        data_dir: PathBuf,
+        client: reqwest::Client,
+    }
+
+    impl Harness {
+        async fn start(account_count: usize, script: Vec<MockReply>) -> Self {
+            // 监控日志、Token 统计、批处理等落盘数据在测试构建中写入进程级临时目录
+            // (见 modules::account::get_data_dir)，不会落到真实的 ~/.antigravity_tools
+            assert!(crate::modules::account::get_data_dir()
+                .unwrap()
+                .starts_with(std::env::temp_dir()));
+            let data_dir = std::env::temp_dir()
+                .join(format!("antigravity-conformance-{}", uuid::Uuid::new_v4()));
+            let accounts_dir = data_dir.join("accounts");
+            std::fs::create_dir_all(&accounts_dir).unwrap();
+
+            let now = chrono::Utc::now().timestamp();
+            for idx in 0..account_count {
+                let account_id = format!("conformance-{}", idx);
+                let account_json = json!({
+                    "id": account_id,
+                    "email": format!("{}@test.local", account_id),
+                    "token": {
+                        "access_token": format!("mock-token-{}", idx),
+                        "refresh_token": format!("mock-refresh-{}", idx),
+                        "expires_in": 3600,
+                        "expiry_timestamp": now + 3600,
+                        "project_id": "mock-project"
+                    },
+                    "disabled": false,
+                    "proxy_disabled": false,
+                    "created_at": now,
+                    "last_used": now
+                });
+                std::fs::write(
+                    accounts_dir.join(format!("{}.json", account_id)),
+                    serde_json::to_string_pretty(&account_json).unwrap(),
+                )
+                .unwrap();
+            }
+
+            let token_manager = Arc::new(TokenManager::new(data_dir.clone()));
+            assert_eq!(token_manager.load_accounts().await.unwrap(), account_count);
+
+            let (upstream_url, mock) = start_mock_upstream(script).await;
+
+            let proxy_config = ProxyConfig {
+                auth_mode: ProxyAuthMode::Off,
+                ..ProxyConfig::default()
+            };
+            let (server, _handle) = AxumServer::start(
+                "127.0.0.1".to_string(),
+                0,
+                token_manager,
+                std::collections::HashMap::new(),
+                proxy_config.request_timeout,
+                UpstreamProxyConfig::default(),
+                None,
+                ProxySecurityConfig::from_proxy_config(&proxy_config),
+                ZaiConfig::default(),
+                Arc::new(crate::proxy::monitor::ProxyMonitor::new(100, None)),
+                ExperimentalConfig::default(),
+                DebugLoggingConfig::default(),
+                crate::modules::integration::SystemManager::Headless,
+                Arc::new(crate::commands::cloudflared::CloudflaredState::new()),
+                ProxyPoolConfig::default(),
+            )
+            .await
+            .expect("AxumServer failed to start");
+            server
+                .update_upstream_base_urls(Some(vec![upstream_url]))
+                .await;
+
+            Self {
+                base_url: format!("http://{}", server.local_addr),
+                mock,
+                server,
+                data_dir,
+                client: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(60))
+                    .build()
+                    .unwrap(),
+            }
+        }
+
+        /// 发送请求并完整读取响应体；流被中途切断时返回已收到的部分
+        async fn post(&self, path: &str, headers: &[(&str, &str)], body: Value) -> (u16, String) {
+            let mut req = self
+                .client
+                .post(format!("{}{}", self.base_url, path))
+                .json(&body);
+            for (k, v) in headers {
+                req = req.header(*k, *v);
+            }
+            let resp = req.send().await.expect("request to proxy failed");
+            let status = resp.status().as_u16();
+
+            let mut collected = Vec::new();
+            let mut stream = resp.bytes_stream();
+            while let Some(chunk) = stream.next().await {
+                match chunk {
+                    Ok(bytes) => collected.extend_from_slice(&bytes),
+                    Err(_) => break,
+                }
+            }
+            (status, String::from_utf8_lossy(&collected).into_owned())
+        }
+
+        fn calls(&self) -> Vec<RecordedCall> {
+            self.mock.calls.lock().unwrap().clone()
+        }
+
+        fn account_json(&self, account_id: &str) -> Value {
+            let path = self
+                .data_dir
+                .join("accounts")
+                .join(format!("{}.json", account_id));
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        }
+    }
+
+    impl Drop for Harness {
+        fn drop(&mut self) {
+            self.server.stop();
+            let _ = std::fs::remove_dir_all(&self.data_dir);
+        }
+    }
+
+    // ==================================================================================
+    // 响应归一化与 golden 比对
+    // ==================================================================================
+
+    fn redact(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map.iter_mut() {
+                    if VOLATILE_KEYS.contains(&key.as_str()) && !v.is_null() {
+                        *v = json!("<redacted>");
+                    } else {
+                        redact(v);
+                    }
+                }
+            }
+            Value::Array(items) => items.iter_mut().for_each(redact),
+            _ => {}
+        }
+    }
+
+    /// JSON 响应格式化输出；SSE 逐行处理 data 负载，心跳注释 (`: ping`) 与时序相关故丢弃；
+    /// 其他内容原样保留
+    fn normalize_body(body: &str) -> String {
+        if let Ok(mut value) = serde_json::from_str::<Value>(body) {
+            redact(&mut value);
+            return serde_json::to_string_pretty(&value).unwrap() + "\n";
+        }
+
+        let mut out = String::new();
+        let mut after_comment = false;
+        for line in body.lines() {
+            if line.starts_with(':') {
+                after_comment = true;
+                continue;
+            }
+            if std::mem::take(&mut after_comment) && line.trim().is_empty() {
+                continue;
+            }
+            match line.strip_prefix("data:").map(str::trim) {
+                Some(data) => match serde_json::from_str::<Value>(data) {
+                    Ok(mut value) => {
+                        redact(&mut value);
+                        out.push_str(&format!("data: {}", value));
+                    }
+                    Err(_) => out.push_str(&format!("data: {}", data)),
+                },
+                None => out.push_str(line.trim_end()),
+            }
+            out.push('\n');
+        }
+        out
+    }
+
+    fn assert_golden(name: &str, status: u16, body: &str) {
+        let actual = format!("status: {}\n\n{}", status, normalize_body(body));
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join(GOLDEN_DIR)
+            .join(format!("{}.golden", name));
+
+        let update = std::env::var("ABV_UPDATE_GOLDEN")
+            .map(|v| v == "1")
+            .unwrap_or(false);
+        if update {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, &actual).unwrap();
+            return;
+        }
+        assert!(
+            path.exists(),
+            "missing golden {} (rerun with ABV_UPDATE_GOLDEN=1 to create it)",
+            path.display()
+        );
+
+        let expected = std::fs::read_to_string(&path)
+            .unwrap()
+            .replace("\r\n", "\n");
+        assert_eq!(
+            expected,
+            actual,
+            "golden mismatch for {} (rerun with ABV_UPDATE_GOLDEN=1 to accept)",
+            path.display()
+        );
+    }
+
+    fn sse_payloads(body: &str) -> Vec<Value> {
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .filter_map(|data| serde_json::from_str(data.trim()).ok())
+            .collect()
+    }
+
+    fn openai_stream_text(body: &str) -> String {
+        sse_payloads(body)
+            .iter()
+            .filter_map(|v| {
+                v["choices"][0]["delta"]["content"]
+                    .as_str()
+                    .map(String::from)
+            })
+            .collect()
+    }
+
+    fn claude_stream_text(body: &str) -> String {
+        sse_payloads(body)
+            .iter()
+            .filter(|v| v["type"] == "content_block_delta")
+            .filter_map(|v| v["delta"]["text"].as_str().map(String::from))
+            .collect()
+    }
+
+    fn gemini_text(chunks: &[Value]) -> String {
+        chunks
+            .iter()
+            .filter_map(|c| c["candidates"][0]["content"]["parts"].as_array())
+            .flatten()
+            .filter_map(|p| p["text"].as_str().map(String::from))
+            .collect()
+    }
+
+    const CLAUDE_HEADERS: &[(&str, &str)] = &[("anthropic-version", "2023-06-01")];
+
+    fn openai_chat(stream: bool) -> Value {
+        json!({
+            "model": "gemini-2.5-flash",
+            "stream": stream,
+            "messages": [{ "role": "user", "content": "Say hello" }]
+        })
+    }
+
+    fn claude_messages(stream: bool) -> Value {
+        json!({
+            "model": "claude-sonnet-4-5",
+            "max_tokens": 256,
+            "stream": stream,
+            "messages": [{ "role": "user", "content": "Say hello" }]
+        })
+    }
+
+    fn weather_schema() -> Value {
+        json!({
+            "type": "object",
+            "properties": { "city": { "type": "string" } },
+            "required": ["city"]
+        })
+    }
+
+    // ==================================================================================
+    // OpenAI 协议
+    // ==================================================================================
+
+    #[tokio::test]
+    async fn test_openai_chat_non_stream() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post("/v1/chat/completions", &[], openai_chat(false))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(resp["choices"][0]["message"]["content"], "Hello, world!");
+        assert_eq!(resp["choices"][0]["finish_reason"], "stop");
+
+        let calls = h.calls();
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].authorization, "Bearer mock-token-0");
+        assert_eq!(calls[0].body["project"], "mock-project");
+        assert_golden("openai_chat_non_stream", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_stream() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h.post("/v1/chat/completions", &[], openai_chat(true)).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(openai_stream_text(&body), "Hello, world!");
+        assert!(body.contains("data: [DONE]"));
+
+        let calls = h.calls();
+        assert_eq!(calls[0].method, "streamGenerateContent");
+        assert_eq!(calls[0].query.as_deref(), Some("alt=sse"));
+        assert_golden("openai_chat_stream", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_openai_chat_stream_tool_call() {
+        let h = Harness::start(
+            1,
+            vec![MockReply::function_call(
+                "get_weather",
+                json!({ "city": "Paris" }),
+            )],
+        )
+        .await;
+        let mut req = openai_chat(true);
+        req["tools"] = json!([{
+            "type": "function",
+            "function": { "name": "get_weather", "parameters": weather_schema() }
+        }]);
+        let (status, body) = h.post("/v1/chat/completions", &[], req).await;
+
+        assert_eq!(status, 200, "{}", body);
+        let payloads = sse_payloads(&body);
+        assert!(payloads.iter().any(|v| {
+            v["choices"][0]["delta"]["tool_calls"][0]["function"]["name"] == "get_weather"
+        }));
+        assert!(payloads
+            .iter()
+            .any(|v| v["choices"][0]["finish_reason"] == "tool_calls"));
+        assert_golden("openai_chat_stream_tool_call", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_openai_rejects_unsupported_sampling_params() {
+        let h = Harness::start(1, vec![MockReply::text(&["unused"])]).await;
+        let mut req = openai_chat(false);
+        req["logit_bias"] = json!({ "50256": -100 });
+        let (status, body) = h.post("/v1/chat/completions", &[], req).await;
+
+        assert_eq!(status, 400, "{}", body);
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(resp["error"]["type"], "invalid_request_error");
+        assert_eq!(resp["error"]["code"], "unsupported_parameter");
+        assert!(h.calls().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_openai_logprobs_forwarded_to_generation_config() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello"])]).await;
+        let mut req = openai_chat(false);
+        req["logprobs"] = json!(true);
+        req["top_logprobs"] = json!(3);
+        req["seed"] = json!(7);
+        let (status, body) = h.post("/v1/chat/completions", &[], req).await;
+
+        assert_eq!(status, 200, "{}", body);
+        let gen_config = &h.calls()[0].body["request"]["generationConfig"];
+        assert_eq!(gen_config["responseLogprobs"], true);
+        assert_eq!(gen_config["logprobs"], 3);
+        assert_eq!(gen_config["seed"], 7);
+    }
+
+    #[tokio::test]
+    async fn test_openai_rotates_account_after_429_retry_delay() {
+        let h = Harness::start(
+            2,
+            vec![
+                MockReply::rate_limited("0.2s"),
+                MockReply::text(&["Recovered"]),
+            ],
+        )
+        .await;
+        let (status, body) = h
+            .post("/v1/chat/completions", &[], openai_chat(false))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let calls = h.calls();
+        assert_eq!(calls.len(), 2);
+        assert_ne!(calls[0].authorization, calls[1].authorization);
+        assert_golden("openai_429_rotation", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_openai_validation_required_blocks_account() {
+        let h = Harness::start(
+            2,
+            vec![
+                MockReply::validation_required(),
+                MockReply::text(&["Hello"]),
+            ],
+        )
+        .await;
+        let (status, body) = h
+            .post("/v1/chat/completions", &[], openai_chat(false))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let calls = h.calls();
+        assert_eq!(calls.len(), 2);
+        assert_ne!(calls[0].authorization, calls[1].authorization);
+
+        // 首个账号应被临时封禁并落盘
+        let blocked_idx = calls[0]
+            .authorization
+            .trim_start_matches("Bearer mock-token-");
+        let account = h.account_json(&format!("conformance-{}", blocked_idx));
+        assert_eq!(account["validation_blocked"], json!(true));
+        assert_golden("openai_validation_required", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_openai_stream_midstream_disconnect() {
+        let h = Harness::start(
+            1,
+            vec![MockReply::Disconnect(vec![json!({ "text": "Hel" })])],
+        )
+        .await;
+        let (status, body) = h.post("/v1/chat/completions", &[], openai_chat(true)).await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert!(openai_stream_text(&body).starts_with("Hel"), "{}", body);
+        assert_golden("openai_stream_disconnect", status, &body);
+    }
+
+    // ==================================================================================
+    // Anthropic 协议
+    // ==================================================================================
+
+    #[tokio::test]
+    async fn test_claude_messages_non_stream() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post("/v1/messages", CLAUDE_HEADERS, claude_messages(false))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        let text: String = resp["content"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|b| b["text"].as_str())
+            .collect();
+        assert_eq!(text, "Hello, world!");
+        assert_eq!(resp["stop_reason"], "end_turn");
+        assert_golden("claude_messages_non_stream", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_claude_messages_stream() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post("/v1/messages", CLAUDE_HEADERS, claude_messages(true))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(claude_stream_text(&body), "Hello, world!");
+        assert!(body.contains("event: message_start"));
+        assert!(body.contains("event: message_stop"));
+        assert_golden("claude_messages_stream", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_claude_messages_stream_tool_use() {
+        let h = Harness::start(
+            1,
+            vec![MockReply::function_call(
+                "get_weather",
+                json!({ "city": "Paris" }),
+            )],
+        )
+        .await;
+        let mut req = claude_messages(true);
+        req["tools"] = json!([{ "name": "get_weather", "input_schema": weather_schema() }]);
+        let (status, body) = h.post("/v1/messages", CLAUDE_HEADERS, req).await;
+
+        assert_eq!(status, 200, "{}", body);
+        let payloads = sse_payloads(&body);
+        assert!(payloads.iter().any(|v| {
+            v["type"] == "content_block_start"
+                && v["content_block"]["type"] == "tool_use"
+                && v["content_block"]["name"] == "get_weather"
+        }));
+        assert!(payloads
+            .iter()
+            .any(|v| v["delta"]["stop_reason"] == "tool_use"));
+        assert_golden("claude_messages_stream_tool_use", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_claude_stream_midstream_disconnect() {
+        let h = Harness::start(
+            1,
+            vec![MockReply::Disconnect(vec![json!({ "text": "Hel" })])],
+        )
+        .await;
+        let (status, body) = h
+            .post("/v1/messages", CLAUDE_HEADERS, claude_messages(true))
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert!(claude_stream_text(&body).starts_with("Hel"), "{}", body);
+        // 中断以 Anthropic error 事件结束，不能伪装成正常的 end_turn
+        assert!(body.contains("event: error"), "{}", body);
+        assert!(!body.contains("end_turn"), "{}", body);
+        assert!(!body.contains("event: message_stop"), "{}", body);
+        assert_golden("claude_stream_disconnect", status, &body);
+    }
+
+    // ==================================================================================
+    // Gemini 协议
+    // ==================================================================================
+
+    fn gemini_request() -> Value {
+        json!({ "contents": [{ "role": "user", "parts": [{ "text": "Say hello" }] }] })
+    }
+
+    #[tokio::test]
+    async fn test_gemini_generate_content() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post(
+                "/v1beta/models/gemini-2.5-flash:generateContent",
+                &[],
+                gemini_request(),
+            )
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let resp: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(gemini_text(&[resp]), "Hello, world!");
+        assert_golden("gemini_generate_content", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_gemini_stream_sse() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post(
+                "/v1beta/models/gemini-2.5-flash:streamGenerateContent?alt=sse",
+                &[],
+                gemini_request(),
+            )
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        assert_eq!(gemini_text(&sse_payloads(&body)), "Hello, world!");
+        assert_golden("gemini_stream_sse", status, &body);
+    }
+
+    #[tokio::test]
+    async fn test_gemini_stream_json_array() {
+        let h = Harness::start(1, vec![MockReply::text(&["Hello", ", world!"])]).await;
+        let (status, body) = h
+            .post(
+                "/v1beta/models/gemini-2.5-flash:streamGenerateContent",
+                &[],
+                gemini_request(),
+            )
+            .await;
+
+        assert_eq!(status, 200, "{}", body);
+        let chunks: Vec<Value> = serde_json::from_str(&body).unwrap();
+        assert_eq!(gemini_text(&chunks), "Hello, world!");
+        assert_golden("gemini_stream_json_array", status, &body);
+    }
+}
@@ -2,3 +2,6 @@ pub mod comprehensive;
 pub mod security_ip_tests;
 pub mod security_integration_tests;
 pub mod quota_protection;
+pub mod tool_schema_corpus;
+pub mod openapi_conformance;
+pub mod conformance;
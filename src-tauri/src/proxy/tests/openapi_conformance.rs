@@ -0,0 +1,156 @@
+// 管理 API OpenAPI 文档一致性测试
+// axum Router 无法枚举已注册路由，因此直接解析 server.rs 中 admin_routes 的源码，
+// 断言每个 (method, path, handler) 都出现在 admin_api_operations 中，反之亦然。
+// 新增管理接口时若忘记补充文档，此测试会失败。
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy::openapi::to_openapi_path;
+    use crate::proxy::server::{admin_api_operations, admin_openapi_document};
+    use regex::Regex;
+    use serde_json::Value;
+    use std::collections::BTreeSet;
+
+    const SERVER_SOURCE: &str = include_str!("../server.rs");
+
+    /// 从 admin_routes 构建代码中提取 (method, path, handler)
+    fn registered_admin_routes() -> BTreeSet<(String, String, String)> {
+        let start = SERVER_SOURCE
+            .find("let admin_routes = Router::new()")
+            .expect("admin_routes block not found in server.rs");
+        let end = start
+            + SERVER_SOURCE[start..]
+                .find("admin_auth_middleware,")
+                .expect("admin_routes block end not found");
+        let block = &SERVER_SOURCE[start..end];
+
+        let route_re =
+            Regex::new(r#"\.route\(\s*"([^"]+)"\s*,\s*((?:[^()]|\((?:[^()]|\([^()]*\))*\))*)\)"#)
+                .unwrap();
+        let method_re = Regex::new(r"(get|post|put|delete|patch)\((\w+)\)").unwrap();
+
+        let mut routes = BTreeSet::new();
+        for route in route_re.captures_iter(block) {
+            for method in method_re.captures_iter(&route[2]) {
+                routes.insert((
+                    method[1].to_string(),
+                    route[1].to_string(),
+                    method[2].to_string(),
+                ));
+            }
+        }
+        routes
+    }
+
+    fn documented_operations() -> BTreeSet<(String, String, String)> {
+        admin_api_operations()
+            .iter()
+            .map(|op| {
+                (
+                    op.method.to_string(),
+                    op.path.to_string(),
+                    op.operation_id.to_string(),
+                )
+            })
+            .collect()
+    }
+
+    fn collect_refs(value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(r)) = map.get("$ref") {
+                    out.push(r.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, out));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, out)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_every_admin_route_is_documented() {
+        let registered = registered_admin_routes();
+        assert!(
+            registered.len() > 100,
+            "route extraction looks broken: {}",
+            registered.len()
+        );
+        let documented = documented_operations();
+
+        let undocumented: Vec<_> = registered.difference(&documented).collect();
+        assert!(
+            undocumented.is_empty(),
+            "admin routes missing from OpenAPI document: {:?}",
+            undocumented
+        );
+
+        let stale: Vec<_> = documented.difference(&registered).collect();
+        assert!(
+            stale.is_empty(),
+            "OpenAPI operations without a registered route: {:?}",
+            stale
+        );
+    }
+
+    #[test]
+    fn test_operations_are_unique() {
+        let ops = admin_api_operations();
+        let unique: BTreeSet<_> = ops.iter().map(|op| (op.method, op.path)).collect();
+        assert_eq!(
+            unique.len(),
+            ops.len(),
+            "duplicate method+path in admin_api_operations"
+        );
+    }
+
+    #[test]
+    fn test_document_paths_and_refs() {
+        let doc = admin_openapi_document();
+        assert_eq!(doc["openapi"], "3.1.0");
+
+        let paths = doc["paths"].as_object().unwrap();
+        for op in admin_api_operations() {
+            let (path, params) = to_openapi_path(op.path);
+            let item = &paths[&path][op.method];
+            assert_eq!(
+                item["operationId"], op.operation_id,
+                "{} {}",
+                op.method, path
+            );
+            for name in params {
+                let declared = item["parameters"]
+                    .as_array()
+                    .map(|ps| {
+                        ps.iter()
+                            .any(|p| p["in"] == "path" && p["name"] == name.as_str())
+                    })
+                    .unwrap_or(false);
+                assert!(
+                    declared,
+                    "path parameter {} not declared for {} {}",
+                    name, op.method, path
+                );
+            }
+        }
+        assert!(paths.keys().all(|p| !p.contains(':')));
+
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        let mut refs = Vec::new();
+        collect_refs(doc, &mut refs);
+        assert!(!refs.is_empty());
+        for r in refs {
+            let name = r
+                .strip_prefix("#/components/schemas/")
+                .unwrap_or_else(|| panic!("unexpected $ref: {}", r));
+            assert!(schemas.contains_key(name), "unresolved $ref: {}", r);
+        }
+
+        // 公开接口显式覆盖全局鉴权
+        assert_eq!(
+            doc["paths"]["/health"]["get"]["security"],
+            serde_json::json!([])
+        );
+        assert!(doc["paths"]["/accounts/{accountId}"]["delete"]["responses"]["204"].is_object());
+    }
+}
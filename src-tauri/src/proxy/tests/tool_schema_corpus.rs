@@ -0,0 +1,263 @@
+// 工具 Schema 语料测试
+// 将常见 MCP 服务器的真实工具定义逐一送入 clean_json_schema_for_tool，
+// 并用严格的 Gemini FunctionDeclaration Schema 校验器验证输出。
+// 新接入的 MCP 服务器只需在 fixtures/tool_schemas 中追加语料即可覆盖。
+
+#[cfg(test)]
+mod tests {
+    use crate::proxy::common::json_schema::clean_json_schema_for_tool;
+    use serde_json::Value;
+
+    const CORPUS: &[(&str, &str)] = &[
+        ("filesystem", include_str!("fixtures/tool_schemas/filesystem.json")),
+        ("playwright", include_str!("fixtures/tool_schemas/playwright.json")),
+        ("github", include_str!("fixtures/tool_schemas/github.json")),
+        ("context7", include_str!("fixtures/tool_schemas/context7.json")),
+        ("misc", include_str!("fixtures/tool_schemas/misc.json")),
+    ];
+
+    /// Gemini Schema 允许的字段
+    const GEMINI_ALLOWED_FIELDS: &[&str] = &[
+        "type",
+        "description",
+        "properties",
+        "required",
+        "items",
+        "enum",
+        "title",
+    ];
+
+    const GEMINI_TYPES: &[&str] = &["string", "number", "integer", "boolean", "array", "object"];
+
+    /// 严格模拟 Gemini 对 FunctionDeclaration.parameters 的校验
+    fn validate_gemini_schema(schema: &Value, path: &str) -> Result<(), String> {
+        let map = schema
+            .as_object()
+            .ok_or_else(|| format!("{}: schema must be an object", path))?;
+
+        for key in map.keys() {
+            if !GEMINI_ALLOWED_FIELDS.contains(&key.as_str()) {
+                return Err(format!("{}: unknown field '{}'", path, key));
+            }
+        }
+
+        let ty = map
+            .get("type")
+            .and_then(|t| t.as_str())
+            .ok_or_else(|| format!("{}.type: must be specified as a string", path))?;
+        if !GEMINI_TYPES.contains(&ty) {
+            return Err(format!("{}.type: invalid type '{}'", path, ty));
+        }
+
+        if let Some(desc) = map.get("description") {
+            if !desc.is_string() {
+                return Err(format!("{}.description: must be a string", path));
+            }
+        }
+
+        if let Some(values) = map.get("enum") {
+            if ty != "string" {
+                return Err(format!("{}.enum: only allowed for STRING type, got '{}'", path, ty));
+            }
+            let arr = values
+                .as_array()
+                .ok_or_else(|| format!("{}.enum: must be an array", path))?;
+            if arr.iter().any(|v| !v.is_string()) {
+                return Err(format!("{}.enum: values must be strings", path));
+            }
+        }
+
+        match ty {
+            "array" => {
+                let items = map
+                    .get("items")
+                    .ok_or_else(|| format!("{}.items: missing field", path))?;
+                validate_gemini_schema(items, &format!("{}.items", path))?;
+            }
+            _ if map.contains_key("items") => {
+                return Err(format!("{}.items: only allowed for ARRAY type", path));
+            }
+            _ => {}
+        }
+
+        match ty {
+            "object" => {
+                let props = map
+                    .get("properties")
+                    .and_then(|p| p.as_object())
+                    .ok_or_else(|| format!("{}.properties: must be an object", path))?;
+                for (name, prop) in props {
+                    validate_gemini_schema(prop, &format!("{}.properties[{}]", path, name))?;
+                }
+                if let Some(required) = map.get("required") {
+                    let arr = required
+                        .as_array()
+                        .ok_or_else(|| format!("{}.required: must be an array", path))?;
+                    for r in arr {
+                        let name = r
+                            .as_str()
+                            .ok_or_else(|| format!("{}.required: entries must be strings", path))?;
+                        if !props.contains_key(name) {
+                            return Err(format!(
+                                "{}.required: property '{}' is not defined",
+                                path, name
+                            ));
+                        }
+                    }
+                }
+            }
+            _ if map.contains_key("properties") || map.contains_key("required") => {
+                return Err(format!("{}: properties/required only allowed for OBJECT type", path));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn load_corpus() -> Vec<(String, Value)> {
+        let mut tools = Vec::new();
+        for (server, raw) in CORPUS {
+            let entries: Vec<Value> = serde_json::from_str(raw)
+                .unwrap_or_else(|e| panic!("fixture {} is not valid JSON: {}", server, e));
+            for entry in entries {
+                let name = entry["name"].as_str().expect("fixture tool without name").to_string();
+                tools.push((name, entry["inputSchema"].clone()));
+            }
+        }
+        tools
+    }
+
+    fn cleaned(tool_name: &str) -> Value {
+        let (_, mut schema) = load_corpus()
+            .into_iter()
+            .find(|(name, _)| name == tool_name)
+            .unwrap_or_else(|| panic!("tool {} not in corpus", tool_name));
+        clean_json_schema_for_tool(&mut schema, tool_name);
+        schema
+    }
+
+    #[test]
+    fn test_corpus_accepted_by_strict_validator() {
+        let tools = load_corpus();
+        assert!(tools.len() >= 20, "corpus unexpectedly small");
+
+        let failures: Vec<String> = tools
+            .into_iter()
+            .filter_map(|(name, mut schema)| {
+                clean_json_schema_for_tool(&mut schema, &name);
+                validate_gemini_schema(&schema, "parameters")
+                    .err()
+                    .map(|e| format!("{}: {}", name, e))
+            })
+            .collect();
+
+        assert!(failures.is_empty(), "rejected schemas:\n{}", failures.join("\n"));
+    }
+
+    #[test]
+    fn test_validator_rejects_raw_schemas() {
+        // 确保校验器足够严格：原始语料必须至少有一部分会被拒绝
+        let rejected = load_corpus()
+            .iter()
+            .filter(|(_, schema)| validate_gemini_schema(schema, "parameters").is_err())
+            .count();
+        assert!(rejected > 0);
+    }
+
+    #[test]
+    fn test_github_missing_items_repaired() {
+        let schema = cleaned("mcp__github__create_issue");
+        assert_eq!(schema["properties"]["labels"]["items"]["type"], "string");
+        assert_eq!(schema["properties"]["assignees"]["items"]["type"], "string");
+        assert_eq!(schema["properties"]["milestone"]["type"], "number");
+    }
+
+    #[test]
+    fn test_github_nullable_fields_not_required() {
+        let schema = cleaned("mcp__github__create_pull_request_review");
+        let item = &schema["properties"]["comments"]["items"];
+        let required: Vec<&str> = item["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert!(required.contains(&"path"));
+        assert!(!required.contains(&"line"));
+    }
+
+    #[test]
+    fn test_adapter_hints_survive_cleaning() {
+        let fs = cleaned("mcp__filesystem__move_file");
+        assert!(fs["properties"]["source"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("allowed directories"));
+
+        let pw = cleaned("mcp__playwright__browser_click");
+        assert!(pw["properties"]["ref"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("browser_snapshot"));
+
+        let c7 = cleaned("mcp__context7__get-library-docs");
+        let tokens_desc = c7["properties"]["tokens"]["description"].as_str().unwrap();
+        assert!(tokens_desc.contains("exclMin: 0"));
+        assert!(c7["properties"]["context7CompatibleLibraryID"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("resolve-library-id"));
+    }
+
+    #[test]
+    fn test_misc_edge_cases() {
+        let schema = cleaned("mcp__db__query");
+        let props = &schema["properties"];
+
+        // 整数 enum 保留 integer 类型，取值移入描述
+        assert_eq!(props["limit"]["type"], "integer");
+        assert!(props["limit"].get("enum").is_none());
+        assert!(props["limit"]["description"]
+            .as_str()
+            .unwrap()
+            .contains("[Allowed values: 10, 100, 1000]"));
+        // 空 items / 元组 items
+        assert_eq!(props["params"]["items"]["type"], "string");
+        assert_eq!(props["row"]["items"]["type"], "string");
+        // const 转为单值 enum
+        assert_eq!(props["mode"]["enum"][0], "readonly");
+        // 仅有 description 的节点补全 type
+        assert_eq!(props["options"]["type"], "string");
+    }
+
+    #[test]
+    fn test_non_string_enum_and_const_keep_type() {
+        let schema = cleaned("mcp__db__configure");
+        let props = &schema["properties"];
+
+        let hint = |name: &str| props[name]["description"].as_str().unwrap_or_default().to_string();
+
+        // 数值 enum: 显式 integer / 推断 number
+        assert_eq!(props["retries"]["type"], "integer");
+        assert!(props["retries"].get("enum").is_none());
+        assert!(hint("retries").contains("[Allowed values: 1, 2, 3]"));
+        assert_eq!(props["ratio"]["type"], "number");
+        assert!(hint("ratio").contains("0.5, 1.5"));
+
+        // 布尔 const / enum
+        assert_eq!(props["strict"]["type"], "boolean");
+        assert!(props["strict"].get("enum").is_none());
+        assert!(hint("strict").contains("[Allowed values: true]"));
+        assert_eq!(props["verbose"]["type"], "boolean");
+
+        // 带 const 的对象节点保留结构
+        assert_eq!(props["pool"]["type"], "object");
+        assert!(props["pool"].get("enum").is_none());
+        assert_eq!(props["pool"]["properties"]["size"]["type"], "integer");
+
+        // 含 null 的字符串 enum 仍转为字符串
+        assert_eq!(props["level"]["type"], "string");
+        assert_eq!(props["level"]["enum"], serde_json::json!(["debug", "info", "null"]));
+    }
+}
@@ -7,6 +7,8 @@ use std::sync::Arc;
 use tokio_util::sync::CancellationToken;
 
 use crate::proxy::rate_limit::RateLimitTracker;
+use crate::proxy::session_snapshot::{self, SessionSnapshot};
+use crate::proxy::signature_cache::SignatureCache;
 use crate::proxy::sticky_config::StickySessionConfig;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -48,6 +50,8 @@ pub struct TokenManager {
     circuit_breaker_config: Arc<tokio::sync::RwLock<crate::models::CircuitBreakerConfig>>, // [NEW] 熔断配置缓存
     /// 支持优雅关闭时主动 abort 后台任务
     auto_cleanup_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
+    /// 会话快照定时写入任务
+    session_snapshot_handle: Arc<tokio::sync::Mutex<Option<tokio::task::JoinHandle<()>>>>,
     cancel_token: CancellationToken,
 }
 
@@ -68,6 +72,7 @@ impl TokenManager {
                 crate::models::CircuitBreakerConfig::default(),
             )),
             auto_cleanup_handle: Arc::new(tokio::sync::Mutex::new(None)),
+            session_snapshot_handle: Arc::new(tokio::sync::Mutex::new(None)),
             cancel_token: CancellationToken::new(),
         }
     }
@@ -109,6 +114,108 @@ impl TokenManager {
         tracing::info!("Rate limit auto-cleanup task started (interval: 15s)");
     }
 
+    /// 启动会话快照定时写入任务（签名缓存 + 粘性会话绑定）
+    /// 写入间隔与开关每轮从调度配置读取，支持热更新
+    pub async fn start_session_snapshot_task(&self) {
+        let cancel = self.cancel_token.child_token();
+        let sticky_config = self.sticky_config.clone();
+        let session_accounts = self.session_accounts.clone();
+        let data_dir = self.data_dir.clone();
+
+        let handle = tokio::spawn(async move {
+            loop {
+                let interval = sticky_config.read().await.snapshot_interval_seconds.max(10);
+                tokio::select! {
+                    _ = cancel.cancelled() => {
+                        tracing::info!("Session snapshot task received cancel signal");
+                        break;
+                    }
+                    _ = tokio::time::sleep(std::time::Duration::from_secs(interval)) => {
+                        if !sticky_config.read().await.persist_sessions {
+                            continue;
+                        }
+                        let snapshot = Self::build_session_snapshot(&session_accounts);
+                        if let Err(e) = Self::write_session_snapshot(data_dir.clone(), snapshot).await {
+                            tracing::warn!("Failed to write session snapshot: {}", e);
+                        }
+                    }
+                }
+            }
+        });
+
+        let mut guard = self.session_snapshot_handle.lock().await;
+        if let Some(old) = guard.take() {
+            old.abort();
+        }
+        *guard = Some(handle);
+    }
+
+    fn build_session_snapshot(session_accounts: &DashMap<String, String>) -> SessionSnapshot {
+        SessionSnapshot {
+            signatures: SignatureCache::global().export_snapshot(),
+            session_bindings: session_accounts
+                .iter()
+                .map(|e| (e.key().clone(), e.value().clone()))
+                .collect(),
+        }
+    }
+
+    async fn write_session_snapshot(data_dir: PathBuf, snapshot: SessionSnapshot) -> Result<(), String> {
+        tokio::task::spawn_blocking(move || session_snapshot::save_snapshot(&data_dir, &snapshot))
+            .await
+            .map_err(|e| format!("Snapshot task panicked: {}", e))?
+    }
+
+    /// 立即写入会话快照（停止服务/退出时调用）
+    pub async fn save_session_snapshot(&self) {
+        if !self.sticky_config.read().await.persist_sessions {
+            return;
+        }
+        let snapshot = Self::build_session_snapshot(&self.session_accounts);
+        let bindings = snapshot.session_bindings.len();
+        match Self::write_session_snapshot(self.data_dir.clone(), snapshot).await {
+            Ok(()) => tracing::info!("Session snapshot saved ({} session binding(s))", bindings),
+            Err(e) => tracing::warn!("Failed to write session snapshot: {}", e),
+        }
+    }
+
+    /// 从磁盘恢复会话快照，仅恢复仍在账号池中的会话绑定
+    /// 须在 load_accounts 之后调用
+    pub async fn restore_session_snapshot(&self) {
+        if !self.sticky_config.read().await.persist_sessions {
+            return;
+        }
+        let data_dir = self.data_dir.clone();
+        let snapshot = match tokio::task::spawn_blocking(move || session_snapshot::load_snapshot(&data_dir)).await {
+            Ok(Ok(snapshot)) => snapshot,
+            Ok(Err(e)) => {
+                tracing::warn!("Failed to load session snapshot: {}", e);
+                return;
+            }
+            Err(e) => {
+                tracing::warn!("Session snapshot task panicked: {}", e);
+                return;
+            }
+        };
+
+        let signatures = SignatureCache::global().import_snapshot(snapshot.signatures);
+        let mut bindings = 0;
+        for (session_id, account_id) in snapshot.session_bindings {
+            if self.tokens.contains_key(&account_id) {
+                self.session_accounts.entry(session_id).or_insert(account_id);
+                bindings += 1;
+            }
+        }
+
+        if signatures > 0 || bindings > 0 {
+            tracing::info!(
+                "Session snapshot restored: {} signature(s), {} session binding(s)",
+                signatures,
+                bindings
+            );
+        }
+    }
+
     /// 从主应用账号目录加载所有账号
     pub async fn load_accounts(&self) -> Result<usize, String> {
         let accounts_dir = self.data_dir.join("accounts");
@@ -288,10 +395,8 @@ impl TokenManager {
 
     /// 加载单个账号
     async fn load_single_account(&self, path: &PathBuf) -> Result<Option<ProxyToken>, String> {
-        let content = std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?;
-
-        let mut account: serde_json::Value =
-            serde_json::from_str(&content).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        // [NEW] 经由保险库读取 (自动解密凭据字段)
+        let mut account = crate::modules::vault::read_account_value(path)?;
 
         // [修复 #1344] 先检查账号是否被手动禁用(非配额保护原因)
         let is_proxy_disabled = account
@@ -351,9 +456,7 @@ impl TokenManager {
                 account["validation_blocked_until"] = serde_json::json!(0);
                 account["validation_blocked_reason"] = serde_json::Value::Null;
 
-                let updated_json =
-                    serde_json::to_string_pretty(&account).map_err(|e| e.to_string())?;
-                std::fs::write(path, updated_json).map_err(|e| e.to_string())?;
+                crate::modules::vault::write_account_value(path, &account)?;
                 tracing::info!(
                     "Validation block expired and cleared for account: {}",
                     account
@@ -762,8 +865,7 @@ impl TokenManager {
             );
 
             // 3. 写入磁盘
-            std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap())
-                .map_err(|e| format!("写入文件失败: {}", e))?;
+            crate::modules::vault::write_account_value(account_path, account_json)?;
 
             return Ok(true);
         }
@@ -810,7 +912,7 @@ impl TokenManager {
 
         account_json["protected_models"] = serde_json::Value::Array(protected_list);
 
-        let _ = std::fs::write(account_path, serde_json::to_string_pretty(account_json).unwrap());
+        let _ = crate::modules::vault::write_account_value(account_path, account_json);
 
         false // 返回 false 表示现在已可以尝试加载该账号（模型级过滤会在 get_token 时发生）
     }
@@ -837,11 +939,7 @@ impl TokenManager {
                     account_id,
                     model_name
                 );
-                std::fs::write(
-                    account_path,
-                    serde_json::to_string_pretty(account_json).unwrap(),
-                )
-                .map_err(|e| format!("写入文件失败: {}", e))?;
+                crate::modules::vault::write_account_value(account_path, account_json)?;
                 return Ok(true);
             }
         }
@@ -919,6 +1017,9 @@ impl TokenManager {
     pub async fn graceful_shutdown(&self, timeout: std::time::Duration) {
         tracing::info!("Initiating graceful shutdown of background tasks...");
 
+        // 退出前写入会话快照，便于重启后恢复
+        self.save_session_snapshot().await;
+
         // 发送取消信号给所有后台任务
         self.cancel_token.cancel();
 
@@ -933,6 +1034,7 @@ impl TokenManager {
     /// abort() 仅设置取消标志，必须 await 确认清理完成
     pub async fn abort_background_tasks(&self) {
         Self::abort_task(&self.auto_cleanup_handle, "Auto-cleanup task").await;
+        Self::abort_task(&self.session_snapshot_handle, "Session snapshot task").await;
     }
 
     /// 中止单个后台任务并记录结果
@@ -1645,23 +1747,24 @@ impl TokenManager {
                 .join(format!("{}.json", account_id))
         };
 
-        let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(&path).map_err(|e| format!("读取文件失败: {}", e))?,
-        )
-        .map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        let mut content = crate::modules::vault::read_account_value(&path)?;
 
         let now = chrono::Utc::now().timestamp();
         content["disabled"] = serde_json::Value::Bool(true);
         content["disabled_at"] = serde_json::Value::Number(now.into());
         content["disabled_reason"] = serde_json::Value::String(truncate_reason(reason, 800));
 
-        std::fs::write(&path, serde_json::to_string_pretty(&content).unwrap())
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        crate::modules::vault::write_account_value(&path, &content)?;
 
         // 【修复 Issue #3】从内存中移除禁用的账号，防止被60s锁定逻辑继续使用
         self.tokens.remove(account_id);
 
         tracing::warn!("Account disabled: {} ({:?})", account_id, path);
+        crate::modules::event_bus::publish(
+            crate::modules::event_bus::EventTopic::Account,
+            "disabled",
+            &serde_json::json!({ "account_id": account_id, "reason": reason }),
+        );
         Ok(())
     }
 
@@ -1672,9 +1775,7 @@ impl TokenManager {
 
         let path = &entry.account_path;
 
-        let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
-        ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        let mut content = crate::modules::vault::read_account_value(path)?;
 
         // [PHASE 2] Only write project_id for accounts that have a token field
         if content.get("token").is_some() {
@@ -1690,8 +1791,7 @@ impl TokenManager {
             return Ok(());
         }
 
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        crate::modules::vault::write_account_value(path, &content)?;
 
         tracing::debug!("已保存 project_id 到账号 {}", account_id);
         Ok(())
@@ -1704,9 +1804,7 @@ impl TokenManager {
 
         let path = &entry.account_path;
 
-        let mut content: serde_json::Value = serde_json::from_str(
-            &std::fs::read_to_string(path).map_err(|e| format!("读取文件失败: {}", e))?
-        ).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        let mut content = crate::modules::vault::read_account_value(path)?;
 
         let now = chrono::Utc::now().timestamp();
 
@@ -1728,8 +1826,7 @@ impl TokenManager {
             return Ok(());
         }
 
-        std::fs::write(path, serde_json::to_string_pretty(&content).unwrap())
-            .map_err(|e| format!("写入文件失败: {}", e))?;
+        crate::modules::vault::write_account_value(path, &content)?;
 
         tracing::debug!("已保存刷新后的 token 到账号 {}", account_id);
         Ok(())
@@ -1739,6 +1836,33 @@ impl TokenManager {
         self.tokens.len()
     }
 
+    /// 获取固定账号的 Token (用于 cachedContents 等需要账号亲和的场景)
+    /// 账号不存在、被限流或对目标模型开启配额保护时返回错误，调用方应回退到常规调度
+    pub async fn get_token_for_account(
+        &self,
+        account_id: &str,
+        target_model: &str,
+    ) -> Result<(String, String, String, String, u64), String> {
+        let (email, protected) = match self.tokens.get(account_id) {
+            Some(token) => (token.email.clone(), token.protected_models.clone()),
+            None => return Err(format!("账号不存在: {}", account_id)),
+        };
+
+        let normalized_target = crate::proxy::common::model_mapping::normalize_to_standard_id(target_model)
+            .unwrap_or_else(|| target_model.to_string());
+        if self.is_rate_limited(account_id, Some(&normalized_target)).await {
+            return Err(format!("账号 {} 已限流", email));
+        }
+        let quota_protection_enabled = crate::modules::config::load_app_config()
+            .map(|cfg| cfg.quota_protection.enabled)
+            .unwrap_or(false);
+        if quota_protection_enabled && protected.contains(&normalized_target) {
+            return Err(format!("账号 {} 对模型 {} 开启了配额保护", email, normalized_target));
+        }
+
+        self.get_token_by_email(&email).await
+    }
+
     /// 通过 email 获取指定账号的 Token（用于预热等需要指定账号的场景）
     /// 此方法会自动刷新过期的 token
     pub async fn get_token_by_email(
@@ -2431,11 +2555,7 @@ impl TokenManager {
              return Err(format!("Account file not found: {:?}", path));
         }
 
-        let content = std::fs::read_to_string(&path)
-             .map_err(|e| format!("Failed to read account file: {}", e))?;
-
-        let mut account: serde_json::Value = serde_json::from_str(&content)
-             .map_err(|e| format!("Failed to parse account JSON: {}", e))?;
+        let mut account = crate::modules::vault::read_account_value(&path)?;
 
         account["validation_blocked"] = serde_json::Value::Bool(true);
         account["validation_blocked_until"] = serde_json::Value::Number(serde_json::Number::from(block_until));
@@ -2444,11 +2564,7 @@ impl TokenManager {
         // Clear sticky session if blocked
         self.session_accounts.retain(|_, v| *v != account_id);
 
-        let json_str = serde_json::to_string_pretty(&account)
-             .map_err(|e| format!("Failed to serialize account JSON: {}", e))?;
-
-        std::fs::write(&path, json_str)
-             .map_err(|e| format!("Failed to write account file: {}", e))?;
+        crate::modules::vault::write_account_value(&path, &account)?;
 
         tracing::info!(
              "🚫 Account {} validation blocked until {} (reason: {})",
@@ -2456,6 +2572,15 @@ impl TokenManager {
              block_until,
              reason
         );
+        crate::modules::event_bus::publish(
+            crate::modules::event_bus::EventTopic::Account,
+            "validation_blocked",
+            &serde_json::json!({
+                "account_id": account_id,
+                "blocked_until": block_until,
+                "reason": reason,
+            }),
+        );
 
         Ok(())
     }
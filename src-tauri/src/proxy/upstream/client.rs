@@ -20,11 +20,15 @@ const V1_INTERNAL_BASE_URL_FALLBACKS: [&str; 3] = [
     V1_INTERNAL_BASE_URL_PROD,    // 优先级 3: Prod (仅作为兜底)
 ];
 
+/// 覆盖 v1internal 端点的环境变量 (逗号分隔，按顺序 fallback)，用于本地 mock 上游联调
+const V1_INTERNAL_BASE_URL_ENV: &str = "ABV_V1INTERNAL_BASE_URL";
+
 pub struct UpstreamClient {
     default_client: Client,
     proxy_pool: Option<Arc<crate::proxy::proxy_pool::ProxyPoolManager>>,
     client_cache: DashMap<String, Client>, // proxy_id -> Client
     user_agent_override: RwLock<Option<String>>,
+    base_url_override: RwLock<Option<Vec<String>>>, // 覆盖内置 v1internal 端点 (测试 / mock 上游)
 }
 
 impl UpstreamClient {
@@ -40,7 +44,22 @@ impl UpstreamClient {
             proxy_pool,
             client_cache: DashMap::new(),
             user_agent_override: RwLock::new(None),
+            base_url_override: RwLock::new(Self::base_urls_from_env()),
+        }
+    }
+
+    fn base_urls_from_env() -> Option<Vec<String>> {
+        let raw = std::env::var(V1_INTERNAL_BASE_URL_ENV).ok()?;
+        let urls: Vec<String> = raw
+            .split(',')
+            .map(|s| s.trim().trim_end_matches('/').to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+        if urls.is_empty() {
+            return None;
         }
+        tracing::info!("UpstreamClient v1internal endpoints overridden by {}: {:?}", V1_INTERNAL_BASE_URL_ENV, urls);
+        Some(urls)
     }
 
     /// Internal helper to build a client with optional upstream proxy config
@@ -93,6 +112,21 @@ impl UpstreamClient {
         ua_override.as_ref().cloned().unwrap_or_else(|| crate::constants::USER_AGENT.clone())
     }
 
+    /// Override v1internal endpoints (None restores the built-in fallback chain)
+    pub async fn set_base_url_override(&self, urls: Option<Vec<String>>) {
+        let mut lock = self.base_url_override.write().await;
+        *lock = urls.filter(|u| !u.is_empty());
+        tracing::debug!("UpstreamClient v1internal endpoints override updated: {:?}", lock);
+    }
+
+    /// Current v1internal endpoints in fallback order
+    pub async fn base_urls(&self) -> Vec<String> {
+        match self.base_url_override.read().await.as_ref() {
+            Some(urls) => urls.clone(),
+            None => V1_INTERNAL_BASE_URL_FALLBACKS.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
     /// Get client for a specific account (or default if no proxy bound)
     pub async fn get_client(&self, account_id: Option<&str>) -> Client {
         if let Some(pool) = &self.proxy_pool {
@@ -102,6 +136,7 @@ impl UpstreamClient {
                     Ok(Some(proxy_cfg)) => {
                          // Check cache
                          if let Some(client) = self.client_cache.get(&proxy_cfg.entry_id) {
+                             crate::proxy::proxy_pool::note_request_proxy(Some(&proxy_cfg.entry_id));
                              return client.clone();
                          }
                          // Build new client and cache it
@@ -109,6 +144,7 @@ impl UpstreamClient {
                              Ok(client) => {
                                  self.client_cache.insert(proxy_cfg.entry_id.clone(), client.clone());
                                  tracing::info!("Using ProxyPool proxy ID: {} for account: {}", proxy_cfg.entry_id, acc_id);
+                                 crate::proxy::proxy_pool::note_request_proxy(Some(&proxy_cfg.entry_id));
                                  return client;
                              }
                              Err(e) => {
@@ -126,6 +162,7 @@ impl UpstreamClient {
             }
         }
         // Fallback to default client
+        crate::proxy::proxy_pool::note_request_proxy(None);
         self.default_client.clone()
     }
 
@@ -207,11 +244,12 @@ impl UpstreamClient {
         }
 
         let mut last_err: Option<String> = None;
+        let base_urls = self.base_urls().await;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, method, query_string);
-            let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+            let has_next = idx + 1 < base_urls.len();
 
             let response = client
                 .post(&url)
@@ -229,7 +267,7 @@ impl UpstreamClient {
                                 "✓ Upstream fallback succeeded | Endpoint: {} | Status: {} | Next endpoints available: {}",
                                 base_url,
                                 status,
-                                V1_INTERNAL_BASE_URL_FALLBACKS.len() - idx - 1
+                                base_urls.len() - idx - 1
                             );
                         } else {
                             tracing::debug!("✓ Upstream request succeeded | Endpoint: {} | Status: {}", base_url, status);
@@ -318,9 +356,10 @@ impl UpstreamClient {
         );
 
         let mut last_err: Option<String> = None;
+        let base_urls = self.base_urls().await;
 
         // 遍历所有端点，失败时自动切换
-        for (idx, base_url) in V1_INTERNAL_BASE_URL_FALLBACKS.iter().enumerate() {
+        for (idx, base_url) in base_urls.iter().enumerate() {
             let url = Self::build_url(base_url, "fetchAvailableModels", None);
 
             let response = client
@@ -351,7 +390,7 @@ impl UpstreamClient {
                     }
 
                     // 如果有下一个端点且当前错误可重试，则切换
-                    let has_next = idx + 1 < V1_INTERNAL_BASE_URL_FALLBACKS.len();
+                    let has_next = idx + 1 < base_urls.len();
                     if has_next && Self::should_try_next_endpoint(status) {
                         tracing::warn!(
                             "fetchAvailableModels returned {} at {}, trying next endpoint",
@@ -371,7 +410,7 @@ impl UpstreamClient {
                     last_err = Some(msg);
 
                     // 如果是最后一个端点，退出循环
-                    if idx + 1 >= V1_INTERNAL_BASE_URL_FALLBACKS.len() {
+                    if idx + 1 >= base_urls.len() {
                         break;
                     }
                     continue;
@@ -404,4 +443,17 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_base_url_override() {
+        let client = UpstreamClient::new(None, None);
+        client
+            .set_base_url_override(Some(vec!["http://127.0.0.1:9/v1internal".to_string()]))
+            .await;
+        assert_eq!(client.base_urls().await, vec!["http://127.0.0.1:9/v1internal"]);
+
+        // 空列表视为取消覆盖，恢复内置 fallback 链
+        client.set_base_url_override(Some(Vec::new())).await;
+        assert_eq!(client.base_urls().await.len(), V1_INTERNAL_BASE_URL_FALLBACKS.len());
+    }
+
 }
@@ -28,6 +28,7 @@ interface ProxyRequestLog {
     output_tokens?: number;
     account_email?: string;
     protocol?: string;  // "openai" | "anthropic" | "gemini"
+    context_compression?: string;  // JSON report of applied compression layers
 }
 
 interface ProxyStats {
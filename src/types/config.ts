@@ -15,6 +15,7 @@ export interface ProxyConfig {
     request_timeout: number;
     enable_logging: boolean;
     debug_logging?: DebugLoggingConfig;
+    log_retention?: LogRetentionConfig;
     upstream_proxy: UpstreamProxyConfig;
     zai?: ZaiConfig;
     scheduling?: StickySessionConfig;
@@ -23,6 +24,35 @@ export interface ProxyConfig {
     saved_user_agent?: string;
     thinking_budget?: ThinkingBudgetConfig;
     proxy_pool?: ProxyPoolConfig;
+    client_adapters?: CustomClientAdapterConfig[];
+    tool_result_compression?: ToolResultCompressionConfig;
+    server_tools?: ServerToolsConfig;
+    message_batches?: MessageBatchesConfig;
+}
+
+// ============================================================================
+// 自定义客户端适配器 (无需重新编译即可声明客户端特性)
+// ============================================================================
+
+export interface HeaderMatchRule {
+    name: string;
+    /** 请求头值的正则，为空时只要求该请求头存在 */
+    value_pattern?: string;
+}
+
+export interface CustomClientAdapterConfig {
+    name: string;
+    enabled: boolean;
+    /** User-Agent 正则 (不区分大小写) */
+    user_agent_pattern?: string;
+    header_matches?: HeaderMatchRule[];
+    bypass_signature_matching?: boolean;
+    let_it_crash?: boolean;
+    signature_buffer_strategy?: 'default' | 'fifo' | 'lifo';
+    beta_headers?: string[];
+    sse_error_format?: 'anthropic' | 'openai' | 'gemini';
+    tool_name_style?: 'mcp_double_underscore' | 'mcp_single_underscore' | 'server_double_underscore' | 'use_mcp_tool';
+    protocols?: Array<'anthropic' | 'openai' | 'oa_compatible' | 'gemini'>;
 }
 
 // ============================================================================
@@ -38,6 +68,21 @@ export interface ThinkingBudgetConfig {
     mode: ThinkingBudgetMode;
     /** 自定义固定值（仅在 mode=custom 时生效），范围 1024-65536 */
     custom_value: number;
+    /** 按模型设置的预算上限（支持 * 通配符） */
+    model_caps?: Record<string, number>;
+}
+
+// ============================================================================
+// 工具结果压缩 (按工具名选择语义压缩策略)
+// ============================================================================
+
+/** 工具结果压缩策略 */
+export type ToolResultStrategy = 'auto' | 'json' | 'log' | 'diff' | 'table' | 'truncate';
+
+/** 工具结果压缩配置 */
+export interface ToolResultCompressionConfig {
+    /** key 为工具名，支持以 `*` 结尾的前缀匹配 (如 `mcp__github__*`) */
+    tool_strategies: Record<string, ToolResultStrategy>;
 }
 
 export interface DebugLoggingConfig {
@@ -45,11 +90,48 @@ export interface DebugLoggingConfig {
     output_dir?: string;
 }
 
+export type BodyCaptureMode = 'none' | 'metadata_only' | 'truncated' | 'full' | 'errors_only_full';
+
+/** 单个日志存储的保留上限 (未设置表示不限制) */
+export interface StoreRetention {
+    max_age_days?: number | null;
+    max_rows?: number | null;
+    max_size_mb?: number | null;
+}
+
+export interface LogRetentionConfig {
+    enabled: boolean;
+    request_logs: StoreRetention;
+    ip_access_logs: StoreRetention;
+    debug_logs: StoreRetention;
+    message_batches: StoreRetention;
+    body_capture: BodyCaptureMode;
+    /** truncated 模式下每个请求 / 响应体保留的大小 (KB) */
+    body_max_kb: number;
+    /** 定期 VACUUM 间隔 (小时)，0 表示仅在超出磁盘上限时执行 */
+    vacuum_interval_hours: number;
+    check_interval_minutes: number;
+}
+
+/** 服务端工具循环: web_search / web_fetch 与函数工具共存 */
+export interface ServerToolsConfig {
+    enabled: boolean;
+    web_fetch: boolean;
+    max_iterations: number;
+}
+
+export interface MessageBatchesConfig {
+    /** 所有批次共享的并发请求上限 */
+    max_concurrency: number;
+}
+
 export type SchedulingMode = 'CacheFirst' | 'Balance' | 'PerformanceFirst';
 
 export interface StickySessionConfig {
     mode: SchedulingMode;
     max_wait_seconds: number;
+    persist_sessions?: boolean;
+    snapshot_interval_seconds?: number;
 }
 
 export type ZaiDispatchMode = 'off' | 'exclusive' | 'pooled' | 'fallback';
@@ -94,6 +176,7 @@ export interface PinnedQuotaModelsConfig {
 
 export interface ExperimentalConfig {
     enable_usage_scaling: boolean;
+    enable_context_compression?: boolean;
     context_compression_threshold_l1?: number;
     context_compression_threshold_l2?: number;
     context_compression_threshold_l3?: number;
@@ -55,6 +55,11 @@ const COMMAND_MAPPING: Record<string, { url: string; method: 'GET' | 'POST' | 'D
   // Logs & Monitoring
   'get_proxy_logs_filtered': { url: '/api/logs', method: 'GET' },
   'get_proxy_logs_count_filtered': { url: '/api/logs/count', method: 'GET' },
+  'search_proxy_logs': { url: '/api/logs/search', method: 'POST' },
+  'aggregate_proxy_logs': { url: '/api/logs/aggregate', method: 'POST' },
+  'get_latency_report': { url: '/api/logs/latency', method: 'POST' },
+  'get_log_retention_report': { url: '/api/logs/retention', method: 'GET' },
+  'run_log_retention': { url: '/api/logs/retention/run', method: 'POST' },
   'clear_proxy_logs': { url: '/api/logs/clear', method: 'POST' },
   'get_proxy_log_detail': { url: '/api/logs/:logId', method: 'GET' },
 
@@ -83,6 +88,11 @@ const COMMAND_MAPPING: Record<string, { url: string; method: 'GET' | 'POST' | 'D
   'get_token_stats_account_trend_hourly': { url: '/api/stats/token/account-trend/hourly', method: 'GET' },
   'get_token_stats_account_trend_daily': { url: '/api/stats/token/account-trend/daily', method: 'GET' },
   'clear_token_stats': { url: '/api/stats/token/clear', method: 'POST' },
+  'get_token_cost_report': { url: '/api/stats/cost', method: 'GET' },
+  'recompute_token_costs': { url: '/api/stats/cost/recompute', method: 'POST' },
+  'get_model_prices': { url: '/api/stats/prices', method: 'GET' },
+  'save_model_price': { url: '/api/stats/prices', method: 'POST' },
+  'delete_model_price': { url: '/api/stats/prices/:priceId', method: 'DELETE' },
 
   // System
   'get_data_dir_path': { url: '/api/system/data-dir', method: 'GET' },
@@ -150,6 +160,13 @@ const COMMAND_MAPPING: Record<string, { url: string; method: 'GET' | 'POST' | 'D
   'delete_user_token': { url: '/api/user-tokens/:id', method: 'DELETE' },
   'update_user_token': { url: '/api/user-tokens/:id', method: 'PATCH' },
 
+  // Account Vault
+  'get_vault_status': { url: '/api/vault/status', method: 'GET' },
+  'enable_vault': { url: '/api/vault/enable', method: 'POST' },
+  'unlock_vault': { url: '/api/vault/unlock', method: 'POST' },
+  'rekey_vault': { url: '/api/vault/rekey', method: 'POST' },
+  'disable_vault': { url: '/api/vault/disable', method: 'POST' },
+
   // Proxy Pool (Web Mode Fix)
   'get_proxy_pool_config': { url: '/api/proxy/pool/config', method: 'GET' },
   'get_all_account_bindings': { url: '/api/proxy/pool/bindings', method: 'GET' },